@@ -1,25 +1,34 @@
 //! An agent to talk to the Internet Computer through the public endpoints.
 use crate::{
-    cbor::{parse_canister_query_response, parse_read_state_response, RequestStatus},
+    cbor::{
+        parse_canister_query_response, parse_read_state_certificate, request_status_from_tree,
+        subnet_node_public_keys_from_tree, unverified_certified_tree, RequestStatus,
+    },
     http_client::HttpClient,
 };
 use backoff::backoff::Backoff;
 use ed25519_dalek::{Keypair, Signer, KEYPAIR_LENGTH};
+use ic_certified_vars::verify_certified_tree;
 use ic_crypto_sha::Sha256;
-use ic_crypto_tree_hash::Path;
-use ic_interfaces::crypto::DOMAIN_IC_REQUEST;
+use ic_crypto_tree_hash::{lookup_path, Label, LabeledTree, Path};
+use ic_interfaces::crypto::{Signable, DOMAIN_IC_REQUEST};
 use ic_protobuf::types::v1 as pb;
 use ic_types::{
     consensus::catchup::CatchUpPackageParam,
+    crypto::threshold_sig::ThresholdSigPublicKey,
     messages::{
-        Blob, HttpReadContent, HttpRequestEnvelope, HttpStatusResponse, HttpSubmitContent,
-        MessageId, ReplicaHealthStatus,
+        Blob, HttpReadContent, HttpRequestEnvelope, HttpSignedQueryResponse, HttpStatusResponse,
+        HttpSubmitContent, MessageId, QueryResponseContent, ReplicaHealthStatus,
     },
-    CanisterId, PrincipalId,
+    time::current_time,
+    CanisterId, NodeId, PrincipalId, SubnetId, Time,
 };
 use prost::Message;
 use serde_cbor::value::Value as CBOR;
-use std::{error::Error, fmt, sync::Arc, time::Duration, time::Instant};
+use std::{
+    collections::BTreeMap, convert::TryFrom, error::Error, fmt, sync::Arc, time::Duration,
+    time::Instant,
+};
 use tokio::time::sleep_until;
 use url::Url;
 
@@ -37,6 +46,10 @@ const MIN_POLL_INTERVAL: Duration = Duration::from_millis(500);
 const MAX_POLL_INTERVAL: Duration = Duration::from_secs(10);
 const POLL_INTERVAL_MULTIPLIER: f64 = 1.2;
 
+/// Maximum age of the `/time` in a verified `read_state` certificate, i.e.
+/// how far the certified state may lag behind the local clock.
+pub(crate) const MAX_CERTIFICATE_AGE: Duration = Duration::from_secs(5 * 60);
+
 /// The HTTP path for query calls on the replica.
 // TODO is this how v1 api works can we just change the URL?
 pub fn query_path(cid: CanisterId) -> String {
@@ -234,6 +247,29 @@ pub struct Agent {
     /// The values that any 'sender' field should have when issuing
     /// calls with the user corresponding to this Agent.
     pub sender_field: Blob,
+
+    // The DER-encoded signing public keys of the nodes of the subnet this
+    // agent talks to. If set, query responses must be signed by one of them.
+    node_public_keys: Option<Arc<BTreeMap<NodeId, Vec<u8>>>>,
+
+    // How the certificates of `read_state` responses are verified.
+    certificate_verification: CertificateVerification,
+
+    max_certificate_age: Duration,
+}
+
+/// How an `Agent` verifies the certificates of `read_state` responses.
+#[derive(Clone, Debug)]
+enum CertificateVerification {
+    /// No root key was set, so certificates cannot be verified and reading the
+    /// certified state fails.
+    MissingRootKey,
+    /// Certificates must be signed by the root subnet with this public key, or
+    /// by a subnet that the root subnet delegated to.
+    RootKey(ThresholdSigPublicKey),
+    /// Certificates are not verified. Only for tests, see
+    /// `Agent::new_unverified_for_testing`.
+    SkippedForTesting,
 }
 
 impl fmt::Debug for Agent {
@@ -243,6 +279,9 @@ impl fmt::Debug for Agent {
             .field("ingress_timeout", &self.ingress_timeout)
             .field("query_timeout", &self.query_timeout)
             .field("sender", &self.sender_field)
+            .field("node_public_keys", &self.node_public_keys)
+            .field("certificate_verification", &self.certificate_verification)
+            .field("max_certificate_age", &self.max_certificate_age)
             .finish()
     }
 }
@@ -256,11 +295,26 @@ impl Agent {
     /// The `sender` identifies the sender on whose behalf the requests are
     /// sent. If the requests are authenticated, the corresponding `pub_key` and
     /// `sender_sig` field are set in the request envelope.
+    ///
+    /// Reading the certified state, including polling the status of update
+    /// calls in `execute_update`, fails until the root key is set with
+    /// `with_root_key`.
     pub fn new(url: Url, sender: Sender) -> Self {
         let http_client = Arc::new(HttpClient::new());
         Self::build_agent(url, http_client, sender)
     }
 
+    /// Creates an agent that accepts the certificates of `read_state`
+    /// responses without verifying them, so that any replica can forge the
+    /// certified state it reads, e.g. the replies to update calls.
+    ///
+    /// Only for tests against local replicas whose root key is not known.
+    pub fn new_unverified_for_testing(url: Url, sender: Sender) -> Self {
+        let mut agent = Self::new(url, sender);
+        agent.certificate_verification = CertificateVerification::SkippedForTesting;
+        agent
+    }
+
     /// Creates an agent.
     ///
     /// Same as above except gives the caller the option to retain a
@@ -269,9 +323,12 @@ impl Agent {
         Self::build_agent(url, Arc::new(http_client), sender)
     }
 
-    /// This is needed by rust_canister tests
+    /// This is needed by rust_canister tests. The new agent verifies
+    /// certificates in the same way as this one.
     pub fn new_for_test(&self, sender: Sender) -> Self {
-        Self::build_agent(self.url.clone(), self.http_client.clone(), sender)
+        let mut agent = Self::build_agent(self.url.clone(), self.http_client.clone(), sender);
+        agent.certificate_verification = self.certificate_verification.clone();
+        agent
     }
 
     /// Helper to create the agent
@@ -284,6 +341,9 @@ impl Agent {
             http_client,
             sender,
             sender_field,
+            node_public_keys: None,
+            certificate_verification: CertificateVerification::MissingRootKey,
+            max_certificate_age: MAX_CERTIFICATE_AGE,
         }
     }
 
@@ -299,6 +359,75 @@ impl Agent {
         self
     }
 
+    /// Sets the DER-encoded signing public keys of the nodes of the subnet
+    /// this agent talks to, as returned by `fetch_node_public_keys`.
+    ///
+    /// Once set, `execute_query` rejects responses that are not signed by one
+    /// of these nodes, or whose signatures do not verify.
+    pub fn with_node_public_keys(mut self, node_public_keys: BTreeMap<NodeId, Vec<u8>>) -> Self {
+        self.node_public_keys = Some(Arc::new(node_public_keys));
+        self
+    }
+
+    /// Sets the public key of the root subnet, e.g. the NNS public key.
+    ///
+    /// The certificates of all `read_state` responses, including the request
+    /// statuses polled by `execute_update`, are verified against this key, and
+    /// rejected if their `/time` is older than the maximum certificate age.
+    pub fn with_root_key(mut self, root_key: ThresholdSigPublicKey) -> Self {
+        self.certificate_verification = CertificateVerification::RootKey(root_key);
+        self
+    }
+
+    /// Sets how far the `/time` of a verified certificate may lag behind the
+    /// local clock.
+    pub fn with_max_certificate_age(mut self, max_certificate_age: Duration) -> Self {
+        self.max_certificate_age = max_certificate_age;
+        self
+    }
+
+    /// Fetches the signing public keys of the nodes of the given subnet from
+    /// the certified state tree, i.e. from `/subnet/<subnet_id>/node`.
+    ///
+    /// Any canister of the subnet can be used as `effective_canister_id`.
+    pub async fn fetch_node_public_keys(
+        &self,
+        subnet_id: SubnetId,
+        effective_canister_id: &CanisterId,
+    ) -> Result<BTreeMap<NodeId, Vec<u8>>, String> {
+        let path = Path::new(vec![
+            "subnet".into(),
+            subnet_id.get().into_vec().into(),
+            "node".into(),
+        ]);
+        let tree = self
+            .read_state(
+                effective_canister_id,
+                vec![path],
+                Instant::now() + self.query_timeout,
+            )
+            .await?;
+        subnet_node_public_keys_from_tree(subnet_id, &tree)
+    }
+
+    /// Reads `path` of the certified state tree and returns the subtree at
+    /// `path`, or `None` if the certificate does not contain it.
+    pub async fn read_certified_path(
+        &self,
+        effective_canister_id: &CanisterId,
+        path: Path,
+    ) -> Result<Option<LabeledTree<Vec<u8>>>, String> {
+        let tree = self
+            .read_state(
+                effective_canister_id,
+                vec![path.clone()],
+                Instant::now() + self.query_timeout,
+            )
+            .await?;
+        let labels: Vec<&[u8]> = path.iter().map(Label::as_bytes).collect();
+        Ok(lookup_path(&tree, &labels).cloned())
+    }
+
     /// Queries the cup endpoint given the provided CatchUpPackageParams.
     pub async fn query_cup_endpoint(
         &self,
@@ -340,7 +469,7 @@ impl Agent {
         method: &str,
         arg: Vec<u8>,
     ) -> Result<Option<Vec<u8>>, String> {
-        let envelope = self
+        let (envelope, request_id) = self
             .prepare_query(canister_id, method, arg)
             .map_err(|e| format!("Failed to prepare query: {}", e))?;
         let bytes = self
@@ -354,6 +483,14 @@ impl Agent {
             .await?;
         let cbor = bytes_to_cbor(bytes)?;
 
+        if let Some(node_public_keys) = &self.node_public_keys {
+            let signed_response = serde_cbor::value::from_value::<HttpSignedQueryResponse>(
+                cbor.clone(),
+            )
+            .map_err(|source| format!("decoding to HttpSignedQueryResponse failed: {}", source))?;
+            verify_query_response_signatures(&signed_response, &request_id, node_public_keys)?;
+        }
+
         let call_response = parse_canister_query_response(&cbor)?;
         if call_response.status == "replied" {
             Ok(call_response.reply)
@@ -421,33 +558,54 @@ impl Agent {
         ))
     }
 
-    /// Requests the status of a pending request once.
+    /// Reads the given paths of the certified state tree and returns the tree
+    /// of the certificate in the response, once it is verified.
     ///
-    /// This is intended to be used in a loop until a final state is reached.
-    ///
-    /// Returns the entire CBOR value from the response, without trying to
-    /// interpret it.
-    async fn request_status_once(
+    /// Fails if no root key is set, unless the agent was created with
+    /// `new_unverified_for_testing`.
+    async fn read_state(
         &self,
-        request_id: MessageId,
+        effective_canister_id: &CanisterId,
+        paths: Vec<Path>,
         deadline: Instant,
-        canister_id: &CanisterId,
-    ) -> Result<CBOR, String> {
-        let path = Path::new(vec!["request_status".into(), request_id.into()]);
-        let status_request_body = self
-            .prepare_read_state(&[path])
+    ) -> Result<LabeledTree<Vec<u8>>, String> {
+        let read_state_body = self
+            .prepare_read_state(&paths)
             .map_err(|e| format!("Failed to prepare read state: {:?}", e))?;
-
         let bytes = self
             .http_client
             .post_with_response(
                 &self.url,
-                &read_state_path(*canister_id),
-                status_request_body,
+                &read_state_path(*effective_canister_id),
+                read_state_body,
                 tokio::time::Instant::from_std(deadline),
             )
             .await?;
-        bytes_to_cbor(bytes)
+        let certificate = parse_read_state_certificate(bytes_to_cbor(bytes)?)?;
+        self.certified_tree(effective_canister_id, &certificate)
+    }
+
+    /// Returns the tree of the certificate of a `read_state` response to a
+    /// request for `effective_canister_id`, once it is verified.
+    fn certified_tree(
+        &self,
+        effective_canister_id: &CanisterId,
+        certificate: &[u8],
+    ) -> Result<LabeledTree<Vec<u8>>, String> {
+        match &self.certificate_verification {
+            CertificateVerification::RootKey(root_key) => verify_read_state_certificate(
+                certificate,
+                effective_canister_id,
+                root_key,
+                self.max_certificate_age,
+                current_time(),
+            ),
+            CertificateVerification::SkippedForTesting => unverified_certified_tree(certificate),
+            CertificateVerification::MissingRootKey => Err(
+                "Cannot verify the certificate of the read_state response: no root key is set"
+                    .to_string(),
+            ),
+        }
     }
 
     /// Requests the status of a pending canister update call request exactly
@@ -460,10 +618,9 @@ impl Agent {
         deadline: Instant,
         canister_id: &CanisterId,
     ) -> Result<RequestStatus, String> {
-        let cbor = self
-            .request_status_once(request_id.clone(), deadline, canister_id)
-            .await?;
-        parse_read_state_response(&request_id, cbor)
+        let path = Path::new(vec!["request_status".into(), request_id.clone().into()]);
+        let tree = self.read_state(canister_id, vec![path], deadline).await?;
+        request_status_from_tree(&request_id, &tree)
     }
 
     async fn get_status(&self) -> Result<HttpStatusResponse, String> {
@@ -568,6 +725,68 @@ pub fn sign_read(
     })
 }
 
+/// Verifies that the given query response is signed by at least one node, and
+/// that all signatures were created by nodes with the given DER-encoded
+/// signing public keys on the response to the query `request_id`.
+pub fn verify_query_response_signatures(
+    response: &HttpSignedQueryResponse,
+    request_id: &MessageId,
+    node_public_keys: &BTreeMap<NodeId, Vec<u8>>,
+) -> Result<(), String> {
+    if response.signatures.is_empty() {
+        return Err("The query response is not signed".to_string());
+    }
+    for node_signature in &response.signatures {
+        let node_id = NodeId::from(
+            PrincipalId::try_from(node_signature.identity.as_slice())
+                .map_err(|e| format!("Invalid node ID in the query response: {}", e))?,
+        );
+        let public_key = node_public_keys
+            .get(&node_id)
+            .ok_or_else(|| format!("Query response signed by unknown node {}", node_id))?;
+        let public_key = ed25519::api::public_key_from_der(public_key)
+            .map_err(|e| format!("Invalid public key of node {}: {}", node_id, e))?;
+        let signature = ed25519::types::SignatureBytes::try_from(&node_signature.signature.0)
+            .map_err(|e| format!("Invalid signature of node {}: {}", node_id, e))?;
+        let content = QueryResponseContent::new(
+            response.response.clone(),
+            request_id.clone(),
+            Time::from_nanos_since_unix_epoch(node_signature.timestamp),
+        );
+        ed25519::api::verify(&signature, &content.as_signed_bytes(), &public_key).map_err(|e| {
+            format!(
+                "Invalid signature of node {} on the query response: {}",
+                node_id, e
+            )
+        })?;
+    }
+    Ok(())
+}
+
+/// Verifies the certificate of a `read_state` response against the public key
+/// of the root subnet, including the delegation to the subnet of
+/// `effective_canister_id` if there is one, and returns its tree.
+///
+/// Certificates whose `/time` is more than `max_age` older than `now` are
+/// rejected, so that a stale certificate cannot be replayed.
+pub fn verify_read_state_certificate(
+    certificate: &[u8],
+    effective_canister_id: &CanisterId,
+    root_key: &ThresholdSigPublicKey,
+    max_age: Duration,
+    now: Time,
+) -> Result<LabeledTree<Vec<u8>>, String> {
+    let (tree, time) = verify_certified_tree(certificate, effective_canister_id, root_key)
+        .map_err(|e| format!("Invalid certificate: {}", e))?;
+    if time + max_age < now {
+        return Err(format!(
+            "The certificate is stale: its time is {}, but the current time is {}",
+            time, now
+        ));
+    }
+    Ok(tree)
+}
+
 fn bytes_to_cbor(bytes: Vec<u8>) -> Result<CBOR, String> {
     let cbor = serde_cbor::from_slice(&bytes).map_err(|e| {
         format!(
@@ -590,12 +809,19 @@ fn bytes_to_cbor(bytes: Vec<u8>) -> Result<CBOR, String> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ic_crypto::{combined_threshold_signature_and_public_key, threshold_sig_public_key_to_der};
+    use ic_crypto_tree_hash::{flatmap, HashTreeBuilder, HashTreeBuilderImpl, WitnessGenerator};
     use ic_test_utilities::crypto::temp_crypto_component_with_fake_registry;
-    use ic_test_utilities::types::ids::node_test_id;
+    use ic_test_utilities::types::ids::{node_test_id, subnet_test_id};
+    use ic_types::consensus::certification::CertificationContent;
+    use ic_types::crypto::CryptoHash;
     use ic_types::malicious_flags::MaliciousFlags;
-    use ic_types::messages::{HttpCanisterUpdate, HttpRequest, HttpUserQuery, UserQuery};
+    use ic_types::messages::{
+        Certificate, CertificateDelegation, HttpCanisterUpdate, HttpRequest, HttpUserQuery,
+        UserQuery,
+    };
     use ic_types::time::current_time;
-    use ic_types::{PrincipalId, RegistryVersion, UserId};
+    use ic_types::{CryptoHashOfPartialState, PrincipalId, Randomness, RegistryVersion, UserId};
     use ic_validator::get_authorized_canisters;
     use rand_chacha::ChaChaRng;
     use rand_core::SeedableRng;
@@ -851,4 +1077,316 @@ mod tests {
             &MaliciousFlags::default(),
         ));
     }
+
+    #[test]
+    fn verify_query_response_signatures_of_known_node() {
+        use ic_types::messages::{HttpQueryResponse, HttpQueryResponseReply, NodeSignature};
+
+        let keypair = {
+            let mut rng = ChaChaRng::seed_from_u64(789_u64);
+            ed25519_dalek::Keypair::generate(&mut rng)
+        };
+        let node_id = node_test_id(1);
+        let mut node_public_keys = BTreeMap::new();
+        node_public_keys.insert(
+            node_id,
+            ed25519_public_key_to_der(keypair.public.to_bytes().to_vec()),
+        );
+        let request_id = MessageId::from([1; 32]);
+        let timestamp = current_time();
+        let response = HttpQueryResponse::Replied {
+            reply: HttpQueryResponseReply {
+                arg: Blob(vec![1, 2, 3]),
+            },
+        };
+        let content = QueryResponseContent::new(response.clone(), request_id.clone(), timestamp);
+        let mut signed_response = HttpSignedQueryResponse {
+            response,
+            signatures: vec![NodeSignature {
+                timestamp: timestamp.as_nanos_since_unix_epoch(),
+                signature: Blob(keypair.sign(&content.as_signed_bytes()).to_bytes().to_vec()),
+                identity: Blob(node_id.get().into_vec()),
+            }],
+        };
+
+        // The signature survives a CBOR roundtrip.
+        let cbor: CBOR =
+            serde_cbor::from_slice(&serde_cbor::to_vec(&signed_response).unwrap()).unwrap();
+        let decoded: HttpSignedQueryResponse = serde_cbor::value::from_value(cbor).unwrap();
+        assert_eq!(decoded, signed_response);
+        assert_ok!(verify_query_response_signatures(
+            &decoded,
+            &request_id,
+            &node_public_keys
+        ));
+
+        // The signature is bound to the request ID.
+        assert!(verify_query_response_signatures(
+            &signed_response,
+            &MessageId::from([2; 32]),
+            &node_public_keys
+        )
+        .is_err());
+
+        // A node outside of the subnet cannot sign responses.
+        let mut other_node_public_keys = BTreeMap::new();
+        other_node_public_keys.insert(node_test_id(2), node_public_keys[&node_id].clone());
+        assert!(verify_query_response_signatures(
+            &signed_response,
+            &request_id,
+            &other_node_public_keys
+        )
+        .is_err());
+
+        // A tampered reply is detected.
+        signed_response.response = HttpQueryResponse::Replied {
+            reply: HttpQueryResponseReply {
+                arg: Blob(vec![4, 5, 6]),
+            },
+        };
+        assert!(
+            verify_query_response_signatures(&signed_response, &request_id, &node_public_keys)
+                .is_err()
+        );
+
+        // Unsigned responses are rejected.
+        signed_response.signatures.clear();
+        assert!(
+            verify_query_response_signatures(&signed_response, &request_id, &node_public_keys)
+                .is_err()
+        );
+    }
+
+    fn hash_full_tree(b: &mut HashTreeBuilderImpl, t: &LabeledTree<Vec<u8>>) {
+        match t {
+            LabeledTree::Leaf(bytes) => {
+                b.start_leaf();
+                b.write_leaf(&bytes[..]);
+                b.finish_leaf();
+            }
+            LabeledTree::SubTree(map) => {
+                b.start_subtree();
+                for (l, child) in map.iter() {
+                    b.new_edge(l.clone());
+                    hash_full_tree(b, child);
+                }
+                b.finish_subtree();
+            }
+        }
+    }
+
+    /// Returns a certificate of `tree` signed with the key derived from `seed`,
+    /// together with that key.
+    fn make_certificate(
+        tree: &LabeledTree<Vec<u8>>,
+        seed: u8,
+        delegation: Option<CertificateDelegation>,
+    ) -> (ThresholdSigPublicKey, Vec<u8>) {
+        let mut b = HashTreeBuilderImpl::new();
+        hash_full_tree(&mut b, tree);
+        let witness_gen = b.witness_generator().unwrap();
+        let root_hash =
+            CryptoHashOfPartialState::from(CryptoHash(witness_gen.hash_tree().digest().to_vec()));
+        let (signature, public_key) = combined_threshold_signature_and_public_key(
+            Randomness::from([seed; 32]),
+            &CertificationContent::new(root_hash),
+        );
+        let certificate = Certificate {
+            tree: witness_gen.mixed_hash_tree(tree).unwrap(),
+            signature: Blob(signature.get().0),
+            delegation,
+        };
+        (public_key, serde_cbor::to_vec(&certificate).unwrap())
+    }
+
+    fn state_tree(time: Time, request_id: &MessageId) -> LabeledTree<Vec<u8>> {
+        let mut encoded_time = vec![];
+        leb128::write::unsigned(&mut encoded_time, time.as_nanos_since_unix_epoch()).unwrap();
+        LabeledTree::SubTree(flatmap![
+            Label::from("request_status") => LabeledTree::SubTree(flatmap![
+                Label::from(request_id.as_bytes().to_vec()) => LabeledTree::SubTree(flatmap![
+                    Label::from("reply") => LabeledTree::Leaf(vec![1, 2, 3]),
+                    Label::from("status") => LabeledTree::Leaf(b"replied".to_vec()),
+                ])
+            ]),
+            Label::from("time") => LabeledTree::Leaf(encoded_time),
+        ])
+    }
+
+    /// Returns a delegation from the root subnet, whose key is derived from
+    /// seed 0, to the subnet whose key is derived from seed 1, for the
+    /// canister IDs in `canister_range`.
+    fn make_delegation(
+        subnet_id: SubnetId,
+        canister_range: (CanisterId, CanisterId),
+    ) -> (ThresholdSigPublicKey, CertificateDelegation) {
+        let (subnet_public_key, _) = make_certificate(&LabeledTree::SubTree(flatmap![]), 1, None);
+        let canister_ranges =
+            serde_cbor::to_vec(&vec![(canister_range.0.get(), canister_range.1.get())]).unwrap();
+        let tree = LabeledTree::SubTree(flatmap![
+            Label::from("subnet") => LabeledTree::SubTree(flatmap![
+                Label::from(subnet_id.get().into_vec()) => LabeledTree::SubTree(flatmap![
+                    Label::from("canister_ranges") => LabeledTree::Leaf(canister_ranges),
+                    Label::from("public_key") => LabeledTree::Leaf(
+                        threshold_sig_public_key_to_der(subnet_public_key).unwrap()
+                    ),
+                ])
+            ]),
+        ]);
+        let (root_public_key, certificate) = make_certificate(&tree, 0, None);
+        let delegation = CertificateDelegation {
+            subnet_id: Blob(subnet_id.get().into_vec()),
+            certificate: Blob(certificate),
+        };
+        (root_public_key, delegation)
+    }
+
+    #[test]
+    fn read_state_certificate_of_root_subnet_is_verified() {
+        let request_id = MessageId::from([1; 32]);
+        let canister_id = CanisterId::from_u64(1);
+        let now = current_time();
+        let (root_key, certificate) = make_certificate(&state_tree(now, &request_id), 0, None);
+
+        let tree = verify_read_state_certificate(
+            &certificate,
+            &canister_id,
+            &root_key,
+            MAX_CERTIFICATE_AGE,
+            now,
+        )
+        .unwrap();
+        let status = request_status_from_tree(&request_id, &tree).unwrap();
+        assert_eq!(status.status, "replied");
+        assert_eq!(status.reply, Some(vec![1, 2, 3]));
+
+        // A certificate signed with another key is rejected.
+        let (other_key, _) = make_certificate(&state_tree(now, &request_id), 2, None);
+        assert!(verify_read_state_certificate(
+            &certificate,
+            &canister_id,
+            &other_key,
+            MAX_CERTIFICATE_AGE,
+            now
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn stale_read_state_certificate_is_rejected() {
+        let request_id = MessageId::from([1; 32]);
+        let canister_id = CanisterId::from_u64(1);
+        let certified_time = current_time();
+        let (root_key, certificate) =
+            make_certificate(&state_tree(certified_time, &request_id), 0, None);
+
+        let verify_at = |now| {
+            verify_read_state_certificate(
+                &certificate,
+                &canister_id,
+                &root_key,
+                MAX_CERTIFICATE_AGE,
+                now,
+            )
+        };
+        assert!(verify_at(certified_time + MAX_CERTIFICATE_AGE).is_ok());
+        let err =
+            verify_at(certified_time + MAX_CERTIFICATE_AGE + Duration::from_secs(1)).unwrap_err();
+        assert!(err.contains("stale"), "{}", err);
+    }
+
+    #[test]
+    fn read_state_certificate_with_delegation_is_verified() {
+        let request_id = MessageId::from([1; 32]);
+        let subnet_id = subnet_test_id(1);
+        let now = current_time();
+        let (root_key, delegation) = make_delegation(
+            subnet_id,
+            (CanisterId::from_u64(10), CanisterId::from_u64(20)),
+        );
+        let (_, certificate) = make_certificate(&state_tree(now, &request_id), 1, Some(delegation));
+
+        let tree = verify_read_state_certificate(
+            &certificate,
+            &CanisterId::from_u64(15),
+            &root_key,
+            MAX_CERTIFICATE_AGE,
+            now,
+        )
+        .unwrap();
+        assert_eq!(
+            request_status_from_tree(&request_id, &tree).unwrap().status,
+            "replied"
+        );
+
+        // The delegation does not cover canisters of other subnets.
+        let err = verify_read_state_certificate(
+            &certificate,
+            &CanisterId::from_u64(21),
+            &root_key,
+            MAX_CERTIFICATE_AGE,
+            now,
+        )
+        .unwrap_err();
+        assert!(err.contains("not assigned"), "{}", err);
+    }
+
+    #[test]
+    fn read_state_certificate_with_forged_delegation_is_rejected() {
+        let request_id = MessageId::from([1; 32]);
+        let canister_id = CanisterId::from_u64(15);
+        let now = current_time();
+        let (root_key, mut delegation) =
+            make_delegation(subnet_test_id(1), (canister_id, canister_id));
+        // The delegation certificate is signed by the subnet instead of the
+        // root subnet.
+        let (_, delegation_signed_by_subnet) = make_certificate(
+            &LabeledTree::<Vec<u8>>::try_from(
+                serde_cbor::from_slice::<Certificate>(&delegation.certificate)
+                    .unwrap()
+                    .tree,
+            )
+            .unwrap(),
+            1,
+            None,
+        );
+        delegation.certificate = Blob(delegation_signed_by_subnet);
+        let (_, certificate) = make_certificate(&state_tree(now, &request_id), 1, Some(delegation));
+
+        assert!(verify_read_state_certificate(
+            &certificate,
+            &canister_id,
+            &root_key,
+            MAX_CERTIFICATE_AGE,
+            now
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn read_state_fails_without_root_key() {
+        let request_id = MessageId::from([1; 32]);
+        let canister_id = CanisterId::from_u64(1);
+        let (root_key, certificate) =
+            make_certificate(&state_tree(current_time(), &request_id), 0, None);
+        let url = Url::parse("http://localhost").unwrap();
+
+        let agent = Agent::new(url.clone(), Sender::Anonymous);
+        let err = agent
+            .certified_tree(&canister_id, &certificate)
+            .unwrap_err();
+        assert!(err.contains("no root key is set"), "{}", err);
+        // Agents derived for tests verify certificates in the same way.
+        let err = agent
+            .new_for_test(Sender::Anonymous)
+            .certified_tree(&canister_id, &certificate)
+            .unwrap_err();
+        assert!(err.contains("no root key is set"), "{}", err);
+
+        let agent = Agent::new(url.clone(), Sender::Anonymous).with_root_key(root_key);
+        assert_ok!(agent.certified_tree(&canister_id, &certificate));
+
+        let agent = Agent::new_unverified_for_testing(url, Sender::Anonymous);
+        assert_ok!(agent.certified_tree(&canister_id, &certificate));
+    }
 }
@@ -1,17 +1,18 @@
 use crate::{
-    agent::{sign_read, Agent},
+    agent::{sign_read, verify_read_state_certificate, Agent, MAX_CERTIFICATE_AGE},
     sign_submit,
 };
-use ic_crypto_tree_hash::{LabeledTree, Path};
+use ic_crypto_tree_hash::{lookup_path, LabeledTree, Path};
 use ic_types::Time;
 use ic_types::{
+    crypto::threshold_sig::ThresholdSigPublicKey,
     messages::{
         Blob, Certificate, HttpCanisterUpdate, HttpReadContent, HttpReadState,
         HttpReadStateResponse, HttpRequestEnvelope, HttpSubmitContent, HttpUserQuery, MessageId,
         SignedRequestBytes,
     },
-    time::current_time_and_expiry_time,
-    CanisterId,
+    time::{current_time, current_time_and_expiry_time},
+    CanisterId, NodeId, PrincipalId, SubnetId,
 };
 use serde::Deserialize;
 use serde_cbor::value::Value as CBOR;
@@ -52,22 +53,76 @@ struct CanisterCallResponse {
 
 /// Given a CBOR response from a `read_state` and a `request_id` extracts
 /// the `RequestStatus` if available.
+///
+/// The certificate of the response is verified against `root_key` first, see
+/// `verify_read_state_certificate`.
 pub fn parse_read_state_response(
     request_id: &MessageId,
+    effective_canister_id: &CanisterId,
+    root_key: &ThresholdSigPublicKey,
     message: CBOR,
 ) -> Result<RequestStatus, String> {
+    let tree = verified_certified_tree(effective_canister_id, root_key, message)?;
+    request_status_from_tree(request_id, &tree)
+}
+
+/// Given a CBOR response from a `read_state` of the path `/subnet/<subnet_id>/node`,
+/// extracts the DER-encoded signing public keys of the nodes of the subnet.
+///
+/// The certificate of the response is verified against `root_key` first, see
+/// `verify_read_state_certificate`.
+pub fn parse_subnet_node_public_keys(
+    subnet_id: SubnetId,
+    effective_canister_id: &CanisterId,
+    root_key: &ThresholdSigPublicKey,
+    message: CBOR,
+) -> Result<BTreeMap<NodeId, Vec<u8>>, String> {
+    let tree = verified_certified_tree(effective_canister_id, root_key, message)?;
+    subnet_node_public_keys_from_tree(subnet_id, &tree)
+}
+
+fn verified_certified_tree(
+    effective_canister_id: &CanisterId,
+    root_key: &ThresholdSigPublicKey,
+    message: CBOR,
+) -> Result<LabeledTree<Vec<u8>>, String> {
+    let certificate = parse_read_state_certificate(message)?;
+    verify_read_state_certificate(
+        &certificate,
+        effective_canister_id,
+        root_key,
+        MAX_CERTIFICATE_AGE,
+        current_time(),
+    )
+}
+
+/// Given a CBOR response from a `read_state`, extracts the CBOR-encoded
+/// certificate.
+pub(crate) fn parse_read_state_certificate(message: CBOR) -> Result<Blob, String> {
     let response = serde_cbor::value::from_value::<HttpReadStateResponse>(message)
         .map_err(|source| format!("decoding to HttpReadStateResponse failed: {}", source))?;
+    Ok(response.certificate)
+}
 
-    let certificate: Certificate = serde_cbor::from_slice(response.certificate.as_slice())
+/// Extracts the tree of the given certificate without verifying it.
+pub(crate) fn unverified_certified_tree(
+    certificate: &[u8],
+) -> Result<LabeledTree<Vec<u8>>, String> {
+    let certificate: Certificate = serde_cbor::from_slice(certificate)
         .map_err(|source| format!("decoding Certificate failed: {}", source))?;
 
-    // Parse the tree.
-    let tree = LabeledTree::try_from(certificate.tree)
-        .map_err(|e| format!("parsing tree in certificate failed: {:?}", e))?;
+    LabeledTree::try_from(certificate.tree)
+        .map_err(|e| format!("parsing tree in certificate failed: {:?}", e))
+}
 
+/// Extracts the status of the request `request_id` from the tree of a
+/// certificate.
+pub(crate) fn request_status_from_tree(
+    request_id: &MessageId,
+    tree: &LabeledTree<Vec<u8>>,
+) -> Result<RequestStatus, String> {
     let request_statuses =
-        RequestStatuses::deserialize(tree_deserializer::LabeledTreeDeserializer::new(&tree))
+        RequestStatuses::deserialize(tree_deserializer::LabeledTreeDeserializer::new(tree))
             .map_err(|err| format!("deserializing request statuses failed: {:?}", err))?;
 
     Ok(match request_statuses.request_status {
@@ -78,6 +133,39 @@ pub fn parse_read_state_response(
     })
 }
 
+/// Extracts the DER-encoded signing public keys of the nodes of the given
+/// subnet from the tree of a certificate.
+pub(crate) fn subnet_node_public_keys_from_tree(
+    subnet_id: SubnetId,
+    tree: &LabeledTree<Vec<u8>>,
+) -> Result<BTreeMap<NodeId, Vec<u8>>, String> {
+    let subnet_id = subnet_id.get();
+    let nodes = match lookup_path(tree, &[b"subnet", subnet_id.as_slice(), b"node"]) {
+        Some(LabeledTree::SubTree(nodes)) => nodes,
+        _ => {
+            return Err(format!(
+                "certificate does not contain the nodes of subnet {}",
+                subnet_id
+            ))
+        }
+    };
+
+    let mut node_public_keys = BTreeMap::new();
+    for (label, node) in nodes.iter() {
+        let node_id = NodeId::from(
+            PrincipalId::try_from(label.as_bytes())
+                .map_err(|e| format!("parsing node ID {:?} failed: {}", label, e))?,
+        );
+        match lookup_path(node, &[b"public_key"]) {
+            Some(LabeledTree::Leaf(public_key)) => {
+                node_public_keys.insert(node_id, public_key.clone());
+            }
+            _ => return Err(format!("public key of node {} not found", node_id)),
+        }
+    }
+    Ok(node_public_keys)
+}
+
 /// Given a CBOR response from a `query`, extract the response.
 pub(crate) fn parse_canister_query_response(message: &CBOR) -> Result<RequestStatus, String> {
     let content = match message {
@@ -188,7 +276,7 @@ impl Agent {
         canister_id: &CanisterId,
         method: &str,
         arguments: Vec<u8>,
-    ) -> Result<Vec<u8>, Box<dyn Error>> {
+    ) -> Result<(Vec<u8>, MessageId), Box<dyn Error>> {
         let content = HttpReadContent::Query {
             query: HttpUserQuery {
                 canister_id: to_blob(canister_id),
@@ -200,8 +288,9 @@ impl Agent {
             },
         };
 
+        let request_id = content.id();
         let request = sign_read(content, &self.sender)?;
-        Ok(SignedRequestBytes::try_from(request)?.into())
+        Ok((SignedRequestBytes::try_from(request)?.into(), request_id))
     }
 
     /// Prepares and serializes a CBOR read_state request, with the given paths
@@ -227,10 +316,22 @@ fn to_blob(canister_id: &CanisterId) -> Blob {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ic_crypto::combined_threshold_signature_and_public_key;
     use ic_crypto_tree_hash::MixedHashTree;
+    use ic_types::consensus::certification::CertificationContent;
+    use ic_types::crypto::CryptoHash;
     use ic_types::messages::HttpReadStateResponse;
+    use ic_types::{CryptoHashOfPartialState, Randomness};
     use serde::Serialize;
 
+    fn parse_unverified_read_state_response(
+        request_id: &MessageId,
+        message: CBOR,
+    ) -> Result<RequestStatus, String> {
+        let certificate = parse_read_state_certificate(message)?;
+        request_status_from_tree(request_id, &unverified_certified_tree(&certificate)?)
+    }
+
     fn to_self_describing_cbor<T: Serialize>(e: &T) -> serde_cbor::Result<Vec<u8>> {
         let mut serialized_bytes = Vec::new();
         let mut serializer = serde_cbor::Serializer::new(&mut serialized_bytes);
@@ -259,7 +360,7 @@ mod tests {
 
         let request_id: MessageId = MessageId::from([0; 32]);
         assert_eq!(
-            parse_read_state_response(&request_id, response),
+            parse_unverified_read_state_response(&request_id, response),
             Ok(RequestStatus::unknown())
         );
     }
@@ -314,7 +415,7 @@ mod tests {
         ]);
 
         assert_eq!(
-            parse_read_state_response(&request_id, response.clone()),
+            parse_unverified_read_state_response(&request_id, response.clone()),
             Ok(RequestStatus {
                 status: "replied".to_string(),
                 reply: Some(vec![68, 73, 68, 76, 0, 0]),
@@ -325,8 +426,34 @@ mod tests {
         // Request ID that doesn't exist.
         let request_id: MessageId = MessageId::from([0; 32]);
         assert_eq!(
-            parse_read_state_response(&request_id, response),
+            parse_unverified_read_state_response(&request_id, response),
             Ok(RequestStatus::unknown())
         );
     }
+
+    #[test]
+    fn test_parse_read_state_response_rejects_unsigned_certificate() {
+        let certificate = Certificate {
+            tree: MixedHashTree::Labeled("time".into(), Box::new(MixedHashTree::Leaf(vec![1]))),
+            signature: Blob(vec![]),
+            delegation: None,
+        };
+        let response = HttpReadStateResponse {
+            certificate: Blob(to_self_describing_cbor(&certificate).unwrap()),
+        };
+        let response: CBOR =
+            serde_cbor::from_slice(&to_self_describing_cbor(&response).unwrap()).unwrap();
+        let (_, root_key) = combined_threshold_signature_and_public_key(
+            Randomness::from([0; 32]),
+            &CertificationContent::new(CryptoHashOfPartialState::from(CryptoHash(vec![]))),
+        );
+
+        let result = parse_read_state_response(
+            &MessageId::from([0; 32]),
+            &CanisterId::from_u64(1),
+            &root_key,
+            response,
+        );
+        assert!(result.unwrap_err().contains("Invalid certificate"));
+    }
 }
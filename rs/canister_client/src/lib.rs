@@ -7,8 +7,8 @@ mod http_client;
 
 pub use agent::{
     ed25519_public_key_to_der, get_backoff_policy, query_path, read_state_path, sign_submit,
-    update_path, Agent, Sender,
+    update_path, verify_query_response_signatures, verify_read_state_certificate, Agent, Sender,
 };
-pub use cbor::parse_read_state_response;
+pub use cbor::{parse_read_state_response, parse_subnet_node_public_keys};
 pub use http_client::HttpClient;
 pub use hyper::StatusCode as HttpStatusCode;
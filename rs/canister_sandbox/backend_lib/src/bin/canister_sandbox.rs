@@ -0,0 +1,29 @@
+//! Entry point of canister sandbox processes, as spawned by the replica (see
+//! `spawn_socketed_process`). The only argument is the ID of the canister the
+//! process executes, for informational purposes.
+use ic_canister_sandbox_backend_lib::run_canister_sandbox;
+use std::os::unix::{io::FromRawFd, net::UnixStream};
+
+/// File descriptor on which the replica passes the control socket.
+const CONTROL_SOCKET_FD: i32 = 3;
+
+fn main() {
+    // Confinement must happen before any other thread is started.
+    confine();
+
+    // Safe because the replica hands over the control socket as file
+    // descriptor 3 and nothing else in this process uses it.
+    let socket = unsafe { UnixStream::from_raw_fd(CONTROL_SOCKET_FD) };
+    run_canister_sandbox(socket);
+}
+
+#[cfg(target_os = "linux")]
+fn confine() {
+    if let Err(err) = ic_canister_sandbox_backend_lib::confinement::confine_sandbox_process() {
+        eprintln!("Wasm Sandbox: Failed to confine sandbox process: {}", err);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn confine() {}
@@ -0,0 +1,358 @@
+//! Confinement of canister sandbox processes.
+//!
+//! A sandbox process only needs to execute Wasm and to talk to the replica
+//! over the control socket it inherits on startup. Before serving any request
+//! it therefore:
+//!
+//! - moves into fresh user, mount and network namespaces, so that it has no
+//!   view of the host's network and its mounts are detached from the host's;
+//! - drops all capabilities (including the ones it holds in its new user
+//!   namespace) and sets `no_new_privs`;
+//! - installs a seccomp-bpf filter that allows only the system calls needed
+//!   for Wasm execution and for the RPC channel. Any other system call (e.g.
+//!   `open` or `socket`) kills the whole process, which the replica observes
+//!   as the control socket being closed.
+use nix::{
+    mount::{mount, MsFlags},
+    sched::{unshare, CloneFlags},
+};
+use std::io;
+
+/// Confines the calling process as described in the module documentation.
+///
+/// Must be called while the process is still single-threaded: the kernel
+/// refuses to move multi-threaded processes into a new user namespace, and
+/// the seccomp filter is only inherited by threads created after it was
+/// installed.
+pub fn confine_sandbox_process() -> io::Result<()> {
+    enter_namespaces()?;
+    drop_capabilities()?;
+    install_syscall_filter(&syscall_filter())
+}
+
+/// Moves the calling process into fresh user, mount and network namespaces.
+fn enter_namespaces() -> io::Result<()> {
+    unshare(CloneFlags::CLONE_NEWUSER | CloneFlags::CLONE_NEWNS | CloneFlags::CLONE_NEWNET)
+        .map_err(nix_to_io_error)?;
+    // Make sure that no mount events propagate back to the host.
+    mount(
+        None::<&str>,
+        "/",
+        None::<&str>,
+        MsFlags::MS_REC | MsFlags::MS_PRIVATE,
+        None::<&str>,
+    )
+    .map_err(nix_to_io_error)
+}
+
+/// Drops all capabilities from the bounding, ambient, effective, permitted
+/// and inheritable sets and prevents the process from ever gaining new
+/// privileges (e.g. through `execve` of a setuid binary).
+fn drop_capabilities() -> io::Result<()> {
+    // The bounding set is dropped one capability at a time, until the kernel
+    // reports an unknown capability.
+    for cap in 0.. {
+        if unsafe { libc::prctl(libc::PR_CAPBSET_DROP, cap, 0, 0, 0) } != 0 {
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() == Some(libc::EINVAL) {
+                break;
+            }
+            return Err(err);
+        }
+    }
+    check(unsafe {
+        libc::prctl(
+            libc::PR_CAP_AMBIENT,
+            libc::PR_CAP_AMBIENT_CLEAR_ALL,
+            0,
+            0,
+            0,
+        )
+    })?;
+
+    let header = CapUserHeader {
+        version: LINUX_CAPABILITY_VERSION_3,
+        pid: 0,
+    };
+    let data = [CapUserData::default(); 2];
+    check(unsafe {
+        libc::syscall(
+            libc::SYS_capset,
+            &header as *const CapUserHeader,
+            data.as_ptr(),
+        )
+    } as libc::c_int)?;
+
+    set_no_new_privs()
+}
+
+fn set_no_new_privs() -> io::Result<()> {
+    check(unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) })
+}
+
+/// Installs the given seccomp-bpf `filter` on the calling thread. Requires
+/// `no_new_privs` to be set (or `CAP_SYS_ADMIN`).
+fn install_syscall_filter(filter: &[SockFilter]) -> io::Result<()> {
+    let prog = SockFprog {
+        len: filter.len() as libc::c_ushort,
+        filter: filter.as_ptr(),
+    };
+    check(unsafe {
+        libc::prctl(
+            libc::PR_SET_SECCOMP,
+            SECCOMP_MODE_FILTER,
+            &prog as *const SockFprog,
+            0,
+            0,
+        )
+    })
+}
+
+/// System calls that a sandbox process may make unconditionally.
+const ALLOWED_SYSCALLS: &[libc::c_long] = &[
+    // RPC channel and logging.
+    libc::SYS_read,
+    libc::SYS_readv,
+    libc::SYS_write,
+    libc::SYS_writev,
+    libc::SYS_recvmsg,
+    libc::SYS_sendmsg,
+    libc::SYS_close,
+    libc::SYS_fcntl,
+    // Memory management, including Wasm memories and page maps.
+    libc::SYS_brk,
+    libc::SYS_mmap,
+    libc::SYS_munmap,
+    libc::SYS_mremap,
+    libc::SYS_mprotect,
+    libc::SYS_madvise,
+    libc::SYS_membarrier,
+    libc::SYS_memfd_create,
+    libc::SYS_ftruncate,
+    // Threads and synchronization.
+    libc::SYS_futex,
+    libc::SYS_set_robust_list,
+    libc::SYS_rseq,
+    libc::SYS_sched_yield,
+    libc::SYS_sched_getaffinity,
+    libc::SYS_getpid,
+    libc::SYS_gettid,
+    libc::SYS_tgkill,
+    libc::SYS_exit,
+    libc::SYS_exit_group,
+    // Signal handling (memory tracking, Wasm traps, stack overflows).
+    libc::SYS_rt_sigaction,
+    libc::SYS_rt_sigprocmask,
+    libc::SYS_rt_sigreturn,
+    libc::SYS_sigaltstack,
+    // Time and randomness.
+    libc::SYS_clock_gettime,
+    libc::SYS_clock_nanosleep,
+    libc::SYS_nanosleep,
+    libc::SYS_getrandom,
+];
+
+/// Builds the seccomp-bpf program enforced in sandbox processes:
+///
+/// - `clone` is allowed for creating threads only (`CLONE_THREAD`), so that a
+///   sandbox process cannot fork;
+/// - `clone3` fails with `ENOSYS`, making the C library fall back to `clone`;
+/// - `prctl` is allowed for naming threads only;
+/// - `ALLOWED_SYSCALLS` are allowed;
+/// - everything else, as well as any system call made using a foreign
+///   architecture's calling convention, kills the process.
+fn syscall_filter() -> Vec<SockFilter> {
+    let mut filter = vec![
+        bpf_stmt(BPF_LD | BPF_W | BPF_ABS, SECCOMP_DATA_ARCH_OFFSET),
+        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, AUDIT_ARCH_CURRENT, 1, 0),
+        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_KILL_PROCESS),
+        bpf_stmt(BPF_LD | BPF_W | BPF_ABS, SECCOMP_DATA_NR_OFFSET),
+        // clone3
+        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, libc::SYS_clone3 as u32, 0, 1),
+        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ERRNO | libc::ENOSYS as u32),
+        // clone
+        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, libc::SYS_clone as u32, 0, 4),
+        bpf_stmt(BPF_LD | BPF_W | BPF_ABS, SECCOMP_DATA_ARG0_OFFSET),
+        bpf_jump(BPF_JMP | BPF_JSET | BPF_K, libc::CLONE_THREAD as u32, 0, 1),
+        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
+        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_KILL_PROCESS),
+        // prctl
+        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, libc::SYS_prctl as u32, 0, 4),
+        bpf_stmt(BPF_LD | BPF_W | BPF_ABS, SECCOMP_DATA_ARG0_OFFSET),
+        bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, libc::PR_SET_NAME as u32, 0, 1),
+        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW),
+        bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_KILL_PROCESS),
+    ];
+    for syscall in ALLOWED_SYSCALLS {
+        filter.push(bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, *syscall as u32, 0, 1));
+        filter.push(bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW));
+    }
+    filter.push(bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_KILL_PROCESS));
+    filter
+}
+
+fn check(result: libc::c_int) -> io::Result<()> {
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+fn nix_to_io_error(err: nix::Error) -> io::Error {
+    io::Error::from_raw_os_error(err as i32)
+}
+
+// Definitions from `linux/filter.h`, `linux/seccomp.h`, `linux/audit.h` and
+// `linux/capability.h`.
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct SockFilter {
+    code: u16,
+    jt: u8,
+    jf: u8,
+    k: u32,
+}
+
+#[repr(C)]
+struct SockFprog {
+    len: libc::c_ushort,
+    filter: *const SockFilter,
+}
+
+#[repr(C)]
+struct CapUserHeader {
+    version: u32,
+    pid: libc::c_int,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct CapUserData {
+    effective: u32,
+    permitted: u32,
+    inheritable: u32,
+}
+
+const LINUX_CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+
+const BPF_LD: u16 = 0x00;
+const BPF_JMP: u16 = 0x05;
+const BPF_RET: u16 = 0x06;
+const BPF_W: u16 = 0x00;
+const BPF_ABS: u16 = 0x20;
+const BPF_JEQ: u16 = 0x10;
+const BPF_JSET: u16 = 0x40;
+const BPF_K: u16 = 0x00;
+
+const SECCOMP_MODE_FILTER: libc::c_ulong = 2;
+const SECCOMP_RET_KILL_PROCESS: u32 = 0x8000_0000;
+const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+
+// Offsets into `struct seccomp_data`. Arguments are 64 bits wide; on the
+// (little-endian) architectures supported here the lower half comes first.
+const SECCOMP_DATA_NR_OFFSET: u32 = 0;
+const SECCOMP_DATA_ARCH_OFFSET: u32 = 4;
+const SECCOMP_DATA_ARG0_OFFSET: u32 = 16;
+
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH_CURRENT: u32 = 0xc000_003e;
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH_CURRENT: u32 = 0xc000_00b7;
+
+fn bpf_stmt(code: u16, k: u32) -> SockFilter {
+    SockFilter {
+        code,
+        jt: 0,
+        jf: 0,
+        k,
+    }
+}
+
+fn bpf_jump(code: u16, k: u32, jt: u8, jf: u8) -> SockFilter {
+    SockFilter { code, jt, jf, k }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nix::{
+        sys::{
+            signal::Signal,
+            wait::{waitpid, WaitStatus},
+        },
+        unistd::{fork, ForkResult},
+    };
+
+    /// Runs `f` in a forked child with the sandbox syscall filter installed
+    /// and returns how the child terminated. The filter is built before
+    /// forking, so that the child does not need to allocate.
+    fn run_filtered(f: fn()) -> WaitStatus {
+        let filter = syscall_filter();
+        match unsafe { fork() }.unwrap() {
+            ForkResult::Child => {
+                if set_no_new_privs().is_err() || install_syscall_filter(&filter).is_err() {
+                    unsafe { libc::_exit(2) };
+                }
+                f();
+                unsafe { libc::_exit(0) };
+            }
+            ForkResult::Parent { child } => waitpid(child, None).unwrap(),
+        }
+    }
+
+    #[test]
+    fn allowed_syscalls_succeed() {
+        let status = run_filtered(|| unsafe {
+            let fd = libc::syscall(libc::SYS_memfd_create, b"\0".as_ptr(), 0) as libc::c_int;
+            if fd < 0 || libc::write(fd, b"x".as_ptr() as *const _, 1) != 1 || libc::close(fd) != 0
+            {
+                libc::_exit(3);
+            }
+        });
+        assert_eq!(status, WaitStatus::Exited(status.pid().unwrap(), 0));
+    }
+
+    #[test]
+    fn open_kills_the_process() {
+        let status = run_filtered(|| unsafe {
+            libc::syscall(
+                libc::SYS_openat,
+                libc::AT_FDCWD,
+                b"/etc/hostname\0".as_ptr(),
+                libc::O_RDONLY,
+            );
+        });
+        assert!(
+            matches!(status, WaitStatus::Signaled(_, Signal::SIGSYS, _)),
+            "{:?}",
+            status
+        );
+    }
+
+    #[test]
+    fn socket_kills_the_process() {
+        let status = run_filtered(|| unsafe {
+            libc::socket(libc::AF_INET, libc::SOCK_STREAM, 0);
+        });
+        assert!(
+            matches!(status, WaitStatus::Signaled(_, Signal::SIGSYS, _)),
+            "{:?}",
+            status
+        );
+    }
+
+    #[test]
+    fn fork_kills_the_process() {
+        let status = run_filtered(|| unsafe {
+            libc::syscall(libc::SYS_clone, libc::SIGCHLD, 0, 0, 0, 0);
+        });
+        assert!(
+            matches!(status, WaitStatus::Signaled(_, Signal::SIGSYS, _)),
+            "{:?}",
+            status
+        );
+    }
+}
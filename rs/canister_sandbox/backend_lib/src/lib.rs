@@ -1,3 +1,5 @@
+#[cfg(target_os = "linux")]
+pub mod confinement;
 pub mod logging;
 pub mod sandbox_manager;
 pub mod sandbox_server;
@@ -203,6 +203,15 @@ impl<Message: 'static + Serialize + Send + EnumerateInnerFileDescriptors>
             // along with it.
             buf.advance(num_bytes_sent.try_into().unwrap());
             fds.drain(0..fds_to_send);
+        } else if num_bytes_sent < 0
+            && std::io::Error::last_os_error().raw_os_error() == Some(libc::EPIPE)
+        {
+            // The other side has closed the socket (e.g. because the
+            // sandbox process terminated). Nothing will ever be
+            // delivered, so drop the pending data instead of retrying
+            // forever.
+            buf.clear();
+            fds.clear();
         }
     }
 
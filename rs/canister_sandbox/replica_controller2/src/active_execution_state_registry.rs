@@ -23,7 +23,10 @@ use ic_canister_sandbox_common::protocol::structs;
 use ic_system_api::SystemStateAccessorDirect;
 
 use std::collections::HashMap;
-use std::sync::{Arc, Condvar, Mutex};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Condvar, Mutex,
+};
 
 type CompletionFunction =
     Box<dyn FnOnce(&String, Option<structs::ExecOutput>) + Sync + Send + 'static>;
@@ -64,6 +67,9 @@ struct ActiveExecutionState {
 pub struct ActiveExecutionStateRegistry {
     states: Mutex<HashMap<String, ActiveExecutionState>>,
     state_accessor_cond: Condvar,
+    /// Set once the sandbox process has terminated. Only read or written
+    /// while holding the `states` lock.
+    terminated: AtomicBool,
 }
 
 /// All active executions on a sandbox process.
@@ -72,6 +78,7 @@ impl ActiveExecutionStateRegistry {
         Self {
             states: Mutex::new(HashMap::new()),
             state_accessor_cond: Condvar::new(),
+            terminated: AtomicBool::new(false),
         }
     }
 
@@ -81,6 +88,9 @@ impl ActiveExecutionStateRegistry {
     /// Returns the id to be used to refer to the execution. The
     /// returned id should generally be identical to the id_hint passed
     /// in, except when there is a possible collision.
+    ///
+    /// If the sandbox process has already terminated, the completion
+    /// is called right away, without output.
     pub fn register_execution<F>(
         &self,
         system_state_accessor: SystemStateAccessorDirect,
@@ -98,10 +108,11 @@ impl ActiveExecutionStateRegistry {
 
         // Try to use the given id, but ultimately ensure that the id
         // is unique by appending a numeric suffix.
-        let exec_id = {
+        let (exec_id, terminated) = {
             let mut suffix: u64 = 0;
             let mut mut_states = self.states.lock().unwrap();
-            loop {
+            let terminated = self.terminated.load(Ordering::SeqCst);
+            let exec_id = loop {
                 let id: String = {
                     if suffix == 0 {
                         id_hint.to_owned()
@@ -115,12 +126,42 @@ impl ActiveExecutionStateRegistry {
                     mut_states.insert(id.to_string(), state);
                     break id;
                 }
-            }
+            };
+            (exec_id, terminated)
         };
 
+        if terminated {
+            if let Some(completion) = self.extract_completion(&exec_id) {
+                completion(&exec_id, None);
+            }
+        }
+
         exec_id
     }
 
+    /// Records that the sandbox process has terminated and completes all
+    /// executions in progress, without output. Executions registered
+    /// afterwards are completed right away.
+    pub fn terminate_all_executions(&self) {
+        let completions: Vec<_> = {
+            let mut mut_states = self.states.lock().unwrap();
+            self.terminated.store(true, Ordering::SeqCst);
+            mut_states
+                .iter_mut()
+                .filter_map(|(id, state)| Some((id.clone(), state.completion.take()?)))
+                .collect()
+        };
+        for (exec_id, completion) in completions {
+            completion(&exec_id, None);
+        }
+    }
+
+    /// Returns `true` if the sandbox process has terminated.
+    pub fn is_terminated(&self) -> bool {
+        let _guard = self.states.lock().unwrap();
+        self.terminated.load(Ordering::SeqCst)
+    }
+
     /// Unregisters the specified execution state and extracts its
     /// system state accessor.
     /// This "should" be called after the sandbox has reported
@@ -340,4 +381,40 @@ mod tests {
 
         assert_eq!(exec1_id, exec1_finished.get());
     }
+
+    /// Validate that terminating the sandbox process completes executions
+    /// in progress as well as executions registered afterwards, without
+    /// output, and that their system state accessors can still be
+    /// unregistered.
+    #[test]
+    fn terminate_all_executions_completes_executions() {
+        let reg = ActiveExecutionStateRegistry::new();
+        let register = |finished: Arc<SyncCell<bool>>| {
+            reg.register_execution(
+                SystemStateAccessorDirect::new(
+                    SystemStateBuilder::default().build(),
+                    Arc::new(CyclesAccountManagerBuilder::new().build()),
+                ),
+                move |_id, exec_out| {
+                    finished.put(exec_out.is_some());
+                },
+                "exec",
+            )
+        };
+
+        let exec1_finished = Arc::new(SyncCell::<bool>::new());
+        let exec1_id = register(Arc::clone(&exec1_finished));
+        assert!(exec1_finished.try_get().is_none());
+        assert!(!reg.is_terminated());
+
+        reg.terminate_all_executions();
+        assert!(reg.is_terminated());
+        assert_eq!(Some(false), exec1_finished.try_get());
+        assert!(reg.unregister_execution(&exec1_id).is_some());
+
+        let exec2_finished = Arc::new(SyncCell::<bool>::new());
+        let exec2_id = register(Arc::clone(&exec2_finished));
+        assert_eq!(Some(false), exec2_finished.try_get());
+        assert!(reg.unregister_execution(&exec2_id).is_some());
+    }
 }
@@ -127,10 +127,14 @@ pub fn spawn_canister_sandbox_process(
     Ok((svc, Pid::from_raw(pid), thread_handle))
 }
 
+/// Spawns the sandbox process for the given canister. Returns the RPC
+/// interface to the process, its PID, and the handle of the thread
+/// handling its incoming messages, which finishes once the process has
+/// closed its end of the socket (i.e. once it has terminated).
 pub fn create_sandbox_process(
     controller_service: Arc<dyn rpc::DemuxServer<ctlsvc::Request, ctlsvc::Reply> + Send + Sync>,
     canister_id: &CanisterId,
-) -> Arc<dyn SandboxService> {
+) -> (Arc<dyn SandboxService>, Pid, std::thread::JoinHandle<()>) {
     let exec_path = if let Ok(path) = std::env::var("CANISTER_SANDBOX_BIN_PATH") {
         path
     } else {
@@ -140,14 +144,12 @@ pub fn create_sandbox_process(
 
     let canister_id = format!("{}", canister_id);
 
-    let (sandbox_handle, _pid, _recv_thread_handle) = spawn_canister_sandbox_process(
+    spawn_canister_sandbox_process(
         &exec_path,
         &[exec_path.clone(), canister_id],
         Arc::clone(&controller_service) as Arc<_>,
     )
-    .expect("Failed to start sandbox process");
-
-    sandbox_handle
+    .expect("Failed to start sandbox process")
 }
 
 // Collects environment variables as vector of strings of "key=value"
@@ -1,12 +1,15 @@
 use ic_canister_sandbox_common::protocol;
 use ic_canister_sandbox_common::sandbox_service::SandboxService;
 use ic_embedders::{WasmExecutionInput, WasmExecutionOutput};
-use ic_logger::ReplicaLogger;
+use ic_interfaces::execution_environment::{HypervisorError, InstanceStats};
+use ic_logger::{error, ReplicaLogger};
 use ic_replicated_state::canister_state::execution_state::WasmBinary;
 use ic_replicated_state::EmbedderCache;
 use ic_replicated_state::PageMap;
 use ic_system_api::SystemStateAccessorDirect;
-use ic_types::CanisterId;
+use ic_types::{CanisterId, NumInstructions};
+use ic_wasm_types::WasmEngineError;
+use nix::sys::wait::waitpid;
 use std::collections::HashMap;
 use std::sync::{
     atomic::{AtomicU64, Ordering},
@@ -53,36 +56,59 @@ impl SandboxedExecutionController {
 
     fn get_sandbox_process(&self, canister_id: &CanisterId) -> SandboxProcess {
         let mut guard = self.backends.lock().unwrap();
-        if let Some(sandbox_process) = (*guard).get(canister_id) {
+        match (*guard).get(canister_id) {
             // Sandbox backend running for this canister already.
-            sandbox_process.clone()
-        } else {
-            // No sandbox backend found for this canister. Start a new
-            // one and register it.
-            let reg = Arc::new(ActiveExecutionStateRegistry::new());
-            let controller_service =
-                ControllerServiceImpl::new(Arc::clone(&reg), self.logger.clone());
-            let sandbox_service = create_sandbox_process(controller_service, canister_id);
-
-            let sandbox_service_copy = Arc::clone(&sandbox_service);
-
-            // Set up compilation cache. Set up "deleter" in cache to issue
-            // CloseWasmRequest when we drop something from cache.
-            let compilation_cache = Cache::new(
-                move |_key, value: String| {
-                    sandbox_service_copy
-                        .close_wasm(protocol::sbxsvc::CloseWasmRequest { wasm_id: value })
-                        .on_completion(|_| {});
-                },
-                2,
-            );
-            let sandbox_process = SandboxProcess {
-                execution_states: reg,
-                sandbox_service,
-                compilation_cache,
-            };
-            (*guard).insert(*canister_id, sandbox_process.clone());
-            sandbox_process
+            Some(sandbox_process) if !sandbox_process.execution_states.is_terminated() => {
+                sandbox_process.clone()
+            }
+            // No (live) sandbox backend found for this canister. Start a
+            // new one and register it.
+            _ => {
+                let reg = Arc::new(ActiveExecutionStateRegistry::new());
+                let controller_service =
+                    ControllerServiceImpl::new(Arc::clone(&reg), self.logger.clone());
+                let (sandbox_service, pid, recv_thread_handle) =
+                    create_sandbox_process(controller_service, canister_id);
+
+                // Once the sandbox process terminates (which it only does on
+                // its own if it crashed or violated its syscall policy), reap
+                // it and fail all executions in progress on it.
+                let watched_reg = Arc::clone(&reg);
+                let logger = self.logger.clone();
+                let watched_canister_id = *canister_id;
+                std::thread::spawn(move || {
+                    let _ = recv_thread_handle.join();
+                    let status = waitpid(pid, None);
+                    error!(
+                        logger,
+                        "Sandbox process {} of canister {} terminated: {:?}",
+                        pid,
+                        watched_canister_id,
+                        status
+                    );
+                    watched_reg.terminate_all_executions();
+                });
+
+                let sandbox_service_copy = Arc::clone(&sandbox_service);
+
+                // Set up compilation cache. Set up "deleter" in cache to issue
+                // CloseWasmRequest when we drop something from cache.
+                let compilation_cache = Cache::new(
+                    move |_key, value: String| {
+                        sandbox_service_copy
+                            .close_wasm(protocol::sbxsvc::CloseWasmRequest { wasm_id: value })
+                            .on_completion(|_| {});
+                    },
+                    2,
+                );
+                let sandbox_process = SandboxProcess {
+                    execution_states: reg,
+                    sandbox_service,
+                    compilation_cache,
+                };
+                (*guard).insert(*canister_id, sandbox_process.clone());
+                sandbox_process
+            }
         }
     }
 
@@ -176,8 +202,30 @@ impl SandboxedExecutionController {
             })
             .on_completion(|_| {});
 
-        // Wait for completion.
-        let exec_output = rx.recv().unwrap().unwrap();
+        // Wait for completion. No output means that the sandbox process
+        // terminated before completing the execution.
+        let exec_output = match rx.recv().unwrap() {
+            Some(exec_output) => exec_output,
+            None => {
+                let system_state = sandbox_process
+                    .execution_states
+                    .unregister_execution(&id)
+                    .unwrap()
+                    .release_system_state();
+                return WasmExecutionOutput {
+                    wasm_result: Err(HypervisorError::WasmEngineError(
+                        WasmEngineError::SandboxProcessTerminated,
+                    )),
+                    num_instructions_left: NumInstructions::from(0),
+                    system_state,
+                    execution_state,
+                    instance_stats: InstanceStats {
+                        accessed_pages: 0,
+                        dirty_pages: 0,
+                    },
+                };
+            }
+        };
 
         // Release all resources on the sandbox process (compiled wasm is
         // left cached).
@@ -225,6 +273,18 @@ impl SandboxedExecutionController {
     pub fn compile_count_for_testing(&self) -> u64 {
         self.compile_count.load(Ordering::Relaxed)
     }
+
+    /// Returns `true` if the sandbox process of the given canister has been
+    /// marked as terminated, `None` if no process was ever started for it.
+    pub fn is_sandbox_process_terminated_for_testing(
+        &self,
+        canister_id: &CanisterId,
+    ) -> Option<bool> {
+        let guard = self.backends.lock().unwrap();
+        (*guard)
+            .get(canister_id)
+            .map(|sandbox_process| sandbox_process.execution_states.is_terminated())
+    }
 }
 
 fn serialize_pagemap(page_map: &PageMap) -> Vec<protocol::structs::IndexedPage> {
@@ -0,0 +1,111 @@
+//! End-to-end test that a sandbox process making a system call outside of
+//! its seccomp policy fails the execution it was serving.
+//!
+//! The test binary doubles as the sandbox executable: when started with
+//! `FAKE_SANDBOX_ENV_VAR` set (i.e. when spawned by the controller), it
+//! confines itself exactly like `canister_sandbox` does, waits for the first
+//! request from the replica and then opens a socket, as a compromised Wasm
+//! engine would. This needs its own `main` (the test is built with
+//! `harness = false`), because confinement requires a single-threaded
+//! process.
+#[cfg(target_os = "linux")]
+use std::os::unix::io::FromRawFd;
+
+#[cfg(target_os = "linux")]
+const FAKE_SANDBOX_ENV_VAR: &str = "FAKE_SANDBOX_MAKE_FORBIDDEN_SYSCALL";
+
+/// File descriptor on which the replica passes the control socket.
+#[cfg(target_os = "linux")]
+const CONTROL_SOCKET_FD: i32 = 3;
+
+#[cfg(target_os = "linux")]
+fn main() {
+    if std::env::var_os(FAKE_SANDBOX_ENV_VAR).is_some() {
+        run_fake_sandbox();
+    } else {
+        sandbox_making_forbidden_syscall_fails_execution();
+        println!("sandbox_making_forbidden_syscall_fails_execution ... ok");
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {}
+
+#[cfg(target_os = "linux")]
+fn run_fake_sandbox() {
+    use std::io::Read;
+
+    if let Err(err) = ic_canister_sandbox_backend_lib::confinement::confine_sandbox_process() {
+        eprintln!("Fake sandbox: Failed to confine sandbox process: {}", err);
+        std::process::exit(1);
+    }
+
+    // Safe because the controller hands over the control socket as file
+    // descriptor 3 and nothing else in this process uses it.
+    let mut socket = unsafe { std::os::unix::net::UnixStream::from_raw_fd(CONTROL_SOCKET_FD) };
+    let mut buf = [0u8; 1];
+    let _ = socket.read(&mut buf);
+
+    // Not allowed by the syscall filter: the kernel kills the process.
+    unsafe { libc::socket(libc::AF_INET, libc::SOCK_STREAM, 0) };
+
+    // Only reached if the filter failed to stop the system call.
+    std::process::exit(0);
+}
+
+#[cfg(target_os = "linux")]
+fn sandbox_making_forbidden_syscall_fails_execution() {
+    use ic_canister_sandbox_replica_controller2::sandboxed_execution_controller::SandboxedExecutionController;
+    use ic_embedders::WasmExecutionInput;
+    use ic_interfaces::execution_environment::{
+        ExecutionParameters, HypervisorError, SubnetAvailableMemory,
+    };
+    use ic_logger::replica_logger::no_op_logger;
+    use ic_system_api::ApiType;
+    use ic_test_utilities::{
+        cycles_account_manager::CyclesAccountManagerBuilder,
+        mock_time,
+        state::{initial_execution_state, SystemStateBuilder},
+        types::ids::user_test_id,
+    };
+    use ic_types::{
+        methods::{FuncRef, WasmMethod},
+        ComputeAllocation, NumBytes, NumInstructions,
+    };
+    use ic_wasm_types::WasmEngineError;
+    use std::sync::Arc;
+
+    let sandbox_path = std::env::current_exe().unwrap();
+    std::env::set_var("CANISTER_SANDBOX_BIN_PATH", sandbox_path);
+    std::env::set_var(FAKE_SANDBOX_ENV_VAR, "1");
+
+    let controller = SandboxedExecutionController::new(no_op_logger());
+    let system_state = SystemStateBuilder::default().build();
+    let canister_id = system_state.canister_id();
+    let output = controller.process(WasmExecutionInput {
+        api_type: ApiType::init(mock_time(), vec![], user_test_id(0).get()),
+        system_state,
+        canister_current_memory_usage: NumBytes::from(0),
+        execution_parameters: ExecutionParameters {
+            instruction_limit: NumInstructions::from(1_000_000),
+            canister_memory_limit: NumBytes::from(1 << 30),
+            subnet_available_memory: SubnetAvailableMemory::new(1 << 30),
+            compute_allocation: ComputeAllocation::zero(),
+            wasm_memory_limit: None,
+        },
+        func_ref: FuncRef::Method(WasmMethod::Update("go".to_string())),
+        execution_state: initial_execution_state(None),
+        cycles_account_manager: Arc::new(CyclesAccountManagerBuilder::new().build()),
+    });
+
+    assert_eq!(
+        output.wasm_result,
+        Err(HypervisorError::WasmEngineError(
+            WasmEngineError::SandboxProcessTerminated
+        ))
+    );
+    assert_eq!(
+        controller.is_sandbox_process_terminated_for_testing(&canister_id),
+        Some(true)
+    );
+}
@@ -8,12 +8,23 @@
 //! (and ideally forwards) compatibility with one or more preceeding
 //! protocol versions.
 
-use crate::{encoding::*, CURRENT_CERTIFICATION_VERSION};
+use crate::{
+    encoding::*,
+    hash_tree::hash_lazy_tree,
+    lazy_tree::{follow_path, materialize::materialize_partial, LazyTree},
+    subtree_visitor::{Pattern, SubtreeVisitor},
+    test_visitors::{NoopVisitor, TraceEntry as E, TracingVisitor},
+    traverse, CURRENT_CERTIFICATION_VERSION,
+};
 use assert_matches::assert_matches;
+use ic_crypto_tree_hash::{LabeledTree, MixedHashTree};
 use ic_registry_subnet_type::SubnetType;
-use ic_replicated_state::metadata_state::SystemMetadata;
+use ic_replicated_state::{
+    metadata_state::{NodeTopology, SubnetTopology, SystemMetadata},
+    ReplicatedState,
+};
 use ic_test_utilities::types::{
-    ids::{canister_test_id, subnet_test_id},
+    ids::{canister_test_id, node_test_id, subnet_test_id},
     messages::{RequestBuilder, ResponseBuilder},
 };
 use ic_types::{
@@ -21,17 +32,24 @@ use ic_types::{
     messages::{CallbackId, Payload, RejectContext, Request, RequestOrResponse, Response},
     user_error::RejectCode,
     xnet::StreamHeader,
-    CryptoHashOfPartialState, Cycles, Funds,
+    CryptoHashOfPartialState, Cycles, Funds, Time,
 };
+use maplit::btreemap;
 use serde::{Deserialize, Serialize};
 use serde_cbor::value::Value;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, VecDeque};
 
 /// Added subnet to canister ID ranges routing tables.
 const CERTIFICATION_VERSION_3: u32 = 3;
 /// Added optional `Request::cycles_payment` and `Response::cycles_refund`
 /// fields that are not yet populated.
 const CERTIFICATION_VERSION_4: u32 = 4;
+/// Added the signing public keys of subnet member nodes.
+const CERTIFICATION_VERSION_5: u32 = 5;
+/// Added optional `StreamHeader::reject_signal_deltas` field.
+const CERTIFICATION_VERSION_6: u32 = 6;
+/// Added optional `Request::deadline` and `Response::deadline` fields.
+const CERTIFICATION_VERSION_7: u32 = 7;
 
 //
 // Tests for exact binary encoding
@@ -65,6 +83,7 @@ fn canonical_encoding_stream_header() {
             begin: 23.into(),
             end: 25.into(),
             signals_end: 256.into(),
+            reject_signals: VecDeque::new(),
         };
 
         assert_eq!(
@@ -74,6 +93,50 @@ fn canonical_encoding_stream_header() {
     }
 }
 
+/// Canonical CBOR encoding of:
+///
+/// ```no_run
+/// StreamHeader {
+///     begin: 23.into(),
+///     end: 25.into(),
+///     signals_end: 256.into(),
+///     reject_signals: vec![249.into(), 250.into(), 252.into()].into(),
+/// }
+/// ```
+///
+/// Expected:
+///
+/// ```text
+/// A4         # map(4)
+///    00      # field_index(StreamHeader::begin)
+///    17      # unsigned(23)
+///    01      # field_index(StreamHeader::end)
+///    18 19   # unsigned(25)
+///    02      # field_index(StreamHeader::signals_end)
+///    19 0100 # unsigned(256)
+///    03      # field_index(StreamHeader::reject_signal_deltas)
+///    83      # array(3)
+///       01   # unsigned(1)
+///       02   # unsigned(2)
+///       04   # unsigned(4)
+/// ```
+#[test]
+fn canonical_encoding_stream_header_with_reject_signals() {
+    for certification_version in CERTIFICATION_VERSION_6..=CURRENT_CERTIFICATION_VERSION {
+        let header = StreamHeader {
+            begin: 23.into(),
+            end: 25.into(),
+            signals_end: 256.into(),
+            reject_signals: vec![249.into(), 250.into(), 252.into()].into(),
+        };
+
+        assert_eq!(
+            "A4 00 17 01 18 19 02 19 01 00 03 83 01 02 04",
+            as_hex(&encode_stream_header(&header, certification_version,))
+        );
+    }
+}
+
 /// Canonical CBOR encoding of:
 ///
 /// ```no_run
@@ -389,6 +452,133 @@ fn canonical_encoding_reject_response() {
     );
 }
 
+/// Canonical CBOR encoding of:
+///
+/// ```no_run
+/// RequestOrResponse::Request(
+///     Request {
+///         receiver: canister_test_id(1),
+///         sender: canister_test_id(2),
+///         sender_reply_callback: CallbackId::from(3),
+///         payment: Cycles::new(4),
+///         method_name: "test".to_string(),
+///         method_payload: vec![6],
+///         deadline: Some(Time::from_nanos_since_unix_epoch(1_000_000_000)),
+///     }
+/// )
+/// ```
+///
+/// Expected:
+///
+/// ```text
+/// A1                            # map(1)
+///    00                         # field_index(RequestOrResponse::request)
+///    A7                         # map(7)
+///       00                      # field_index(Request::receiver)
+///       4A                      # bytes(10)
+///          00000000000000010101 # "\x00\x00\x00\x00\x00\x00\x00\x01\x01\x01"
+///       01                      # field_index(Request::sender)
+///       4A                      # bytes(10)
+///          00000000000000020101 # "\x00\x00\x00\x00\x00\x00\x00\x02\x01\x01"
+///       02                      # field_index(Request::sender_reply_callback)
+///       03                      # unsigned(3)
+///       03                      # field_index(Request::payment)
+///       A1                      # map(1)
+///          00                   # field_index(Funds::cycles)
+///          A1                   # map(1)
+///             00                # field_index(Cycles::raw)
+///             04                # unsigned(4)
+///       04                      # field_index(Request::method_name)
+///       64                      # text(4)
+///          74657374             # "test"
+///       05                      # field_index(Request::method_payload)
+///       41                      # bytes(1)
+///          06                   # "\x06"
+///       07                      # field_index(Request::deadline)
+///       1A 3B9ACA00             # unsigned(1000000000)
+/// ```
+#[test]
+fn canonical_encoding_best_effort_request() {
+    let request = RequestOrResponse::Request(
+        RequestBuilder::new()
+            .receiver(canister_test_id(1))
+            .sender(canister_test_id(2))
+            .sender_reply_callback(CallbackId::from(3))
+            .payment(Cycles::new(4))
+            .method_name("test".to_string())
+            .method_payload(vec![6])
+            .deadline(Some(Time::from_nanos_since_unix_epoch(1_000_000_000)))
+            .build(),
+    );
+
+    assert_eq!(
+        "A1 00 A7 00 4A 00 00 00 00 00 00 00 01 01 01 01 4A 00 00 00 00 00 00 00 02 01 01 02 03 03 A1 00 A1 00 04 04 64 74 65 73 74 05 41 06 07 1A 3B 9A CA 00",
+        as_hex(&encode_message(&request, CURRENT_CERTIFICATION_VERSION))
+    );
+}
+
+/// Canonical CBOR encoding of:
+///
+/// ```no_run
+/// RequestOrResponse::Response(
+///     Response {
+///         originator: canister_test_id(5),
+///         respondent: canister_test_id(4),
+///         originator_reply_callback: CallbackId::from(3),
+///         refund: Cycles::new(2),
+///         response_payload: Payload::Data(vec![1]),
+///         deadline: Some(Time::from_nanos_since_unix_epoch(1_000_000_000)),
+///     }
+/// )
+/// ```
+///
+/// Expected:
+///
+/// ```text
+/// A1                            # map(1)
+///    01                         # field_index(RequestOrResponse::response)
+///    A6                         # map(6)
+///       00                      # field_index(Response::originator)
+///       4A                      # bytes(10)
+///          00000000000000050101 # "\x00\x00\x00\x00\x00\x00\x00\x05\x01\x01"
+///       01                      # field_index(Response::respondent)
+///       4A                      # bytes(10)
+///          00000000000000040101 # "\x00\x00\x00\x00\x00\x00\x00\x04\x01\x01"
+///       02                      # field_index(Response::originator_reply_callback)
+///       03                      # unsigned(3)
+///       03                      # field_index(Response::refund)
+///       A1                      # map(1)
+///          00                   # field_index(Funds::cycles)
+///          A1                   # map(1)
+///             00                # field_index(Cycles::raw)
+///             02                # unsigned(2)
+///       04                      # field_index(Response::response_payload)
+///       A1                      # map(1)
+///          00                   # field_index(Payload::data)
+///          41                   # bytes(1)
+///             01                # "\x01"
+///       06                      # field_index(Response::deadline)
+///       1A 3B9ACA00             # unsigned(1000000000)
+/// ```
+#[test]
+fn canonical_encoding_best_effort_response() {
+    let response = RequestOrResponse::Response(
+        ResponseBuilder::new()
+            .originator(canister_test_id(5))
+            .respondent(canister_test_id(4))
+            .originator_reply_callback(CallbackId::from(3))
+            .refund(Cycles::new(2))
+            .response_payload(Payload::Data(vec![1]))
+            .deadline(Some(Time::from_nanos_since_unix_epoch(1_000_000_000)))
+            .build(),
+    );
+
+    assert_eq!(
+        "A1 01 A6 00 4A 00 00 00 00 00 00 00 05 01 01 01 4A 00 00 00 00 00 00 00 04 01 01 02 03 03 A1 00 A1 00 02 04 A1 00 41 01 06 1A 3B 9A CA 00",
+        as_hex(&encode_message(&response, CURRENT_CERTIFICATION_VERSION))
+    );
+}
+
 /// Canonical CBOR encoding of:
 ///
 /// ```no_run
@@ -453,6 +643,74 @@ fn invalid_message_empty() {
     }
 }
 
+//
+// `StreamHeader` decoding
+//
+
+#[test]
+fn valid_stream_header_without_reject_signals() {
+    // A header encoded before the addition of `reject_signal_deltas`.
+    let bytes = serde_cbor::ser::to_vec_packed(&StreamHeaderV5 {
+        begin: 23,
+        end: 25,
+        signals_end: 256,
+    })
+    .unwrap();
+
+    assert_eq!(
+        StreamHeader {
+            begin: 23.into(),
+            end: 25.into(),
+            signals_end: 256.into(),
+            reject_signals: VecDeque::new(),
+        },
+        types::StreamHeader::proxy_decode(&bytes).unwrap()
+    );
+}
+
+#[test]
+fn invalid_stream_header_zero_reject_signal_delta() {
+    let bytes = serde_cbor::ser::to_vec_packed(&types::StreamHeader {
+        begin: 23,
+        end: 25,
+        signals_end: 256,
+        reject_signal_deltas: vec![1, 0],
+    })
+    .unwrap();
+
+    let res: Result<StreamHeader, ProxyDecodeError> = types::StreamHeader::proxy_decode(&bytes);
+    assert_matches!(
+        res,
+        Err(ProxyDecodeError::Other(err)) if err.contains("invalid reject signal deltas")
+    );
+}
+
+#[test]
+fn invalid_stream_header_reject_signal_before_zero() {
+    let bytes = serde_cbor::ser::to_vec_packed(&types::StreamHeader {
+        begin: 23,
+        end: 25,
+        signals_end: 256,
+        reject_signal_deltas: vec![200, 57],
+    })
+    .unwrap();
+
+    let res: Result<StreamHeader, ProxyDecodeError> = types::StreamHeader::proxy_decode(&bytes);
+    assert_matches!(
+        res,
+        Err(ProxyDecodeError::Other(err)) if err.contains("invalid reject signal deltas")
+    );
+}
+
+// Copy of `types::StreamHeader` before adding `reject_signal_deltas`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StreamHeaderV5 {
+    pub begin: u64,
+    pub end: u64,
+    pub signals_end: u64,
+}
+
 // Copy of `types::Request` before adding `cycles_payment`.
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
@@ -526,6 +784,38 @@ fn valid_request() {
     }
 }
 
+#[test]
+fn valid_best_effort_request() {
+    let mut request = request();
+    request.deadline = Some(Time::from_nanos_since_unix_epoch(1_000_000_000));
+    let bytes = types::Request::proxy_encode((&request, CURRENT_CERTIFICATION_VERSION)).unwrap();
+
+    assert_eq!(request, types::Request::proxy_decode(&bytes).unwrap());
+}
+
+#[test]
+fn best_effort_request_deadline_is_only_encoded_starting_with_certification_7() {
+    let mut request = request();
+    request.deadline = Some(Time::from_nanos_since_unix_epoch(1_000_000_000));
+    for certification_version in 0..=CURRENT_CERTIFICATION_VERSION {
+        let bytes = types::Request::proxy_encode((&request, certification_version)).unwrap();
+        let decoded = types::Request::proxy_decode(&bytes).unwrap();
+
+        if certification_version < CERTIFICATION_VERSION_7 {
+            assert_eq!(None, decoded.deadline);
+            let mut guaranteed_response_request = request.clone();
+            guaranteed_response_request.deadline = None;
+            assert_eq!(
+                types::Request::proxy_encode((&guaranteed_response_request, certification_version))
+                    .unwrap(),
+                bytes
+            );
+        } else {
+            assert_eq!(request, decoded);
+        }
+    }
+}
+
 #[test]
 fn encoding_request_for_certification_3_and_4_is_the_same() {
     let request = RequestOrResponse::Request(
@@ -570,7 +860,7 @@ fn invalid_request_extra_field() {
             assert_matches!(
                 res,
                 Err(ProxyDecodeError::CborDecodeError(err))
-                    if err.to_string().contains("expected field index 0 <= i < 7")
+                    if err.to_string().contains("expected field index 0 <= i < 8")
             );
         }
     }
@@ -685,6 +975,38 @@ fn valid_response() {
     }
 }
 
+#[test]
+fn valid_best_effort_response() {
+    let mut response = response();
+    response.deadline = Some(Time::from_nanos_since_unix_epoch(1_000_000_000));
+    let bytes = types::Response::proxy_encode((&response, CURRENT_CERTIFICATION_VERSION)).unwrap();
+
+    assert_eq!(response, types::Response::proxy_decode(&bytes).unwrap());
+}
+
+#[test]
+fn best_effort_response_deadline_is_only_encoded_starting_with_certification_7() {
+    let mut response = response();
+    response.deadline = Some(Time::from_nanos_since_unix_epoch(1_000_000_000));
+    for certification_version in 0..=CURRENT_CERTIFICATION_VERSION {
+        let bytes = types::Response::proxy_encode((&response, certification_version)).unwrap();
+        let decoded = types::Response::proxy_decode(&bytes).unwrap();
+
+        if certification_version < CERTIFICATION_VERSION_7 {
+            assert_eq!(None, decoded.deadline);
+            let mut guaranteed_response = response.clone();
+            guaranteed_response.deadline = None;
+            assert_eq!(
+                types::Response::proxy_encode((&guaranteed_response, certification_version))
+                    .unwrap(),
+                bytes
+            );
+        } else {
+            assert_eq!(response, decoded);
+        }
+    }
+}
+
 #[test]
 fn encoding_response_for_certification_3_and_4_is_the_same() {
     let response = RequestOrResponse::Response(
@@ -728,7 +1050,7 @@ fn invalid_response_extra_field() {
             assert_matches!(
                 res,
                 Err(ProxyDecodeError::CborDecodeError(err))
-                    if err.to_string().contains("expected field index 0 <= i < 6")
+                    if err.to_string().contains("expected field index 0 <= i < 7")
             );
         }
     }
@@ -1006,6 +1328,125 @@ fn encode_with_mutation<T: serde::Serialize>(
     serde_cbor::ser::to_vec_packed(&value)
 }
 
+//
+// Subnet node public keys
+//
+
+/// Traces `/subnet/<subnet_id>/node/<node_id>/public_key` for a subnet with
+/// two nodes: the `node` subtree is only part of the canonical state from
+/// certification version 5 on, and its leaves hold the public keys as is.
+///
+/// Expected CBOR encoding of the witness for the whole `node` subtree:
+///
+/// ```text
+/// 83                                  # array(3)
+///    01                               # fork
+///    83                               # array(3)
+///       02                            # labeled
+///       4A 0100000000000000FD01       # bytes(node_test_id(1))
+///       83                            # array(3)
+///          02                         # labeled
+///          4A 7075626C69635F6B6579    # bytes("public_key")
+///          82                         # array(2)
+///             03                      # leaf
+///             42 0506                 # bytes([5, 6])
+///    83                               # array(3)
+///       02                            # labeled
+///       4A 0200000000000000FD01       # bytes(node_test_id(2))
+///       83                            # array(3)
+///          02                         # labeled
+///          4A 7075626C69635F6B6579    # bytes("public_key")
+///          82                         # array(2)
+///             03                      # leaf
+///             42 0708                 # bytes([7, 8])
+/// ```
+#[test]
+fn subnet_node_public_keys() {
+    let tmpdir = tempfile::Builder::new().prefix("test").tempdir().unwrap();
+    let mut state = ReplicatedState::new_rooted_at(
+        subnet_test_id(1),
+        SubnetType::Application,
+        tmpdir.path().into(),
+    );
+    state.metadata.network_topology.subnets = btreemap! {
+        subnet_test_id(0) => SubnetTopology {
+            public_key: vec![1, 2, 3, 4],
+            nodes: btreemap! {
+                node_test_id(1) => NodeTopology {
+                    ip_address: "2001:db8::1".to_string(),
+                    http_port: 8080,
+                    public_key: vec![5, 6],
+                },
+                node_test_id(2) => NodeTopology {
+                    ip_address: "2001:db8::2".to_string(),
+                    http_port: 8080,
+                    public_key: vec![7, 8],
+                },
+            },
+            subnet_type: SubnetType::Application,
+        },
+    };
+
+    let node_subtree = vec![
+        E::EnterEdge(b"node".to_vec()),
+        E::StartSubtree,
+        E::EnterEdge(node_test_id(1).get().into_vec()),
+        E::StartSubtree,
+        E::EnterEdge(b"public_key".to_vec()),
+        E::VisitBlob(vec![5, 6]),
+        E::EndSubtree,
+        E::EnterEdge(node_test_id(2).get().into_vec()),
+        E::StartSubtree,
+        E::EnterEdge(b"public_key".to_vec()),
+        E::VisitBlob(vec![7, 8]),
+        E::EndSubtree,
+        E::EndSubtree,
+    ];
+
+    let pattern = Pattern::match_only("subnet", Pattern::all());
+    for certification_version in 0..=CURRENT_CERTIFICATION_VERSION {
+        state.metadata.certification_version = certification_version;
+        let visitor = SubtreeVisitor::new(&pattern, TracingVisitor::new(NoopVisitor));
+        let trace = traverse(&state, visitor).0;
+
+        let contains_node_subtree = trace
+            .windows(node_subtree.len())
+            .any(|window| window == &node_subtree[..]);
+        assert_eq!(
+            certification_version >= CERTIFICATION_VERSION_5,
+            contains_node_subtree,
+            "Unexpected node subtree at certification version {}: {:?}",
+            certification_version,
+            trace
+        );
+        if certification_version < CERTIFICATION_VERSION_5 {
+            assert!(!trace.contains(&E::EnterEdge(b"node".to_vec())));
+        }
+
+        let lazy_tree = LazyTree::from(&state);
+        let node_tree = follow_path(
+            &lazy_tree,
+            &[b"subnet", subnet_test_id(0).get().as_slice(), b"node"],
+        );
+        if certification_version < CERTIFICATION_VERSION_5 {
+            assert!(node_tree.is_none());
+            continue;
+        }
+        let node_tree = node_tree.unwrap();
+        let witness = hash_lazy_tree(&node_tree).witness::<MixedHashTree>(
+            &materialize_partial(&node_tree, &LabeledTree::Leaf(())).unwrap(),
+        );
+        assert_eq!(
+            "83 01 \
+             83 02 4A 01 00 00 00 00 00 00 00 FD 01 \
+             83 02 4A 70 75 62 6C 69 63 5F 6B 65 79 82 03 42 05 06 \
+             83 02 4A 02 00 00 00 00 00 00 00 FD 01 \
+             83 02 4A 70 75 62 6C 69 63 5F 6B 65 79 82 03 42 07 08",
+            as_hex(&serde_cbor::to_vec(&witness).unwrap())
+        );
+    }
+}
+
 //
 // Own fixtures, to ensure that compatibility tests are self-contained.
 //
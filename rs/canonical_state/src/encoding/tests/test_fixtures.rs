@@ -14,6 +14,7 @@ pub fn stream_header() -> StreamHeader {
         begin: 23.into(),
         end: 25.into(),
         signals_end: 256.into(),
+        reject_signals: vec![249.into(), 250.into(), 252.into()].into(),
     }
 }
 
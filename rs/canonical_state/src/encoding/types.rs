@@ -12,10 +12,16 @@
 
 use ic_protobuf::proxy::ProxyDecodeError;
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 use std::convert::{From, Into, TryFrom, TryInto};
 
 pub(crate) type Bytes = Vec<u8>;
 
+/// The certification version starting with which the `deadline` of
+/// best-effort requests and responses is encoded. Earlier versions omit it, so
+/// that their encoding does not change.
+pub(crate) const CERTIFICATION_VERSION_DEADLINES: u32 = 7;
+
 /// Canonical representation of `ic_types::xnet::StreamHeader`.
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
@@ -23,6 +29,10 @@ pub struct StreamHeader {
     pub begin: u64,
     pub end: u64,
     pub signals_end: u64,
+    /// Reject signals, each encoded as the difference between the next higher
+    /// reject signal (or `signals_end`, for the last one) and itself.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reject_signal_deltas: Vec<u64>,
 }
 
 /// Canonical representation of `ic_types::messages::RequestOrResponse`.
@@ -50,6 +60,9 @@ pub struct Request {
     pub method_payload: Bytes,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cycles_payment: Option<Cycles>,
+    /// Deadline of a best-effort call, in nanoseconds since the Unix epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<u64>,
 }
 
 /// Canonical representation of `ic_types::messages::Response`.
@@ -65,6 +78,10 @@ pub struct Response {
     pub response_payload: Payload,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cycles_refund: Option<Cycles>,
+    /// Deadline of the best-effort call being responded to, in nanoseconds
+    /// since the Unix epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<u64>,
 }
 
 /// Canonical representation of `ic_types::funds::Cycles`.
@@ -119,21 +136,50 @@ pub struct SystemMetadata {
 
 impl From<(&ic_types::xnet::StreamHeader, u32)> for StreamHeader {
     fn from((header, _certification_version): (&ic_types::xnet::StreamHeader, u32)) -> Self {
+        let mut next_index = header.signals_end;
+        let mut reject_signal_deltas = vec![0; header.reject_signals.len()];
+        for (i, stream_index) in header.reject_signals.iter().enumerate().rev() {
+            assert!(
+                *stream_index < next_index,
+                "Reject signals not strictly increasing or not below signals_end: {:?}",
+                header
+            );
+            reject_signal_deltas[i] = (next_index - *stream_index).get();
+            next_index = *stream_index;
+        }
+
         Self {
             begin: header.begin.get(),
             end: header.end.get(),
             signals_end: header.signals_end.get(),
+            reject_signal_deltas,
         }
     }
 }
 
-impl From<StreamHeader> for ic_types::xnet::StreamHeader {
-    fn from(header: StreamHeader) -> Self {
-        Self {
+impl TryFrom<StreamHeader> for ic_types::xnet::StreamHeader {
+    type Error = ProxyDecodeError;
+
+    fn try_from(header: StreamHeader) -> Result<Self, Self::Error> {
+        let mut reject_signals = VecDeque::with_capacity(header.reject_signal_deltas.len());
+        let mut stream_index = header.signals_end;
+        for delta in header.reject_signal_deltas.iter().rev() {
+            if *delta == 0 || *delta > stream_index {
+                return Err(ProxyDecodeError::Other(format!(
+                    "StreamHeader: invalid reject signal deltas: signals_end {}, deltas {:?}",
+                    header.signals_end, header.reject_signal_deltas
+                )));
+            }
+            stream_index -= delta;
+            reject_signals.push_front(stream_index.into());
+        }
+
+        Ok(Self {
             begin: header.begin.into(),
             end: header.end.into(),
             signals_end: header.signals_end.into(),
-        }
+            reject_signals,
+        })
     }
 }
 
@@ -190,6 +236,10 @@ impl From<(&ic_types::messages::Request, u32)> for Request {
             method_name: request.method_name.clone(),
             method_payload: request.method_payload.clone(),
             cycles_payment: None,
+            deadline: request
+                .deadline
+                .filter(|_| certification_version >= CERTIFICATION_VERSION_DEADLINES)
+                .map(|deadline| deadline.as_nanos_since_unix_epoch()),
         }
     }
 }
@@ -211,6 +261,9 @@ impl TryFrom<Request> for ic_types::messages::Request {
             payment,
             method_name: request.method_name,
             method_payload: request.method_payload,
+            deadline: request
+                .deadline
+                .map(ic_types::Time::from_nanos_since_unix_epoch),
         })
     }
 }
@@ -228,6 +281,10 @@ impl From<(&ic_types::messages::Response, u32)> for Response {
             refund: funds,
             response_payload: (&response.response_payload, certification_version).into(),
             cycles_refund: None,
+            deadline: response
+                .deadline
+                .filter(|_| certification_version >= CERTIFICATION_VERSION_DEADLINES)
+                .map(|deadline| deadline.as_nanos_since_unix_epoch()),
         }
     }
 }
@@ -248,6 +305,9 @@ impl TryFrom<Response> for ic_types::messages::Response {
             originator_reply_callback: response.originator_reply_callback.into(),
             refund,
             response_payload: response.response_payload.try_into()?,
+            deadline: response
+                .deadline
+                .map(ic_types::Time::from_nanos_since_unix_epoch),
         })
     }
 }
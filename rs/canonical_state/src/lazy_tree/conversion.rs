@@ -9,7 +9,9 @@ use ic_crypto_tree_hash::Label;
 use ic_registry_routing_table::RoutingTable;
 use ic_replicated_state::{
     canister_state::CanisterState,
-    metadata_state::{IngressHistoryState, StreamMap, SubnetTopology, SystemMetadata},
+    metadata_state::{
+        IngressHistoryState, NodeTopology, StreamMap, SubnetTopology, SystemMetadata,
+    },
     replicated_state::ReplicatedStateMessageRouting,
     ReplicatedState,
 };
@@ -18,7 +20,7 @@ use ic_types::{
     messages::{MessageId, EXPECTED_MESSAGE_ID_LENGTH},
     user_error::RejectCode,
     xnet::{StreamHeader, StreamIndex, StreamIndexedQueue},
-    CanisterId, PrincipalId, SubnetId,
+    CanisterId, NodeId, PrincipalId, SubnetId,
 };
 use std::collections::BTreeMap;
 use std::convert::{AsRef, TryInto};
@@ -374,8 +376,26 @@ fn subnets_as_tree(
                                 )
                             }
                         }),
+                    )
+                    .with_tree_if(
+                        certification_version > 4,
+                        "node",
+                        nodes_as_tree(&subnet_topology.nodes, certification_version),
                     ),
             )
         },
     })
 }
+
+fn nodes_as_tree(
+    nodes: &BTreeMap<NodeId, NodeTopology>,
+    certification_version: u32,
+) -> LazyTree<'_> {
+    fork(MapTransformFork {
+        map: nodes,
+        certification_version,
+        mk_tree: |_node_id, node_topology, _certification_version| {
+            fork(FiniteMap::default().with_tree("public_key", Blob(&node_topology.public_key[..])))
+        },
+    })
+}
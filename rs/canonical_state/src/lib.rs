@@ -29,4 +29,9 @@ pub use visitor::{Control, Visitor};
 ///   3. Added subnet to canister ID ranges routing tables.
 ///   4. Added optional `Request::cycles_payment` and `Response::cycles_refund`
 ///      fields that are not yet populated.
-pub const CURRENT_CERTIFICATION_VERSION: u32 = 4;
+///   5. Added the signing public keys of subnet member nodes.
+///   6. Added optional `StreamHeader::reject_signal_deltas`, replacing reject
+///      responses synthesized by the receiving subnet.
+///   7. Added optional `Request::deadline` and `Response::deadline` fields,
+///      populated for best-effort calls.
+pub const CURRENT_CERTIFICATION_VERSION: u32 = 7;
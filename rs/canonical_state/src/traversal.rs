@@ -55,7 +55,7 @@ mod tests {
         canister_state::{
             execution_state::WasmBinary, ExecutionState, ExportedFunctions, Global, NumWasmPages,
         },
-        metadata_state::SubnetTopology,
+        metadata_state::{NodeTopology, SubnetTopology},
         page_map::PageMap,
         testing::ReplicatedStateTesting,
         Memory,
@@ -63,7 +63,7 @@ mod tests {
     use ic_test_utilities::{
         mock_time,
         state::new_canister_state,
-        types::ids::{canister_test_id, subnet_test_id, user_test_id},
+        types::ids::{canister_test_id, node_test_id, subnet_test_id, user_test_id},
     };
     use ic_types::{CanisterId, Cycles, ExecutionRound};
     use ic_wasm_types::BinaryEncodedWasm;
@@ -338,11 +338,13 @@ mod tests {
     fn test_traverse_xnet_stream_header() {
         use ic_replicated_state::metadata_state::Stream;
         use ic_types::xnet::{StreamHeader, StreamIndex, StreamIndexedQueue};
+        use std::collections::VecDeque;
 
         let header = StreamHeader {
             begin: StreamIndex::from(4),
             end: StreamIndex::from(4),
             signals_end: StreamIndex::new(11),
+            reject_signals: VecDeque::new(),
         };
 
         let stream = Stream::new(
@@ -682,4 +684,89 @@ mod tests {
             traverse(&state, visitor).0
         );
     }
+
+    #[test]
+    fn test_traverse_subnet_nodes() {
+        let tmpdir = tempfile::Builder::new().prefix("test").tempdir().unwrap();
+        let mut state = ReplicatedState::new_rooted_at(
+            subnet_test_id(1),
+            SubnetType::Application,
+            tmpdir.path().into(),
+        );
+
+        state.metadata.network_topology.subnets = btreemap! {
+            subnet_test_id(0) => SubnetTopology {
+                public_key: vec![1, 2, 3, 4],
+                nodes: btreemap!{
+                    node_test_id(1) => NodeTopology {
+                        ip_address: "2001:db8::1".to_string(),
+                        http_port: 8080,
+                        public_key: vec![5, 6],
+                    },
+                    node_test_id(2) => NodeTopology {
+                        ip_address: "2001:db8::2".to_string(),
+                        http_port: 8080,
+                        public_key: vec![7, 8],
+                    },
+                },
+                subnet_type: SubnetType::Application,
+            },
+        };
+
+        let pattern = Pattern::match_only("subnet", Pattern::all());
+
+        // Node public keys are not part of the canonical state before version 5.
+        let visitor = SubtreeVisitor::new(&pattern, TracingVisitor::new(NoopVisitor));
+        state.metadata.certification_version = 4;
+        assert_eq!(
+            vec![
+                E::StartSubtree,
+                edge("subnet"),
+                E::StartSubtree,
+                E::EnterEdge(subnet_test_id(0).get().into_vec()),
+                E::StartSubtree,
+                edge("canister_ranges"),
+                E::VisitBlob(hex::decode("d9d9f780").unwrap()),
+                edge("public_key"),
+                E::VisitBlob(vec![1, 2, 3, 4]),
+                E::EndSubtree, // subnet
+                E::EndSubtree, // subnets
+                E::EndSubtree, // global
+            ],
+            traverse(&state, visitor).0
+        );
+
+        let visitor = SubtreeVisitor::new(&pattern, TracingVisitor::new(NoopVisitor));
+        state.metadata.certification_version = 5;
+        assert_eq!(
+            vec![
+                E::StartSubtree,
+                edge("subnet"),
+                E::StartSubtree,
+                E::EnterEdge(subnet_test_id(0).get().into_vec()),
+                E::StartSubtree,
+                edge("canister_ranges"),
+                E::VisitBlob(hex::decode("d9d9f780").unwrap()),
+                edge("node"),
+                E::StartSubtree,
+                E::EnterEdge(node_test_id(1).get().into_vec()),
+                E::StartSubtree,
+                edge("public_key"),
+                E::VisitBlob(vec![5, 6]),
+                E::EndSubtree, // node
+                E::EnterEdge(node_test_id(2).get().into_vec()),
+                E::StartSubtree,
+                edge("public_key"),
+                E::VisitBlob(vec![7, 8]),
+                E::EndSubtree, // node
+                E::EndSubtree, // nodes
+                edge("public_key"),
+                E::VisitBlob(vec![1, 2, 3, 4]),
+                E::EndSubtree, // subnet
+                E::EndSubtree, // subnets
+                E::EndSubtree, // global
+            ],
+            traverse(&state, visitor).0
+        );
+    }
 }
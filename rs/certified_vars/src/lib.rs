@@ -1,13 +1,13 @@
-use ic_crypto_tree_hash::LabeledTree;
-use ic_crypto_utils_threshold_sig::verify_combined;
+use ic_crypto_tree_hash::{lookup_path, LabeledTree};
+use ic_crypto_utils_threshold_sig::{threshold_sig_public_key_from_der, verify_combined};
 use ic_types::{
     consensus::certification::CertificationContent,
     crypto::{
         threshold_sig::ThresholdSigPublicKey, CombinedThresholdSig, CombinedThresholdSigOf,
         CryptoHash,
     },
-    messages::{Blob, Certificate},
-    CanisterId, CryptoHashOfPartialState, Time,
+    messages::{Blob, Certificate, CertificateDelegation},
+    CanisterId, CryptoHashOfPartialState, PrincipalId, SubnetId, Time,
 };
 use serde::Deserialize;
 use std::collections::BTreeMap;
@@ -34,6 +34,9 @@ pub enum CertificateValidationError {
     /// The certification contains a subnet delegation, which is not allowed for
     /// certificates coming from the root subnet.
     SubnetDelegationNotAllowed,
+    /// The subnet delegation in the certificate is not valid for the
+    /// effective canister ID.
+    InvalidDelegation(String),
 }
 
 impl fmt::Display for CertificateValidationError {
@@ -58,6 +61,7 @@ impl fmt::Display for CertificateValidationError {
                 f,
                 "expected certificate from the root subnet but found delegations in the certificate"
             ),
+            Self::InvalidDelegation(err) => write!(f, "invalid subnet delegation: {}", err),
         }
     }
 }
@@ -95,23 +99,7 @@ pub fn verify_certificate(
         return Err(CertificateValidationError::SubnetDelegationNotAllowed);
     }
 
-    let digest = CryptoHashOfPartialState::from(CryptoHash(certificate.tree.digest().to_vec()));
-    let content = CertificationContent::new(digest.clone());
-    let sig = CombinedThresholdSigOf::new(CombinedThresholdSig(certificate.signature.to_vec()));
-    verify_combined(&content, &sig, root_pk).map_err(|err| {
-        CertificateValidationError::InvalidSignature(format!(
-            "root_hash={:?}, sig={:?}, pk={:?}, error={:?}",
-            digest, certificate.signature, root_pk, err
-        ))
-    })?;
-
-    let replica_labeled_tree =
-        LabeledTree::<Vec<u8>>::try_from(certificate.tree).map_err(|err| {
-            CertificateValidationError::MalformedHashTree(format!(
-                "failed to convert hash tree to labeled tree: {:?}",
-                err
-            ))
-        })?;
+    let replica_labeled_tree = verify_tree(certificate, root_pk)?;
 
     let replica_state = ReplicaState::deserialize(LabeledTreeDeserializer::new(
         &replica_labeled_tree,
@@ -145,3 +133,130 @@ pub fn verify_certificate(
 
     Ok(time)
 }
+
+/// Checks the signature of the specified certificate, which may be signed by
+/// any subnet that the root subnet delegated `effective_canister_id` to.
+///
+/// If the check is successful, this function returns the tree of the
+/// certificate, from which pruned subtrees are omitted, together with the
+/// timestamp on the certificate.
+pub fn verify_certified_tree(
+    certificate: &[u8],
+    effective_canister_id: &CanisterId,
+    root_pk: &ThresholdSigPublicKey,
+) -> Result<(LabeledTree<Vec<u8>>, Time), CertificateValidationError> {
+    #[derive(Deserialize)]
+    struct ReplicaTime {
+        time: Leb128EncodedU64,
+    }
+
+    let certificate = decode_certificate(certificate)?;
+    let public_key = match &certificate.delegation {
+        Some(delegation) => verify_delegation(delegation, effective_canister_id, root_pk)?,
+        None => *root_pk,
+    };
+    let tree = verify_tree(certificate, &public_key)?;
+
+    let replica_time =
+        ReplicaTime::deserialize(LabeledTreeDeserializer::new(&tree)).map_err(|err| {
+            CertificateValidationError::DeserError(format!(
+                "failed to unpack time from a labeled tree: {}",
+                err
+            ))
+        })?;
+
+    Ok((tree, Time::from_nanos_since_unix_epoch(replica_time.time.0)))
+}
+
+/// Checks that the delegation is signed by the root subnet and that it covers
+/// `effective_canister_id`, and returns the public key of the subnet that the
+/// certificate was delegated to.
+fn verify_delegation(
+    delegation: &CertificateDelegation,
+    effective_canister_id: &CanisterId,
+    root_pk: &ThresholdSigPublicKey,
+) -> Result<ThresholdSigPublicKey, CertificateValidationError> {
+    let certificate = decode_certificate(delegation.certificate.as_slice())?;
+    if certificate.delegation.is_some() {
+        return Err(CertificateValidationError::InvalidDelegation(
+            "the delegation certificate contains another delegation".to_string(),
+        ));
+    }
+    let subnet_id = SubnetId::from(
+        PrincipalId::try_from(delegation.subnet_id.as_slice()).map_err(|err| {
+            CertificateValidationError::InvalidDelegation(format!("invalid subnet ID: {}", err))
+        })?,
+    );
+    let tree = verify_tree(certificate, root_pk)?;
+
+    let subnet_label = subnet_id.get().into_vec();
+    let canister_ranges = match lookup_path(&tree, &[b"subnet", &subnet_label, b"canister_ranges"])
+    {
+        Some(LabeledTree::Leaf(bytes)) => {
+            serde_cbor::from_slice::<Vec<(PrincipalId, PrincipalId)>>(bytes).map_err(|err| {
+                CertificateValidationError::DeserError(format!(
+                    "failed to decode canister ranges of subnet {}: {}",
+                    subnet_id, err
+                ))
+            })?
+        }
+        _ => {
+            return Err(CertificateValidationError::InvalidDelegation(format!(
+                "canister ranges of subnet {} not found",
+                subnet_id
+            )))
+        }
+    };
+    let canister_id = effective_canister_id.get();
+    if !canister_ranges
+        .iter()
+        .any(|(start, end)| *start <= canister_id && canister_id <= *end)
+    {
+        return Err(CertificateValidationError::InvalidDelegation(format!(
+            "canister {} is not assigned to subnet {}",
+            effective_canister_id, subnet_id
+        )));
+    }
+
+    match lookup_path(&tree, &[b"subnet", &subnet_label, b"public_key"]) {
+        Some(LabeledTree::Leaf(der)) => threshold_sig_public_key_from_der(der).map_err(|err| {
+            CertificateValidationError::DeserError(format!(
+                "failed to decode public key of subnet {}: {}",
+                subnet_id, err
+            ))
+        }),
+        _ => Err(CertificateValidationError::InvalidDelegation(format!(
+            "public key of subnet {} not found",
+            subnet_id
+        ))),
+    }
+}
+
+fn decode_certificate(certificate: &[u8]) -> Result<Certificate, CertificateValidationError> {
+    serde_cbor::from_slice(certificate).map_err(|err| {
+        CertificateValidationError::DeserError(format!("failed to decode certificate: {}", err))
+    })
+}
+
+/// Checks the signature of the certificate with `pk` and returns its tree.
+fn verify_tree(
+    certificate: Certificate,
+    pk: &ThresholdSigPublicKey,
+) -> Result<LabeledTree<Vec<u8>>, CertificateValidationError> {
+    let digest = CryptoHashOfPartialState::from(CryptoHash(certificate.tree.digest().to_vec()));
+    let content = CertificationContent::new(digest.clone());
+    let sig = CombinedThresholdSigOf::new(CombinedThresholdSig(certificate.signature.to_vec()));
+    verify_combined(&content, &sig, pk).map_err(|err| {
+        CertificateValidationError::InvalidSignature(format!(
+            "root_hash={:?}, sig={:?}, pk={:?}, error={:?}",
+            digest, certificate.signature, pk, err
+        ))
+    })?;
+
+    LabeledTree::<Vec<u8>>::try_from(certificate.tree).map_err(|err| {
+        CertificateValidationError::MalformedHashTree(format!(
+            "failed to convert hash tree to labeled tree: {:?}",
+            err
+        ))
+    })
+}
@@ -19,6 +19,7 @@ use crate::{
     registration::Config as RegistrationConfig,
     registry_client::Config as RegistryClientConfig,
     state_manager::Config as StateManagerConfig,
+    tracing::Config as TracingConfig,
 };
 use ic_types::{malicious_behaviour::MaliciousBehaviour, transport::TransportConfig};
 use serde::{Deserialize, Serialize};
@@ -46,6 +47,7 @@ pub struct Config {
     pub firewall: FirewallConfig,
     pub registration: RegistrationConfig,
     pub nns_registry_replicator: NnsRegistryReplicatorConfig,
+    pub tracing: TracingConfig,
 }
 
 /// Mirrors the Config struct except that fields are made optional. This is
@@ -68,6 +70,7 @@ pub struct ConfigOptional {
     pub firewall: Option<FirewallConfig>,
     pub registration: Option<RegistrationConfig>,
     pub nns_registry_replicator: Option<NnsRegistryReplicatorConfig>,
+    pub tracing: Option<TracingConfig>,
 }
 
 impl Config {
@@ -95,6 +98,7 @@ impl Config {
             firewall: FirewallConfig::default(),
             registration: RegistrationConfig::default(),
             nns_registry_replicator: NnsRegistryReplicatorConfig::default(),
+            tracing: TracingConfig::default(),
         }
     }
 
@@ -151,6 +155,7 @@ impl Config {
             nns_registry_replicator: cfg
                 .nns_registry_replicator
                 .unwrap_or(default.nns_registry_replicator),
+            tracing: cfg.tracing.unwrap_or(default.tracing),
         })
     }
 
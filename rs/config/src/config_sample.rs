@@ -225,6 +225,10 @@ pub const SAMPLE_CONFIG: &str = r#"
         // The default for this value is `false` and thus matches the previously expected behavior in
         // production use cases.
         block_on_overflow: true,
+
+        // If set and the log target is a file, the file is rotated once it would exceed
+        // `max_size_bytes`, keeping at most `max_rotated_files` rotated files.
+        // EXAMPLE: file_rotation: { max_size_bytes: 104857600, max_rotated_files: 5 },
     },
     // ===================================
     // Configuration of the logging setup for the nodemanager.
@@ -317,6 +321,24 @@ pub const SAMPLE_CONFIG: &str = r#"
     nns_registry_replicator: {
       poll_delay_duration_ms: 5000
     },
+    // =================================
+    // Tracing of ingress messages
+    // =================================
+    tracing: {
+        // How to export traces.
+        //
+        // Alternatives:
+        // - EXAMPLE: exporter: "none",
+        //   Do not trace ingress messages.
+        // - EXAMPLE: exporter: { otlp: "http://127.0.0.1:4318" },
+        //   Send traces to the specified OpenTelemetry collector using OTLP/HTTP.
+        // - EXAMPLE: exporter: { file: "/path/to/file" },
+        //   Append traces to the specified file in the OTLP JSON encoding.
+        exporter: "none",
+
+        // The fraction of ingress messages to trace.
+        sampling_ratio: 1.0,
+    },
 }
 "#;
 
@@ -31,12 +31,77 @@ pub struct CryptoConfig {
         proptest(strategy = "any::<String>().prop_map(|x| PathBuf::from(x))")
     )]
     pub crypto_root: PathBuf,
+    /// Where the crypto service provider's secret keys are kept and used.
+    #[serde(default)]
+    pub csp_vault_type: CspVaultType,
+    /// Where the key-encryption key that seals the node's secret key store is
+    /// obtained from. If `None`, the secret key store is stored unencrypted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sks_key_encryption_key: Option<KeyEncryptionKeySource>,
+}
+
+/// Where the crypto service provider (CSP) keeps its secret key store and
+/// performs all operations involving secret keys.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[cfg_attr(test, derive(Arbitrary))]
+pub enum CspVaultType {
+    /// In the replica process itself. Node key rotation (see
+    /// `registration::Config::node_key_rotation_period_secs`) is not
+    /// supported with this vault type.
+    InReplica,
+    /// In a separate vault process that listens on the Unix domain socket at
+    /// the given path. The vault process exclusively owns the secret key
+    /// store, so that its contents cannot leak through the replica's memory.
+    UnixSocket(
+        #[cfg_attr(
+            test,
+            proptest(strategy = "any::<String>().prop_map(|x| PathBuf::from(x))")
+        )]
+        PathBuf,
+    ),
+}
+
+/// The source of the key-encryption key (KEK) with which the node's secret key
+/// store is sealed at rest. The KEK is a 256-bit key, hex-encoded.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[cfg_attr(test, derive(Arbitrary))]
+pub enum KeyEncryptionKeySource {
+    /// A file at the given path, which must not be readable by others.
+    File(
+        #[cfg_attr(
+            test,
+            proptest(strategy = "any::<String>().prop_map(|x| PathBuf::from(x))")
+        )]
+        PathBuf,
+    ),
+    /// The environment variable with the given name.
+    EnvironmentVariable(String),
+}
+
+impl Default for CspVaultType {
+    fn default() -> Self {
+        CspVaultType::InReplica
+    }
 }
 
 impl CryptoConfig {
     /// Return a new CryptoConfig with the given crypto_root path.
     pub fn new(crypto_root: PathBuf) -> Self {
-        Self { crypto_root }
+        Self {
+            crypto_root,
+            csp_vault_type: CspVaultType::InReplica,
+            sks_key_encryption_key: None,
+        }
+    }
+
+    /// Return a new CryptoConfig with the given crypto_root path, using the
+    /// CSP vault listening on the Unix domain socket at `socket_path`.
+    pub fn new_with_unix_socket_vault(crypto_root: PathBuf, socket_path: PathBuf) -> Self {
+        Self {
+            crypto_root,
+            csp_vault_type: CspVaultType::UnixSocket(socket_path),
+            sks_key_encryption_key: None,
+        }
     }
 
     /// Creates a new CryptoConfig in a temporary directory and returns the
@@ -105,6 +170,36 @@ mod tests {
         CryptoConfig::run_with_temp_config(|config| serde_test(config));
     }
 
+    #[test]
+    fn unix_socket_vault_config_serializes_and_deserializes() {
+        serde_test(CryptoConfig::new_with_unix_socket_vault(
+            PathBuf::from("/tmp/ic_crypto"),
+            PathBuf::from("/run/ic-node/csp_vault.sock"),
+        ));
+    }
+
+    #[test]
+    fn sealed_secret_key_store_config_serializes_and_deserializes() {
+        CryptoConfig::run_with_temp_config(|mut config| {
+            config.sks_key_encryption_key = Some(KeyEncryptionKeySource::File(PathBuf::from(
+                "/run/ic-node/sks_kek",
+            )));
+            serde_test(config)
+        });
+    }
+
+    #[test]
+    fn sks_key_encryption_key_defaults_to_none() {
+        let config: CryptoConfig = json5::from_str("{ crypto_root: '/tmp/ic_crypto' }").unwrap();
+        assert_eq!(config.sks_key_encryption_key, None);
+    }
+
+    #[test]
+    fn csp_vault_type_defaults_to_in_replica() {
+        let config: CryptoConfig = json5::from_str("{ crypto_root: '/tmp/ic_crypto' }").unwrap();
+        assert_eq!(config.csp_vault_type, CspVaultType::InReplica);
+    }
+
     proptest! {
         #[test]
         #[ignore]
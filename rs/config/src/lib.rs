@@ -18,6 +18,7 @@ pub mod nns_registry_replicator;
 pub mod registration;
 pub mod registry_client;
 pub mod state_manager;
+pub mod tracing;
 
 pub use config::*;
 pub use config_parser::*;
@@ -35,6 +35,18 @@ pub enum LogTarget {
     File(PathBuf),
 }
 
+/// Size-based rotation of a log file.
+///
+/// Once the log file has reached `max_size_bytes`, it is renamed to `<file>.1`
+/// before the next line is written, previously rotated files are shifted to
+/// `<file>.2`, `<file>.3`, and so on, and a new log file is started. At most
+/// `max_rotated_files` rotated files are kept.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogFileRotation {
+    pub max_size_bytes: u64,
+    pub max_rotated_files: usize,
+}
+
 //Because serde is particular with its options and we want
 //to be retrocompatible, we'll keep Stdout as the default
 //log target, but it has to be through a function that gets
@@ -60,6 +72,10 @@ pub struct Config {
     /// is full.
     #[serde(default = "default_block_on_overflow")]
     pub block_on_overflow: bool,
+    /// If set and `target` is a file, the file is rotated based on its size.
+    /// Otherwise, the file is truncated at startup.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_rotation: Option<LogFileRotation>,
 }
 
 /// Messages are logged asynchronously. That is, log messages are sent over an
@@ -81,6 +97,7 @@ impl Default for Config {
             enabled_tags: vec![],
             target: default_logtarget(),
             block_on_overflow: true,
+            file_rotation: None,
         }
     }
 }
@@ -33,6 +33,15 @@ pub struct Config {
     /// up the initial state of the registry's local store.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub nns_pub_key_pem: Option<PathBuf>,
+
+    /// If set, the node manager rotates the node's committee signing, DKG
+    /// dealing encryption and TLS keys once they are older than this many
+    /// seconds, and registers the new keys with the NNS. Key rotation is only
+    /// performed if the secret keys are held by a separate CSP vault process
+    /// (`CspVaultType::UnixSocket`); with `CspVaultType::InReplica`, the keys
+    /// are not rotated and the node manager logs an error on startup.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node_key_rotation_period_secs: Option<u64>,
 }
 
 // We allow for the operator to only specify some of the fields while the others
@@ -64,6 +73,7 @@ impl Default for Config {
             eject_keycard_signal_file: PathBuf::from("/var/lib/dfinity-node/eject-hsm"),
             nns_url: None,
             nns_pub_key_pem: None,
+            node_key_rotation_period_secs: None,
         }
     }
 }
@@ -210,6 +210,10 @@ pub struct CyclesAccountManagerConfig {
 
     /// How often to charge canisters for memory and compute allocations.
     pub duration_between_allocation_charges: Duration,
+
+    /// How long a canister that failed to pay for its memory and compute
+    /// allocations stays frozen before its Wasm state is deleted.
+    pub out_of_cycles_grace_period: Duration,
 }
 
 impl CyclesAccountManagerConfig {
@@ -234,6 +238,7 @@ impl CyclesAccountManagerConfig {
             // 4 SDR per GiB per year => 4e12 Cycles per year
             gib_storage_per_second_fee: Cycles::new(127_000),
             duration_between_allocation_charges: Duration::from_secs(10),
+            out_of_cycles_grace_period: Duration::from_secs(30 * 24 * 3600), // 30 days
         }
     }
 
@@ -250,6 +255,7 @@ impl CyclesAccountManagerConfig {
             ingress_byte_reception_fee: Cycles::new(0),
             gib_storage_per_second_fee: Cycles::new(0),
             duration_between_allocation_charges: Duration::from_secs(10),
+            out_of_cycles_grace_period: Duration::from_secs(30 * 24 * 3600), // 30 days
         }
     }
 }
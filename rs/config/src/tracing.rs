@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Exporter {
+    /// Do not export traces.
+    None,
+    /// Send traces to the OTLP/HTTP collector at the specified URL, e.g.,
+    /// `http://127.0.0.1:4318`, in the OTLP JSON encoding.
+    Otlp(String),
+    /// Append traces to the given file, one OTLP JSON export request per line.
+    File(PathBuf),
+}
+
+impl Default for Exporter {
+    fn default() -> Self {
+        Exporter::None
+    }
+}
+
+/// The config for tracing ingress messages through the replica.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub exporter: Exporter,
+    /// The fraction of ingress messages that are traced, between 0 and 1.
+    /// The sampling decision is derived from the message ID, so that all
+    /// components and all nodes trace the same messages.
+    pub sampling_ratio: f64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            exporter: Exporter::None,
+            sampling_ratio: 1.0,
+        }
+    }
+}
@@ -306,6 +306,7 @@ pub fn generate_responses_to_setup_initial_dkg_calls(
                 originator_reply_callback: *callback_id,
                 refund: Cycles::zero(),
                 response_payload,
+                deadline: None,
             });
         }
     }
@@ -337,6 +338,7 @@ pub fn generate_responses_to_sign_with_mock_ecdsa_calls(
                 originator_reply_callback: *callback_id,
                 refund: Cycles::zero(),
                 response_payload,
+                deadline: None,
             });
         }
     }
@@ -68,6 +68,10 @@ impl From<CspDkgLoadPrivateKeyError> for DkgLoadTranscriptError {
                     panic_prefix, ciphertext_epoch, secret_key_epoch
                 );
             }
+            CspDkgLoadPrivateKeyError::TransientInternalError(error) => {
+                // Forward to the caller, since retrying may succeed
+                DkgLoadTranscriptError::TransientInternalError(error)
+            }
         }
     }
 }
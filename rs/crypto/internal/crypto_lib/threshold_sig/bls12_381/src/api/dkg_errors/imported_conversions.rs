@@ -355,6 +355,10 @@ mod create_dealing_error_conversions_v2 {
                         panic_prefix, error
                     );
                 }
+                CspDkgCreateReshareDealingError::TransientInternalError(error) => {
+                    // Forward to the caller, since retrying may succeed
+                    DkgCreateDealingError::TransientInternalError(error)
+                }
             }
         }
     }
@@ -457,6 +461,9 @@ mod retain_active_keys_error_conversions {
                 CspDkgUpdateFsEpochError::FsKeyNotInSecretKeyStoreError(e) => {
                     DkgKeyRemovalError::FsKeyNotInSecretKeyStoreError(e)
                 }
+                CspDkgUpdateFsEpochError::TransientInternalError(e) => {
+                    DkgKeyRemovalError::TransientInternalError(e)
+                }
             }
         }
     }
@@ -4,6 +4,7 @@ use ic_crypto_internal_types::sign::threshold_sig::ni_dkg::Epoch;
 use ic_crypto_internal_types::sign::threshold_sig::public_key::bls12_381::PublicKeyBytes;
 use ic_types::crypto::AlgorithmId;
 use ic_types::{NodeIndex, NumberOfNodes};
+use serde::{Deserialize, Serialize};
 
 // These are the base error types used by ni_dkg
 // TODO(CRP-574): Move these up, out of dkg.
@@ -44,11 +45,13 @@ impl From<MisnumberedReceiverError> for CspDkgVerifyDealingError {
 }
 
 /// Creation of a forward-secure keypair during DKG failed.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CspDkgCreateFsKeyError {
     /// Precondition error: The AlgorithmId does not correspond to a NiDkg
     /// variant.
     UnsupportedAlgorithmId(AlgorithmId),
+    /// The CSP vault could not be reached; retrying may succeed.
+    TransientInternalError(String),
 }
 
 /// Verification of a DKG forward-secure key failed.
@@ -63,12 +66,14 @@ pub enum CspDkgVerifyFsKeyError {
 }
 
 /// Updating the forward-secure epoch for DKG failed.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CspDkgUpdateFsEpochError {
     /// Precondition error: The AlgorithmId does not correspond to a NiDkg
     /// variant.
     UnsupportedAlgorithmId(AlgorithmId),
     FsKeyNotInSecretKeyStoreError(KeyNotFoundError),
+    /// The CSP vault could not be reached; retrying may succeed.
+    TransientInternalError(String),
 }
 
 /// Encrypting or zero-knowledge proving during DKG failed.
@@ -128,6 +133,8 @@ pub enum CspDkgCreateDealingError {
     /// Hardware error: This machine cannot handle this request because some
     /// parameter was too large.
     SizeError(SizeError),
+    /// The CSP vault could not be reached; retrying may succeed.
+    TransientInternalError(String),
 }
 
 impl From<EncryptAndZKProveError> for CspDkgCreateDealingError {
@@ -148,7 +155,7 @@ impl From<EncryptAndZKProveError> for CspDkgCreateDealingError {
 }
 
 /// Creation of a DKG resharing dealing failed.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CspDkgCreateReshareDealingError {
     /// Precondition error: The AlgorithmId does not correspond to a NiDkg
     /// variant.
@@ -175,6 +182,8 @@ pub enum CspDkgCreateReshareDealingError {
     /// Hardware error: This machine cannot handle this request because some
     /// parameter was too large.
     SizeError(SizeError),
+    /// The CSP vault could not be reached; retrying may succeed.
+    TransientInternalError(String),
 }
 
 impl From<EncryptAndZKProveError> for CspDkgCreateReshareDealingError {
@@ -220,6 +229,9 @@ impl From<CspDkgCreateDealingError> for CspDkgCreateReshareDealingError {
             CspDkgCreateDealingError::SizeError(error) => {
                 CspDkgCreateReshareDealingError::SizeError(error)
             }
+            CspDkgCreateDealingError::TransientInternalError(error) => {
+                CspDkgCreateReshareDealingError::TransientInternalError(error)
+            }
         }
     }
 }
@@ -256,6 +268,9 @@ impl From<CspDkgCreateReshareDealingError> for CspDkgCreateDealingError {
             CspDkgCreateReshareDealingError::SizeError(error) => {
                 CspDkgCreateDealingError::SizeError(error)
             }
+            CspDkgCreateReshareDealingError::TransientInternalError(error) => {
+                CspDkgCreateDealingError::TransientInternalError(error)
+            }
         }
     }
 }
@@ -438,7 +453,7 @@ impl From<CspDkgCreateTranscriptError> for CspDkgCreateReshareTranscriptError {
 }
 
 /// Loading a private key from a DKG transcript failed.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CspDkgLoadPrivateKeyError {
     /// The AlgorithmId does not correspond to a NiDkg variant
     UnsupportedAlgorithmId(AlgorithmId),
@@ -457,6 +472,8 @@ pub enum CspDkgLoadPrivateKeyError {
         ciphertext_epoch: Epoch,
         secret_key_epoch: Epoch,
     },
+    /// The CSP vault could not be reached; retrying may succeed.
+    TransientInternalError(String),
 }
 
 impl CspDkgVerifyReshareDealingError {
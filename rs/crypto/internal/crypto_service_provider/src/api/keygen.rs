@@ -63,6 +63,15 @@ pub trait CspSecretKeyStoreChecker {
     fn sks_contains_tls_key(&self, cert: &TlsPublicKeyCert) -> bool;
 }
 
+/// A trait that allows removing keys from the secret key store, e.g., the keys
+/// that were replaced by a key rotation.
+pub trait CspSecretKeyStoreRemover {
+    /// Removes the key with the given `id` from the store.
+    ///
+    /// Returns `true` if the key was present.
+    fn sks_remove(&self, key_id: &KeyId) -> bool;
+}
+
 /// A trait that exposes the information about node public keys and key
 /// identifiers.
 pub trait NodePublicKeyData {
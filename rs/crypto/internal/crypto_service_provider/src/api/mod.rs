@@ -5,7 +5,9 @@ mod sign;
 mod threshold;
 mod tls_stub;
 
-pub use keygen::{CspKeyGenerator, CspSecretKeyStoreChecker, NodePublicKeyData};
+pub use keygen::{
+    CspKeyGenerator, CspSecretKeyStoreChecker, CspSecretKeyStoreRemover, NodePublicKeyData,
+};
 pub use sign::CspSigner;
 pub use threshold::{
     threshold_sign_error::CspThresholdSignError, DistributedKeyGenerationCspClient, NiDkgCspClient,
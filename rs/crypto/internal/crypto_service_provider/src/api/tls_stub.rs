@@ -14,6 +14,12 @@ pub mod tls_errors;
 /// signature needed for performing a TLS handshake.
 pub trait CspTlsHandshakeSignerProvider: Send + Sync {
     fn handshake_signer(&self) -> Arc<dyn TlsHandshakeCspServer>;
+
+    /// Returns `true` if the TLS secret keys are held by a CSP vault that
+    /// runs in a separate process. The secret keys then never leave the
+    /// vault, so TLS handshakes must be performed with the
+    /// `handshake_signer`.
+    fn tls_secret_keys_are_remote(&self) -> bool;
 }
 
 /// A trait that exposes TLS server-side handshaking
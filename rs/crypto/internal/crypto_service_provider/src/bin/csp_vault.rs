@@ -0,0 +1,66 @@
+//! The CSP vault: a separate process that holds the node's secret keys and
+//! performs all operations involving them on behalf of the replica, which
+//! connects to it using `RemoteCspVault` (see `CspVaultType::UnixSocket`).
+use ic_config::crypto::KeyEncryptionKeySource;
+use ic_config::logger::Config as LoggerConfig;
+use ic_crypto_internal_csp::secret_key_store::sealing::key_encryption_key_provider;
+use ic_crypto_internal_csp::{CspVaultServer, LocalCspServer};
+use ic_crypto_internal_logmon::metrics::CryptoMetrics;
+use ic_logger::{new_replica_logger, LoggerImpl};
+use std::path::PathBuf;
+use std::sync::Arc;
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+#[structopt(name = "ic-crypto-csp-vault")]
+struct CspVaultArgs {
+    /// The directory containing the secret key stores.
+    #[structopt(long, parse(from_os_str))]
+    crypto_root: PathBuf,
+
+    /// The path of the Unix domain socket to listen on.
+    #[structopt(long, parse(from_os_str))]
+    socket: PathBuf,
+
+    /// The user ID of the replica. Connections from other users are rejected.
+    /// Defaults to the user ID of the vault.
+    #[structopt(long)]
+    allowed_client_uid: Option<u32>,
+
+    /// The path of a file containing the hex-encoded key-encryption key with
+    /// which the secret key stores are sealed.
+    #[structopt(long, parse(from_os_str), conflicts_with = "kek-env-var")]
+    kek_file: Option<PathBuf>,
+
+    /// The name of an environment variable containing the hex-encoded
+    /// key-encryption key with which the secret key stores are sealed.
+    #[structopt(long)]
+    kek_env_var: Option<String>,
+}
+
+fn main() {
+    let args = CspVaultArgs::from_args();
+    let logger_config = LoggerConfig::default();
+    let base_logger = LoggerImpl::new(&logger_config, "csp_vault".to_string());
+    let logger = new_replica_logger(base_logger.root.clone(), &logger_config);
+
+    let kek_source = match (args.kek_file, args.kek_env_var) {
+        (Some(path), _) => Some(KeyEncryptionKeySource::File(path)),
+        (None, Some(variable)) => Some(KeyEncryptionKeySource::EnvironmentVariable(variable)),
+        (None, None) => None,
+    };
+    let local_csp_server = LocalCspServer::new_in_dir(
+        &args.crypto_root,
+        kek_source.as_ref().map(key_encryption_key_provider),
+        Arc::new(CryptoMetrics::none()),
+        logger.clone(),
+    );
+    let allowed_client_uid = args
+        .allowed_client_uid
+        .unwrap_or_else(|| nix::unistd::getuid().as_raw());
+    let server = CspVaultServer::bind(&args.socket, allowed_client_uid, logger)
+        .unwrap_or_else(|e| panic!("Failed to bind to {}: {}", args.socket.display(), e));
+    if let Err(e) = server.run(Arc::new(local_csp_server)) {
+        panic!("CSP vault stopped accepting connections: {}", e);
+    }
+}
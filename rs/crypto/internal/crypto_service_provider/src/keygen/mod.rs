@@ -1,15 +1,13 @@
 //! Utilities for key generation and key identifier generation
 
-use crate::api::{CspKeyGenerator, CspSecretKeyStoreChecker};
-use crate::secret_key_store::{SecretKeyStore, SecretKeyStoreError};
-use crate::types::{CspPop, CspPublicKey, CspSecretKey};
+use crate::api::{CspKeyGenerator, CspSecretKeyStoreChecker, CspSecretKeyStoreRemover};
+use crate::secret_key_store::SecretKeyStore;
+use crate::types::{CspPop, CspPublicKey};
 use crate::Csp;
-use ic_crypto_internal_tls::keygen::generate_tls_key_pair_der;
 use ic_crypto_sha::{Context, DomainSeparationContext};
 use ic_crypto_tls_interfaces::TlsPublicKeyCert;
 use ic_types::crypto::{AlgorithmId, CryptoError, KeyId};
 use ic_types::NodeId;
-use openssl::asn1::Asn1Time;
 use rand::{CryptoRng, Rng};
 use std::convert::TryFrom;
 
@@ -17,6 +15,7 @@ const KEY_ID_DOMAIN: &str = "ic-key-id";
 
 use crate::server::api::{
     BasicSignatureCspServer, MultiSignatureCspServer, SecretKeyStoreCspServer,
+    TlsHandshakeCspServer,
 };
 use ic_crypto_internal_types::encrypt::forward_secure::CspFsEncryptionPublicKey;
 use ic_crypto_sha::Sha256;
@@ -29,29 +28,21 @@ impl<R: Rng + CryptoRng, S: SecretKeyStore, C: SecretKeyStore> CspKeyGenerator f
     fn gen_key_pair(&self, alg_id: AlgorithmId) -> Result<(KeyId, CspPublicKey), CryptoError> {
         match alg_id {
             AlgorithmId::MultiBls12_381 => {
-                let (key_id, csp_pk, _pop) = self.csp_server.gen_key_pair_with_pop(alg_id)?;
+                let (key_id, csp_pk, _pop) = self.csp_vault.gen_key_pair_with_pop(alg_id)?;
                 Ok((key_id, csp_pk))
             }
-            _ => Ok(self.csp_server.gen_key_pair(alg_id)?),
+            _ => Ok(self.csp_vault.gen_key_pair(alg_id)?),
         }
     }
     fn gen_key_pair_with_pop(
         &self,
         algorithm_id: AlgorithmId,
     ) -> Result<(KeyId, CspPublicKey, CspPop), CryptoError> {
-        Ok(self.csp_server.gen_key_pair_with_pop(algorithm_id)?)
+        Ok(self.csp_vault.gen_key_pair_with_pop(algorithm_id)?)
     }
 
     fn gen_tls_key_pair(&mut self, node: NodeId, not_after: &str) -> TlsPublicKeyCert {
-        let serial = self.rng_write_lock().gen::<[u8; 19]>();
-        let common_name = &node.get().to_string()[..];
-        let not_after = Asn1Time::from_str_x509(not_after)
-            .expect("invalid X.509 certificate expiration date (not_after)");
-        let (cert, secret_key) = generate_tls_key_pair_der(common_name, serial, &not_after);
-
-        let x509_pk_cert = TlsPublicKeyCert::new_from_der(cert.bytes)
-            .expect("generated X509 certificate has malformed DER encoding");
-        let _key_id = self.store_tls_secret_key(&x509_pk_cert, secret_key);
+        let (_key_id, x509_pk_cert) = self.csp_vault.gen_tls_key_pair(node, not_after);
         x509_pk_cert
     }
 }
@@ -60,7 +51,7 @@ impl<R: Rng + CryptoRng, S: SecretKeyStore, C: SecretKeyStore> CspSecretKeyStore
     for Csp<R, S, C>
 {
     fn sks_contains(&self, key_id: &KeyId) -> bool {
-        self.csp_server.sks_contains(key_id)
+        self.csp_vault.sks_contains(key_id)
     }
 
     fn sks_contains_tls_key(&self, cert: &TlsPublicKeyCert) -> bool {
@@ -70,14 +61,11 @@ impl<R: Rng + CryptoRng, S: SecretKeyStore, C: SecretKeyStore> CspSecretKeyStore
     }
 }
 
-impl<R: Rng + CryptoRng, S: SecretKeyStore, C: SecretKeyStore> Csp<R, S, C> {
-    fn store_secret_key_or_panic(&self, csp_secret_key: CspSecretKey, key_id: KeyId) {
-        match &self.sks_write_lock().insert(key_id, csp_secret_key, None) {
-            Ok(()) => {}
-            Err(SecretKeyStoreError::DuplicateKeyId(key_id)) => {
-                panic!("A key with ID {} has already been inserted", key_id);
-            }
-        };
+impl<R: Rng + CryptoRng, S: SecretKeyStore, C: SecretKeyStore> CspSecretKeyStoreRemover
+    for Csp<R, S, C>
+{
+    fn sks_remove(&self, key_id: &KeyId) -> bool {
+        self.csp_vault.sks_remove(key_id)
     }
 }
 
@@ -118,24 +106,11 @@ pub fn forward_secure_key_id(public_key: &CspFsEncryptionPublicKey) -> KeyId {
 
 mod tls_keygen {
     use super::*;
-    use ic_crypto_internal_tls::keygen::TlsEd25519SecretKeyDerBytes;
 
     /// Create a key identifier by hashing the bytes of the certificate
     pub fn tls_cert_hash_as_key_id(cert: &TlsPublicKeyCert) -> KeyId {
         bytes_hash_as_key_id(AlgorithmId::Tls, cert.as_der())
     }
-
-    impl<R: Rng + CryptoRng, S: SecretKeyStore, C: SecretKeyStore> Csp<R, S, C> {
-        pub(super) fn store_tls_secret_key(
-            &mut self,
-            cert: &TlsPublicKeyCert,
-            secret_key: TlsEd25519SecretKeyDerBytes,
-        ) -> KeyId {
-            let key_id = tls_cert_hash_as_key_id(cert);
-            self.store_secret_key_or_panic(CspSecretKey::TlsEd25519(secret_key), key_id);
-            key_id
-        }
-    }
 }
 
 /// Some key related utils
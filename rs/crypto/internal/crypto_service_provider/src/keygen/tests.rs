@@ -10,23 +10,22 @@ use rand_chacha::ChaCha20Rng;
 
 #[test]
 fn should_correctly_generate_ed25519_keys() {
-    let csprng = csprng_seeded_with(42);
-    let csp = Csp::of(csprng, volatile_key_store());
+    for csp in csps_with_local_and_remote_vault(csprng_seeded_with(42)) {
+        let (key_id, pk) = csp.gen_key_pair(AlgorithmId::Ed25519).unwrap();
 
-    let (key_id, pk) = csp.gen_key_pair(AlgorithmId::Ed25519).unwrap();
-
-    assert_eq!(
-        key_id,
-        KeyId::from(hex_to_32_bytes(
-            "be652632635fa33651721671afa29c576396beaec8af0d8ba819605fc7dea8e4"
-        )),
-    );
-    assert_eq!(
-        pk,
-        CspPublicKey::ed25519_from_hex(
-            "78eda21ba04a15e2000fe8810fe3e56741d23bb9ae44aa9d5bb21b76675ff34b"
-        )
-    );
+        assert_eq!(
+            key_id,
+            KeyId::from(hex_to_32_bytes(
+                "be652632635fa33651721671afa29c576396beaec8af0d8ba819605fc7dea8e4"
+            )),
+        );
+        assert_eq!(
+            pk,
+            CspPublicKey::ed25519_from_hex(
+                "78eda21ba04a15e2000fe8810fe3e56741d23bb9ae44aa9d5bb21b76675ff34b"
+            )
+        );
+    }
 }
 
 #[test]
@@ -91,6 +90,17 @@ fn volatile_key_store() -> VolatileSecretKeyStore {
     VolatileSecretKeyStore::new()
 }
 
+/// Returns two CSPs whose vaults use `csprng`: one with an in-process vault
+/// and one with a vault that is reached over a Unix domain socket.
+fn csps_with_local_and_remote_vault<R: Rng + CryptoRng + Clone + Send + Sync + 'static>(
+    csprng: R,
+) -> Vec<Csp<R, VolatileSecretKeyStore, VolatileSecretKeyStore>> {
+    vec![
+        Csp::of(csprng.clone(), volatile_key_store()),
+        Csp::of_with_remote_vault(csprng, volatile_key_store()),
+    ]
+}
+
 mod multi {
     use super::*;
     use ic_crypto_internal_multi_sig_bls12381::types::{PopBytes, PublicKeyBytes};
@@ -125,12 +135,12 @@ mod multi {
     #[test]
     fn key_generation_is_stable() {
         let test_vector = test_vector_42();
-        let csprng = csprng_seeded_with(test_vector.seed);
-        let csp = Csp::of(csprng, volatile_key_store());
-        let (key_id, public_key) = csp.gen_key_pair(AlgorithmId::MultiBls12_381).unwrap();
+        for csp in csps_with_local_and_remote_vault(csprng_seeded_with(test_vector.seed)) {
+            let (key_id, public_key) = csp.gen_key_pair(AlgorithmId::MultiBls12_381).unwrap();
 
-        assert_eq!(key_id, test_vector.key_id);
-        assert_eq!(public_key, test_vector.public_key);
+            assert_eq!(key_id, test_vector.key_id);
+            assert_eq!(public_key, test_vector.public_key);
+        }
     }
 
     /// This test checks that the functionality is consistent; the values are
@@ -138,15 +148,15 @@ mod multi {
     #[test]
     fn key_generation_with_pop_is_stable() {
         let test_vector = test_vector_42();
-        let csprng = csprng_seeded_with(test_vector.seed);
-        let csp = Csp::of(csprng, volatile_key_store());
-        let (key_id, public_key, pop) = csp
-            .gen_key_pair_with_pop(AlgorithmId::MultiBls12_381)
-            .expect("Failed to generate key pair with PoP");
-
-        assert_eq!(key_id, test_vector.key_id);
-        assert_eq!(public_key, test_vector.public_key);
-        assert_eq!(pop, test_vector.proof_of_possession);
+        for csp in csps_with_local_and_remote_vault(csprng_seeded_with(test_vector.seed)) {
+            let (key_id, public_key, pop) = csp
+                .gen_key_pair_with_pop(AlgorithmId::MultiBls12_381)
+                .expect("Failed to generate key pair with PoP");
+
+            assert_eq!(key_id, test_vector.key_id);
+            assert_eq!(public_key, test_vector.public_key);
+            assert_eq!(pop, test_vector.proof_of_possession);
+        }
     }
 }
 
@@ -196,14 +206,14 @@ mod tls {
 
     #[test]
     fn should_return_der_encoded_self_signed_certificate() {
-        let mut csp = Csp::of(rng(), volatile_key_store());
-
-        let cert = csp.gen_tls_key_pair(node_test_id(NODE_1), NOT_AFTER);
+        for mut csp in csps_with_local_and_remote_vault(rng()) {
+            let cert = csp.gen_tls_key_pair(node_test_id(NODE_1), NOT_AFTER);
 
-        let x509_cert = cert.as_x509();
-        let public_key = x509_cert.public_key().unwrap();
-        assert_eq!(x509_cert.verify(&public_key).ok(), Some(true));
-        assert_eq!(x509_cert.issued(x509_cert), X509VerifyResult::OK);
+            let x509_cert = cert.as_x509();
+            let public_key = x509_cert.public_key().unwrap();
+            assert_eq!(x509_cert.verify(&public_key).ok(), Some(true));
+            assert_eq!(x509_cert.issued(x509_cert), X509VerifyResult::OK);
+        }
     }
 
     #[test]
@@ -257,14 +267,14 @@ mod tls {
 
     #[test]
     fn should_set_random_cert_serial_number() {
-        let mut csp = Csp::of(csprng_seeded_with(FIXED_SEED), volatile_key_store());
-
-        let cert = csp.gen_tls_key_pair(node_test_id(NODE_1), NOT_AFTER);
+        for mut csp in csps_with_local_and_remote_vault(csprng_seeded_with(FIXED_SEED)) {
+            let cert = csp.gen_tls_key_pair(node_test_id(NODE_1), NOT_AFTER);
 
-        let cert_serial = cert.as_x509().serial_number().to_bn().unwrap();
-        let expected_randomness = csprng_seeded_with(FIXED_SEED).gen::<[u8; 19]>();
-        let expected_serial = BigNum::from_slice(&expected_randomness).unwrap();
-        assert_eq!(expected_serial, cert_serial);
+            let cert_serial = cert.as_x509().serial_number().to_bn().unwrap();
+            let expected_randomness = csprng_seeded_with(FIXED_SEED).gen::<[u8; 19]>();
+            let expected_serial = BigNum::from_slice(&expected_randomness).unwrap();
+            assert_eq!(expected_serial, cert_serial);
+        }
     }
 
     #[test]
@@ -282,12 +292,13 @@ mod tls {
 
     #[test]
     fn should_set_cert_not_after_correctly() {
-        let mut csp = Csp::of(rng(), volatile_key_store());
-        let not_after = NOT_AFTER;
+        for mut csp in csps_with_local_and_remote_vault(rng()) {
+            let not_after = NOT_AFTER;
 
-        let cert = csp.gen_tls_key_pair(node_test_id(NODE_1), not_after);
+            let cert = csp.gen_tls_key_pair(node_test_id(NODE_1), not_after);
 
-        assert!(cert.as_x509().not_after() == Asn1Time::from_str_x509(not_after).unwrap());
+            assert!(cert.as_x509().not_after() == Asn1Time::from_str_x509(not_after).unwrap());
+        }
     }
 
     #[test]
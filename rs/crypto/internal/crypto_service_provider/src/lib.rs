@@ -15,8 +15,9 @@ pub mod threshold;
 pub mod tls_stub;
 pub mod types;
 
-pub use crate::server::api::TlsHandshakeCspServer;
+pub use crate::server::api::{CspVault, TlsHandshakeCspServer};
 pub use crate::server::local_csp_server::LocalCspServer;
+pub use crate::server::remote_csp_vault::{CspVaultServer, RemoteCspVault};
 
 use crate::api::{
     CspKeyGenerator, CspSecretKeyStoreChecker, CspSigner, CspTlsClientHandshake,
@@ -24,23 +25,30 @@ use crate::api::{
     ThresholdSignatureCspClient,
 };
 use crate::keygen::{forward_secure_key_id, public_key_hash_as_key_id};
-use crate::public_key_store::read_node_public_keys;
+use crate::public_key_store::{
+    node_public_keys_modification_time, previous_node_public_keys_modification_time,
+    read_node_public_keys, read_previous_node_public_keys,
+};
+use crate::secret_key_store::sealing::key_encryption_key_provider;
 use crate::secret_key_store::volatile_store::VolatileSecretKeyStore;
 use crate::secret_key_store::SecretKeyStore;
 use crate::types::CspPublicKey;
-use ic_config::crypto::CryptoConfig;
+use ic_config::crypto::{CryptoConfig, CspVaultType};
 use ic_crypto_internal_logmon::metrics::CryptoMetrics;
 use ic_crypto_internal_types::encrypt::forward_secure::CspFsEncryptionPublicKey;
 use ic_logger::{new_logger, replica_logger::no_op_logger, ReplicaLogger};
 use ic_protobuf::crypto::v1::NodePublicKeys;
 use ic_types::crypto::KeyId;
-use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+#[cfg(test)]
+use parking_lot::RwLockReadGuard;
+use parking_lot::{RwLock, RwLockWriteGuard};
 use rand::rngs::OsRng;
 use rand::{CryptoRng, Rng};
 use secret_key_store::proto_store::ProtoSecretKeyStore;
 use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Instant, SystemTime};
 
 const SKS_DATA_FILENAME: &str = "sks_data.pb";
 const CANISTER_SKS_DATA_FILENAME: &str = "canister_sks_data.pb";
@@ -76,14 +84,59 @@ impl<T> CryptoServiceProvider for T where
 struct SksKeyIds {
     node_signing_key_id: Option<KeyId>,
     dkg_dealing_encryption_key_id: Option<KeyId>,
+    // The id of the DKG dealing encryption key that was replaced by a key
+    // rotation whose new keys are not registered yet. Dealings may still be
+    // encrypted for this key until the registry contains the new key.
+    previous_dkg_dealing_encryption_key_id: Option<KeyId>,
 }
 
 struct PublicKeyData {
     node_public_keys: NodePublicKeys,
     sks_key_ids: SksKeyIds,
+    // The modification times of the public key store files at the time the
+    // data was read from them, if it was.
+    modification_times: Option<PublicKeyStoreModificationTimes>,
+}
+
+/// The modification times of the files holding the current and the previous
+/// node public keys, which change whenever the keys are rotated.
+type PublicKeyStoreModificationTimes = (Option<SystemTime>, Option<SystemTime>);
+
+fn public_key_store_modification_times(crypto_root: &Path) -> PublicKeyStoreModificationTimes {
+    (
+        node_public_keys_modification_time(crypto_root).ok(),
+        previous_node_public_keys_modification_time(crypto_root)
+            .ok()
+            .flatten(),
+    )
 }
 
 impl PublicKeyData {
+    /// Reads the current and, if a key rotation is pending, the previous
+    /// node public keys from the public key store at `crypto_root`.
+    ///
+    /// Returns empty public key data if there are no node public keys.
+    fn read_from(crypto_root: &Path) -> Self {
+        Self::try_read_from(crypto_root)
+            .unwrap_or_else(|| PublicKeyData::new(NodePublicKeys::default()))
+    }
+
+    fn try_read_from(crypto_root: &Path) -> Option<Self> {
+        // The modification times are determined before the files are read,
+        // so that a concurrent modification triggers another read.
+        let modification_times = public_key_store_modification_times(crypto_root);
+        let node_public_keys = read_node_public_keys(crypto_root).ok()?;
+        let mut public_key_data = PublicKeyData::new(node_public_keys);
+        public_key_data.modification_times = Some(modification_times);
+        if let Ok(Some(previous_node_public_keys)) = read_previous_node_public_keys(crypto_root) {
+            public_key_data
+                .sks_key_ids
+                .previous_dkg_dealing_encryption_key_id =
+                dkg_dealing_encryption_key_id(&previous_node_public_keys);
+        }
+        Some(public_key_data)
+    }
+
     fn new(node_public_keys: NodePublicKeys) -> Self {
         let node_signing_key_id = match node_public_keys.node_signing_pk.to_owned() {
             None => None,
@@ -94,35 +147,53 @@ impl PublicKeyData {
             }
         };
 
-        let dkg_dealing_encryption_key_id = match node_public_keys
-            .dkg_dealing_encryption_pk
-            .to_owned()
-        {
-            None => None,
-            Some(dkg_dealing_encryption_pk) => {
-                let csp_pk = CspFsEncryptionPublicKey::try_from(dkg_dealing_encryption_pk)
-                    .expect("Unsupported public key proto as dkg dealing encryption public key.");
-                Some(forward_secure_key_id(&csp_pk))
-            }
-        };
+        let dkg_dealing_encryption_key_id = dkg_dealing_encryption_key_id(&node_public_keys);
         let sks_key_ids = SksKeyIds {
             node_signing_key_id,
             dkg_dealing_encryption_key_id,
+            previous_dkg_dealing_encryption_key_id: None,
         };
         PublicKeyData {
             node_public_keys,
             sks_key_ids,
+            modification_times: None,
         }
     }
 }
 
+fn dkg_dealing_encryption_key_id(node_public_keys: &NodePublicKeys) -> Option<KeyId> {
+    node_public_keys
+        .dkg_dealing_encryption_pk
+        .to_owned()
+        .map(|dkg_dealing_encryption_pk| {
+            let csp_pk = CspFsEncryptionPublicKey::try_from(dkg_dealing_encryption_pk)
+                .expect("Unsupported public key proto as dkg dealing encryption public key.");
+            forward_secure_key_id(&csp_pk)
+        })
+}
+
 /// Implements the CryptoServiceProvider for an RNG and a SecretKeyStore.
+///
+/// All operations involving secret keys are delegated to a `CspVault`, which
+/// either runs in-process (`LocalCspServer`) or in a separate process that is
+/// reached over a Unix domain socket (`RemoteCspVault`).
 pub struct Csp<R: Rng + CryptoRng, S: SecretKeyStore, C: SecretKeyStore> {
-    // CSPRNG stands for cryptographically secure random number generator.
-    csprng: CspRwLock<R>,
-    csp_server: Arc<LocalCspServer<R, S, C>>,
-    public_key_data: PublicKeyData,
+    csp_vault: Arc<dyn CspVault>,
+    tls_handshake_signer: Arc<dyn TlsHandshakeCspServer>,
+    // Only present if the vault runs in-process, i.e., if the secret key
+    // store is directly accessible by this process.
+    local_csp_server: Option<Arc<LocalCspServer<R, S, C>>>,
+    public_key_data: RwLock<PublicKeyData>,
+    // If present, the public key data is re-read from the public key store in
+    // this directory whenever the node public keys or the DKG dealing
+    // encryption keys are used, so that keys rotated by another process are
+    // picked up.
+    crypto_root: Option<PathBuf>,
     logger: ReplicaLogger,
+    // Holds the socket of the vault served by `of_with_remote_vault`, which
+    // is removed when the directory is dropped.
+    #[cfg(test)]
+    remote_vault_socket_dir: Option<tempfile::TempDir>,
 }
 
 /// This lock provides the option to add metrics about lock acquisition times.
@@ -178,20 +249,80 @@ impl<T> CspRwLock<T> {
     }
 }
 
+impl<
+        R: Rng + CryptoRng + Send + Sync + 'static,
+        S: SecretKeyStore + 'static,
+        C: SecretKeyStore + 'static,
+    > Csp<R, S, C>
+{
+    fn new_with_local_csp_server(
+        local_csp_server: LocalCspServer<R, S, C>,
+        public_key_data: PublicKeyData,
+        crypto_root: Option<PathBuf>,
+        logger: ReplicaLogger,
+    ) -> Self {
+        let local_csp_server = Arc::new(local_csp_server);
+        Csp {
+            csp_vault: Arc::clone(&local_csp_server) as Arc<dyn CspVault>,
+            tls_handshake_signer: Arc::clone(&local_csp_server) as Arc<dyn TlsHandshakeCspServer>,
+            local_csp_server: Some(local_csp_server),
+            public_key_data: RwLock::new(public_key_data),
+            crypto_root,
+            logger,
+            #[cfg(test)]
+            remote_vault_socket_dir: None,
+        }
+    }
+}
+
 impl<R: Rng + CryptoRng, S: SecretKeyStore, C: SecretKeyStore> Csp<R, S, C> {
-    fn rng_write_lock(&self) -> RwLockWriteGuard<'_, R> {
-        // TODO (CRP-696): inline this method
-        self.csprng.write()
+    /// Returns the ids of the DKG dealing encryption keys for which dealings
+    /// may be encrypted: the current key and, while a key rotation is pending,
+    /// the key it replaced.
+    fn dkg_dealing_encryption_key_ids(&self) -> Vec<KeyId> {
+        self.refresh_public_key_data();
+        let public_key_data = self.public_key_data.read();
+        let sks_key_ids = &public_key_data.sks_key_ids;
+        let current_key_id = sks_key_ids
+            .dkg_dealing_encryption_key_id
+            .to_owned()
+            .expect("Missing dkg dealing encryption key id");
+        let mut key_ids = vec![current_key_id];
+        if let Some(previous_key_id) = sks_key_ids.previous_dkg_dealing_encryption_key_id {
+            if previous_key_id != current_key_id {
+                key_ids.push(previous_key_id);
+            }
+        }
+        key_ids
     }
 
-    fn sks_write_lock(&self) -> RwLockWriteGuard<'_, S> {
-        // TODO (CRP-696): inline this method
-        self.csp_server.sks_write_lock()
+    /// Re-reads the public key data from the public key store, if this CSP
+    /// was created for a crypto root, so that keys rotated by another process
+    /// are picked up.
+    ///
+    /// The files are only read if they were modified since they were last
+    /// read, so that an unchanged store costs a metadata lookup only.
+    fn refresh_public_key_data(&self) {
+        let crypto_root = match &self.crypto_root {
+            Some(crypto_root) => crypto_root,
+            None => return,
+        };
+        let modification_times = public_key_store_modification_times(crypto_root);
+        if self.public_key_data.read().modification_times == Some(modification_times) {
+            return;
+        }
+        if let Some(public_key_data) = PublicKeyData::try_read_from(crypto_root) {
+            *self.public_key_data.write() = public_key_data;
+        }
     }
 
+    #[cfg(test)]
     fn sks_read_lock(&self) -> RwLockReadGuard<'_, S> {
         // TODO (CRP-696): inline this method
-        self.csp_server.sks_read_lock()
+        self.local_csp_server
+            .as_ref()
+            .expect("secret key store is only accessible with an in-replica CSP vault")
+            .sks_read_lock()
     }
 }
 
@@ -203,56 +334,65 @@ impl Csp<OsRng, ProtoSecretKeyStore, ProtoSecretKeyStore> {
         metrics: Arc<CryptoMetrics>,
     ) -> Self {
         let logger = logger.unwrap_or_else(no_op_logger);
-        let secret_key_store = ProtoSecretKeyStore::open(
-            &config.crypto_root,
-            SKS_DATA_FILENAME,
-            Some(new_logger!(&logger)),
-        );
-        let canister_key_store = ProtoSecretKeyStore::open(
+        let public_key_data = PublicKeyData::read_from(&config.crypto_root);
+        let crypto_root = Some(config.crypto_root.clone());
+
+        if let CspVaultType::UnixSocket(socket_path) = &config.csp_vault_type {
+            let remote_csp_vault = Arc::new(RemoteCspVault::new(socket_path));
+            return Csp {
+                csp_vault: Arc::clone(&remote_csp_vault) as Arc<dyn CspVault>,
+                tls_handshake_signer: remote_csp_vault as Arc<dyn TlsHandshakeCspServer>,
+                local_csp_server: None,
+                public_key_data: RwLock::new(public_key_data),
+                crypto_root,
+                logger,
+                #[cfg(test)]
+                remote_vault_socket_dir: None,
+            };
+        }
+
+        let kek_provider = config
+            .sks_key_encryption_key
+            .as_ref()
+            .map(key_encryption_key_provider);
+        let local_csp_server = LocalCspServer::new_in_dir(
             &config.crypto_root,
-            CANISTER_SKS_DATA_FILENAME,
-            Some(new_logger!(&logger)),
+            kek_provider,
+            metrics,
+            new_logger!(&logger),
         );
-        let node_public_keys = match read_node_public_keys(&config.crypto_root) {
-            Ok(node_pks) => node_pks,
-            Err(_) => Default::default(),
-        };
-        let public_key_data = PublicKeyData::new(node_public_keys);
-
-        Csp {
-            csprng: CspRwLock::new_for_rng(OsRng::default(), Arc::clone(&metrics)),
-            public_key_data,
-            csp_server: Arc::new(LocalCspServer::new(
-                secret_key_store,
-                canister_key_store,
-                metrics,
-                new_logger!(&logger),
-            )),
-            logger,
-        }
+        Csp::new_with_local_csp_server(local_csp_server, public_key_data, crypto_root, logger)
     }
 }
 
-impl<R: Rng + CryptoRng + Clone> Csp<R, ProtoSecretKeyStore, VolatileSecretKeyStore> {
+impl<R: Rng + CryptoRng + Send + Sync + 'static>
+    Csp<R, ProtoSecretKeyStore, VolatileSecretKeyStore>
+{
     /// Creates a crypto service provider for testing.
     ///
     /// Note: This MUST NOT be used in production as the secrecy of the random
     /// number generator, hence the keys, is not guaranteed.
+    ///
+    /// If the `config` specifies a key-encryption key, the secret key store is
+    /// opened sealed with that key.
     pub fn new_with_rng(csprng: R, config: &CryptoConfig) -> Self {
-        let node_public_keys = match read_node_public_keys(&config.crypto_root) {
-            Ok(node_pks) => node_pks,
-            Err(_) => Default::default(),
+        let public_key_data = PublicKeyData::read_from(&config.crypto_root);
+        let secret_key_store = match &config.sks_key_encryption_key {
+            Some(kek_source) => ProtoSecretKeyStore::open_sealed(
+                &config.crypto_root,
+                SKS_DATA_FILENAME,
+                key_encryption_key_provider(kek_source),
+                None,
+            ),
+            None => ProtoSecretKeyStore::open(&config.crypto_root, SKS_DATA_FILENAME, None),
         };
-        let public_key_data = PublicKeyData::new(node_public_keys);
-        Csp {
-            csprng: CspRwLock::new_for_rng(csprng.clone(), Arc::new(CryptoMetrics::none())),
+        let local_csp_server = LocalCspServer::new_for_test(csprng, secret_key_store);
+        Csp::new_with_local_csp_server(
+            local_csp_server,
             public_key_data,
-            csp_server: Arc::new(LocalCspServer::new_for_test(
-                csprng,
-                ProtoSecretKeyStore::open(&config.crypto_root, SKS_DATA_FILENAME, None),
-            )),
-            logger: no_op_logger(),
-        }
+            Some(config.crypto_root.clone()),
+            no_op_logger(),
+        )
     }
 }
 
@@ -261,17 +401,19 @@ impl<R: Rng + CryptoRng> Csp<R, VolatileSecretKeyStore, VolatileSecretKeyStore>
     ///
     /// Note: This is for testing only and MUST NOT be used in production.
     pub fn reset_public_key_data(&mut self, node_public_keys: NodePublicKeys) {
-        self.public_key_data = PublicKeyData::new(node_public_keys);
+        *self.public_key_data.get_mut() = PublicKeyData::new(node_public_keys);
     }
 }
 
 impl<R: Rng + CryptoRng, S: SecretKeyStore, C: SecretKeyStore> NodePublicKeyData for Csp<R, S, C> {
     fn node_public_keys(&self) -> NodePublicKeys {
-        self.public_key_data.node_public_keys.clone()
+        self.refresh_public_key_data();
+        self.public_key_data.read().node_public_keys.clone()
     }
 
     fn node_signing_key_id(&self) -> KeyId {
         self.public_key_data
+            .read()
             .sks_key_ids
             .node_signing_key_id
             .to_owned()
@@ -280,6 +422,7 @@ impl<R: Rng + CryptoRng, S: SecretKeyStore, C: SecretKeyStore> NodePublicKeyData
 
     fn dkg_dealing_encryption_key_id(&self) -> KeyId {
         self.public_key_data
+            .read()
             .sks_key_ids
             .dkg_dealing_encryption_key_id
             .to_owned()
@@ -287,7 +430,9 @@ impl<R: Rng + CryptoRng, S: SecretKeyStore, C: SecretKeyStore> NodePublicKeyData
     }
 }
 
-impl<R: Rng + CryptoRng + Clone, S: SecretKeyStore> Csp<R, S, VolatileSecretKeyStore> {
+impl<R: Rng + CryptoRng + Send + Sync + 'static, S: SecretKeyStore + 'static>
+    Csp<R, S, VolatileSecretKeyStore>
+{
     /// Creates a crypto service provider for testing.
     ///
     /// Note: This MUST NOT be used in production as the secrecy of the secret
@@ -295,12 +440,32 @@ impl<R: Rng + CryptoRng + Clone, S: SecretKeyStore> Csp<R, S, VolatileSecretKeyS
     pub fn of(csprng: R, secret_key_store: S) -> Self {
         let node_public_keys = Default::default();
         let public_key_data = PublicKeyData::new(node_public_keys);
-        let metrics = Arc::new(CryptoMetrics::none());
+        let local_csp_server = LocalCspServer::new_for_test(csprng, secret_key_store);
+        Csp::new_with_local_csp_server(local_csp_server, public_key_data, None, no_op_logger())
+    }
+
+    /// Like `of`, but the secret keys are held by a vault that is served on a
+    /// Unix domain socket and reached via a `RemoteCspVault`, so that tests
+    /// can be run against both kinds of vault.
+    #[cfg(test)]
+    pub fn of_with_remote_vault(csprng: R, secret_key_store: S) -> Self {
+        let local_csp_server = LocalCspServer::new_for_test(csprng, secret_key_store);
+        let socket_dir =
+            ic_crypto_internal_csp_test_utils::files::mk_temp_dir_with_permissions(0o700);
+        let socket_path = socket_dir.path().join("csp_vault.sock");
+        let current_uid = nix::unistd::getuid().as_raw();
+        let server = CspVaultServer::bind(&socket_path, current_uid, no_op_logger())
+            .expect("failed to bind CSP vault server");
+        std::thread::spawn(move || server.run(Arc::new(local_csp_server)));
+        let remote_csp_vault = Arc::new(RemoteCspVault::new(&socket_path));
         Csp {
-            csprng: CspRwLock::new_for_rng(csprng.clone(), metrics),
-            public_key_data,
-            csp_server: Arc::new(LocalCspServer::new_for_test(csprng, secret_key_store)),
+            csp_vault: Arc::clone(&remote_csp_vault) as Arc<dyn CspVault>,
+            tls_handshake_signer: remote_csp_vault as Arc<dyn TlsHandshakeCspServer>,
+            local_csp_server: None,
+            public_key_data: RwLock::new(PublicKeyData::new(Default::default())),
+            crypto_root: None,
             logger: no_op_logger(),
+            remote_vault_socket_dir: Some(socket_dir),
         }
     }
 }
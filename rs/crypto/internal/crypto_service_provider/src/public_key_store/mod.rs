@@ -1,11 +1,14 @@
 //! Interfaces for saving and retrieving public keys
 use prost::Message;
 use std::fs;
+use std::io;
 use std::path::Path;
+use std::time::SystemTime;
 
 use ic_protobuf::crypto::v1::NodePublicKeys;
 
 const PK_DATA_FILENAME: &str = "public_keys.pb";
+const PREVIOUS_PK_DATA_FILENAME: &str = "previous_public_keys.pb";
 
 /// Error while reading or writing public keys
 #[derive(Clone, Debug)]
@@ -35,3 +38,70 @@ pub fn read_node_public_keys(crypto_root: &Path) -> Result<NodePublicKeys, Publi
         Err(err) => Err(PublicKeyStoreError::IOError(err.to_string())),
     }
 }
+
+/// Returns the time at which the node public keys were last written to local
+/// storage, i.e., when they were generated or last rotated.
+pub fn node_public_keys_modification_time(
+    crypto_root: &Path,
+) -> Result<SystemTime, PublicKeyStoreError> {
+    let pk_file = crypto_root.join(PK_DATA_FILENAME);
+    fs::metadata(pk_file)
+        .and_then(|metadata| metadata.modified())
+        .map_err(|err| PublicKeyStoreError::IOError(err.to_string()))
+}
+
+/// Write the node public keys that were replaced by a key rotation to local
+/// storage.
+///
+/// The previous keys are kept until the rotated keys are registered, so that
+/// the secret keys that are no longer needed can be identified and deleted.
+pub fn store_previous_node_public_keys(
+    crypto_root: &Path,
+    node_pks: &NodePublicKeys,
+) -> Result<(), PublicKeyStoreError> {
+    let pk_file = crypto_root.join(PREVIOUS_PK_DATA_FILENAME);
+
+    ic_utils::fs::write_protobuf_using_tmp_file(pk_file, node_pks)
+        .map_err(|err| PublicKeyStoreError::IOError(err.to_string()))
+}
+
+/// Read the node public keys that were replaced by a key rotation from local
+/// storage.
+///
+/// Returns `Ok(None)` if no key rotation is pending.
+pub fn read_previous_node_public_keys(
+    crypto_root: &Path,
+) -> Result<Option<NodePublicKeys>, PublicKeyStoreError> {
+    let pk_file = crypto_root.join(PREVIOUS_PK_DATA_FILENAME);
+    match fs::read(pk_file) {
+        Ok(data) => NodePublicKeys::decode(&*data)
+            .map(Some)
+            .map_err(|err| PublicKeyStoreError::ParsingError(err.to_string())),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(PublicKeyStoreError::IOError(err.to_string())),
+    }
+}
+
+/// Returns the time at which the node public keys that were replaced by a key
+/// rotation were stored, or `Ok(None)` if no key rotation is pending.
+pub fn previous_node_public_keys_modification_time(
+    crypto_root: &Path,
+) -> Result<Option<SystemTime>, PublicKeyStoreError> {
+    let pk_file = crypto_root.join(PREVIOUS_PK_DATA_FILENAME);
+    match fs::metadata(pk_file).and_then(|metadata| metadata.modified()) {
+        Ok(modification_time) => Ok(Some(modification_time)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(PublicKeyStoreError::IOError(err.to_string())),
+    }
+}
+
+/// Remove the node public keys that were replaced by a key rotation from local
+/// storage. Does nothing if no key rotation is pending.
+pub fn remove_previous_node_public_keys(crypto_root: &Path) -> Result<(), PublicKeyStoreError> {
+    let pk_file = crypto_root.join(PREVIOUS_PK_DATA_FILENAME);
+    match fs::remove_file(pk_file) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(PublicKeyStoreError::IOError(err.to_string())),
+    }
+}
@@ -8,6 +8,7 @@ use std::fmt;
 
 // Implementations
 pub mod proto_store;
+pub mod sealing;
 pub mod volatile_store;
 
 #[cfg(test)]
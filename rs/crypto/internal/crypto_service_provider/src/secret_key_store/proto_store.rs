@@ -1,5 +1,6 @@
 //! Filesystem-backed secret key store
 #![allow(clippy::unwrap_used)]
+use crate::secret_key_store::sealing::{self, KeyEncryptionKeyProvider};
 use crate::secret_key_store::{Scope, SecretKeyStore, SecretKeyStoreError};
 use crate::threshold::ni_dkg::{NIDKG_FS_SCOPE, NIDKG_THRESHOLD_SCOPE};
 use crate::types::CspSecretKey;
@@ -18,8 +19,12 @@ use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::Arc;
+use zeroize::Zeroize;
 
 const CURRENT_SKS_VERSION: u32 = 2;
+// A sealed store holds a store of the current version, encrypted with a
+// key-encryption key.
+const SEALED_SKS_VERSION: u32 = 3;
 
 // TODO(CRP-523): turn this to FromStr-trait once KeyId is not public.
 const KEY_ID_PREFIX: &str = "KeyId(0x";
@@ -58,25 +63,71 @@ type SecretKeys = HashMap<KeyId, (CspSecretKey, Option<Scope>)>;
 
 /// A secret key store that persists data to the filesystem, using protobufs for
 /// serialization
+///
+/// If the store is sealed, the data is encrypted with a key-encryption key
+/// before it is written to the filesystem.
 pub struct ProtoSecretKeyStore {
     proto_file: PathBuf,
     keys: Arc<RwLock<SecretKeys>>,
+    kek_provider: Option<Arc<dyn KeyEncryptionKeyProvider>>,
     logger: ReplicaLogger,
 }
 
 impl ProtoSecretKeyStore {
     /// Creates a database instance.
+    ///
+    /// # Panics
+    /// If the data on disk is sealed.
     pub fn open(dir: &Path, file_name: &str, logger: Option<ReplicaLogger>) -> Self {
+        Self::open_internal(dir, file_name, None, logger)
+    }
+
+    /// Creates a database instance that is sealed with the key-encryption key
+    /// provided by `kek_provider`.
+    ///
+    /// Unsealed data on disk is sealed right away.
+    ///
+    /// # Panics
+    /// If the key-encryption key is unavailable, or if the data on disk cannot
+    /// be unsealed with it.
+    pub fn open_sealed(
+        dir: &Path,
+        file_name: &str,
+        kek_provider: Arc<dyn KeyEncryptionKeyProvider>,
+        logger: Option<ReplicaLogger>,
+    ) -> Self {
+        Self::open_internal(dir, file_name, Some(kek_provider), logger)
+    }
+
+    fn open_internal(
+        dir: &Path,
+        file_name: &str,
+        kek_provider: Option<Arc<dyn KeyEncryptionKeyProvider>>,
+        logger: Option<ReplicaLogger>,
+    ) -> Self {
         Self::check_path(dir);
         let proto_file = dir.join(file_name);
-        let secret_keys = match Self::read_sks_data_from_disk(&proto_file) {
-            Some(sks_proto) => sks_proto,
+        let logger = logger.unwrap_or_else(no_op_logger);
+        let secret_keys = match Self::read_sks_data_from_disk(&proto_file, &kek_provider) {
+            Some((secret_keys, version)) => {
+                if kek_provider.is_some() && version != SEALED_SKS_VERSION {
+                    info!(
+                        logger,
+                        "Sealing secret key store {} of version {}",
+                        proto_file.display(),
+                        version
+                    );
+                    Self::write_secret_keys_to_disk(&proto_file, &secret_keys, &kek_provider);
+                }
+                secret_keys
+            }
             None => SecretKeys::new(),
         };
         ProtoSecretKeyStore {
             proto_file,
             keys: Arc::new(RwLock::new(secret_keys)),
-            logger: logger.unwrap_or_else(no_op_logger),
+            kek_provider,
+            logger,
         }
     }
 
@@ -85,12 +136,17 @@ impl ProtoSecretKeyStore {
         self.proto_file.as_path()
     }
 
-    fn read_sks_data_from_disk(sks_data_file: &Path) -> Option<SecretKeys> {
+    /// Returns the secret keys together with the version of the data on disk.
+    fn read_sks_data_from_disk(
+        sks_data_file: &Path,
+        kek_provider: &Option<Arc<dyn KeyEncryptionKeyProvider>>,
+    ) -> Option<(SecretKeys, u32)> {
         match fs::read(sks_data_file) {
             Ok(data) => {
                 let sks_pb = pb::SecretKeyStore::decode(&*data).expect("error parsing SKS data");
-                let keys = ProtoSecretKeyStore::migrate_to_current_version(sks_pb);
-                Some(keys)
+                let version = sks_pb.version;
+                let keys = ProtoSecretKeyStore::migrate_to_current_version(sks_pb, kek_provider);
+                Some((keys, version))
             }
             Err(err) => {
                 if err.kind() == ErrorKind::NotFound {
@@ -104,8 +160,19 @@ impl ProtoSecretKeyStore {
 
     // TODO(CRP-532): remove support for the legacy format in a few weeks after
     // merging.
-    fn migrate_to_current_version(sks_proto: pb::SecretKeyStore) -> SecretKeys {
+    fn migrate_to_current_version(
+        sks_proto: pb::SecretKeyStore,
+        kek_provider: &Option<Arc<dyn KeyEncryptionKeyProvider>>,
+    ) -> SecretKeys {
         match sks_proto.version {
+            SEALED_SKS_VERSION => {
+                let kek_provider = kek_provider.as_ref().unwrap_or_else(|| {
+                    panic!("The SKS data is sealed, but no key-encryption key is configured")
+                });
+                let unsealed_sks_proto =
+                    ProtoSecretKeyStore::unseal_sks_proto(&sks_proto, kek_provider.as_ref());
+                ProtoSecretKeyStore::sks_proto_to_secret_keys(&unsealed_sks_proto)
+            }
             CURRENT_SKS_VERSION => ProtoSecretKeyStore::sks_proto_to_secret_keys(&sks_proto),
             0 => {
                 let mut secret_keys = SecretKeys::new();
@@ -220,8 +287,53 @@ impl ProtoSecretKeyStore {
         sks_proto
     }
 
-    fn write_secret_keys_to_disk(sks_data_file: &Path, secret_keys: &SecretKeys) {
-        let sks_proto = ProtoSecretKeyStore::secret_keys_to_sks_proto(secret_keys);
+    fn seal_sks_proto(
+        sks_proto: &pb::SecretKeyStore,
+        kek_provider: &dyn KeyEncryptionKeyProvider,
+    ) -> pb::SecretKeyStore {
+        let kek = kek_provider
+            .key_encryption_key()
+            .unwrap_or_else(|e| panic!("Error sealing SKS data: {}", e));
+        let mut sks_bytes = Vec::new();
+        sks_proto
+            .encode(&mut sks_bytes)
+            .expect("Protobuf serialization of SKS data failed");
+        let sealed_secret_keys = sealing::seal(&kek, &sks_bytes);
+        sks_bytes.zeroize();
+        pb::SecretKeyStore {
+            version: SEALED_SKS_VERSION,
+            sealed_secret_keys: Some(sealed_secret_keys),
+            ..Default::default()
+        }
+    }
+
+    fn unseal_sks_proto(
+        sks_proto: &pb::SecretKeyStore,
+        kek_provider: &dyn KeyEncryptionKeyProvider,
+    ) -> pb::SecretKeyStore {
+        let sealed_secret_keys = sks_proto
+            .sealed_secret_keys
+            .as_ref()
+            .expect("Sealed SKS data lacks the sealed secret keys");
+        let kek = kek_provider
+            .key_encryption_key()
+            .unwrap_or_else(|e| panic!("Error unsealing SKS data: {}", e));
+        let mut sks_bytes = sealing::unseal(&kek, sealed_secret_keys)
+            .unwrap_or_else(|e| panic!("Error unsealing SKS data: {}", e));
+        let unsealed_sks_proto = pb::SecretKeyStore::decode(&*sks_bytes);
+        sks_bytes.zeroize();
+        unsealed_sks_proto.expect("error parsing unsealed SKS data")
+    }
+
+    fn write_secret_keys_to_disk(
+        sks_data_file: &Path,
+        secret_keys: &SecretKeys,
+        kek_provider: &Option<Arc<dyn KeyEncryptionKeyProvider>>,
+    ) {
+        let mut sks_proto = ProtoSecretKeyStore::secret_keys_to_sks_proto(secret_keys);
+        if let Some(kek_provider) = kek_provider {
+            sks_proto = ProtoSecretKeyStore::seal_sks_proto(&sks_proto, kek_provider.as_ref());
+        }
         ic_utils::fs::write_protobuf_using_tmp_file(sks_data_file, &sks_proto).unwrap();
     }
 
@@ -271,7 +383,11 @@ impl SecretKeyStore for ProtoSecretKeyStore {
             Some(_) => Err(SecretKeyStoreError::DuplicateKeyId(id)),
             None => {
                 keys.insert(id, (key, scope));
-                ProtoSecretKeyStore::write_secret_keys_to_disk(&self.proto_file, keys);
+                ProtoSecretKeyStore::write_secret_keys_to_disk(
+                    &self.proto_file,
+                    keys,
+                    &self.kek_provider,
+                );
                 Ok(())
             }
         })
@@ -291,7 +407,11 @@ impl SecretKeyStore for ProtoSecretKeyStore {
         let result = with_write_lock(&self.keys, |keys| match keys.get(id) {
             Some(_) => {
                 keys.remove(id);
-                ProtoSecretKeyStore::write_secret_keys_to_disk(&self.proto_file, keys);
+                ProtoSecretKeyStore::write_secret_keys_to_disk(
+                    &self.proto_file,
+                    keys,
+                    &self.kek_provider,
+                );
                 Ok(true)
             }
             None => Ok(false),
@@ -318,7 +438,11 @@ impl SecretKeyStore for ProtoSecretKeyStore {
                 }
             }
             if keys.len() < orig_keys_count {
-                ProtoSecretKeyStore::write_secret_keys_to_disk(&self.proto_file, keys);
+                ProtoSecretKeyStore::write_secret_keys_to_disk(
+                    &self.proto_file,
+                    keys,
+                    &self.kek_provider,
+                );
             }
             Ok(())
         })
@@ -420,4 +544,104 @@ pub mod tests {
     fn proto_key_store() -> TempSecretKeyStore {
         TempSecretKeyStore::new()
     }
+
+    mod sealed_store {
+        use super::*;
+        use crate::secret_key_store::sealing::LocalKeyEncryptionKeyProvider;
+        use crate::secret_key_store::test_utils::{make_key_id, make_secret_key};
+        use rand::SeedableRng;
+        use rand_chacha::ChaCha20Rng;
+
+        const SKS_FILE: &str = "sks_data.pb";
+
+        fn kek_provider(seed: u64) -> Arc<dyn KeyEncryptionKeyProvider> {
+            Arc::new(LocalKeyEncryptionKeyProvider::new_random(
+                &mut ChaCha20Rng::seed_from_u64(seed),
+            ))
+        }
+
+        fn sks_proto_on_disk(dir: &Path) -> pb::SecretKeyStore {
+            let data = fs::read(dir.join(SKS_FILE)).unwrap();
+            pb::SecretKeyStore::decode(&*data).unwrap()
+        }
+
+        #[test]
+        fn should_retrieve_key_after_reopening_sealed_store() {
+            let dir = mk_temp_dir_with_permissions(0o700);
+            let kek_provider = kek_provider(42);
+            let mut store =
+                ProtoSecretKeyStore::open_sealed(dir.path(), SKS_FILE, kek_provider.clone(), None);
+            store
+                .insert(make_key_id(1), make_secret_key(2), None)
+                .unwrap();
+
+            let store = ProtoSecretKeyStore::open_sealed(dir.path(), SKS_FILE, kek_provider, None);
+
+            assert_eq!(store.get(&make_key_id(1)), Some(make_secret_key(2)));
+        }
+
+        #[test]
+        fn should_not_store_keys_in_plaintext_when_sealed() {
+            let dir = mk_temp_dir_with_permissions(0o700);
+            let mut store =
+                ProtoSecretKeyStore::open_sealed(dir.path(), SKS_FILE, kek_provider(42), None);
+            store
+                .insert(make_key_id(1), make_secret_key(2), None)
+                .unwrap();
+
+            let sks_proto = sks_proto_on_disk(dir.path());
+            assert_eq!(sks_proto.version, SEALED_SKS_VERSION);
+            assert!(sks_proto.key_id_to_secret_key_v1.is_empty());
+            assert!(sks_proto.sealed_secret_keys.is_some());
+        }
+
+        #[test]
+        fn should_seal_plaintext_store_when_opened_sealed() {
+            let dir = mk_temp_dir_with_permissions(0o700);
+            let mut store = ProtoSecretKeyStore::open(dir.path(), SKS_FILE, None);
+            store
+                .insert(make_key_id(1), make_secret_key(2), None)
+                .unwrap();
+            assert_eq!(sks_proto_on_disk(dir.path()).version, CURRENT_SKS_VERSION);
+
+            let kek_provider = kek_provider(42);
+            let store =
+                ProtoSecretKeyStore::open_sealed(dir.path(), SKS_FILE, kek_provider.clone(), None);
+
+            assert_eq!(store.get(&make_key_id(1)), Some(make_secret_key(2)));
+            assert_eq!(sks_proto_on_disk(dir.path()).version, SEALED_SKS_VERSION);
+            let reopened_store =
+                ProtoSecretKeyStore::open_sealed(dir.path(), SKS_FILE, kek_provider, None);
+            assert_eq!(
+                reopened_store.get(&make_key_id(1)),
+                Some(make_secret_key(2))
+            );
+        }
+
+        #[test]
+        #[should_panic(expected = "Error unsealing SKS data")]
+        fn should_panic_when_opening_sealed_store_with_other_kek() {
+            let dir = mk_temp_dir_with_permissions(0o700);
+            let mut store =
+                ProtoSecretKeyStore::open_sealed(dir.path(), SKS_FILE, kek_provider(42), None);
+            store
+                .insert(make_key_id(1), make_secret_key(2), None)
+                .unwrap();
+
+            ProtoSecretKeyStore::open_sealed(dir.path(), SKS_FILE, kek_provider(43), None);
+        }
+
+        #[test]
+        #[should_panic(expected = "no key-encryption key is configured")]
+        fn should_panic_when_opening_sealed_store_without_kek() {
+            let dir = mk_temp_dir_with_permissions(0o700);
+            let mut store =
+                ProtoSecretKeyStore::open_sealed(dir.path(), SKS_FILE, kek_provider(42), None);
+            store
+                .insert(make_key_id(1), make_secret_key(2), None)
+                .unwrap();
+
+            ProtoSecretKeyStore::open(dir.path(), SKS_FILE, None);
+        }
+    }
 }
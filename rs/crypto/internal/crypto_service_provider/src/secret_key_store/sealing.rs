@@ -0,0 +1,374 @@
+//! Sealing of the secret key store at rest with a key-encryption key (KEK)
+//!
+//! The KEK is obtained from a `KeyEncryptionKeyProvider`, so that it can be
+//! kept separately from the node's disk, e.g., in a file on a different
+//! volume, in the environment of the process, or in a hardware-backed key
+//! store. The secret keys are sealed with AES-256-GCM, i.e., with
+//! authenticated encryption, so that a tampered store is detected when it is
+//! opened.
+use crate::secret_key_store::proto_store::pb;
+use ic_config::crypto::KeyEncryptionKeySource;
+use ic_crypto_secrets_containers::SecretArray;
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+use rand::{CryptoRng, Rng};
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use zeroize::Zeroize;
+
+const KEK_SIZE: usize = 32;
+const NONCE_SIZE: usize = 12;
+const TAG_SIZE: usize = 16;
+
+// Binds the ciphertext to the format of sealed secret key stores.
+const SEALED_SKS_AAD: &[u8] = b"ic-crypto-sealed-secret-key-store-v3";
+
+/// A 256-bit key-encryption key.
+#[derive(Clone, Debug)]
+pub struct KeyEncryptionKey(SecretArray<KEK_SIZE>);
+
+impl KeyEncryptionKey {
+    /// Constructs a KEK from `key`, and clears the memory used by `key`.
+    pub fn new_and_zeroize_argument(key: &mut [u8; KEK_SIZE]) -> Self {
+        Self(SecretArray::new_and_zeroize_argument(key))
+    }
+
+    /// Parses a hex-encoded KEK, ignoring surrounding whitespace.
+    pub fn from_hex(key_hex: &str) -> Result<Self, KeyEncryptionKeyError> {
+        let mut bytes = hex::decode(key_hex.trim()).map_err(|e| {
+            KeyEncryptionKeyError::Malformed(format!("the KEK is not hex-encoded: {}", e))
+        })?;
+        let result = match <[u8; KEK_SIZE]>::try_from(&bytes[..]) {
+            Ok(mut key) => Ok(Self::new_and_zeroize_argument(&mut key)),
+            Err(_) => Err(KeyEncryptionKeyError::Malformed(format!(
+                "the KEK has {} bytes instead of {}",
+                bytes.len(),
+                KEK_SIZE
+            ))),
+        };
+        bytes.zeroize();
+        result
+    }
+
+    fn expose_secret(&self) -> &[u8; KEK_SIZE] {
+        self.0.expose_secret()
+    }
+}
+
+/// Provides the key-encryption key with which the secret key store is sealed.
+///
+/// The KEK is requested whenever the store is read from or written to disk,
+/// so that implementations need not keep it in memory.
+pub trait KeyEncryptionKeyProvider: Send + Sync {
+    /// Returns the key-encryption key.
+    fn key_encryption_key(&self) -> Result<KeyEncryptionKey, KeyEncryptionKeyError>;
+}
+
+/// Errors that occur when obtaining a key-encryption key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KeyEncryptionKeyError {
+    /// The KEK could not be obtained from its source.
+    Unavailable(String),
+    /// The KEK obtained from its source is malformed.
+    Malformed(String),
+}
+
+impl fmt::Display for KeyEncryptionKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyEncryptionKeyError::Unavailable(msg) => {
+                write!(f, "Key-encryption key unavailable: {}", msg)
+            }
+            KeyEncryptionKeyError::Malformed(msg) => {
+                write!(f, "Malformed key-encryption key: {}", msg)
+            }
+        }
+    }
+}
+
+/// Returns the provider for the KEK from `source`.
+pub fn key_encryption_key_provider(
+    source: &KeyEncryptionKeySource,
+) -> Arc<dyn KeyEncryptionKeyProvider> {
+    match source {
+        KeyEncryptionKeySource::File(path) => Arc::new(FileKeyEncryptionKeyProvider::new(path)),
+        KeyEncryptionKeySource::EnvironmentVariable(name) => {
+            Arc::new(EnvironmentKeyEncryptionKeyProvider::new(name))
+        }
+    }
+}
+
+/// Reads a hex-encoded KEK from a file that must not be accessible by others.
+pub struct FileKeyEncryptionKeyProvider {
+    path: PathBuf,
+}
+
+impl FileKeyEncryptionKeyProvider {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl KeyEncryptionKeyProvider for FileKeyEncryptionKeyProvider {
+    fn key_encryption_key(&self) -> Result<KeyEncryptionKey, KeyEncryptionKeyError> {
+        let unavailable = |e: std::io::Error| {
+            KeyEncryptionKeyError::Unavailable(format!(
+                "cannot read {}: {}",
+                self.path.display(),
+                e
+            ))
+        };
+        let permissions = fs::metadata(&self.path).map_err(unavailable)?.permissions();
+        if permissions.mode() & 0o77 != 0 {
+            return Err(KeyEncryptionKeyError::Unavailable(format!(
+                "{} has permissions {:#o}, allowing access by others",
+                self.path.display(),
+                permissions.mode()
+            )));
+        }
+        let mut key_hex = fs::read_to_string(&self.path).map_err(unavailable)?;
+        let result = KeyEncryptionKey::from_hex(&key_hex);
+        key_hex.zeroize();
+        result
+    }
+}
+
+/// Reads a hex-encoded KEK from an environment variable.
+pub struct EnvironmentKeyEncryptionKeyProvider {
+    variable: String,
+}
+
+impl EnvironmentKeyEncryptionKeyProvider {
+    pub fn new<S: Into<String>>(variable: S) -> Self {
+        Self {
+            variable: variable.into(),
+        }
+    }
+}
+
+impl KeyEncryptionKeyProvider for EnvironmentKeyEncryptionKeyProvider {
+    fn key_encryption_key(&self) -> Result<KeyEncryptionKey, KeyEncryptionKeyError> {
+        let mut key_hex = std::env::var(&self.variable).map_err(|e| {
+            KeyEncryptionKeyError::Unavailable(format!(
+                "cannot read environment variable {}: {}",
+                self.variable, e
+            ))
+        })?;
+        let result = KeyEncryptionKey::from_hex(&key_hex);
+        key_hex.zeroize();
+        result
+    }
+}
+
+/// Holds a KEK in memory.
+///
+/// Note: This is intended for testing. A store sealed with a randomly
+/// generated KEK cannot be opened anymore once the provider is dropped.
+#[derive(Clone)]
+pub struct LocalKeyEncryptionKeyProvider {
+    key: KeyEncryptionKey,
+}
+
+impl LocalKeyEncryptionKeyProvider {
+    pub fn new(key: KeyEncryptionKey) -> Self {
+        Self { key }
+    }
+
+    /// Creates a provider for a KEK generated using `csprng`.
+    pub fn new_random<R: Rng + CryptoRng>(csprng: &mut R) -> Self {
+        let mut key = [0u8; KEK_SIZE];
+        csprng.fill_bytes(&mut key);
+        Self::new(KeyEncryptionKey::new_and_zeroize_argument(&mut key))
+    }
+}
+
+impl KeyEncryptionKeyProvider for LocalKeyEncryptionKeyProvider {
+    fn key_encryption_key(&self) -> Result<KeyEncryptionKey, KeyEncryptionKeyError> {
+        Ok(self.key.clone())
+    }
+}
+
+/// Encrypts `plaintext` with `kek` using a fresh random nonce.
+///
+/// # Panics
+/// If the encryption fails or no randomness is available.
+pub(crate) fn seal(kek: &KeyEncryptionKey, plaintext: &[u8]) -> pb::SealedSecretKeys {
+    let mut nonce = vec![0u8; NONCE_SIZE];
+    openssl::rand::rand_bytes(&mut nonce).expect("Failed to generate a nonce");
+    let mut tag = vec![0u8; TAG_SIZE];
+    let ciphertext = encrypt_aead(
+        Cipher::aes_256_gcm(),
+        kek.expose_secret(),
+        Some(&nonce),
+        SEALED_SKS_AAD,
+        plaintext,
+        &mut tag,
+    )
+    .expect("Failed to seal the secret key store");
+    pb::SealedSecretKeys {
+        nonce,
+        ciphertext,
+        tag,
+    }
+}
+
+/// Decrypts `sealed` with `kek`.
+///
+/// Returns an error if `sealed` is malformed, was sealed with a different
+/// KEK, or was tampered with.
+pub(crate) fn unseal(
+    kek: &KeyEncryptionKey,
+    sealed: &pb::SealedSecretKeys,
+) -> Result<Vec<u8>, String> {
+    if sealed.nonce.len() != NONCE_SIZE || sealed.tag.len() != TAG_SIZE {
+        return Err("malformed nonce or authentication tag".to_string());
+    }
+    decrypt_aead(
+        Cipher::aes_256_gcm(),
+        kek.expose_secret(),
+        Some(&sealed.nonce),
+        SEALED_SKS_AAD,
+        &sealed.ciphertext,
+        &sealed.tag,
+    )
+    .map_err(|_| "decryption failed: wrong key-encryption key or corrupted data".to_string())
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use ic_crypto_internal_csp_test_utils::files::mk_temp_dir_with_permissions;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    const KEK_HEX: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+    fn random_kek(seed: u64) -> KeyEncryptionKey {
+        LocalKeyEncryptionKeyProvider::new_random(&mut ChaCha20Rng::seed_from_u64(seed))
+            .key_encryption_key()
+            .unwrap()
+    }
+
+    #[test]
+    fn should_unseal_sealed_data() {
+        let kek = random_kek(42);
+        let sealed = seal(&kek, b"secret keys");
+
+        assert_eq!(unseal(&kek, &sealed).unwrap(), b"secret keys".to_vec());
+    }
+
+    #[test]
+    fn should_not_contain_plaintext_in_sealed_data() {
+        let sealed = seal(&random_kek(42), b"secret keys");
+
+        assert_ne!(sealed.ciphertext, b"secret keys".to_vec());
+    }
+
+    #[test]
+    fn should_use_fresh_nonce_for_every_sealing() {
+        let kek = random_kek(42);
+
+        assert_ne!(
+            seal(&kek, b"secret keys").nonce,
+            seal(&kek, b"secret keys").nonce
+        );
+    }
+
+    #[test]
+    fn should_fail_to_unseal_with_other_kek() {
+        let sealed = seal(&random_kek(42), b"secret keys");
+
+        assert!(unseal(&random_kek(43), &sealed).is_err());
+    }
+
+    #[test]
+    fn should_fail_to_unseal_tampered_data() {
+        let kek = random_kek(42);
+        let mut sealed = seal(&kek, b"secret keys");
+        sealed.ciphertext[0] ^= 1;
+
+        assert!(unseal(&kek, &sealed).is_err());
+    }
+
+    #[test]
+    fn should_fail_to_unseal_with_malformed_tag() {
+        let kek = random_kek(42);
+        let mut sealed = seal(&kek, b"secret keys");
+        sealed.tag.pop();
+
+        assert!(unseal(&kek, &sealed).is_err());
+    }
+
+    #[test]
+    fn should_parse_hex_encoded_kek_with_whitespace() {
+        let kek = KeyEncryptionKey::from_hex(&format!("  {}\n", KEK_HEX)).unwrap();
+
+        assert_eq!(kek.expose_secret()[31], 0x1f);
+    }
+
+    #[test]
+    fn should_reject_kek_of_wrong_size() {
+        assert!(matches!(
+            KeyEncryptionKey::from_hex("0001"),
+            Err(KeyEncryptionKeyError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn should_read_kek_from_file() {
+        let dir = mk_temp_dir_with_permissions(0o700);
+        let path = dir.path().join("kek");
+        fs::write(&path, KEK_HEX).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+
+        let kek = FileKeyEncryptionKeyProvider::new(&path)
+            .key_encryption_key()
+            .unwrap();
+
+        assert_eq!(
+            kek.expose_secret(),
+            KeyEncryptionKey::from_hex(KEK_HEX).unwrap().expose_secret()
+        );
+    }
+
+    #[test]
+    fn should_reject_kek_file_readable_by_others() {
+        let dir = mk_temp_dir_with_permissions(0o700);
+        let path = dir.path().join("kek");
+        fs::write(&path, KEK_HEX).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        assert!(matches!(
+            FileKeyEncryptionKeyProvider::new(&path).key_encryption_key(),
+            Err(KeyEncryptionKeyError::Unavailable(_))
+        ));
+    }
+
+    #[test]
+    fn should_read_kek_from_environment_variable() {
+        let variable = "IC_CRYPTO_SEALING_TEST_KEK";
+        std::env::set_var(variable, KEK_HEX);
+
+        let kek = EnvironmentKeyEncryptionKeyProvider::new(variable)
+            .key_encryption_key()
+            .unwrap();
+
+        assert_eq!(
+            kek.expose_secret(),
+            KeyEncryptionKey::from_hex(KEK_HEX).unwrap().expose_secret()
+        );
+    }
+
+    #[test]
+    fn should_report_missing_environment_variable() {
+        assert!(matches!(
+            EnvironmentKeyEncryptionKeyProvider::new("IC_CRYPTO_SEALING_TEST_UNSET_KEK")
+                .key_encryption_key(),
+            Err(KeyEncryptionKeyError::Unavailable(_))
+        ));
+    }
+}
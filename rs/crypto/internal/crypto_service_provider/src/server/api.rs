@@ -30,11 +30,15 @@ pub enum CspBasicSignatureError {
     MalformedSecretKey {
         algorithm: AlgorithmId,
     },
+    TransientInternalError {
+        internal_error: String,
+    },
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum CspBasicSignatureKeygenError {
     UnsupportedAlgorithm { algorithm: AlgorithmId },
+    TransientInternalError { internal_error: String },
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -52,6 +56,9 @@ pub enum CspMultiSignatureError {
     InternalError {
         internal_error: String,
     },
+    TransientInternalError {
+        internal_error: String,
+    },
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -64,12 +71,16 @@ pub enum CspMultiSignatureKeygenError {
         key_bytes: Option<Vec<u8>>,
         internal_error: String,
     },
+    TransientInternalError {
+        internal_error: String,
+    },
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum CspThresholdSignatureKeygenError {
     UnsupportedAlgorithm { algorithm: AlgorithmId },
     InvalidArgument { message: String },
+    TransientInternalError { internal_error: String },
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -78,6 +89,7 @@ pub enum CspTlsSignError {
     WrongSecretKeyType { algorithm: AlgorithmId },
     MalformedSecretKey { error: String },
     SigningFailed { error: String },
+    TransientInternalError { internal_error: String },
 }
 
 /// `CspServer` offers a selection of operations that involve
@@ -91,6 +103,23 @@ pub trait CspServer:
 {
 }
 
+impl<T> CspServer for T where
+    T: BasicSignatureCspServer
+        + MultiSignatureCspServer
+        + ThresholdSignatureCspServer
+        + NiDkgCspServer
+        + SecretKeyStoreCspServer
+{
+}
+
+/// All operations that involve secret keys, i.e. everything a CSP vault
+/// offers: a `CspServer` that also handles TLS handshake signing, and that
+/// may run in-process (`LocalCspServer`) or in a separate process
+/// (`RemoteCspVault`).
+pub trait CspVault: CspServer + TlsHandshakeCspServer {}
+
+impl<T> CspVault for T where T: CspServer + TlsHandshakeCspServer {}
+
 /// Operations of `CspServer` related to basic signatures
 /// (cf. `CspSigner` and `CspKeyGenerator`).
 pub trait BasicSignatureCspServer {
@@ -326,6 +355,14 @@ pub trait SecretKeyStoreCspServer {
     /// `key_id`. # Arguments
     /// * `key_id` identifies the key whose presence should be checked.
     fn sks_contains(&self, key_id: &KeyId) -> bool;
+
+    /// Removes the key with the given `key_id` from the secret key store.
+    ///
+    /// Returns `true` if the key was present.
+    ///
+    /// # Arguments
+    /// * `key_id` identifies the key that should be removed.
+    fn sks_remove(&self, key_id: &KeyId) -> bool;
 }
 
 /// Operations of `CspServer` related to TLS handshakes.
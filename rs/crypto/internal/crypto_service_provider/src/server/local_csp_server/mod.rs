@@ -8,17 +8,19 @@ mod threshold_sig;
 mod tls;
 
 use crate::secret_key_store::proto_store::ProtoSecretKeyStore;
+use crate::secret_key_store::sealing::KeyEncryptionKeyProvider;
 use crate::secret_key_store::volatile_store::VolatileSecretKeyStore;
 use crate::secret_key_store::{SecretKeyStore, SecretKeyStoreError};
 use crate::types::CspSecretKey;
-use crate::CspRwLock;
+use crate::{CspRwLock, CANISTER_SKS_DATA_FILENAME, SKS_DATA_FILENAME};
 use ic_crypto_internal_logmon::metrics::CryptoMetrics;
 use ic_logger::replica_logger::no_op_logger;
-use ic_logger::ReplicaLogger;
+use ic_logger::{new_logger, ReplicaLogger};
 use ic_types::crypto::KeyId;
 use parking_lot::{RwLockReadGuard, RwLockWriteGuard};
 use rand::rngs::OsRng;
 use rand::{CryptoRng, Rng};
+use std::path::Path;
 use std::sync::Arc;
 
 /// An implementation of `CspServer`-trait that runs in-process
@@ -53,6 +55,36 @@ impl LocalCspServer<OsRng, ProtoSecretKeyStore, ProtoSecretKeyStore> {
             logger,
         )
     }
+
+    /// Creates a production-grade local CSP server whose secret key stores
+    /// are kept in the directory `crypto_root`.
+    ///
+    /// If a `kek_provider` is given, the secret key stores are sealed with
+    /// the key-encryption key it provides.
+    pub fn new_in_dir(
+        crypto_root: &Path,
+        kek_provider: Option<Arc<dyn KeyEncryptionKeyProvider>>,
+        metrics: Arc<CryptoMetrics>,
+        logger: ReplicaLogger,
+    ) -> Self {
+        let open = |file_name| match &kek_provider {
+            Some(kek_provider) => ProtoSecretKeyStore::open_sealed(
+                crypto_root,
+                file_name,
+                Arc::clone(kek_provider),
+                Some(new_logger!(&logger)),
+            ),
+            None => ProtoSecretKeyStore::open(crypto_root, file_name, Some(new_logger!(&logger))),
+        };
+        let node_secret_key_store = open(SKS_DATA_FILENAME);
+        let canister_secret_key_store = open(CANISTER_SKS_DATA_FILENAME);
+        LocalCspServer::new(
+            node_secret_key_store,
+            canister_secret_key_store,
+            metrics,
+            logger,
+        )
+    }
 }
 
 impl<S: SecretKeyStore, C: SecretKeyStore> LocalCspServer<OsRng, S, C> {
@@ -15,4 +15,8 @@ impl<R: Rng + CryptoRng, S: SecretKeyStore, C: SecretKeyStore> SecretKeyStoreCsp
     fn sks_contains(&self, id: &KeyId) -> bool {
         self.sks_read_lock().contains(id)
     }
+
+    fn sks_remove(&self, id: &KeyId) -> bool {
+        self.sks_write_lock().remove(id)
+    }
 }
@@ -61,3 +61,28 @@ fn key_should_be_present_only_after_generation() {
         "Key first CSP should not contain the keys of the second."
     );
 }
+
+#[test]
+fn key_should_be_absent_after_removal() {
+    let csp_server = {
+        let key_store = TempSecretKeyStore::new();
+        let csprng = ChaChaRng::from_seed(thread_rng().gen::<[u8; 32]>());
+        LocalCspServer::new_for_test(csprng, key_store)
+    };
+    let (key_id, _public_key) = csp_server
+        .gen_key_pair(AlgorithmId::Ed25519)
+        .expect("Test setup failed: Failed to generate keys");
+
+    assert!(
+        csp_server.sks_remove(&key_id),
+        "Key should have been present."
+    );
+    assert!(
+        !csp_server.sks_contains(&key_id),
+        "Key should be absent after removal."
+    );
+    assert!(
+        !csp_server.sks_remove(&key_id),
+        "Key should not be removed twice."
+    );
+}
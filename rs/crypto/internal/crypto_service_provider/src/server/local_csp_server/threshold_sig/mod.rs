@@ -46,6 +46,9 @@ impl From<CspThresholdSignatureKeygenError> for CryptoError {
             CspThresholdSignatureKeygenError::InvalidArgument { message } => {
                 CryptoError::InvalidArgument { message }
             }
+            CspThresholdSignatureKeygenError::TransientInternalError { internal_error } => {
+                CryptoError::TransientInternalError { internal_error }
+            }
         }
     }
 }
@@ -6,6 +6,7 @@ use ic_types::crypto::CryptoError;
 
 pub mod api;
 pub mod local_csp_server;
+pub mod remote_csp_vault;
 
 impl From<CspBasicSignatureError> for CryptoError {
     fn from(e: CspBasicSignatureError) -> CryptoError {
@@ -30,6 +31,9 @@ impl From<CspBasicSignatureError> for CryptoError {
                     internal_error: "Malformed secret key".to_string(),
                 }
             }
+            CspBasicSignatureError::TransientInternalError { internal_error } => {
+                CryptoError::TransientInternalError { internal_error }
+            }
         }
     }
 }
@@ -43,6 +47,9 @@ impl From<CspBasicSignatureKeygenError> for CryptoError {
                     reason: "Unsupported algorithm".to_string(),
                 }
             }
+            CspBasicSignatureKeygenError::TransientInternalError { internal_error } => {
+                CryptoError::TransientInternalError { internal_error }
+            }
         }
     }
 }
@@ -69,6 +76,9 @@ impl From<CspMultiSignatureError> for CryptoError {
                     message: internal_error,
                 }
             }
+            CspMultiSignatureError::TransientInternalError { internal_error } => {
+                CryptoError::TransientInternalError { internal_error }
+            }
         }
     }
 }
@@ -91,6 +101,9 @@ impl From<CspMultiSignatureKeygenError> for CryptoError {
                 key_bytes,
                 internal_error,
             },
+            CspMultiSignatureKeygenError::TransientInternalError { internal_error } => {
+                CryptoError::TransientInternalError { internal_error }
+            }
         }
     }
 }
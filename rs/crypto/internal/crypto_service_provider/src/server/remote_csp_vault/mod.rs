@@ -0,0 +1,549 @@
+//! A `CspVault` that runs in a separate process and is reached over a Unix
+//! domain socket.
+//!
+//! The replica uses a `RemoteCspVault` as client, and the vault process runs a
+//! `CspVaultServer` that serves the requests using a `LocalCspServer`, so that
+//! secret keys never leave the vault process. Each request and each response
+//! is sent as a CBOR-encoded message prefixed by its length as a big-endian
+//! `u32`.
+use crate::api::CspThresholdSignError;
+use crate::server::api::{
+    BasicSignatureCspServer, CspBasicSignatureError, CspBasicSignatureKeygenError,
+    CspMultiSignatureError, CspMultiSignatureKeygenError, CspThresholdSignatureKeygenError,
+    CspTlsSignError, MultiSignatureCspServer, NiDkgCspServer, SecretKeyStoreCspServer,
+    ThresholdSignatureCspServer, TlsHandshakeCspServer,
+};
+#[cfg(test)]
+use crate::types::CspPublicCoefficients;
+use crate::types::{CspPop, CspPublicKey, CspSignature};
+use ic_crypto_internal_threshold_sig_bls12381::api::ni_dkg_errors;
+use ic_crypto_internal_types::encrypt::forward_secure::{
+    CspFsEncryptionPop, CspFsEncryptionPublicKey,
+};
+use ic_crypto_internal_types::sign::threshold_sig::ni_dkg::{
+    CspNiDkgDealing, CspNiDkgTranscript, Epoch,
+};
+use ic_crypto_tls_interfaces::TlsPublicKeyCert;
+use ic_types::crypto::{AlgorithmId, KeyId};
+use ic_types::{NodeId, NodeIndex, NumberOfNodes};
+use parking_lot::Mutex;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+
+mod server;
+#[cfg(test)]
+mod tests;
+
+pub use server::CspVaultServer;
+
+/// Upper bound on the size of a single message, to protect both ends against
+/// allocating arbitrary amounts of memory for a malformed length prefix.
+const MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+
+/// A request to the CSP vault, one for each method of `CspVault`.
+#[derive(Serialize, Deserialize)]
+enum CspVaultRequest {
+    BasicSign {
+        algorithm_id: AlgorithmId,
+        message: Vec<u8>,
+        key_id: KeyId,
+    },
+    GenKeyPair {
+        algorithm_id: AlgorithmId,
+    },
+    MultiSign {
+        algorithm_id: AlgorithmId,
+        message: Vec<u8>,
+        key_id: KeyId,
+    },
+    GenKeyPairWithPop {
+        algorithm_id: AlgorithmId,
+    },
+    #[cfg(test)]
+    ThresholdKeygenForTest {
+        algorithm_id: AlgorithmId,
+        threshold: NumberOfNodes,
+        signatory_eligibility: Vec<bool>,
+    },
+    ThresholdSign {
+        algorithm_id: AlgorithmId,
+        message: Vec<u8>,
+        key_id: KeyId,
+    },
+    GenForwardSecureKeyPair {
+        node_id: NodeId,
+        algorithm_id: AlgorithmId,
+    },
+    UpdateForwardSecureEpoch {
+        algorithm_id: AlgorithmId,
+        key_id: KeyId,
+        epoch: Epoch,
+    },
+    CreateDealing {
+        algorithm_id: AlgorithmId,
+        dealer_index: NodeIndex,
+        threshold: NumberOfNodes,
+        epoch: Epoch,
+        receiver_keys: BTreeMap<NodeIndex, CspFsEncryptionPublicKey>,
+        maybe_resharing_secret: Option<KeyId>,
+    },
+    LoadThresholdSigningKey {
+        algorithm_id: AlgorithmId,
+        epoch: Epoch,
+        csp_transcript: CspNiDkgTranscript,
+        fs_key_id: KeyId,
+        receiver_index: NodeIndex,
+    },
+    RetainThresholdKeysIfPresent {
+        active_key_ids: BTreeSet<KeyId>,
+    },
+    SksContains {
+        key_id: KeyId,
+    },
+    SksRemove {
+        key_id: KeyId,
+    },
+    GenTlsKeyPair {
+        node_id: NodeId,
+        not_after: String,
+    },
+    TlsSign {
+        message: Vec<u8>,
+        key_id: KeyId,
+    },
+}
+
+impl CspVaultRequest {
+    /// Whether the request leaves the state of the vault unchanged, so that
+    /// it can safely be sent again if it is unknown whether the vault
+    /// received it.
+    fn is_read_only(&self) -> bool {
+        match self {
+            CspVaultRequest::BasicSign { .. }
+            | CspVaultRequest::MultiSign { .. }
+            | CspVaultRequest::ThresholdSign { .. }
+            | CspVaultRequest::CreateDealing { .. }
+            | CspVaultRequest::SksContains { .. }
+            | CspVaultRequest::TlsSign { .. } => true,
+            CspVaultRequest::GenKeyPair { .. }
+            | CspVaultRequest::GenKeyPairWithPop { .. }
+            | CspVaultRequest::GenForwardSecureKeyPair { .. }
+            | CspVaultRequest::UpdateForwardSecureEpoch { .. }
+            | CspVaultRequest::LoadThresholdSigningKey { .. }
+            | CspVaultRequest::RetainThresholdKeysIfPresent { .. }
+            | CspVaultRequest::SksRemove { .. }
+            | CspVaultRequest::GenTlsKeyPair { .. } => false,
+            #[cfg(test)]
+            CspVaultRequest::ThresholdKeygenForTest { .. } => false,
+        }
+    }
+}
+
+/// The response of the CSP vault to a `CspVaultRequest` of the same name.
+#[derive(Serialize, Deserialize)]
+enum CspVaultResponse {
+    BasicSign(Result<CspSignature, CspBasicSignatureError>),
+    GenKeyPair(Result<(KeyId, CspPublicKey), CspBasicSignatureKeygenError>),
+    MultiSign(Result<CspSignature, CspMultiSignatureError>),
+    GenKeyPairWithPop(Result<(KeyId, CspPublicKey, CspPop), CspMultiSignatureKeygenError>),
+    #[cfg(test)]
+    ThresholdKeygenForTest(
+        Result<(CspPublicCoefficients, Vec<Option<KeyId>>), CspThresholdSignatureKeygenError>,
+    ),
+    ThresholdSign(Result<CspSignature, CspThresholdSignError>),
+    GenForwardSecureKeyPair(
+        Result<
+            (CspFsEncryptionPublicKey, CspFsEncryptionPop),
+            ni_dkg_errors::CspDkgCreateFsKeyError,
+        >,
+    ),
+    UpdateForwardSecureEpoch(Result<(), ni_dkg_errors::CspDkgUpdateFsEpochError>),
+    CreateDealing(Result<CspNiDkgDealing, ni_dkg_errors::CspDkgCreateReshareDealingError>),
+    LoadThresholdSigningKey(Result<(), ni_dkg_errors::CspDkgLoadPrivateKeyError>),
+    RetainThresholdKeysIfPresent,
+    SksContains(bool),
+    SksRemove(bool),
+    /// The key ID and the DER encoding of the certificate.
+    GenTlsKeyPair((KeyId, Vec<u8>)),
+    TlsSign(Result<CspSignature, CspTlsSignError>),
+    /// The vault panicked while handling the request, e.g., because of an
+    /// invalid argument. The client re-raises the panic with the same
+    /// message.
+    Panic(String),
+}
+
+/// A `CspVault` that forwards all operations to a vault process listening on
+/// a Unix domain socket.
+///
+/// If the vault cannot be reached, methods that return a `Result` return a
+/// `TransientInternalError`. The remaining methods (`sks_contains`,
+/// `sks_remove`, `retain_threshold_keys_if_present` and `gen_tls_key_pair`)
+/// have no way to report errors and panic instead.
+pub struct RemoteCspVault {
+    socket_path: PathBuf,
+    idle_connections: Mutex<Vec<UnixStream>>,
+}
+
+impl RemoteCspVault {
+    /// Creates a client for the vault listening on `socket_path`.
+    ///
+    /// Connections are established lazily, so the vault need not be running
+    /// yet when this is called.
+    pub fn new(socket_path: &Path) -> Self {
+        RemoteCspVault {
+            socket_path: socket_path.to_path_buf(),
+            idle_connections: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Sends `request` to the vault and returns its response.
+    ///
+    /// Connections are reused across requests. If a reused connection fails
+    /// (e.g., because the vault was restarted), a read-only request is
+    /// retried once on a fresh connection. Other requests are not retried,
+    /// since the vault may already have executed them.
+    ///
+    /// Returns an error describing the failure if the vault cannot be
+    /// reached.
+    ///
+    /// # Panics
+    /// * if the vault panicked while handling the request.
+    fn call(&self, request: CspVaultRequest) -> Result<CspVaultResponse, String> {
+        let idle_connection = self.idle_connections.lock().pop();
+        let result = match idle_connection {
+            Some(mut stream) => match exchange(&mut stream, &request) {
+                Ok(response) => Ok((stream, response)),
+                Err(_) if request.is_read_only() => self.call_on_new_connection(&request),
+                Err(e) => Err(e),
+            },
+            None => self.call_on_new_connection(&request),
+        };
+        match result {
+            Ok((stream, CspVaultResponse::Panic(message))) => {
+                self.idle_connections.lock().push(stream);
+                panic!("{}", message)
+            }
+            Ok((stream, response)) => {
+                self.idle_connections.lock().push(stream);
+                Ok(response)
+            }
+            Err(e) => Err(format!(
+                "Failed to communicate with the CSP vault at {}: {}",
+                self.socket_path.display(),
+                e
+            )),
+        }
+    }
+
+    /// Like `call`, for methods that cannot report errors.
+    ///
+    /// # Panics
+    /// * if the vault cannot be reached, or
+    /// * if the vault panicked while handling the request.
+    fn call_or_panic(&self, request: CspVaultRequest) -> CspVaultResponse {
+        self.call(request).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn call_on_new_connection(
+        &self,
+        request: &CspVaultRequest,
+    ) -> io::Result<(UnixStream, CspVaultResponse)> {
+        let mut stream = UnixStream::connect(&self.socket_path)?;
+        let response = exchange(&mut stream, request)?;
+        Ok((stream, response))
+    }
+}
+
+fn exchange(stream: &mut UnixStream, request: &CspVaultRequest) -> io::Result<CspVaultResponse> {
+    write_message(stream, request)?;
+    read_message(stream)
+}
+
+/// Writes `message` to `stream`, prefixed by its length.
+fn write_message<T: Serialize, W: Write>(stream: &mut W, message: &T) -> io::Result<()> {
+    let bytes =
+        serde_cbor::to_vec(message).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    if bytes.len() > MAX_MESSAGE_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("message of {} bytes exceeds the maximum size", bytes.len()),
+        ));
+    }
+    let len = u32::try_from(bytes.len()).expect("message size is bounded by MAX_MESSAGE_SIZE");
+    stream.write_all(&len.to_be_bytes())?;
+    stream.write_all(&bytes)?;
+    stream.flush()
+}
+
+/// Reads a length-prefixed message from `stream`.
+fn read_message<T: DeserializeOwned, R: Read>(stream: &mut R) -> io::Result<T> {
+    let mut len_bytes = [0u8; 4];
+    stream.read_exact(&mut len_bytes)?;
+    let len = u32::from_be_bytes(len_bytes) as usize;
+    if len > MAX_MESSAGE_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("message of {} bytes exceeds the maximum size", len),
+        ));
+    }
+    let mut bytes = vec![0u8; len];
+    stream.read_exact(&mut bytes)?;
+    serde_cbor::from_slice(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn unexpected_response(method: &str) -> ! {
+    panic!("The CSP vault sent an unexpected response to {}", method)
+}
+
+impl BasicSignatureCspServer for RemoteCspVault {
+    fn sign(
+        &self,
+        algorithm_id: AlgorithmId,
+        message: &[u8],
+        key_id: KeyId,
+    ) -> Result<CspSignature, CspBasicSignatureError> {
+        match self.call(CspVaultRequest::BasicSign {
+            algorithm_id,
+            message: message.to_vec(),
+            key_id,
+        }) {
+            Ok(CspVaultResponse::BasicSign(result)) => result,
+            Err(internal_error) => {
+                Err(CspBasicSignatureError::TransientInternalError { internal_error })
+            }
+            Ok(_) => unexpected_response("sign"),
+        }
+    }
+
+    fn gen_key_pair(
+        &self,
+        algorithm_id: AlgorithmId,
+    ) -> Result<(KeyId, CspPublicKey), CspBasicSignatureKeygenError> {
+        match self.call(CspVaultRequest::GenKeyPair { algorithm_id }) {
+            Ok(CspVaultResponse::GenKeyPair(result)) => result,
+            Err(internal_error) => {
+                Err(CspBasicSignatureKeygenError::TransientInternalError { internal_error })
+            }
+            Ok(_) => unexpected_response("gen_key_pair"),
+        }
+    }
+}
+
+impl MultiSignatureCspServer for RemoteCspVault {
+    fn multi_sign(
+        &self,
+        algorithm_id: AlgorithmId,
+        message: &[u8],
+        key_id: KeyId,
+    ) -> Result<CspSignature, CspMultiSignatureError> {
+        match self.call(CspVaultRequest::MultiSign {
+            algorithm_id,
+            message: message.to_vec(),
+            key_id,
+        }) {
+            Ok(CspVaultResponse::MultiSign(result)) => result,
+            Err(internal_error) => {
+                Err(CspMultiSignatureError::TransientInternalError { internal_error })
+            }
+            Ok(_) => unexpected_response("multi_sign"),
+        }
+    }
+
+    fn gen_key_pair_with_pop(
+        &self,
+        algorithm_id: AlgorithmId,
+    ) -> Result<(KeyId, CspPublicKey, CspPop), CspMultiSignatureKeygenError> {
+        match self.call(CspVaultRequest::GenKeyPairWithPop { algorithm_id }) {
+            Ok(CspVaultResponse::GenKeyPairWithPop(result)) => result,
+            Err(internal_error) => {
+                Err(CspMultiSignatureKeygenError::TransientInternalError { internal_error })
+            }
+            Ok(_) => unexpected_response("gen_key_pair_with_pop"),
+        }
+    }
+}
+
+impl ThresholdSignatureCspServer for RemoteCspVault {
+    #[cfg(test)]
+    fn threshold_keygen_for_test(
+        &self,
+        algorithm_id: AlgorithmId,
+        threshold: NumberOfNodes,
+        signatory_eligibility: &[bool],
+    ) -> Result<(CspPublicCoefficients, Vec<Option<KeyId>>), CspThresholdSignatureKeygenError> {
+        match self.call(CspVaultRequest::ThresholdKeygenForTest {
+            algorithm_id,
+            threshold,
+            signatory_eligibility: signatory_eligibility.to_vec(),
+        }) {
+            Ok(CspVaultResponse::ThresholdKeygenForTest(result)) => result,
+            Err(internal_error) => {
+                Err(CspThresholdSignatureKeygenError::TransientInternalError { internal_error })
+            }
+            Ok(_) => unexpected_response("threshold_keygen_for_test"),
+        }
+    }
+
+    fn threshold_sign(
+        &self,
+        algorithm_id: AlgorithmId,
+        message: &[u8],
+        key_id: KeyId,
+    ) -> Result<CspSignature, CspThresholdSignError> {
+        match self.call(CspVaultRequest::ThresholdSign {
+            algorithm_id,
+            message: message.to_vec(),
+            key_id,
+        }) {
+            Ok(CspVaultResponse::ThresholdSign(result)) => result,
+            Err(internal_error) => {
+                Err(CspThresholdSignError::TransientInternalError { internal_error })
+            }
+            Ok(_) => unexpected_response("threshold_sign"),
+        }
+    }
+}
+
+impl NiDkgCspServer for RemoteCspVault {
+    fn gen_forward_secure_key_pair(
+        &self,
+        node_id: NodeId,
+        algorithm_id: AlgorithmId,
+    ) -> Result<(CspFsEncryptionPublicKey, CspFsEncryptionPop), ni_dkg_errors::CspDkgCreateFsKeyError>
+    {
+        match self.call(CspVaultRequest::GenForwardSecureKeyPair {
+            node_id,
+            algorithm_id,
+        }) {
+            Ok(CspVaultResponse::GenForwardSecureKeyPair(result)) => result,
+            Err(internal_error) => {
+                Err(ni_dkg_errors::CspDkgCreateFsKeyError::TransientInternalError(internal_error))
+            }
+            Ok(_) => unexpected_response("gen_forward_secure_key_pair"),
+        }
+    }
+
+    fn update_forward_secure_epoch(
+        &self,
+        algorithm_id: AlgorithmId,
+        key_id: KeyId,
+        epoch: Epoch,
+    ) -> Result<(), ni_dkg_errors::CspDkgUpdateFsEpochError> {
+        match self.call(CspVaultRequest::UpdateForwardSecureEpoch {
+            algorithm_id,
+            key_id,
+            epoch,
+        }) {
+            Ok(CspVaultResponse::UpdateForwardSecureEpoch(result)) => result,
+            Err(internal_error) => {
+                Err(ni_dkg_errors::CspDkgUpdateFsEpochError::TransientInternalError(internal_error))
+            }
+            Ok(_) => unexpected_response("update_forward_secure_epoch"),
+        }
+    }
+
+    fn create_dealing(
+        &self,
+        algorithm_id: AlgorithmId,
+        dealer_index: NodeIndex,
+        threshold: NumberOfNodes,
+        epoch: Epoch,
+        receiver_keys: &BTreeMap<NodeIndex, CspFsEncryptionPublicKey>,
+        maybe_resharing_secret: Option<KeyId>,
+    ) -> Result<CspNiDkgDealing, ni_dkg_errors::CspDkgCreateReshareDealingError> {
+        match self.call(CspVaultRequest::CreateDealing {
+            algorithm_id,
+            dealer_index,
+            threshold,
+            epoch,
+            receiver_keys: receiver_keys.clone(),
+            maybe_resharing_secret,
+        }) {
+            Ok(CspVaultResponse::CreateDealing(result)) => result,
+            Err(internal_error) => Err(
+                ni_dkg_errors::CspDkgCreateReshareDealingError::TransientInternalError(
+                    internal_error,
+                ),
+            ),
+            Ok(_) => unexpected_response("create_dealing"),
+        }
+    }
+
+    fn load_threshold_signing_key(
+        &self,
+        algorithm_id: AlgorithmId,
+        epoch: Epoch,
+        csp_transcript: CspNiDkgTranscript,
+        fs_key_id: KeyId,
+        receiver_index: NodeIndex,
+    ) -> Result<(), ni_dkg_errors::CspDkgLoadPrivateKeyError> {
+        match self.call(CspVaultRequest::LoadThresholdSigningKey {
+            algorithm_id,
+            epoch,
+            csp_transcript,
+            fs_key_id,
+            receiver_index,
+        }) {
+            Ok(CspVaultResponse::LoadThresholdSigningKey(result)) => result,
+            Err(internal_error) => Err(
+                ni_dkg_errors::CspDkgLoadPrivateKeyError::TransientInternalError(internal_error),
+            ),
+            Ok(_) => unexpected_response("load_threshold_signing_key"),
+        }
+    }
+
+    fn retain_threshold_keys_if_present(&self, active_key_ids: BTreeSet<KeyId>) {
+        match self.call_or_panic(CspVaultRequest::RetainThresholdKeysIfPresent { active_key_ids }) {
+            CspVaultResponse::RetainThresholdKeysIfPresent => (),
+            _ => unexpected_response("retain_threshold_keys_if_present"),
+        }
+    }
+}
+
+impl SecretKeyStoreCspServer for RemoteCspVault {
+    fn sks_contains(&self, key_id: &KeyId) -> bool {
+        match self.call_or_panic(CspVaultRequest::SksContains { key_id: *key_id }) {
+            CspVaultResponse::SksContains(contains) => contains,
+            _ => unexpected_response("sks_contains"),
+        }
+    }
+
+    fn sks_remove(&self, key_id: &KeyId) -> bool {
+        match self.call_or_panic(CspVaultRequest::SksRemove { key_id: *key_id }) {
+            CspVaultResponse::SksRemove(removed) => removed,
+            _ => unexpected_response("sks_remove"),
+        }
+    }
+}
+
+impl TlsHandshakeCspServer for RemoteCspVault {
+    fn gen_tls_key_pair(&self, node: NodeId, not_after: &str) -> (KeyId, TlsPublicKeyCert) {
+        match self.call_or_panic(CspVaultRequest::GenTlsKeyPair {
+            node_id: node,
+            not_after: not_after.to_string(),
+        }) {
+            CspVaultResponse::GenTlsKeyPair((key_id, cert_der)) => {
+                let cert = TlsPublicKeyCert::new_from_der(cert_der)
+                    .expect("the CSP vault sent a malformed X509 certificate");
+                (key_id, cert)
+            }
+            _ => unexpected_response("gen_tls_key_pair"),
+        }
+    }
+
+    fn sign(&self, message: &[u8], key_id: &KeyId) -> Result<CspSignature, CspTlsSignError> {
+        match self.call(CspVaultRequest::TlsSign {
+            message: message.to_vec(),
+            key_id: *key_id,
+        }) {
+            Ok(CspVaultResponse::TlsSign(result)) => result,
+            Err(internal_error) => Err(CspTlsSignError::TransientInternalError { internal_error }),
+            Ok(_) => unexpected_response("sign"),
+        }
+    }
+}
@@ -0,0 +1,224 @@
+//! The server side of the remote CSP vault, which runs in the vault process.
+use super::{read_message, write_message, CspVaultRequest, CspVaultResponse};
+use crate::server::api::{
+    BasicSignatureCspServer, CspVault, MultiSignatureCspServer, NiDkgCspServer,
+    SecretKeyStoreCspServer, ThresholdSignatureCspServer, TlsHandshakeCspServer,
+};
+use ic_logger::{info, warn, ReplicaLogger};
+use std::fs;
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+
+/// Serves the requests of `RemoteCspVault` clients using a `CspVault` that
+/// runs in this process.
+///
+/// Only clients running as user `allowed_client_uid` may connect. In
+/// addition, the socket is only accessible by the owner of the vault process.
+pub struct CspVaultServer {
+    listener: UnixListener,
+    allowed_client_uid: u32,
+    logger: ReplicaLogger,
+}
+
+impl CspVaultServer {
+    /// Binds the vault to a Unix domain socket at `socket_path`, replacing a
+    /// socket left over by a previous run of the vault.
+    pub fn bind(
+        socket_path: &Path,
+        allowed_client_uid: u32,
+        logger: ReplicaLogger,
+    ) -> io::Result<Self> {
+        match fs::remove_file(socket_path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        let listener = UnixListener::bind(socket_path)?;
+        fs::set_permissions(socket_path, fs::Permissions::from_mode(0o600))?;
+        info!(logger, "CSP vault listening on {}", socket_path.display());
+        Ok(CspVaultServer {
+            listener,
+            allowed_client_uid,
+            logger,
+        })
+    }
+
+    /// Accepts connections and serves each of them on a separate thread. Only
+    /// returns if accepting connections fails.
+    pub fn run(self, vault: Arc<dyn CspVault>) -> io::Result<()> {
+        loop {
+            let (stream, _) = self.listener.accept()?;
+            match peer_uid(&stream) {
+                Ok(uid) if uid == self.allowed_client_uid => {}
+                Ok(uid) => {
+                    warn!(
+                        self.logger,
+                        "Rejecting connection to the CSP vault from unauthorized user {}", uid
+                    );
+                    continue;
+                }
+                Err(e) => {
+                    warn!(
+                        self.logger,
+                        "Rejecting connection to the CSP vault: cannot determine peer: {}", e
+                    );
+                    continue;
+                }
+            }
+            let vault = Arc::clone(&vault);
+            let logger = self.logger.clone();
+            thread::spawn(move || serve_connection(&*vault, stream, &logger));
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn peer_uid(stream: &UnixStream) -> io::Result<u32> {
+    use nix::sys::socket::{getsockopt, sockopt::PeerCredentials};
+    use std::os::unix::io::AsRawFd;
+
+    getsockopt(stream.as_raw_fd(), PeerCredentials)
+        .map(|credentials| credentials.uid())
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn peer_uid(_stream: &UnixStream) -> io::Result<u32> {
+    // Peer credentials are only checked on Linux; elsewhere, access is
+    // restricted by the permissions of the socket only.
+    Ok(nix::unistd::getuid().as_raw())
+}
+
+fn serve_connection(vault: &dyn CspVault, mut stream: UnixStream, logger: &ReplicaLogger) {
+    loop {
+        let request: CspVaultRequest = match read_message(&mut stream) {
+            Ok(request) => request,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return,
+            Err(e) => {
+                warn!(logger, "Failed to read request to the CSP vault: {}", e);
+                return;
+            }
+        };
+        let response = panic::catch_unwind(AssertUnwindSafe(|| handle_request(vault, request)))
+            .unwrap_or_else(|payload| {
+                let message = if let Some(message) = payload.downcast_ref::<&str>() {
+                    message.to_string()
+                } else if let Some(message) = payload.downcast_ref::<String>() {
+                    message.clone()
+                } else {
+                    "CSP vault panicked".to_string()
+                };
+                CspVaultResponse::Panic(message)
+            });
+        if let Err(e) = write_message(&mut stream, &response) {
+            warn!(logger, "Failed to send response of the CSP vault: {}", e);
+            return;
+        }
+    }
+}
+
+fn handle_request(vault: &dyn CspVault, request: CspVaultRequest) -> CspVaultResponse {
+    match request {
+        CspVaultRequest::BasicSign {
+            algorithm_id,
+            message,
+            key_id,
+        } => CspVaultResponse::BasicSign(BasicSignatureCspServer::sign(
+            vault,
+            algorithm_id,
+            &message,
+            key_id,
+        )),
+        CspVaultRequest::GenKeyPair { algorithm_id } => {
+            CspVaultResponse::GenKeyPair(vault.gen_key_pair(algorithm_id))
+        }
+        CspVaultRequest::MultiSign {
+            algorithm_id,
+            message,
+            key_id,
+        } => CspVaultResponse::MultiSign(vault.multi_sign(algorithm_id, &message, key_id)),
+        CspVaultRequest::GenKeyPairWithPop { algorithm_id } => {
+            CspVaultResponse::GenKeyPairWithPop(vault.gen_key_pair_with_pop(algorithm_id))
+        }
+        #[cfg(test)]
+        CspVaultRequest::ThresholdKeygenForTest {
+            algorithm_id,
+            threshold,
+            signatory_eligibility,
+        } => CspVaultResponse::ThresholdKeygenForTest(vault.threshold_keygen_for_test(
+            algorithm_id,
+            threshold,
+            &signatory_eligibility,
+        )),
+        CspVaultRequest::ThresholdSign {
+            algorithm_id,
+            message,
+            key_id,
+        } => CspVaultResponse::ThresholdSign(vault.threshold_sign(algorithm_id, &message, key_id)),
+        CspVaultRequest::GenForwardSecureKeyPair {
+            node_id,
+            algorithm_id,
+        } => CspVaultResponse::GenForwardSecureKeyPair(
+            vault.gen_forward_secure_key_pair(node_id, algorithm_id),
+        ),
+        CspVaultRequest::UpdateForwardSecureEpoch {
+            algorithm_id,
+            key_id,
+            epoch,
+        } => CspVaultResponse::UpdateForwardSecureEpoch(vault.update_forward_secure_epoch(
+            algorithm_id,
+            key_id,
+            epoch,
+        )),
+        CspVaultRequest::CreateDealing {
+            algorithm_id,
+            dealer_index,
+            threshold,
+            epoch,
+            receiver_keys,
+            maybe_resharing_secret,
+        } => CspVaultResponse::CreateDealing(vault.create_dealing(
+            algorithm_id,
+            dealer_index,
+            threshold,
+            epoch,
+            &receiver_keys,
+            maybe_resharing_secret,
+        )),
+        CspVaultRequest::LoadThresholdSigningKey {
+            algorithm_id,
+            epoch,
+            csp_transcript,
+            fs_key_id,
+            receiver_index,
+        } => CspVaultResponse::LoadThresholdSigningKey(vault.load_threshold_signing_key(
+            algorithm_id,
+            epoch,
+            csp_transcript,
+            fs_key_id,
+            receiver_index,
+        )),
+        CspVaultRequest::RetainThresholdKeysIfPresent { active_key_ids } => {
+            vault.retain_threshold_keys_if_present(active_key_ids);
+            CspVaultResponse::RetainThresholdKeysIfPresent
+        }
+        CspVaultRequest::SksContains { key_id } => {
+            CspVaultResponse::SksContains(vault.sks_contains(&key_id))
+        }
+        CspVaultRequest::SksRemove { key_id } => {
+            CspVaultResponse::SksRemove(vault.sks_remove(&key_id))
+        }
+        CspVaultRequest::GenTlsKeyPair { node_id, not_after } => {
+            let (key_id, cert) = vault.gen_tls_key_pair(node_id, &not_after);
+            CspVaultResponse::GenTlsKeyPair((key_id, cert.as_der().clone()))
+        }
+        CspVaultRequest::TlsSign { message, key_id } => {
+            CspVaultResponse::TlsSign(TlsHandshakeCspServer::sign(vault, &message, &key_id))
+        }
+    }
+}
@@ -0,0 +1,276 @@
+//! Tests of the remote CSP vault.
+use super::*;
+use crate::api::{CspKeyGenerator, CspSigner, CspTlsHandshakeSignerProvider};
+use crate::secret_key_store::test_utils::TempSecretKeyStore;
+use crate::server::api::CspVault;
+use crate::server::local_csp_server::LocalCspServer;
+use crate::Csp;
+use ic_config::crypto::CryptoConfig;
+use ic_crypto_internal_csp_test_utils::files::mk_temp_dir_with_permissions;
+use ic_crypto_internal_logmon::metrics::CryptoMetrics;
+use ic_logger::replica_logger::no_op_logger;
+use ic_types_test_utils::ids::node_test_id;
+use rand::{thread_rng, Rng, SeedableRng};
+use rand_chacha::ChaChaRng;
+use std::os::unix::net::UnixListener;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use tempfile::TempDir;
+
+const NOT_AFTER: &str = "25670102030405Z";
+
+/// Serves `vault` on a socket in a fresh temporary directory and returns the
+/// directory, which must be kept alive for as long as the vault is used.
+fn serve(vault: Arc<dyn CspVault>, allowed_client_uid: u32) -> (TempDir, PathBuf) {
+    let socket_dir = mk_temp_dir_with_permissions(0o700);
+    let socket_path = socket_dir.path().join("csp_vault.sock");
+    let server = CspVaultServer::bind(&socket_path, allowed_client_uid, no_op_logger())
+        .expect("failed to bind CSP vault server");
+    thread::spawn(move || server.run(vault));
+    (socket_dir, socket_path)
+}
+
+fn remote_vault_for_test() -> (TempDir, RemoteCspVault) {
+    let csprng = ChaChaRng::from_seed(thread_rng().gen::<[u8; 32]>());
+    let local_csp_server = LocalCspServer::new_for_test(csprng, TempSecretKeyStore::new());
+    let (socket_dir, socket_path) = serve(Arc::new(local_csp_server), current_uid());
+    (socket_dir, RemoteCspVault::new(&socket_path))
+}
+
+fn current_uid() -> u32 {
+    nix::unistd::getuid().as_raw()
+}
+
+#[test]
+fn should_generate_key_and_sign_via_remote_vault() {
+    let (_socket_dir, vault) = remote_vault_for_test();
+
+    let (key_id, _csp_pub_key) = vault
+        .gen_key_pair(AlgorithmId::Ed25519)
+        .expect("failed to generate keys");
+
+    assert!(vault.sks_contains(&key_id));
+    assert!(
+        BasicSignatureCspServer::sign(&vault, AlgorithmId::Ed25519, b"message", key_id).is_ok()
+    );
+}
+
+#[test]
+fn should_remove_key_via_remote_vault() {
+    let (_socket_dir, vault) = remote_vault_for_test();
+    let (key_id, _csp_pub_key) = vault
+        .gen_key_pair(AlgorithmId::Ed25519)
+        .expect("failed to generate keys");
+
+    assert!(vault.sks_remove(&key_id));
+    assert!(!vault.sks_contains(&key_id));
+}
+
+#[test]
+fn should_multi_sign_via_remote_vault() {
+    let (_socket_dir, vault) = remote_vault_for_test();
+
+    let (key_id, _csp_pub_key, _pop) = vault
+        .gen_key_pair_with_pop(AlgorithmId::MultiBls12_381)
+        .expect("failed to generate keys");
+
+    assert!(vault
+        .multi_sign(AlgorithmId::MultiBls12_381, b"message", key_id)
+        .is_ok());
+}
+
+#[test]
+fn should_return_errors_of_remote_vault() {
+    let (_socket_dir, vault) = remote_vault_for_test();
+    let key_id = KeyId::from([42; 32]);
+
+    assert_eq!(
+        BasicSignatureCspServer::sign(&vault, AlgorithmId::Ed25519, b"message", key_id)
+            .unwrap_err(),
+        CspBasicSignatureError::SecretKeyNotFound {
+            algorithm: AlgorithmId::Ed25519,
+            key_id,
+        }
+    );
+    assert_eq!(
+        vault.gen_key_pair(AlgorithmId::Tls).unwrap_err(),
+        CspBasicSignatureKeygenError::UnsupportedAlgorithm {
+            algorithm: AlgorithmId::Tls,
+        }
+    );
+}
+
+#[test]
+fn should_generate_tls_key_and_sign_via_remote_vault() {
+    let (_socket_dir, vault) = remote_vault_for_test();
+
+    let (key_id, cert) = vault.gen_tls_key_pair(node_test_id(1), NOT_AFTER);
+
+    assert_eq!(key_id, crate::keygen::tls_cert_hash_as_key_id(&cert));
+    assert!(TlsHandshakeCspServer::sign(&vault, b"message", &key_id).is_ok());
+}
+
+#[test]
+#[should_panic(expected = "invalid X.509 certificate expiration date (not_after)")]
+fn should_propagate_panic_of_remote_vault() {
+    let (_socket_dir, vault) = remote_vault_for_test();
+
+    vault.gen_tls_key_pair(node_test_id(1), "invalid_not_after_date");
+}
+
+#[test]
+fn should_remain_usable_after_panic_of_remote_vault() {
+    let (_socket_dir, vault) = remote_vault_for_test();
+    let vault = Arc::new(vault);
+
+    let panicking_vault = Arc::clone(&vault);
+    let result = thread::spawn(move || {
+        panicking_vault.gen_tls_key_pair(node_test_id(1), "invalid_not_after_date")
+    })
+    .join();
+
+    assert!(result.is_err());
+    assert!(vault.gen_key_pair(AlgorithmId::Ed25519).is_ok());
+}
+
+#[test]
+fn should_reject_connection_from_unauthorized_user() {
+    let csprng = ChaChaRng::from_seed(thread_rng().gen::<[u8; 32]>());
+    let local_csp_server = LocalCspServer::new_for_test(csprng, TempSecretKeyStore::new());
+    let (_socket_dir, socket_path) =
+        serve(Arc::new(local_csp_server), current_uid().wrapping_add(1));
+    let vault = RemoteCspVault::new(&socket_path);
+
+    let result = vault.gen_key_pair(AlgorithmId::Ed25519);
+
+    assert!(matches!(
+        result,
+        Err(CspBasicSignatureKeygenError::TransientInternalError { internal_error })
+            if internal_error.contains("Failed to communicate with the CSP vault")
+    ));
+}
+
+#[test]
+fn should_return_transient_error_if_remote_vault_is_unreachable() {
+    let socket_dir = mk_temp_dir_with_permissions(0o700);
+    let vault = RemoteCspVault::new(&socket_dir.path().join("nonexistent.sock"));
+
+    let result = BasicSignatureCspServer::sign(
+        &vault,
+        AlgorithmId::Ed25519,
+        b"message",
+        KeyId::from([42; 32]),
+    );
+
+    assert!(matches!(
+        result,
+        Err(CspBasicSignatureError::TransientInternalError { internal_error })
+            if internal_error.contains("Failed to communicate with the CSP vault")
+    ));
+}
+
+#[test]
+#[should_panic(expected = "Failed to communicate with the CSP vault")]
+fn should_panic_if_remote_vault_is_unreachable_and_method_cannot_return_error() {
+    let socket_dir = mk_temp_dir_with_permissions(0o700);
+    let vault = RemoteCspVault::new(&socket_dir.path().join("nonexistent.sock"));
+
+    vault.sks_contains(&KeyId::from([42; 32]));
+}
+
+/// Serves a fake vault that answers the first request on each connection with
+/// `SksContains(true)` and then closes the connection, as if the vault had
+/// been restarted. Returns the number of connections accepted so far.
+fn serve_vault_closing_connections_after_first_request() -> (TempDir, PathBuf, Arc<AtomicUsize>) {
+    let socket_dir = mk_temp_dir_with_permissions(0o700);
+    let socket_path = socket_dir.path().join("csp_vault.sock");
+    let listener = UnixListener::bind(&socket_path).expect("failed to bind");
+    let connections = Arc::new(AtomicUsize::new(0));
+    let connections_in_server = Arc::clone(&connections);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.expect("failed to accept connection");
+            connections_in_server.fetch_add(1, Ordering::SeqCst);
+            let _request: CspVaultRequest =
+                read_message(&mut stream).expect("failed to read request");
+            write_message(&mut stream, &CspVaultResponse::SksContains(true))
+                .expect("failed to write response");
+        }
+    });
+    (socket_dir, socket_path, connections)
+}
+
+#[test]
+fn should_retry_read_only_request_on_new_connection_if_reused_connection_fails() {
+    let (_socket_dir, socket_path, connections) =
+        serve_vault_closing_connections_after_first_request();
+    let vault = RemoteCspVault::new(&socket_path);
+    assert!(vault.sks_contains(&KeyId::from([42; 32])));
+
+    assert!(vault.sks_contains(&KeyId::from([42; 32])));
+
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn should_not_retry_state_changing_request_if_reused_connection_fails() {
+    let (_socket_dir, socket_path, connections) =
+        serve_vault_closing_connections_after_first_request();
+    let vault = RemoteCspVault::new(&socket_path);
+    assert!(vault.sks_contains(&KeyId::from([42; 32])));
+
+    let result = vault.gen_key_pair(AlgorithmId::Ed25519);
+
+    assert!(matches!(
+        result,
+        Err(CspBasicSignatureKeygenError::TransientInternalError { .. })
+    ));
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn should_sign_and_verify_with_csp_using_remote_vault() {
+    let crypto_root = mk_temp_dir_with_permissions(0o700);
+    let local_csp_server = LocalCspServer::new_in_dir(
+        crypto_root.path(),
+        None,
+        Arc::new(CryptoMetrics::none()),
+        no_op_logger(),
+    );
+    let (_socket_dir, socket_path) = serve(Arc::new(local_csp_server), current_uid());
+    let config =
+        CryptoConfig::new_with_unix_socket_vault(crypto_root.path().to_path_buf(), socket_path);
+    let csp = Csp::new(&config, None, Arc::new(CryptoMetrics::none()));
+
+    let (key_id, csp_pub_key) = csp
+        .gen_key_pair(AlgorithmId::Ed25519)
+        .expect("failed to generate keys");
+    let sig = csp
+        .sign(AlgorithmId::Ed25519, b"message", key_id)
+        .expect("failed to sign");
+
+    assert!(csp
+        .verify(&sig, b"message", AlgorithmId::Ed25519, csp_pub_key)
+        .is_ok());
+}
+
+#[test]
+fn should_sign_tls_handshakes_remotely_only_if_csp_uses_remote_vault() {
+    let crypto_root = mk_temp_dir_with_permissions(0o700);
+    let local_csp_server = LocalCspServer::new_in_dir(
+        crypto_root.path(),
+        None,
+        Arc::new(CryptoMetrics::none()),
+        no_op_logger(),
+    );
+    let (_socket_dir, socket_path) = serve(Arc::new(local_csp_server), current_uid());
+    let config =
+        CryptoConfig::new_with_unix_socket_vault(crypto_root.path().to_path_buf(), socket_path);
+    let csp_with_remote_vault = Csp::new(&config, None, Arc::new(CryptoMetrics::none()));
+    let csprng = ChaChaRng::from_seed(thread_rng().gen::<[u8; 32]>());
+    let csp_with_local_vault = Csp::of(csprng, TempSecretKeyStore::new());
+
+    assert!(csp_with_remote_vault.tls_secret_keys_are_remote());
+    assert!(!csp_with_local_vault.tls_secret_keys_are_remote());
+}
@@ -27,11 +27,11 @@ impl<R: Rng + CryptoRng, S: SecretKeyStore, C: SecretKeyStore> CspSigner for Csp
     ) -> CryptoResult<CspSignature> {
         match algorithm_id {
             AlgorithmId::Ed25519 => self
-                .csp_server
+                .csp_vault
                 .sign(algorithm_id, message, key_id)
                 .map_err(CspBasicSignatureError::into),
             AlgorithmId::MultiBls12_381 => self
-                .csp_server
+                .csp_vault
                 .multi_sign(algorithm_id, message, key_id)
                 .map_err(CspMultiSignatureError::into),
             _ => Err(CryptoError::InvalidArgument {
@@ -31,6 +31,15 @@ mod sign_common {
         assert!(result.unwrap_err().is_secret_key_not_found());
     }
 
+    #[test]
+    fn should_fail_with_secret_key_not_found_if_secret_key_not_found_in_remote_vault() {
+        let csp = Csp::of_with_remote_vault(csprng(), secret_key_store_returning_none());
+
+        let result = csp.sign(Ed25519, b"msg", KeyId::from(KEY_ID));
+
+        assert!(result.unwrap_err().is_secret_key_not_found());
+    }
+
     #[test]
     #[should_panic]
     fn should_panic_when_secret_key_store_panics() {
@@ -38,6 +47,14 @@ mod sign_common {
 
         let _ = csp.sign(Ed25519, b"msg", KeyId::from(KEY_ID));
     }
+
+    #[test]
+    #[should_panic]
+    fn should_panic_when_secret_key_store_of_remote_vault_panics() {
+        let csp = Csp::of_with_remote_vault(csprng(), secret_key_store_panicking_on_usage());
+
+        let _ = csp.sign(Ed25519, b"msg", KeyId::from(KEY_ID));
+    }
 }
 
 mod sign_ed25519 {
@@ -54,6 +71,16 @@ mod sign_ed25519 {
         assert_eq!(csp.sign(Ed25519, &msg, KeyId::from(KEY_ID)).unwrap(), sig);
     }
 
+    #[test]
+    fn should_correctly_sign_with_remote_vault() {
+        let (sk, _, msg, sig) = csp_testvec(RFC8032_ED25519_SHA_ABC);
+
+        let csp =
+            Csp::of_with_remote_vault(csprng(), secret_key_store_with(KeyId::from(KEY_ID), sk));
+
+        assert_eq!(csp.sign(Ed25519, &msg, KeyId::from(KEY_ID)).unwrap(), sig);
+    }
+
     #[test]
     fn should_fail_to_sign_if_secret_key_in_store_has_wrong_type() {
         let sk_with_wrong_type = CspSecretKey::MultiBls12_381(multi_types::SecretKeyBytes(
@@ -47,7 +47,7 @@ impl<R: Rng + CryptoRng, S: SecretKeyStore, C: SecretKeyStore> NiDkgCspClient fo
     ) -> Result<(CspFsEncryptionPublicKey, CspFsEncryptionPop), ni_dkg_errors::CspDkgCreateFsKeyError>
     {
         debug!(self.logger; crypto.method_name => "create_forward_secure_key_pair");
-        self.csp_server
+        self.csp_vault
             .gen_forward_secure_key_pair(node_id, algorithm_id)
     }
 
@@ -59,9 +59,13 @@ impl<R: Rng + CryptoRng, S: SecretKeyStore, C: SecretKeyStore> NiDkgCspClient fo
     ) -> Result<(), ni_dkg_errors::CspDkgUpdateFsEpochError> {
         debug!(self.logger; crypto.method_name => "update_forward_secure_epoch", crypto.dkg_epoch => epoch.get());
 
-        let key_id = self.dkg_dealing_encryption_key_id();
-        self.csp_server
-            .update_forward_secure_epoch(algorithm_id, key_id, epoch)
+        // While a key rotation is pending, both the current and the previous
+        // key may be used by dealers, so both keys are updated.
+        for key_id in self.dkg_dealing_encryption_key_ids() {
+            self.csp_vault
+                .update_forward_secure_epoch(algorithm_id, key_id, epoch)?;
+        }
+        Ok(())
     }
 
     /// Creates a CSP dealing
@@ -75,7 +79,7 @@ impl<R: Rng + CryptoRng, S: SecretKeyStore, C: SecretKeyStore> NiDkgCspClient fo
         receiver_keys: BTreeMap<NodeIndex, CspFsEncryptionPublicKey>,
     ) -> Result<CspNiDkgDealing, ni_dkg_errors::CspDkgCreateDealingError> {
         debug!(self.logger; crypto.method_name => "create_dealing", crypto.dkg_epoch => epoch.get());
-        Ok(self.csp_server.create_dealing(
+        Ok(self.csp_vault.create_dealing(
             algorithm_id,
             dealer_index,
             threshold,
@@ -98,7 +102,7 @@ impl<R: Rng + CryptoRng, S: SecretKeyStore, C: SecretKeyStore> NiDkgCspClient fo
     ) -> Result<CspNiDkgDealing, ni_dkg_errors::CspDkgCreateReshareDealingError> {
         debug!(self.logger; crypto.method_name => "create_resharing_dealing", crypto.dkg_epoch => epoch.get());
         let key_id = key_id_from_csp_pub_coeffs(&resharing_public_coefficients);
-        self.csp_server.create_dealing(
+        self.csp_vault.create_dealing(
             algorithm_id,
             dealer_resharing_index,
             threshold,
@@ -194,21 +198,37 @@ impl<R: Rng + CryptoRng, S: SecretKeyStore, C: SecretKeyStore> NiDkgCspClient fo
         receiver_index: NodeIndex,
     ) -> Result<(), ni_dkg_errors::CspDkgLoadPrivateKeyError> {
         debug!(self.logger; crypto.method_name => "load_threshold_signing_key", crypto.dkg_epoch => epoch.get());
-        let fs_key_id = self.dkg_dealing_encryption_key_id();
-        self.csp_server.load_threshold_signing_key(
-            algorithm_id,
-            epoch,
-            csp_transcript,
-            fs_key_id,
-            receiver_index,
-        )
+        // The transcript may have been created for the key that was replaced by
+        // a pending key rotation, so if decryption with the current key fails,
+        // the previous key is tried. The error for the current key is returned
+        // if no key succeeds.
+        let mut first_error = None;
+        for fs_key_id in self.dkg_dealing_encryption_key_ids() {
+            match self.csp_vault.load_threshold_signing_key(
+                algorithm_id,
+                epoch,
+                csp_transcript.clone(),
+                fs_key_id,
+                receiver_index,
+            ) {
+                Ok(()) => return Ok(()),
+                Err(error @ ni_dkg_errors::CspDkgLoadPrivateKeyError::KeyNotFoundError(_))
+                | Err(
+                    error @ ni_dkg_errors::CspDkgLoadPrivateKeyError::InvalidTranscriptError(_),
+                ) => {
+                    first_error.get_or_insert(error);
+                }
+                Err(error) => return Err(error),
+            }
+        }
+        Err(first_error.expect("there is always a current DKG dealing encryption key"))
     }
 
     fn retain_threshold_keys_if_present(&self, active_keys: BTreeSet<CspPublicCoefficients>) {
         debug!(self.logger; crypto.method_name => "retain_threshold_keys_if_present");
         let active_key_ids: BTreeSet<KeyId> =
             active_keys.iter().map(key_id_from_csp_pub_coeffs).collect();
-        self.csp_server
+        self.csp_vault
             .retain_threshold_keys_if_present(active_key_ids)
     }
 }
@@ -57,7 +57,7 @@ pub mod util {
         let signatures: Result<Vec<CspSignature>, CspThresholdSignError> = signers
             .iter()
             .map(|(csp, key_id)| {
-                csp.csp_server
+                csp.csp_vault
                     .threshold_sign(AlgorithmId::ThresBls12_381, message, *key_id)
             })
             .collect();
@@ -69,7 +69,7 @@ pub mod util {
                 if algorithm_id != AlgorithmId::ThresBls12_381 {
                     if let Some((csp, key_id)) = signers.get(0) {
                         assert!(
-                            csp.csp_server
+                            csp.csp_vault
                                 .threshold_sign(algorithm_id, message, *key_id)
                                 .is_err(),
                             "Managed to threshold sign with algorithm ID {:?}",
@@ -89,7 +89,7 @@ pub mod util {
                     "Bad RNG: A randomly generated KeyId was in the list of keys"
                 );
                 assert!(
-                    csp.csp_server
+                    csp.csp_vault
                         .threshold_sign(AlgorithmId::ThresBls12_381, message, wrong_key_id)
                         .is_err(),
                     "A randomly generated key_id managed to sign"
@@ -237,7 +237,14 @@ pub mod util {
     ///   * If the threshold is higher than the number of signers, keygen fails.
     /// * Correct keygen arguments yield keys that behave correctly with regards
     ///   to signing and verification.
-    pub fn test_threshold_scheme_with_basic_keygen(seed: Randomness, message: &[u8]) {
+    ///
+    /// If `use_remote_vault` is set, the keys are held by a vault that is
+    /// reached over a Unix domain socket.
+    pub fn test_threshold_scheme_with_basic_keygen(
+        seed: Randomness,
+        message: &[u8],
+        use_remote_vault: bool,
+    ) {
         let mut rng = ChaChaRng::from_seed(seed.get());
         let threshold = NumberOfNodes::from(rng.gen_range(0, 10));
         let number_of_signers = NumberOfNodes::from(rng.gen_range(0, 10));
@@ -245,7 +252,11 @@ pub mod util {
         let mut csp = {
             let key_store = TempSecretKeyStore::new();
             let csprng = ChaChaRng::from_seed(rng.gen::<[u8; 32]>());
-            Csp::of(csprng, key_store)
+            if use_remote_vault {
+                Csp::of_with_remote_vault(csprng, key_store)
+            } else {
+                Csp::of(csprng, key_store)
+            }
         };
 
         match csp.threshold_keygen(
@@ -285,6 +296,11 @@ proptest! {
 
     #[test]
     fn test_threshold_scheme_with_basic_keygen(seed: [u8;32], message in proptest::collection::vec(any::<u8>(), 0..100)) {
-        util::test_threshold_scheme_with_basic_keygen(Randomness::from(seed), &message);
+        util::test_threshold_scheme_with_basic_keygen(Randomness::from(seed), &message, false);
+    }
+
+    #[test]
+    fn test_threshold_scheme_with_basic_keygen_and_remote_vault(seed: [u8;32], message in proptest::collection::vec(any::<u8>(), 0..100)) {
+        util::test_threshold_scheme_with_basic_keygen(Randomness::from(seed), &message, true);
     }
 }
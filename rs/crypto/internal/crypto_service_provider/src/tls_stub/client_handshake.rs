@@ -1,7 +1,7 @@
 use crate::api::tls_errors::CspTlsClientHandshakeError;
 use crate::api::CspTlsClientHandshake;
 use crate::secret_key_store::SecretKeyStore;
-use crate::tls_stub::{key_from_secret_key_store, peer_cert_from_stream, CspTlsSecretKeyError};
+use crate::tls_stub::{peer_cert_from_stream, CspTlsSecretKeyError};
 use crate::Csp;
 use async_trait::async_trait;
 use ic_crypto_tls_interfaces::TlsPublicKeyCert;
@@ -59,7 +59,7 @@ impl<R: Rng + CryptoRng + Send + Sync, S: SecretKeyStore, C: SecretKeyStore> Csp
         trusted_server_cert: TlsPublicKeyCert,
     ) -> Result<ConnectConfiguration, CspTlsClientHandshakeError> {
         Ok(ic_crypto_internal_tls::tls_connector(
-            &key_from_secret_key_store(&*self.sks_read_lock(), &self_cert)?,
+            &self.tls_secret_key(&self_cert)?,
             self_cert.as_x509(),
             trusted_server_cert.as_x509(),
         )?)
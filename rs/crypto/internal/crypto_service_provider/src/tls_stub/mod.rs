@@ -7,10 +7,12 @@ use crate::keygen::tls_cert_hash_as_key_id;
 use crate::secret_key_store::SecretKeyStore;
 use crate::tls_stub::cert_chain::CspCertificateChainCreationError;
 use crate::types::CspSecretKey;
+use crate::Csp;
 use cert_chain::CspCertificateChain;
 use ic_crypto_tls_interfaces::TlsPublicKeyCert;
 use openssl::pkey::{PKey, Private};
 use openssl::x509::X509VerifyResult;
+use rand::{CryptoRng, Rng};
 use std::convert::TryFrom;
 use tokio::net::TcpStream;
 use tokio_openssl::SslStream;
@@ -40,6 +42,26 @@ fn key_from_secret_key_store<S: SecretKeyStore>(
         .map_err(|_| CspTlsSecretKeyError::MalformedSecretKey)
 }
 
+impl<R: Rng + CryptoRng, S: SecretKeyStore, C: SecretKeyStore> Csp<R, S, C> {
+    /// Returns the TLS secret key corresponding to `self_cert`.
+    ///
+    /// The key can only be retrieved if the CSP vault runs in-process. If the
+    /// vault runs in a separate process, the secret key never leaves the vault
+    /// and TLS handshakes must be performed with the `handshake_signer` (see
+    /// `CspTlsHandshakeSignerProvider::tls_secret_keys_are_remote`).
+    fn tls_secret_key(
+        &self,
+        self_cert: &TlsPublicKeyCert,
+    ) -> Result<PKey<Private>, CspTlsSecretKeyError> {
+        match &self.local_csp_server {
+            Some(local_csp_server) => {
+                key_from_secret_key_store(&*local_csp_server.sks_read_lock(), self_cert)
+            }
+            None => Err(CspTlsSecretKeyError::SecretKeyNotFound),
+        }
+    }
+}
+
 enum CspTlsSecretKeyError {
     SecretKeyNotFound,
     MalformedSecretKey,
@@ -2,9 +2,7 @@ use crate::api::tls_errors::CspTlsServerHandshakeError;
 use crate::api::CspTlsServerHandshake;
 use crate::secret_key_store::SecretKeyStore;
 use crate::tls_stub::cert_chain::CspCertificateChain;
-use crate::tls_stub::{
-    key_from_secret_key_store, peer_cert_chain_from_stream, CspTlsSecretKeyError,
-};
+use crate::tls_stub::{peer_cert_chain_from_stream, CspTlsSecretKeyError};
 use crate::Csp;
 use async_trait::async_trait;
 use ic_crypto_tls_interfaces::TlsPublicKeyCert;
@@ -82,7 +80,7 @@ impl<R: Rng + CryptoRng, S: SecretKeyStore, C: SecretKeyStore> Csp<R, S, C> {
             None => ClientAuthentication::NoAuthentication,
         };
         Ok(tls_acceptor(
-            &key_from_secret_key_store(&*self.sks_read_lock(), &self_cert)?,
+            &self.tls_secret_key(&self_cert)?,
             self_cert.as_x509(),
             trusted_client_certs_x509,
         )?)
@@ -10,9 +10,9 @@ use std::sync::Arc;
 
 const NODE_ID: u64 = 42;
 
-/// Note that `S: 'static` is required so that `CspTlsHandshakeSignerProvider`
-/// can be implemented for [Csp]. See the documentation of the respective `impl`
-/// block for more details on the meaning of `S: 'static`.
+/// Note that `S: 'static` is required because [Csp] holds its local secret
+/// key store behind a `CspVault` trait object. `S: 'static` does not mean that
+/// `S` must _have_ a 'static lifetime, only that it is _bounded by_ one.
 pub fn crypto_component_with<S: SecretKeyStore + 'static>(
     registry_client: Arc<dyn RegistryClient>,
     secret_key_store: S,
@@ -352,5 +352,7 @@ mock! {
 
     pub trait CspTlsHandshakeSignerProvider: Send + Sync {
         fn handshake_signer(&self) -> Arc<dyn TlsHandshakeCspServer>;
+
+        fn tls_secret_keys_are_remote(&self) -> bool;
     }
 }
@@ -20,10 +20,15 @@ use std::sync::Arc;
 
 pub mod ni_dkg;
 
+mod key_rotation;
 mod temp_crypto;
 
 pub use crate::sign::utils::combined_threshold_signature_and_public_key;
 use ic_crypto_internal_logmon::metrics::CryptoMetrics;
+pub use key_rotation::{
+    node_public_keys_age, previous_node_public_keys, remove_previous_node_keys, rotate_node_keys,
+    sign_message_id_with_node_signing_key,
+};
 pub use temp_crypto::{NodeKeysToGenerate, TempCryptoComponent};
 
 #[cfg(test)]
@@ -37,9 +42,17 @@ mod tests;
 ///
 /// If the `crypto_root` directory does not exist, it is created with the
 /// required permissions. If there exists no key store in `crypto_root`, a new
-/// one is created.
+/// one is created. The key store is not sealed with a key-encryption key; use
+/// `get_node_keys_or_generate_if_missing_for_config` for a sealed key store.
 pub fn generate_dkg_dealing_encryption_keys(crypto_root: &Path, node_id: NodeId) -> PublicKeyProto {
-    let mut csp = csp_at_root(crypto_root);
+    let mut csp = csp_for_config(&CryptoConfig::new(crypto_root.to_path_buf()));
+    generate_dkg_dealing_encryption_keys_using(&mut csp, node_id)
+}
+
+fn generate_dkg_dealing_encryption_keys_using<C: CryptoServiceProvider>(
+    csp: &mut C,
+    node_id: NodeId,
+) -> PublicKeyProto {
     let (pubkey, pop) = csp
         .create_forward_secure_key_pair(AlgorithmId::NiDkg_Groth20_Bls12_381, node_id)
         .expect("Failed to generate DKG dealing encryption keys");
@@ -65,15 +78,26 @@ pub fn generate_dkg_dealing_encryption_keys(crypto_root: &Path, node_id: NodeId)
 ///  * if public keys exist but are inconsistent with the secret keys.
 ///  * if an error occurs when accessing or generating the keys.
 pub fn get_node_keys_or_generate_if_missing(crypto_root: &Path) -> (NodePublicKeys, NodeId) {
-    match check_keys_locally(crypto_root) {
+    get_node_keys_or_generate_if_missing_for_config(&CryptoConfig::new(crypto_root.to_path_buf()))
+}
+
+/// Like `get_node_keys_or_generate_if_missing`, but accesses and generates the
+/// secret keys with the CSP vault and the secret key store encryption
+/// configured in `config`.
+pub fn get_node_keys_or_generate_if_missing_for_config(
+    config: &CryptoConfig,
+) -> (NodePublicKeys, NodeId) {
+    let crypto_root = config.crypto_root.as_path();
+    let mut csp = csp_for_config(config);
+    match check_keys_locally_using(crypto_root, &csp) {
         Ok(None) => {
             // Generate new keys.
-            let committee_signing_pk = generate_committee_signing_keys(crypto_root);
-            let node_signing_pk = generate_node_signing_keys(crypto_root);
+            let committee_signing_pk = generate_committee_signing_keys_using(&csp);
+            let node_signing_pk = generate_node_signing_keys_using(&csp);
             let node_id = derive_node_id(&node_signing_pk);
             let dkg_dealing_encryption_pk =
-                generate_dkg_dealing_encryption_keys(crypto_root, node_id);
-            let tls_certificate = generate_tls_keys(crypto_root, node_id).to_proto();
+                generate_dkg_dealing_encryption_keys_using(&mut csp, node_id);
+            let tls_certificate = generate_tls_keys_using(&mut csp, node_id).to_proto();
             let node_pks = NodePublicKeys {
                 version: 0,
                 node_signing_pk: Some(node_signing_pk),
@@ -84,7 +108,7 @@ pub fn get_node_keys_or_generate_if_missing(crypto_root: &Path) -> (NodePublicKe
             public_key_store::store_node_public_keys(crypto_root, &node_pks)
                 .unwrap_or_else(|_| panic!("Failed to store public key material"));
             // Re-check the generated keys.
-            let stored_keys = check_keys_locally(crypto_root)
+            let stored_keys = check_keys_locally_using(crypto_root, &csp)
                 .expect("Could not read generated keys.")
                 .expect("Newly generated keys are inconsistent.");
             if stored_keys != node_pks {
@@ -109,8 +133,11 @@ pub fn derive_node_id(node_signing_pk: &PublicKeyProto) -> NodeId {
         .expect("Corrupted node signing public key")
 }
 
-fn generate_node_signing_keys(crypto_root: &Path) -> PublicKeyProto {
-    let csp = csp_at_root(crypto_root);
+fn generate_node_signing_keys(config: &CryptoConfig) -> PublicKeyProto {
+    generate_node_signing_keys_using(&csp_for_config(config))
+}
+
+fn generate_node_signing_keys_using<C: CryptoServiceProvider>(csp: &C) -> PublicKeyProto {
     let generated = csp
         .gen_key_pair(AlgorithmId::Ed25519)
         .expect("Could not generate node signing keys");
@@ -139,7 +166,10 @@ fn read_public_keys(crypto_root: &Path) -> CryptoResult<NodePublicKeys> {
 ///    consistent with the secret keys.
 ///  - `Ok(None)` if no public keys are found.
 ///  - `Err(...)` in all other cases.
-fn check_keys_locally(crypto_root: &Path) -> CryptoResult<Option<NodePublicKeys>> {
+fn check_keys_locally_using<C: CryptoServiceProvider>(
+    crypto_root: &Path,
+    csp: &C,
+) -> CryptoResult<Option<NodePublicKeys>> {
     let node_pks = match read_public_keys(crypto_root) {
         Ok(pks) => pks,
         Err(_) => return Ok(None),
@@ -147,12 +177,16 @@ fn check_keys_locally(crypto_root: &Path) -> CryptoResult<Option<NodePublicKeys>
     if node_public_keys_are_empty(&node_pks) {
         return Ok(None);
     }
-    let csp = csp_at_root(crypto_root);
-    ensure_node_signing_key_is_set_up_locally(&node_pks.node_signing_pk, &csp)?;
+    ensure_node_signing_key_is_set_up_locally(&node_pks.node_signing_pk, csp)?;
     // TODO (CRP-994): add checks for other local keys.
     Ok(Some(node_pks))
 }
 
+#[cfg(test)]
+fn check_keys_locally(config: &CryptoConfig) -> CryptoResult<Option<NodePublicKeys>> {
+    check_keys_locally_using(&config.crypto_root, &csp_for_config(config))
+}
+
 fn node_public_keys_are_empty(node_pks: &NodePublicKeys) -> bool {
     node_pks.node_signing_pk.is_none()
         && node_pks.committee_signing_pk.is_none()
@@ -190,8 +224,11 @@ fn ensure_node_signing_key_is_set_up_locally(
     Ok(())
 }
 
-fn generate_committee_signing_keys(crypto_root: &Path) -> PublicKeyProto {
-    let csp = csp_at_root(crypto_root);
+fn generate_committee_signing_keys(config: &CryptoConfig) -> PublicKeyProto {
+    generate_committee_signing_keys_using(&csp_for_config(config))
+}
+
+fn generate_committee_signing_keys_using<C: CryptoServiceProvider>(csp: &C) -> PublicKeyProto {
     let generated = csp
         .gen_key_pair_with_pop(AlgorithmId::MultiBls12_381)
         .expect("Could not generate committee signing keys");
@@ -210,9 +247,9 @@ fn generate_committee_signing_keys(crypto_root: &Path) -> PublicKeyProto {
 
 /// Generates TLS key material for a `node`.
 ///
-/// Stores the secret key in the key store at `crypto_root` and uses it to
-/// create a self-signed public key certificate. If there exists no key store
-/// in `crypto_root` yet, a new key store is created.
+/// Stores the secret key in the key store configured in `config` and uses it
+/// to create a self-signed public key certificate. If there exists no key
+/// store in the crypto root yet, a new key store is created.
 ///
 ///
 /// The certificate's notAfter date indicates according to RFC5280 (section
@@ -220,24 +257,27 @@ fn generate_committee_signing_keys(crypto_root: &Path) -> PublicKeyProto {
 /// certificate has no well-defined expiration date.
 ///
 /// Returns the certificate.
-fn generate_tls_keys(crypto_root: &Path, node: NodeId) -> TlsPublicKeyCert {
-    let mut csp = csp_at_root(crypto_root);
-    csp.gen_tls_key_pair(node, "99991231235959Z")
+fn generate_tls_keys(config: &CryptoConfig, node: NodeId) -> TlsPublicKeyCert {
+    let mut csp = csp_for_config(config);
+    generate_tls_keys_using(&mut csp, node)
 }
 
-pub(crate) fn csp_at_root(
-    crypto_root: &Path,
-) -> Csp<OsRng, ProtoSecretKeyStore, ProtoSecretKeyStore> {
-    let config = config_with_dir_and_permissions(crypto_root);
-    // disable metrics
-    Csp::new(&config, None, Arc::new(CryptoMetrics::none()))
+fn generate_tls_keys_using<C: CryptoServiceProvider>(
+    csp: &mut C,
+    node: NodeId,
+) -> TlsPublicKeyCert {
+    csp.gen_tls_key_pair(node, "99991231235959Z")
 }
 
-fn config_with_dir_and_permissions(crypto_root: &Path) -> CryptoConfig {
-    std::fs::create_dir_all(&crypto_root)
+/// Creates a CSP that uses the CSP vault and the secret key store encryption
+/// configured in `config`, creating the crypto root if it does not exist.
+pub(crate) fn csp_for_config(
+    config: &CryptoConfig,
+) -> Csp<OsRng, ProtoSecretKeyStore, ProtoSecretKeyStore> {
+    std::fs::create_dir_all(&config.crypto_root)
         .unwrap_or_else(|err| panic!("Failed to create crypto root directory: {}", err));
-    let config = CryptoConfig::new(crypto_root.to_path_buf());
     CryptoConfig::set_dir_with_required_permission(&config.crypto_root)
         .expect("Could not setup crypto_root directory");
-    config
+    // disable metrics
+    Csp::new(config, None, Arc::new(CryptoMetrics::none()))
 }
@@ -0,0 +1,178 @@
+//! Rotation of the node's committee signing, DKG dealing encryption and TLS
+//! keys.
+//!
+//! A key rotation generates new keys and makes them the node's current public
+//! keys, while the replaced public keys are kept as the node's *previous*
+//! public keys. As long as previous public keys exist, the rotation is
+//! *pending*: other nodes may still use the replaced keys, so the replaced
+//! secret keys are kept. The replaced keys are needed until no registry version
+//! that is still in use, e.g., by consensus or DKG, contains them, since
+//! signatures are verified and dealings are encrypted with the keys contained
+//! in the registry version in use. The rotation is then completed with
+//! `remove_previous_node_keys`.
+//!
+//! The node signing key is never rotated, because the node ID is derived from
+//! it.
+//!
+//! Note that keys must only be rotated by a process other than the replica if
+//! the secret keys are held by a CSP vault (see `CspVaultType::UnixSocket`),
+//! since concurrent writes to the same secret key store by several processes
+//! may result in lost keys.
+use super::{
+    csp_for_config, generate_committee_signing_keys_using,
+    generate_dkg_dealing_encryption_keys_using, generate_tls_keys_using,
+};
+use ic_config::crypto::CryptoConfig;
+use ic_crypto_internal_csp::api::{CspSecretKeyStoreRemover, CspSigner, NodePublicKeyData};
+use ic_crypto_internal_csp::keygen::{
+    forward_secure_key_id, public_key_hash_as_key_id, tls_cert_hash_as_key_id,
+};
+use ic_crypto_internal_csp::public_key_store;
+use ic_crypto_internal_csp::types::CspPublicKey;
+use ic_crypto_internal_types::encrypt::forward_secure::CspFsEncryptionPublicKey;
+use ic_crypto_tls_interfaces::TlsPublicKeyCert;
+use ic_interfaces::crypto::Signable;
+use ic_protobuf::crypto::v1::NodePublicKeys;
+use ic_types::crypto::{AlgorithmId, CryptoResult, KeyId};
+use ic_types::messages::MessageId;
+use ic_types::NodeId;
+use std::convert::TryFrom;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+#[cfg(test)]
+mod tests;
+
+/// Returns how long ago the node's current public keys were generated or last
+/// rotated, or `None` if this cannot be determined, e.g., because the node
+/// has no keys.
+pub fn node_public_keys_age(crypto_root: &Path) -> Option<Duration> {
+    let modification_time =
+        public_key_store::node_public_keys_modification_time(crypto_root).ok()?;
+    SystemTime::now().duration_since(modification_time).ok()
+}
+
+/// Returns the node public keys that were replaced by the last key rotation if
+/// that rotation is still pending, and `None` otherwise.
+///
+/// # Panics
+///  * if the previous public keys exist but cannot be read.
+pub fn previous_node_public_keys(crypto_root: &Path) -> Option<NodePublicKeys> {
+    public_key_store::read_previous_node_public_keys(crypto_root)
+        .unwrap_or_else(|e| panic!("Failed to read previous node public keys: {:?}", e))
+}
+
+/// Rotates the node's committee signing, DKG dealing encryption and TLS keys.
+///
+/// Generates new key pairs using the CSP vault configured in `config`, makes
+/// the new public keys the node's current public keys, and keeps the replaced
+/// public keys as the node's previous public keys. The replaced secret keys
+/// are kept until `remove_previous_node_keys` is called. The node signing key
+/// is left unchanged.
+///
+/// Returns the node's new public keys, which are yet to be registered.
+///
+/// # Panics
+///  * if a key rotation is already pending.
+///  * if the node has no public keys.
+///  * if an error occurs when accessing or generating the keys.
+pub fn rotate_node_keys(config: &CryptoConfig, node_id: NodeId) -> NodePublicKeys {
+    let crypto_root = &config.crypto_root;
+    if previous_node_public_keys(crypto_root).is_some() {
+        panic!("Cannot rotate node keys while a key rotation is pending");
+    }
+    let current_pks = public_key_store::read_node_public_keys(crypto_root)
+        .unwrap_or_else(|e| panic!("Failed to read node public keys: {:?}", e));
+    if current_pks.node_signing_pk.is_none() {
+        panic!("Cannot rotate node keys of a node without keys");
+    }
+
+    let mut csp = csp_for_config(config);
+    let rotated_pks = NodePublicKeys {
+        version: current_pks.version + 1,
+        node_signing_pk: current_pks.node_signing_pk.clone(),
+        committee_signing_pk: Some(generate_committee_signing_keys_using(&csp)),
+        tls_certificate: Some(generate_tls_keys_using(&mut csp, node_id).to_proto()),
+        dkg_dealing_encryption_pk: Some(generate_dkg_dealing_encryption_keys_using(
+            &mut csp, node_id,
+        )),
+    };
+
+    // The previous keys are stored first: if the process stops in between,
+    // the previous keys are equal to the current keys, and completing the
+    // rotation does not remove any key.
+    public_key_store::store_previous_node_public_keys(crypto_root, &current_pks)
+        .unwrap_or_else(|e| panic!("Failed to store previous node public keys: {:?}", e));
+    public_key_store::store_node_public_keys(crypto_root, &rotated_pks)
+        .unwrap_or_else(|e| panic!("Failed to store rotated node public keys: {:?}", e));
+    rotated_pks
+}
+
+/// Completes a pending key rotation by removing all secret keys that were
+/// replaced by it, together with the previous public keys. Does nothing if no
+/// key rotation is pending.
+///
+/// Callers must ensure that the node's current public keys are contained in
+/// every registry version that is still in use, e.g., the registry versions
+/// used by consensus and DKG according to the latest catch-up package, as
+/// other nodes may otherwise still use the replaced keys.
+///
+/// # Panics
+///  * if an error occurs when accessing the keys.
+pub fn remove_previous_node_keys(config: &CryptoConfig) {
+    let crypto_root = &config.crypto_root;
+    let previous_pks = match previous_node_public_keys(crypto_root) {
+        Some(previous_pks) => previous_pks,
+        None => return,
+    };
+    let current_pks = public_key_store::read_node_public_keys(crypto_root)
+        .unwrap_or_else(|e| panic!("Failed to read node public keys: {:?}", e));
+
+    let csp = csp_for_config(config);
+    let current_key_ids = secret_key_ids(&current_pks);
+    for key_id in secret_key_ids(&previous_pks) {
+        if !current_key_ids.contains(&key_id) {
+            csp.sks_remove(&key_id);
+        }
+    }
+    public_key_store::remove_previous_node_public_keys(crypto_root)
+        .unwrap_or_else(|e| panic!("Failed to remove previous node public keys: {:?}", e));
+}
+
+/// Signs the ID of an ingress message with the node signing key, so that the
+/// node can send ingress messages as itself, e.g., to register rotated keys.
+///
+/// Returns the raw Ed25519 signature.
+pub fn sign_message_id_with_node_signing_key(
+    config: &CryptoConfig,
+    message_id: &MessageId,
+) -> CryptoResult<Vec<u8>> {
+    let csp = csp_for_config(config);
+    let signature = csp.sign(
+        AlgorithmId::Ed25519,
+        &message_id.as_signed_bytes(),
+        csp.node_signing_key_id(),
+    )?;
+    Ok(signature.as_ref().to_vec())
+}
+
+/// Returns the IDs of the rotatable secret keys that belong to `node_pks`.
+fn secret_key_ids(node_pks: &NodePublicKeys) -> Vec<KeyId> {
+    let mut key_ids = vec![];
+    if let Some(pk) = &node_pks.committee_signing_pk {
+        let csp_pk = CspPublicKey::try_from(pk.clone())
+            .expect("Malformed committee signing public key in public key store");
+        key_ids.push(public_key_hash_as_key_id(&csp_pk));
+    }
+    if let Some(cert) = &node_pks.tls_certificate {
+        let cert = TlsPublicKeyCert::new_from_der(cert.certificate_der.clone())
+            .expect("Malformed TLS certificate in public key store");
+        key_ids.push(tls_cert_hash_as_key_id(&cert));
+    }
+    if let Some(pk) = &node_pks.dkg_dealing_encryption_pk {
+        let csp_pk = CspFsEncryptionPublicKey::try_from(pk.clone())
+            .expect("Malformed DKG dealing encryption public key in public key store");
+        key_ids.push(forward_secure_key_id(&csp_pk));
+    }
+    key_ids
+}
@@ -0,0 +1,144 @@
+#![allow(clippy::unwrap_used)]
+use super::*;
+use crate::utils::get_node_keys_or_generate_if_missing;
+use ic_crypto_internal_basic_sig_ed25519::types::{PublicKeyBytes, SignatureBytes};
+use ic_crypto_internal_csp::api::CspSecretKeyStoreChecker;
+
+#[test]
+fn should_rotate_all_keys_but_node_signing_key() {
+    CryptoConfig::run_with_temp_config(|config| {
+        let (node_pks, node_id) = get_node_keys_or_generate_if_missing(&config.crypto_root);
+
+        let rotated_pks = rotate_node_keys(&config, node_id);
+
+        assert_eq!(rotated_pks.version, node_pks.version + 1);
+        assert_eq!(rotated_pks.node_signing_pk, node_pks.node_signing_pk);
+        assert_ne!(
+            rotated_pks.committee_signing_pk,
+            node_pks.committee_signing_pk
+        );
+        assert_ne!(
+            rotated_pks.dkg_dealing_encryption_pk,
+            node_pks.dkg_dealing_encryption_pk
+        );
+        assert_ne!(rotated_pks.tls_certificate, node_pks.tls_certificate);
+        assert_eq!(
+            public_key_store::read_node_public_keys(&config.crypto_root).unwrap(),
+            rotated_pks
+        );
+        assert_eq!(
+            previous_node_public_keys(&config.crypto_root),
+            Some(node_pks)
+        );
+    })
+}
+
+#[test]
+fn should_keep_previous_and_rotated_secret_keys_while_rotation_is_pending() {
+    CryptoConfig::run_with_temp_config(|config| {
+        let (node_pks, node_id) = get_node_keys_or_generate_if_missing(&config.crypto_root);
+
+        let rotated_pks = rotate_node_keys(&config, node_id);
+
+        let csp = csp_for_config(&config);
+        for key_id in secret_key_ids(&node_pks)
+            .into_iter()
+            .chain(secret_key_ids(&rotated_pks))
+        {
+            assert!(csp.sks_contains(&key_id));
+        }
+    })
+}
+
+#[test]
+fn should_remove_replaced_secret_keys_when_completing_rotation() {
+    CryptoConfig::run_with_temp_config(|config| {
+        let (node_pks, node_id) = get_node_keys_or_generate_if_missing(&config.crypto_root);
+        let rotated_pks = rotate_node_keys(&config, node_id);
+
+        remove_previous_node_keys(&config);
+
+        let csp = csp_for_config(&config);
+        for key_id in secret_key_ids(&node_pks) {
+            assert!(!csp.sks_contains(&key_id));
+        }
+        for key_id in secret_key_ids(&rotated_pks) {
+            assert!(csp.sks_contains(&key_id));
+        }
+        assert!(csp.sks_contains(&csp.node_signing_key_id()));
+        assert_eq!(previous_node_public_keys(&config.crypto_root), None);
+    })
+}
+
+#[test]
+fn should_pick_up_rotated_keys_in_existing_csp() {
+    CryptoConfig::run_with_temp_config(|config| {
+        let (node_pks, node_id) = get_node_keys_or_generate_if_missing(&config.crypto_root);
+        let csp = csp_for_config(&config);
+        assert_eq!(csp.node_public_keys(), node_pks);
+
+        let rotated_pks = rotate_node_keys(&config, node_id);
+
+        assert_eq!(csp.node_public_keys(), rotated_pks);
+        assert_eq!(
+            csp.dkg_dealing_encryption_key_id(),
+            *secret_key_ids(&rotated_pks).last().unwrap()
+        );
+    })
+}
+
+#[test]
+fn should_do_nothing_when_completing_rotation_if_none_is_pending() {
+    CryptoConfig::run_with_temp_config(|config| {
+        let (node_pks, _node_id) = get_node_keys_or_generate_if_missing(&config.crypto_root);
+
+        remove_previous_node_keys(&config);
+
+        let csp = csp_for_config(&config);
+        for key_id in secret_key_ids(&node_pks) {
+            assert!(csp.sks_contains(&key_id));
+        }
+    })
+}
+
+#[test]
+#[should_panic(expected = "Cannot rotate node keys while a key rotation is pending")]
+fn should_panic_if_rotation_is_pending() {
+    CryptoConfig::run_with_temp_config(|config| {
+        let (_node_pks, node_id) = get_node_keys_or_generate_if_missing(&config.crypto_root);
+        rotate_node_keys(&config, node_id);
+
+        rotate_node_keys(&config, node_id);
+    })
+}
+
+#[test]
+fn should_sign_message_id_with_node_signing_key() {
+    CryptoConfig::run_with_temp_config(|config| {
+        let (node_pks, _node_id) = get_node_keys_or_generate_if_missing(&config.crypto_root);
+        let message_id = MessageId::from([42; 32]);
+
+        let signature = sign_message_id_with_node_signing_key(&config, &message_id).unwrap();
+
+        let public_key = PublicKeyBytes::try_from(&node_pks.node_signing_pk.unwrap()).unwrap();
+        let signature =
+            SignatureBytes(<[u8; SignatureBytes::SIZE]>::try_from(&signature[..]).unwrap());
+        assert!(ic_crypto_internal_basic_sig_ed25519::verify(
+            &signature,
+            &message_id.as_signed_bytes(),
+            &public_key
+        )
+        .is_ok());
+    })
+}
+
+#[test]
+fn should_report_age_of_node_public_keys() {
+    CryptoConfig::run_with_temp_config(|config| {
+        assert_eq!(node_public_keys_age(&config.crypto_root), None);
+
+        get_node_keys_or_generate_if_missing(&config.crypto_root);
+
+        assert!(node_public_keys_age(&config.crypto_root).unwrap() < Duration::from_secs(3600));
+    })
+}
@@ -1,14 +1,17 @@
-use crate::common::utils::generate_tls_keys;
+use crate::common::utils::{csp_for_config, generate_tls_keys, generate_tls_keys_using};
 use crate::common::utils::{
-    generate_committee_signing_keys, generate_dkg_dealing_encryption_keys,
-    generate_node_signing_keys,
+    generate_committee_signing_keys_using, generate_dkg_dealing_encryption_keys_using,
+    generate_node_signing_keys_using,
 };
 use crate::{CryptoComponent, CryptoComponentFatClient};
 use async_trait::async_trait;
 use ic_config::crypto::CryptoConfig;
 use ic_crypto_internal_csp::secret_key_store::proto_store::ProtoSecretKeyStore;
 use ic_crypto_internal_csp::secret_key_store::volatile_store::VolatileSecretKeyStore;
-use ic_crypto_internal_csp::{public_key_store, CryptoServiceProvider, Csp};
+use ic_crypto_internal_csp::{
+    public_key_store, CryptoServiceProvider, Csp, CspVaultServer, LocalCspServer,
+};
+use ic_crypto_internal_logmon::metrics::CryptoMetrics;
 use ic_crypto_tls_interfaces::TlsPublicKeyCert;
 use ic_crypto_tls_interfaces::{
     AllowedClients, AuthenticatedPeer, TlsClientHandshakeError, TlsHandshake,
@@ -50,6 +53,8 @@ use rand::SeedableRng;
 use rand_chacha::ChaChaRng;
 use std::collections::{BTreeMap, BTreeSet};
 use std::ops::Deref;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
 use std::sync::Arc;
 use tempfile::TempDir;
 use tokio::net::TcpStream;
@@ -113,7 +118,7 @@ impl TempCryptoComponent {
         let (config, temp_dir) = CryptoConfig::new_in_temp_dir();
         let crypto_root = temp_dir.path().to_path_buf();
         let dkg_dealing_encryption_pubkey =
-            generate_dkg_dealing_encryption_keys(&crypto_root, node_id);
+            generate_dkg_dealing_encryption_keys_using(&mut csp_for_config(&config), node_id);
         let node_pks = NodePublicKeys {
             version: 0,
             dkg_dealing_encryption_pk: Some(dkg_dealing_encryption_pubkey.to_owned()),
@@ -136,7 +141,25 @@ impl TempCryptoComponent {
         node_id: NodeId,
     ) -> (Self, TlsPublicKeyCert) {
         let (config, temp_dir) = CryptoConfig::new_in_temp_dir();
-        let tls_pubkey = generate_tls_keys(&temp_dir.path().to_path_buf(), node_id);
+        let tls_pubkey = generate_tls_keys(&config, node_id);
+
+        let temp_crypto =
+            TempCryptoComponent::new_with(registry_client, node_id, &config, temp_dir);
+        (temp_crypto, tls_pubkey)
+    }
+
+    /// Like `new_with_tls_key_generation`, but the secret keys are held by a
+    /// CSP vault that is reached over a Unix domain socket, as if it ran in a
+    /// separate process.
+    pub fn new_with_tls_key_generation_and_remote_csp_vault(
+        registry_client: Arc<dyn RegistryClient>,
+        node_id: NodeId,
+    ) -> (Self, TlsPublicKeyCert) {
+        let (config, temp_dir) = CryptoConfig::new_in_temp_dir();
+        let socket_path = temp_dir.path().join("csp_vault.sock");
+        start_csp_vault(&config.crypto_root, &socket_path);
+        let config = CryptoConfig::new_with_unix_socket_vault(config.crypto_root, socket_path);
+        let tls_pubkey = generate_tls_keys_using(&mut csp_for_config(&config), node_id);
 
         let temp_crypto =
             TempCryptoComponent::new_with(registry_client, node_id, &config, temp_dir);
@@ -154,46 +177,27 @@ impl TempCryptoComponent {
         selector: NodeKeysToGenerate,
     ) -> (Self, NodePublicKeys) {
         let (config, temp_dir) = CryptoConfig::new_in_temp_dir();
-        let temp_dir_path = std::path::PathBuf::from(
-            temp_dir
-                .path()
-                .to_str()
-                .expect("failed to convert path to string"),
-        );
-
-        let node_signing_pk = match selector.generate_node_signing_keys {
-            true => Some(generate_node_signing_keys(&temp_dir_path)),
-            false => None,
-        };
-        let committee_signing_pk = match selector.generate_committee_signing_keys {
-            true => Some(generate_committee_signing_keys(&temp_dir_path)),
-            false => None,
-        };
-        let dkg_dealing_encryption_pk = match selector.generate_dkg_dealing_encryption_keys {
-            true => Some(generate_dkg_dealing_encryption_keys(
-                &temp_dir_path,
-                node_id,
-            )),
-            false => None,
-        };
-        let tls_certificate = match selector.generate_tls_keys_and_certificate {
-            true => Some(generate_tls_keys(&temp_dir_path, node_id).to_proto()),
-            false => None,
-        };
-
-        let node_pubkeys = NodePublicKeys {
-            version: 0,
-            node_signing_pk,
-            committee_signing_pk,
-            dkg_dealing_encryption_pk,
-            tls_certificate,
-        };
-
+        let node_pubkeys = generate_node_keys_for_config(&config, node_id, selector);
         let temp_crypto =
             TempCryptoComponent::new_with(registry_client, node_id, &config, temp_dir);
         (temp_crypto, node_pubkeys)
     }
 
+    /// Like `new_with_node_keys_generation`, but the keys are generated
+    /// directly into the key store configured in `config`, e.g., a key store
+    /// that is sealed with a key-encryption key.
+    pub fn new_with_node_keys_generation_for_config(
+        registry_client: Arc<dyn RegistryClient>,
+        node_id: NodeId,
+        selector: NodeKeysToGenerate,
+        config: &CryptoConfig,
+        temp_dir: TempDir,
+    ) -> (Self, NodePublicKeys) {
+        let node_pubkeys = generate_node_keys_for_config(config, node_id, selector);
+        let temp_crypto = TempCryptoComponent::new_with(registry_client, node_id, config, temp_dir);
+        (temp_crypto, node_pubkeys)
+    }
+
     pub fn new_with(
         registry_client: Arc<dyn RegistryClient>,
         node_id: NodeId,
@@ -231,6 +235,60 @@ impl TempCryptoComponent {
     }
 }
 
+/// Generates the keys selected by `selector` with a single CSP for `config`,
+/// so that all secret keys end up in the same (possibly sealed) key store.
+fn generate_node_keys_for_config(
+    config: &CryptoConfig,
+    node_id: NodeId,
+    selector: NodeKeysToGenerate,
+) -> NodePublicKeys {
+    let mut csp = csp_for_config(config);
+    let node_signing_pk = match selector.generate_node_signing_keys {
+        true => Some(generate_node_signing_keys_using(&csp)),
+        false => None,
+    };
+    let committee_signing_pk = match selector.generate_committee_signing_keys {
+        true => Some(generate_committee_signing_keys_using(&csp)),
+        false => None,
+    };
+    let dkg_dealing_encryption_pk = match selector.generate_dkg_dealing_encryption_keys {
+        true => Some(generate_dkg_dealing_encryption_keys_using(
+            &mut csp, node_id,
+        )),
+        false => None,
+    };
+    let tls_certificate = match selector.generate_tls_keys_and_certificate {
+        true => Some(generate_tls_keys_using(&mut csp, node_id).to_proto()),
+        false => None,
+    };
+    NodePublicKeys {
+        version: 0,
+        node_signing_pk,
+        committee_signing_pk,
+        dkg_dealing_encryption_pk,
+        tls_certificate,
+    }
+}
+
+/// Serves a CSP vault with a secret key store in `crypto_root` on a Unix
+/// domain socket at `socket_path`, on a thread that runs until the process
+/// exits. Only the owner of `crypto_root`, i.e., the current user, may
+/// connect.
+fn start_csp_vault(crypto_root: &Path, socket_path: &Path) {
+    let local_csp_server = LocalCspServer::new_in_dir(
+        crypto_root,
+        None,
+        Arc::new(CryptoMetrics::none()),
+        no_op_logger(),
+    );
+    let current_uid = std::fs::metadata(crypto_root)
+        .expect("failed to read metadata of the crypto root")
+        .uid();
+    let server = CspVaultServer::bind(socket_path, current_uid, no_op_logger())
+        .expect("failed to bind CSP vault server");
+    std::thread::spawn(move || server.run(Arc::new(local_csp_server)));
+}
+
 /// Selects which keys should be generated for a `TempCryptoComponent`.
 #[derive(Clone)]
 pub struct NodeKeysToGenerate {
@@ -12,7 +12,7 @@ fn should_generate_all_keys_for_new_node() {
     CryptoConfig::run_with_temp_config(|config| {
         let (node_pks, _node_id) = get_node_keys_or_generate_if_missing(&config.crypto_root);
         assert!(all_node_keys_are_present(&node_pks));
-        let result = check_keys_locally(&config.crypto_root);
+        let result = check_keys_locally(&config);
         assert!(result.is_ok());
         let maybe_pks = result.unwrap();
         assert!(maybe_pks.is_some());
@@ -24,11 +24,11 @@ fn should_generate_all_keys_for_new_node() {
 #[test]
 fn should_generate_all_keys_for_a_node_without_public_keys() {
     CryptoConfig::run_with_temp_config(|config| {
-        let first_node_signing_pk = generate_node_signing_keys(&config.crypto_root);
+        let first_node_signing_pk = generate_node_signing_keys(&config);
         // first_node_signing_pk NOT saved.
         let (node_pks, _node_id) = get_node_keys_or_generate_if_missing(&config.crypto_root);
         assert!(all_node_keys_are_present(&node_pks));
-        let result = check_keys_locally(&config.crypto_root);
+        let result = check_keys_locally(&config);
         assert!(result.is_ok());
         let maybe_pks = result.unwrap();
         assert!(maybe_pks.is_some());
@@ -42,9 +42,11 @@ fn should_generate_all_keys_for_a_node_without_public_keys() {
 #[should_panic(expected = "inconsistent key material")]
 fn should_panic_if_node_has_inconsistent_keys() {
     CryptoConfig::run_with_temp_config(|config| {
-        let _node_signing_pk = Some(generate_node_signing_keys(&config.crypto_root));
+        let _node_signing_pk = Some(generate_node_signing_keys(&config));
         let different_crypto_root = config.crypto_root.join("different_subdir");
-        let different_node_signing_pk = Some(generate_node_signing_keys(&different_crypto_root));
+        let different_node_signing_pk = Some(generate_node_signing_keys(&CryptoConfig::new(
+            different_crypto_root,
+        )));
 
         // Store different_node_signing_pk in `config.crypto_root`.
         store_public_keys(
@@ -61,7 +63,7 @@ fn should_panic_if_node_has_inconsistent_keys() {
 #[test]
 fn check_keys_locally_returns_none_if_no_keys_are_present() {
     CryptoConfig::run_with_temp_config(|config| {
-        let result = check_keys_locally(&config.crypto_root);
+        let result = check_keys_locally(&config);
         assert!(result.is_ok());
         assert!(result.unwrap().is_none());
     })
@@ -70,9 +72,9 @@ fn check_keys_locally_returns_none_if_no_keys_are_present() {
 #[test]
 fn check_keys_locally_returns_none_if_no_public_keys_are_present() {
     CryptoConfig::run_with_temp_config(|config| {
-        let _node_signing_pk = generate_node_signing_keys(&config.crypto_root);
+        let _node_signing_pk = generate_node_signing_keys(&config);
         // _node_signing_pk NOT saved.
-        let result = check_keys_locally(&config.crypto_root);
+        let result = check_keys_locally(&config);
         assert!(result.is_ok());
         assert!(result.unwrap().is_none());
     })
@@ -81,9 +83,11 @@ fn check_keys_locally_returns_none_if_no_public_keys_are_present() {
 #[test]
 fn should_fail_check_keys_locally_if_no_matching_secret_key_is_present() {
     CryptoConfig::run_with_temp_config(|config| {
-        let node_signing_pk = Some(generate_node_signing_keys(&config.crypto_root));
+        let node_signing_pk = Some(generate_node_signing_keys(&config));
         let different_crypto_root = config.crypto_root.join("different_subdir");
-        let different_node_signing_pk = Some(generate_node_signing_keys(&different_crypto_root));
+        let different_node_signing_pk = Some(generate_node_signing_keys(&CryptoConfig::new(
+            different_crypto_root,
+        )));
 
         // Fail the check if `different_node_signing_pk` is stored.
         store_public_keys(
@@ -93,7 +97,7 @@ fn should_fail_check_keys_locally_if_no_matching_secret_key_is_present() {
                 ..Default::default()
             },
         );
-        let result = check_keys_locally(&config.crypto_root);
+        let result = check_keys_locally(&config);
         assert!(result.is_err());
 
         // Succeed the check if node_signing_pk is stored.
@@ -104,7 +108,7 @@ fn should_fail_check_keys_locally_if_no_matching_secret_key_is_present() {
                 ..Default::default()
             },
         );
-        let result = check_keys_locally(&config.crypto_root);
+        let result = check_keys_locally(&config);
         assert!(result.is_ok());
         assert!(result.unwrap().is_some());
     })
@@ -113,7 +117,7 @@ fn should_fail_check_keys_locally_if_no_matching_secret_key_is_present() {
 #[test]
 fn should_succeed_check_keys_locally_if_all_keys_are_present() {
     CryptoConfig::run_with_temp_config(|config| {
-        let node_signing_pk = Some(generate_node_signing_keys(&config.crypto_root));
+        let node_signing_pk = Some(generate_node_signing_keys(&config));
         store_public_keys(
             &config.crypto_root,
             &NodePublicKeys {
@@ -121,13 +125,99 @@ fn should_succeed_check_keys_locally_if_all_keys_are_present() {
                 ..Default::default()
             },
         );
-        let result = check_keys_locally(&config.crypto_root);
+        let result = check_keys_locally(&config);
         assert!(result.is_ok());
     })
 }
 
+mod sealed_secret_key_store {
+    use super::*;
+    use ic_config::crypto::KeyEncryptionKeySource;
+    use ic_registry_client::fake::FakeRegistryClient;
+    use ic_registry_common::proto_registry_data_provider::ProtoRegistryDataProvider;
+    use ic_test_utilities::types::ids::node_test_id;
+    use ic_types::crypto::KeyId;
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::TempDir;
+
+    const KEK_HEX: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+    #[test]
+    fn should_return_same_keys_when_reopening_sealed_key_store() {
+        let (config, _temp_dir) = sealed_config();
+
+        let (node_pks, node_id) = get_node_keys_or_generate_if_missing_for_config(&config);
+        let (reopened_node_pks, reopened_node_id) =
+            get_node_keys_or_generate_if_missing_for_config(&config);
+
+        assert!(all_node_keys_are_present(&node_pks));
+        assert_eq!(reopened_node_pks, node_pks);
+        assert_eq!(reopened_node_id, node_id);
+    }
+
+    #[test]
+    fn should_find_generated_keys_with_test_csp_for_sealed_key_store() {
+        let (config, _temp_dir) = sealed_config();
+        let (node_pks, _node_id) = get_node_keys_or_generate_if_missing_for_config(&config);
+
+        let csp = Csp::new_with_rng(ChaChaRng::seed_from_u64(42), &config);
+
+        assert_eq!(
+            check_keys_locally_using(&config.crypto_root, &csp).unwrap(),
+            Some(node_pks)
+        );
+    }
+
+    #[test]
+    fn should_generate_keys_for_temp_crypto_component_into_sealed_key_store() {
+        let (config, temp_dir) = sealed_config();
+        let registry = Arc::new(FakeRegistryClient::new(Arc::new(
+            ProtoRegistryDataProvider::new(),
+        )));
+
+        let (_crypto, node_pks) = TempCryptoComponent::new_with_node_keys_generation_for_config(
+            registry,
+            node_test_id(1),
+            NodeKeysToGenerate::only_node_signing_key(),
+            &config,
+            temp_dir,
+        );
+
+        let key_id = node_signing_key_id(&node_pks);
+        let csp = Csp::new_with_rng(ChaChaRng::seed_from_u64(42), &config);
+        assert!(csp.sks_contains(&key_id));
+    }
+
+    #[test]
+    #[should_panic(expected = "The SKS data is sealed, but no key-encryption key is configured")]
+    fn should_store_generated_keys_sealed() {
+        let (config, _temp_dir) = sealed_config();
+        let _ = get_node_keys_or_generate_if_missing_for_config(&config);
+
+        get_node_keys_or_generate_if_missing(&config.crypto_root);
+    }
+
+    fn sealed_config() -> (CryptoConfig, TempDir) {
+        let (mut config, temp_dir) = CryptoConfig::new_in_temp_dir();
+        let kek_path = temp_dir.path().join("sks_kek");
+        fs::write(&kek_path, KEK_HEX).unwrap();
+        fs::set_permissions(&kek_path, fs::Permissions::from_mode(0o600)).unwrap();
+        config.sks_key_encryption_key = Some(KeyEncryptionKeySource::File(kek_path));
+        (config, temp_dir)
+    }
+
+    fn node_signing_key_id(node_pks: &NodePublicKeys) -> KeyId {
+        let pk_proto = node_pks.node_signing_pk.clone().unwrap();
+        public_key_hash_as_key_id(&CspPublicKey::try_from(pk_proto).unwrap())
+    }
+}
+
 mod tls {
     use super::super::generate_tls_keys;
+    use ic_config::crypto::CryptoConfig;
     use ic_test_utilities::crypto::temp_dir::temp_dir;
     use ic_test_utilities::types::ids::node_test_id;
     use openssl::x509::X509VerifyResult;
@@ -139,7 +229,10 @@ mod tls {
     fn should_return_self_signed_certificate() {
         let temp_dir = temp_dir();
 
-        let cert = generate_tls_keys(&temp_dir.into_path(), node_test_id(NODE_ID));
+        let cert = generate_tls_keys(
+            &CryptoConfig::new(temp_dir.into_path()),
+            node_test_id(NODE_ID),
+        );
 
         let x509_cert = cert.as_x509();
         let public_key = x509_cert.public_key().unwrap();
@@ -151,7 +244,10 @@ mod tls {
     fn should_not_set_subject_alt_name() {
         let temp_dir = temp_dir();
 
-        let cert = generate_tls_keys(&temp_dir.into_path(), node_test_id(NODE_ID));
+        let cert = generate_tls_keys(
+            &CryptoConfig::new(temp_dir.into_path()),
+            node_test_id(NODE_ID),
+        );
 
         let x509_cert = cert.as_x509();
         let subject_alt_names = x509_cert.subject_alt_names();
@@ -162,7 +258,10 @@ mod tls {
     fn should_set_cert_issuer_and_subject_cn_as_node_id() {
         let temp_dir = temp_dir();
 
-        let cert = generate_tls_keys(&temp_dir.into_path(), node_test_id(NODE_ID));
+        let cert = generate_tls_keys(
+            &CryptoConfig::new(temp_dir.into_path()),
+            node_test_id(NODE_ID),
+        );
 
         let x509_cert = cert.as_x509();
         let issuer_cn = issuer_cn(x509_cert);
@@ -177,7 +276,10 @@ mod tls {
         const RFC5280_NO_WELL_DEFINED_CERTIFICATE_EXPIRATION_DATE: &str = "99991231235959Z";
         let temp_dir = temp_dir();
 
-        let cert = generate_tls_keys(&temp_dir.into_path(), node_test_id(NODE_ID));
+        let cert = generate_tls_keys(
+            &CryptoConfig::new(temp_dir.into_path()),
+            node_test_id(NODE_ID),
+        );
 
         let expected_not_after =
             Asn1Time::from_str_x509(RFC5280_NO_WELL_DEFINED_CERTIFICATE_EXPIRATION_DATE).unwrap();
@@ -209,7 +311,7 @@ mod committee_signing {
     #[test]
     fn should_generate_committee_signing_key_with_version_0() {
         CryptoConfig::run_with_temp_config(|config| {
-            let public_key = generate_committee_signing_keys(&config.crypto_root);
+            let public_key = generate_committee_signing_keys(&config);
             assert_eq!(public_key.version, 0);
         })
     }
@@ -217,7 +319,7 @@ mod committee_signing {
     #[test]
     fn should_generate_committee_signing_key_with_correct_algorithm() {
         CryptoConfig::run_with_temp_config(|config| {
-            let public_key = generate_committee_signing_keys(&config.crypto_root);
+            let public_key = generate_committee_signing_keys(&config);
             assert_eq!(public_key.algorithm, AlgorithmIdProto::MultiBls12381 as i32);
         })
     }
@@ -225,7 +327,7 @@ mod committee_signing {
     #[test]
     fn should_generate_committee_signing_key_with_non_empty_key_value() {
         CryptoConfig::run_with_temp_config(|config| {
-            let public_key = generate_committee_signing_keys(&config.crypto_root);
+            let public_key = generate_committee_signing_keys(&config);
             assert!(!public_key.key_value.is_empty());
         })
     }
@@ -233,7 +335,7 @@ mod committee_signing {
     #[test]
     fn should_generate_committee_signing_key_with_proof_data() {
         CryptoConfig::run_with_temp_config(|config| {
-            let public_key = generate_committee_signing_keys(&config.crypto_root);
+            let public_key = generate_committee_signing_keys(&config);
             assert!(public_key.proof_data.is_some());
         })
     }
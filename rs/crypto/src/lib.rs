@@ -169,9 +169,9 @@ impl LockableThresholdSigDataStore {
     }
 }
 
-/// Note that `R: 'static` is required so that `CspTlsHandshakeSignerProvider`
-/// can be implemented for [Csp]. See the documentation of the respective `impl`
-/// block for more details on the meaning of `R: 'static`.
+/// Note that `R: 'static` is required because [Csp] holds its local CSP
+/// server behind a `CspVault` trait object. `R: 'static` does not mean that `R`
+/// must _have_ a 'static lifetime, only that it is _bounded by_ one.
 impl<R: Rng + CryptoRng + Send + Sync + Clone + 'static>
     CryptoComponentFatClient<Csp<R, ProtoSecretKeyStore, VolatileSecretKeyStore>>
 {
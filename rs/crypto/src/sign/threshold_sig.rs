@@ -96,6 +96,9 @@ fn map_threshold_sign_error_or_panic(
                 key_id,
             }
         }
+        CspThresholdSignError::TransientInternalError { internal_error } => {
+            ThresholdSignError::TransientInternalError { internal_error }
+        }
         // Panic, since these would be implementation errors:
         CspThresholdSignError::UnsupportedAlgorithm { .. }
         | CspThresholdSignError::MalformedSecretKey { .. }
@@ -1,5 +1,6 @@
 use super::*;
 use async_trait::async_trait;
+use ic_crypto_internal_csp::api::CspTlsHandshakeSignerProvider;
 use ic_crypto_tls_interfaces::TlsPublicKeyCert;
 use ic_crypto_tls_interfaces::{
     AllowedClients, AuthenticatedPeer, MalformedPeerCertificateError, TlsClientHandshakeError,
@@ -36,15 +37,29 @@ where
             crypto.allowed_tls_clients => format!("{:?}", allowed_clients),
         );
         debug!(logger; crypto.description => "start",);
-        let result = server_handshake::perform_tls_server_handshake(
-            &self.csp,
-            self.node_id,
-            &self.registry_client,
-            tcp_stream,
-            allowed_clients,
-            registry_version,
-        )
-        .await;
+        let result = if self.csp.tls_secret_keys_are_remote() {
+            // The TLS secret key never leaves a remote CSP vault, so the
+            // handshake is performed with rustls, which signs via the vault.
+            rustls::server_handshake::perform_tls_server_handshake(
+                &self.csp,
+                self.node_id,
+                &self.registry_client,
+                tcp_stream,
+                allowed_clients,
+                registry_version,
+            )
+            .await
+        } else {
+            server_handshake::perform_tls_server_handshake(
+                &self.csp,
+                self.node_id,
+                &self.registry_client,
+                tcp_stream,
+                allowed_clients,
+                registry_version,
+            )
+            .await
+        };
         debug!(logger;
             crypto.description => "end",
             crypto.is_ok => result.is_ok(),
@@ -95,14 +110,27 @@ where
             crypto.allowed_tls_clients => "all clients allowed",
         );
         debug!(logger; crypto.description => "start",);
-        let result = server_handshake::perform_tls_server_handshake_without_client_auth(
-            &self.csp,
-            self.node_id,
-            &self.registry_client,
-            tcp_stream,
-            registry_version,
-        )
-        .await;
+        let result = if self.csp.tls_secret_keys_are_remote() {
+            // The TLS secret key never leaves a remote CSP vault, so the
+            // handshake is performed with rustls, which signs via the vault.
+            rustls::server_handshake::perform_tls_server_handshake_without_client_auth(
+                &self.csp,
+                self.node_id,
+                &self.registry_client,
+                tcp_stream,
+                registry_version,
+            )
+            .await
+        } else {
+            server_handshake::perform_tls_server_handshake_without_client_auth(
+                &self.csp,
+                self.node_id,
+                &self.registry_client,
+                tcp_stream,
+                registry_version,
+            )
+            .await
+        };
         debug!(logger;
             crypto.description => "end",
             crypto.is_ok => result.is_ok(),
@@ -152,15 +180,29 @@ where
             crypto.tls_server => format!("{}", server),
         );
         debug!(logger; crypto.description => "start",);
-        let result = client_handshake::perform_tls_client_handshake(
-            &self.csp,
-            self.node_id,
-            &self.registry_client,
-            tcp_stream,
-            server,
-            registry_version,
-        )
-        .await;
+        let result = if self.csp.tls_secret_keys_are_remote() {
+            // The TLS secret key never leaves a remote CSP vault, so the
+            // handshake is performed with rustls, which signs via the vault.
+            rustls::client_handshake::perform_tls_client_handshake(
+                &self.csp,
+                self.node_id,
+                &self.registry_client,
+                tcp_stream,
+                server,
+                registry_version,
+            )
+            .await
+        } else {
+            client_handshake::perform_tls_client_handshake(
+                &self.csp,
+                self.node_id,
+                &self.registry_client,
+                tcp_stream,
+                server,
+                registry_version,
+            )
+            .await
+        };
         debug!(logger;
             crypto.description => "end",
             crypto.is_ok => result.is_ok(),
@@ -68,6 +68,48 @@ mod handshakes {
         assert!(client_result.is_ok());
         assert_peer_node_eq(authenticated_client.unwrap(), CLIENT_ID_2);
     }
+
+    #[tokio::test]
+    async fn should_perform_tls_handshake_if_secret_keys_are_in_remote_csp_vault() {
+        let registry = TlsRegistry::new();
+        let server = OpenSslServer::builder(SERVER_ID_1)
+            .add_allowed_client(CLIENT_ID_1)
+            .with_remote_csp_vault()
+            .build(registry.get());
+        let client = OpenSslClient::builder(CLIENT_ID_1, SERVER_ID_1)
+            .with_remote_csp_vault()
+            .build(registry.get());
+        registry
+            .add_cert(SERVER_ID_1, server.cert())
+            .add_cert(CLIENT_ID_1, client.cert())
+            .update();
+
+        let (client_result, authenticated_client) =
+            tokio::join!(client.run(server.port()), server.run());
+
+        assert!(client_result.is_ok());
+        assert_peer_node_eq(authenticated_client.unwrap(), CLIENT_ID_1);
+    }
+
+    #[tokio::test]
+    async fn should_perform_tls_handshake_if_only_server_secret_keys_are_in_remote_csp_vault() {
+        let registry = TlsRegistry::new();
+        let server = OpenSslServer::builder(SERVER_ID_1)
+            .add_allowed_client(CLIENT_ID_1)
+            .with_remote_csp_vault()
+            .build(registry.get());
+        let client = OpenSslClient::builder(CLIENT_ID_1, SERVER_ID_1).build(registry.get());
+        registry
+            .add_cert(SERVER_ID_1, server.cert())
+            .add_cert(CLIENT_ID_1, client.cert())
+            .update();
+
+        let (client_result, authenticated_client) =
+            tokio::join!(client.run(server.port()), server.run());
+
+        assert!(client_result.is_ok());
+        assert_peer_node_eq(authenticated_client.unwrap(), CLIENT_ID_1);
+    }
 }
 
 mod server_with_certs {
@@ -25,3 +25,13 @@ pub fn temp_crypto_component_with_tls_keys(
 ) -> (TempCryptoComponent, TlsPublicKeyCert) {
     TempCryptoComponent::new_with_tls_key_generation(registry as Arc<_>, node_id)
 }
+
+pub fn temp_crypto_component_with_tls_keys_in_remote_csp_vault(
+    registry: Arc<FakeRegistryClient>,
+    node_id: NodeId,
+) -> (TempCryptoComponent, TlsPublicKeyCert) {
+    TempCryptoComponent::new_with_tls_key_generation_and_remote_csp_vault(
+        registry as Arc<_>,
+        node_id,
+    )
+}
@@ -1,5 +1,8 @@
 #![allow(clippy::unwrap_used)]
-use crate::tls_utils::{temp_crypto_component_with_tls_keys, REG_V1};
+use crate::tls_utils::{
+    temp_crypto_component_with_tls_keys, temp_crypto_component_with_tls_keys_in_remote_csp_vault,
+    REG_V1,
+};
 use ic_crypto::utils::TempCryptoComponent;
 use ic_crypto_tls_interfaces::TlsPublicKeyCert;
 use ic_crypto_tls_interfaces::{TlsClientHandshakeError, TlsHandshake, TlsReadHalf, TlsWriteHalf};
@@ -16,6 +19,7 @@ pub struct OpenSslClientBuilder {
     msg_expected_from_server: Option<String>,
     msg_for_server: Option<String>,
     expected_error_substring_when_reading_stream: Option<String>,
+    remote_csp_vault: bool,
 }
 
 impl OpenSslClientBuilder {
@@ -34,8 +38,17 @@ impl OpenSslClientBuilder {
         self
     }
 
+    pub fn with_remote_csp_vault(mut self) -> Self {
+        self.remote_csp_vault = true;
+        self
+    }
+
     pub fn build(self, registry: Arc<FakeRegistryClient>) -> OpenSslClient {
-        let (crypto, cert) = temp_crypto_component_with_tls_keys(registry, self.node_id);
+        let (crypto, cert) = if self.remote_csp_vault {
+            temp_crypto_component_with_tls_keys_in_remote_csp_vault(registry, self.node_id)
+        } else {
+            temp_crypto_component_with_tls_keys(registry, self.node_id)
+        };
         OpenSslClient {
             crypto,
             server_node_id: self.server_node_id,
@@ -69,6 +82,7 @@ impl OpenSslClient {
             msg_expected_from_server: None,
             msg_for_server: None,
             expected_error_substring_when_reading_stream: None,
+            remote_csp_vault: false,
         }
     }
 
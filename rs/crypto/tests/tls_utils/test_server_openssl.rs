@@ -1,5 +1,8 @@
 #![allow(clippy::unwrap_used)]
-use crate::tls_utils::{temp_crypto_component_with_tls_keys, REG_V1};
+use crate::tls_utils::{
+    temp_crypto_component_with_tls_keys, temp_crypto_component_with_tls_keys_in_remote_csp_vault,
+    REG_V1,
+};
 use ic_crypto::utils::TempCryptoComponent;
 use ic_crypto_tls_interfaces::TlsPublicKeyCert;
 use ic_crypto_tls_interfaces::{
@@ -21,6 +24,7 @@ pub struct OpenSslServerBuilder {
     msg_expected_from_client: Option<String>,
     allowed_nodes: Option<SomeOrAllNodes>,
     allowed_certs: HashSet<TlsPublicKeyCert>,
+    remote_csp_vault: bool,
 }
 
 impl OpenSslServerBuilder {
@@ -75,9 +79,18 @@ impl OpenSslServerBuilder {
         self
     }
 
+    pub fn with_remote_csp_vault(mut self) -> Self {
+        self.remote_csp_vault = true;
+        self
+    }
+
     pub fn build(self, registry: Arc<FakeRegistryClient>) -> OpenSslServer {
         let listener = std::net::TcpListener::bind(("0.0.0.0", 0)).expect("failed to bind");
-        let (crypto, cert) = temp_crypto_component_with_tls_keys(registry, self.node_id);
+        let (crypto, cert) = if self.remote_csp_vault {
+            temp_crypto_component_with_tls_keys_in_remote_csp_vault(registry, self.node_id)
+        } else {
+            temp_crypto_component_with_tls_keys(registry, self.node_id)
+        };
         let allowed_clients = AllowedClients::new(
             self.allowed_nodes
                 .unwrap_or_else(|| SomeOrAllNodes::Some(BTreeSet::new())),
@@ -116,6 +129,7 @@ impl OpenSslServer {
             msg_expected_from_client: None,
             allowed_nodes: None,
             allowed_certs: HashSet::new(),
+            remote_csp_vault: false,
         }
     }
 
@@ -39,8 +39,21 @@ pub fn parse_threshold_sig_key(pem_file: &Path) -> Result<ThresholdSigPublicKey>
     let decoded = base64::decode(&lines[1..n - 1].join(""))
         .map_err(|err| invalid_data_err(format!("failed to decode base64: {}", err)))?;
 
-    let pubkey_bytes = bls12_381::api::public_key_from_der(&decoded)
-        .map_err(|err| invalid_data_err(format!("failed to decode public key: {}", err)))?;
+    threshold_sig_public_key_from_der(&decoded)
+}
+
+/// Decode a DER-encoded threshold signature public key, e.g. the value of
+/// `/subnet/<subnet_id>/public_key` in the state tree.
+///
+/// # Error
+/// * `std::io::Error` if the encoded key is not BLS12-381.
+pub fn threshold_sig_public_key_from_der(der: &[u8]) -> Result<ThresholdSigPublicKey> {
+    let pubkey_bytes = bls12_381::api::public_key_from_der(der).map_err(|err| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("failed to decode public key: {}", err),
+        )
+    })?;
 
     Ok(ThresholdSigPublicKey::from(pubkey_bytes))
 }
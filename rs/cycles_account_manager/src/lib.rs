@@ -17,7 +17,7 @@ use ic_config::subnet_config::CyclesAccountManagerConfig;
 use ic_interfaces::execution_environment::CanisterOutOfCyclesError;
 use ic_logger::{info, ReplicaLogger};
 use ic_registry_subnet_type::SubnetType;
-use ic_replicated_state::{CanisterState, SystemState};
+use ic_replicated_state::{CanisterState, CyclesUseCase, SystemState};
 use ic_types::{
     ic00::{
         CanisterIdRecord, InstallCodeArgs, Method, Payload, SetControllerArgs, UpdateSettingsArgs,
@@ -130,12 +130,19 @@ impl CyclesAccountManager {
     }
 
     /// Returns the freezing threshold for this canister in Cycles.
+    ///
+    /// A canister that is frozen because it failed to pay for its resource
+    /// allocation and usage cannot spend any of its balance.
     pub fn freeze_threshold_cycles(
         &self,
         system_state: &SystemState,
         memory_usage: NumBytes,
         compute_allocation: ComputeAllocation,
     ) -> Cycles {
+        if system_state.is_frozen_out_of_cycles() {
+            return system_state.cycles_balance;
+        }
+
         let one_gib = 1 << 30;
 
         let memory_fee = {
@@ -203,13 +210,14 @@ impl CyclesAccountManager {
         canister_current_memory_usage: NumBytes,
         canister_compute_allocation: ComputeAllocation,
         cycles: Cycles,
+        use_case: CyclesUseCase,
     ) -> Result<(), CanisterOutOfCyclesError> {
         let threshold = self.freeze_threshold_cycles(
             system_state,
             canister_current_memory_usage,
             canister_compute_allocation,
         );
-        self.consume_with_threshold(system_state, cycles, threshold)
+        self.consume_with_threshold(system_state, cycles, threshold, use_case)
     }
 
     /// Updates the metric `consumed_cycles_since_replica_started` with the
@@ -220,6 +228,23 @@ impl CyclesAccountManager {
             .consumed_cycles_since_replica_started += NominalCycles::from_cycles(cycles);
     }
 
+    /// Updates the metrics `consumed_cycles_since_replica_started` and
+    /// `consumed_cycles_by_use_case` with the amount of cycles consumed for
+    /// `use_case`.
+    fn observe_consumed_cycles_with_use_case(
+        &self,
+        system_state: &mut SystemState,
+        cycles: Cycles,
+        use_case: CyclesUseCase,
+    ) {
+        self.observe_consumed_cycles(system_state, cycles);
+        *system_state
+            .canister_metrics
+            .consumed_cycles_by_use_case
+            .entry(use_case)
+            .or_default() += NominalCycles::from_cycles(cycles);
+    }
+
     /// Subtracts the corresponding cycles worth of the provided
     /// `num_instructions` from the canister's balance.
     ///
@@ -243,6 +268,7 @@ impl CyclesAccountManager {
                 canister_current_memory_usage,
                 canister_compute_allocation,
             ),
+            CyclesUseCase::Execution,
         )
     }
 
@@ -255,7 +281,7 @@ impl CyclesAccountManager {
     ) {
         let cycles_to_refund = self.config.ten_update_instructions_execution_fee
             * Cycles::from(num_instructions.get() / 10);
-        self.refund_cycles(system_state, cycles_to_refund);
+        self.refund_cycles(system_state, cycles_to_refund, CyclesUseCase::Execution);
     }
 
     /// Charges the canister for its compute allocation
@@ -273,7 +299,12 @@ impl CyclesAccountManager {
         let cycles = self.compute_allocation_cost(compute_allocation, duration);
 
         // Can charge all the way to the empty account (zero cycles)
-        self.consume_with_threshold(system_state, cycles, Cycles::from(0))
+        self.consume_with_threshold(
+            system_state,
+            cycles,
+            Cycles::from(0),
+            CyclesUseCase::ComputeAllocation,
+        )
     }
 
     /// The cost of compute allocation, per round
@@ -363,6 +394,12 @@ impl CyclesAccountManager {
         self.config.duration_between_allocation_charges
     }
 
+    /// How long a canister that failed to pay for its memory and compute
+    /// allocation stays frozen before it is uninstalled.
+    pub fn out_of_cycles_grace_period(&self) -> Duration {
+        self.config.out_of_cycles_grace_period
+    }
+
     ////////////////////////////////////////////////////////////////////////////
     //
     // Storage
@@ -389,7 +426,12 @@ impl CyclesAccountManager {
         let cycles_amount = self.memory_cost(bytes, duration);
 
         // Can charge all the way to the empty account (zero cycles)
-        self.consume_with_threshold(system_state, cycles_amount, Cycles::from(0))
+        self.consume_with_threshold(
+            system_state,
+            cycles_amount,
+            Cycles::from(0),
+            CyclesUseCase::Storage,
+        )
     }
 
     /// The cost of using `bytes` worth of memory.
@@ -437,21 +479,25 @@ impl CyclesAccountManager {
         // response) + the fee to send the request + the fee for the largest
         // possible response + the fee for executing the largest allowed
         // response when it eventually arrives.
-        let fee = self.config.xnet_call_fee
+        let xnet_fee = self.config.xnet_call_fee
             + self.config.xnet_byte_transmission_fee
                 * Cycles::from(request.payload_size_bytes().get())
             + self.config.xnet_byte_transmission_fee
-                * Cycles::from(MAX_INTER_CANISTER_PAYLOAD_IN_BYTES.get())
-            + self.execution_cost(self.max_num_instructions);
-        self.consume_with_threshold(
+                * Cycles::from(MAX_INTER_CANISTER_PAYLOAD_IN_BYTES.get());
+        let execution_fee = self.execution_cost(self.max_num_instructions);
+        let threshold = self.freeze_threshold_cycles(
             system_state,
-            fee,
-            self.freeze_threshold_cycles(
-                system_state,
-                canister_current_memory_usage,
-                canister_compute_allocation,
-            ),
-        )
+            canister_current_memory_usage,
+            canister_compute_allocation,
+        );
+        self.withdraw_with_threshold(system_state, xnet_fee + execution_fee, threshold)?;
+        self.observe_consumed_cycles_with_use_case(system_state, xnet_fee, CyclesUseCase::Xnet);
+        self.observe_consumed_cycles_with_use_case(
+            system_state,
+            execution_fee,
+            CyclesUseCase::Execution,
+        );
+        Ok(())
     }
 
     /// Refunds the cycles from the response. In particular, adds leftover
@@ -463,7 +509,7 @@ impl CyclesAccountManager {
         let extra_bytes = MAX_INTER_CANISTER_PAYLOAD_IN_BYTES - response.response_payload.size_of();
         let cycles_to_refund =
             self.config.xnet_byte_transmission_fee * Cycles::from(extra_bytes.get());
-        self.refund_cycles(system_state, cycles_to_refund);
+        self.refund_cycles(system_state, cycles_to_refund, CyclesUseCase::Xnet);
     }
 
     ////////////////////////////////////////////////////////////////////////////
@@ -504,11 +550,21 @@ impl CyclesAccountManager {
 
     /// Note that this function is made public only for the tests.
     #[doc(hidden)]
-    pub fn refund_cycles(&self, system_state: &mut SystemState, cycles: Cycles) {
+    pub fn refund_cycles(
+        &self,
+        system_state: &mut SystemState,
+        cycles: Cycles,
+        use_case: CyclesUseCase,
+    ) {
         system_state.cycles_balance += cycles;
         system_state
             .canister_metrics
             .consumed_cycles_since_replica_started -= NominalCycles::from_cycles(cycles);
+        *system_state
+            .canister_metrics
+            .consumed_cycles_by_use_case
+            .entry(use_case)
+            .or_default() -= NominalCycles::from_cycles(cycles);
     }
 
     /// Subtracts and consumes the cycles. This call should be used when the
@@ -518,9 +574,10 @@ impl CyclesAccountManager {
         system_state: &mut SystemState,
         cycles: Cycles,
         threshold: Cycles,
+        use_case: CyclesUseCase,
     ) -> Result<(), CanisterOutOfCyclesError> {
         self.withdraw_with_threshold(system_state, cycles, threshold)
-            .map(|()| self.observe_consumed_cycles(system_state, cycles))
+            .map(|()| self.observe_consumed_cycles_with_use_case(system_state, cycles, use_case))
     }
 
     /// Subtracts `cycles` worth of cycles from the canister's balance as long
@@ -623,13 +680,7 @@ impl CyclesAccountManager {
         canister: &mut CanisterState,
         duration_between_blocks: Duration,
     ) -> Result<(), CanisterOutOfCyclesError> {
-        let bytes_to_charge = match canister.memory_allocation() {
-            // The canister has explicitly asked for a memory allocation, so charge
-            // based on it accordingly.
-            MemoryAllocation::Reserved(bytes) => bytes,
-            // The canister uses best-effort memory allocation, so charge based on current usage.
-            MemoryAllocation::BestEffort => canister.memory_usage(),
-        };
+        let bytes_to_charge = Self::memory_bytes_to_charge(canister);
         if let Err(err) = self.charge_for_memory(
             &mut canister.system_state,
             bytes_to_charge,
@@ -660,6 +711,25 @@ impl CyclesAccountManager {
         }
         Ok(())
     }
+
+    /// Returns the cycles a canister burns per day for its memory and compute
+    /// allocation and usage, even if it does not execute any messages.
+    pub fn idle_cycles_burned_per_day(&self, canister: &CanisterState) -> Cycles {
+        let one_day = Duration::from_secs(24 * 60 * 60);
+        self.memory_cost(Self::memory_bytes_to_charge(canister), one_day)
+            + self.compute_allocation_cost(canister.compute_allocation(), one_day)
+    }
+
+    /// Returns the number of bytes of memory the canister is charged for.
+    fn memory_bytes_to_charge(canister: &CanisterState) -> NumBytes {
+        match canister.memory_allocation() {
+            // The canister has explicitly asked for a memory allocation, so charge
+            // based on it accordingly.
+            MemoryAllocation::Reserved(bytes) => bytes,
+            // The canister uses best-effort memory allocation, so charge based on current usage.
+            MemoryAllocation::BestEffort => canister.memory_usage(),
+        }
+    }
 }
 
 /// Encapsulates the payer and cost of inducting an ingress messages.
@@ -3,9 +3,10 @@ use ic_config::subnet_config::SubnetConfigs;
 use ic_cycles_account_manager::{IngressInductionCost, IngressInductionCostError};
 use ic_interfaces::execution_environment::CanisterOutOfCyclesError;
 use ic_registry_subnet_type::SubnetType;
-use ic_replicated_state::SystemState;
+use ic_replicated_state::{CyclesUseCase, OutOfCyclesStage, SystemState};
 use ic_test_utilities::{
     cycles_account_manager::CyclesAccountManagerBuilder,
+    mock_time,
     state::{new_canister_state, SystemStateBuilder},
     types::{
         ids::{canister_test_id, subnet_test_id, user_test_id},
@@ -595,11 +596,23 @@ fn cycles_withdraw_for_execution() {
 
     let amount = Cycles::from(initial_amount / 2);
     assert!(cycles_account_manager
-        .consume_cycles(&mut system_state, memory_usage, compute_allocation, amount)
+        .consume_cycles(
+            &mut system_state,
+            memory_usage,
+            compute_allocation,
+            amount,
+            CyclesUseCase::Execution,
+        )
         .is_ok());
     assert_eq!(system_state.cycles_balance, initial_cycles - amount);
     assert!(cycles_account_manager
-        .consume_cycles(&mut system_state, memory_usage, compute_allocation, amount)
+        .consume_cycles(
+            &mut system_state,
+            memory_usage,
+            compute_allocation,
+            amount,
+            CyclesUseCase::Execution,
+        )
         .is_err());
 
     let exec_cycles_max = system_state.cycles_balance - freeze_threshold_cycles;
@@ -617,7 +630,8 @@ fn cycles_withdraw_for_execution() {
             &mut system_state,
             memory_usage,
             compute_allocation,
-            exec_cycles_max
+            exec_cycles_max,
+            CyclesUseCase::Execution,
         )
         .is_ok());
     assert_eq!(system_state.cycles_balance, freeze_threshold_cycles);
@@ -642,7 +656,8 @@ fn cycles_withdraw_for_execution() {
             &mut system_state,
             memory_usage,
             compute_allocation,
-            exec_cycles_max
+            exec_cycles_max,
+            CyclesUseCase::Execution,
         )
         .is_err());
     assert!(cycles_account_manager
@@ -650,7 +665,8 @@ fn cycles_withdraw_for_execution() {
             &mut system_state,
             memory_usage,
             compute_allocation,
-            Cycles::from(10u64)
+            Cycles::from(10u64),
+            CyclesUseCase::Execution,
         )
         .is_err());
     assert!(cycles_account_manager
@@ -658,7 +674,8 @@ fn cycles_withdraw_for_execution() {
             &mut system_state,
             memory_usage,
             compute_allocation,
-            Cycles::from(1u64)
+            Cycles::from(1u64),
+            CyclesUseCase::Execution,
         )
         .is_err());
     assert!(cycles_account_manager
@@ -666,7 +683,8 @@ fn cycles_withdraw_for_execution() {
             &mut system_state,
             memory_usage,
             compute_allocation,
-            Cycles::from(0u64)
+            Cycles::from(0u64),
+            CyclesUseCase::Execution,
         )
         .is_ok());
     assert_eq!(system_state.cycles_balance, freeze_threshold_cycles);
@@ -738,6 +756,7 @@ fn consume_cycles_updates_consumed_cycles() {
             NumBytes::from(0),
             ComputeAllocation::default(),
             Cycles::from(1_000_000),
+            CyclesUseCase::Execution,
         )
         .unwrap();
     let consumed_cycles_after = system_state
@@ -760,7 +779,7 @@ fn verify_refund() {
         .consumed_cycles_since_replica_started = initial_consumed_cycles;
 
     let cycles = Cycles::from(100);
-    cycles_account_manager.refund_cycles(&mut system_state, cycles);
+    cycles_account_manager.refund_cycles(&mut system_state, cycles, CyclesUseCase::Execution);
     assert_eq!(system_state.cycles_balance, INITIAL_CYCLES + cycles);
     assert_eq!(
         system_state
@@ -769,3 +788,92 @@ fn verify_refund() {
         initial_consumed_cycles - NominalCycles::from(cycles)
     );
 }
+
+#[test]
+fn consumed_cycles_are_tracked_by_use_case() {
+    let mut system_state = SystemStateBuilder::new().build();
+    let cycles_account_manager = CyclesAccountManagerBuilder::new()
+        .with_subnet_type(SubnetType::Application)
+        .build();
+
+    cycles_account_manager
+        .withdraw_execution_cycles(
+            &mut system_state,
+            NumBytes::from(0),
+            ComputeAllocation::default(),
+            NumInstructions::from(1_000_000),
+        )
+        .unwrap();
+    cycles_account_manager
+        .refund_execution_cycles(&mut system_state, NumInstructions::from(500_000));
+    cycles_account_manager
+        .charge_for_memory(
+            &mut system_state,
+            NumBytes::from(1 << 30),
+            Duration::from_secs(1),
+        )
+        .unwrap();
+
+    let execution_cost = cycles_account_manager.execution_cost(NumInstructions::from(1_000_000))
+        - cycles_account_manager.execution_cost(NumInstructions::from(500_000))
+        + cycles_account_manager.execution_cost(NumInstructions::from(0));
+    let storage_cost =
+        cycles_account_manager.memory_cost(NumBytes::from(1 << 30), Duration::from_secs(1));
+    let consumed = &system_state.canister_metrics.consumed_cycles_by_use_case;
+    assert_eq!(
+        consumed.get(&CyclesUseCase::Execution),
+        Some(&NominalCycles::from(execution_cost))
+    );
+    assert_eq!(
+        consumed.get(&CyclesUseCase::Storage),
+        Some(&NominalCycles::from(storage_cost))
+    );
+    assert_eq!(consumed.get(&CyclesUseCase::Xnet), None);
+    assert_eq!(
+        system_state
+            .canister_metrics
+            .consumed_cycles_since_replica_started,
+        NominalCycles::from(execution_cost + storage_cost)
+    );
+}
+
+#[test]
+fn canister_frozen_out_of_cycles_cannot_spend_cycles() {
+    let cycles_account_manager = CyclesAccountManagerBuilder::new()
+        .with_subnet_type(SubnetType::Application)
+        .build();
+    let mut system_state = SystemStateBuilder::new()
+        .initial_cycles(INITIAL_CYCLES)
+        .build();
+    system_state.out_of_cycles_stage = Some(OutOfCyclesStage::Frozen { since: mock_time() });
+
+    let err = cycles_account_manager
+        .withdraw_execution_cycles(
+            &mut system_state,
+            NumBytes::from(0),
+            ComputeAllocation::default(),
+            NumInstructions::from(1_000_000),
+        )
+        .unwrap_err();
+    assert_eq!(err.threshold, INITIAL_CYCLES);
+    assert!(cycles_account_manager
+        .withdraw_cycles_for_transfer(
+            &mut system_state,
+            NumBytes::from(0),
+            ComputeAllocation::default(),
+            Cycles::from(1u64),
+        )
+        .is_err());
+    assert_eq!(system_state.cycles_balance, INITIAL_CYCLES);
+
+    // Once the canister is able to pay again, it can spend cycles.
+    system_state.out_of_cycles_stage = None;
+    assert!(cycles_account_manager
+        .withdraw_execution_cycles(
+            &mut system_state,
+            NumBytes::from(0),
+            ComputeAllocation::default(),
+            NumInstructions::from(1_000_000),
+        )
+        .is_ok());
+}
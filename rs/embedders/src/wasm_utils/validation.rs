@@ -289,6 +289,16 @@ fn get_valid_system_apis(
                 },
             )],
         ),
+        (
+            "call_with_best_effort_response",
+            vec![(
+                API_VERSION_IC0,
+                FunctionSignature {
+                    param_types: vec![ValueType::I32],
+                    return_type: vec![],
+                },
+            )],
+        ),
         (
             "call_cycles_add",
             vec![(
@@ -646,6 +656,13 @@ fn get_valid_exported_functions() -> HashMap<String, FunctionSignature> {
                 return_type: vec![],
             },
         ),
+        (
+            "canister_on_low_cycles",
+            FunctionSignature {
+                param_types: vec![],
+                return_type: vec![],
+            },
+        ),
     ];
 
     valid_exported_functions
@@ -41,6 +41,7 @@ fn test_wasmtime_system_api() {
             canister_memory_limit,
             subnet_available_memory: MAX_SUBNET_AVAILABLE_MEMORY.clone(),
             compute_allocation: ComputeAllocation::default(),
+            wasm_memory_limit: None,
         },
         no_op_logger(),
     );
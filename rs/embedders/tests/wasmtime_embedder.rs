@@ -23,6 +23,7 @@ fn execution_parameters() -> ExecutionParameters {
         canister_memory_limit: ic_types::NumBytes::from(4 << 30),
         subnet_available_memory: SubnetAvailableMemory::new(i64::MAX / 2),
         compute_allocation: ComputeAllocation::default(),
+        wasm_memory_limit: None,
     }
 }
 
@@ -78,6 +78,7 @@ fn test_api_for_update(
             canister_memory_limit,
             subnet_available_memory: MAX_SUBNET_AVAILABLE_MEMORY.clone(),
             compute_allocation: ComputeAllocation::default(),
+            wasm_memory_limit: None,
         },
         log,
     )
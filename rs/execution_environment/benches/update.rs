@@ -106,6 +106,7 @@ where
         canister_memory_limit: canister_state.memory_limit(NumBytes::new(std::u64::MAX)),
         subnet_available_memory: MAX_SUBNET_AVAILABLE_MEMORY.clone(),
         compute_allocation: canister_state.scheduler_state.compute_allocation,
+        wasm_memory_limit: canister_state.system_state.wasm_memory_limit,
     };
     ExecuteUpdateArgs(
         canister_state,
@@ -9,8 +9,8 @@ use ic_base_types::NumSeconds;
 use ic_cow_state::CowMemoryManager;
 use ic_cycles_account_manager::CyclesAccountManager;
 use ic_ic00_types::{
-    CanisterIdRecord, CanisterStatusResultV2, InstallCodeArgs, Method as Ic00Method,
-    SetControllerArgs, UpdateSettingsArgs,
+    CanisterCyclesStage, CanisterIdRecord, CanisterStatusResultV2, CyclesSpentMetrics,
+    InstallCodeArgs, MemoryMetrics, Method as Ic00Method, SetControllerArgs, UpdateSettingsArgs,
 };
 use ic_interfaces::execution_environment::{
     CanisterOutOfCyclesError, ExecutionParameters, HypervisorError, IngressHistoryWriter,
@@ -18,7 +18,8 @@ use ic_interfaces::execution_environment::{
 use ic_logger::{error, fatal, info, ReplicaLogger};
 use ic_registry_provisional_whitelist::ProvisionalWhitelist;
 use ic_replicated_state::{
-    CallOrigin, CanisterState, CanisterStatus, ReplicatedState, SchedulerState, SystemState,
+    num_bytes_from, num_bytes_try_from64, CallOrigin, CanisterState, CanisterStatus, CyclesUseCase,
+    OutOfCyclesStage, ReplicatedState, SchedulerState, SystemState,
 };
 use ic_state_layout::{CanisterLayout, CheckpointLayout, RwPolicy};
 use ic_types::{
@@ -289,6 +290,15 @@ impl CanisterManager {
         if let Some(freezing_threshold) = settings.freezing_threshold {
             canister.system_state.freeze_threshold = freezing_threshold;
         }
+        if let Some(low_cycles_threshold) = settings.low_cycles_threshold {
+            canister.system_state.low_cycles_threshold = low_cycles_threshold;
+        }
+        if let Some(wasm_memory_limit) = settings.wasm_memory_limit {
+            canister.system_state.wasm_memory_limit = match wasm_memory_limit.get() {
+                0 => None,
+                _ => Some(wasm_memory_limit),
+            };
+        }
     }
 
     /// Tries to apply the requested settings on the canister identified by
@@ -506,6 +516,13 @@ impl CanisterManager {
                 let new_wasm_hash = self.get_wasm_hash(&new_canister);
                 self.cycles_account_manager
                     .refund_execution_cycles(&mut new_canister.system_state, instructions_left);
+                // A canister that was uninstalled because it ran out of cycles
+                // is active again once it has code.
+                if new_canister.system_state.out_of_cycles_stage
+                    == Some(OutOfCyclesStage::Uninstalled)
+                {
+                    new_canister.system_state.out_of_cycles_stage = None;
+                }
                 state.put_canister_state(new_canister);
                 // We managed to create a new canister and will be dropping the
                 // older one. So we get rid of the previous heap to make sure it
@@ -695,6 +712,44 @@ impl CanisterManager {
             .copied()
             .collect::<Vec<PrincipalId>>();
 
+        let stable_memory_size = num_bytes_try_from64(canister.system_state.stable_memory.size)
+            .expect("could not convert from wasm pages to bytes");
+        let (wasm_memory_size, globals_size, wasm_module_size) = match &canister.execution_state {
+            // We use 8 bytes per global. The module size is the size of the
+            // Wasm binary, as the size of the compiled module differs between
+            // replicas and the reply must be the same on all of them.
+            Some(es) => (
+                num_bytes_from(es.wasm_memory.size),
+                NumBytes::from(8 * es.num_wasm_globals() as u64),
+                NumBytes::from(es.wasm_binary.binary.len() as u64),
+            ),
+            None => (NumBytes::from(0), NumBytes::from(0), NumBytes::from(0)),
+        };
+        let memory_metrics = MemoryMetrics::new(
+            wasm_memory_size,
+            stable_memory_size,
+            globals_size,
+            wasm_module_size,
+            canister.system_state.message_memory_usage(),
+        );
+
+        let consumed_cycles = &canister
+            .system_state
+            .canister_metrics
+            .consumed_cycles_by_use_case;
+        let cycles_spent_on = |use_case: CyclesUseCase| {
+            consumed_cycles
+                .get(&use_case)
+                .map_or(0, |cycles| cycles.get())
+        };
+        let cycles_spent = CyclesSpentMetrics::new(
+            cycles_spent_on(CyclesUseCase::Execution),
+            cycles_spent_on(CyclesUseCase::IngressInduction),
+            cycles_spent_on(CyclesUseCase::Xnet),
+            cycles_spent_on(CyclesUseCase::Storage),
+            cycles_spent_on(CyclesUseCase::ComputeAllocation),
+        );
+
         Ok(CanisterStatusResultV2::new(
             canister.status(),
             canister
@@ -708,6 +763,26 @@ impl CanisterManager {
             canister.scheduler_state.compute_allocation.as_percent(),
             Some(canister.memory_allocation().bytes().get()),
             canister.system_state.freeze_threshold.get(),
+            canister.system_state.low_cycles_threshold.get(),
+            canister
+                .system_state
+                .wasm_memory_limit
+                .map(|limit| limit.get()),
+            match canister.system_state.out_of_cycles_stage {
+                None => CanisterCyclesStage::Active,
+                Some(OutOfCyclesStage::Frozen { since }) => CanisterCyclesStage::Frozen {
+                    since: since.as_nanos_since_unix_epoch(),
+                    uninstall_at: (since
+                        + self.cycles_account_manager.out_of_cycles_grace_period())
+                    .as_nanos_since_unix_epoch(),
+                },
+                Some(OutOfCyclesStage::Uninstalled) => CanisterCyclesStage::Uninstalled,
+            },
+            memory_metrics,
+            self.cycles_account_manager
+                .idle_cycles_burned_per_day(canister)
+                .get(),
+            cycles_spent,
         ))
     }
 
@@ -726,7 +801,8 @@ impl CanisterManager {
             .canister_state_mut(&canister_id)
             .ok_or(CanisterManagerError::CanisterNotFound(canister_id))?;
 
-        let settings = CanisterSettings::new(Some(new_controller), None, None, None, None);
+        let settings =
+            CanisterSettings::new(Some(new_controller), None, None, None, None, None, None);
         self.update_settings(
             sender,
             settings,
@@ -1655,6 +1731,7 @@ pub fn uninstall_canister(
                             code: RejectCode::CanisterReject,
                             message: String::from("Canister has been uninstalled."),
                         }),
+                        deadline: call_context.deadline(),
                     }));
                 }
                 CallOrigin::CanisterQuery(_, _) | CallOrigin::Query(_) => fatal!(
@@ -1680,6 +1757,8 @@ struct ValidatedCanisterSettings {
     pub compute_allocation: Option<ComputeAllocation>,
     pub memory_allocation: Option<MemoryAllocation>,
     pub freezing_threshold: Option<NumSeconds>,
+    pub low_cycles_threshold: Option<Cycles>,
+    pub wasm_memory_limit: Option<NumBytes>,
 }
 
 impl TryFrom<(CanisterSettings, usize)> for ValidatedCanisterSettings {
@@ -1715,6 +1794,8 @@ impl TryFrom<(CanisterSettings, usize)> for ValidatedCanisterSettings {
             compute_allocation: settings.compute_allocation(),
             memory_allocation: settings.memory_allocation(),
             freezing_threshold: settings.freezing_threshold(),
+            low_cycles_threshold: settings.low_cycles_threshold(),
+            wasm_memory_limit: settings.wasm_memory_limit(),
         })
     }
 }
@@ -74,6 +74,7 @@ lazy_static! {
         canister_memory_limit: NumBytes::new(u64::MAX / 2),
         subnet_available_memory: MAX_SUBNET_AVAILABLE_MEMORY.clone(),
         compute_allocation: ComputeAllocation::default(),
+        wasm_memory_limit: None,
     };
 }
 
@@ -2808,6 +2809,8 @@ fn lower_memory_allocation_than_usage_fails() {
             None,
             Some(MemoryAllocation::try_from(NumBytes::from(2)).unwrap()),
             None,
+            None,
+            None,
         );
 
         let compute_allocation_used = state.total_compute_allocation();
@@ -2839,6 +2842,8 @@ fn test_install_when_updating_memory_allocation_via_canister_settings() {
             None,
             Some(MemoryAllocation::try_from(NumBytes::from(2)).unwrap()),
             None,
+            None,
+            None,
         );
         let canister_id = canister_manager
             .create_canister(
@@ -2881,6 +2886,8 @@ fn test_install_when_updating_memory_allocation_via_canister_settings() {
             None,
             Some(MemoryAllocation::try_from(NumBytes::from(MEMORY_CAPACITY.get() / 2)).unwrap()),
             None,
+            None,
+            None,
         );
 
         let compute_allocation_used = state.total_compute_allocation();
@@ -2929,6 +2936,8 @@ fn test_upgrade_when_updating_memory_allocation_via_canister_settings() {
                 MemoryAllocation::try_from(NumBytes::from(WASM_PAGE_SIZE_IN_BYTES + 100)).unwrap(),
             ),
             None,
+            None,
+            None,
         );
         let wat = r#"
         (module
@@ -3004,6 +3013,8 @@ fn test_upgrade_when_updating_memory_allocation_via_canister_settings() {
                     .unwrap(),
             ),
             None,
+            None,
+            None,
         );
 
         let compute_allocation_used = state.total_compute_allocation();
@@ -3080,13 +3091,115 @@ fn uninstall_code_can_be_invoked_by_governance_canister() {
     );
 }
 
+#[test]
+fn update_settings_sets_and_clears_wasm_memory_limit() {
+    with_setup(|canister_manager, mut state, subnet_id| {
+        let sender = canister_test_id(100).get();
+        let settings = CanisterSettings::new(None, None, None, None, None, None, None);
+        let canister_id = canister_manager
+            .create_canister(
+                sender,
+                subnet_id,
+                *INITIAL_CYCLES,
+                settings,
+                MAX_NUMBER_OF_CANISTERS,
+                &mut state,
+            )
+            .0
+            .unwrap();
+
+        for &(requested, expected) in &[(1u64 << 30, Some(1u64 << 30)), (0, None)] {
+            let settings = CanisterSettings::new(
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some(NumBytes::from(requested)),
+            );
+            let compute_allocation_used = state.total_compute_allocation();
+            let memory_allocation_used = state.total_memory_taken();
+            let mut canister = state.canister_state_mut(&canister_id).unwrap();
+            canister_manager
+                .update_settings(
+                    sender,
+                    settings,
+                    &mut canister,
+                    compute_allocation_used,
+                    memory_allocation_used,
+                )
+                .unwrap();
+
+            assert_eq!(
+                canister.system_state.wasm_memory_limit,
+                expected.map(NumBytes::from)
+            );
+            let status = canister_manager
+                .get_canister_status(sender, &mut canister)
+                .unwrap();
+            assert_eq!(status.settings().wasm_memory_limit(), expected);
+        }
+    });
+}
+
+#[test]
+fn get_canister_status_reports_the_wasm_binary_size_of_a_compiled_module() {
+    with_setup(|canister_manager, mut state, subnet_id| {
+        let wasm = ic_test_utilities::universal_canister::UNIVERSAL_CANISTER_WASM.to_vec();
+
+        let sender = canister_test_id(100).get();
+        let settings = CanisterSettings::new(None, None, None, None, None, None, None);
+        let canister_id = canister_manager
+            .create_canister(
+                sender,
+                subnet_id,
+                *INITIAL_CYCLES,
+                settings,
+                MAX_NUMBER_OF_CANISTERS,
+                &mut state,
+            )
+            .0
+            .unwrap();
+
+        canister_manager
+            .install_code(
+                InstallCodeContext {
+                    sender,
+                    canister_id,
+                    wasm_module: wasm.clone(),
+                    arg: vec![],
+                    compute_allocation: None,
+                    memory_allocation: None,
+                    mode: CanisterInstallMode::Install,
+                    query_allocation: QueryAllocation::default(),
+                },
+                &mut state,
+                EXECUTION_PARAMETERS.clone(),
+            )
+            .1
+            .unwrap();
+
+        // The module was compiled on this replica, but its compiled size is
+        // not reported, as it may differ on other replicas.
+        let mut canister = state.canister_state_mut(&canister_id).unwrap();
+        let status = canister_manager
+            .get_canister_status(sender, &mut canister)
+            .unwrap();
+        assert_eq!(
+            status.memory_metrics().wasm_module_size(),
+            NumBytes::from(wasm.len() as u64)
+        );
+    })
+}
+
 #[test]
 fn test_install_when_setting_memory_allocation_to_zero() {
     with_setup(|canister_manager, mut state, subnet_id| {
         let wasm = ic_test_utilities::universal_canister::UNIVERSAL_CANISTER_WASM.to_vec();
 
         let sender = canister_test_id(100).get();
-        let settings = CanisterSettings::new(None, None, None, None, None);
+        let settings = CanisterSettings::new(None, None, None, None, None, None, None);
         let canister_id = canister_manager
             .create_canister(
                 sender,
@@ -3106,6 +3219,8 @@ fn test_install_when_setting_memory_allocation_to_zero() {
             None,
             Some(MemoryAllocation::try_from(NumBytes::from(0)).unwrap()),
             None,
+            None,
+            None,
         );
 
         let compute_allocation_used = state.total_compute_allocation();
@@ -3154,6 +3269,8 @@ fn test_upgrade_when_setting_memory_allocation_to_zero() {
             None,
             Some(MemoryAllocation::try_from(NumBytes::from(MEMORY_CAPACITY.get() / 2)).unwrap()),
             None,
+            None,
+            None,
         );
         let canister_id = canister_manager
             .create_canister(
@@ -3192,6 +3309,8 @@ fn test_upgrade_when_setting_memory_allocation_to_zero() {
             None,
             Some(MemoryAllocation::try_from(NumBytes::from(0)).unwrap()),
             None,
+            None,
+            None,
         );
 
         let compute_allocation_used = state.total_compute_allocation();
@@ -2,12 +2,16 @@ use ic_base_types::{NumBytes, NumSeconds};
 use ic_ic00_types::CanisterSettingsArgs;
 use ic_types::{
     user_error::{ErrorCode, UserError},
-    ComputeAllocation, InvalidComputeAllocationError, InvalidMemoryAllocationError,
+    ComputeAllocation, Cycles, InvalidComputeAllocationError, InvalidMemoryAllocationError,
     MemoryAllocation, PrincipalId,
 };
 use num_traits::cast::ToPrimitive;
 use std::convert::TryFrom;
 
+/// The largest Wasm memory limit that can be set: the size of a 32-bit Wasm
+/// heap.
+const MAX_WASM_MEMORY_LIMIT: u64 = 4 << 30;
+
 /// Struct used for decoding CanisterSettingsArgs
 #[derive(Default)]
 pub(crate) struct CanisterSettings {
@@ -16,6 +20,8 @@ pub(crate) struct CanisterSettings {
     compute_allocation: Option<ComputeAllocation>,
     memory_allocation: Option<MemoryAllocation>,
     freezing_threshold: Option<NumSeconds>,
+    low_cycles_threshold: Option<Cycles>,
+    wasm_memory_limit: Option<NumBytes>,
 }
 
 impl CanisterSettings {
@@ -25,6 +31,8 @@ impl CanisterSettings {
         compute_allocation: Option<ComputeAllocation>,
         memory_allocation: Option<MemoryAllocation>,
         freezing_threshold: Option<NumSeconds>,
+        low_cycles_threshold: Option<Cycles>,
+        wasm_memory_limit: Option<NumBytes>,
     ) -> Self {
         Self {
             controller,
@@ -32,6 +40,8 @@ impl CanisterSettings {
             compute_allocation,
             memory_allocation,
             freezing_threshold,
+            low_cycles_threshold,
+            wasm_memory_limit,
         }
     }
 
@@ -54,6 +64,15 @@ impl CanisterSettings {
     pub fn freezing_threshold(&self) -> Option<NumSeconds> {
         self.freezing_threshold
     }
+
+    pub fn low_cycles_threshold(&self) -> Option<Cycles> {
+        self.low_cycles_threshold
+    }
+
+    /// A limit of zero bytes means that the limit is removed.
+    pub fn wasm_memory_limit(&self) -> Option<NumBytes> {
+        self.wasm_memory_limit
+    }
 }
 
 impl TryFrom<CanisterSettingsArgs> for CanisterSettings {
@@ -83,12 +102,29 @@ impl TryFrom<CanisterSettingsArgs> for CanisterSettings {
             None => None,
         };
 
+        let low_cycles_threshold = match input.low_cycles_threshold {
+            Some(lct) => Some(Cycles::from(lct.0.to_u128().ok_or(
+                UpdateSettingsError::LowCyclesThresholdOutOfRange { provided: lct },
+            )?)),
+            None => None,
+        };
+
+        let wasm_memory_limit = match input.wasm_memory_limit {
+            Some(wml) => match wml.0.to_u64() {
+                Some(limit) if limit <= MAX_WASM_MEMORY_LIMIT => Some(NumBytes::from(limit)),
+                _ => return Err(UpdateSettingsError::WasmMemoryLimitOutOfRange { provided: wml }),
+            },
+            None => None,
+        };
+
         Ok(CanisterSettings::new(
             input.controller,
             input.controllers,
             compute_allocation,
             memory_allocation,
             freezing_threshold,
+            low_cycles_threshold,
+            wasm_memory_limit,
         ))
     }
 }
@@ -108,6 +144,8 @@ pub enum UpdateSettingsError {
     ComputeAllocation(InvalidComputeAllocationError),
     MemoryAllocation(InvalidMemoryAllocationError),
     FreezingThresholdOutOfRange { provided: candid::Nat },
+    LowCyclesThresholdOutOfRange { provided: candid::Nat },
+    WasmMemoryLimitOutOfRange { provided: candid::Nat },
 }
 
 impl From<UpdateSettingsError> for UserError {
@@ -136,6 +174,20 @@ impl From<UpdateSettingsError> for UserError {
                     provided
                 ),
             ),
+            UpdateSettingsError::LowCyclesThresholdOutOfRange { provided } => UserError::new(
+                ErrorCode::CanisterContractViolation,
+                format!(
+                    "Low cycles threshold expected to be in the range of [0..2^128-1], got {}",
+                    provided
+                ),
+            ),
+            UpdateSettingsError::WasmMemoryLimitOutOfRange { provided } => UserError::new(
+                ErrorCode::CanisterContractViolation,
+                format!(
+                    "Wasm memory limit expected to be in the range of [0..{}], got {}",
+                    MAX_WASM_MEMORY_LIMIT, provided
+                ),
+            ),
         }
     }
 }
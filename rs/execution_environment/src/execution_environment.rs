@@ -39,6 +39,7 @@ use ic_types::{
         is_subnet_message, CallbackId, Ingress, MessageId, Payload, RejectContext, Request,
         Response, SignedIngressContent, StopCanisterContext,
     },
+    methods::SystemMethod,
     user_error::{ErrorCode, RejectCode, UserError},
     CanisterId, CanisterStatusType, ComputeAllocation, Cycles, InstallCodeContext, NumBytes,
     NumInstructions, SubnetId, Time, UserId,
@@ -106,6 +107,21 @@ pub trait ExecutionEnvironment: Sync + Send {
         Result<NumBytes, CanisterHeartbeatError>,
     );
 
+    /// Executes the `canister_on_low_cycles` system method of a given canister.
+    fn execute_canister_on_low_cycles(
+        &self,
+        canister_state: CanisterState,
+        instructions_limit: NumInstructions,
+        routing_table: Arc<RoutingTable>,
+        subnet_records: Arc<BTreeMap<SubnetId, SubnetType>>,
+        time: Time,
+        subnet_available_memory: SubnetAvailableMemory,
+    ) -> (
+        CanisterState,
+        NumInstructions,
+        Result<NumBytes, CanisterHeartbeatError>,
+    );
+
     /// Look up the current amount of memory available on the subnet.
     /// EXC-185 will make this method obsolete.
     fn subnet_available_memory(&self, state: &ReplicatedState) -> i64;
@@ -170,6 +186,7 @@ impl ExecutionEnvironment for ExecutionEnvironmentImpl {
                             originator_reply_callback: request.sender_reply_callback,
                             refund: request.payment,
                             response_payload: response.response_payload,
+                            deadline: request.deadline,
                         });
                         (state, instructions_limit)
                     }
@@ -243,6 +260,7 @@ impl ExecutionEnvironment for ExecutionEnvironmentImpl {
                                 canister_memory_limit: self.config.max_canister_memory_size,
                                 subnet_available_memory,
                                 compute_allocation: ComputeAllocation::default(),
+                                wasm_memory_limit: None,
                             };
 
                             let (instructions_left, result) = self.canister_manager.install_code(
@@ -476,6 +494,7 @@ impl ExecutionEnvironment for ExecutionEnvironmentImpl {
                                 code: ic_types::user_error::RejectCode::CanisterReject,
                                 message: reject_message,
                             }),
+                            deadline: request.deadline,
                         });
                         return (state, instructions_limit);
                     }
@@ -688,6 +707,12 @@ impl ExecutionEnvironment for ExecutionEnvironmentImpl {
             }
 
             CanisterInputMessage::Ingress(ingress) => {
+                let mut span = ic_tracing::ingress_span(
+                    &ingress.message_id,
+                    "execution_environment.execute_ingress",
+                );
+                span.set_attribute("canister_id", ingress.receiver);
+                span.set_attribute("method_name", &ingress.method_name);
                 let memory_usage = canister.memory_usage();
                 let compute_allocation = canister.scheduler_state.compute_allocation;
                 if let Err(err) = self.cycles_account_manager.withdraw_execution_cycles(
@@ -697,6 +722,7 @@ impl ExecutionEnvironment for ExecutionEnvironmentImpl {
                     instructions_limit,
                 ) {
                     // Canister is out of cycles. Reject the request.
+                    span.set_attribute("result", "out_of_cycles");
                     let canister_id = canister.canister_id();
                     return ExecuteMessageResult {
                         canister,
@@ -755,7 +781,7 @@ impl ExecutionEnvironment for ExecutionEnvironmentImpl {
 
     fn execute_canister_heartbeat(
         &self,
-        mut canister: CanisterState,
+        canister: CanisterState,
         instructions_limit: NumInstructions,
         routing_table: Arc<RoutingTable>,
         subnet_records: Arc<BTreeMap<SubnetId, SubnetType>>,
@@ -766,55 +792,39 @@ impl ExecutionEnvironment for ExecutionEnvironmentImpl {
         NumInstructions,
         Result<NumBytes, CanisterHeartbeatError>,
     ) {
-        if canister.status() != CanisterStatusType::Running {
-            let status = canister.status();
-            return (
-                canister,
-                instructions_limit,
-                Err(CanisterHeartbeatError::CanisterNotRunning { status }),
-            );
-        }
-
-        let memory_usage = canister.memory_usage();
-        let compute_allocation = canister.scheduler_state.compute_allocation;
-        if let Err(err) = self.cycles_account_manager.withdraw_execution_cycles(
-            &mut canister.system_state,
-            memory_usage,
-            compute_allocation,
+        self.execute_canister_system_task(
+            SystemMethod::CanisterHeartbeat,
+            canister,
             instructions_limit,
-        ) {
-            return (
-                canister,
-                instructions_limit,
-                Err(CanisterHeartbeatError::OutOfCycles(err)),
-            );
-        }
-
-        let execution_parameters =
-            self.execution_parameters(&canister, instructions_limit, subnet_available_memory);
-
-        let (mut canister, num_instructions_left, result) =
-            self.hypervisor.execute_canister_heartbeat(
-                canister,
-                routing_table,
-                subnet_records,
-                time,
-                execution_parameters,
-            );
-
-        // Clone the `cycles_account_manager` to avoid having to require 'static
-        // lifetime bound on `self`.
-        let cycles_account_manager = Arc::clone(&self.cycles_account_manager);
-
-        // Refund the canister with any cycles left after message execution.
-        cycles_account_manager
-            .refund_execution_cycles(&mut canister.system_state, num_instructions_left);
-        let result = match result {
-            Ok(heap_delta) => Ok(heap_delta),
-            Err(err) => Err(CanisterHeartbeatError::CanisterExecutionFailed(err)),
-        };
+            routing_table,
+            subnet_records,
+            time,
+            subnet_available_memory,
+        )
+    }
 
-        (canister, num_instructions_left, result)
+    fn execute_canister_on_low_cycles(
+        &self,
+        canister: CanisterState,
+        instructions_limit: NumInstructions,
+        routing_table: Arc<RoutingTable>,
+        subnet_records: Arc<BTreeMap<SubnetId, SubnetType>>,
+        time: Time,
+        subnet_available_memory: SubnetAvailableMemory,
+    ) -> (
+        CanisterState,
+        NumInstructions,
+        Result<NumBytes, CanisterHeartbeatError>,
+    ) {
+        self.execute_canister_system_task(
+            SystemMethod::CanisterOnLowCycles,
+            canister,
+            instructions_limit,
+            routing_table,
+            subnet_records,
+            time,
+            subnet_available_memory,
+        )
     }
 
     fn max_canister_memory_size(&self) -> NumBytes {
@@ -832,6 +842,7 @@ impl ExecutionEnvironment for ExecutionEnvironmentImpl {
             canister_memory_limit: canister.memory_limit(self.config.max_canister_memory_size),
             subnet_available_memory,
             compute_allocation: canister.scheduler_state.compute_allocation,
+            wasm_memory_limit: canister.system_state.wasm_memory_limit,
         }
     }
 }
@@ -876,6 +887,74 @@ impl ExecutionEnvironmentImpl {
         }
     }
 
+    // Charges the canister for executing a system task, i.e.
+    // `canister_heartbeat` or `canister_on_low_cycles`, and executes it.
+    #[allow(clippy::too_many_arguments)]
+    fn execute_canister_system_task(
+        &self,
+        system_method: SystemMethod,
+        mut canister: CanisterState,
+        instructions_limit: NumInstructions,
+        routing_table: Arc<RoutingTable>,
+        subnet_records: Arc<BTreeMap<SubnetId, SubnetType>>,
+        time: Time,
+        subnet_available_memory: SubnetAvailableMemory,
+    ) -> (
+        CanisterState,
+        NumInstructions,
+        Result<NumBytes, CanisterHeartbeatError>,
+    ) {
+        if canister.status() != CanisterStatusType::Running {
+            let status = canister.status();
+            return (
+                canister,
+                instructions_limit,
+                Err(CanisterHeartbeatError::CanisterNotRunning { status }),
+            );
+        }
+
+        let memory_usage = canister.memory_usage();
+        let compute_allocation = canister.scheduler_state.compute_allocation;
+        if let Err(err) = self.cycles_account_manager.withdraw_execution_cycles(
+            &mut canister.system_state,
+            memory_usage,
+            compute_allocation,
+            instructions_limit,
+        ) {
+            return (
+                canister,
+                instructions_limit,
+                Err(CanisterHeartbeatError::OutOfCycles(err)),
+            );
+        }
+
+        let execution_parameters =
+            self.execution_parameters(&canister, instructions_limit, subnet_available_memory);
+
+        let (mut canister, num_instructions_left, result) = self.hypervisor.execute_system_task(
+            system_method,
+            canister,
+            routing_table,
+            subnet_records,
+            time,
+            execution_parameters,
+        );
+
+        // Clone the `cycles_account_manager` to avoid having to require 'static
+        // lifetime bound on `self`.
+        let cycles_account_manager = Arc::clone(&self.cycles_account_manager);
+
+        // Refund the canister with any cycles left after message execution.
+        cycles_account_manager
+            .refund_execution_cycles(&mut canister.system_state, num_instructions_left);
+        let result = match result {
+            Ok(heap_delta) => Ok(heap_delta),
+            Err(err) => Err(CanisterHeartbeatError::CanisterExecutionFailed(err)),
+        };
+
+        (canister, num_instructions_left, result)
+    }
+
     fn create_canister(
         &self,
         sender: PrincipalId,
@@ -1115,6 +1194,7 @@ impl ExecutionEnvironmentImpl {
             }
         };
         let call_origin = call_context.call_origin().clone();
+        let deadline = call_context.deadline();
         let is_call_context_deleted = call_context.is_deleted();
         let num_outstanding_calls = call_context_manager.outstanding_calls(call_context_id);
 
@@ -1205,6 +1285,7 @@ impl ExecutionEnvironmentImpl {
                         action,
                         caller_canister_id,
                         callback_id,
+                        deadline,
                     );
                     None
                 }
@@ -1289,6 +1370,7 @@ impl ExecutionEnvironmentImpl {
             originator_reply_callback: req.sender_reply_callback,
             refund: req.payment,
             response_payload: Payload::Reject(reject_context),
+            deadline: req.deadline,
         });
 
         ExecuteMessageResult {
@@ -1313,6 +1395,7 @@ impl ExecutionEnvironmentImpl {
     ) -> ExecuteMessageResult<CanisterState> {
         let sender = req.sender;
         let reply_callback = req.sender_reply_callback;
+        let deadline = req.deadline;
 
         let execution_parameters =
             self.execution_parameters(&canister, cycles, subnet_available_memory);
@@ -1326,7 +1409,7 @@ impl ExecutionEnvironmentImpl {
             execution_parameters,
         );
 
-        produce_inter_canister_response(&mut canister, action, sender, reply_callback);
+        produce_inter_canister_response(&mut canister, action, sender, reply_callback, deadline);
         ExecuteMessageResult {
             canister,
             num_instructions_left: cycles,
@@ -1370,6 +1453,7 @@ impl ExecutionEnvironmentImpl {
             originator_reply_callback: req.sender_reply_callback,
             refund: Cycles::zero(),
             response_payload,
+            deadline: req.deadline,
         });
         ExecuteMessageResult {
             canister,
@@ -1654,6 +1738,7 @@ impl ExecutionEnvironmentImpl {
                     originator_reply_callback: req.sender_reply_callback,
                     refund,
                     response_payload: payload,
+                    deadline: req.deadline,
                 };
 
                 state.push_subnet_output_response(response);
@@ -1732,6 +1817,7 @@ impl ExecutionEnvironmentImpl {
                             code: RejectCode::CanisterReject,
                             message: format!("Canister {}'s stop request cancelled", canister_id),
                         }),
+                        deadline: None,
                     };
                     state.push_subnet_output_response(response);
                 }
@@ -1936,6 +2022,7 @@ fn produce_inter_canister_response(
     action: CallContextAction,
     originator: CanisterId,
     reply_callback_id: CallbackId,
+    deadline: Option<Time>,
 ) {
     let response_payload_and_refund = match action {
         CallContextAction::NotYetResponded | CallContextAction::AlreadyResponded => None,
@@ -1975,6 +2062,7 @@ fn produce_inter_canister_response(
             originator_reply_callback: reply_callback_id,
             refund,
             response_payload,
+            deadline,
         });
     }
 }
@@ -178,7 +178,11 @@ impl Hypervisor {
         let call_context_id = system_state
             .call_context_manager_mut()
             .unwrap()
-            .new_call_context(CallOrigin::from(&request), incoming_cycles);
+            .new_call_context_with_deadline(
+                CallOrigin::from(&request),
+                incoming_cycles,
+                request.deadline(),
+            );
 
         let api_type = ApiType::update(
             time,
@@ -913,7 +917,52 @@ impl Hypervisor {
         time: Time,
         execution_parameters: ExecutionParameters,
     ) -> (CanisterState, NumInstructions, HypervisorResult<NumBytes>) {
-        let method = WasmMethod::System(SystemMethod::CanisterHeartbeat);
+        self.execute_system_task(
+            SystemMethod::CanisterHeartbeat,
+            canister,
+            routing_table,
+            subnet_records,
+            time,
+            execution_parameters,
+        )
+    }
+
+    /// Executes the `canister_on_low_cycles` system method.
+    ///
+    /// Returns the same as `execute_canister_heartbeat`.
+    #[allow(clippy::type_complexity)]
+    pub fn execute_canister_on_low_cycles(
+        &self,
+        canister: CanisterState,
+        routing_table: Arc<RoutingTable>,
+        subnet_records: Arc<BTreeMap<SubnetId, SubnetType>>,
+        time: Time,
+        execution_parameters: ExecutionParameters,
+    ) -> (CanisterState, NumInstructions, HypervisorResult<NumBytes>) {
+        self.execute_system_task(
+            SystemMethod::CanisterOnLowCycles,
+            canister,
+            routing_table,
+            subnet_records,
+            time,
+            execution_parameters,
+        )
+    }
+
+    // Executes a system method that is triggered by the system rather than by
+    // a message, i.e. `canister_heartbeat` or `canister_on_low_cycles`. Both
+    // run with the heartbeat system API and cannot reply.
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn execute_system_task(
+        &self,
+        system_method: SystemMethod,
+        canister: CanisterState,
+        routing_table: Arc<RoutingTable>,
+        subnet_records: Arc<BTreeMap<SubnetId, SubnetType>>,
+        time: Time,
+        execution_parameters: ExecutionParameters,
+    ) -> (CanisterState, NumInstructions, HypervisorResult<NumBytes>) {
+        let method = WasmMethod::System(system_method);
         let memory_usage = canister.memory_usage();
         let (execution_state, mut system_state, scheduler_state) = canister.into_parts();
 
@@ -1053,6 +1102,7 @@ impl Hypervisor {
                 canister_memory_limit: memory_usage,
                 subnet_available_memory: SubnetAvailableMemory::new(memory_usage.get() as i64),
                 compute_allocation: ComputeAllocation::zero(),
+                wasm_memory_limit: None,
             },
             self.cycles_account_manager.clone(),
         )
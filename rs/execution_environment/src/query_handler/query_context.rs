@@ -89,6 +89,7 @@ fn generate_response(request: Request, payload: Payload) -> Response {
         originator_reply_callback: request.sender_reply_callback,
         response_payload: payload,
         refund: Cycles::zero(),
+        deadline: request.deadline,
     }
 }
 
@@ -766,6 +767,7 @@ impl<'a> QueryContext<'a> {
                 originator_reply_callback: callback_id,
                 response_payload: payload,
                 refund: Cycles::zero(),
+                deadline: None,
             };
             self.outstanding_response = Some(response);
         };
@@ -925,6 +927,7 @@ impl<'a> QueryContext<'a> {
             canister_memory_limit: canister.memory_limit(self.max_canister_memory_size),
             subnet_available_memory: self.subnet_available_memory.clone(),
             compute_allocation: canister.scheduler_state.compute_allocation,
+            wasm_memory_limit: canister.system_state.wasm_memory_limit,
         }
     }
 }
@@ -131,6 +131,7 @@ fn universal_canister(
                 canister_memory_limit: MEMORY_CAPACITY,
                 subnet_available_memory: SubnetAvailableMemory::new(MEMORY_CAPACITY.get() as i64),
                 compute_allocation: ComputeAllocation::default(),
+                wasm_memory_limit: None,
             },
         )
         .1
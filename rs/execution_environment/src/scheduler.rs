@@ -16,7 +16,8 @@ use ic_registry_provisional_whitelist::ProvisionalWhitelist;
 use ic_registry_routing_table::RoutingTable;
 use ic_registry_subnet_type::SubnetType;
 use ic_replicated_state::{
-    canister_state::QUEUE_INDEX_NONE, CanisterState, CanisterStatus, ReplicatedState,
+    canister_state::QUEUE_INDEX_NONE, CanisterState, CanisterStatus, OutOfCyclesStage,
+    ReplicatedState,
 };
 use ic_types::{
     ic00::{EmptyBlob, InstallCodeArgs, Payload as _, IC_00},
@@ -234,7 +235,8 @@ fn filter_idle_canisters(
             }
             (canister.has_input()
                 || (heartbeat_handling.should_execute_heartbeat()
-                    && canister.exports_heartbeat_method()))
+                    && (canister.exports_heartbeat_method()
+                        || canister.system_state.low_cycles_notification_pending)))
                 && is_under_limit
         })
         .cloned()
@@ -456,6 +458,8 @@ impl SchedulerImpl {
         };
 
         for (message_id, status) in ingress_execution_results {
+            let mut span = ic_tracing::ingress_span(&message_id, "scheduler.record_status");
+            span.set_attribute("status", status.as_str());
             self.ingress_history_writer
                 .set_status(&mut state, message_id, status);
         }
@@ -635,6 +639,7 @@ impl SchedulerImpl {
                                 originator_reply_callback: reply_callback,
                                 refund: cycles,
                                 response_payload: Payload::Data(EmptyBlob::encode()),
+                                deadline: None,
                             };
                             state.push_subnet_output_response(response);
                         }
@@ -654,6 +659,8 @@ impl SchedulerImpl {
                 if ingress.expiry_time >= current_time {
                     true
                 } else {
+                    let _span =
+                        ic_tracing::ingress_span(&ingress.message_id, "scheduler.purge_expired");
                     self.metrics.expired_ingress_messages_count.inc();
                     let error = UserError::new(
                         ErrorCode::IngressMessageTimeout,
@@ -680,8 +687,32 @@ impl SchedulerImpl {
         state.put_canister_states(canisters);
     }
 
+    // Rejects all best-effort calls whose deadline expired without a response
+    // having been received.
+    fn time_out_best_effort_calls(&self, state: &mut ReplicatedState) {
+        let current_time = state.time();
+        for canister in state.canisters_iter_mut() {
+            let (timed_out, failed) = canister.system_state.time_out_callbacks(current_time);
+            self.metrics.timed_out_calls_count.inc_by(timed_out as u64);
+            if failed > 0 {
+                warn!(
+                    self.log,
+                    "Failed to time out {} best-effort calls of canister {}",
+                    failed,
+                    canister.canister_id()
+                );
+                self.metrics
+                    .failed_call_timeouts_count
+                    .inc_by(failed as u64);
+            }
+        }
+    }
+
     // Charge canisters for their resource allocation and usage. Canisters
-    // that did not manage to pay are uninstalled.
+    // that do not manage to pay are frozen, and uninstalled if they still
+    // cannot pay once the out of cycles grace period has elapsed. Canisters
+    // whose balance dropped below their low cycles threshold are marked to
+    // be notified.
     fn charge_canisters_for_resource_allocation_and_usage(&self, state: &mut ReplicatedState) {
         let duration_since_last_charge = state
             .metadata
@@ -700,32 +731,63 @@ impl SchedulerImpl {
 
         let state_path = state.root.clone();
         let state_time = state.time();
+        let grace_period = self.cycles_account_manager.out_of_cycles_grace_period();
         let mut all_rejects = Vec::new();
         for canister in state.canisters_iter_mut() {
-            if self
+            let charged = self
                 .cycles_account_manager
                 .charge_canister_for_resource_allocation_and_usage(
                     &self.log,
                     canister,
                     duration_since_last_charge,
                 )
-                .is_err()
-            {
-                all_rejects.push(uninstall_canister(
-                    &self.log,
-                    canister,
-                    &state_path,
-                    state_time,
-                ));
-                canister.scheduler_state.compute_allocation = ComputeAllocation::zero();
-                canister.system_state.memory_allocation = MemoryAllocation::BestEffort;
+                .is_ok();
+            match (charged, canister.system_state.out_of_cycles_stage) {
+                (true, Some(OutOfCyclesStage::Frozen { .. })) => {
+                    canister.system_state.out_of_cycles_stage = None;
+                    info!(
+                        self.log,
+                        "Unfreezing canister {} because it is able to pay for its resources again",
+                        canister.canister_id()
+                    );
+                    self.metrics.num_canisters_unfrozen_after_top_up.inc();
+                }
+                (true, _) | (false, Some(OutOfCyclesStage::Uninstalled)) => {}
+                (false, None) => {
+                    canister.system_state.out_of_cycles_stage =
+                        Some(OutOfCyclesStage::Frozen { since: state_time });
+                    info!(
+                        self.log,
+                        "Freezing canister {} because it ran out of cycles",
+                        canister.canister_id()
+                    );
+                    self.metrics.num_canisters_frozen_out_of_cycles.inc();
+                }
+                (false, Some(OutOfCyclesStage::Frozen { since })) => {
+                    if state_time >= since + grace_period {
+                        all_rejects.push(uninstall_canister(
+                            &self.log,
+                            canister,
+                            &state_path,
+                            state_time,
+                        ));
+                        canister.scheduler_state.compute_allocation = ComputeAllocation::zero();
+                        canister.system_state.memory_allocation = MemoryAllocation::BestEffort;
+                        canister.system_state.out_of_cycles_stage =
+                            Some(OutOfCyclesStage::Uninstalled);
+
+                        info!(
+                            self.log,
+                            "Uninstalling canister {} because it ran out of cycles",
+                            canister.canister_id()
+                        );
+                        self.metrics.num_canisters_uninstalled_out_of_cycles.inc();
+                    }
+                }
+            }
 
-                info!(
-                    self.log,
-                    "Uninstalling canister {} because it ran out of cycles",
-                    canister.canister_id()
-                );
-                self.metrics.num_canisters_uninstalled_out_of_cycles.inc();
+            if canister.system_state.check_low_cycles_threshold() {
+                self.metrics.low_cycles_notifications.inc();
             }
         }
 
@@ -829,6 +891,7 @@ impl Scheduler for SchedulerImpl {
             {
                 let _timer = self.metrics.round_preparation_ingress.start_timer();
                 self.purge_expired_ingress_messages(&mut state);
+                self.time_out_best_effort_calls(&mut state);
             }
 
             // See documentation around definition of `heap_delta_estimate` for an
@@ -1086,6 +1149,46 @@ fn execute_canisters_on_thread(
             only_track_system_errors,
         } = heartbeat_handling
         {
+            // Notify the canister that its cycles balance dropped below its
+            // low cycles threshold. The notification is delivered at most once
+            // per crossing, even if it fails.
+            if canister.system_state.low_cycles_notification_pending {
+                canister.system_state.low_cycles_notification_pending = false;
+                if canister.exports_on_low_cycles_method() {
+                    let (new_canister, num_instructions_left, result) = exec_env
+                        .execute_canister_on_low_cycles(
+                            canister,
+                            canister_execution_limits.instruction_limit_per_message,
+                            Arc::clone(&routing_table),
+                            Arc::clone(&subnet_records),
+                            time,
+                            subnet_available_memory.clone(),
+                        );
+                    let heap_delta = match result {
+                        Ok(heap_delta) => heap_delta,
+                        Err(err) => {
+                            info!(
+                                logger,
+                                "Error executing canister_on_low_cycles on canister {} with failure `{}`",
+                                new_canister.canister_id(),
+                                err;
+                                messaging.canister_id => new_canister.canister_id().to_string(),
+                            );
+                            NumBytes::from(0)
+                        }
+                    };
+                    let instructions_consumed = canister_execution_limits
+                        .instruction_limit_per_message
+                        - num_instructions_left;
+                    measurement_scope.add(instructions_consumed, NumMessages::from(1));
+                    canister = new_canister;
+                    total_instructions_executed += instructions_consumed;
+                    total_messages_executed.inc_assign();
+                    total_heap_delta += heap_delta;
+                    canister.scheduler_state.heap_delta_debit += heap_delta;
+                }
+            }
+
             if canister.exports_heartbeat_method() {
                 let measurement_scope = MeasurementScope::nested(
                     &metrics.round_inner_iteration_thread_heartbeat,
@@ -1158,6 +1261,13 @@ fn execute_canisters_on_thread(
             );
             let message = canister.pop_input().unwrap();
             let msg_info = message.to_string();
+            let mut span = match &message {
+                CanisterInputMessage::Ingress(ingress) => Some(ic_tracing::ingress_span(
+                    &ingress.message_id,
+                    "scheduler.execute_message",
+                )),
+                _ => None,
+            };
             let timer = metrics.msg_execution_duration.start_timer();
             let result = exec_env.execute_canister_message(
                 canister,
@@ -1171,6 +1281,9 @@ fn execute_canisters_on_thread(
             let instructions_consumed = canister_execution_limits.instruction_limit_per_message
                 - result.num_instructions_left;
             measurement_scope.add(instructions_consumed, NumMessages::from(1));
+            if let Some(span) = &mut span {
+                span.set_attribute("instructions_consumed", instructions_consumed.get());
+            }
             observe_instructions_consumed_per_message(
                 &logger,
                 &metrics,
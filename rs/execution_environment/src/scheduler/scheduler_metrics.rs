@@ -17,6 +17,8 @@ pub(super) struct SchedulerMetrics {
     pub(super) instructions_consumed_per_round: Histogram,
     pub(super) executable_canisters_per_round: Histogram,
     pub(super) expired_ingress_messages_count: IntCounter,
+    pub(super) timed_out_calls_count: IntCounter,
+    pub(super) failed_call_timeouts_count: IntCounter,
     pub(super) ingress_history_length: IntGauge,
     pub(super) msg_execution_duration: Histogram,
     pub(super) registered_canisters: IntGaugeVec,
@@ -33,6 +35,9 @@ pub(super) struct SchedulerMetrics {
     pub(super) inner_loop_consumed_non_zero_instructions_count: IntCounter,
     pub(super) inner_round_loop_consumed_max_instructions: IntCounter,
     pub(super) num_canisters_uninstalled_out_of_cycles: IntCounter,
+    pub(super) num_canisters_frozen_out_of_cycles: IntCounter,
+    pub(super) num_canisters_unfrozen_after_top_up: IntCounter,
+    pub(super) low_cycles_notifications: IntCounter,
     pub(super) round: ScopedMetrics,
     pub(super) round_preparation_duration: Histogram,
     pub(super) round_preparation_ingress: Histogram,
@@ -101,6 +106,17 @@ impl SchedulerMetrics {
                 "Total number of ingress messages that expired before \
                       reaching a terminal state.",
             ),
+            timed_out_calls_count: metrics_registry.int_counter(
+                "scheduler_timed_out_calls_count",
+                "Total number of best-effort calls rejected because their \
+                      deadline expired before a response was received.",
+            ),
+            failed_call_timeouts_count: metrics_registry.int_counter(
+                "scheduler_failed_call_timeouts_count",
+                "Total number of attempts to reject an expired best-effort call \
+                      that failed because no input queue slot was reserved for \
+                      its response.",
+            ),
             ingress_history_length: metrics_registry.int_gauge(
                 "replicated_state_ingress_history_length",
                 "Total number of entries kept in the ingress history.",
@@ -185,6 +201,22 @@ impl SchedulerMetrics {
                 "The number of canisters that were uninstalled because \
                       they ran out of cycles.",
             ),
+            num_canisters_frozen_out_of_cycles: metrics_registry.int_counter(
+                "scheduler_num_canisters_frozen_out_of_cycles",
+                "The number of canisters that were frozen because they \
+                      could not pay for their resource allocation and usage.",
+            ),
+            num_canisters_unfrozen_after_top_up: metrics_registry.int_counter(
+                "scheduler_num_canisters_unfrozen_after_top_up",
+                "The number of frozen canisters that were able to pay for \
+                      their resource allocation and usage again before their \
+                      grace period elapsed.",
+            ),
+            low_cycles_notifications: metrics_registry.int_counter(
+                "scheduler_low_cycles_notifications",
+                "The number of times a canister's cycles balance dropped \
+                      below its low cycles threshold.",
+            ),
             round: ScopedMetrics {
                 duration: duration_histogram(
                     "execution_round_duration_seconds",
@@ -1156,9 +1156,9 @@ fn dont_execute_any_canisters_if_not_enough_cycles() {
     );
 }
 
-// Creates an initial state with some canisters that contain very few cycles.
-// Ensures that after `execute_round` returns, the canisters have been
-// uninstalled.
+// Creates an initial state with some canisters that contain very few cycles and
+// have been frozen for the whole grace period. Ensures that after
+// `execute_round` returns, the canisters have been uninstalled.
 #[test]
 fn canisters_with_insufficient_cycles_are_uninstalled() {
     let num_instructions_consumed_per_msg = NumInstructions::from(5);
@@ -1195,14 +1195,19 @@ fn canisters_with_insufficient_cycles_are_uninstalled() {
             // that they cannot pay for their resource usage but also do not set
             // it to 0 as that is a simpler test.
             for i in 0..num_canisters {
-                let canister_state = CanisterStateBuilder::new()
+                let mut canister_state = CanisterStateBuilder::new()
                     .with_canister_id(canister_test_id(i))
                     .with_cycles(Cycles::from(100))
                     .with_wasm(vec![1; 1 << 30])
                     .build();
+                canister_state.system_state.out_of_cycles_stage =
+                    Some(OutOfCyclesStage::Frozen { since: UNIX_EPOCH });
                 state.put_canister_state(canister_state);
             }
-            state.metadata.time_of_last_allocation_charge = UNIX_EPOCH + Duration::from_secs(1);
+            state.metadata.time_of_last_allocation_charge = UNIX_EPOCH
+                + scheduler
+                    .cycles_account_manager
+                    .out_of_cycles_grace_period();
             state.metadata.batch_time = state.metadata.time_of_last_allocation_charge
                 + scheduler
                     .cycles_account_manager
@@ -1226,6 +1231,10 @@ fn canisters_with_insufficient_cycles_are_uninstalled() {
                     canister.system_state.memory_allocation,
                     MemoryAllocation::BestEffort
                 );
+                assert_eq!(
+                    canister.system_state.out_of_cycles_stage,
+                    Some(OutOfCyclesStage::Uninstalled)
+                );
             }
             assert_eq!(
                 scheduler
@@ -1240,6 +1249,193 @@ fn canisters_with_insufficient_cycles_are_uninstalled() {
     );
 }
 
+// Creates an initial state with some canisters that contain very few cycles.
+// Ensures that after `execute_round` returns, the canisters have been frozen
+// but their Wasm state is still there.
+#[test]
+fn canisters_with_insufficient_cycles_are_frozen_before_uninstalling() {
+    let num_canisters = 3;
+    let scheduler_test_fixture = SchedulerTestFixture {
+        scheduler_config: SchedulerConfig {
+            scheduler_cores: 1,
+            ..SchedulerConfig::application_subnet()
+        },
+        metrics_registry: MetricsRegistry::new(),
+        canister_num: num_canisters,
+        message_num_per_canister: 0,
+    };
+    let exec_env = default_exec_env_mock(
+        &scheduler_test_fixture,
+        0,
+        scheduler_test_fixture
+            .scheduler_config
+            .max_instructions_per_message,
+        NumBytes::new(0),
+    );
+    let exec_env = Arc::new(exec_env);
+
+    let ingress_history_writer = default_ingress_history_writer_mock(0);
+    let ingress_history_writer = Arc::new(ingress_history_writer);
+    scheduler_test(
+        &scheduler_test_fixture,
+        |scheduler| {
+            let mut state = get_initial_state(0, 0);
+            for i in 0..num_canisters {
+                let canister_state = CanisterStateBuilder::new()
+                    .with_canister_id(canister_test_id(i))
+                    .with_cycles(Cycles::from(100))
+                    .with_wasm(vec![1; 1 << 30])
+                    .build();
+                state.put_canister_state(canister_state);
+            }
+            state.metadata.time_of_last_allocation_charge = UNIX_EPOCH + Duration::from_secs(1);
+            state.metadata.batch_time = state.metadata.time_of_last_allocation_charge
+                + scheduler
+                    .cycles_account_manager
+                    .duration_between_allocation_charges();
+            let batch_time = state.metadata.batch_time;
+
+            scheduler.charge_canisters_for_resource_allocation_and_usage(&mut state);
+
+            for (_, canister) in state.canister_states.iter() {
+                assert!(canister.execution_state.is_some());
+                assert_eq!(
+                    canister.system_state.out_of_cycles_stage,
+                    Some(OutOfCyclesStage::Frozen { since: batch_time })
+                );
+            }
+            assert_eq!(
+                scheduler.metrics.num_canisters_frozen_out_of_cycles.get() as u64,
+                num_canisters
+            );
+            assert_eq!(
+                scheduler
+                    .metrics
+                    .num_canisters_uninstalled_out_of_cycles
+                    .get(),
+                0
+            );
+        },
+        ingress_history_writer,
+        exec_env,
+    );
+}
+
+#[test]
+fn frozen_canister_is_unfrozen_after_top_up() {
+    let scheduler_test_fixture = SchedulerTestFixture {
+        scheduler_config: SchedulerConfig {
+            scheduler_cores: 1,
+            ..SchedulerConfig::application_subnet()
+        },
+        metrics_registry: MetricsRegistry::new(),
+        canister_num: 1,
+        message_num_per_canister: 0,
+    };
+    let exec_env = default_exec_env_mock(
+        &scheduler_test_fixture,
+        0,
+        scheduler_test_fixture
+            .scheduler_config
+            .max_instructions_per_message,
+        NumBytes::new(0),
+    );
+    let exec_env = Arc::new(exec_env);
+
+    let ingress_history_writer = Arc::new(default_ingress_history_writer_mock(0));
+    scheduler_test(
+        &scheduler_test_fixture,
+        |scheduler| {
+            let mut canister_state = CanisterStateBuilder::new()
+                .with_canister_id(canister_test_id(0))
+                .with_cycles(*INITIAL_CYCLES)
+                .build();
+            canister_state.system_state.out_of_cycles_stage =
+                Some(OutOfCyclesStage::Frozen { since: UNIX_EPOCH });
+            let mut state = ReplicatedStateBuilder::new()
+                .with_canister(canister_state)
+                .build();
+            state.metadata.time_of_last_allocation_charge = UNIX_EPOCH + Duration::from_secs(1);
+            state.metadata.batch_time = state.metadata.time_of_last_allocation_charge
+                + scheduler
+                    .cycles_account_manager
+                    .duration_between_allocation_charges();
+
+            scheduler.charge_canisters_for_resource_allocation_and_usage(&mut state);
+
+            let canister = state.canister_state(&canister_test_id(0)).unwrap();
+            assert_eq!(canister.system_state.out_of_cycles_stage, None);
+            assert_eq!(
+                scheduler.metrics.num_canisters_unfrozen_after_top_up.get(),
+                1
+            );
+        },
+        ingress_history_writer,
+        exec_env,
+    );
+}
+
+#[test]
+fn low_cycles_notification_is_scheduled_once_per_crossing() {
+    let scheduler_test_fixture = SchedulerTestFixture {
+        scheduler_config: SchedulerConfig {
+            scheduler_cores: 1,
+            ..SchedulerConfig::application_subnet()
+        },
+        metrics_registry: MetricsRegistry::new(),
+        canister_num: 1,
+        message_num_per_canister: 0,
+    };
+    let exec_env = default_exec_env_mock(
+        &scheduler_test_fixture,
+        0,
+        scheduler_test_fixture
+            .scheduler_config
+            .max_instructions_per_message,
+        NumBytes::new(0),
+    );
+    let exec_env = Arc::new(exec_env);
+
+    let ingress_history_writer = Arc::new(default_ingress_history_writer_mock(0));
+    scheduler_test(
+        &scheduler_test_fixture,
+        |scheduler| {
+            let mut canister_state = CanisterStateBuilder::new()
+                .with_canister_id(canister_test_id(0))
+                .with_cycles(*INITIAL_CYCLES)
+                .build();
+            canister_state.system_state.low_cycles_threshold = *INITIAL_CYCLES + Cycles::new(1);
+            let mut state = ReplicatedStateBuilder::new()
+                .with_canister(canister_state)
+                .build();
+            let duration_between_allocation_charges = scheduler
+                .cycles_account_manager
+                .duration_between_allocation_charges();
+            state.metadata.time_of_last_allocation_charge = UNIX_EPOCH + Duration::from_secs(1);
+            state.metadata.batch_time =
+                state.metadata.time_of_last_allocation_charge + duration_between_allocation_charges;
+
+            scheduler.charge_canisters_for_resource_allocation_and_usage(&mut state);
+
+            let canister = state.canister_state_mut(&canister_test_id(0)).unwrap();
+            assert!(canister.system_state.below_low_cycles_threshold);
+            assert!(canister.system_state.low_cycles_notification_pending);
+            canister.system_state.low_cycles_notification_pending = false;
+
+            // Staying below the threshold does not schedule another notification.
+            state.metadata.batch_time += duration_between_allocation_charges;
+            scheduler.charge_canisters_for_resource_allocation_and_usage(&mut state);
+
+            let canister = state.canister_state(&canister_test_id(0)).unwrap();
+            assert!(canister.system_state.below_low_cycles_threshold);
+            assert!(!canister.system_state.low_cycles_notification_pending);
+            assert_eq!(scheduler.metrics.low_cycles_notifications.get(), 1);
+        },
+        ingress_history_writer,
+        exec_env,
+    );
+}
+
 #[test]
 fn can_execute_messages_with_just_enough_cycles() {
     // In this test we have 3 canisters with 1 message each and the maximum allowed
@@ -3058,7 +3254,9 @@ fn canisters_reject_open_call_contexts_when_forcibly_uninstalled() {
 
     // A canister gets charged based on the duration of time between two blocks,
     // which is the difference between the following two times.
-    let time_in_future = Time::from_nanos_since_unix_epoch(100000000000000);
+    // It also needs to exceed the out of cycles grace period, so that the
+    // frozen canister gets uninstalled.
+    let time_in_future = Time::from_nanos_since_unix_epoch(3_000_000_000_000_000);
     let time_now = Time::from_nanos_since_unix_epoch(1);
 
     let ingress_history_writer = Arc::new(default_ingress_history_writer_mock(0));
@@ -3102,6 +3300,11 @@ fn canisters_reject_open_call_contexts_when_forcibly_uninstalled() {
                 )
                 .build();
 
+            state
+                .canister_state_mut(&canister_test_id(1))
+                .unwrap()
+                .system_state
+                .out_of_cycles_stage = Some(OutOfCyclesStage::Frozen { since: time_now });
             state.metadata.time_of_last_allocation_charge = time_now;
             scheduler.charge_canisters_for_resource_allocation_and_usage(&mut state);
 
@@ -69,6 +69,7 @@ fn execution_parameters(
         canister_memory_limit: canister.memory_limit(NumBytes::new(u64::MAX / 2)),
         subnet_available_memory: MAX_SUBNET_AVAILABLE_MEMORY.clone(),
         compute_allocation: canister.scheduler_state.compute_allocation,
+        wasm_memory_limit: canister.system_state.wasm_memory_limit,
     }
 }
 
@@ -297,6 +298,7 @@ fn execute(
         canister_memory_limit: NumBytes::from(4 << 30),
         subnet_available_memory: MAX_SUBNET_AVAILABLE_MEMORY.clone(),
         compute_allocation: ComputeAllocation::default(),
+        wasm_memory_limit: None,
     };
 
     hypervisor_execute(
@@ -3144,6 +3146,85 @@ fn sys_api_call_update_available_memory_2() {
     });
 }
 
+const GROW_MEMORY_BY_TEN_PAGES_WAT: &str = r#"
+    (module
+      (func $grow
+        i32.const 10
+        memory.grow
+        drop
+      )
+      (memory (;0;) 1 20)
+      (export "memory" (memory 0))
+      (export "canister_pre_upgrade" (func $grow))
+      (export "canister_update test" (func $grow)))"#;
+
+#[test]
+// Growing memory past the Wasm memory limit traps in update calls.
+fn grow_memory_beyond_wasm_memory_limit_traps_in_update() {
+    with_hypervisor(|hypervisor, tmp_path| {
+        let execution_state = ExecutionStateBuilder::new(
+            wabt::wat2wasm(GROW_MEMORY_BY_TEN_PAGES_WAT).unwrap(),
+            tmp_path,
+        )
+        .build();
+        let mut canister = canister_from_exec_state(execution_state);
+        let limit = ic_replicated_state::num_bytes_from(NumWasmPages::from(5));
+        canister.system_state.wasm_memory_limit = Some(limit);
+
+        let req = IngressBuilder::new()
+            .method_name("test".to_string())
+            .source(user_test_id(24))
+            .build();
+        let (_, _, routing_table, subnet_records) = setup();
+        let execution_parameters = execution_parameters(&canister, MAX_NUM_INSTRUCTIONS);
+        let (_, _, action, _) = hypervisor.execute_update(
+            canister,
+            RequestOrIngress::Ingress(req),
+            mock_time(),
+            routing_table,
+            subnet_records,
+            execution_parameters,
+        );
+
+        assert_eq!(
+            action,
+            CallContextAction::Fail {
+                error: HypervisorError::WasmMemoryLimitExceeded {
+                    size: ic_replicated_state::num_bytes_from(NumWasmPages::from(11)),
+                    limit,
+                },
+                refund: Cycles::from(0),
+            }
+        );
+    });
+}
+
+#[test]
+// Upgrade hooks may grow memory past the Wasm memory limit, so that a
+// canister that reached the limit can still be upgraded.
+fn grow_memory_beyond_wasm_memory_limit_succeeds_in_pre_upgrade() {
+    with_hypervisor(|hypervisor, tmp_path| {
+        let execution_state = ExecutionStateBuilder::new(
+            wabt::wat2wasm(GROW_MEMORY_BY_TEN_PAGES_WAT).unwrap(),
+            tmp_path,
+        )
+        .build();
+        let mut canister = canister_from_exec_state(execution_state);
+        canister.system_state.wasm_memory_limit =
+            Some(ic_replicated_state::num_bytes_from(NumWasmPages::from(5)));
+        let execution_parameters = execution_parameters(&canister, MAX_NUM_INSTRUCTIONS);
+
+        let (_, _, res) = hypervisor.execute_canister_pre_upgrade(
+            canister,
+            test_caller(),
+            mock_time(),
+            execution_parameters,
+        );
+
+        assert!(res.is_ok());
+    });
+}
+
 #[test]
 fn sys_api_call_msg_cycles_available_for_ingress() {
     with_hypervisor(|hypervisor, tmp_path| {
@@ -3497,6 +3578,7 @@ fn test_system_method_is_callable(system_method: SystemMethod) {
             SystemMethod::CanisterInspectMessage => unimplemented!(),
             SystemMethod::Empty => unimplemented!(),
             SystemMethod::CanisterHeartbeat => unimplemented!("We don't need this test."),
+            SystemMethod::CanisterOnLowCycles => unimplemented!("We don't need this test."),
         };
 
         assert!(
@@ -3588,6 +3670,13 @@ fn test_non_existing_system_method(system_method: SystemMethod) {
                 mock_time(),
                 execution_parameters,
             ),
+            SystemMethod::CanisterOnLowCycles => hypervisor.execute_canister_on_low_cycles(
+                canister,
+                routing_table,
+                subnet_records,
+                mock_time(),
+                execution_parameters,
+            ),
         };
 
         assert!(
@@ -3628,6 +3717,11 @@ fn test_non_existing_canister_heartbeat() {
     test_non_existing_system_method(SystemMethod::CanisterHeartbeat);
 }
 
+#[test]
+fn test_non_existing_canister_on_low_cycles() {
+    test_non_existing_system_method(SystemMethod::CanisterOnLowCycles);
+}
+
 #[test]
 fn canister_init_can_set_mutable_globals() {
     with_hypervisor(|hypervisor, tmp_path| {
@@ -4106,6 +4200,37 @@ fn canister_heartbeat() {
     });
 }
 
+// Tests that canister_on_low_cycles is executed.
+#[test]
+fn canister_on_low_cycles() {
+    with_hypervisor(|hypervisor, tmp_path| {
+        let wasm = wabt::wat2wasm(
+            r#"
+            (module
+              (func (export "canister_on_low_cycles")
+                (i32.store (i32.const 10) (i32.const 10))
+              )
+              (memory (export "memory") 1))"#,
+        )
+        .unwrap();
+
+        let execution_state = ExecutionStateBuilder::new(wasm, tmp_path).build();
+        let canister = canister_from_exec_state(execution_state);
+        let (_, _, routing_table, subnet_records) = setup();
+        let execution_parameters = execution_parameters(&canister, MAX_NUM_INSTRUCTIONS);
+
+        let (_, num_instructions_left, result) = hypervisor.execute_canister_on_low_cycles(
+            canister,
+            routing_table,
+            subnet_records,
+            mock_time(),
+            execution_parameters,
+        );
+        assert_eq!(result.unwrap().get(), (PAGE_SIZE) as u64);
+        assert!(num_instructions_left < MAX_NUM_INSTRUCTIONS);
+    });
+}
+
 // Tests that execute_canister_heartbeat produces a heap delta.
 #[test]
 fn execute_canister_heartbeat_produces_heap_delta() {
@@ -23,8 +23,8 @@ use ic_registry_subnet_type::SubnetType;
 use ic_replicated_state::{
     canister_state::{ENFORCE_MESSAGE_MEMORY_USAGE, QUEUE_INDEX_NONE},
     testing::{CanisterQueuesTesting, ReplicatedStateTesting, SystemStateTesting},
-    CallContextManager, CallOrigin, CanisterState, CanisterStatus, ReplicatedState, SchedulerState,
-    SystemState,
+    CallContextManager, CallOrigin, CanisterState, CanisterStatus, CyclesUseCase, OutOfCyclesStage,
+    ReplicatedState, SchedulerState, SystemState,
 };
 use ic_test_utilities::state::get_stopping_canister_on_nns;
 use ic_test_utilities::{
@@ -50,8 +50,8 @@ use ic_types::{
     canonical_error::{not_found_error, permission_denied_error},
     ic00,
     ic00::{
-        CanisterIdRecord, CanisterStatusResultV2, EmptyBlob, InstallCodeArgs, Method,
-        Payload as Ic00Payload, IC_00,
+        CanisterCyclesStage, CanisterIdRecord, CanisterStatusResultV2, CyclesSpentMetrics,
+        EmptyBlob, InstallCodeArgs, MemoryMetrics, Method, Payload as Ic00Payload, IC_00,
     },
     ingress::{IngressStatus, WasmResult},
     messages::{
@@ -59,6 +59,7 @@ use ic_types::{
         Response, StopCanisterContext, MAX_RESPONSE_COUNT_BYTES,
     },
     methods::{Callback, WasmClosure},
+    nominal_cycles::NominalCycles,
     user_error::{ErrorCode, RejectCode, UserError},
     CanisterId, CanisterStatusType, ComputeAllocation, Cycles, MemoryAllocation, NumBytes,
     NumInstructions, PrincipalId, QueueIndex, RegistryVersion, SubnetId,
@@ -954,6 +955,7 @@ fn stopping_canister_rejects_requests() {
                         code: RejectCode::SysFatal,
                         message: format!("Canister {} is not running", canister_id),
                     }),
+                    deadline: None,
                 })
             );
         },
@@ -1054,6 +1056,7 @@ fn stopped_canister_rejects_requests() {
                         code: RejectCode::SysFatal,
                         message: format!("Canister {} is not running", canister_id),
                     }),
+                    deadline: None,
                 })
             );
         },
@@ -1388,6 +1391,18 @@ fn get_running_canister_status_from_another_canister() {
             ComputeAllocation::default().as_percent(),
             None,
             123,
+            0,
+            None,
+            CanisterCyclesStage::Active,
+            MemoryMetrics::new(
+                NumBytes::from(0),
+                NumBytes::from(0),
+                NumBytes::from(0),
+                NumBytes::from(0),
+                NumBytes::from(0),
+            ),
+            0,
+            CyclesSpentMetrics::new(0, 0, 0, 0, 0),
         ),
     )
 }
@@ -1412,6 +1427,18 @@ fn get_stopped_canister_status_from_another_canister() {
             ComputeAllocation::default().as_percent(),
             None,
             123,
+            0,
+            None,
+            CanisterCyclesStage::Active,
+            MemoryMetrics::new(
+                NumBytes::from(0),
+                NumBytes::from(0),
+                NumBytes::from(0),
+                NumBytes::from(0),
+                NumBytes::from(0),
+            ),
+            0,
+            CyclesSpentMetrics::new(0, 0, 0, 0, 0),
         ),
     );
 }
@@ -1436,6 +1463,106 @@ fn get_stopping_canister_status_from_another_canister() {
             ComputeAllocation::default().as_percent(),
             None,
             123,
+            0,
+            None,
+            CanisterCyclesStage::Active,
+            MemoryMetrics::new(
+                NumBytes::from(0),
+                NumBytes::from(0),
+                NumBytes::from(0),
+                NumBytes::from(0),
+                NumBytes::from(0),
+            ),
+            0,
+            CyclesSpentMetrics::new(0, 0, 0, 0, 0),
+        ),
+    );
+}
+
+#[test]
+fn get_frozen_canister_status_reports_cycles_stage() {
+    let controller = canister_test_id(1);
+    let mut canister = CanisterStateBuilder::new()
+        .with_status(CanisterStatusType::Running)
+        .with_controller(controller)
+        .with_cycles(INITIAL_CYCLES)
+        .with_freezing_threshold(123)
+        .build();
+    canister.system_state.low_cycles_threshold = Cycles::from(1_000u64);
+    canister.system_state.out_of_cycles_stage =
+        Some(OutOfCyclesStage::Frozen { since: mock_time() });
+    let grace_period = CyclesAccountManagerConfig::application_subnet().out_of_cycles_grace_period;
+    test_canister_status_helper(
+        canister,
+        CanisterStatusResultV2::new(
+            CanisterStatusType::Running,
+            None,
+            controller.get(),
+            vec![controller.get()],
+            NumBytes::from(0),
+            INITIAL_CYCLES.get(),
+            ComputeAllocation::default().as_percent(),
+            None,
+            123,
+            1_000,
+            None,
+            CanisterCyclesStage::Frozen {
+                since: mock_time().as_nanos_since_unix_epoch(),
+                uninstall_at: (mock_time() + grace_period).as_nanos_since_unix_epoch(),
+            },
+            MemoryMetrics::new(
+                NumBytes::from(0),
+                NumBytes::from(0),
+                NumBytes::from(0),
+                NumBytes::from(0),
+                NumBytes::from(0),
+            ),
+            0,
+            CyclesSpentMetrics::new(0, 0, 0, 0, 0),
+        ),
+    );
+}
+
+#[test]
+fn get_canister_status_reports_cycles_spent_by_use_case() {
+    let controller = canister_test_id(1);
+    let mut canister = CanisterStateBuilder::new()
+        .with_status(CanisterStatusType::Running)
+        .with_controller(controller)
+        .with_cycles(INITIAL_CYCLES)
+        .with_freezing_threshold(123)
+        .build();
+    let consumed_cycles = &mut canister
+        .system_state
+        .canister_metrics
+        .consumed_cycles_by_use_case;
+    consumed_cycles.insert(CyclesUseCase::Execution, NominalCycles::from(100));
+    consumed_cycles.insert(CyclesUseCase::IngressInduction, NominalCycles::from(20));
+    consumed_cycles.insert(CyclesUseCase::Storage, NominalCycles::from(3));
+    test_canister_status_helper(
+        canister,
+        CanisterStatusResultV2::new(
+            CanisterStatusType::Running,
+            None,
+            controller.get(),
+            vec![controller.get()],
+            NumBytes::from(0),
+            INITIAL_CYCLES.get(),
+            ComputeAllocation::default().as_percent(),
+            None,
+            123,
+            0,
+            None,
+            CanisterCyclesStage::Active,
+            MemoryMetrics::new(
+                NumBytes::from(0),
+                NumBytes::from(0),
+                NumBytes::from(0),
+                NumBytes::from(0),
+                NumBytes::from(0),
+            ),
+            0,
+            CyclesSpentMetrics::new(100, 20, 0, 3, 0),
         ),
     );
 }
@@ -1754,7 +1881,8 @@ fn subnet_canister_request_unknown_method() {
                     code: RejectCode::DestinationInvalid,
                     message: "Management canister has no method \'non_existing_method\'"
                         .to_string(),
-                })
+                }),
+                deadline: None,
             })
         );
     });
@@ -1858,7 +1986,8 @@ fn subnet_canister_request_bad_candid_payload() {
                 response_payload: Payload::Reject(RejectContext {
                     code: RejectCode::CanisterError,
                     message: "Error decoding candid: Cannot parse header 010203".to_string()
-                })
+                }),
+                deadline: None,
             })
         );
     });
@@ -1991,7 +2120,8 @@ fn check_create_canister_fails(
                 message:
                 "Cannot create canister. Sender should be on the same subnet or on the NNS subnet."
                     .to_string()
-            })
+            }),
+            deadline: None,
         })
     );
 }
@@ -2241,7 +2371,8 @@ fn setup_initial_dkg_sender_not_on_nns() {
                         ic00::Method::SetupInitialDKG.to_string(),
                         sender,
                     )
-                })
+                }),
+                deadline: None,
             })
         );
     });
@@ -2585,6 +2716,7 @@ fn can_reject_a_request_when_canister_is_out_of_cycles() {
                         Cycles::from(0),
                     ),
                 }),
+                deadline: None,
             })
         );
             // Verify the canister's cycles balance is still the same.
@@ -27,6 +27,7 @@ fn execution_parameters() -> ExecutionParameters {
         canister_memory_limit: NumBytes::new(u64::MAX / 2),
         subnet_available_memory: SubnetAvailableMemory::new(i64::MAX / 2),
         compute_allocation: ComputeAllocation::default(),
+        wasm_memory_limit: None,
     }
 }
 
@@ -36,7 +36,7 @@ use ic_crypto_tls_interfaces::TlsHandshake;
 use ic_crypto_tree_hash::Path;
 use ic_interfaces::{
     consensus_pool::ConsensusPoolCache,
-    crypto::IngressSigVerifier,
+    crypto::{BasicSigner, IngressSigVerifier},
     execution_environment::{IngressFilterService, QueryExecutionService},
     p2p::IngressIngestionService,
     registry::RegistryClient,
@@ -53,10 +53,10 @@ use ic_types::{
     malicious_flags::MaliciousFlags,
     messages::{
         Blob, CertificateDelegation, HttpReadContent, HttpReadState, HttpReadStateResponse,
-        HttpRequestEnvelope, ReplicaHealthStatus,
+        HttpRequestEnvelope, QueryResponseContent, ReplicaHealthStatus,
     },
     time::current_time_and_expiry_time,
-    SubnetId,
+    NodeId, SubnetId,
 };
 use metrics::HttpHandlerMetrics;
 use rand::Rng;
@@ -141,6 +141,8 @@ struct HttpHandler {
     registry_client: Arc<dyn RegistryClient>,
     state_reader: Arc<dyn StateReader<State = ReplicatedState>>,
     validator: Arc<dyn IngressSigVerifier + Send + Sync>,
+    node_id: NodeId,
+    query_signer: Arc<dyn BasicSigner<QueryResponseContent> + Send + Sync>,
 
     body_parser: BodyParserService,
     ingress_filter: IngressFilterService,
@@ -263,6 +265,8 @@ pub async fn start_server(
     registry_client: Arc<dyn RegistryClient>,
     tls_handshake: Arc<dyn TlsHandshake + Send + Sync>,
     ingress_verifier: Arc<dyn IngressSigVerifier + Send + Sync>,
+    node_id: NodeId,
+    query_signer: Arc<dyn BasicSigner<QueryResponseContent> + Send + Sync>,
     subnet_id: SubnetId,
     nns_subnet_id: SubnetId,
     log: ReplicaLogger,
@@ -288,6 +292,8 @@ pub async fn start_server(
         query_handler,
         state_reader,
         ingress_verifier,
+        node_id,
+        query_signer,
         consensus_pool_cache,
         backup_spool_path,
         malicious_flags,
@@ -421,6 +427,8 @@ impl HttpHandler {
         query_handler: QueryExecutionService,
         state_reader: Arc<dyn StateReader<State = ReplicatedState>>,
         validator: Arc<dyn IngressSigVerifier + Send + Sync>,
+        node_id: NodeId,
+        query_signer: Arc<dyn BasicSigner<QueryResponseContent> + Send + Sync>,
         consensus_pool_cache: Arc<dyn ConsensusPoolCache>,
         backup_spool_path: Option<PathBuf>,
         malicious_flags: MaliciousFlags,
@@ -453,6 +461,8 @@ impl HttpHandler {
             registry_client,
             state_reader,
             validator,
+            node_id,
+            query_signer,
             body_parser: BodyParserService::new(
                 MAX_REQUEST_SIZE_BYTES,
                 MAX_REQUEST_RECEIVE_DURATION,
@@ -691,6 +701,8 @@ async fn route_to_handlers(
                 http_handler.query_handler.clone(),
                 Arc::clone(&http_handler.validator),
                 Arc::clone(&http_handler.registry_client),
+                http_handler.node_id,
+                Arc::clone(&http_handler.query_signer),
                 parsed_body,
                 http_handler.malicious_flags.clone(),
             )
@@ -3,20 +3,23 @@
 use crate::{common, map_box_error_to_canonical_error, ReplicaHealthStatus};
 use hyper::{Body, Response};
 use ic_interfaces::{
-    crypto::IngressSigVerifier, execution_environment::QueryExecutionService,
+    crypto::{BasicSigner, IngressSigVerifier},
+    execution_environment::QueryExecutionService,
     registry::RegistryClient,
 };
-use ic_logger::{trace, ReplicaLogger};
+use ic_logger::{trace, warn, ReplicaLogger};
 use ic_types::{
     canonical_error::{
         invalid_argument_error, permission_denied_error, unavailable_error, CanonicalError,
     },
     malicious_flags::MaliciousFlags,
     messages::{
-        CertificateDelegation, HttpReadContent, HttpRequest, HttpRequestEnvelope,
-        SignedRequestBytes, UserQuery,
+        Blob, CertificateDelegation, HttpReadContent, HttpRequest, HttpRequestEnvelope,
+        HttpSignedQueryResponse, NodeSignature, QueryResponseContent, SignedRequestBytes,
+        UserQuery,
     },
     time::current_time,
+    NodeId, RegistryVersion,
 };
 use ic_validator::get_authorized_canisters;
 use std::convert::TryFrom;
@@ -32,6 +35,8 @@ pub(crate) async fn handle(
     mut query_handler: QueryExecutionService,
     validator: Arc<dyn IngressSigVerifier + Send + Sync>,
     registry_client: Arc<dyn RegistryClient>,
+    node_id: NodeId,
+    query_signer: Arc<dyn BasicSigner<QueryResponseContent> + Send + Sync>,
     body: Vec<u8>,
     malicious_flags: MaliciousFlags,
 ) -> Result<Response<Body>, CanonicalError> {
@@ -64,12 +69,13 @@ pub(crate) async fn handle(
         }
     };
     let query = request.content();
+    let registry_version = registry_client.get_latest_version();
 
     match get_authorized_canisters(
         &request,
         validator.as_ref(),
         current_time(),
-        registry_client.get_latest_version(),
+        registry_version,
         &malicious_flags,
     ) {
         Ok(targets) => {
@@ -96,5 +102,125 @@ pub(crate) async fn handle(
         .call((query.clone(), delegation_from_nns))
         .await
         .map_err(|err| map_box_error_to_canonical_error(err))?;
-    Ok(common::cbor_response(&query_result))
+
+    let content = QueryResponseContent::new(query_result, request.id(), current_time());
+    let signatures = sign_query_response(
+        log,
+        &content,
+        node_id,
+        registry_version,
+        query_signer.as_ref(),
+    )?;
+    Ok(common::cbor_response(&HttpSignedQueryResponse {
+        response: content.response().clone(),
+        signatures,
+    }))
+}
+
+// Signs the query response with the signing key of this node, so that clients
+// can detect responses that were tampered with on the way or forged by a
+// single replica. If signing fails, no response is returned at all, as clients
+// that verify signatures could not tell an unsigned response from a forged one.
+fn sign_query_response(
+    log: &ReplicaLogger,
+    content: &QueryResponseContent,
+    node_id: NodeId,
+    registry_version: RegistryVersion,
+    query_signer: &(dyn BasicSigner<QueryResponseContent> + Send + Sync),
+) -> Result<Vec<NodeSignature>, CanonicalError> {
+    match query_signer.sign_basic(content, node_id, registry_version) {
+        Ok(signature) => Ok(vec![NodeSignature {
+            timestamp: content.timestamp().as_nanos_since_unix_epoch(),
+            signature: Blob(signature.get().0),
+            identity: Blob(node_id.get().into_vec()),
+        }]),
+        Err(err) => {
+            warn!(
+                log,
+                "Failed to sign the response to query {}: {}",
+                content.request_id(),
+                err
+            );
+            Err(unavailable_error(&format!(
+                "Failed to sign the query response: {}",
+                err
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_logger::replica_logger::no_op_logger;
+    use ic_test_utilities::{crypto::CryptoReturningOk, types::ids::node_test_id};
+    use ic_types::{
+        canonical_error::CanonicalErrorCode,
+        crypto::{AlgorithmId, BasicSigOf, CryptoError, CryptoResult, KeyId},
+        messages::{HttpQueryResponse, MessageId},
+    };
+
+    struct FailingSigner;
+
+    impl BasicSigner<QueryResponseContent> for FailingSigner {
+        fn sign_basic(
+            &self,
+            _message: &QueryResponseContent,
+            _signer: NodeId,
+            _registry_version: RegistryVersion,
+        ) -> CryptoResult<BasicSigOf<QueryResponseContent>> {
+            Err(CryptoError::SecretKeyNotFound {
+                algorithm: AlgorithmId::Ed25519,
+                key_id: KeyId::from([0; 32]),
+            })
+        }
+    }
+
+    fn query_response_content() -> QueryResponseContent {
+        QueryResponseContent::new(
+            HttpQueryResponse::Rejected {
+                reject_code: 4,
+                reject_message: "rejected".to_string(),
+            },
+            MessageId::from([1; 32]),
+            current_time(),
+        )
+    }
+
+    #[test]
+    fn query_response_is_signed_by_this_node() {
+        let content = query_response_content();
+        let signatures = sign_query_response(
+            &no_op_logger(),
+            &content,
+            node_test_id(1),
+            RegistryVersion::from(1),
+            &CryptoReturningOk::default(),
+        )
+        .unwrap();
+
+        assert_eq!(signatures.len(), 1);
+        assert_eq!(
+            signatures[0].identity,
+            Blob(node_test_id(1).get().into_vec())
+        );
+        assert_eq!(
+            signatures[0].timestamp,
+            content.timestamp().as_nanos_since_unix_epoch()
+        );
+    }
+
+    #[test]
+    fn query_response_is_not_returned_if_signing_fails() {
+        let err = sign_query_response(
+            &no_op_logger(),
+            &query_response_content(),
+            node_test_id(1),
+            RegistryVersion::from(1),
+            &FailingSigner,
+        )
+        .unwrap_err();
+
+        assert_eq!(err.code, CanonicalErrorCode::Unavailable);
+    }
 }
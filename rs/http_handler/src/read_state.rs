@@ -198,6 +198,8 @@ fn verify_paths(
             [b"canister", _canister_id, b"module_hash"] => {}
             [b"subnet", _subnet_id, b"public_key"] => {}
             [b"subnet", _subnet_id, b"canister_ranges"] => {}
+            [b"subnet", _subnet_id, b"node"] => {}
+            [b"subnet", _subnet_id, b"node", _node_id, b"public_key"] => {}
             [b"request_status", request_id] | [b"request_status", request_id, ..] => {
                 num_request_ids += 1;
 
@@ -86,6 +86,9 @@ pub(crate) async fn handle(
         }
     };
     let message_id = msg.id();
+    let mut span = ic_tracing::ingress_root_span(&message_id, "http_handler.submit");
+    span.set_attribute("canister_id", msg.canister_id());
+    span.set_attribute("method_name", msg.content().method_name());
     let registry_version = registry_client.get_latest_version();
     let (ingress_registry_settings, provisional_whitelist) =
         get_registry_data(&log, subnet_id, registry_version, registry_client)?;
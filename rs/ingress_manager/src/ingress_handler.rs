@@ -58,6 +58,9 @@ impl IngressHandler for IngressManager {
         change_set.extend(unvalidated_artifacts.map(|artifact| {
             let ingress_object = &artifact.message;
             let ingress_message = &ingress_object.signed_ingress;
+            let mut span =
+                ic_tracing::ingress_span(&ingress_object.message_id, "ingress_manager.validate");
+            span.set_attribute("peer_id", artifact.peer_id);
             let max_ingress_bytes_per_message =
                 ingress_message_settings.max_ingress_bytes_per_message;
             // If the message is too large, consider the ingress message invalid
@@ -70,6 +73,7 @@ impl IngressHandler for IngressManager {
                     ingress_message.reason => "message_too_large",
                     ingress_message.size => size as u64,
                 );
+                span.set_attribute("result", "message_too_large");
                 return RemoveFromUnvalidated(IngressMessageId::from(ingress_object));
             }
 
@@ -83,6 +87,7 @@ impl IngressHandler for IngressManager {
                     ingress_message.message_id => format!("{}", ingress_object.message_id),
                     ingress_message.reason => format!("unexpected_status_{}", status.as_str()),
                 );
+                span.set_attribute("result", "unexpected_status");
                 return RemoveFromUnvalidated(IngressMessageId::from(ingress_object));
             }
 
@@ -101,6 +106,7 @@ impl IngressHandler for IngressManager {
                     ingress_message.message_id => format!("{}", ingress_object.message_id),
                     ingress_message.reason => format!("auth_failure: {}", err),
                 );
+                span.set_attribute("result", "auth_failure");
                 return RemoveFromUnvalidated(IngressMessageId::from(ingress_object));
             }
 
@@ -109,6 +115,7 @@ impl IngressHandler for IngressManager {
                 "ingress_message_insert_validated";
                 ingress_message.message_id => format!("{}", ingress_object.message_id),
             );
+            span.set_attribute("result", "validated");
             let integrity_hash = ic_crypto::crypto_hash(ingress_message.binary()).get();
             MoveToValidated((
                 IngressMessageId::from(ingress_object),
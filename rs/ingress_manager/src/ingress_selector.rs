@@ -80,6 +80,8 @@ impl<'a> IngressSelector for IngressManager {
         let messages_in_payload = ingress_pool.select_validated(
             expiry_range,
             Box::new(move |ingress_obj| {
+                let mut span =
+                    ic_tracing::ingress_span(&ingress_obj.message_id, "ingress_selector.select");
                 let result = self.validate_ingress(
                     IngressMessageId::from(ingress_obj),
                     &ingress_obj.signed_ingress,
@@ -91,6 +93,7 @@ impl<'a> IngressSelector for IngressManager {
                     accumulated_size,
                     &mut cycles_needed,
                 );
+                span.set_attribute("selected", result.is_ok());
                 match result {
                     Ok(()) => {
                         num_messages += 1;
@@ -62,6 +62,8 @@ impl ErrorReplication for CryptoError {
             CryptoError::DkgTranscriptNotFound { .. } => true,
             // true, as the registry is guaranteed to be consistent across replicas
             CryptoError::RootSubnetPublicKeyNotFound { .. } => true,
+            // false, as the operation may succeed if retried
+            CryptoError::TransientInternalError { .. } => false,
         }
     }
 }
@@ -75,6 +75,8 @@ pub trait NiDkgAlgorithm {
     ///   in the secret key store.  This error indicates that
     ///   `NiDkgAlgorithm::load_transcript`  must be called prior to calling
     ///   this method.
+    /// * `DkgCreateDealingError::TransientInternalError` if the CSP vault
+    ///   could not be reached. Retrying may succeed.
     fn create_dealing(&self, config: &NiDkgConfig) -> Result<NiDkgDealing, DkgCreateDealingError>;
 
     /// Verifies a non-interactive DKG dealing.
@@ -154,6 +156,8 @@ pub trait NiDkgAlgorithm {
     ///   error, e.g. because the registry version is not available.
     /// * `DkgLoadTranscriptError::InvalidTranscript` if the transcript could
     ///   not be parsed.
+    /// * `DkgLoadTranscriptError::TransientInternalError` if the CSP vault
+    ///   could not be reached. Retrying may succeed.
     fn load_transcript(
         &self,
         transcript: &NiDkgTranscript,
@@ -188,6 +192,8 @@ pub trait NiDkgAlgorithm {
     ///   keys is still ensured.
     /// * `FsKeyNotInSecretKeyStoreError::FsKeyNotInSecretKeyStoreError`: If the
     ///   forward secure key to be updated is not found in the secret key store.
+    /// * `DkgKeyRemovalError::TransientInternalError`: If the CSP vault could
+    ///   not be reached. Retrying may succeed.
     fn retain_only_active_keys(
         &self,
         transcripts: HashSet<NiDkgTranscript>,
@@ -85,6 +85,8 @@ pub struct ExecutionParameters {
     pub canister_memory_limit: NumBytes,
     pub subnet_available_memory: SubnetAvailableMemory,
     pub compute_allocation: ComputeAllocation,
+    /// The controller-set limit on the canister's Wasm heap, if any.
+    pub wasm_memory_limit: Option<NumBytes>,
 }
 
 /// The data structure returned by
@@ -388,6 +390,16 @@ pub trait SystemApi {
     /// See https://sdk.dfinity.org/docs/interface-spec/index.html#system-api-call
     fn ic0_call_on_cleanup(&mut self, fun: u32, env: u32) -> HypervisorResult<()>;
 
+    /// Turns the call under construction into a best-effort call: if no
+    /// response is received within `timeout_seconds`, the call is rejected
+    /// with `SYS_UNKNOWN`. Can be called at most once between `ic0.call_new`
+    /// and `ic0.call_perform`.
+    ///
+    /// Timeouts above 300 seconds are silently capped at 300 seconds, so that
+    /// the subnets involved can drop the state of expired calls within a
+    /// bounded time.
+    fn ic0_call_with_best_effort_response(&mut self, timeout_seconds: u32) -> HypervisorResult<()>;
+
     /// (deprecated) Please use `ic0_call_cycles_add128` instead, as this API
     /// can only add a 64-bit value.
     ///
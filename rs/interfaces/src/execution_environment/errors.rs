@@ -1,6 +1,6 @@
 use ic_base_types::{CanisterIdError, PrincipalIdBlobParseError};
 use ic_types::{
-    methods::WasmMethod, user_error::UserError, CanisterId, CanisterStatusType, Cycles,
+    methods::WasmMethod, user_error::UserError, CanisterId, CanisterStatusType, Cycles, NumBytes,
 };
 use ic_wasm_types::{WasmEngineError, WasmInstrumentationError, WasmValidationError};
 use serde::{Deserialize, Serialize};
@@ -65,7 +65,7 @@ impl std::fmt::Display for CanisterOutOfCyclesError {
     }
 }
 
-/// Errors when executing `canister_heartbeat`.
+/// Errors when executing `canister_heartbeat` or `canister_on_low_cycles`.
 #[derive(Debug, Eq, PartialEq)]
 pub enum CanisterHeartbeatError {
     /// The canister isn't running.
@@ -139,6 +139,12 @@ pub enum HypervisorError {
     /// An attempt was made to grow the canister's memory above its memory
     /// allocation.
     OutOfMemory,
+    /// An attempt was made to grow the canister's Wasm heap to `size` bytes,
+    /// above the `wasm_memory_limit` set by its controllers.
+    WasmMemoryLimitExceeded {
+        size: NumBytes,
+        limit: NumBytes,
+    },
     /// An attempt to perform an operation that isn't allowed when the canister
     /// is stopped.
     CanisterStopped,
@@ -268,6 +274,13 @@ impl HypervisorError {
                     canister_id
                 ),
             ),
+            Self::WasmMemoryLimitExceeded { size, limit } => UserError::new(
+                E::CanisterOutOfMemory,
+                format!(
+                    "Canister {} attempted to grow its Wasm memory to {} bytes, above its wasm_memory_limit of {} bytes",
+                    canister_id, size, limit
+                ),
+            ),
             Self::CanisterStopped => UserError::new(
                 E::CanisterStopped,
                 format!("Canister {} is stopped", canister_id,),
@@ -332,6 +345,7 @@ impl HypervisorError {
             HypervisorError::CalledTrap(_) => "CalledTrap",
             HypervisorError::WasmModuleNotFound => "WasmModuleNotFound",
             HypervisorError::OutOfMemory => "OutOfMemory",
+            HypervisorError::WasmMemoryLimitExceeded { .. } => "WasmMemoryLimitExceeded",
             HypervisorError::CanisterStopped => "CanisterStopped",
             HypervisorError::InsufficientCyclesInCall { .. } => "InsufficientCyclesInCall",
             HypervisorError::InvalidPrincipalId(_) => "InvalidPrincipalId",
@@ -361,6 +375,7 @@ impl HypervisorError {
             | HypervisorError::CalledTrap(_)
             | HypervisorError::WasmModuleNotFound
             | HypervisorError::OutOfMemory
+            | HypervisorError::WasmMemoryLimitExceeded { .. }
             | HypervisorError::CanisterStopped
             | HypervisorError::InsufficientCyclesInCall {
                 available: _,
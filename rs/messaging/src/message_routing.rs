@@ -31,6 +31,7 @@ use ic_replicated_state::{
 };
 use ic_types::{
     batch::Batch,
+    crypto::KeyPurpose,
     ingress::IngressStatus,
     messages::MessageId,
     registry::RegistryClientError,
@@ -40,7 +41,7 @@ use ic_types::{
 use ic_utils::thread::JoinOnDrop;
 #[cfg(test)]
 use mockall::automock;
-use prometheus::{Histogram, HistogramVec, IntCounterVec, IntGauge};
+use prometheus::{Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge};
 use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use std::convert::TryFrom;
 use std::ops::Range;
@@ -69,6 +70,7 @@ const PHASE_COMMIT: &str = "commit";
 
 const METRIC_PROCESS_BATCH_DURATION: &str = "mr_process_batch_duration_seconds";
 const METRIC_PROCESS_BATCH_PHASE_DURATION: &str = "mr_process_batch_phase_duration_seconds";
+const METRIC_INVALID_NODE_PUBLIC_KEYS: &str = "mr_invalid_node_public_keys";
 
 /// Records the timestamp when all messages before the given index (down to the
 /// previous `MessageTime`) were first added to / learned about in a stream.
@@ -237,6 +239,9 @@ pub(crate) struct MessageRoutingMetrics {
     /// for the extra copies of the state that the protocol has to store for
     /// correct operations.
     canisters_memory_usage_bytes: IntGauge,
+    /// Number of member nodes found without a valid signing public key while
+    /// populating the network topology.
+    invalid_node_public_keys: IntCounter,
 }
 
 impl MessageRoutingMetrics {
@@ -272,6 +277,11 @@ impl MessageRoutingMetrics {
                 "canister_memory_usage_bytes",
                 "Total memory footprint of all canisters on this subnet.",
             ),
+            invalid_node_public_keys: metrics_registry.int_counter(
+                METRIC_INVALID_NODE_PUBLIC_KEYS,
+                "Number of member nodes found without a valid signing public key while \
+                    populating the network topology.",
+            ),
         }
     }
 }
@@ -451,11 +461,14 @@ impl BatchProcessorImpl {
                     }
                 };
 
+                let public_key = self.get_node_public_key(node_id, registry_version)?;
+
                 nodes.insert(
                     node_id,
                     NodeTopology {
                         ip_address: http_info.ip_addr,
                         http_port,
+                        public_key,
                     },
                 );
             }
@@ -484,6 +497,46 @@ impl BatchProcessorImpl {
         })
     }
 
+    // Returns the DER-encoded signing public key of the given node.
+    //
+    // A member node without a valid signing public key must not stall the
+    // subnet, so an empty key is certified for it instead (after logging a
+    // warning and incrementing `METRIC_INVALID_NODE_PUBLIC_KEYS`).
+    fn get_node_public_key(
+        &self,
+        node_id: NodeId,
+        registry_version: RegistryVersion,
+    ) -> Result<Vec<u8>, RegistryClientError> {
+        use ic_crypto::ed25519_public_key_to_der;
+        let public_key = match self.registry.get_crypto_key_for_node(
+            node_id,
+            KeyPurpose::NodeSigning,
+            registry_version,
+        )? {
+            Some(public_key) => public_key,
+            None => {
+                warn!(
+                    self.log,
+                    "No signing public key found for node {}. Certifying an empty key...", node_id
+                );
+                self.metrics.invalid_node_public_keys.inc();
+                return Ok(vec![]);
+            }
+        };
+        Ok(
+            ed25519_public_key_to_der(public_key.key_value).unwrap_or_else(|err| {
+                warn!(
+                    self.log,
+                    "Invalid signing public key for node {}: {:?}. Certifying an empty key...",
+                    node_id,
+                    err
+                );
+                self.metrics.invalid_node_public_keys.inc();
+                vec![]
+            }),
+        )
+    }
+
     fn get_nns_subnet_id(&self, registry_version: RegistryVersion) -> SubnetId {
         // Note: The following assumes that root == NNS subnet.
         match self.registry.get_root_subnet_id(registry_version) {
@@ -616,6 +669,21 @@ impl BatchProcessor for BatchProcessorImpl {
         let max_number_of_canisters =
             self.get_max_number_of_canisters(state.metadata.own_subnet_id, batch.registry_version);
 
+        // Each ingress message in the batch is traced until the state after
+        // the batch is committed.
+        let _ingress_spans: Vec<_> = batch
+            .payload
+            .ingress
+            .message_ids()
+            .iter()
+            .map(|id| {
+                let mut span =
+                    ic_tracing::ingress_span(&id.message_id, "message_routing.process_batch");
+                span.set_attribute("batch_number", batch.batch_number);
+                span
+            })
+            .collect();
+
         let batch_requires_full_state_hash = batch.requires_full_state_hash;
         let mut state_after_round = self.state_machine.execute_round(
             state,
@@ -966,13 +1034,25 @@ impl MessageRouting for MessageRoutingImpl {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::state_machine::MockStateMachine;
+    use ic_crypto::ed25519_public_key_to_der;
+    use ic_protobuf::registry::{
+        crypto::v1::{AlgorithmId as AlgorithmIdProto, PublicKey as PublicKeyProto},
+        node::v1::{connection_endpoint::Protocol, ConnectionEndpoint, NodeRecord},
+    };
+    use ic_registry_keys::{make_crypto_node_key, make_node_record_key, ROOT_SUBNET_ID_KEY};
     use ic_test_utilities::{
-        metrics::{fetch_int_counter_vec, metric_vec},
+        metrics::{fetch_int_counter, fetch_int_counter_vec, metric_vec},
         notification::{Notification, WaitResult},
+        registry::{setup_registry_non_final, SubnetRecordBuilder},
         state_manager::MockStateManager,
-        types::batch::BatchBuilder,
+        types::{
+            batch::BatchBuilder,
+            ids::{node_test_id, subnet_test_id},
+        },
         with_test_replica_logger,
     };
+    use ic_types::subnet_id_into_protobuf;
     use std::sync::Arc;
     use std::time::Duration;
 
@@ -1059,4 +1139,94 @@ mod tests {
             notification.notify(());
         });
     }
+
+    #[test]
+    fn network_topology_certifies_empty_key_for_node_without_valid_key() {
+        with_test_replica_logger(|log| {
+            let subnet_id = subnet_test_id(0);
+            let node_with_key = node_test_id(1);
+            let node_without_key = node_test_id(2);
+            let node_with_invalid_key = node_test_id(3);
+            let registry_version = RegistryVersion::from(1);
+
+            let (data_provider, registry) = setup_registry_non_final(
+                subnet_id,
+                vec![(
+                    registry_version.get(),
+                    SubnetRecordBuilder::from(&[
+                        node_with_key,
+                        node_without_key,
+                        node_with_invalid_key,
+                    ])
+                    .build(),
+                )],
+            );
+            data_provider
+                .add(
+                    ROOT_SUBNET_ID_KEY,
+                    registry_version,
+                    Some(subnet_id_into_protobuf(subnet_id)),
+                )
+                .unwrap();
+            for node_id in &[node_with_key, node_without_key, node_with_invalid_key] {
+                data_provider
+                    .add(
+                        &make_node_record_key(*node_id),
+                        registry_version,
+                        Some(NodeRecord {
+                            http: Some(ConnectionEndpoint {
+                                ip_addr: "127.0.0.1".to_string(),
+                                port: 8080,
+                                protocol: Protocol::Http1 as i32,
+                            }),
+                            ..Default::default()
+                        }),
+                    )
+                    .unwrap();
+            }
+            for (node_id, key_value) in &[
+                (node_with_key, vec![1; 32]),
+                (node_with_invalid_key, vec![1; 3]),
+            ] {
+                data_provider
+                    .add(
+                        &make_crypto_node_key(*node_id, KeyPurpose::NodeSigning),
+                        registry_version,
+                        Some(PublicKeyProto {
+                            algorithm: AlgorithmIdProto::Ed25519 as i32,
+                            key_value: key_value.clone(),
+                            version: 0,
+                            proof_data: None,
+                        }),
+                    )
+                    .unwrap();
+            }
+            registry.update_to_latest_version();
+
+            let metrics_registry = MetricsRegistry::new();
+            let batch_processor = BatchProcessorImpl::new(
+                Arc::new(MockStateManager::new()),
+                Box::new(MockStateMachine::new()),
+                registry,
+                Arc::new(MessageRoutingMetrics::new(&metrics_registry)),
+                log,
+            );
+
+            // Nodes without a valid signing public key do not prevent populating
+            // the network topology, they get an empty key certified instead.
+            let network_topology = batch_processor.populate_network_topology(registry_version);
+            let nodes = &network_topology.subnets[&subnet_id].nodes;
+            assert_eq!(3, nodes.len());
+            assert_eq!(
+                ed25519_public_key_to_der(vec![1; 32]).unwrap(),
+                nodes[&node_with_key].public_key
+            );
+            assert!(nodes[&node_without_key].public_key.is_empty());
+            assert!(nodes[&node_with_invalid_key].public_key.is_empty());
+            assert_eq!(
+                Some(2),
+                fetch_int_counter(&metrics_registry, METRIC_INVALID_NODE_PUBLIC_KEYS)
+            );
+        });
+    }
 }
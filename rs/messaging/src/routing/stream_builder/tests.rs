@@ -96,6 +96,7 @@ fn reject_local_request() {
                         code: RejectCode::SysFatal,
                         message: reject_message.to_string(),
                     }),
+                    deadline: msg.deadline,
                 }
                 .into(),
             )
@@ -158,6 +159,7 @@ fn reject_local_request_for_subnet() {
                         code: RejectCode::SysFatal,
                         message: reject_message.to_string(),
                     }),
+                    deadline: msg.deadline,
                 }
                 .into(),
             )
@@ -22,7 +22,7 @@ use ic_types::{
 };
 use prometheus::{Histogram, IntCounter, IntCounterVec, IntGaugeVec};
 use std::cell::RefCell;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, VecDeque};
 use std::sync::{Arc, Mutex};
 
 #[cfg(test)]
@@ -49,11 +49,17 @@ const LABEL_STATUS: &str = "status";
 const LABEL_VALUE_SUCCESS: &str = "success";
 const LABEL_VALUE_SENDER_SUBNET_MISMATCH: &str = "SenderSubnetMismatch";
 const LABEL_VALUE_SENDER_SUBNET_UNKNOWN: &str = "SenderSubnetUnknown";
+const LABEL_VALUE_DEADLINE_EXPIRED: &str = "DeadlineExpired";
 const LABEL_TYPE: &str = "type";
 const LABEL_VALUE_TYPE_REQUEST: &str = "request";
 const LABEL_VALUE_TYPE_RESPONSE: &str = "response";
 const LABEL_REMOTE: &str = "remote";
 
+/// Certification version from which the receiving subnet produces reject
+/// signals for requests that failed induction, instead of enqueuing reject
+/// responses into the reverse stream.
+const CERTIFICATION_VERSION_REJECT_SIGNALS: u32 = 6;
+
 impl StreamHandlerMetrics {
     pub fn new(metrics_registry: &MetricsRegistry) -> Self {
         let inducted_xnet_messages = metrics_registry.int_counter_vec(
@@ -190,8 +196,9 @@ impl StreamHandlerImpl {
     /// Inducts and consumes all messages from the subnet's loopback stream.
     ///
     /// After the call completes, the loopback stream may only contain reject
-    /// responses, with all initial messages garbage collected and signals
-    /// generated for them.
+    /// responses or rerouted requests, with all initial messages garbage
+    /// collected and signals generated for them. Reject signals are consumed
+    /// right away.
     fn induct_loopback_stream(&self, mut state: ReplicatedState) -> ReplicatedState {
         let loopback_stream = state.get_stream(&self.subnet_id);
 
@@ -223,27 +230,39 @@ impl StreamHandlerImpl {
 
         let mut streams = state.take_streams();
         // We know for sure that the loopback stream exists, so it is safe to unwrap.
-        let loopback_stream = streams.get_mut(&self.subnet_id).unwrap();
-        // Garbage collect all initial messages.
+        let mut loopback_stream = streams.get_mut(&self.subnet_id).unwrap();
+        // Collect the rejected messages, then garbage collect all initial messages
+        // and all signals (which have all been consumed).
+        let reject_signals = loopback_stream.reject_signals().clone();
+        let rejected_messages = collect_rejected_messages(
+            &loopback_stream,
+            &reject_signals,
+            loopback_stream_messages_end,
+        );
+        loopback_stream.discard_signals_before(loopback_stream_messages_end);
         self.discard_messages_before(loopback_stream, loopback_stream_messages_end);
         state.put_streams(streams);
 
-        state
+        self.handle_rejected_messages(state, self.subnet_id, rejected_messages)
     }
 
     /// Garbage collects outgoing `Streams` based on the signals present in
-    /// incoming `stream_slices`. As we do not have explicit signals (only a
-    /// `signals_end` index) there is no need to garbage collect signals.
+    /// incoming `stream_slices`; and reject signals already consumed by the
+    /// respective remote subnets. Messages rejected by remote subnets are
+    /// rerouted or answered with reject responses.
     fn garbage_collect_local_state(
         &self,
         mut state: ReplicatedState,
         stream_slices: &BTreeMap<SubnetId, StreamSlice>,
     ) -> ReplicatedState {
         let mut streams = state.take_streams();
+        let mut rejected_messages = Vec::new();
         for (remote_subnet, stream_slice) in stream_slices {
             match streams.get_mut(remote_subnet) {
                 Some(stream) => {
-                    self.garbage_collect_messages(stream, *remote_subnet, stream_slice);
+                    let rejected =
+                        self.garbage_collect_messages(stream, *remote_subnet, stream_slice);
+                    rejected_messages.push((*remote_subnet, rejected));
                 }
                 None => {
                     // New stream.
@@ -271,20 +290,28 @@ impl StreamHandlerImpl {
                 .set(backlog.get() as i64);
         }
         state.put_streams(streams);
+
+        for (remote_subnet, rejected) in rejected_messages {
+            state = self.handle_rejected_messages(state, remote_subnet, rejected);
+        }
         state
     }
 
     /// Garbage collects the messages of an outgoing `Stream` based on the
-    /// signals in an incoming stream slice.
+    /// signals in an incoming stream slice; and the reject signals of the
+    /// `Stream` already consumed by the remote subnet.
+    ///
+    /// Returns the garbage collected messages that were rejected by the remote
+    /// subnet.
     ///
     /// Panics if any of the incoming slices' `signals_end` refers to a
     /// nonexistent (already garbage collected or future) message.
     fn garbage_collect_messages(
         &self,
-        stream: StreamHandle,
+        mut stream: StreamHandle,
         remote_subnet: SubnetId,
         stream_slice: &StreamSlice,
-    ) {
+    ) -> Vec<RequestOrResponse> {
         assert!(
             stream.messages_begin() <= stream_slice.header().signals_end
                 && stream_slice.header().signals_end <= stream.messages_end(),
@@ -304,8 +331,97 @@ impl StreamHandlerImpl {
             );
         }
 
-        // Remove the consumed messages from our outgoing stream.
+        let rejected_messages = collect_rejected_messages(
+            &stream,
+            &stream_slice.header().reject_signals,
+            stream_slice.header().signals_end,
+        );
+
+        // Remove the consumed messages and signals from our outgoing stream.
+        stream.discard_signals_before(stream_slice.header().begin);
         self.discard_messages_before(stream, stream_slice.header().signals_end);
+
+        rejected_messages
+    }
+
+    /// Handles messages rejected by `remote_subnet_id`: requests whose
+    /// receiver has since migrated to a different subnet are rerouted to that
+    /// subnet; reject responses are inducted for all other requests.
+    fn handle_rejected_messages(
+        &self,
+        mut state: ReplicatedState,
+        remote_subnet_id: SubnetId,
+        rejected_messages: Vec<RequestOrResponse>,
+    ) -> ReplicatedState {
+        if rejected_messages.is_empty() {
+            return state;
+        }
+
+        let mut streams = state.take_streams();
+        let mut reject_responses = Vec::new();
+        for msg in rejected_messages {
+            match msg {
+                RequestOrResponse::Request(request) => {
+                    match state
+                        .metadata
+                        .network_topology
+                        .routing_table
+                        .route(request.receiver.get())
+                    {
+                        // Receiver has migrated, reroute the request.
+                        Some(host_subnet) if host_subnet != remote_subnet_id => {
+                            debug!(
+                                self.log,
+                                "Rerouting request rejected by subnet {} to subnet {}: {:?}",
+                                remote_subnet_id,
+                                host_subnet,
+                                request
+                            );
+                            streams.push(host_subnet, request.into());
+                        }
+
+                        _ => {
+                            let context = RejectContext::new(
+                                RejectCode::SysTransient,
+                                format!(
+                                    "Canister {} rejected the request (subnet {})",
+                                    request.receiver, remote_subnet_id
+                                ),
+                            );
+                            reject_responses
+                                .push(generate_reject_response(request.into(), context));
+                        }
+                    }
+                }
+
+                // Responses are never rejected by well-behaved subnets.
+                RequestOrResponse::Response(response) => {
+                    warn!(
+                        self.log,
+                        "Dropping response rejected by subnet {}: {:?}", remote_subnet_id, response
+                    );
+                }
+            }
+        }
+        state.put_streams(streams);
+
+        let mut subnet_available_memory =
+            self.subnet_memory_capacity.get() as i64 - state.total_memory_taken().get() as i64;
+        for response in reject_responses {
+            if let Err((err, response)) = state.push_input(
+                QUEUE_INDEX_NONE,
+                response,
+                self.max_canister_memory_size,
+                &mut subnet_available_memory,
+            ) {
+                warn!(
+                    self.log,
+                    "Failed to induct reject response with error '{}': {:?}", err, response
+                );
+            }
+        }
+
+        state
     }
 
     /// Helper function, discards all messages before `new_begin` while
@@ -365,10 +481,15 @@ impl StreamHandlerImpl {
     /// the following outcomes (in addition to the signal):
     ///
     ///  * enqueuing the message into the corresponding input queue;
+    ///  * a reject signal (instead of an accept signal): if enqueuing of a
+    ///    request failed (queue full, canister not found, out of memory) and
+    ///    `state` is at a certification version supporting reject signals;
     ///  * a reject response enqueued into the reverse stream: if enqueuing of a
-    ///    request failed (queue full, canister not found, out of memory);
+    ///    request failed at an earlier certification version;
     ///  * no other action: if the sender canister and source subnet do not
-    ///    match; or enqueuing of a response failed.
+    ///    match; the message is a best-effort request whose deadline has
+    ///    already expired (the caller is delivered a `SYS_UNKNOWN` reject by
+    ///    its own subnet); or enqueuing of a response failed.
     ///
     /// Updates `subnet_available_memory` to reflect any change in memory usage.
     fn induct_message(
@@ -388,6 +509,9 @@ impl StreamHandlerImpl {
             RequestOrResponse::Request(_) => LABEL_VALUE_TYPE_REQUEST,
             RequestOrResponse::Response(_) => LABEL_VALUE_TYPE_RESPONSE,
         };
+        let reject_signals_enabled =
+            state.metadata.certification_version >= CERTIFICATION_VERSION_REJECT_SIGNALS;
+        let mut reject_signal = false;
 
         match state
             .metadata
@@ -396,7 +520,13 @@ impl StreamHandlerImpl {
             .route(msg.sender().get())
         {
             Some(host_subnet) => {
-                if host_subnet == remote_subnet_id {
+                if host_subnet == remote_subnet_id
+                    && matches!(&msg, RequestOrResponse::Request(req) if req.is_expired(state.time()))
+                {
+                    // Expired best-effort request, drop it rather than reserve queue slots.
+                    debug!(self.log, "Dropping expired best-effort request {:?}", &msg);
+                    self.observe_inducted_message_status(msg_type, LABEL_VALUE_DEADLINE_EXPIRED);
+                } else if host_subnet == remote_subnet_id {
                     // Sender is hosted by `remote_subnet_id`, proceed with induction.
                     match state.push_input(
                         QUEUE_INDEX_NONE,
@@ -410,6 +540,15 @@ impl StreamHandlerImpl {
                             self.observe_inducted_payload_size(payload_size);
                         }
 
+                        // Request not inducted, generate a reject signal.
+                        Err((err, msg @ RequestOrResponse::Request(_)))
+                            if reject_signals_enabled =>
+                        {
+                            debug!(self.log, "Induction failed with error '{}', generating reject signal for {:?}", &err, &msg);
+                            self.observe_inducted_message_status(msg_type, err.to_label_value());
+                            reject_signal = true;
+                        }
+
                         // Message not inducted.
                         Err((err, msg)) => {
                             debug!(self.log, "Induction failed with error '{}', generating reject Response for {:?}", &err, &msg);
@@ -459,7 +598,11 @@ impl StreamHandlerImpl {
             stream.signals_end(),
             stream_index
         );
-        stream.increment_signals_end();
+        if reject_signal {
+            stream.push_reject_signal(stream_index);
+        } else {
+            stream.increment_signals_end();
+        }
     }
 
     /// Enqueues a reject `Response` for the provided `msg` (iff it is a
@@ -519,6 +662,7 @@ fn generate_reject_response(msg: RequestOrResponse, context: RejectContext) -> R
             originator_reply_callback: msg.sender_reply_callback,
             refund: msg.payment,
             response_payload: Payload::Reject(context),
+            deadline: msg.deadline,
         }
         .into()
     } else {
@@ -526,6 +670,21 @@ fn generate_reject_response(msg: RequestOrResponse, context: RejectContext) -> R
     }
 }
 
+/// Returns the messages in `stream` rejected by `reject_signals`, ignoring
+/// reject signals for messages already garbage collected or at or beyond
+/// `signals_end`.
+fn collect_rejected_messages(
+    stream: &StreamHandle,
+    reject_signals: &VecDeque<StreamIndex>,
+    signals_end: StreamIndex,
+) -> Vec<RequestOrResponse> {
+    reject_signals
+        .iter()
+        .filter(|index| stream.messages_begin() <= **index && **index < signals_end)
+        .filter_map(|index| stream.messages().get(*index).cloned())
+        .collect()
+}
+
 /// Maps a `StateError` resulting from a failed induction to a `RejectCode`.
 fn reject_code_for_state_error(err: &StateError) -> RejectCode {
     match err {
@@ -22,7 +22,7 @@ use ic_test_utilities::{
         fetch_int_gauge_vec, metric_vec, nonzero_values, HistogramStats, MetricVec,
     },
     state::new_canister_state,
-    types::ids::{user_test_id, SUBNET_12, SUBNET_23},
+    types::ids::{user_test_id, SUBNET_12, SUBNET_23, SUBNET_27},
     types::messages::{RequestBuilder, ResponseBuilder},
     types::xnet::{StreamHeaderBuilder, StreamSliceBuilder},
     with_test_replica_logger,
@@ -465,6 +465,153 @@ fn garbage_collect_local_state_success() {
     });
 }
 
+/// Tests that requests rejected by the remote subnet are garbage collected and
+/// answered with reject responses; and that reject signals already consumed by
+/// the remote subnet are garbage collected.
+#[test]
+fn garbage_collect_local_state_with_reject_signals() {
+    with_test_replica_logger(|log| {
+        let (stream_handler, mut initial_state, metrics_registry) = new_fixture(&log);
+        let mut expected_state = initial_state.clone();
+
+        // Canister with a reservation for one incoming response.
+        let mut initial_canister_state = new_canister_state(
+            *LOCAL_CANISTER,
+            user_test_id(24).get(),
+            *INITIAL_CYCLES,
+            NumSeconds::from(100_000),
+        );
+        initial_canister_state
+            .push_output_request(test_request(*LOCAL_CANISTER, *REMOTE_CANISTER))
+            .unwrap();
+        initial_canister_state.output_into_iter().count();
+        let mut expected_canister_state = initial_canister_state.clone();
+
+        // Outgoing stream with 3 messages and 2 reject signals.
+        let messages = generate_outgoing_stream(StreamConfig {
+            messages_begin: 31,
+            message_count: 3,
+            signals_end: 43,
+        })
+        .messages()
+        .clone();
+        let rejected_request = messages.get(32.into()).unwrap().clone();
+        let initial_stream =
+            Stream::with_signals(messages, 43.into(), vec![40.into(), 42.into()].into());
+        initial_state.put_canister_state(initial_canister_state);
+        initial_state.with_streams(btreemap![REMOTE_SUBNET => initial_stream]);
+
+        // Incoming slice consuming the first reject signal; with signals for 2
+        // messages, the second one rejected.
+        let mut stream_slice = generate_stream_slice(StreamSliceConfig {
+            header_begin: 42,
+            header_end: None,
+            messages_begin: 43,
+            message_count: 0,
+            signals_end: 33,
+        });
+        stream_slice.header_mut().reject_signals = vec![32.into()].into();
+
+        // The expected stream must contain only messages past the last signal and
+        // the reject signals not yet consumed...
+        let expected_stream = Stream::with_signals(
+            generate_outgoing_stream(StreamConfig {
+                messages_begin: 33,
+                message_count: 1,
+                signals_end: 43,
+            })
+            .messages()
+            .clone(),
+            43.into(),
+            vec![42.into()].into(),
+        );
+        expected_state.with_streams(btreemap![REMOTE_SUBNET => expected_stream]);
+
+        // ...and the canister must have a reject response for the rejected request.
+        assert_eq!(
+            Ok(()),
+            expected_canister_state
+                .system_state
+                .queues_mut()
+                .push_input(
+                    QUEUE_INDEX_NONE,
+                    generate_reject_response(
+                        rejected_request,
+                        RejectContext::new(
+                            RejectCode::SysTransient,
+                            format!(
+                                "Canister {} rejected the request (subnet {})",
+                                *REMOTE_CANISTER, REMOTE_SUBNET
+                            ),
+                        ),
+                    )
+                )
+        );
+        expected_state.put_canister_state(expected_canister_state);
+
+        let pruned_state = stream_handler
+            .garbage_collect_local_state(initial_state, &btreemap![REMOTE_SUBNET => stream_slice]);
+
+        assert_eq!(expected_state, pruned_state);
+        assert_eq!(
+            2,
+            fetch_int_counter(&metrics_registry, METRIC_GCED_XNET_MESSAGES).unwrap()
+        );
+    });
+}
+
+/// Tests that a request rejected by the remote subnet is rerouted if its
+/// receiver has since migrated to a different subnet.
+#[test]
+fn garbage_collect_local_state_reroutes_rejected_request() {
+    with_test_replica_logger(|log| {
+        let (stream_handler, mut initial_state, _) = new_fixture(&log);
+
+        let initial_stream = generate_outgoing_stream(StreamConfig {
+            messages_begin: 31,
+            message_count: 3,
+            signals_end: 43,
+        });
+        let rejected_request = initial_stream.messages().get(31.into()).unwrap().clone();
+        initial_state.with_streams(btreemap![REMOTE_SUBNET => initial_stream]);
+
+        // `REMOTE_CANISTER` has migrated to `SUBNET_27`.
+        initial_state.metadata.network_topology.routing_table = RoutingTable::new(btreemap! {
+            CanisterIdRange{ start: CanisterId::from(0x0), end: CanisterId::from(0xff) } => LOCAL_SUBNET,
+            CanisterIdRange{ start: CanisterId::from(0x100), end: CanisterId::from(0x1ff) } => SUBNET_27,
+        });
+        let mut expected_state = initial_state.clone();
+
+        // Incoming slice rejecting the first message.
+        let mut stream_slice = generate_stream_slice(StreamSliceConfig {
+            header_begin: 42,
+            header_end: None,
+            messages_begin: 43,
+            message_count: 0,
+            signals_end: 32,
+        });
+        stream_slice.header_mut().reject_signals = vec![31.into()].into();
+
+        // The rejected request is garbage collected and rerouted to `SUBNET_27`.
+        let expected_stream = generate_outgoing_stream(StreamConfig {
+            messages_begin: 32,
+            message_count: 2,
+            signals_end: 43,
+        });
+        let mut rerouted_stream = Stream::default();
+        rerouted_stream.push(rejected_request);
+        expected_state.with_streams(btreemap![
+            REMOTE_SUBNET => expected_stream,
+            SUBNET_27 => rerouted_stream,
+        ]);
+
+        let pruned_state = stream_handler
+            .garbage_collect_local_state(initial_state, &btreemap![REMOTE_SUBNET => stream_slice]);
+
+        assert_eq!(expected_state, pruned_state);
+    });
+}
+
 #[test]
 fn enqueue_reject_response_queue_full() {
     with_test_replica_logger(|log| {
@@ -492,6 +639,7 @@ fn enqueue_reject_response_queue_full() {
                     RejectCode::SysTransient,
                     err.to_string(),
                 )),
+                deadline: msg.deadline,
             }
             .into(),
         );
@@ -536,6 +684,7 @@ fn enqueue_reject_response_canister_not_found() {
                     RejectCode::DestinationInvalid,
                     err.to_string(),
                 )),
+                deadline: msg.deadline,
             }
             .into(),
         );
@@ -727,6 +876,99 @@ fn induct_stream_slices_partial_success() {
     });
 }
 
+/// Tests that, starting with `CERTIFICATION_VERSION_REJECT_SIGNALS`, requests
+/// that fail induction result in reject signals rather than reject responses.
+#[test]
+fn induct_stream_slices_reject_signals() {
+    with_test_replica_logger(|log| {
+        let (stream_handler, mut initial_state, metrics_registry) = new_fixture(&log);
+        initial_state.metadata.certification_version = CERTIFICATION_VERSION_REJECT_SIGNALS;
+        let mut expected_state = initial_state.clone();
+
+        let initial_canister_state = new_canister_state(
+            *LOCAL_CANISTER,
+            user_test_id(24).get(),
+            *INITIAL_CYCLES,
+            NumSeconds::from(100_000),
+        );
+        let mut expected_canister_state = initial_canister_state.clone();
+
+        let initial_stream = generate_outgoing_stream(StreamConfig {
+            messages_begin: 31,
+            message_count: 3,
+            signals_end: 43,
+        });
+        initial_state.put_canister_state(initial_canister_state);
+        initial_state.with_streams(btreemap![REMOTE_SUBNET => initial_stream]);
+
+        // 1 incoming request...
+        let mut stream_slice = generate_stream_slice(StreamSliceConfig {
+            header_begin: 42,
+            header_end: None,
+            messages_begin: 43,
+            message_count: 1,
+            signals_end: 31,
+        });
+        for (_stream_index, msg) in stream_slice.messages().unwrap().iter() {
+            assert_eq!(
+                Ok(()),
+                expected_canister_state
+                    .system_state
+                    .queues_mut()
+                    .push_input(QUEUE_INDEX_NONE, msg.clone())
+            );
+        }
+        // ...a request addressed to a missing canister...
+        stream_slice.push_message(test_request(*REMOTE_CANISTER, *REMOTE_CANISTER).into());
+        // ...and a response addressed to a missing canister.
+        stream_slice.push_message(test_response(*REMOTE_CANISTER, *REMOTE_CANISTER).into());
+
+        // Expecting an accept signal, a reject signal and another accept signal; and
+        // no reject response.
+        let mut expected_stream = generate_outgoing_stream(StreamConfig {
+            messages_begin: 31,
+            message_count: 3,
+            signals_end: 44,
+        });
+        expected_stream.push_reject_signal(44.into());
+        expected_stream.increment_signals_end();
+
+        expected_state.put_canister_state(expected_canister_state);
+        expected_state.with_streams(btreemap![REMOTE_SUBNET => expected_stream]);
+
+        let inducted_state = stream_handler
+            .induct_stream_slices(initial_state, btreemap![REMOTE_SUBNET => stream_slice]);
+
+        assert_eq!(expected_state, inducted_state);
+        assert_inducted_xnet_messages_eq(
+            metric_vec(&[
+                (
+                    &[
+                        (LABEL_TYPE, LABEL_VALUE_TYPE_REQUEST),
+                        (LABEL_STATUS, LABEL_VALUE_SUCCESS),
+                    ],
+                    1,
+                ),
+                (
+                    &[
+                        (LABEL_TYPE, LABEL_VALUE_TYPE_REQUEST),
+                        (LABEL_STATUS, LABEL_VALUE_CANISTER_NOT_FOUND),
+                    ],
+                    1,
+                ),
+                (
+                    &[
+                        (LABEL_TYPE, LABEL_VALUE_TYPE_RESPONSE),
+                        (LABEL_STATUS, LABEL_VALUE_CANISTER_NOT_FOUND),
+                    ],
+                    1,
+                ),
+            ]),
+            &metrics_registry,
+        );
+    });
+}
+
 /// Tests that canister memory limit is enforced when inducting stream slices.
 ///
 /// Sets up a stream handler with only enough canister memory for one in-flight
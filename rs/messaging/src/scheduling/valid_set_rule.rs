@@ -15,7 +15,7 @@ use ic_replicated_state::{
         LABEL_VALUE_CANISTER_STOPPED, LABEL_VALUE_CANISTER_STOPPING,
         LABEL_VALUE_INVALID_SUBNET_PAYLOAD, LABEL_VALUE_UNKNOWN_SUBNET_METHOD,
     },
-    ReplicatedState, StateError,
+    CyclesUseCase, ReplicatedState, StateError,
 };
 use ic_types::messages::HttpRequestContent;
 use ic_types::{
@@ -129,6 +129,7 @@ impl ValidSetRuleImpl {
     fn induct_message(&self, mut state: &mut ReplicatedState, msg: SignedIngressContent) {
         trace!(self.log, "induct_message");
         let message_id = msg.id();
+        let mut span = ic_tracing::ingress_span(&message_id, "valid_set_rule.induct_message");
         let source = msg.sender();
         let receiver = msg.canister_id();
         let payload_bytes = msg.arg().len();
@@ -182,6 +183,7 @@ impl ValidSetRuleImpl {
                 err.to_label_value()
             }
         };
+        span.set_attribute("status", status);
         self.observe_inducted_ingress_status(status);
         self.observe_unreliable_induct_ingress_message_duration(status, ingress_expiry);
     }
@@ -279,6 +281,7 @@ impl ValidSetRuleImpl {
                     memory_usage,
                     compute_allocation,
                     cost,
+                    CyclesUseCase::IngressInduction,
                 ) {
                     return Err(StateError::CanisterOutOfCycles(err));
                 }
@@ -7,6 +7,8 @@ use ic_registry_provisional_whitelist::ProvisionalWhitelist;
 use ic_registry_subnet_features::SubnetFeatures;
 use ic_replicated_state::{NetworkTopology, ReplicatedState};
 use ic_types::{batch::Batch, ExecutionRound};
+#[cfg(test)]
+use mockall::automock;
 use std::sync::Arc;
 
 #[cfg(test)]
@@ -16,6 +18,7 @@ const PHASE_INDUCTION: &str = "induction";
 const PHASE_EXECUTION: &str = "execution";
 const PHASE_MESSAGE_ROUTING: &str = "message_routing";
 
+#[cfg_attr(test, automock)]
 pub(crate) trait StateMachine: Send {
     fn execute_round(
         &self,
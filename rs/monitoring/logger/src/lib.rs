@@ -5,20 +5,22 @@ use slog_scope::GlobalLoggerGuard;
 use std::io;
 use std::sync::{Arc, Mutex};
 
+pub mod log_control;
 pub mod replica_logger;
+mod rotating_file;
 pub use ic_context_logger::{debug, error, fatal, info, info_sample, log, new_logger, trace, warn};
+pub use log_control::{LogControl, LogSettings, LogSettingsUpdate};
 use replica_logger::LogEntryLogger;
 pub use replica_logger::ReplicaLogger;
+use rotating_file::RotatingFile;
 
+/// Creates a `ReplicaLogger` with the settings from `config`.
+///
+/// The settings can be changed at runtime through
+/// `logger.inner_logger.control()`.
 pub fn new_replica_logger(log: slog::Logger, config: &LoggerConfig) -> ReplicaLogger {
-    let log_entry_logger = LogEntryLogger::new(
-        log,
-        config.level,
-        config.debug_overrides.clone(),
-        config.sampling_rates.clone(),
-        config.enabled_tags.clone(),
-    );
-    ReplicaLogger::new(log_entry_logger)
+    let control = LogControl::new(LogSettings::from(config));
+    ReplicaLogger::new(LogEntryLogger::new_with_control(log, control))
 }
 
 pub struct LoggerImpl {
@@ -31,11 +33,18 @@ impl LoggerImpl {
         match config.target.clone() {
             LogTarget::Stdout => Self::new_internal(std::io::stdout(), config, thread_name),
             LogTarget::Stderr => Self::new_internal(std::io::stderr(), config, thread_name),
-            LogTarget::File(f) => Self::new_internal(
-                std::fs::File::create(f).expect("Couldn't open/create log file"),
-                config,
-                thread_name,
-            ),
+            LogTarget::File(f) => match config.file_rotation.clone() {
+                Some(rotation) => Self::new_internal(
+                    RotatingFile::open(&f, rotation).expect("Couldn't open/create log file"),
+                    config,
+                    thread_name,
+                ),
+                None => Self::new_internal(
+                    std::fs::File::create(f).expect("Couldn't open/create log file"),
+                    config,
+                    thread_name,
+                ),
+            },
         }
     }
 
@@ -0,0 +1,138 @@
+//! Runtime control of the log settings
+//!
+//! All clones of a `LogEntryLogger` share a `LogControl`, so that the log
+//! level, the debug overrides, the sampling rates and the enabled tags can be
+//! changed for the whole process while it is running.
+use ic_config::logger::{Config as LoggerConfig, LevelDef};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+
+/// The log settings that can be changed at runtime.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogSettings {
+    #[serde(with = "LevelDef")]
+    pub level: slog::Level,
+    pub debug_overrides: Vec<String>,
+    pub sampling_rates: HashMap<String, u32>,
+    pub enabled_tags: Vec<String>,
+}
+
+impl From<&LoggerConfig> for LogSettings {
+    fn from(config: &LoggerConfig) -> Self {
+        Self {
+            level: config.level,
+            debug_overrides: config.debug_overrides.clone(),
+            sampling_rates: config.sampling_rates.clone(),
+            enabled_tags: config.enabled_tags.clone(),
+        }
+    }
+}
+
+/// A change of the log settings. Settings that are `None` are left unchanged.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogSettingsUpdate {
+    pub level: Option<Level>,
+    pub debug_overrides: Option<Vec<String>>,
+    pub sampling_rates: Option<HashMap<String, u32>>,
+    pub enabled_tags: Option<Vec<String>>,
+}
+
+/// A log level, (de)serialized like the level in the logger config.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Level(#[serde(with = "LevelDef")] pub slog::Level);
+
+/// A handle to change the log settings of all loggers that share it.
+#[derive(Clone, Debug)]
+pub struct LogControl {
+    settings: Arc<RwLock<LogSettings>>,
+}
+
+impl LogControl {
+    pub fn new(settings: LogSettings) -> Self {
+        Self {
+            settings: Arc::new(RwLock::new(settings)),
+        }
+    }
+
+    /// Returns the current log settings.
+    pub fn settings(&self) -> LogSettings {
+        self.read().clone()
+    }
+
+    /// Applies `update` and returns the resulting log settings.
+    pub fn update(&self, update: LogSettingsUpdate) -> LogSettings {
+        let mut settings = self.settings.write().unwrap();
+        if let Some(Level(level)) = update.level {
+            settings.level = level;
+        }
+        if let Some(debug_overrides) = update.debug_overrides {
+            settings.debug_overrides = debug_overrides;
+        }
+        if let Some(sampling_rates) = update.sampling_rates {
+            settings.sampling_rates = sampling_rates;
+        }
+        if let Some(enabled_tags) = update.enabled_tags {
+            settings.enabled_tags = enabled_tags;
+        }
+        settings.clone()
+    }
+
+    pub(crate) fn read(&self) -> RwLockReadGuard<'_, LogSettings> {
+        self.settings.read().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn control() -> LogControl {
+        LogControl::new(LogSettings::from(&LoggerConfig::default()))
+    }
+
+    #[test]
+    fn update_changes_only_given_settings() {
+        let control = control();
+        let before = control.settings();
+
+        let after = control.update(LogSettingsUpdate {
+            level: Some(Level(slog::Level::Trace)),
+            enabled_tags: Some(vec!["my_tag".to_string()]),
+            ..Default::default()
+        });
+
+        assert_eq!(after.level, slog::Level::Trace);
+        assert_eq!(after.enabled_tags, vec!["my_tag".to_string()]);
+        assert_eq!(after.debug_overrides, before.debug_overrides);
+        assert_eq!(after.sampling_rates, before.sampling_rates);
+        assert_eq!(control.settings(), after);
+    }
+
+    #[test]
+    fn update_is_visible_through_clones() {
+        let control = control();
+
+        control.clone().update(LogSettingsUpdate {
+            debug_overrides: Some(vec!["ic_consensus::finalizer".to_string()]),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            control.settings().debug_overrides,
+            vec!["ic_consensus::finalizer".to_string()]
+        );
+    }
+
+    #[test]
+    fn update_deserializes_from_partial_json() {
+        let update: LogSettingsUpdate =
+            serde_json::from_str(r#"{ "level": "warning", "sampling_rates": { "key": 10 } }"#)
+                .unwrap();
+
+        assert_eq!(update.level, Some(Level(slog::Level::Warning)));
+        assert_eq!(update.debug_overrides, None);
+        assert_eq!(update.sampling_rates.unwrap().get("key"), Some(&10));
+    }
+}
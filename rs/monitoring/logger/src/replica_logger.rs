@@ -1,3 +1,4 @@
+use crate::log_control::{LogControl, LogSettings};
 use ic_context_logger::{ContextLogger, LogMetadata, Logger};
 use ic_protobuf::log::log_entry::v1::LogEntry;
 use std::collections::HashMap;
@@ -26,13 +27,12 @@ impl From<LogEntryLogger> for ReplicaLogger {
 }
 
 /// Logs `LogEntry`s using `slog`
+///
+/// The log settings are shared with all clones of the logger and can be
+/// changed at runtime through its `LogControl`.
 pub struct LogEntryLogger {
     pub root: slog::Logger,
-    // Only logs at `level` or above
-    pub level: slog::Level,
-    pub debug_overrides: Vec<String>,
-    pub sampling_rates: HashMap<String, u32>,
-    pub enabled_tags: Vec<String>,
+    control: LogControl,
     pub last_log: Mutex<HashMap<String, Instant>>,
 }
 
@@ -44,15 +44,31 @@ impl LogEntryLogger {
         sampling_rates: HashMap<String, u32>,
         enabled_tags: Vec<String>,
     ) -> Self {
+        Self::new_with_control(
+            root,
+            LogControl::new(LogSettings {
+                level,
+                debug_overrides,
+                sampling_rates,
+                enabled_tags,
+            }),
+        )
+    }
+
+    /// Creates a logger whose settings are controlled by `control`.
+    pub fn new_with_control(root: slog::Logger, control: LogControl) -> Self {
         Self {
             root,
-            level,
-            debug_overrides,
-            sampling_rates,
-            enabled_tags,
+            control,
             last_log: Mutex::new(HashMap::new()),
         }
     }
+
+    /// Returns the handle to change the settings of this logger and its
+    /// clones at runtime.
+    pub fn control(&self) -> &LogControl {
+        &self.control
+    }
 }
 
 impl From<slog::Logger> for LogEntryLogger {
@@ -71,10 +87,7 @@ impl Clone for LogEntryLogger {
     fn clone(&self) -> Self {
         Self {
             root: self.root.new(slog::o!()),
-            level: self.level,
-            debug_overrides: self.debug_overrides.clone(),
-            sampling_rates: self.sampling_rates.clone(),
-            enabled_tags: self.enabled_tags.clone(),
+            control: self.control.clone(),
             // `last_log` is not cloned because different instances of this
             // logger will log at disjoint module/line pairs, so these
             // instances don't need to share the same mutex, or need to both
@@ -115,18 +128,19 @@ impl Logger<LogEntry> for LogEntryLogger {
     }
 
     fn is_enabled_at(&self, level: slog::Level, module_path: &'static str) -> bool {
-        if !self.debug_overrides.is_empty()
+        let settings = self.control.read();
+        if !settings.debug_overrides.is_empty()
             && level == slog::Level::Debug
-            && self.debug_overrides.contains(&module_path.to_string())
+            && settings.debug_overrides.iter().any(|m| m == module_path)
         {
             true
         } else {
-            level.is_at_least(self.level)
+            level.is_at_least(settings.level)
         }
     }
 
     fn should_sample<T: Into<u32>>(&self, key: String, value: T) -> bool {
-        if let Some(&sample_rate) = self.sampling_rates.get(&key) {
+        if let Some(&sample_rate) = self.control.read().sampling_rates.get(&key) {
             sample_rate != 0 && value.into() % sample_rate == 0
         } else {
             false
@@ -134,7 +148,7 @@ impl Logger<LogEntry> for LogEntryLogger {
     }
 
     fn is_tag_enabled(&self, tag: String) -> bool {
-        self.enabled_tags.contains(&tag)
+        self.control.read().enabled_tags.contains(&tag)
     }
 
     fn is_n_seconds<T: Into<i32>>(&self, seconds: T, metadata: LogMetadata) -> bool {
@@ -175,26 +189,25 @@ pub fn get_module(module_path: &'static str) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::log_control::{Level, LogSettingsUpdate};
 
     #[test]
     fn test_should_sample() {
-        let mut logger = LogEntryLogger::new(
+        let logger = LogEntryLogger::new(
             slog::Logger::root(slog::Discard, slog::o!()),
             slog::Level::Critical,
             vec![],
-            HashMap::new(),
+            [
+                ("ten".into(), 10u32),
+                ("one".into(), 1u32),
+                ("zero".into(), 0u32),
+            ]
+            .iter()
+            .cloned()
+            .collect(),
             vec![],
         );
 
-        logger.sampling_rates = [
-            ("ten".into(), 10u32),
-            ("one".into(), 1u32),
-            ("zero".into(), 0u32),
-        ]
-        .iter()
-        .cloned()
-        .collect();
-
         for i in 1u32..10u32 {
             assert!(!logger.should_sample("ten".to_string(), i));
             assert!(logger.should_sample("one".to_string(), i));
@@ -217,6 +230,37 @@ mod tests {
         assert!(logger.is_tag_enabled("my_tag".to_string()));
     }
 
+    #[test]
+    fn test_settings_changes_apply_to_clones() {
+        let logger = LogEntryLogger::new(
+            slog::Logger::root(slog::Discard, slog::o!()),
+            slog::Level::Info,
+            vec![],
+            HashMap::new(),
+            vec![],
+        );
+        let clone = logger.clone();
+        assert!(!clone.is_enabled_at(slog::Level::Debug, "ic_consensus::finalizer"));
+        assert!(!clone.is_tag_enabled("my_tag".to_string()));
+
+        logger.control().update(LogSettingsUpdate {
+            debug_overrides: Some(vec!["ic_consensus::finalizer".to_string()]),
+            enabled_tags: Some(vec!["my_tag".to_string()]),
+            ..Default::default()
+        });
+
+        assert!(clone.is_enabled_at(slog::Level::Debug, "ic_consensus::finalizer"));
+        assert!(!clone.is_enabled_at(slog::Level::Debug, "ic_consensus::notary"));
+        assert!(clone.is_tag_enabled("my_tag".to_string()));
+
+        logger.control().update(LogSettingsUpdate {
+            level: Some(Level(slog::Level::Debug)),
+            ..Default::default()
+        });
+
+        assert!(clone.is_enabled_at(slog::Level::Debug, "ic_consensus::notary"));
+    }
+
     #[test]
     fn test_is_seconds() {
         let logger = LogEntryLogger::new(
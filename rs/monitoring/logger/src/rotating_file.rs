@@ -0,0 +1,154 @@
+//! A log file that is rotated based on its size
+use ic_config::logger::LogFileRotation;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Writes to a file, rotating it before the next line is written once it has
+/// reached the configured size, so that lines are never split across files.
+/// See `LogFileRotation` for the naming of rotated files.
+///
+/// Unlike a plain log file, an existing file is appended to rather than
+/// truncated, so that logs are not lost when the process restarts.
+pub struct RotatingFile {
+    path: PathBuf,
+    rotation: LogFileRotation,
+    file: File,
+    size: u64,
+    at_line_start: bool,
+}
+
+impl RotatingFile {
+    pub fn open(path: &Path, rotation: LogFileRotation) -> io::Result<Self> {
+        let file = Self::open_file(path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path: path.to_path_buf(),
+            rotation,
+            file,
+            size,
+            at_line_start: true,
+        })
+    }
+
+    fn open_file(path: &Path) -> io::Result<File> {
+        OpenOptions::new().create(true).append(true).open(path)
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        PathBuf::from(path)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.rotation.max_rotated_files == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            for index in (1..self.rotation.max_rotated_files).rev() {
+                let from = self.rotated_path(index);
+                if from.exists() {
+                    fs::rename(&from, self.rotated_path(index + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated_path(1))?;
+        }
+        self.file = Self::open_file(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.at_line_start && self.size >= self.rotation.max_size_bytes {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        if written > 0 {
+            self.at_line_start = buf[written - 1] == b'\n';
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rotation(max_size_bytes: u64, max_rotated_files: usize) -> LogFileRotation {
+        LogFileRotation {
+            max_size_bytes,
+            max_rotated_files,
+        }
+    }
+
+    fn read(path: PathBuf) -> String {
+        fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn should_rotate_when_size_is_reached() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("replica.log");
+        let mut file = RotatingFile::open(&path, rotation(6, 2)).unwrap();
+
+        file.write_all(b"first\n").unwrap();
+        file.write_all(b"second\n").unwrap();
+        file.flush().unwrap();
+
+        assert_eq!(read(dir.path().join("replica.log.1")), "first\n");
+        assert_eq!(read(path), "second\n");
+    }
+
+    #[test]
+    fn should_keep_at_most_max_rotated_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("replica.log");
+        let mut file = RotatingFile::open(&path, rotation(3, 2)).unwrap();
+
+        for line in &["one\n", "two\n", "three\n", "four\n"] {
+            file.write_all(line.as_bytes()).unwrap();
+        }
+        file.flush().unwrap();
+
+        assert_eq!(read(path), "four\n");
+        assert_eq!(read(dir.path().join("replica.log.1")), "three\n");
+        assert_eq!(read(dir.path().join("replica.log.2")), "two\n");
+        assert!(!dir.path().join("replica.log.3").exists());
+    }
+
+    #[test]
+    fn should_append_to_existing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("replica.log");
+        fs::write(&path, "before restart\n").unwrap();
+
+        let mut file = RotatingFile::open(&path, rotation(100, 1)).unwrap();
+        file.write_all(b"after restart\n").unwrap();
+        file.flush().unwrap();
+
+        assert_eq!(read(path), "before restart\nafter restart\n");
+    }
+
+    #[test]
+    fn should_not_split_lines_across_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("replica.log");
+        let mut file = RotatingFile::open(&path, rotation(3, 1)).unwrap();
+
+        file.write_all(b"abc").unwrap();
+        file.write_all(b"def\n").unwrap();
+        file.write_all(b"g\n").unwrap();
+        file.flush().unwrap();
+
+        assert_eq!(read(dir.path().join("replica.log.1")), "abcdef\n");
+        assert_eq!(read(path), "g\n");
+    }
+}
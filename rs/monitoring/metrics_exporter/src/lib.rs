@@ -1,11 +1,12 @@
-use hyper::{server::conn::Http, service::service_fn, Body, Response};
+use hyper::{server::conn::Http, service::service_fn, Body, Method, Request, Response, StatusCode};
 use ic_config::metrics::{Config, Exporter};
 use ic_crypto_tls_interfaces::TlsHandshake;
 use ic_interfaces::registry::RegistryClient;
+use ic_logger::{LogControl, LogSettingsUpdate};
 use ic_metrics::registry::MetricsRegistry;
 use prometheus::{Encoder, TextEncoder};
-use slog::{error, trace, warn};
-use std::net::SocketAddr;
+use slog::{error, info, trace, warn};
+use std::net::{IpAddr, SocketAddr};
 use std::string::String;
 use std::sync::Arc;
 use std::time::Duration;
@@ -13,23 +14,32 @@ use tokio::net::TcpListener;
 
 const LOG_INTERVAL_SECS: u64 = 30;
 
+/// The path of the admin endpoint to read (`GET`) and change (`POST`) the log
+/// settings at runtime. Only requests from localhost are served.
+const LOG_CONTROL_PATH: &str = "/_/log_control";
+
 /// The type of a metrics runtime implementation.
 pub struct MetricsRuntimeImpl {
     rt_handle: tokio::runtime::Handle,
     config: Config,
     metrics_registry: MetricsRegistry,
     crypto_tls: Option<(Arc<dyn RegistryClient>, Arc<dyn TlsHandshake + Send + Sync>)>,
+    log_control: Option<LogControl>,
     log: slog::Logger,
 }
 
 /// An implementation of the metrics runtime type.
 impl MetricsRuntimeImpl {
+    ///
+    /// If `log_control` is given, the HTTP exporter also serves the log
+    /// control endpoint to localhost.
     pub fn new(
         rt_handle: tokio::runtime::Handle,
         config: Config,
         metrics_registry: MetricsRegistry,
         registry_client: Arc<dyn RegistryClient>,
         crypto: Arc<dyn TlsHandshake + Send + Sync>,
+        log_control: Option<LogControl>,
         log: &slog::Logger,
     ) -> Self {
         let log = log.new(slog::o!("Application" => "MetricsRuntime"));
@@ -39,6 +49,7 @@ impl MetricsRuntimeImpl {
             config,
             metrics_registry,
             crypto_tls: Some((registry_client, crypto)),
+            log_control,
             log,
         };
 
@@ -66,6 +77,7 @@ impl MetricsRuntimeImpl {
             config,
             metrics_registry,
             crypto_tls: None,
+            log_control: None,
             log,
         };
 
@@ -104,21 +116,9 @@ impl MetricsRuntimeImpl {
     /// task will need to be joined.
     fn start_http(&self, address: SocketAddr) {
         let metrics_registry = self.metrics_registry.clone();
+        let log_control = self.log_control.clone();
         let log = self.log.clone();
 
-        let aservice = service_fn(move |_req| {
-            // Clone again to ensure that `metrics_registry` outlives this closure.
-            let metrics_registry = metrics_registry.clone();
-            let encoder = TextEncoder::new();
-
-            async move {
-                let metric_families = metrics_registry.prometheus_registry().gather();
-                let mut buffer = vec![];
-                encoder.encode(&metric_families, &mut buffer).unwrap();
-                Ok::<_, hyper::Error>(Response::new(Body::from(buffer)))
-            }
-        });
-
         let crypto_tls = self.crypto_tls.clone();
         // Temporarily listen on [::] so that we accept both IPv4 and IPv6 connections.
         // This requires net.ipv6.bindv6only = 0.  TODO: revert this once we have rolled
@@ -137,9 +137,21 @@ impl MetricsRuntimeImpl {
             loop {
                 let log = log.clone();
                 let http = http.clone();
-                let aservice = aservice.clone();
                 let crypto_tls = crypto_tls.clone();
-                if let Ok((stream, _)) = listener.accept().await {
+                if let Ok((stream, peer_addr)) = listener.accept().await {
+                    let metrics_registry = metrics_registry.clone();
+                    let log_control = log_control.clone();
+                    let service_log = log.clone();
+                    let aservice = service_fn(move |req| {
+                        // Clone again to ensure that the captured values outlive this closure.
+                        handle_request(
+                            req,
+                            peer_addr,
+                            metrics_registry.clone(),
+                            log_control.clone(),
+                            service_log.clone(),
+                        )
+                    });
                     tokio::spawn(async move {
                         let mut b = [0_u8; 1];
                         if stream.peek(&mut b).await.is_ok() && b[0] == 22 {
@@ -177,6 +189,93 @@ impl MetricsRuntimeImpl {
     }
 }
 
+async fn handle_request(
+    req: Request<Body>,
+    peer_addr: SocketAddr,
+    metrics_registry: MetricsRegistry,
+    log_control: Option<LogControl>,
+    log: slog::Logger,
+) -> Result<Response<Body>, hyper::Error> {
+    if req.uri().path() == LOG_CONTROL_PATH {
+        if let Some(log_control) = log_control {
+            return handle_log_control(req, peer_addr, log_control, log).await;
+        }
+    }
+    let encoder = TextEncoder::new();
+    let metric_families = metrics_registry.prometheus_registry().gather();
+    let mut buffer = vec![];
+    encoder.encode(&metric_families, &mut buffer).unwrap();
+    Ok(Response::new(Body::from(buffer)))
+}
+
+/// Returns the current log settings for `GET` requests and applies the
+/// `LogSettingsUpdate` in the body of `POST` requests.
+async fn handle_log_control(
+    req: Request<Body>,
+    peer_addr: SocketAddr,
+    log_control: LogControl,
+    log: slog::Logger,
+) -> Result<Response<Body>, hyper::Error> {
+    if !is_localhost(peer_addr.ip()) {
+        warn!(
+            log,
+            "Rejected log control request from non-local address {}", peer_addr
+        );
+        return Ok(text_response(
+            StatusCode::FORBIDDEN,
+            "Log control is only available from localhost",
+        ));
+    }
+    let settings = match *req.method() {
+        Method::GET => log_control.settings(),
+        Method::POST => {
+            let body = hyper::body::to_bytes(req.into_body()).await?;
+            let update: LogSettingsUpdate = match serde_json::from_slice(&body) {
+                Ok(update) => update,
+                Err(e) => {
+                    return Ok(text_response(
+                        StatusCode::BAD_REQUEST,
+                        &format!("Invalid log settings update: {}", e),
+                    ))
+                }
+            };
+            let settings = log_control.update(update);
+            info!(log, "Log settings changed to {:?}", settings);
+            settings
+        }
+        _ => {
+            return Ok(text_response(
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Use GET to read or POST to change the log settings",
+            ))
+        }
+    };
+    let body = serde_json::to_vec(&settings).expect("Failed to serialize log settings");
+    Ok(Response::builder()
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body))
+        .expect("Failed to build log control response"))
+}
+
+fn text_response(status: StatusCode, text: &str) -> Response<Body> {
+    let mut response = Response::new(Body::from(text.to_string()));
+    *response.status_mut() = status;
+    response
+}
+
+/// Returns true iff `ip` is a loopback address, including IPv4 loopback
+/// addresses mapped to IPv6, as the exporter listens on `[::]`.
+fn is_localhost(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_loopback(),
+        IpAddr::V6(ip) => {
+            ip.is_loopback()
+                || (ip.segments()[..6] == [0, 0, 0, 0, 0, 0xffff]
+                    && ip.to_ipv4().map_or(false, |ip| ip.is_loopback()))
+        }
+    }
+}
+
 impl Drop for MetricsRuntimeImpl {
     fn drop(&mut self) {
         if let Exporter::File(ref path) = self.config.exporter {
@@ -207,3 +306,24 @@ impl Drop for MetricsRuntimeImpl {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_recognize_localhost() {
+        assert!(is_localhost("127.0.0.1".parse().unwrap()));
+        assert!(is_localhost("::1".parse().unwrap()));
+        assert!(is_localhost("::ffff:127.0.0.1".parse().unwrap()));
+    }
+
+    #[test]
+    fn should_not_recognize_remote_addresses_as_localhost() {
+        assert!(!is_localhost("10.0.0.1".parse().unwrap()));
+        assert!(!is_localhost("2001:db8::1".parse().unwrap()));
+        assert!(!is_localhost("::ffff:10.0.0.1".parse().unwrap()));
+        // IPv4-compatible (not mapped) addresses are not treated as IPv4.
+        assert!(!is_localhost("::127.0.0.1".parse().unwrap()));
+    }
+}
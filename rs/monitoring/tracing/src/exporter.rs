@@ -0,0 +1,167 @@
+//! Exporters that ship finished spans in the OTLP JSON encoding
+use crate::otlp::ExportTraceServiceRequest;
+use crate::FinishedSpan;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+
+const OTLP_HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Ships batches of finished spans to their destination.
+pub trait SpanExporter: Send {
+    fn export(&mut self, spans: &[FinishedSpan]) -> Result<(), String>;
+}
+
+/// Appends every exported batch to a file as one line holding an OTLP JSON
+/// `ExportTraceServiceRequest`.
+pub struct FileExporter {
+    service_name: String,
+    file: BufWriter<File>,
+}
+
+impl FileExporter {
+    pub fn new(path: &Path, service_name: &str) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            service_name: service_name.to_string(),
+            file: BufWriter::new(file),
+        })
+    }
+}
+
+impl SpanExporter for FileExporter {
+    fn export(&mut self, spans: &[FinishedSpan]) -> Result<(), String> {
+        let request = ExportTraceServiceRequest::new(&self.service_name, spans);
+        serde_json::to_writer(&mut self.file, &request).map_err(|e| e.to_string())?;
+        self.file
+            .write_all(b"\n")
+            .and_then(|()| self.file.flush())
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Sends every exported batch to an OTLP/HTTP collector, e.g.
+/// `http://localhost:4318`, using the JSON encoding.
+pub struct OtlpHttpExporter {
+    service_name: String,
+    url: String,
+    client: reqwest::blocking::Client,
+}
+
+impl OtlpHttpExporter {
+    pub fn new(endpoint: &str, service_name: &str) -> io::Result<Self> {
+        let client = reqwest::blocking::Client::builder()
+            .timeout(OTLP_HTTP_TIMEOUT)
+            .build()
+            .map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("Failed to build the OTLP HTTP client: {}", e),
+                )
+            })?;
+        Ok(Self {
+            service_name: service_name.to_string(),
+            url: format!("{}/v1/traces", endpoint.trim_end_matches('/')),
+            client,
+        })
+    }
+}
+
+impl SpanExporter for OtlpHttpExporter {
+    fn export(&mut self, spans: &[FinishedSpan]) -> Result<(), String> {
+        let request = ExportTraceServiceRequest::new(&self.service_name, spans);
+        self.client
+            .post(&self.url)
+            .json(&request)
+            .send()
+            .and_then(|response| response.error_for_status())
+            .map(|_| ())
+            .map_err(|e| format!("POST {} failed: {}", self.url, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::IngressTracer;
+    use ic_logger::replica_logger::no_op_logger;
+    use ic_metrics::MetricsRegistry;
+    use ic_types::messages::MessageId;
+    use serde_json::Value;
+
+    /// Returns the spans of all requests in the file once there are `count`.
+    fn wait_for_spans(path: &Path, count: usize) -> (Vec<Value>, Vec<Value>) {
+        for _ in 0..100 {
+            let content = std::fs::read_to_string(path).unwrap_or_default();
+            let requests: Vec<Value> = content
+                .lines()
+                .filter_map(|line| serde_json::from_str(line).ok())
+                .collect();
+            let spans: Vec<Value> = requests
+                .iter()
+                .flat_map(|r| r["resourceSpans"][0]["scopeSpans"][0]["spans"].as_array())
+                .flatten()
+                .cloned()
+                .collect();
+            if spans.len() >= count {
+                return (requests, spans);
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        panic!(
+            "Fewer than {} spans were exported to {}",
+            count,
+            path.display()
+        );
+    }
+
+    #[test]
+    fn file_exporter_writes_otlp_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("traces.json");
+        let exporter = FileExporter::new(&path, "replica").unwrap();
+        let tracer = IngressTracer::new_with_exporter(
+            Box::new(exporter),
+            1.0,
+            no_op_logger(),
+            &MetricsRegistry::new(),
+        );
+        let message_id = MessageId::from([0xab; 32]);
+
+        {
+            let _root = tracer.root_span(&message_id, "http_handler.submit");
+            let mut child = tracer.span(&message_id, "execution_environment.execute");
+            child.set_attribute("canister_id", "rwlgt-iiaaa-aaaaa-aaaaa-cai");
+        }
+        drop(tracer);
+
+        let (requests, spans) = wait_for_spans(&path, 2);
+        assert_eq!(
+            requests[0]["resourceSpans"][0]["resource"]["attributes"][0]["value"]["stringValue"],
+            "replica"
+        );
+        assert_eq!(spans.len(), 2);
+        let root = spans
+            .iter()
+            .find(|s| s["name"] == "http_handler.submit")
+            .unwrap();
+        let child = spans
+            .iter()
+            .find(|s| s["name"] == "execution_environment.execute")
+            .unwrap();
+        assert_eq!(root["traceId"], "ab".repeat(16));
+        assert_eq!(root["spanId"], "ab".repeat(8));
+        assert!(root.get("parentSpanId").is_none());
+        assert_eq!(child["traceId"], root["traceId"]);
+        assert_eq!(child["parentSpanId"], root["spanId"]);
+        assert_eq!(child["attributes"][0]["key"], "canister_id");
+        let start: u128 = child["startTimeUnixNano"]
+            .as_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        let end: u128 = child["endTimeUnixNano"].as_str().unwrap().parse().unwrap();
+        assert!(start <= end);
+    }
+}
@@ -0,0 +1,446 @@
+//! Tracing of ingress messages through the replica
+//!
+//! Every sampled ingress message is traced as a distributed trace whose trace
+//! ID is derived from the message's `MessageId`. Hence, the trace context need
+//! not be propagated along with the message: every component that processes
+//! the message, on any node, records its spans into the same trace just by
+//! knowing the `MessageId`. The root span of a trace is recorded by the HTTP
+//! handler that receives the message; all other spans are its children.
+//!
+//! Spans are exported in the OpenTelemetry protocol (OTLP) JSON encoding,
+//! either to an OTLP/HTTP collector or to a local file (see
+//! `ic_config::tracing::Config`).
+//!
+//! Components record spans using the process-wide tracer, which is set up once
+//! by the replica using `set_global_tracer`:
+//! ```
+//! # use ic_types::messages::MessageId;
+//! # let message_id = MessageId::from([0; 32]);
+//! let mut span = ic_tracing::ingress_span(&message_id, "ingress_manager.validate");
+//! span.set_attribute("valid", true);
+//! // The span ends when it is dropped.
+//! ```
+mod exporter;
+mod otlp;
+
+pub use exporter::{FileExporter, OtlpHttpExporter, SpanExporter};
+
+use crossbeam_channel::{Receiver, Sender};
+use ic_config::tracing::{Config, Exporter};
+use ic_logger::{warn, ReplicaLogger};
+use ic_metrics::MetricsRegistry;
+use ic_types::messages::MessageId;
+use lazy_static::lazy_static;
+use prometheus::IntCounter;
+use rand::Rng;
+use std::convert::TryInto;
+use std::io;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+/// The maximum number of finished spans that are buffered for export. Spans
+/// are dropped if the exporter cannot keep up.
+const SPAN_QUEUE_CAPACITY: usize = 10_000;
+/// The maximum number of spans exported at once.
+const MAX_EXPORT_BATCH_SIZE: usize = 512;
+/// The maximum time a finished span is buffered before it is exported.
+const EXPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+lazy_static! {
+    static ref GLOBAL_TRACER: RwLock<Arc<IngressTracer>> =
+        RwLock::new(Arc::new(IngressTracer::disabled()));
+}
+
+/// Sets the tracer that is used by `ingress_span` and `ingress_root_span`.
+pub fn set_global_tracer(tracer: IngressTracer) {
+    *GLOBAL_TRACER.write().unwrap() = Arc::new(tracer);
+}
+
+/// Starts a span of the trace of the ingress message with ID `message_id`
+/// using the global tracer.
+pub fn ingress_span(message_id: &MessageId, name: &'static str) -> SpanGuard {
+    GLOBAL_TRACER.read().unwrap().span(message_id, name)
+}
+
+/// Starts the root span of the trace of the ingress message with ID
+/// `message_id` using the global tracer.
+pub fn ingress_root_span(message_id: &MessageId, name: &'static str) -> SpanGuard {
+    GLOBAL_TRACER.read().unwrap().root_span(message_id, name)
+}
+
+/// A span that has ended and is ready for export.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FinishedSpan {
+    pub trace_id: [u8; 16],
+    pub span_id: [u8; 8],
+    pub parent_span_id: Option<[u8; 8]>,
+    pub name: &'static str,
+    pub start_time: SystemTime,
+    pub end_time: SystemTime,
+    pub attributes: Vec<(String, String)>,
+}
+
+/// Records spans of sampled ingress messages and hands them to an exporter
+/// running on a background thread.
+pub struct IngressTracer {
+    sampling_ratio: f64,
+    sender: Option<Sender<FinishedSpan>>,
+}
+
+/// Metrics of the span export.
+struct ExportMetrics {
+    exported_spans: IntCounter,
+    export_errors: IntCounter,
+}
+
+impl ExportMetrics {
+    fn new(metrics_registry: &MetricsRegistry) -> Self {
+        Self {
+            exported_spans: metrics_registry.int_counter(
+                "ingress_tracing_exported_spans_total",
+                "Number of ingress message spans that were exported successfully.",
+            ),
+            export_errors: metrics_registry.int_counter(
+                "ingress_tracing_export_errors_total",
+                "Number of span batches that could not be exported.",
+            ),
+        }
+    }
+}
+
+impl IngressTracer {
+    /// Creates a tracer that exports spans as configured in `config`, with
+    /// `service_name` as the OTLP service name.
+    ///
+    /// Returns an error if the exporter cannot be set up, e.g., if the file
+    /// to export to cannot be opened.
+    pub fn new(
+        config: &Config,
+        service_name: &str,
+        log: ReplicaLogger,
+        metrics_registry: &MetricsRegistry,
+    ) -> io::Result<Self> {
+        let exporter: Box<dyn SpanExporter> = match &config.exporter {
+            Exporter::None => return Ok(Self::disabled()),
+            Exporter::Otlp(endpoint) => Box::new(OtlpHttpExporter::new(endpoint, service_name)?),
+            Exporter::File(path) => {
+                Box::new(FileExporter::new(path, service_name).map_err(|e| {
+                    io::Error::new(
+                        e.kind(),
+                        format!("Failed to open {}: {}", path.display(), e),
+                    )
+                })?)
+            }
+        };
+        Ok(Self::new_with_exporter(
+            exporter,
+            config.sampling_ratio,
+            log,
+            metrics_registry,
+        ))
+    }
+
+    /// Creates a tracer that exports the spans of a `sampling_ratio` fraction
+    /// of all ingress messages using `exporter`. Export failures are logged to
+    /// `log` and counted in `metrics_registry`.
+    pub fn new_with_exporter(
+        exporter: Box<dyn SpanExporter>,
+        sampling_ratio: f64,
+        log: ReplicaLogger,
+        metrics_registry: &MetricsRegistry,
+    ) -> Self {
+        let (sender, receiver) = crossbeam_channel::bounded(SPAN_QUEUE_CAPACITY);
+        let metrics = ExportMetrics::new(metrics_registry);
+        std::thread::Builder::new()
+            .name("ingress_tracing".to_string())
+            .spawn(move || export_spans(receiver, exporter, log, metrics))
+            .expect("Failed to spawn the span export thread");
+        Self {
+            sampling_ratio,
+            sender: Some(sender),
+        }
+    }
+
+    /// Creates a tracer that does not record any spans.
+    pub fn disabled() -> Self {
+        Self {
+            sampling_ratio: 0.0,
+            sender: None,
+        }
+    }
+
+    /// Returns true iff the message with ID `message_id` is traced.
+    ///
+    /// Like OpenTelemetry's trace ID ratio based sampler, this compares part of
+    /// the trace ID with the sampling ratio, so that the decision is the same
+    /// for every component and every node.
+    pub fn is_sampled(&self, message_id: &MessageId) -> bool {
+        if self.sender.is_none() || self.sampling_ratio <= 0.0 {
+            return false;
+        }
+        if self.sampling_ratio >= 1.0 {
+            return true;
+        }
+        let bytes: [u8; 8] = message_id.as_bytes()[..8].try_into().unwrap();
+        let threshold = (self.sampling_ratio * (1u64 << 63) as f64) as u64;
+        (u64::from_be_bytes(bytes) >> 1) < threshold
+    }
+
+    /// Starts the root span of the trace of the message with ID `message_id`.
+    pub fn root_span(&self, message_id: &MessageId, name: &'static str) -> SpanGuard {
+        self.start(message_id, name, root_span_id(message_id), None)
+    }
+
+    /// Starts a child of the root span of the trace of the message with ID
+    /// `message_id`.
+    pub fn span(&self, message_id: &MessageId, name: &'static str) -> SpanGuard {
+        let span_id = rand::thread_rng().gen();
+        self.start(message_id, name, span_id, Some(root_span_id(message_id)))
+    }
+
+    fn start(
+        &self,
+        message_id: &MessageId,
+        name: &'static str,
+        span_id: [u8; 8],
+        parent_span_id: Option<[u8; 8]>,
+    ) -> SpanGuard {
+        if !self.is_sampled(message_id) {
+            return SpanGuard { span: None };
+        }
+        let span = FinishedSpan {
+            trace_id: trace_id(message_id),
+            span_id,
+            parent_span_id,
+            name,
+            start_time: SystemTime::now(),
+            end_time: SystemTime::UNIX_EPOCH,
+            attributes: vec![],
+        };
+        SpanGuard {
+            span: self.sender.clone().map(|sender| (span, sender)),
+        }
+    }
+}
+
+/// A span in progress that ends when it is dropped. Does nothing if the
+/// message is not sampled.
+#[must_use = "the span ends when the guard is dropped"]
+pub struct SpanGuard {
+    span: Option<(FinishedSpan, Sender<FinishedSpan>)>,
+}
+
+impl SpanGuard {
+    /// Adds an attribute to the span.
+    pub fn set_attribute<T: ToString>(&mut self, key: &str, value: T) {
+        if let Some((span, _)) = &mut self.span {
+            span.attributes.push((key.to_string(), value.to_string()));
+        }
+    }
+}
+
+impl Drop for SpanGuard {
+    fn drop(&mut self) {
+        if let Some((mut span, sender)) = self.span.take() {
+            span.end_time = SystemTime::now();
+            // Drop the span rather than block if the exporter cannot keep up.
+            let _ = sender.try_send(span);
+        }
+    }
+}
+
+fn trace_id(message_id: &MessageId) -> [u8; 16] {
+    message_id.as_bytes()[..16].try_into().unwrap()
+}
+
+fn root_span_id(message_id: &MessageId) -> [u8; 8] {
+    message_id.as_bytes()[16..24].try_into().unwrap()
+}
+
+/// Exports the received spans in batches until all senders are dropped.
+fn export_spans(
+    receiver: Receiver<FinishedSpan>,
+    mut exporter: Box<dyn SpanExporter>,
+    log: ReplicaLogger,
+    metrics: ExportMetrics,
+) {
+    let mut batch = Vec::with_capacity(MAX_EXPORT_BATCH_SIZE);
+    loop {
+        let disconnected = match receiver.recv_timeout(EXPORT_INTERVAL) {
+            Ok(span) => {
+                batch.push(span);
+                batch.extend(receiver.try_iter().take(MAX_EXPORT_BATCH_SIZE - 1));
+                false
+            }
+            Err(crossbeam_channel::RecvTimeoutError::Timeout) => false,
+            Err(crossbeam_channel::RecvTimeoutError::Disconnected) => true,
+        };
+        if !batch.is_empty() {
+            match exporter.export(&batch) {
+                Ok(()) => metrics.exported_spans.inc_by(batch.len() as u64),
+                Err(e) => {
+                    metrics.export_errors.inc();
+                    warn!(log, "Failed to export {} spans: {}", batch.len(), e);
+                }
+            }
+            batch.clear();
+        }
+        if disconnected {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_logger::replica_logger::no_op_logger;
+    use std::sync::Mutex;
+
+    #[derive(Clone, Default)]
+    struct RecordingExporter {
+        spans: Arc<Mutex<Vec<FinishedSpan>>>,
+    }
+
+    impl SpanExporter for RecordingExporter {
+        fn export(&mut self, spans: &[FinishedSpan]) -> Result<(), String> {
+            self.spans.lock().unwrap().extend_from_slice(spans);
+            Ok(())
+        }
+    }
+
+    struct FailingExporter;
+
+    impl SpanExporter for FailingExporter {
+        fn export(&mut self, _spans: &[FinishedSpan]) -> Result<(), String> {
+            Err("collector unavailable".to_string())
+        }
+    }
+
+    fn new_tracer(exporter: Box<dyn SpanExporter>, sampling_ratio: f64) -> IngressTracer {
+        IngressTracer::new_with_exporter(
+            exporter,
+            sampling_ratio,
+            no_op_logger(),
+            &MetricsRegistry::new(),
+        )
+    }
+
+    fn message_id(first_byte: u8) -> MessageId {
+        let mut bytes = [7; 32];
+        bytes[0] = first_byte;
+        MessageId::from(bytes)
+    }
+
+    fn recorded_spans(exporter: &RecordingExporter, count: usize) -> Vec<FinishedSpan> {
+        for _ in 0..100 {
+            if exporter.spans.lock().unwrap().len() >= count {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        exporter.spans.lock().unwrap().clone()
+    }
+
+    #[test]
+    fn should_record_spans_into_trace_of_message() {
+        let exporter = RecordingExporter::default();
+        let tracer = new_tracer(Box::new(exporter.clone()), 1.0);
+        let message_id = message_id(1);
+
+        {
+            let _root = tracer.root_span(&message_id, "http_handler.submit");
+            let mut child = tracer.span(&message_id, "ingress_manager.validate");
+            child.set_attribute("valid", true);
+        }
+
+        let spans = recorded_spans(&exporter, 2);
+        assert_eq!(spans.len(), 2);
+        let child = spans.iter().find(|s| s.parent_span_id.is_some()).unwrap();
+        let root = spans.iter().find(|s| s.parent_span_id.is_none()).unwrap();
+        assert_eq!(root.name, "http_handler.submit");
+        assert_eq!(root.trace_id, trace_id(&message_id));
+        assert_eq!(root.span_id, root_span_id(&message_id));
+        assert_eq!(child.name, "ingress_manager.validate");
+        assert_eq!(child.trace_id, root.trace_id);
+        assert_eq!(child.parent_span_id, Some(root.span_id));
+        assert_eq!(
+            child.attributes,
+            vec![("valid".to_string(), "true".to_string())]
+        );
+        assert!(child.start_time <= child.end_time);
+    }
+
+    #[test]
+    fn should_sample_by_message_id() {
+        let tracer = new_tracer(Box::new(RecordingExporter::default()), 0.5);
+
+        assert!(tracer.is_sampled(&message_id(0x00)));
+        assert!(tracer.is_sampled(&message_id(0x7f)));
+        assert!(!tracer.is_sampled(&message_id(0x80)));
+        assert!(!tracer.is_sampled(&message_id(0xff)));
+    }
+
+    #[test]
+    fn should_not_record_unsampled_spans() {
+        let exporter = RecordingExporter::default();
+        let tracer = new_tracer(Box::new(exporter.clone()), 0.0);
+
+        drop(tracer.root_span(&message_id(1), "http_handler.submit"));
+        drop(tracer);
+
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(exporter.spans.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn disabled_tracer_samples_nothing() {
+        assert!(!IngressTracer::disabled().is_sampled(&message_id(0)));
+    }
+
+    #[test]
+    fn should_count_failed_exports() {
+        let metrics_registry = MetricsRegistry::new();
+        let tracer = IngressTracer::new_with_exporter(
+            Box::new(FailingExporter),
+            1.0,
+            no_op_logger(),
+            &metrics_registry,
+        );
+
+        drop(tracer.root_span(&message_id(1), "http_handler.submit"));
+        drop(tracer);
+
+        for _ in 0..100 {
+            if export_errors(&metrics_registry) > 0 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(export_errors(&metrics_registry), 1);
+    }
+
+    fn export_errors(metrics_registry: &MetricsRegistry) -> u64 {
+        metrics_registry
+            .prometheus_registry()
+            .gather()
+            .iter()
+            .find(|family| family.get_name() == "ingress_tracing_export_errors_total")
+            .map(|family| family.get_metric()[0].get_counter().get_value() as u64)
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn should_fail_to_create_tracer_if_export_file_cannot_be_opened() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            exporter: Exporter::File(dir.path().join("missing_dir").join("traces.json")),
+            sampling_ratio: 1.0,
+        };
+
+        let result =
+            IngressTracer::new(&config, "replica", no_op_logger(), &MetricsRegistry::new());
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,122 @@
+//! The OTLP JSON encoding of spans
+//!
+//! Mirrors the `ExportTraceServiceRequest` message of the OpenTelemetry
+//! protocol, as specified for OTLP/HTTP with JSON payloads: field names are in
+//! lowerCamelCase, trace and span IDs are hex-encoded and 64-bit integers are
+//! encoded as decimal strings.
+use crate::FinishedSpan;
+use serde::Serialize;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// `SPAN_KIND_INTERNAL`: the span represents an operation within the replica.
+const SPAN_KIND_INTERNAL: u32 = 1;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ExportTraceServiceRequest {
+    resource_spans: Vec<ResourceSpans>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ResourceSpans {
+    resource: Resource,
+    scope_spans: Vec<ScopeSpans>,
+}
+
+#[derive(Serialize)]
+struct Resource {
+    attributes: Vec<KeyValue>,
+}
+
+#[derive(Serialize)]
+struct ScopeSpans {
+    scope: InstrumentationScope,
+    spans: Vec<Span>,
+}
+
+#[derive(Serialize)]
+struct InstrumentationScope {
+    name: &'static str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Span {
+    trace_id: String,
+    span_id: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    parent_span_id: String,
+    name: &'static str,
+    kind: u32,
+    start_time_unix_nano: String,
+    end_time_unix_nano: String,
+    attributes: Vec<KeyValue>,
+}
+
+#[derive(Serialize)]
+struct KeyValue {
+    key: String,
+    value: AnyValue,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AnyValue {
+    string_value: String,
+}
+
+impl KeyValue {
+    fn new(key: &str, value: &str) -> Self {
+        Self {
+            key: key.to_string(),
+            value: AnyValue {
+                string_value: value.to_string(),
+            },
+        }
+    }
+}
+
+impl ExportTraceServiceRequest {
+    pub(crate) fn new(service_name: &str, spans: &[FinishedSpan]) -> Self {
+        Self {
+            resource_spans: vec![ResourceSpans {
+                resource: Resource {
+                    attributes: vec![KeyValue::new("service.name", service_name)],
+                },
+                scope_spans: vec![ScopeSpans {
+                    scope: InstrumentationScope {
+                        name: env!("CARGO_PKG_NAME"),
+                    },
+                    spans: spans.iter().map(Span::from).collect(),
+                }],
+            }],
+        }
+    }
+}
+
+impl From<&FinishedSpan> for Span {
+    fn from(span: &FinishedSpan) -> Self {
+        Self {
+            trace_id: hex::encode(span.trace_id),
+            span_id: hex::encode(span.span_id),
+            parent_span_id: span.parent_span_id.map(hex::encode).unwrap_or_default(),
+            name: span.name,
+            kind: SPAN_KIND_INTERNAL,
+            start_time_unix_nano: unix_nanos(span.start_time),
+            end_time_unix_nano: unix_nanos(span.end_time),
+            attributes: span
+                .attributes
+                .iter()
+                .map(|(key, value)| KeyValue::new(key, value))
+                .collect(),
+        }
+    }
+}
+
+fn unix_nanos(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+        .to_string()
+}
@@ -18,7 +18,7 @@ use criterion::{criterion_group, criterion_main, Criterion};
 use futures::future::FutureExt;
 use std::convert::TryFrom;
 
-use ic_base_types::PrincipalId;
+use ic_base_types::{CanisterId, PrincipalId};
 use ic_nns_common::pb::v1::NeuronId;
 use ic_nns_governance::governance::{Environment, Governance, HeapGrowthPotential, Ledger};
 
@@ -43,6 +43,7 @@ struct MockEnvironment {
     secs: u64,
 }
 
+#[async_trait]
 impl Environment for MockEnvironment {
     fn now(&self) -> u64 {
         self.secs
@@ -67,6 +68,15 @@ impl Environment for MockEnvironment {
     fn heap_growth_potential(&self) -> HeapGrowthPotential {
         HeapGrowthPotential::NoIssue
     }
+
+    async fn call_canister(
+        &self,
+        _canister_id: CanisterId,
+        _method_name: &str,
+        _arg: Vec<u8>,
+    ) -> Result<Vec<u8>, (Option<i32>, String)> {
+        panic!("unexpected call")
+    }
 }
 
 struct MockLedger {}
@@ -473,6 +473,22 @@ fn main() {
         ]
         .join("\n"),
     );
+    config.type_attribute(
+        "ic_nns_governance.pb.v1.MonthlyNodeProviderRewards",
+        [
+            "#[derive(candid::CandidType, candid::Deserialize)]",
+            "#[cfg_attr(feature = \"test\", derive(comparable::Comparable))]",
+        ]
+        .join("\n"),
+    );
+    config.type_attribute(
+        "ic_nns_governance.pb.v1.ListNodeProviderRewardsRequest",
+        "#[derive(candid::CandidType, candid::Deserialize)]",
+    );
+    config.type_attribute(
+        "ic_nns_governance.pb.v1.ListNodeProviderRewardsResponse",
+        "#[derive(candid::CandidType, candid::Deserialize)]",
+    );
     config.type_attribute(
         "ic_nns_governance.pb.v1.SetDefaultFollowees",
         [
@@ -481,6 +497,26 @@ fn main() {
         ]
         .join("\n"),
     );
+    config.type_attribute(
+        "ic_nns_governance.pb.v1.KnownNeuronData",
+        [
+            "#[derive(candid::CandidType, candid::Deserialize, Eq)]",
+            "#[cfg_attr(feature = \"test\", derive(comparable::Comparable))]",
+        ]
+        .join("\n"),
+    );
+    config.type_attribute(
+        "ic_nns_governance.pb.v1.KnownNeuron",
+        [
+            "#[derive(candid::CandidType, candid::Deserialize)]",
+            "#[cfg_attr(feature = \"test\", derive(comparable::Comparable))]",
+        ]
+        .join("\n"),
+    );
+    config.type_attribute(
+        "ic_nns_governance.pb.v1.ListKnownNeuronsResponse",
+        "#[derive(candid::CandidType, candid::Deserialize)]",
+    );
     config.type_attribute(
         "ic_nns_governance.pb.v1.Proposal.action",
         [
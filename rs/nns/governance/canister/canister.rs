@@ -26,7 +26,7 @@ use dfn_core::{
 };
 use dfn_protobuf::protobuf;
 
-use ic_base_types::PrincipalId;
+use ic_base_types::{CanisterId, PrincipalId};
 use ic_nns_common::{
     access_control::{check_caller_is_ledger, check_caller_is_root},
     pb::v1::{CanisterAuthzInfo, NeuronId as NeuronIdProto, ProposalId as ProposalIdProto},
@@ -46,9 +46,10 @@ use ic_nns_governance::{
         },
         manage_neuron_response, ClaimOrRefreshNeuronFromAccount,
         ClaimOrRefreshNeuronFromAccountResponse, ExecuteNnsFunction, Governance as GovernanceProto,
-        GovernanceError, ListNeurons, ListNeuronsResponse, ListProposalInfo,
-        ListProposalInfoResponse, ManageNeuron, ManageNeuronResponse, Neuron, NeuronInfo,
-        NnsFunction, Proposal, ProposalInfo, Vote,
+        GovernanceError, ListKnownNeuronsResponse, ListNeurons, ListNeuronsResponse,
+        ListNodeProviderRewardsRequest, ListNodeProviderRewardsResponse, ListProposalInfo,
+        ListProposalInfoResponse, ManageNeuron, ManageNeuronResponse, MonthlyNodeProviderRewards,
+        Neuron, NeuronInfo, NnsFunction, Proposal, ProposalInfo, Vote,
     },
 };
 
@@ -59,6 +60,7 @@ use ledger_canister::{
     metrics_encoder, AccountBalanceArgs, AccountIdentifier, ICPTs, Memo, SendArgs, Subaccount,
     TotalSupplyArgs,
 };
+use on_wire::bytes;
 
 /// Size of the buffer for stable memory reads and writes.
 ///
@@ -118,6 +120,7 @@ impl CanisterEnv {
     }
 }
 
+#[async_trait]
 impl Environment for CanisterEnv {
     fn now(&self) -> u64 {
         now()
@@ -201,6 +204,15 @@ impl Environment for CanisterEnv {
     fn heap_growth_potential(&self) -> HeapGrowthPotential {
         unimplemented!("CanisterEnv can only be used with wasm32 environment.");
     }
+
+    async fn call_canister(
+        &self,
+        canister_id: CanisterId,
+        method_name: &str,
+        arg: Vec<u8>,
+    ) -> Result<Vec<u8>, (Option<i32>, String)> {
+        call(canister_id, method_name, bytes, arg).await
+    }
 }
 
 struct LedgerCanister {}
@@ -631,6 +643,54 @@ fn list_neurons_(req: ListNeurons) -> ListNeuronsResponse {
     governance().list_neurons_by_principal(&req, &caller())
 }
 
+/// Returns all known neurons, i.e. neurons that were given a name (and
+/// optionally a description) by a `RegisterKnownNeuron` proposal.
+#[export_name = "canister_query list_known_neurons"]
+fn list_known_neurons() {
+    println!("{}list_known_neurons", LOG_PREFIX);
+    over(candid, |()| -> ListKnownNeuronsResponse {
+        list_known_neurons_()
+    })
+}
+
+#[candid_method(query, rename = "list_known_neurons")]
+fn list_known_neurons_() -> ListKnownNeuronsResponse {
+    governance().list_known_neurons()
+}
+
+/// Computes the monthly node provider rewards from the registry and the
+/// cycles minting canister's average ICP/XDR conversion rate, without minting
+/// them. This lets voters check what a `RewardNodeProviders` proposal using
+/// registry-derived rewards would mint.
+#[export_name = "canister_update get_monthly_node_provider_rewards"]
+fn get_monthly_node_provider_rewards() {
+    println!("{}get_monthly_node_provider_rewards", LOG_PREFIX);
+    over_async(candid, |()| async move {
+        get_monthly_node_provider_rewards_().await
+    })
+}
+
+#[candid_method(update, rename = "get_monthly_node_provider_rewards")]
+async fn get_monthly_node_provider_rewards_() -> Result<MonthlyNodeProviderRewards, GovernanceError>
+{
+    governance().get_monthly_node_provider_rewards().await
+}
+
+/// Returns the node provider rewards that were minted from registry-derived
+/// rewards, most recent first.
+#[export_name = "canister_query list_node_provider_rewards"]
+fn list_node_provider_rewards() {
+    println!("{}list_node_provider_rewards", LOG_PREFIX);
+    over(candid_one, list_node_provider_rewards_)
+}
+
+#[candid_method(query, rename = "list_node_provider_rewards")]
+fn list_node_provider_rewards_(
+    req: ListNodeProviderRewardsRequest,
+) -> ListNodeProviderRewardsResponse {
+    governance().list_node_provider_rewards(&req)
+}
+
 /// DEPRECATED: Always panics. Use manage_neuron instead.
 /// TODO(NNS1-413): Remove this once we are sure that there are no callers.
 #[export_name = "canister_update submit_proposal"]
@@ -19,23 +19,28 @@ use crate::pb::v1::{
     neuron::DissolveState,
     neuron::Followees,
     proposal,
-    reward_node_provider::RewardMode,
+    reward_node_provider::{RewardMode, RewardToAccount},
     Ballot, BallotInfo, ExecuteNnsFunction, Governance as GovernanceProto, GovernanceError,
-    ListNeurons, ListNeuronsResponse, ListProposalInfo, ListProposalInfoResponse, ManageNeuron,
-    ManageNeuronResponse, NetworkEconomics, Neuron, NeuronInfo, NeuronState, NnsFunction,
-    NodeProvider, Proposal, ProposalData, ProposalInfo, ProposalRewardStatus, ProposalStatus,
-    RewardEvent, RewardNodeProvider, Tally, Topic, UpdateNodeProvider, Vote,
+    KnownNeuron, ListKnownNeuronsResponse, ListNeurons, ListNeuronsResponse,
+    ListNodeProviderRewardsRequest, ListNodeProviderRewardsResponse, ListProposalInfo,
+    ListProposalInfoResponse, ManageNeuron, ManageNeuronResponse, MonthlyNodeProviderRewards,
+    NetworkEconomics, Neuron, NeuronInfo, NeuronState, NnsFunction, NodeProvider, Proposal,
+    ProposalData, ProposalInfo, ProposalRewardStatus, ProposalStatus, RewardEvent,
+    RewardNodeProvider, Tally, Topic, UpdateNodeProvider, Vote,
 };
-use candid::Decode;
+use candid::{Decode, Encode};
+use cycles_minting_canister::IcpXdrConversionRateCertifiedResponse;
 use dfn_protobuf::ToProto;
 use ic_base_types::{CanisterId, PrincipalId};
 use ic_crypto_sha::Sha256;
+use ic_error_types::RejectCode;
 use ic_nns_common::pb::v1::{NeuronId, ProposalId};
 use ic_nns_constants::{
     CYCLES_MINTING_CANISTER_ID, GENESIS_TOKEN_CANISTER_ID, GOVERNANCE_CANISTER_ID,
     LIFELINE_CANISTER_ID, REGISTRY_CANISTER_ID, ROOT_CANISTER_ID,
 };
 use ic_protobuf::registry::dc::v1::AddOrRemoveDataCentersProposalPayload;
+use ic_protobuf::registry::node_rewards::v2::NodeProvidersMonthlyXdrRewards;
 use ledger_canister::{AccountIdentifier, Subaccount, TRANSACTION_FEE};
 use registry_canister::mutations::do_add_node_operator::AddNodeOperatorPayload;
 
@@ -83,6 +88,11 @@ pub const EXECUTE_NNS_FUNCTION_PAYLOAD_LISTING_BYTES_MAX: usize = 1000;
 // 10 KB
 pub const PROPOSAL_MOTION_TEXT_BYTES_MAX: usize = 10000;
 
+// The maximum number of bytes in a known neuron's name.
+pub const KNOWN_NEURON_NAME_MAX_LEN: usize = 200;
+// The maximum number of bytes in a known neuron's description.
+pub const KNOWN_NEURON_DESCRIPTION_MAX_LEN: usize = 3000;
+
 // The maximum dissolve delay allowed for a neuron.
 pub const MAX_DISSOLVE_DELAY_SECONDS: u64 = 8 * ONE_YEAR_SECONDS;
 
@@ -115,6 +125,16 @@ pub const MAX_NUMBER_OF_NEURONS: usize = 200_000;
 /// The maximum number results returned by the method `list_proposals`.
 pub const MAX_LIST_PROPOSAL_RESULTS: u32 = 100;
 
+/// The maximum number results returned by the method
+/// `list_node_provider_rewards`.
+pub const MAX_LIST_NODE_PROVIDER_REWARDS_RESULTS: u32 = 100;
+
+/// The minimum time between two mintings of registry-derived node provider
+/// rewards. As no month is shorter than 28 days, this allows rewards to be
+/// minted on the same day of every month, but prevents them from being minted
+/// twice for the same month.
+pub const NODE_PROVIDER_REWARDS_MIN_PERIOD_SECONDS: u64 = 28 * ONE_DAY_SECONDS;
+
 /// The number of e8s per ICPT;
 const E8S_PER_ICPT: u64 = ICP_SUBDIVIDABLE_BY;
 
@@ -909,6 +929,7 @@ impl Neuron {
             created_timestamp_seconds: self.created_timestamp_seconds,
             stake_e8s: self.stake_e8s(),
             joined_community_fund_timestamp_seconds: self.joined_community_fund_timestamp_seconds,
+            known_neuron_data: self.known_neuron_data.clone(),
         }
     }
 
@@ -1030,6 +1051,7 @@ impl Proposal {
                 proposal::Action::RewardNodeProvider(_)
                 | proposal::Action::RewardNodeProviders(_) => Topic::NodeProviderRewards,
                 proposal::Action::SetDefaultFollowees(_) => Topic::Governance,
+                proposal::Action::RegisterKnownNeuron(_) => Topic::Governance,
             }
         } else {
             Topic::Unspecified
@@ -1602,6 +1624,7 @@ impl fmt::Display for RewardEvent {
 }
 
 /// A general trait for the environment in which governance is running.
+#[async_trait]
 pub trait Environment: Send + Sync {
     /// Returns the current time, in seconds since the epoch.
     fn now(&self) -> u64;
@@ -1631,6 +1654,16 @@ pub trait Environment: Send + Sync {
     /// non-essential memory-consuming operations when the potential for heap
     /// growth becomes limited.
     fn heap_growth_potential(&self) -> HeapGrowthPotential;
+
+    /// Calls `method_name` of the canister `canister_id` with the (already
+    /// encoded) argument `arg`, and returns the (still encoded) reply, or the
+    /// reject code and message.
+    async fn call_canister(
+        &self,
+        canister_id: CanisterId,
+        method_name: &str,
+        arg: Vec<u8>,
+    ) -> Result<Vec<u8>, (Option<i32>, String)>;
 }
 
 /// Rough buckets for how much the heap can still grow.
@@ -2090,6 +2123,40 @@ impl Governance {
         }
     }
 
+    /// Returns all known neurons, ordered by neuron ID.
+    pub fn list_known_neurons(&self) -> ListKnownNeuronsResponse {
+        let mut known_neurons: Vec<KnownNeuron> = self
+            .proto
+            .neurons
+            .values()
+            .filter(|neuron| neuron.known_neuron_data.is_some())
+            .map(|neuron| KnownNeuron {
+                id: neuron.id.clone(),
+                known_neuron_data: neuron.known_neuron_data.clone(),
+            })
+            .collect();
+        known_neurons.sort_by_key(|known_neuron| known_neuron.id.as_ref().map(|id| id.id));
+        ListKnownNeuronsResponse { known_neurons }
+    }
+
+    /// Returns the ID of the known neuron with the given name, if any.
+    ///
+    /// Like `get_neuron_by_subaccount`, this does a linear search on the
+    /// neurons, which is fine as it is only used when registering known
+    /// neurons.
+    fn get_known_neuron_id_by_name(&self, name: &str) -> Option<NeuronId> {
+        self.proto
+            .neurons
+            .values()
+            .find(|neuron| {
+                neuron
+                    .known_neuron_data
+                    .as_ref()
+                    .map_or(false, |data| data.name == name)
+            })
+            .and_then(|neuron| neuron.id.clone())
+    }
+
     /// Returns a neuron, given a subaccount.
     ///
     /// Currently we just do linear search on the neurons. We tried an index at
@@ -2566,6 +2633,9 @@ impl Governance {
             // of the fund with the same "join date".
             joined_community_fund_timestamp_seconds: parent_neuron
                 .joined_community_fund_timestamp_seconds,
+            // The name of a known neuron must remain unique, so the child
+            // neuron is not a known neuron.
+            known_neuron_data: None,
         };
 
         // Add the child neuron to the set of neurons undergoing ledger updates.
@@ -2736,6 +2806,7 @@ impl Governance {
             // joined the community fund: the spawned neuron is not
             // considered part of the community fund.
             joined_community_fund_timestamp_seconds: None,
+            known_neuron_data: None,
         };
 
         self.add_neuron(child_nid.id, child_neuron.clone())?;
@@ -3094,6 +3165,7 @@ impl Governance {
             maturity_e8s_equivalent: 0,
             not_for_profit: false,
             joined_community_fund_timestamp_seconds: None,
+            known_neuron_data: None,
         };
 
         self.add_neuron(child_nid.id, child_neuron.clone())?;
@@ -3718,6 +3790,7 @@ impl Governance {
                     not_for_profit: false,
                     transfer: None,
                     joined_community_fund_timestamp_seconds: None,
+                    known_neuron_data: None,
                 };
                 self.add_neuron(nid.id, neuron)
             }
@@ -3824,6 +3897,283 @@ impl Governance {
         self.set_proposal_execution_status(pid, result);
     }
 
+    /// Computes the monthly rewards of the registered node providers.
+    ///
+    /// The rewards in XDR are those that the registry derives from its node
+    /// rewards table, node operator records and data center records. They are
+    /// converted to ICP at the cycles minting canister's average ICP/XDR
+    /// conversion rate, and capped at `maximum_node_provider_rewards_e8s`.
+    /// Rewards are minted to the node provider's reward account, if any, and
+    /// to the node provider's default account otherwise.
+    pub async fn get_monthly_node_provider_rewards(
+        &self,
+    ) -> Result<MonthlyNodeProviderRewards, GovernanceError> {
+        let xdr_rewards = self.get_node_providers_monthly_xdr_rewards().await?;
+        let xdr_permyriad_per_icp = self
+            .get_average_icp_xdr_conversion_rate()
+            .await?
+            .xdr_permyriad_per_icp;
+        if xdr_permyriad_per_icp == 0 {
+            return Err(GovernanceError::new_with_message(
+                ErrorType::External,
+                "The average ICP/XDR conversion rate is zero.",
+            ));
+        }
+        let maximum_node_provider_rewards_e8s = self.economics().maximum_node_provider_rewards_e8s;
+
+        let mut rewards = vec![];
+        for node_provider in &self.proto.node_providers {
+            let node_provider_id = match node_provider.id.as_ref() {
+                Some(id) => id,
+                None => continue,
+            };
+            if let Some(xdr_permyriad) = xdr_rewards.rewards.get(&node_provider_id.to_string()) {
+                let amount_e8s = (*xdr_permyriad as u128) * (E8S_PER_ICPT as u128)
+                    / (xdr_permyriad_per_icp as u128);
+                let amount_e8s =
+                    std::cmp::min(amount_e8s, maximum_node_provider_rewards_e8s as u128) as u64;
+                rewards.push(RewardNodeProvider {
+                    node_provider: Some(node_provider.clone()),
+                    amount_e8s,
+                    reward_mode: Some(RewardMode::RewardToAccount(RewardToAccount {
+                        to_account: node_provider.reward_account.clone(),
+                    })),
+                });
+            }
+        }
+
+        Ok(MonthlyNodeProviderRewards {
+            timestamp: self.env.now(),
+            rewards,
+            xdr_permyriad_per_icp,
+            failed_rewards: vec![],
+        })
+    }
+
+    /// Returns the monthly rewards, in 10,000ths of XDR, of each node provider
+    /// as computed by the registry.
+    async fn get_node_providers_monthly_xdr_rewards(
+        &self,
+    ) -> Result<NodeProvidersMonthlyXdrRewards, GovernanceError> {
+        let reply = self
+            .env
+            .call_canister(
+                REGISTRY_CANISTER_ID,
+                "get_node_providers_monthly_xdr_rewards",
+                Encode!().unwrap(),
+            )
+            .await
+            .map_err(|(code, msg)| {
+                GovernanceError::new_with_message(
+                    ErrorType::External,
+                    format!(
+                        "Error calling method 'get_node_providers_monthly_xdr_rewards' of the registry canister. Code: {:?}. Message: {}",
+                        code, msg
+                    ),
+                )
+            })?;
+
+        Decode!(&reply, Result<NodeProvidersMonthlyXdrRewards, String>)
+            .map_err(|e| {
+                GovernanceError::new_with_message(
+                    ErrorType::External,
+                    format!(
+                        "Could not decode the node provider rewards returned by the registry canister: {}",
+                        e
+                    ),
+                )
+            })?
+            .map_err(|msg| {
+                GovernanceError::new_with_message(
+                    ErrorType::External,
+                    format!(
+                        "The registry canister could not compute the node provider rewards: {}",
+                        msg
+                    ),
+                )
+            })
+    }
+
+    /// Returns the average ICP/XDR conversion rate of the cycles minting
+    /// canister.
+    async fn get_average_icp_xdr_conversion_rate(
+        &self,
+    ) -> Result<cycles_minting_canister::IcpXdrConversionRate, GovernanceError> {
+        let reply = self
+            .env
+            .call_canister(
+                CYCLES_MINTING_CANISTER_ID,
+                "get_average_icp_xdr_conversion_rate",
+                Encode!().unwrap(),
+            )
+            .await
+            .map_err(|(code, msg)| {
+                GovernanceError::new_with_message(
+                    ErrorType::External,
+                    format!(
+                        "Error calling method 'get_average_icp_xdr_conversion_rate' of the cycles minting canister. Code: {:?}. Message: {}",
+                        code, msg
+                    ),
+                )
+            })?;
+
+        Decode!(&reply, IcpXdrConversionRateCertifiedResponse)
+            .map(|response| response.data)
+            .map_err(|e| {
+                GovernanceError::new_with_message(
+                    ErrorType::External,
+                    format!(
+                        "Could not decode the ICP/XDR conversion rate returned by the cycles minting canister: {}",
+                        e
+                    ),
+                )
+            })
+    }
+
+    /// Returns an error if registry-derived node provider rewards can be
+    /// neither minted nor retried now, see
+    /// `mint_monthly_node_provider_rewards`.
+    fn check_node_provider_rewards_period(&self) -> Result<(), GovernanceError> {
+        if self.has_failed_node_provider_rewards() {
+            return Ok(());
+        }
+        self.check_node_provider_rewards_min_period()
+    }
+
+    /// Returns an error if registry-derived node provider rewards were minted
+    /// less than `NODE_PROVIDER_REWARDS_MIN_PERIOD_SECONDS` ago.
+    fn check_node_provider_rewards_min_period(&self) -> Result<(), GovernanceError> {
+        if let Some(most_recent) = self.proto.node_provider_rewards_history.last() {
+            let earliest_next_timestamp =
+                most_recent.timestamp + NODE_PROVIDER_REWARDS_MIN_PERIOD_SECONDS;
+            if self.env.now() < earliest_next_timestamp {
+                return Err(GovernanceError::new_with_message(
+                    ErrorType::PreconditionFailed,
+                    format!(
+                        "Node provider rewards were last minted at {}, the next ones cannot be minted before {}.",
+                        most_recent.timestamp, earliest_next_timestamp
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Computes the monthly node provider rewards from the registry, mints
+    /// them, and records the rewards that were minted, as well as those that
+    /// failed to be minted, in the node provider rewards history.
+    ///
+    /// If the most recent rewards were computed less than
+    /// `NODE_PROVIDER_REWARDS_MIN_PERIOD_SECONDS` ago and some of them failed
+    /// to be minted, only those are retried instead.
+    async fn mint_monthly_node_provider_rewards(&mut self) -> Result<(), GovernanceError> {
+        match self.check_node_provider_rewards_min_period() {
+            Ok(()) => (),
+            Err(_) if self.has_failed_node_provider_rewards() => {
+                return self.retry_failed_node_provider_rewards().await;
+            }
+            Err(e) => return Err(e),
+        }
+        let monthly_rewards = self.get_monthly_node_provider_rewards().await?;
+
+        // Other rewards may have been minted while waiting for the registry
+        // and the cycles minting canister, so check again, and record the
+        // rewards before minting them to prevent this from happening while
+        // waiting for the ledger.
+        self.check_node_provider_rewards_min_period()?;
+        let MonthlyNodeProviderRewards {
+            timestamp,
+            rewards,
+            xdr_permyriad_per_icp,
+            ..
+        } = monthly_rewards;
+        self.proto
+            .node_provider_rewards_history
+            .push(MonthlyNodeProviderRewards {
+                timestamp,
+                rewards: vec![],
+                xdr_permyriad_per_icp,
+                failed_rewards: vec![],
+            });
+        let history_index = self.proto.node_provider_rewards_history.len() - 1;
+        self.mint_node_provider_rewards(history_index, rewards)
+            .await
+    }
+
+    /// Retries minting the rewards of the most recent entry of the node
+    /// provider rewards history that failed to be minted.
+    async fn retry_failed_node_provider_rewards(&mut self) -> Result<(), GovernanceError> {
+        let history_index = self.proto.node_provider_rewards_history.len() - 1;
+        // The failed rewards are taken out of the history before minting them,
+        // so that a concurrent retry does not mint them again.
+        let failed_rewards = std::mem::take(
+            &mut self.proto.node_provider_rewards_history[history_index].failed_rewards,
+        );
+        self.mint_node_provider_rewards(history_index, failed_rewards)
+            .await
+    }
+
+    /// Mints `rewards` and records each of them in the entry of the node
+    /// provider rewards history at `history_index`, either as minted or as
+    /// failed. Returns the first error, if any.
+    async fn mint_node_provider_rewards(
+        &mut self,
+        history_index: usize,
+        rewards: Vec<RewardNodeProvider>,
+    ) -> Result<(), GovernanceError> {
+        let mut result = Ok(());
+        for reward in rewards {
+            let reward_result = self.reward_node_provider_helper(&reward).await;
+            let history_entry = &mut self.proto.node_provider_rewards_history[history_index];
+            match reward_result {
+                Ok(()) => history_entry.rewards.push(reward),
+                Err(e) => {
+                    println!("{}Rewarding {:?} failed. Reason: {}", LOG_PREFIX, reward, e);
+                    history_entry.failed_rewards.push(reward);
+                    if result.is_ok() {
+                        result = Err(e);
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// Returns true iff some of the most recently computed node provider
+    /// rewards failed to be minted.
+    fn has_failed_node_provider_rewards(&self) -> bool {
+        self.proto
+            .node_provider_rewards_history
+            .last()
+            .map_or(false, |most_recent| !most_recent.failed_rewards.is_empty())
+    }
+
+    /// Returns the node provider rewards minted from registry-derived
+    /// rewards, most recent first.
+    pub fn list_node_provider_rewards(
+        &self,
+        req: &ListNodeProviderRewardsRequest,
+    ) -> ListNodeProviderRewardsResponse {
+        let limit = if req.limit == 0 || req.limit > MAX_LIST_NODE_PROVIDER_REWARDS_RESULTS {
+            MAX_LIST_NODE_PROVIDER_REWARDS_RESULTS
+        } else {
+            req.limit
+        };
+        let rewards = self
+            .proto
+            .node_provider_rewards_history
+            .iter()
+            .rev()
+            .filter(|rewards| {
+                req.before_timestamp_seconds
+                    .map_or(true, |before| rewards.timestamp < before)
+            })
+            .take(limit as usize)
+            .cloned()
+            .collect();
+        ListNodeProviderRewardsResponse { rewards }
+    }
+
     async fn perform_action(&mut self, pid: u64, action: proposal::Action) {
         match action {
             proposal::Action::ManageNeuron(mgmt) => {
@@ -4025,11 +4375,94 @@ impl Governance {
                 self.set_proposal_execution_status(pid, Ok(()));
             }
             proposal::Action::RewardNodeProviders(proposal) => {
-                self.reward_node_providers(pid, proposal.rewards).await;
+                if proposal.use_registry_derived_rewards == Some(true) {
+                    let result = self.mint_monthly_node_provider_rewards().await;
+                    self.set_proposal_execution_status(pid, result);
+                } else {
+                    self.reward_node_providers(pid, proposal.rewards).await;
+                }
+            }
+            proposal::Action::RegisterKnownNeuron(known_neuron) => {
+                let result = self.register_known_neuron(known_neuron);
+                self.set_proposal_execution_status(pid, result);
             }
         }
     }
 
+    /// Validates that the given known neuron can be registered: the neuron
+    /// must exist, and its name must be non-empty, not too long, and not
+    /// already used by another known neuron.
+    fn validate_known_neuron(&self, known_neuron: &KnownNeuron) -> Result<(), GovernanceError> {
+        let neuron_id = known_neuron.id.as_ref().ok_or_else(|| {
+            GovernanceError::new_with_message(
+                ErrorType::InvalidProposal,
+                "No neuron ID specified in the request to register a known neuron.",
+            )
+        })?;
+        let known_neuron_data = known_neuron.known_neuron_data.as_ref().ok_or_else(|| {
+            GovernanceError::new_with_message(
+                ErrorType::InvalidProposal,
+                "No known neuron data specified in the request to register a known neuron.",
+            )
+        })?;
+        if known_neuron_data.name.is_empty() {
+            return Err(GovernanceError::new_with_message(
+                ErrorType::InvalidProposal,
+                "The name of a known neuron must not be empty.",
+            ));
+        }
+        if known_neuron_data.name.len() > KNOWN_NEURON_NAME_MAX_LEN {
+            return Err(GovernanceError::new_with_message(
+                ErrorType::InvalidProposal,
+                &format!(
+                    "The maximum length of a known neuron's name is {} bytes, this name is: {} bytes",
+                    KNOWN_NEURON_NAME_MAX_LEN,
+                    known_neuron_data.name.len()
+                ),
+            ));
+        }
+        if let Some(description) = &known_neuron_data.description {
+            if description.len() > KNOWN_NEURON_DESCRIPTION_MAX_LEN {
+                return Err(GovernanceError::new_with_message(
+                    ErrorType::InvalidProposal,
+                    &format!(
+                        "The maximum length of a known neuron's description is {} bytes, this description is: {} bytes",
+                        KNOWN_NEURON_DESCRIPTION_MAX_LEN,
+                        description.len()
+                    ),
+                ));
+            }
+        }
+        if !self.proto.neurons.contains_key(&neuron_id.id) {
+            return Err(Self::neuron_not_found_error(neuron_id));
+        }
+        if let Some(other_id) = self.get_known_neuron_id_by_name(&known_neuron_data.name) {
+            if other_id != *neuron_id {
+                return Err(GovernanceError::new_with_message(
+                    ErrorType::PreconditionFailed,
+                    &format!(
+                        "The name {} is already used by known neuron {}.",
+                        known_neuron_data.name, other_id.id
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Registers the given neuron as a known neuron, or updates its name and
+    /// description if it is already a known neuron.
+    ///
+    /// The request is validated again, as the neuron may have been removed,
+    /// or its name taken by another neuron, since the proposal was made.
+    fn register_known_neuron(&mut self, known_neuron: KnownNeuron) -> Result<(), GovernanceError> {
+        self.validate_known_neuron(&known_neuron)?;
+        let neuron_id = known_neuron.id.expect("Known neuron must have an ID");
+        let neuron = self.get_neuron_mut(&neuron_id)?;
+        neuron.known_neuron_data = known_neuron.known_neuron_data;
+        Ok(())
+    }
+
     /// Mark all Neurons controlled by the given principals as having passed
     /// KYC verification
     pub fn approve_genesis_kyc(&mut self, principals: &[PrincipalId]) {
@@ -4378,6 +4811,16 @@ impl Governance {
             } else {
                 return Ok(());
             }
+        } else if let Some(proposal::Action::RegisterKnownNeuron(known_neuron)) = &proposal.action {
+            return self.validate_known_neuron(known_neuron);
+        } else if let Some(proposal::Action::RewardNodeProviders(reward)) = &proposal.action {
+            if reward.use_registry_derived_rewards != Some(true) {
+                return Ok(());
+            } else if !reward.rewards.is_empty() {
+                "Rewards must not be specified when using registry-derived rewards.".to_string()
+            } else {
+                return self.check_node_provider_rewards_period();
+            }
         } else if let Some(proposal::Action::Motion(motion)) = &proposal.action {
             if motion.motion_text.len() > PROPOSAL_MOTION_TEXT_BYTES_MAX {
                 format!(
@@ -4400,30 +4843,103 @@ impl Governance {
         ))
     }
 
-    pub fn make_proposal(
+    /// Complements `validate_proposal` for the `ExecuteNnsFunction` proposals
+    /// whose payload the registry can dry-run: the registry checks the payload
+    /// against its invariants without applying it, so that a proposal that
+    /// would fail at execution is rejected when it is submitted.
+    ///
+    /// This is separate from `validate_proposal` because it calls the
+    /// registry canister.
+    async fn validate_proposal_with_registry(
+        &self,
+        proposal: &Proposal,
+    ) -> Result<(), GovernanceError> {
+        let update = match &proposal.action {
+            Some(proposal::Action::ExecuteNnsFunction(update)) => update,
+            _ => return Ok(()),
+        };
+        let method_name = match NnsFunction::from_i32(update.nns_function) {
+            Some(NnsFunction::UpdateConfigOfSubnet) => "dry_run_update_subnet",
+            Some(NnsFunction::CreateSubnet) => "dry_run_create_subnet",
+            _ => return Ok(()),
+        };
+
+        // The payload is passed as is, as it is the candid-encoded argument of
+        // the method that executes the proposal.
+        let reply = self
+            .env
+            .call_canister(REGISTRY_CANISTER_ID, method_name, update.payload.clone())
+            .await
+            .map_err(|(code, msg)| {
+                let (error_type, description) = match code
+                    .map(|code| RejectCode::try_from(code as u64))
+                {
+                    // The registry could not decode or check the payload.
+                    Some(Ok(RejectCode::CanisterReject)) | Some(Ok(RejectCode::CanisterError)) => (
+                        ErrorType::InvalidProposal,
+                        "The registry canister rejected the payload of the proposal",
+                    ),
+                    // The proposal can be submitted again later.
+                    None
+                    | Some(Ok(RejectCode::SysTransient))
+                    | Some(Ok(RejectCode::SysUnknown)) => (
+                        ErrorType::Unavailable,
+                        "The registry canister could not be called to validate the proposal",
+                    ),
+                    _ => (
+                        ErrorType::External,
+                        "The registry canister could not be called to validate the proposal",
+                    ),
+                };
+                GovernanceError::new_with_message(
+                    error_type,
+                    format!(
+                        "{} in '{}'. Code: {:?}. Message: {}",
+                        description, method_name, code, msg
+                    ),
+                )
+            })?;
+
+        let violations = Decode!(&reply, Vec<String>).map_err(|e| {
+            GovernanceError::new_with_message(
+                ErrorType::External,
+                format!(
+                    "Could not decode the reply of method '{}' of the registry canister: {}",
+                    method_name, e
+                ),
+            )
+        })?;
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(GovernanceError::new_with_message(
+                ErrorType::InvalidProposal,
+                format!(
+                    "Executing the proposal would violate the registry invariants: {}",
+                    violations.join("; ")
+                ),
+            ))
+        }
+    }
+
+    /// Runs the checks of `make_proposal` that do not call other canisters:
+    /// the validation of the proposal and, unless it is a manage neuron
+    /// proposal, which `make_manage_neuron_proposal` checks itself, whether
+    /// the proposer may submit it.
+    fn validate_proposal_submission(
         &mut self,
         proposer_id: &NeuronId,
         caller: &PrincipalId,
         proposal: &Proposal,
-    ) -> Result<ProposalId, GovernanceError> {
-        let topic = proposal.topic();
-        let now_seconds = self.env.now();
-
-        // Validate proposal
+    ) -> Result<(), GovernanceError> {
         self.validate_proposal(proposal)?;
 
-        if let Some(proposal::Action::ManageNeuron(m)) = &proposal.action {
-            assert_eq!(topic, Topic::NeuronManagement);
-            return self.make_manage_neuron_proposal(
-                proposer_id,
-                caller,
-                now_seconds,
-                m,
-                &proposal.summary,
-                &proposal.url,
-            );
+        if let Some(proposal::Action::ManageNeuron(_)) = &proposal.action {
+            return Ok(());
         }
+
         let reject_cost_e8s = self.economics().reject_cost_e8s;
+        let now_seconds = self.env.now();
         // Before actually modifying anything, we first make sure that
         // the neuron is allowed to make this proposal and create the
         // electoral roll.
@@ -4488,6 +5004,32 @@ impl Governance {
                 Please try again later.",
             ));
         }
+        Ok(())
+    }
+
+    pub fn make_proposal(
+        &mut self,
+        proposer_id: &NeuronId,
+        caller: &PrincipalId,
+        proposal: &Proposal,
+    ) -> Result<ProposalId, GovernanceError> {
+        let topic = proposal.topic();
+        let now_seconds = self.env.now();
+
+        self.validate_proposal_submission(proposer_id, caller, proposal)?;
+
+        if let Some(proposal::Action::ManageNeuron(m)) = &proposal.action {
+            assert_eq!(topic, Topic::NeuronManagement);
+            return self.make_manage_neuron_proposal(
+                proposer_id,
+                caller,
+                now_seconds,
+                m,
+                &proposal.summary,
+                &proposal.url,
+            );
+        }
+        let reject_cost_e8s = self.economics().reject_cost_e8s;
         // === Preparation
         //
         // For normal proposals, every neuron with a
@@ -5093,6 +5635,7 @@ impl Governance {
             not_for_profit: false,
             recent_ballots: vec![],
             joined_community_fund_timestamp_seconds: None,
+            known_neuron_data: None,
         };
 
         // This also verifies that there are not too many neurons already.
@@ -5257,9 +5800,15 @@ impl Governance {
             Some(manage_neuron::Command::Follow(f)) => self
                 .follow(&id, caller, f)
                 .map(|_| ManageNeuronResponse::follow_response()),
-            Some(manage_neuron::Command::MakeProposal(p)) => self
-                .make_proposal(&id, caller, p)
-                .map(ManageNeuronResponse::make_proposal_response),
+            Some(manage_neuron::Command::MakeProposal(p)) => {
+                // The registry is only called for proposals that pass the
+                // other checks. These run again in `make_proposal`, as the
+                // state may change while the registry is called.
+                self.validate_proposal_submission(&id, caller, p)?;
+                self.validate_proposal_with_registry(p).await?;
+                self.make_proposal(&id, caller, p)
+                    .map(ManageNeuronResponse::make_proposal_response)
+            }
             Some(manage_neuron::Command::RegisterVote(v)) => self
                 .register_vote(&id, caller, v)
                 .map(|_| ManageNeuronResponse::register_vote_response()),
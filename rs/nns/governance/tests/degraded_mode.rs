@@ -3,7 +3,7 @@
 use assert_matches::assert_matches;
 use async_trait::async_trait;
 use futures::future::FutureExt;
-use ic_base_types::PrincipalId;
+use ic_base_types::{CanisterId, PrincipalId};
 use ic_nns_common::pb::v1::NeuronId;
 use ic_nns_governance::{
     governance::{Environment, Governance, Ledger},
@@ -11,7 +11,7 @@ use ic_nns_governance::{
         governance_error::ErrorType,
         manage_neuron::{
             claim_or_refresh::{By, MemoAndController},
-            ClaimOrRefresh, Command,
+            ClaimOrRefresh, Command, NeuronIdOrSubaccount,
         },
         manage_neuron_response::Command as CommandResponse,
         neuron, proposal, ExecuteNnsFunction, Governance as GovernanceProto, GovernanceError,
@@ -27,6 +27,7 @@ use ic_nns_governance::governance::{HeapGrowthPotential, HEAP_SIZE_SOFT_LIMIT_IN
 use ledger_canister::Subaccount;
 
 struct DegradedEnv {}
+#[async_trait]
 impl Environment for DegradedEnv {
     fn now(&self) -> u64 {
         111000222
@@ -47,6 +48,19 @@ impl Environment for DegradedEnv {
     fn heap_growth_potential(&self) -> HeapGrowthPotential {
         HeapGrowthPotential::LimitedAvailability
     }
+
+    async fn call_canister(
+        &self,
+        canister_id: CanisterId,
+        method_name: &str,
+        _: Vec<u8>,
+    ) -> Result<Vec<u8>, (Option<i32>, String)> {
+        // Other canisters are never reachable from this environment.
+        Err((
+            Some(2), // SYS_TRANSIENT
+            format!("{}.{} is unreachable", canister_id, method_name),
+        ))
+    }
 }
 
 #[async_trait]
@@ -167,6 +181,32 @@ fn test_can_submit_nns_canister_upgrade_in_degraded_mode() {
     );
 }
 
+/// The registry is unreachable from the degraded environment, so this also
+/// verifies that the proposal is rejected before the registry is called.
+#[test]
+fn test_cannot_submit_subnet_update_in_degraded_mode() {
+    let mut gov = degraded_governance();
+
+    assert_matches!(gov.manage_neuron(&principal(1), &ManageNeuron {
+        id: None,
+        command: Some(Command::MakeProposal(Box::new(Proposal {
+            summary: "proposal 1".to_string(),
+            action: Some(proposal::Action::ExecuteNnsFunction(ExecuteNnsFunction {
+                nns_function: NnsFunction::UpdateConfigOfSubnet as i32,
+                payload: Vec::new(),
+            })),
+            ..Default::default()
+        }))),
+        neuron_id_or_subaccount: Some(NeuronIdOrSubaccount::NeuronId(NeuronId { id: 1 })),
+    })
+    .now_or_never()
+    .unwrap(),
+     ManageNeuronResponse {
+     command: Some(CommandResponse::Error(e))
+     }  if e.error_type == ErrorType::ResourceExhausted as i32
+    );
+}
+
 #[test]
 fn test_cannot_create_neuron_in_degraded_mode() {
     let mut gov = degraded_governance();
@@ -188,3 +228,14 @@ fn test_cannot_create_neuron_in_degraded_mode() {
      }  if e.error_type == ErrorType::ResourceExhausted as i32
     );
 }
+
+#[test]
+fn test_node_provider_rewards_are_not_computed_if_the_registry_is_unreachable() {
+    let gov = degraded_governance();
+
+    assert_matches!(
+        gov.get_monthly_node_provider_rewards().now_or_never().unwrap(),
+        Err(e) if e.error_type == ErrorType::External as i32
+            && e.error_message.contains("get_node_providers_monthly_xdr_rewards")
+    );
+}
@@ -1,8 +1,10 @@
 use async_trait::async_trait;
+use candid::Encode;
+use cycles_minting_canister::{IcpXdrConversionRate, IcpXdrConversionRateCertifiedResponse};
 use futures::future::FutureExt;
-use ic_base_types::PrincipalId;
+use ic_base_types::{CanisterId, PrincipalId};
 use ic_nns_common::pb::v1::{NeuronId, ProposalId};
-use ic_nns_constants::GOVERNANCE_CANISTER_ID;
+use ic_nns_constants::{CYCLES_MINTING_CANISTER_ID, GOVERNANCE_CANISTER_ID, REGISTRY_CANISTER_ID};
 use ic_nns_governance::{
     governance::{Environment, Governance, Ledger},
     pb::v1::{
@@ -11,11 +13,13 @@ use ic_nns_governance::{
         Motion, Neuron, Proposal, Vote,
     },
 };
+use ic_protobuf::registry::node_rewards::v2::NodeProvidersMonthlyXdrRewards;
 use ledger_canister::{AccountIdentifier, ICPTs};
 use rand::rngs::StdRng;
 use rand_core::{RngCore, SeedableRng};
 use std::collections::hash_map::Entry;
 use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::convert::TryInto;
@@ -42,6 +46,22 @@ pub struct FakeState {
     pub now: u64,
     pub rng: StdRng,
     pub accounts: LedgerMap,
+    /// The monthly node provider rewards, in 10,000ths of XDR, returned by
+    /// the fake registry canister.
+    pub node_providers_monthly_xdr_rewards: BTreeMap<String, u64>,
+    /// The average ICP/XDR conversion rate returned by the fake cycles
+    /// minting canister.
+    pub xdr_permyriad_per_icp: u64,
+    /// The invariant violations returned by the dry runs of the fake registry
+    /// canister.
+    pub registry_dry_run_violations: Vec<String>,
+    /// If set, the dry runs of the fake registry canister are rejected with
+    /// this reject code.
+    pub registry_dry_run_reject_code: Option<i32>,
+    /// The number of dry runs that the fake registry canister was called for.
+    pub registry_dry_run_calls: u64,
+    /// The accounts to which the fake ledger fails all transfers.
+    pub failing_transfer_accounts: BTreeSet<AccountIdentifier>,
 }
 
 impl Default for FakeState {
@@ -62,6 +82,12 @@ impl Default for FakeState {
             // different places doesn't conflict.
             rng: StdRng::seed_from_u64(9539),
             accounts: HashMap::new(),
+            node_providers_monthly_xdr_rewards: BTreeMap::new(),
+            xdr_permyriad_per_icp: 10_000,
+            registry_dry_run_violations: vec![],
+            registry_dry_run_reject_code: None,
+            registry_dry_run_calls: 0,
+            failing_transfer_accounts: BTreeSet::new(),
         }
     }
 }
@@ -228,7 +254,14 @@ impl Ledger for FakeDriver {
             "Issuing ledger transfer from account {} (subaccount {}) to account {} amount {} fee {}",
             from_account, from_subaccount.as_ref().map_or_else(||"None".to_string(), ToString::to_string), to_account, amount_e8s, fee_e8s
         );
-        let accounts = &mut self.state.try_lock().unwrap().accounts;
+        let state = &mut *self.state.try_lock().unwrap();
+        if state.failing_transfer_accounts.contains(&to_account) {
+            return Err(GovernanceError::new_with_message(
+                ErrorType::External,
+                format!("Transfers to account {} fail", to_account),
+            ));
+        }
+        let accounts = &mut state.accounts;
 
         let from_e8s = accounts.get_mut(&from_account).ok_or_else(|| {
             GovernanceError::new_with_message(ErrorType::External, "Source account doesn't exist")
@@ -261,6 +294,7 @@ impl Ledger for FakeDriver {
     }
 }
 
+#[async_trait]
 impl Environment for FakeDriver {
     fn now(&self) -> u64 {
         self.state.try_lock().unwrap().now
@@ -289,6 +323,46 @@ impl Environment for FakeDriver {
     fn heap_growth_potential(&self) -> HeapGrowthPotential {
         HeapGrowthPotential::NoIssue
     }
+
+    async fn call_canister(
+        &self,
+        canister_id: CanisterId,
+        method_name: &str,
+        _arg: Vec<u8>,
+    ) -> Result<Vec<u8>, (Option<i32>, String)> {
+        let mut state = self.state.try_lock().unwrap();
+        if canister_id == REGISTRY_CANISTER_ID
+            && method_name == "get_node_providers_monthly_xdr_rewards"
+        {
+            let rewards: Result<NodeProvidersMonthlyXdrRewards, String> =
+                Ok(NodeProvidersMonthlyXdrRewards {
+                    rewards: state.node_providers_monthly_xdr_rewards.clone(),
+                });
+            Ok(Encode!(&rewards).unwrap())
+        } else if canister_id == REGISTRY_CANISTER_ID
+            && (method_name == "dry_run_update_subnet" || method_name == "dry_run_create_subnet")
+        {
+            state.registry_dry_run_calls += 1;
+            if let Some(code) = state.registry_dry_run_reject_code {
+                return Err((Some(code), format!("{} failed", method_name)));
+            }
+            Ok(Encode!(&state.registry_dry_run_violations).unwrap())
+        } else if canister_id == CYCLES_MINTING_CANISTER_ID
+            && method_name == "get_average_icp_xdr_conversion_rate"
+        {
+            Ok(Encode!(&IcpXdrConversionRateCertifiedResponse {
+                data: IcpXdrConversionRate {
+                    timestamp_seconds: state.now,
+                    xdr_permyriad_per_icp: state.xdr_permyriad_per_icp,
+                },
+                hash_tree: vec![],
+                certificate: vec![],
+            })
+            .unwrap())
+        } else {
+            panic!("unexpected call to {}.{}", canister_id, method_name)
+        }
+    }
 }
 
 /// Constructs a test principal id from an integer.
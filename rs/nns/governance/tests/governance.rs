@@ -15,8 +15,9 @@ use ic_nns_governance::{
     governance::{
         subaccount_from_slice, Environment, Governance, Ledger,
         EXECUTE_NNS_FUNCTION_PAYLOAD_LISTING_BYTES_MAX,
-        MIN_DISSOLVE_DELAY_FOR_VOTE_ELIGIBILITY_SECONDS, PROPOSAL_MOTION_TEXT_BYTES_MAX,
-        REWARD_DISTRIBUTION_PERIOD_SECONDS, WAIT_FOR_QUIET_DEADLINE_INCREASE_SECONDS,
+        MIN_DISSOLVE_DELAY_FOR_VOTE_ELIGIBILITY_SECONDS, NODE_PROVIDER_REWARDS_MIN_PERIOD_SECONDS,
+        PROPOSAL_MOTION_TEXT_BYTES_MAX, REWARD_DISTRIBUTION_PERIOD_SECONDS,
+        WAIT_FOR_QUIET_DEADLINE_INCREASE_SECONDS,
     },
     init::GovernanceCanisterInitPayloadBuilder,
     pb::v1::{
@@ -49,14 +50,15 @@ use ic_nns_governance::{
         proposal,
         reward_node_provider::{RewardMode, RewardToAccount, RewardToNeuron},
         AddOrRemoveNodeProvider, Ballot, BallotInfo, Empty, ExecuteNnsFunction,
-        Governance as GovernanceProto, GovernanceError, ListNeurons, ListNeuronsResponse,
-        ListProposalInfo, ManageNeuron, Motion, NetworkEconomics, Neuron, NeuronState, NnsFunction,
-        NodeProvider, Proposal, ProposalData, ProposalStatus, RewardEvent, RewardNodeProvider,
-        SetDefaultFollowees, Tally, Topic, Vote,
+        Governance as GovernanceProto, GovernanceError, KnownNeuron, KnownNeuronData, ListNeurons,
+        ListNeuronsResponse, ListNodeProviderRewardsRequest, ListProposalInfo, ManageNeuron,
+        Motion, NetworkEconomics, Neuron, NeuronState, NnsFunction, NodeProvider, Proposal,
+        ProposalData, ProposalStatus, RewardEvent, RewardNodeProvider, SetDefaultFollowees, Tally,
+        Topic, Vote,
     },
 };
 use ledger_canister::{AccountIdentifier, ICPTs, Memo};
-use maplit::hashmap;
+use maplit::{btreemap, hashmap};
 use registry_canister::mutations::{
     do_add_node_operator::AddNodeOperatorPayload,
     do_update_icp_xdr_conversion_rate::UpdateIcpXdrConversionRatePayload,
@@ -4642,6 +4644,7 @@ fn test_manage_and_reward_multiple_node_providers() {
                         })),
                     },
                 ],
+                use_registry_derived_rewards: None,
             })),
         }))),
     };
@@ -4800,6 +4803,388 @@ fn test_manage_and_reward_multiple_node_providers() {
     );
 }
 
+/// Submits a proposal to reward node providers with registry-derived
+/// rewards.
+fn propose_registry_derived_node_provider_rewards(
+    gov: &mut Governance,
+    voter_pid: &PrincipalId,
+    voter_neuron: &NeuronId,
+    rewards: Vec<RewardNodeProvider>,
+) -> Result<ProposalId, GovernanceError> {
+    let response = gov
+        .manage_neuron(
+            voter_pid,
+            &ManageNeuron {
+                id: None,
+                neuron_id_or_subaccount: Some(NeuronIdOrSubaccount::NeuronId(voter_neuron.clone())),
+                command: Some(manage_neuron::Command::MakeProposal(Box::new(Proposal {
+                    title: Some("Monthly node provider rewards".to_string()),
+                    summary: "Reward node providers with registry-derived rewards.".to_string(),
+                    url: "".to_string(),
+                    action: Some(proposal::Action::RewardNodeProviders(RewardNodeProviders {
+                        rewards,
+                        use_registry_derived_rewards: Some(true),
+                    })),
+                }))),
+            },
+        )
+        .now_or_never()
+        .unwrap();
+    match response.command.unwrap() {
+        manage_neuron_response::Command::MakeProposal(resp) => Ok(resp.proposal_id.unwrap()),
+        manage_neuron_response::Command::Error(e) => Err(e),
+        _ => panic!("Invalid response"),
+    }
+}
+
+#[test]
+fn test_reward_node_providers_with_registry_derived_rewards() {
+    let p: PathBuf = ["tests", "neurons.csv"].iter().collect();
+    let mut builder = GovernanceCanisterInitPayloadBuilder::new();
+    let init_neurons = &mut builder.add_all_neurons_from_csv_file(&p).proto.neurons;
+
+    let voter_pid = *init_neurons[&42].controller.as_ref().unwrap();
+    let voter_neuron = init_neurons[&42].id.as_ref().unwrap().clone();
+    init_neurons.get_mut(&42).unwrap().dissolve_state = Some(DissolveState::DissolveDelaySeconds(
+        MIN_DISSOLVE_DELAY_FOR_VOTE_ELIGIBILITY_SECONDS,
+    ));
+    let (mut driver, mut gov) =
+        governance_with_neurons(&init_neurons.values().cloned().collect::<Vec<Neuron>>());
+
+    let np_pid_0 = PrincipalId::new_self_authenticating(&[14]);
+    let np_pid_1 = PrincipalId::new_self_authenticating(&[15]);
+    // Known to the registry, but not registered in governance.
+    let np_pid_2 = PrincipalId::new_self_authenticating(&[16]);
+    let to_subaccount = Subaccount([3u8; 32]);
+    gov.proto.node_providers = vec![
+        NodeProvider {
+            id: Some(np_pid_0),
+            reward_account: None,
+        },
+        NodeProvider {
+            id: Some(np_pid_1),
+            reward_account: Some(AccountIdentifier::new(np_pid_1, Some(to_subaccount)).into()),
+        },
+    ];
+    {
+        let mut state = driver.state.try_lock().unwrap();
+        state.node_providers_monthly_xdr_rewards = btreemap! {
+            np_pid_0.to_string() => 20_000,
+            np_pid_1.to_string() => 15_000,
+            np_pid_2.to_string() => 10_000,
+        };
+        // 1 ICP = 0.5 XDR
+        state.xdr_permyriad_per_icp = 5_000;
+    }
+
+    // Rewards must not be given explicitly along with registry-derived ones.
+    let explicit_reward = RewardNodeProvider {
+        node_provider: Some(gov.proto.node_providers[0].clone()),
+        amount_e8s: 1,
+        reward_mode: None,
+    };
+    assert_matches!(
+        propose_registry_derived_node_provider_rewards(
+            &mut gov,
+            &voter_pid,
+            &voter_neuron,
+            vec![explicit_reward],
+        ),
+        Err(GovernanceError { error_type, .. }) if error_type == ErrorType::InvalidProposal as i32
+    );
+
+    let pid =
+        propose_registry_derived_node_provider_rewards(&mut gov, &voter_pid, &voter_neuron, vec![])
+            .expect("Couldn't submit proposal.");
+    assert_eq!(
+        gov.get_proposal_data(pid).unwrap().status(),
+        ProposalStatus::Executed
+    );
+    driver.assert_account_contains(&AccountIdentifier::new(np_pid_0, None), 4 * 100_000_000);
+    driver.assert_account_contains(
+        &AccountIdentifier::new(np_pid_1, Some(to_subaccount)),
+        3 * 100_000_000,
+    );
+
+    let first_rewards = gov
+        .list_node_provider_rewards(&ListNodeProviderRewardsRequest::default())
+        .rewards;
+    assert_eq!(first_rewards.len(), 1);
+    assert_eq!(first_rewards[0].timestamp, driver.now());
+    assert_eq!(first_rewards[0].xdr_permyriad_per_icp, 5_000);
+    assert_eq!(
+        first_rewards[0]
+            .rewards
+            .iter()
+            .map(|r| (r.node_provider.as_ref().unwrap().id.unwrap(), r.amount_e8s))
+            .collect::<Vec<_>>(),
+        vec![(np_pid_0, 4 * 100_000_000), (np_pid_1, 3 * 100_000_000)]
+    );
+
+    // Rewards cannot be minted twice in the same month.
+    assert_matches!(
+        propose_registry_derived_node_provider_rewards(&mut gov, &voter_pid, &voter_neuron, vec![]),
+        Err(GovernanceError { error_type, .. }) if error_type == PreconditionFailed as i32
+    );
+
+    driver.advance_time_by(NODE_PROVIDER_REWARDS_MIN_PERIOD_SECONDS);
+    driver.state.try_lock().unwrap().xdr_permyriad_per_icp = 10_000;
+    let pid =
+        propose_registry_derived_node_provider_rewards(&mut gov, &voter_pid, &voter_neuron, vec![])
+            .expect("Couldn't submit proposal.");
+    assert_eq!(
+        gov.get_proposal_data(pid).unwrap().status(),
+        ProposalStatus::Executed
+    );
+    driver.assert_account_contains(&AccountIdentifier::new(np_pid_0, None), 6 * 100_000_000);
+
+    // The history is listed most recent first.
+    let all_rewards = gov
+        .list_node_provider_rewards(&ListNodeProviderRewardsRequest::default())
+        .rewards;
+    assert_eq!(all_rewards.len(), 2);
+    assert_eq!(all_rewards[0].xdr_permyriad_per_icp, 10_000);
+    assert_eq!(all_rewards[1], first_rewards[0]);
+    assert_eq!(
+        gov.list_node_provider_rewards(&ListNodeProviderRewardsRequest {
+            limit: 0,
+            before_timestamp_seconds: Some(all_rewards[0].timestamp),
+        })
+        .rewards,
+        first_rewards
+    );
+}
+
+#[test]
+fn test_retry_registry_derived_node_provider_rewards_that_failed() {
+    let p: PathBuf = ["tests", "neurons.csv"].iter().collect();
+    let mut builder = GovernanceCanisterInitPayloadBuilder::new();
+    let init_neurons = &mut builder.add_all_neurons_from_csv_file(&p).proto.neurons;
+
+    let voter_pid = *init_neurons[&42].controller.as_ref().unwrap();
+    let voter_neuron = init_neurons[&42].id.as_ref().unwrap().clone();
+    init_neurons.get_mut(&42).unwrap().dissolve_state = Some(DissolveState::DissolveDelaySeconds(
+        MIN_DISSOLVE_DELAY_FOR_VOTE_ELIGIBILITY_SECONDS,
+    ));
+    let (mut driver, mut gov) =
+        governance_with_neurons(&init_neurons.values().cloned().collect::<Vec<Neuron>>());
+
+    let np_pid_0 = PrincipalId::new_self_authenticating(&[14]);
+    let np_pid_1 = PrincipalId::new_self_authenticating(&[15]);
+    gov.proto.node_providers = vec![
+        NodeProvider {
+            id: Some(np_pid_0),
+            reward_account: None,
+        },
+        NodeProvider {
+            id: Some(np_pid_1),
+            reward_account: None,
+        },
+    ];
+    let failing_account = AccountIdentifier::new(np_pid_1, None);
+    {
+        let mut state = driver.state.try_lock().unwrap();
+        state.node_providers_monthly_xdr_rewards = btreemap! {
+            np_pid_0.to_string() => 20_000,
+            np_pid_1.to_string() => 10_000,
+        };
+        state.xdr_permyriad_per_icp = 10_000;
+        state.failing_transfer_accounts.insert(failing_account);
+    }
+
+    // Minting the reward of the second node provider fails, but the reward of
+    // the first one is minted, and both are recorded.
+    let pid =
+        propose_registry_derived_node_provider_rewards(&mut gov, &voter_pid, &voter_neuron, vec![])
+            .expect("Couldn't submit proposal.");
+    assert_eq!(
+        gov.get_proposal_data(pid).unwrap().status(),
+        ProposalStatus::Failed
+    );
+    driver.assert_account_contains(&AccountIdentifier::new(np_pid_0, None), 2 * 100_000_000);
+    assert!(driver
+        .state
+        .try_lock()
+        .unwrap()
+        .accounts
+        .get(&failing_account)
+        .is_none());
+    let rewards = gov
+        .list_node_provider_rewards(&ListNodeProviderRewardsRequest::default())
+        .rewards;
+    assert_eq!(rewards.len(), 1);
+    let provider_ids = |rewards: &[RewardNodeProvider]| {
+        rewards
+            .iter()
+            .map(|r| r.node_provider.as_ref().unwrap().id.unwrap())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(provider_ids(&rewards[0].rewards), vec![np_pid_0]);
+    assert_eq!(provider_ids(&rewards[0].failed_rewards), vec![np_pid_1]);
+
+    // Retrying the failed reward is not blocked by the minimum period between
+    // monthly rewards, and only mints the failed reward.
+    driver
+        .state
+        .try_lock()
+        .unwrap()
+        .failing_transfer_accounts
+        .clear();
+    let pid =
+        propose_registry_derived_node_provider_rewards(&mut gov, &voter_pid, &voter_neuron, vec![])
+            .expect("Couldn't submit proposal.");
+    assert_eq!(
+        gov.get_proposal_data(pid).unwrap().status(),
+        ProposalStatus::Executed
+    );
+    driver.assert_account_contains(&AccountIdentifier::new(np_pid_0, None), 2 * 100_000_000);
+    driver.assert_account_contains(&failing_account, 100_000_000);
+    let rewards = gov
+        .list_node_provider_rewards(&ListNodeProviderRewardsRequest::default())
+        .rewards;
+    assert_eq!(rewards.len(), 1);
+    assert_eq!(provider_ids(&rewards[0].rewards), vec![np_pid_0, np_pid_1]);
+    assert!(rewards[0].failed_rewards.is_empty());
+
+    // Once nothing is left to retry, rewards cannot be minted again in the
+    // same month.
+    assert_matches!(
+        propose_registry_derived_node_provider_rewards(&mut gov, &voter_pid, &voter_neuron, vec![]),
+        Err(GovernanceError { error_type, .. }) if error_type == PreconditionFailed as i32
+    );
+}
+
+/// Submits a proposal to update the configuration of a subnet.
+fn propose_update_config_of_subnet(
+    gov: &mut Governance,
+    voter_pid: &PrincipalId,
+    voter_neuron: &NeuronId,
+) -> Result<ProposalId, GovernanceError> {
+    let response = gov
+        .manage_neuron(
+            voter_pid,
+            &ManageNeuron {
+                id: None,
+                neuron_id_or_subaccount: Some(NeuronIdOrSubaccount::NeuronId(voter_neuron.clone())),
+                command: Some(manage_neuron::Command::MakeProposal(Box::new(Proposal {
+                    title: Some("Update the configuration of a subnet".to_string()),
+                    summary: "".to_string(),
+                    url: "".to_string(),
+                    action: Some(proposal::Action::ExecuteNnsFunction(ExecuteNnsFunction {
+                        nns_function: NnsFunction::UpdateConfigOfSubnet as i32,
+                        payload: Vec::new(),
+                    })),
+                }))),
+            },
+        )
+        .now_or_never()
+        .unwrap();
+    match response.command.unwrap() {
+        manage_neuron_response::Command::MakeProposal(resp) => Ok(resp.proposal_id.unwrap()),
+        manage_neuron_response::Command::Error(e) => Err(e),
+        _ => panic!("Invalid response"),
+    }
+}
+
+#[test]
+fn test_proposals_violating_registry_invariants_are_rejected_at_submission() {
+    let p: PathBuf = ["tests", "neurons.csv"].iter().collect();
+    let mut builder = GovernanceCanisterInitPayloadBuilder::new();
+    let init_neurons = &mut builder.add_all_neurons_from_csv_file(&p).proto.neurons;
+
+    let voter_pid = *init_neurons[&42].controller.as_ref().unwrap();
+    let voter_neuron = init_neurons[&42].id.as_ref().unwrap().clone();
+    init_neurons.get_mut(&42).unwrap().dissolve_state = Some(DissolveState::DissolveDelaySeconds(
+        MIN_DISSOLVE_DELAY_FOR_VOTE_ELIGIBILITY_SECONDS,
+    ));
+
+    let (driver, mut gov) =
+        governance_with_neurons(&init_neurons.values().cloned().collect::<Vec<Neuron>>());
+
+    // The registry reports a violation, so the proposal is not created.
+    driver.state.try_lock().unwrap().registry_dry_run_violations =
+        vec!["Subnet has no nodes".to_string()];
+    assert_matches!(
+        propose_update_config_of_subnet(&mut gov, &voter_pid, &voter_neuron),
+        Err(GovernanceError { error_type, error_message })
+            if error_type == ErrorType::InvalidProposal as i32
+                && error_message.contains("Subnet has no nodes")
+    );
+    assert!(gov.proto.proposals.is_empty());
+
+    // Without violations, the proposal is created.
+    driver.state.try_lock().unwrap().registry_dry_run_violations = vec![];
+    propose_update_config_of_subnet(&mut gov, &voter_pid, &voter_neuron)
+        .expect("Couldn't submit proposal.");
+    assert_eq!(gov.proto.proposals.len(), 1);
+}
+
+/// Verifies that the registry is only called for proposals that pass the
+/// checks that do not call other canisters.
+#[test]
+fn test_registry_is_not_called_for_proposals_failing_other_checks() {
+    let p: PathBuf = ["tests", "neurons.csv"].iter().collect();
+    let mut builder = GovernanceCanisterInitPayloadBuilder::new();
+    let init_neurons = &mut builder.add_all_neurons_from_csv_file(&p).proto.neurons;
+
+    let voter_neuron = init_neurons[&42].id.as_ref().unwrap().clone();
+    init_neurons.get_mut(&42).unwrap().dissolve_state = Some(DissolveState::DissolveDelaySeconds(
+        MIN_DISSOLVE_DELAY_FOR_VOTE_ELIGIBILITY_SECONDS,
+    ));
+
+    let (driver, mut gov) =
+        governance_with_neurons(&init_neurons.values().cloned().collect::<Vec<Neuron>>());
+
+    assert_matches!(
+        propose_update_config_of_subnet(&mut gov, &principal(1_000_000), &voter_neuron),
+        Err(GovernanceError { error_type, .. })
+            if error_type == ErrorType::NotAuthorized as i32
+    );
+    assert_eq!(driver.state.try_lock().unwrap().registry_dry_run_calls, 0);
+    assert!(gov.proto.proposals.is_empty());
+}
+
+/// Verifies that a proposal whose payload the registry traps on is invalid,
+/// while one that the registry could not be reached for is not.
+#[test]
+fn test_registry_rejections_at_submission_are_classified_by_reject_code() {
+    let p: PathBuf = ["tests", "neurons.csv"].iter().collect();
+    let mut builder = GovernanceCanisterInitPayloadBuilder::new();
+    let init_neurons = &mut builder.add_all_neurons_from_csv_file(&p).proto.neurons;
+
+    let voter_pid = *init_neurons[&42].controller.as_ref().unwrap();
+    let voter_neuron = init_neurons[&42].id.as_ref().unwrap().clone();
+    init_neurons.get_mut(&42).unwrap().dissolve_state = Some(DissolveState::DissolveDelaySeconds(
+        MIN_DISSOLVE_DELAY_FOR_VOTE_ELIGIBILITY_SECONDS,
+    ));
+
+    let (driver, mut gov) =
+        governance_with_neurons(&init_neurons.values().cloned().collect::<Vec<Neuron>>());
+
+    for (reject_code, expected_error_type) in &[
+        (1, ErrorType::External),        // SYS_FATAL
+        (2, ErrorType::Unavailable),     // SYS_TRANSIENT
+        (3, ErrorType::External),        // DESTINATION_INVALID
+        (4, ErrorType::InvalidProposal), // CANISTER_REJECT
+        (5, ErrorType::InvalidProposal), // CANISTER_ERROR
+    ] {
+        driver
+            .state
+            .try_lock()
+            .unwrap()
+            .registry_dry_run_reject_code = Some(*reject_code);
+        assert_matches!(
+            propose_update_config_of_subnet(&mut gov, &voter_pid, &voter_neuron),
+            Err(GovernanceError { error_type, .. })
+                if error_type == *expected_error_type as i32,
+            "Unexpected error for reject code {}",
+            reject_code
+        );
+    }
+    assert_eq!(driver.state.try_lock().unwrap().registry_dry_run_calls, 5);
+    assert!(gov.proto.proposals.is_empty());
+}
+
 #[test]
 fn test_network_economics_proposal() {
     let p: PathBuf = ["tests", "neurons.csv"].iter().collect();
@@ -4868,6 +5253,91 @@ fn test_network_economics_proposal() {
     );
 }
 
+#[test]
+fn test_register_known_neuron() {
+    let p: PathBuf = ["tests", "neurons.csv"].iter().collect();
+    let mut builder = GovernanceCanisterInitPayloadBuilder::new();
+    let init_neurons = &mut builder.add_all_neurons_from_csv_file(&p).proto.neurons;
+
+    let voter_pid = *init_neurons[&42].controller.as_ref().unwrap();
+    let voter_neuron = init_neurons[&42].id.as_ref().unwrap().clone();
+    init_neurons.get_mut(&42).unwrap().dissolve_state = Some(DissolveState::DissolveDelaySeconds(
+        MIN_DISSOLVE_DELAY_FOR_VOTE_ELIGIBILITY_SECONDS,
+    ));
+    let (_, mut gov) =
+        governance_with_neurons(&init_neurons.values().cloned().collect::<Vec<Neuron>>());
+
+    let mut register = |id: u64, name: &str| {
+        gov.manage_neuron(
+            &voter_pid,
+            &ManageNeuron {
+                id: None,
+                neuron_id_or_subaccount: Some(NeuronIdOrSubaccount::NeuronId(voter_neuron.clone())),
+                command: Some(manage_neuron::Command::MakeProposal(Box::new(Proposal {
+                    title: Some("Register a known neuron".to_string()),
+                    summary: "".to_string(),
+                    url: "".to_string(),
+                    action: Some(proposal::Action::RegisterKnownNeuron(KnownNeuron {
+                        id: Some(NeuronId { id }),
+                        known_neuron_data: Some(KnownNeuronData {
+                            name: name.to_string(),
+                            description: Some(format!("This is {}.", name)),
+                        }),
+                    })),
+                }))),
+            },
+        )
+        .now_or_never()
+        .unwrap()
+    };
+
+    let pid = match register(25, "Alice")
+        .expect("Couldn't submit proposal.")
+        .command
+    {
+        Some(manage_neuron_response::Command::MakeProposal(resp)) => resp.proposal_id.unwrap(),
+        _ => panic!("Invalid response"),
+    };
+
+    // The name is already taken by another neuron.
+    assert_matches!(
+        register(100, "Alice"),
+        Err(GovernanceError { error_type, .. }) if error_type == PreconditionFailed as i32
+    );
+    // The neuron does not exist.
+    assert_matches!(
+        register(1234, "Bob"),
+        Err(GovernanceError { error_type, .. }) if error_type == NotFound as i32
+    );
+    // The name must not be empty.
+    assert_matches!(
+        register(100, ""),
+        Err(GovernanceError { error_type, .. }) if error_type == ErrorType::InvalidProposal as i32
+    );
+
+    assert_eq!(
+        gov.get_proposal_data(pid).unwrap().status(),
+        ProposalStatus::Executed
+    );
+    let expected_data = KnownNeuronData {
+        name: "Alice".to_string(),
+        description: Some("This is Alice.".to_string()),
+    };
+    assert_eq!(
+        gov.list_known_neurons().known_neurons,
+        vec![KnownNeuron {
+            id: Some(NeuronId { id: 25 }),
+            known_neuron_data: Some(expected_data.clone()),
+        }]
+    );
+    assert_eq!(
+        gov.get_neuron_info(&NeuronId { id: 25 })
+            .unwrap()
+            .known_neuron_data,
+        Some(expected_data)
+    );
+}
+
 #[test]
 fn test_default_followees() {
     let p: PathBuf = ["tests", "neurons.csv"].iter().collect();
@@ -1,4 +1,5 @@
 use dfn_candid::candid;
+use dfn_core::api::CanisterId;
 use dfn_core::{
     api::caller,
     endpoint::{over, over_async},
@@ -10,7 +11,7 @@ use ic_nns_handler_root::{
     canister_management,
     common::{
         AddNnsCanisterProposalPayload, CanisterIdRecord, ChangeNnsCanisterProposalPayload,
-        StopOrStartNnsCanisterProposalPayload, LOG_PREFIX,
+        StopOrStartNnsCanisterProposalPayload, UpgradeRecord, LOG_PREFIX,
     },
     root_proposals::{GovernanceUpgradeRootProposal, RootProposalBallot},
 };
@@ -30,13 +31,27 @@ fn canister_init() {
     println!("{}canister_init", LOG_PREFIX);
 }
 
+#[export_name = "canister_pre_upgrade"]
+fn canister_pre_upgrade() {
+    println!("{}canister_pre_upgrade", LOG_PREFIX);
+    stable::set(&canister_management::encode_stable_state());
+}
+
 #[export_name = "canister_post_upgrade"]
 fn canister_post_upgrade() {
     dfn_core::printer::hook();
     println!("{}canister_post_upgrade", LOG_PREFIX);
-    // Wipe out stable memory, because earlier version of this canister were
-    // stateful. This minimizes risk of future mis-interpretation of data.
-    stable::set(&[]);
+    // The stable memory is empty when upgrading from a version that did not
+    // keep the upgrade history.
+    let bytes = stable::get();
+    if !bytes.is_empty() {
+        if let Err(msg) = canister_management::restore_stable_state(&bytes) {
+            println!(
+                "{}Could not restore the upgrade history, starting with an empty one: {}",
+                LOG_PREFIX, msg
+            );
+        }
+    }
 }
 
 /// Returns the status of the canister specified in the input.
@@ -127,6 +142,18 @@ fn change_nns_canister() {
     });
 }
 
+/// Returns the recorded attempts to change NNS canisters, from the oldest to
+/// the most recent, optionally restricted to one canister.
+#[export_name = "canister_query get_upgrade_history"]
+fn get_upgrade_history() {
+    over(
+        candid,
+        |(canister_id,): (Option<CanisterId>,)| -> Vec<UpgradeRecord> {
+            canister_management::get_upgrade_history(canister_id)
+        },
+    )
+}
+
 #[export_name = "canister_update add_nns_canister"]
 fn add_nns_canister() {
     check_caller_is_governance();
@@ -1,12 +1,18 @@
 use crate::common::{
     AddNnsCanisterProposalPayload, CanisterAction, CanisterIdRecord, CanisterStatusResult,
-    CanisterStatusType, ChangeNnsCanisterProposalPayload, StopOrStartNnsCanisterProposalPayload,
-    LOG_PREFIX,
+    CanisterStatusType, ChangeNnsCanisterProposalPayload, RollbackOutcome,
+    StopOrStartNnsCanisterProposalPayload, UpgradeOutcome, UpgradeRecord, LOG_PREFIX,
 };
-use dfn_core::api::{call, call_with_funds, CanisterId, Funds};
+use async_trait::async_trait;
+use candid::{Decode, Encode};
+use dfn_core::api::{call, call_with_funds, now, CanisterId, Funds};
 #[cfg(target_arch = "wasm32")]
 use dfn_core::println;
-use ic_base_types::{CanisterInstallMode::Install, PrincipalId};
+use ic_base_types::{
+    CanisterInstallMode::{self, Install},
+    PrincipalId,
+};
+use ic_crypto_sha::Sha256;
 
 use futures::future::join_all;
 use ic_ic00_types::{InstallCodeArgs, IC_00};
@@ -18,8 +24,10 @@ use ic_protobuf::registry::nns::v1::{NnsCanisterRecord, NnsCanisterRecords};
 use ic_protobuf::types::v1 as pb;
 use ic_registry_keys::make_nns_canister_records_key;
 use ic_registry_transport::pb::v1::{registry_mutation::Type, Precondition, RegistryMutation};
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::convert::TryFrom;
+use std::time::SystemTime;
 
 pub async fn canister_status(canister_id_record: CanisterIdRecord) -> CanisterStatusResult {
     call(
@@ -99,13 +107,195 @@ pub async fn update_authz(
     .await;
 }
 
+/// The maximum number of records kept in the upgrade history. Older records
+/// are dropped first.
+pub const MAX_UPGRADE_HISTORY_LEN: usize = 100;
+
+// The upgrade history and the installed modules are written to stable memory
+// before an upgrade of the root canister, see `encode_stable_state`.
+thread_local! {
+  static UPGRADE_HISTORY: RefCell<VecDeque<UpgradeRecord>> = RefCell::new(VecDeque::new());
+  // The last module successfully installed by this canister, per canister, so
+  // that a failed change can be rolled back.
+  static INSTALLED_MODULES: RefCell<BTreeMap<CanisterId, Vec<u8>>> = RefCell::new(BTreeMap::new());
+}
+
+/// The state of this canister that is kept across its upgrades.
+#[derive(candid::CandidType, candid::Deserialize)]
+struct StableState {
+    upgrade_history: Vec<UpgradeRecord>,
+    installed_modules: Vec<InstalledModule>,
+}
+
+#[derive(candid::CandidType, candid::Deserialize)]
+struct InstalledModule {
+    canister_id: CanisterId,
+    #[serde(with = "serde_bytes")]
+    wasm_module: Vec<u8>,
+}
+
+/// Encodes the upgrade history and the installed modules, to be written to
+/// stable memory before an upgrade of this canister.
+pub fn encode_stable_state() -> Vec<u8> {
+    let state = StableState {
+        upgrade_history: UPGRADE_HISTORY.with(|history| history.borrow().iter().cloned().collect()),
+        installed_modules: INSTALLED_MODULES.with(|modules| {
+            modules
+                .borrow()
+                .iter()
+                .map(|(canister_id, wasm_module)| InstalledModule {
+                    canister_id: *canister_id,
+                    wasm_module: wasm_module.clone(),
+                })
+                .collect()
+        }),
+    };
+    Encode!(&state).expect("Could not encode the state of the root canister")
+}
+
+/// Restores the upgrade history and the installed modules encoded by
+/// `encode_stable_state`.
+pub fn restore_stable_state(bytes: &[u8]) -> Result<(), String> {
+    let state = Decode!(bytes, StableState).map_err(|e| e.to_string())?;
+    UPGRADE_HISTORY.with(|history| *history.borrow_mut() = state.upgrade_history.into());
+    INSTALLED_MODULES.with(|modules| {
+        *modules.borrow_mut() = state
+            .installed_modules
+            .into_iter()
+            .map(|module| (module.canister_id, module.wasm_module))
+            .collect()
+    });
+    Ok(())
+}
+
+/// Returns the recorded attempts to change NNS canisters, from the oldest to
+/// the most recent, optionally restricted to one canister.
+pub fn get_upgrade_history(canister_id: Option<CanisterId>) -> Vec<UpgradeRecord> {
+    UPGRADE_HISTORY.with(|history| {
+        history
+            .borrow()
+            .iter()
+            .filter(|record| canister_id.map_or(true, |id| record.canister_id == id))
+            .cloned()
+            .collect()
+    })
+}
+
+fn record_upgrade(record: UpgradeRecord) {
+    println!("{}Change of NNS canister: {:?}", LOG_PREFIX, record);
+    UPGRADE_HISTORY.with(|history| {
+        let mut history = history.borrow_mut();
+        history.push_back(record);
+        while history.len() > MAX_UPGRADE_HISTORY_LEN {
+            history.pop_front();
+        }
+    });
+}
+
+fn remember_installed_module(canister_id: CanisterId, wasm_module: Vec<u8>) {
+    INSTALLED_MODULES.with(|modules| modules.borrow_mut().insert(canister_id, wasm_module));
+}
+
+/// Drops the copy of the module of `canister_id` unless it has the hash that
+/// the management canister reports, e.g. because another controller changed
+/// the canister since.
+fn forget_module_unless_running(canister_id: CanisterId, module_hash: &Option<Vec<u8>>) {
+    INSTALLED_MODULES.with(|modules| {
+        let mut modules = modules.borrow_mut();
+        let running = modules
+            .get(&canister_id)
+            .map(|module| module_hash.as_deref() == Some(&Sha256::hash(module)[..]));
+        if running == Some(false) {
+            println!(
+                "{}{} no longer runs the module installed by the root, forgetting it",
+                LOG_PREFIX, canister_id
+            );
+            modules.remove(&canister_id);
+        }
+    });
+}
+
+/// The calls to the management canister made to change an NNS canister.
+#[async_trait(?Send)]
+pub trait ManagementCanister {
+    async fn canister_status(&self, canister_id: CanisterId) -> CanisterStatusResult;
+    async fn stop_canister(&self, canister_id: CanisterId);
+    async fn install_code(&self, install_code_args: InstallCodeArgs) -> Result<(), String>;
+    async fn start_canister(&self, canister_id: CanisterId) -> Result<(), String>;
+}
+
+/// The management canister of the IC.
+pub struct Ic00;
+
+#[async_trait(?Send)]
+impl ManagementCanister for Ic00 {
+    async fn canister_status(&self, canister_id: CanisterId) -> CanisterStatusResult {
+        canister_status(CanisterIdRecord::from(canister_id)).await
+    }
+
+    async fn stop_canister(&self, canister_id: CanisterId) {
+        stop_canister(canister_id).await
+    }
+
+    async fn install_code(&self, install_code_args: InstallCodeArgs) -> Result<(), String> {
+        install_code(install_code_args).await
+    }
+
+    async fn start_canister(&self, canister_id: CanisterId) -> Result<(), String> {
+        start_canister(canister_id).await
+    }
+}
+
 pub async fn do_change_nns_canister(payload: ChangeNnsCanisterProposalPayload) {
     let canister_id = payload.canister_id;
     let authz_changes = payload.authz_changes.clone();
+    let timestamp_seconds = now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .expect("Could not get the duration.")
+        .as_secs();
+
+    let outcome = change_nns_canister(&Ic00, payload, timestamp_seconds).await;
+
+    // Update authz of other canisters, if required.
+    if outcome != UpgradeOutcome::WasmHashMismatch {
+        update_authz(canister_id, authz_changes).await;
+    }
+}
+
+/// Installs the module of the proposal, installs the previous module again if
+/// that fails, and records the attempt in the upgrade history.
+pub async fn change_nns_canister(
+    management_canister: &impl ManagementCanister,
+    payload: ChangeNnsCanisterProposalPayload,
+    timestamp_seconds: u64,
+) -> UpgradeOutcome {
+    let canister_id = payload.canister_id;
     let stop_before_installing = payload.stop_before_installing;
+    let wasm_module = payload.wasm_module.clone();
+    let mut record = UpgradeRecord {
+        canister_id,
+        timestamp_seconds,
+        mode: payload.mode,
+        previous_wasm_sha256: None,
+        proposed_wasm_sha256: Sha256::hash(&wasm_module).to_vec(),
+        outcome: UpgradeOutcome::Succeeded,
+    };
+
+    if let Err(msg) = payload.verify_wasm_sha256() {
+        println!("{}Not changing {}: {}", LOG_PREFIX, canister_id, msg);
+        record.outcome = UpgradeOutcome::WasmHashMismatch;
+        record_upgrade(record);
+        return UpgradeOutcome::WasmHashMismatch;
+    }
+
+    record.previous_wasm_sha256 = management_canister
+        .canister_status(canister_id)
+        .await
+        .module_hash;
+    forget_module_unless_running(canister_id, &record.previous_wasm_sha256);
 
     if stop_before_installing {
-        stop_canister(canister_id).await;
+        management_canister.stop_canister(canister_id).await;
     }
 
     // Ship code to the canister.
@@ -115,49 +305,106 @@ pub async fn do_change_nns_canister(payload: ChangeNnsCanisterProposalPayload) {
     // because there could be a concurrent proposal to restart it. This could be
     // guaranteed with a "stopped precondition" in the management canister, or
     // with some locking here.
-    let res = install_code(payload).await;
-    // For once, we don't want to unwrap the result here. The reason is that, if the
-    // installation failed (e.g., the wasm was rejected because it's invalid),
-    // then we want to restart the canister. So we just keep the res to be
-    // unwrapped later.
+    let mut res = management_canister
+        .install_code(InstallCodeArgs {
+            mode: payload.mode,
+            canister_id: canister_id.get(),
+            wasm_module: payload.wasm_module,
+            arg: payload.arg,
+            compute_allocation: payload.compute_allocation,
+            memory_allocation: payload.memory_allocation,
+            query_allocation: payload.query_allocation,
+        })
+        .await;
 
     // Restart the canister, if needed
-    if stop_before_installing {
-        start_canister(canister_id).await;
+    if res.is_ok() && stop_before_installing {
+        res = management_canister.start_canister(canister_id).await;
     }
 
-    // Update authz of other canisters, if required.
-    update_authz(canister_id, authz_changes).await;
+    record.outcome = match res {
+        Ok(()) => {
+            remember_installed_module(canister_id, wasm_module);
+            UpgradeOutcome::Succeeded
+        }
+        Err(reason) => {
+            // The installation failed (e.g., the wasm was rejected because it's
+            // invalid, or the canister trapped in post_upgrade), or the new module
+            // does not start. Make sure that the previous module is running again.
+            let rollback = roll_back(
+                management_canister,
+                canister_id,
+                &record.previous_wasm_sha256,
+            )
+            .await;
+            if let Err(msg) = management_canister.start_canister(canister_id).await {
+                println!(
+                    "{}Could not restart {} after a failed change: {}",
+                    LOG_PREFIX, canister_id, msg
+                );
+            }
+            UpgradeOutcome::Failed { reason, rollback }
+        }
+    };
+    let outcome = record.outcome.clone();
+    record_upgrade(record);
+    outcome
+}
 
-    // Check the result of the install_code
-    res.unwrap();
+/// Installs again the module that `canister_id` ran before a failed change,
+/// unless it still runs it.
+async fn roll_back(
+    management_canister: &impl ManagementCanister,
+    canister_id: CanisterId,
+    previous_wasm_sha256: &Option<Vec<u8>>,
+) -> RollbackOutcome {
+    let current_wasm_sha256 = management_canister
+        .canister_status(canister_id)
+        .await
+        .module_hash;
+    if current_wasm_sha256 == *previous_wasm_sha256 {
+        return RollbackOutcome::NotNeeded;
+    }
+    let previous_module = INSTALLED_MODULES
+        .with(|modules| modules.borrow().get(&canister_id).cloned())
+        .filter(|module| previous_wasm_sha256.as_deref() == Some(&Sha256::hash(module)[..]));
+    let previous_module = match previous_module {
+        Some(module) => module,
+        None => return RollbackOutcome::NoPreviousModule,
+    };
+    let res = management_canister
+        .install_code(InstallCodeArgs {
+            mode: CanisterInstallMode::Upgrade,
+            canister_id: canister_id.get(),
+            wasm_module: previous_module,
+            // The previous module is upgraded to with no arguments.
+            arg: Encode!().unwrap(),
+            compute_allocation: None,
+            memory_allocation: None,
+            query_allocation: None,
+        })
+        .await;
+    match res {
+        Ok(()) => RollbackOutcome::Succeeded,
+        Err(msg) => RollbackOutcome::Failed(msg),
+    }
 }
 
 /// Calls the "install_code" method of the management canister.
-async fn install_code(
-    payload: ChangeNnsCanisterProposalPayload,
-) -> ic_cdk::api::call::CallResult<()> {
-    let install_code_args = InstallCodeArgs {
-        mode: payload.mode,
-        canister_id: payload.canister_id.get(),
-        wasm_module: payload.wasm_module,
-        arg: payload.arg,
-        compute_allocation: payload.compute_allocation,
-        memory_allocation: payload.memory_allocation,
-        query_allocation: payload.query_allocation,
-    };
+async fn install_code(install_code_args: InstallCodeArgs) -> Result<(), String> {
     // Warning: despite dfn_core::call returning a Result, it actually traps when
     // the callee traps! Use the public cdk instead, which does not have this
     // issue.
-    ic_cdk::api::call::call(
+    let res: ic_cdk::api::call::CallResult<()> = ic_cdk::api::call::call(
         ic_cdk::export::Principal::try_from(IC_00.get().as_slice()).unwrap(),
         "install_code",
         (&install_code_args,),
     )
-    .await
+    .await;
+    res.map_err(|(code, msg)| format!("{:?}: {}", code, msg))
 }
 
-async fn start_canister(canister_id: CanisterId) {
+async fn start_canister(canister_id: CanisterId) -> Result<(), String> {
     // start_canister returns the candid empty type, which cannot be parsed using
     // dfn_candid::candid
     let res: Result<(), (Option<i32>, String)> = call(
@@ -168,9 +415,16 @@ async fn start_canister(canister_id: CanisterId) {
     )
     .await;
 
-    // Let's make sure this worked. We can abort if not.
-    res.unwrap();
+    res.map_err(|(code, msg)| {
+        format!(
+            "{}{}",
+            code.map(|c| format!("error code {}: ", c))
+                .unwrap_or_default(),
+            msg
+        )
+    })?;
     println!("{}Restart call successful.", LOG_PREFIX);
+    Ok(())
 }
 
 /// Stops the given canister, and polls until the `Stopped` state is reached.
@@ -216,6 +470,7 @@ pub async fn do_add_nns_canister(payload: AddNnsCanisterProposalPayload) {
     let key = make_nns_canister_records_key().into_bytes();
     let authz_changes = payload.authz_changes.clone();
     let name = payload.name.clone();
+    let wasm_module = payload.wasm_module.clone();
 
     // We first need to claim the name of this new canister. Indeed, even though we
     // don't yet know its ID, if we create it first and then find out the name
@@ -262,6 +517,7 @@ pub async fn do_add_nns_canister(payload: AddNnsCanisterProposalPayload) {
     let id_or_error = try_to_create_and_install_canister(payload).await;
     let id = id_or_error.unwrap();
     // TODO(NNS-81): If it did not work, remove the name from the registry
+    remember_installed_module(id, wasm_module);
 
     nns_canister_records.canisters.insert(
         name.clone(),
@@ -342,7 +598,8 @@ async fn try_to_create_and_install_canister(
 // Stops or starts any NNS canister.
 pub async fn stop_or_start_nns_canister(payload: StopOrStartNnsCanisterProposalPayload) {
     match payload.action {
-        CanisterAction::Start => start_canister(payload.canister_id).await,
+        // Let's make sure this worked. We can abort if not.
+        CanisterAction::Start => start_canister(payload.canister_id).await.unwrap(),
         CanisterAction::Stop => stop_canister(payload.canister_id).await,
     }
 }
@@ -111,6 +111,12 @@ pub struct ChangeNnsCanisterProposalPayload {
 
     /// A list of authz changes to enact, in addition to changing new canister.
     pub authz_changes: Vec<MethodAuthzChange>,
+
+    /// The SHA-256 hash that `wasm_module` must have. The module is not
+    /// installed unless its hash matches. Optional only so that proposals
+    /// made before the hash was required still decode: the module of a
+    /// proposal without hash is not installed either.
+    pub expected_wasm_sha256: Option<Vec<u8>>,
 }
 
 impl ChangeNnsCanisterProposalPayload {
@@ -132,8 +138,28 @@ impl ChangeNnsCanisterProposalPayload {
             .field("memory_allocation", &self.memory_allocation)
             .field("query_allocation", &self.query_allocation)
             .field("authz_changes", &self.authz_changes)
+            .field(
+                "expected_wasm_sha256",
+                &format!("{:x?}", self.expected_wasm_sha256),
+            )
             .finish()
     }
+
+    /// Checks that the wasm module has the expected hash, which the proposal
+    /// must specify.
+    pub fn verify_wasm_sha256(&self) -> Result<(), String> {
+        let expected_wasm_sha256 = self.expected_wasm_sha256.as_ref().ok_or_else(|| {
+            "The proposal does not specify the expected SHA-256 of the wasm module.".to_string()
+        })?;
+        let wasm_sha = Sha256::hash(&self.wasm_module);
+        if expected_wasm_sha256[..] != wasm_sha[..] {
+            return Err(format!(
+                "The wasm module has SHA-256 {:x?} instead of the expected {:x?}.",
+                wasm_sha, expected_wasm_sha256
+            ));
+        }
+        Ok(())
+    }
 }
 
 impl std::fmt::Debug for ChangeNnsCanisterProposalPayload {
@@ -164,10 +190,13 @@ impl ChangeNnsCanisterProposalPayload {
             memory_allocation: Some(candid::Nat::from(memory_allocation_of(canister_id))),
             query_allocation: None,
             authz_changes: Vec::new(),
+            expected_wasm_sha256: Some(Sha256::hash(&[]).to_vec()),
         }
     }
 
+    /// Sets the wasm module, and pins its hash.
     pub fn with_wasm(mut self, wasm_module: Vec<u8>) -> Self {
+        self.expected_wasm_sha256 = Some(Sha256::hash(&wasm_module).to_vec());
         self.wasm_module = wasm_module;
         self
     }
@@ -217,3 +246,49 @@ pub struct StopOrStartNnsCanisterProposalPayload {
     pub canister_id: CanisterId,
     pub action: CanisterAction,
 }
+
+/// How an attempt to change an NNS canister ended.
+#[derive(candid::CandidType, candid::Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum UpgradeOutcome {
+    /// The new module was installed, and the canister restarted if it had been
+    /// stopped.
+    Succeeded,
+    /// The wasm module did not have the hash expected by the proposal, or the
+    /// proposal did not specify one, so nothing was installed.
+    WasmHashMismatch,
+    /// Installing the new module, or restarting the canister afterwards,
+    /// failed.
+    Failed {
+        reason: String,
+        rollback: RollbackOutcome,
+    },
+}
+
+/// What was done to bring back the previously running module after a failed
+/// change.
+#[derive(candid::CandidType, candid::Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum RollbackOutcome {
+    /// The canister still runs the module it ran before the change.
+    NotNeeded,
+    /// The previous module was installed again.
+    Succeeded,
+    /// The canister runs another module than before, but the root canister
+    /// does not hold a copy of the previous one.
+    NoPreviousModule,
+    /// Installing the previous module again failed.
+    Failed(String),
+}
+
+/// A record of one attempt to change an NNS canister.
+#[derive(candid::CandidType, candid::Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct UpgradeRecord {
+    pub canister_id: CanisterId,
+    /// The timestamp, in seconds, at which the change started.
+    pub timestamp_seconds: u64,
+    pub mode: CanisterInstallMode,
+    /// The hash of the module the canister ran before the change, if any.
+    pub previous_wasm_sha256: Option<Vec<u8>>,
+    /// The hash of the module of the proposal.
+    pub proposed_wasm_sha256: Vec<u8>,
+    pub outcome: UpgradeOutcome,
+}
@@ -7,6 +7,7 @@ use dfn_candid::candid;
 use ic_base_types::CanisterInstallMode::{self, Reinstall, Upgrade};
 use ic_nns_handler_root::common::{
     CanisterIdRecord, CanisterStatusResult, CanisterStatusType, ChangeNnsCanisterProposalPayload,
+    RollbackOutcome, UpgradeOutcome, UpgradeRecord,
 };
 use ic_nns_handler_root::init::RootCanisterInitPayloadBuilder;
 use ic_nns_test_utils::itest_helpers::{
@@ -89,6 +90,98 @@ async fn install_invalid_wasm(
         .await
         .unwrap();
     assert_is_running_universal_canister(&status);
+
+    // The failed attempt is in the upgrade history
+    let history: Vec<UpgradeRecord> = root
+        .query_(
+            "get_upgrade_history",
+            candid,
+            (Some(universal.canister_id()),),
+        )
+        .await
+        .unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(
+        history[0].previous_wasm_sha256,
+        Some(UNIVERSAL_CANISTER_WASM_SHA256.to_vec())
+    );
+    match &history[0].outcome {
+        UpgradeOutcome::Failed { rollback, .. } => {
+            assert_eq!(rollback, &RollbackOutcome::NotNeeded)
+        }
+        outcome => panic!("Unexpected outcome: {:?}", outcome),
+    }
+}
+
+/// Verifies that a module is not installed if the proposal pins
+/// `expected_wasm_sha256`, which is not the hash of the module, and that the
+/// mismatch is recorded in the upgrade history.
+fn assert_wasm_is_not_installed(expected_wasm_sha256: Option<Vec<u8>>) {
+    local_test_on_nns_subnet(move |runtime| async move {
+        let root =
+            set_up_root_canister(&runtime, RootCanisterInitPayloadBuilder::new().build()).await;
+        let fake_proposal_canister = set_up_universal_canister(&runtime).await;
+        assert_eq!(
+            fake_proposal_canister.canister_id(),
+            ic_nns_constants::GOVERNANCE_CANISTER_ID
+        );
+        let universal = set_up_universal_canister(&runtime).await;
+        universal
+            .set_controller(root.canister_id().get())
+            .await
+            .unwrap();
+
+        let mut proposal_payload =
+            ChangeNnsCanisterProposalPayload::new(true, Reinstall, universal.canister_id())
+                .with_wasm(b"This is not legal wasm binary.".to_vec());
+        proposal_payload.expected_wasm_sha256 = expected_wasm_sha256;
+
+        assert!(
+            forward_call_via_universal_canister(
+                &fake_proposal_canister,
+                &root,
+                "change_nns_canister",
+                Encode!(&proposal_payload).unwrap()
+            )
+            .await
+        );
+        root.stop_then_restart().await.unwrap();
+
+        let status: CanisterStatusResult = root
+            .update_(
+                "canister_status",
+                candid,
+                (CanisterIdRecord::from(universal.canister_id()),),
+            )
+            .await
+            .unwrap();
+        assert_is_running_universal_canister(&status);
+
+        let history: Vec<UpgradeRecord> = root
+            .query_(
+                "get_upgrade_history",
+                candid,
+                (None::<ic_types::CanisterId>,),
+            )
+            .await
+            .unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].outcome, UpgradeOutcome::WasmHashMismatch);
+        assert_eq!(history[0].previous_wasm_sha256, None);
+        Ok(())
+    });
+}
+
+#[test]
+fn test_wasm_with_unexpected_hash_is_not_installed() {
+    assert_wasm_is_not_installed(Some(vec![0; 32]));
+}
+
+/// Proposals made before the hash had to be pinned still decode, but are
+/// rejected.
+#[test]
+fn test_wasm_without_expected_hash_is_not_installed() {
+    assert_wasm_is_not_installed(None);
 }
 
 #[test]
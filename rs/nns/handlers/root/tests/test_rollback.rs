@@ -0,0 +1,218 @@
+//! Tests of the rollback of failed changes of NNS canisters, against a fake
+//! management canister, since a real one cannot be made to fail to start a
+//! canister after installing its code.
+
+use async_trait::async_trait;
+use candid::Encode;
+use futures::executor::block_on;
+use ic_base_types::{
+    CanisterId,
+    CanisterInstallMode::{Install, Upgrade},
+    PrincipalId,
+};
+use ic_crypto_sha::Sha256;
+use ic_ic00_types::InstallCodeArgs;
+use ic_nns_handler_root::canister_management::{
+    change_nns_canister, encode_stable_state, get_upgrade_history, restore_stable_state,
+    ManagementCanister,
+};
+use ic_nns_handler_root::common::{
+    CanisterStatusResult, CanisterStatusType, ChangeNnsCanisterProposalPayload, RollbackOutcome,
+    UpgradeOutcome,
+};
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+
+const ORIGINAL_MODULE: &[u8] = b"\0asm original module";
+const MODULE_A: &[u8] = b"\0asm module A";
+const MODULE_B: &[u8] = b"\0asm module B";
+
+/// A management canister that accepts any module starting with the wasm
+/// magic number, and fails to start canisters `failing_starts` times.
+#[derive(Default)]
+struct FakeManagementCanister {
+    modules: RefCell<BTreeMap<CanisterId, Vec<u8>>>,
+    failing_starts: Cell<u32>,
+    installs: RefCell<Vec<InstallCodeArgs>>,
+}
+
+#[async_trait(?Send)]
+impl ManagementCanister for FakeManagementCanister {
+    async fn canister_status(&self, canister_id: CanisterId) -> CanisterStatusResult {
+        CanisterStatusResult {
+            status: CanisterStatusType::Running,
+            module_hash: self
+                .modules
+                .borrow()
+                .get(&canister_id)
+                .map(|module| Sha256::hash(module).to_vec()),
+            controller: PrincipalId::new_anonymous(),
+            memory_size: candid::Nat::from(0),
+        }
+    }
+
+    async fn stop_canister(&self, _canister_id: CanisterId) {}
+
+    async fn install_code(&self, install_code_args: InstallCodeArgs) -> Result<(), String> {
+        self.installs.borrow_mut().push(install_code_args.clone());
+        if !install_code_args.wasm_module.starts_with(b"\0asm") {
+            return Err("Invalid wasm module".to_string());
+        }
+        let canister_id = CanisterId::new(install_code_args.canister_id).unwrap();
+        self.modules
+            .borrow_mut()
+            .insert(canister_id, install_code_args.wasm_module);
+        Ok(())
+    }
+
+    async fn start_canister(&self, _canister_id: CanisterId) -> Result<(), String> {
+        match self.failing_starts.get() {
+            0 => Ok(()),
+            n => {
+                self.failing_starts.set(n - 1);
+                Err("The canister does not start".to_string())
+            }
+        }
+    }
+}
+
+fn upgrade_to(canister_id: CanisterId, wasm_module: &[u8]) -> ChangeNnsCanisterProposalPayload {
+    ChangeNnsCanisterProposalPayload::new(true, Upgrade, canister_id)
+        .with_wasm(wasm_module.to_vec())
+}
+
+/// Verifies that when the upgraded canister does not start, the root installs
+/// the module that it installed before, with empty Candid arguments.
+#[test]
+fn test_failed_upgrade_is_rolled_back_to_the_previous_module() {
+    let canister_id = CanisterId::from_u64(42);
+    let management_canister = FakeManagementCanister::default();
+    management_canister
+        .modules
+        .borrow_mut()
+        .insert(canister_id, ORIGINAL_MODULE.to_vec());
+
+    let outcome = block_on(change_nns_canister(
+        &management_canister,
+        upgrade_to(canister_id, MODULE_A),
+        1,
+    ));
+    assert_eq!(outcome, UpgradeOutcome::Succeeded);
+
+    management_canister.failing_starts.set(1);
+    let outcome = block_on(change_nns_canister(
+        &management_canister,
+        upgrade_to(canister_id, MODULE_B),
+        2,
+    ));
+    assert_eq!(
+        outcome,
+        UpgradeOutcome::Failed {
+            reason: "The canister does not start".to_string(),
+            rollback: RollbackOutcome::Succeeded,
+        }
+    );
+
+    // The canister runs module A again, installed with valid Candid arguments.
+    assert_eq!(
+        management_canister.modules.borrow().get(&canister_id),
+        Some(&MODULE_A.to_vec())
+    );
+    let installs = management_canister.installs.borrow();
+    let rollback = installs.last().unwrap();
+    assert_eq!(rollback.mode, Upgrade);
+    assert_eq!(rollback.wasm_module, MODULE_A.to_vec());
+    assert_eq!(rollback.arg, Encode!().unwrap());
+
+    let history = get_upgrade_history(Some(canister_id));
+    assert_eq!(history.len(), 2);
+    assert_eq!(
+        history[1].previous_wasm_sha256,
+        Some(Sha256::hash(MODULE_A).to_vec())
+    );
+    assert_eq!(
+        history[1].proposed_wasm_sha256,
+        Sha256::hash(MODULE_B).to_vec()
+    );
+    assert_eq!(history[1].outcome, outcome);
+}
+
+/// Verifies that the root does not roll back to a module that the canister
+/// stopped running since the root installed it.
+#[test]
+fn test_module_replaced_by_another_controller_is_not_rolled_back_to() {
+    let canister_id = CanisterId::from_u64(42);
+    let management_canister = FakeManagementCanister::default();
+
+    let outcome = block_on(change_nns_canister(
+        &management_canister,
+        ChangeNnsCanisterProposalPayload::new(false, Install, canister_id)
+            .with_wasm(MODULE_A.to_vec()),
+        1,
+    ));
+    assert_eq!(outcome, UpgradeOutcome::Succeeded);
+
+    // Another controller installs another module.
+    management_canister
+        .modules
+        .borrow_mut()
+        .insert(canister_id, ORIGINAL_MODULE.to_vec());
+
+    management_canister.failing_starts.set(1);
+    let outcome = block_on(change_nns_canister(
+        &management_canister,
+        upgrade_to(canister_id, MODULE_B),
+        2,
+    ));
+    assert_eq!(
+        outcome,
+        UpgradeOutcome::Failed {
+            reason: "The canister does not start".to_string(),
+            rollback: RollbackOutcome::NoPreviousModule,
+        }
+    );
+    assert_eq!(
+        management_canister.modules.borrow().get(&canister_id),
+        Some(&MODULE_B.to_vec())
+    );
+}
+
+/// Verifies that the upgrade history and the installed modules are restored
+/// from the state written to stable memory, as after an upgrade of the root.
+#[test]
+fn test_stable_state_is_restored() {
+    let canister_id = CanisterId::from_u64(42);
+    let management_canister = FakeManagementCanister::default();
+    block_on(change_nns_canister(
+        &management_canister,
+        upgrade_to(canister_id, MODULE_A),
+        1,
+    ));
+    let history = get_upgrade_history(None);
+    let stable_state = encode_stable_state();
+
+    // Thread-local state starts empty in another thread, like the heap of an
+    // upgraded canister.
+    std::thread::spawn(move || {
+        assert!(get_upgrade_history(None).is_empty());
+        restore_stable_state(&stable_state).unwrap();
+        assert_eq!(get_upgrade_history(None), history);
+
+        // Module A can be rolled back to.
+        management_canister.failing_starts.set(1);
+        let outcome = block_on(change_nns_canister(
+            &management_canister,
+            upgrade_to(canister_id, MODULE_B),
+            2,
+        ));
+        assert_eq!(
+            outcome,
+            UpgradeOutcome::Failed {
+                reason: "The canister does not start".to_string(),
+                rollback: RollbackOutcome::Succeeded,
+            }
+        );
+    })
+    .join()
+    .unwrap();
+}
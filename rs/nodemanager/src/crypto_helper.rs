@@ -15,7 +15,7 @@ pub(crate) fn setup_crypto(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use ic_crypto::utils::get_node_keys_or_generate_if_missing;
+    use ic_crypto::utils::get_node_keys_or_generate_if_missing_for_config;
     use ic_logger::replica_logger::no_op_logger;
     use ic_registry_client::fake::FakeRegistryClient;
     use ic_registry_common::proto_registry_data_provider::ProtoRegistryDataProvider;
@@ -25,7 +25,7 @@ mod tests {
         CryptoConfig::run_with_temp_config(|config| {
             // Create node keys.
             let (_created_node_pks, _node_id) =
-                get_node_keys_or_generate_if_missing(&config.crypto_root);
+                get_node_keys_or_generate_if_missing_for_config(&config);
             let registry_client =
                 FakeRegistryClient::new(Arc::new(ProtoRegistryDataProvider::new()));
             let _crypto = setup_crypto(&config, Arc::new(registry_client), no_op_logger());
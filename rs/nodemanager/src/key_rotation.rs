@@ -0,0 +1,521 @@
+use crate::catch_up_package_provider::CatchUpPackageProvider;
+use crate::error::NodeManagerError;
+use crate::registry_helper::RegistryHelper;
+use candid::Encode;
+use ic_canister_client::{ed25519_public_key_to_der, Agent, Sender};
+use ic_config::crypto::{CryptoConfig, CspVaultType};
+use ic_crypto::utils::{
+    node_public_keys_age, previous_node_public_keys, remove_previous_node_keys, rotate_node_keys,
+    sign_message_id_with_node_signing_key,
+};
+use ic_interfaces::crypto::{KeyManager, DOMAIN_IC_REQUEST};
+use ic_interfaces::registry::RegistryClient;
+use ic_logger::{error, info, warn, ReplicaLogger};
+use ic_nns_constants::REGISTRY_CANISTER_ID;
+use ic_protobuf::crypto::v1::NodePublicKeys;
+use ic_registry_client::helper::crypto::CryptoRegistry;
+use ic_registry_client::helper::subnet::SubnetRegistry;
+use ic_types::consensus::dkg;
+use ic_types::crypto::KeyPurpose;
+use ic_types::messages::MessageId;
+use ic_types::{NodeId, RegistryVersion, SubnetId};
+use prost::Message;
+use rand::prelude::*;
+use registry_canister::mutations::do_update_node_public_keys::UpdateNodePublicKeysPayload;
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use url::Url;
+
+const KEY_ROTATION_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Periodically rotates the node's committee signing, DKG dealing encryption
+/// and TLS keys, and registers the new keys with the registry canister.
+///
+/// The node's keys are rotated once they are older than the configured
+/// rotation period. The secret keys that were replaced are kept until the
+/// latest registry version and all registry versions used by the latest
+/// catch-up package contain the new keys: consensus signs and verifies with
+/// the keys of the (possibly lagging) registry version of the summary block,
+/// and dealings are encrypted for the key contained in the registry version
+/// of the DKG.
+///
+/// Key rotation is not supported if the node's secret keys are held by the
+/// replica process itself (`CspVaultType::InReplica`), since rotating them
+/// from the node manager would write to the replica's secret key store
+/// concurrently. In that case, the background task is not started and an
+/// error is logged.
+///
+/// The new keys are registered by the node itself, i.e., the update call to
+/// the registry canister is signed with the node signing key, which is never
+/// rotated.
+pub(crate) struct NodeKeyRotation {
+    registry: Arc<RegistryHelper>,
+    cup_provider: Arc<CatchUpPackageProvider>,
+    key_manager: Arc<dyn KeyManager>,
+    crypto_config: CryptoConfig,
+    node_id: NodeId,
+    rotation_period: Duration,
+    logger: ReplicaLogger,
+
+    // If false, do not start or terminate the background task
+    enabled: Arc<AtomicBool>,
+}
+
+impl NodeKeyRotation {
+    pub(crate) fn new(
+        registry: Arc<RegistryHelper>,
+        cup_provider: Arc<CatchUpPackageProvider>,
+        key_manager: Arc<dyn KeyManager>,
+        crypto_config: CryptoConfig,
+        node_id: NodeId,
+        rotation_period: Duration,
+        logger: ReplicaLogger,
+    ) -> Self {
+        Self {
+            registry,
+            cup_provider,
+            key_manager,
+            crypto_config,
+            node_id,
+            rotation_period,
+            logger,
+            enabled: Arc::new(AtomicBool::new(true)),
+        }
+    }
+
+    /// Starts the background task, unless the node's secret keys are held by
+    /// the replica process itself: rotating keys from the node manager would
+    /// then write to the replica's secret key store concurrently.
+    pub(crate) fn start(self) -> Arc<AtomicBool> {
+        let result = self.enabled.clone();
+        if let CspVaultType::InReplica = self.crypto_config.csp_vault_type {
+            error!(
+                self.logger,
+                "A node key rotation period is configured, but node key rotation is not supported \
+                with CspVaultType::InReplica: the node's keys will NOT be rotated. Configure a \
+                separate CSP vault process (CspVaultType::UnixSocket) to enable key rotation."
+            );
+            result.store(false, Ordering::Relaxed);
+            return result;
+        }
+        tokio::spawn(background_task(self));
+        result
+    }
+
+    /// Rotates the node's keys if they are due for rotation, and drives a
+    /// pending key rotation to completion.
+    pub(crate) async fn check_for_key_rotation(&self) {
+        let crypto_root = &self.crypto_config.crypto_root;
+        if previous_node_public_keys(crypto_root).is_none() {
+            match node_public_keys_age(crypto_root) {
+                Some(age) if age >= self.rotation_period => {
+                    info!(
+                        self.logger,
+                        "Node keys are {} seconds old, rotating them.",
+                        age.as_secs()
+                    );
+                    rotate_node_keys(&self.crypto_config, self.node_id);
+                }
+                _ => return,
+            }
+        }
+
+        let node_pks = self.key_manager.node_public_keys();
+        let registry_version = self.registry.get_latest_version();
+        let registry_client = self.registry.registry_client.as_ref();
+        if !are_registered(registry_client, self.node_id, &node_pks, registry_version) {
+            if let Err(e) = self.register(&node_pks, registry_version).await {
+                warn!(self.logger, "Failed to register rotated node keys: {}", e);
+            }
+            return;
+        }
+
+        match self.registry_versions_in_use(registry_version).await {
+            Ok(versions)
+                if are_registered_at_all(registry_client, self.node_id, &node_pks, &versions) =>
+            {
+                info!(
+                    self.logger,
+                    "Rotated node keys are registered at all registry versions in use, removing the replaced keys."
+                );
+                remove_previous_node_keys(&self.crypto_config);
+            }
+            Ok(_) => (),
+            Err(e) => warn!(
+                self.logger,
+                "Failed to determine the registry versions in use: {}", e
+            ),
+        }
+    }
+
+    /// Returns the registry versions used by consensus and DKG according to
+    /// the latest catch-up package of the node's subnet, or no versions if
+    /// the node is unassigned.
+    async fn registry_versions_in_use(
+        &self,
+        version: RegistryVersion,
+    ) -> Result<BTreeSet<RegistryVersion>, String> {
+        let subnet_id = match self.registry.get_subnet_id(version) {
+            Ok(subnet_id) => subnet_id,
+            Err(NodeManagerError::NodeUnassignedError(_, _)) => return Ok(BTreeSet::new()),
+            Err(e) => return Err(e.to_string()),
+        };
+        let cup = self
+            .cup_provider
+            .get_latest_cup(subnet_id)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(dkg_registry_versions(
+            &cup.cup
+                .content
+                .block
+                .get_value()
+                .payload
+                .as_ref()
+                .as_summary()
+                .dkg,
+        ))
+    }
+
+    /// Sends an update call that registers `node_pks` to a random NNS node.
+    async fn register(
+        &self,
+        node_pks: &NodePublicKeys,
+        version: RegistryVersion,
+    ) -> Result<(), String> {
+        let nns_subnet_id = self.nns_subnet_id(version)?;
+        let nns_url = self.random_nns_url(nns_subnet_id, version)?;
+        let nns_public_key = self
+            .registry
+            .registry_client
+            .get_threshold_signing_public_key_for_subnet(nns_subnet_id, version)
+            .map_err(|e| format!("Error when fetching NNS public key: {:?}", e))?
+            .ok_or("NNS public key not defined")?;
+        let payload = UpdateNodePublicKeysPayload {
+            node_id: self.node_id,
+            committee_signing_pk: protobuf_to_vec(&node_pks.committee_signing_pk),
+            ni_dkg_dealing_encryption_pk: protobuf_to_vec(&node_pks.dkg_dealing_encryption_pk),
+            transport_tls_cert: protobuf_to_vec(&node_pks.tls_certificate),
+        };
+        let node_signing_pk = node_pks
+            .node_signing_pk
+            .as_ref()
+            .ok_or("Missing node signing public key")?;
+        let pub_key = ed25519_public_key_to_der(node_signing_pk.key_value.clone());
+        let crypto_config = self.crypto_config.clone();
+        let sign_cmd = move |msg: &[u8]| {
+            // The agent signs the domain separated message ID.
+            let message_id = msg
+                .strip_prefix(DOMAIN_IC_REQUEST)
+                .and_then(|message_id| MessageId::try_from(message_id).ok())
+                .ok_or_else(|| {
+                    Box::<dyn std::error::Error>::from("Refusing to sign a non-request message")
+                })?;
+            sign_message_id_with_node_signing_key(&crypto_config, &message_id)
+                .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
+        };
+        let agent = Agent::new(
+            nns_url,
+            Sender::from_external_hsm(pub_key, Arc::new(sign_cmd)),
+        )
+        .with_root_key(nns_public_key);
+        agent
+            .execute_update(
+                &REGISTRY_CANISTER_ID,
+                "update_node_public_keys",
+                Encode!(&payload)
+                    .expect("Could not encode payload for update_node_public_keys-call."),
+                generate_nonce(),
+            )
+            .await
+            .map(|_| ())
+    }
+
+    fn nns_subnet_id(&self, version: RegistryVersion) -> Result<SubnetId, String> {
+        self.registry
+            .registry_client
+            .get_root_subnet_id(version)
+            .map_err(|e| format!("Error when fetching NNS subnet id: {:?}", e))?
+            .ok_or_else(|| "NNS subnet id not defined".to_string())
+    }
+
+    fn random_nns_url(
+        &self,
+        nns_subnet_id: SubnetId,
+        version: RegistryVersion,
+    ) -> Result<Url, String> {
+        self.registry
+            .get_node_urls(nns_subnet_id, version)
+            .into_iter()
+            .flatten()
+            .choose(&mut thread_rng())
+            .ok_or_else(|| "No NNS node URL found".to_string())
+    }
+}
+
+/// Returns true iff the registry at `version` contains `node_pks`.
+fn are_registered(
+    registry_client: &dyn RegistryClient,
+    node_id: NodeId,
+    node_pks: &NodePublicKeys,
+    version: RegistryVersion,
+) -> bool {
+    let key_for = |key_purpose| {
+        registry_client
+            .get_crypto_key_for_node(node_id, key_purpose, version)
+            .ok()
+            .flatten()
+    };
+    key_for(KeyPurpose::CommitteeSigning) == node_pks.committee_signing_pk
+        && key_for(KeyPurpose::DkgDealingEncryption) == node_pks.dkg_dealing_encryption_pk
+        && registry_client
+            .get_tls_certificate(node_id, version)
+            .ok()
+            .flatten()
+            == node_pks.tls_certificate
+}
+
+/// Returns true iff the registry contains `node_pks` at each of the given
+/// `versions`.
+fn are_registered_at_all(
+    registry_client: &dyn RegistryClient,
+    node_id: NodeId,
+    node_pks: &NodePublicKeys,
+    versions: &BTreeSet<RegistryVersion>,
+) -> bool {
+    versions
+        .iter()
+        .all(|version| are_registered(registry_client, node_id, node_pks, *version))
+}
+
+/// Returns the registry version used by consensus in the interval of
+/// `summary`, together with the registry versions of the DKGs that are
+/// computed in that interval and of the transcripts that are still in use.
+fn dkg_registry_versions(summary: &dkg::Summary) -> BTreeSet<RegistryVersion> {
+    let config_versions = summary
+        .configs
+        .values()
+        .map(|config| config.registry_version());
+    let transcript_versions = summary
+        .current_transcripts()
+        .values()
+        .chain(summary.next_transcripts().values())
+        .map(|transcript| transcript.registry_version);
+    std::iter::once(summary.registry_version)
+        .chain(config_versions)
+        .chain(transcript_versions)
+        .collect()
+}
+
+async fn background_task(rotation: NodeKeyRotation) {
+    loop {
+        if !rotation.enabled.load(Ordering::Relaxed) {
+            return;
+        }
+
+        rotation.check_for_key_rotation().await;
+        tokio::time::sleep(KEY_ROTATION_CHECK_INTERVAL).await;
+    }
+}
+
+/// Create a nonce to be included with the ingress message sent to the
+/// registry canister.
+fn generate_nonce() -> Vec<u8> {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos()
+        .to_le_bytes()
+        .to_vec()
+}
+
+fn protobuf_to_vec<M: Message>(entry: &Option<M>) -> Vec<u8> {
+    let mut buf: Vec<u8> = Vec::new();
+    if let Some(entry) = entry {
+        entry.encode(&mut buf).expect("This must not fail");
+    }
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_protobuf::registry::crypto::v1::{PublicKey as PublicKeyProto, X509PublicKeyCert};
+    use ic_registry_client::fake::FakeRegistryClient;
+    use ic_registry_common::proto_registry_data_provider::ProtoRegistryDataProvider;
+    use ic_registry_keys::{make_crypto_node_key, make_crypto_tls_cert_key};
+    use ic_test_utilities::types::ids::node_test_id;
+    use ic_types::crypto::threshold_sig::ni_dkg::{NiDkgTag, NiDkgTranscript};
+    use ic_types::Height;
+    use std::collections::BTreeMap;
+
+    const NODE_ID: u64 = 42;
+
+    fn public_key(key_value: u8) -> PublicKeyProto {
+        PublicKeyProto {
+            key_value: vec![key_value; 32],
+            ..Default::default()
+        }
+    }
+
+    fn node_pks(key_value: u8) -> NodePublicKeys {
+        NodePublicKeys {
+            committee_signing_pk: Some(public_key(key_value)),
+            dkg_dealing_encryption_pk: Some(public_key(key_value)),
+            tls_certificate: Some(X509PublicKeyCert {
+                certificate_der: vec![key_value; 32],
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// Returns a registry that contains the keys of `node_pks[i]` at registry
+    /// version `i + 1`.
+    fn registry_with_node_pks(node_pks: &[NodePublicKeys]) -> FakeRegistryClient {
+        let node_id = node_test_id(NODE_ID);
+        let data_provider = Arc::new(ProtoRegistryDataProvider::new());
+        for (i, pks) in node_pks.iter().enumerate() {
+            let version = RegistryVersion::from(i as u64 + 1);
+            data_provider
+                .add(
+                    &make_crypto_node_key(node_id, KeyPurpose::CommitteeSigning),
+                    version,
+                    pks.committee_signing_pk.clone(),
+                )
+                .unwrap();
+            data_provider
+                .add(
+                    &make_crypto_node_key(node_id, KeyPurpose::DkgDealingEncryption),
+                    version,
+                    pks.dkg_dealing_encryption_pk.clone(),
+                )
+                .unwrap();
+            data_provider
+                .add(
+                    &make_crypto_tls_cert_key(node_id),
+                    version,
+                    pks.tls_certificate.clone(),
+                )
+                .unwrap();
+        }
+        let registry_client = FakeRegistryClient::new(data_provider);
+        registry_client.update_to_latest_version();
+        registry_client
+    }
+
+    fn versions(versions: &[u64]) -> BTreeSet<RegistryVersion> {
+        versions
+            .iter()
+            .copied()
+            .map(RegistryVersion::from)
+            .collect()
+    }
+
+    #[test]
+    fn should_consider_keys_registered_only_if_all_keys_match() {
+        let previous_pks = node_pks(1);
+        let rotated_pks = node_pks(2);
+        let registry_client = registry_with_node_pks(&[previous_pks.clone(), rotated_pks.clone()]);
+        let node_id = node_test_id(NODE_ID);
+
+        assert!(are_registered(
+            &registry_client,
+            node_id,
+            &rotated_pks,
+            RegistryVersion::from(2)
+        ));
+        assert!(!are_registered(
+            &registry_client,
+            node_id,
+            &rotated_pks,
+            RegistryVersion::from(1)
+        ));
+        let mut partially_rotated_pks = rotated_pks;
+        partially_rotated_pks.tls_certificate = previous_pks.tls_certificate;
+        assert!(!are_registered(
+            &registry_client,
+            node_id,
+            &partially_rotated_pks,
+            RegistryVersion::from(2)
+        ));
+    }
+
+    #[test]
+    fn should_keep_previous_keys_while_a_registry_version_in_use_contains_them() {
+        let previous_pks = node_pks(1);
+        let rotated_pks = node_pks(2);
+        let registry_client = registry_with_node_pks(&[previous_pks, rotated_pks.clone()]);
+        let node_id = node_test_id(NODE_ID);
+
+        assert!(!are_registered_at_all(
+            &registry_client,
+            node_id,
+            &rotated_pks,
+            &versions(&[1, 2])
+        ));
+        assert!(are_registered_at_all(
+            &registry_client,
+            node_id,
+            &rotated_pks,
+            &versions(&[2])
+        ));
+    }
+
+    #[test]
+    fn should_remove_previous_keys_if_no_registry_version_is_in_use() {
+        let registry_client = registry_with_node_pks(&[node_pks(1), node_pks(2)]);
+
+        assert!(are_registered_at_all(
+            &registry_client,
+            node_test_id(NODE_ID),
+            &node_pks(2),
+            &BTreeSet::new()
+        ));
+    }
+
+    #[test]
+    fn should_return_registry_versions_of_summary_and_its_transcripts() {
+        let transcript = |tag, version| {
+            NiDkgTranscript::dummy_transcript_for_tests_with_params(
+                vec![node_test_id(NODE_ID)],
+                tag,
+                1,
+                version,
+            )
+        };
+        let current_transcripts = vec![
+            (
+                NiDkgTag::LowThreshold,
+                transcript(NiDkgTag::LowThreshold, 3),
+            ),
+            (
+                NiDkgTag::HighThreshold,
+                transcript(NiDkgTag::HighThreshold, 1),
+            ),
+        ]
+        .into_iter()
+        .collect();
+        let next_transcripts = vec![(
+            NiDkgTag::LowThreshold,
+            transcript(NiDkgTag::LowThreshold, 5),
+        )]
+        .into_iter()
+        .collect();
+        let summary = dkg::Summary::new(
+            vec![],
+            current_transcripts,
+            next_transcripts,
+            BTreeMap::new(),
+            RegistryVersion::from(7),
+            Height::from(99),
+            Height::from(99),
+            Height::from(100),
+            BTreeMap::new(),
+        );
+
+        assert_eq!(dkg_registry_versions(&summary), versions(&[1, 3, 5, 7]));
+    }
+}
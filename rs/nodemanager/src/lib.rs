@@ -37,6 +37,7 @@ mod catch_up_package_provider;
 mod crypto_helper;
 mod error;
 mod firewall;
+mod key_rotation;
 mod metrics;
 mod nns_registry_replicator;
 pub mod node_manager;
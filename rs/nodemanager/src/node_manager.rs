@@ -2,6 +2,7 @@ use crate::args::NodeManagerArgs;
 use crate::catch_up_package_provider::CatchUpPackageProvider;
 use crate::crypto_helper::setup_crypto;
 use crate::firewall::Firewall;
+use crate::key_rotation::NodeKeyRotation;
 use crate::metrics::NodeManagerMetrics;
 use crate::nns_registry_replicator::NnsRegistryReplicator;
 use crate::registration::NodeRegistration;
@@ -16,7 +17,7 @@ use ic_config::{
     metrics::{Config as MetricsConfig, Exporter},
     Config,
 };
-use ic_crypto::utils::get_node_keys_or_generate_if_missing;
+use ic_crypto::utils::get_node_keys_or_generate_if_missing_for_config;
 use ic_crypto::CryptoComponentForNonReplicaProcess;
 use ic_crypto_tls_interfaces::TlsHandshake;
 use ic_interfaces::{crypto::KeyManager, registry::RegistryClient};
@@ -39,6 +40,7 @@ pub struct NodeManager {
     release_package: Arc<std::sync::atomic::AtomicBool>,
     firewall: Arc<std::sync::atomic::AtomicBool>,
     ssh_access_manager: Arc<std::sync::atomic::AtomicBool>,
+    node_key_rotation: Arc<std::sync::atomic::AtomicBool>,
     replica_process: Arc<Mutex<ReplicaProcess>>,
 }
 
@@ -54,11 +56,14 @@ impl NodeManager {
     /// data centers. If a new data center is added, node manager will
     /// generate a new firewall configuration allowing access from the
     /// IP range specified in the DC record.
+    ///
+    /// If a key rotation period is configured, it also spawns a task that
+    /// periodically rotates the node's keys and registers the new ones.
     pub async fn start(args: NodeManagerArgs) -> Result<Self, ()> {
         args.create_dirs();
         let metrics_addr = args.get_metrics_addr();
         let config = args.get_ic_config();
-        let (_node_pks, node_id) = get_node_keys_or_generate_if_missing(&config.crypto.crypto_root);
+        let (_node_pks, node_id) = get_node_keys_or_generate_if_missing_for_config(&config.crypto);
 
         let (logger, _async_log_guard) = Self::get_logger(&config);
         let slog_logger = logger.inner_logger.root.clone();
@@ -164,7 +169,7 @@ impl NodeManager {
             Arc::clone(&registry),
             replica_process.clone(),
             release_package_provider,
-            cup_provider,
+            Arc::clone(&cup_provider),
             args.replica_binary_dir.clone(),
             args.force_replica_binary.clone(),
             args.replica_config_file.clone(),
@@ -188,6 +193,19 @@ impl NodeManager {
         let ssh_access_manager =
             SshAccessManager::new(Arc::clone(&registry), Arc::clone(&metrics), logger.clone())
                 .start();
+        let node_key_rotation = match config.registration.node_key_rotation_period_secs {
+            Some(rotation_period_secs) => NodeKeyRotation::new(
+                Arc::clone(&registry),
+                cup_provider,
+                Arc::clone(&crypto) as Arc<dyn KeyManager>,
+                config.crypto.clone(),
+                node_id,
+                std::time::Duration::from_secs(rotation_period_secs),
+                logger.clone(),
+            )
+            .start(),
+            None => Arc::new(std::sync::atomic::AtomicBool::new(false)),
+        };
         Ok(Self {
             logger,
             _async_log_guard,
@@ -197,6 +215,7 @@ impl NodeManager {
             replica_process,
             firewall,
             ssh_access_manager,
+            node_key_rotation,
         })
     }
 
@@ -215,6 +234,9 @@ impl NodeManager {
         self.ssh_access_manager
             .as_ref()
             .store(false, std::sync::atomic::Ordering::Relaxed);
+        self.node_key_rotation
+            .as_ref()
+            .store(false, std::sync::atomic::Ordering::Relaxed);
         let e = self.replica_process.clone().lock().unwrap().stop();
         warn!(self.logger, "unable to stop replica: {:?}", e);
     }
@@ -249,6 +271,7 @@ impl NodeManager {
             metrics_registry.clone(),
             registry_client,
             crypto,
+            None,
             logger,
         );
 
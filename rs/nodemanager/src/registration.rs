@@ -170,13 +170,22 @@ impl NodeRegistration {
             version = self.registry_client.get_latest_version();
         }
 
-        use ic_registry_client::helper::{node::NodeRegistry, subnet::SubnetRegistry};
+        use ic_registry_client::helper::{
+            crypto::CryptoRegistry, node::NodeRegistry, subnet::SubnetRegistry,
+        };
 
         let nns_subnet_id = self
             .registry_client
             .get_root_subnet_id(version)
             .expect("Error when fetching nns subnet id.")
             .expect("NNS subnet id not defined");
+        // The replies of the NNS nodes are only accepted if they are certified
+        // by the NNS subnet.
+        let nns_public_key = self
+            .registry_client
+            .get_threshold_signing_public_key_for_subnet(nns_subnet_id, version)
+            .expect("Error when fetching the NNS public key.")
+            .expect("NNS public key not defined");
         let node_ids = self
             .registry_client
             .get_node_ids_on_subnet(nns_subnet_id, version)
@@ -244,7 +253,8 @@ impl NodeRegistration {
                 pub_key: hsm_pub_key.clone(),
                 sign: Arc::new(sign_cmd),
             };
-            let agent = Agent::new(nns_urls.next().unwrap().clone(), sender);
+            let agent =
+                Agent::new(nns_urls.next().unwrap().clone(), sender).with_root_key(nns_public_key);
 
             if let Err(e) = agent
                 .execute_update(
@@ -0,0 +1,271 @@
+//! A command-line tool to build and submit registry mutations and governance
+//! proposals, and to read registry records.
+//!
+//! Every mutation command builds the payload of the corresponding `do_*`
+//! mutation of the registry canister from its flags. The payload is then
+//! either submitted as an `ExecuteNnsFunction` proposal of a neuron
+//! (`--proposer`), or sent directly to the canister method that applies it
+//! (`--direct`), which only succeeds if the sender is authorized to call it,
+//! e.g. in test setups. With `--dry-run`, the payload is printed instead.
+mod mutations;
+mod records;
+
+pub use mutations::MutationCommand;
+pub use records::ReadCommand;
+
+use candid::{CandidType, Encode};
+use ic_canister_client::{Agent, Sender};
+use ic_crypto_internal_types::sign::eddsa::ed25519::SecretKey;
+use ic_crypto_utils_basic_sig::conversions::Ed25519SecretKeyConversions;
+use ic_crypto_utils_threshold_sig::parse_threshold_sig_key;
+use ic_nns_common::types::NeuronId;
+use ic_nns_constants::{ids::TEST_NEURON_1_OWNER_KEYPAIR, GOVERNANCE_CANISTER_ID};
+use ic_nns_governance::pb::v1::NnsFunction;
+use ic_nns_governance::proposal_submission::{
+    create_external_update_proposal_binary, create_make_proposal_payload,
+    decode_make_proposal_response,
+};
+use ic_types::CanisterId;
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+use url::Url;
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "ic-admin",
+    about = "Administer the registry of the Internet Computer"
+)]
+pub struct Opts {
+    /// The URL of a replica of the NNS subnet.
+    #[structopt(long, default_value = "http://localhost:8080")]
+    pub nns_url: Url,
+
+    /// The PEM file of the public key of the NNS subnet, against which the
+    /// replies of mutations are verified. Mutations other than `--dry-run`
+    /// fail without it.
+    #[structopt(long, parse(from_os_str))]
+    pub nns_public_key_pem: Option<PathBuf>,
+
+    /// The PEM file of the Ed25519 secret key with which requests are signed.
+    /// Requests are sent anonymously if neither this nor
+    /// `--use-test-neuron-1-owner-key` is given.
+    #[structopt(long, parse(from_os_str))]
+    pub secret_key_pem: Option<PathBuf>,
+
+    /// Sign requests with the key of the owner of the first test neuron, which
+    /// exists if governance was initialized with test neurons.
+    #[structopt(long, conflicts_with = "secret-key-pem")]
+    pub use_test_neuron_1_owner_key: bool,
+
+    #[structopt(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Command {
+    #[structopt(flatten)]
+    Mutation(MutationCommand),
+    #[structopt(flatten)]
+    Read(ReadCommand),
+}
+
+/// How a mutation is submitted.
+#[derive(Debug, StructOpt)]
+pub struct SubmissionArgs {
+    /// Submit the payload as a proposal of the neuron with this ID, which must
+    /// be controlled by the sender.
+    #[structopt(long, required_unless_one = &["direct", "dry-run"])]
+    pub proposer: Option<u64>,
+
+    /// Call the canister method that applies the mutation directly instead of
+    /// submitting a proposal.
+    #[structopt(long, conflicts_with = "proposer")]
+    pub direct: bool,
+
+    /// Print the payload instead of submitting it.
+    #[structopt(long)]
+    pub dry_run: bool,
+
+    /// The title of the proposal.
+    #[structopt(long)]
+    pub proposal_title: Option<String>,
+
+    /// The summary of the proposal.
+    #[structopt(long, default_value = "")]
+    pub summary: String,
+
+    /// A URL with more information about the proposal.
+    #[structopt(long, default_value = "")]
+    pub proposal_url: String,
+}
+
+/// The candid-encoded payload of a mutation together with the canister method
+/// that applies it.
+pub struct Mutation {
+    /// The NNS function that applies the mutation when a proposal is adopted,
+    /// or `None` if the mutation cannot be proposed.
+    pub nns_function: Option<NnsFunction>,
+    pub canister_id: CanisterId,
+    pub method: String,
+    pub arg: Vec<u8>,
+    /// The human-readable payload.
+    pub description: String,
+}
+
+impl Mutation {
+    /// A mutation that is applied by `nns_function`.
+    pub fn proposal<T: CandidType + Debug>(nns_function: NnsFunction, payload: T) -> Self {
+        Self::for_nns_function(
+            nns_function,
+            Encode!(&payload).expect("Error encoding payload"),
+            format!("{:#?}", payload),
+        )
+    }
+
+    /// A mutation without arguments that is applied by `nns_function`.
+    pub fn proposal_without_payload(nns_function: NnsFunction) -> Self {
+        Self::for_nns_function(
+            nns_function,
+            Encode!().expect("Error encoding payload"),
+            "()".to_string(),
+        )
+    }
+
+    fn for_nns_function(nns_function: NnsFunction, arg: Vec<u8>, description: String) -> Self {
+        let (canister_id, method) = nns_function
+            .canister_and_function()
+            .expect("Every NNS function of a command has a target method");
+        Self {
+            nns_function: Some(nns_function),
+            canister_id,
+            method: method.to_string(),
+            arg,
+            description,
+        }
+    }
+
+    /// A mutation that can only be applied by calling `method` directly.
+    pub fn direct_only<T: CandidType + Debug>(
+        canister_id: CanisterId,
+        method: &str,
+        payload: T,
+    ) -> Self {
+        Self {
+            nns_function: None,
+            canister_id,
+            method: method.to_string(),
+            arg: Encode!(&payload).expect("Error encoding payload"),
+            description: format!("{:#?}", payload),
+        }
+    }
+}
+
+/// Runs `opts.command` and returns its output.
+pub async fn run(opts: Opts) -> Result<String, String> {
+    match opts.command {
+        Command::Read(command) => records::read(&opts.nns_url, command).await,
+        Command::Mutation(command) => {
+            let sender = if opts.use_test_neuron_1_owner_key {
+                Sender::from_keypair(&TEST_NEURON_1_OWNER_KEYPAIR)
+            } else if let Some(path) = &opts.secret_key_pem {
+                sender_from_pem(path)?
+            } else {
+                Sender::Anonymous
+            };
+            let mut agent = Agent::new(opts.nns_url.clone(), sender);
+            if let Some(path) = &opts.nns_public_key_pem {
+                let nns_public_key = parse_threshold_sig_key(path).map_err(|e| {
+                    format!("Failed to read the NNS public key from {:?}: {}", path, e)
+                })?;
+                agent = agent.with_root_key(nns_public_key);
+            }
+            let (mutation, submission) = command.into_mutation()?;
+            submit(&agent, mutation, submission).await
+        }
+    }
+}
+
+async fn submit(
+    agent: &Agent,
+    mutation: Mutation,
+    submission: SubmissionArgs,
+) -> Result<String, String> {
+    if submission.dry_run {
+        return Ok(format!(
+            "{}.{}:\n{}",
+            mutation.canister_id, mutation.method, mutation.description
+        ));
+    }
+
+    if submission.direct {
+        agent
+            .execute_update(
+                &mutation.canister_id,
+                &mutation.method,
+                mutation.arg,
+                nonce(),
+            )
+            .await?;
+        return Ok(format!(
+            "Called {}.{}",
+            mutation.canister_id, mutation.method
+        ));
+    }
+
+    let nns_function = mutation.nns_function.ok_or_else(|| {
+        format!(
+            "{} cannot be proposed, use --direct instead",
+            mutation.method
+        )
+    })?;
+    let proposer = NeuronId(
+        submission
+            .proposer
+            .ok_or("--proposer, --direct or --dry-run is required")?,
+    );
+    let title = submission
+        .proposal_title
+        .unwrap_or_else(|| format!("{}: {}", mutation.method, mutation.canister_id));
+    let proposal = create_external_update_proposal_binary(
+        &title,
+        &submission.summary,
+        &submission.proposal_url,
+        nns_function,
+        mutation.arg,
+    );
+    let response = agent
+        .execute_update(
+            &GOVERNANCE_CANISTER_ID,
+            "manage_neuron",
+            Encode!(&create_make_proposal_payload(proposal, &proposer))
+                .expect("Error encoding manage_neuron payload"),
+            nonce(),
+        )
+        .await?
+        .ok_or("No response was received from manage_neuron")?;
+    let proposal_id = decode_make_proposal_response(response)?;
+    Ok(format!("Submitted proposal {}", proposal_id))
+}
+
+fn sender_from_pem(path: &Path) -> Result<Sender, String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let (secret_key, public_key) = SecretKey::from_pem(&contents)
+        .map_err(|e| format!("Invalid secret key in {}: {:?}", path.display(), e))?;
+    let keypair = ed25519_dalek::Keypair {
+        public: ed25519_dalek::PublicKey::from_bytes(public_key.as_bytes())
+            .map_err(|e| format!("Invalid public key in {}: {}", path.display(), e))?,
+        secret: ed25519_dalek::SecretKey::from_bytes(secret_key.as_bytes())
+            .map_err(|e| format!("Invalid secret key in {}: {}", path.display(), e))?,
+    };
+    Ok(Sender::from_keypair(&keypair))
+}
+
+/// Returns a nonce that makes every submitted message unique.
+fn nonce() -> Vec<u8> {
+    chrono::Utc::now()
+        .timestamp_nanos()
+        .to_string()
+        .into_bytes()
+}
@@ -0,0 +1,13 @@
+use ic_admin::{run, Opts};
+use structopt::StructOpt;
+
+#[tokio::main]
+async fn main() {
+    match run(Opts::from_args()).await {
+        Ok(output) => println!("{}", output),
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
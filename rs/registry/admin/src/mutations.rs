@@ -0,0 +1,907 @@
+//! The commands that build the payloads of registry mutations.
+use crate::{Mutation, SubmissionArgs};
+use ic_nns_constants::REGISTRY_CANISTER_ID;
+use ic_nns_governance::pb::v1::NnsFunction;
+use ic_protobuf::registry::dc::v1::{AddOrRemoveDataCentersProposalPayload, DataCenterRecord};
+use ic_protobuf::registry::node_rewards::v2::{
+    NodeRewardRate, NodeRewardRates, UpdateNodeRewardsTableProposalPayload,
+};
+use ic_registry_subnet_features::SubnetFeatures;
+use ic_registry_subnet_type::SubnetType;
+use ic_types::p2p;
+use ic_types::{NodeId, PrincipalId, SubnetId};
+use registry_canister::mutations::{
+    do_add_node_operator::AddNodeOperatorPayload, do_add_nodes_to_subnet::AddNodesToSubnetPayload,
+    do_bless_replica_version::BlessReplicaVersionPayload, do_create_subnet::CreateSubnetPayload,
+    do_delete_subnet::DeleteSubnetPayload, do_recover_subnet::RecoverSubnetPayload,
+    do_remove_node_directly::RemoveNodeDirectlyPayload, do_remove_nodes::RemoveNodesPayload,
+    do_remove_nodes_from_subnet::RemoveNodesFromSubnetPayload,
+    do_set_firewall_config::SetFirewallConfigPayload,
+    do_update_icp_xdr_conversion_rate::UpdateIcpXdrConversionRatePayload,
+    do_update_node_operator_config::UpdateNodeOperatorConfigPayload,
+    do_update_subnet::UpdateSubnetPayload,
+    do_update_subnet_replica::UpdateSubnetReplicaVersionPayload,
+};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub enum MutationCommand {
+    /// Create a subnet from unassigned nodes.
+    CreateSubnet(CreateSubnetArgs),
+    /// Change the configuration of a subnet. Unspecified fields are left
+    /// unchanged.
+    UpdateSubnet(UpdateSubnetArgs),
+    /// Delete a subnet. Can only be applied directly.
+    DeleteSubnet(DeleteSubnetArgs),
+    /// Add unassigned nodes to a subnet.
+    AddNodesToSubnet(AddNodesToSubnetArgs),
+    /// Remove nodes from their subnets, making them unassigned.
+    RemoveNodesFromSubnet(NodesArgs),
+    /// Remove unassigned nodes from the registry.
+    RemoveNodes(NodesArgs),
+    /// Remove a node from the registry on behalf of its node operator. Can
+    /// only be applied directly.
+    RemoveNodeDirectly(RemoveNodeDirectlyArgs),
+    /// Replace the catch-up package of a halted subnet in order to recover it.
+    RecoverSubnet(RecoverSubnetArgs),
+    /// Bless a replica version, so that subnets can be upgraded to it.
+    BlessReplicaVersion(BlessReplicaVersionArgs),
+    /// Upgrade a subnet to a blessed replica version.
+    UpdateSubnetReplicaVersion(UpdateSubnetReplicaVersionArgs),
+    /// Add a node operator.
+    AddNodeOperator(AddNodeOperatorArgs),
+    /// Change the configuration of a node operator.
+    UpdateNodeOperatorConfig(UpdateNodeOperatorConfigArgs),
+    /// Set the firewall config of all nodes.
+    SetFirewallConfig(SetFirewallConfigArgs),
+    /// Remove all principals from the provisional whitelist.
+    ClearProvisionalWhitelist(SubmissionOnlyArgs),
+    /// Set the ICP/XDR conversion rate.
+    UpdateIcpXdrConversionRate(UpdateIcpXdrConversionRateArgs),
+    /// Add or replace entries of the node rewards table.
+    UpdateNodeRewardsTable(UpdateNodeRewardsTableArgs),
+    /// Add or remove data center records.
+    AddOrRemoveDataCenters(AddOrRemoveDataCentersArgs),
+}
+
+impl MutationCommand {
+    /// Builds the mutation and returns it together with how it is submitted.
+    pub fn into_mutation(self) -> Result<(Mutation, SubmissionArgs), String> {
+        Ok(match self {
+            Self::CreateSubnet(args) => (
+                Mutation::proposal(NnsFunction::CreateSubnet, args.payload()),
+                args.submission,
+            ),
+            Self::UpdateSubnet(args) => (
+                Mutation::proposal(NnsFunction::UpdateConfigOfSubnet, args.payload()),
+                args.submission,
+            ),
+            Self::DeleteSubnet(args) => (
+                Mutation::direct_only(
+                    REGISTRY_CANISTER_ID,
+                    "delete_subnet",
+                    DeleteSubnetPayload {
+                        subnet_id: Some(args.subnet),
+                    },
+                ),
+                args.submission,
+            ),
+            Self::AddNodesToSubnet(args) => (
+                Mutation::proposal(
+                    NnsFunction::AddNodeToSubnet,
+                    AddNodesToSubnetPayload {
+                        subnet_id: args.subnet,
+                        node_ids: node_ids(args.nodes),
+                    },
+                ),
+                args.submission,
+            ),
+            Self::RemoveNodesFromSubnet(args) => (
+                Mutation::proposal(
+                    NnsFunction::RemoveNodesFromSubnet,
+                    RemoveNodesFromSubnetPayload {
+                        node_ids: node_ids(args.nodes),
+                    },
+                ),
+                args.submission,
+            ),
+            Self::RemoveNodes(args) => (
+                Mutation::proposal(
+                    NnsFunction::RemoveNodes,
+                    RemoveNodesPayload {
+                        node_ids: node_ids(args.nodes),
+                    },
+                ),
+                args.submission,
+            ),
+            Self::RemoveNodeDirectly(args) => (
+                Mutation::direct_only(
+                    REGISTRY_CANISTER_ID,
+                    "remove_node_directly",
+                    RemoveNodeDirectlyPayload {
+                        node_id: NodeId::from(args.node),
+                    },
+                ),
+                args.submission,
+            ),
+            Self::RecoverSubnet(args) => (
+                Mutation::proposal(NnsFunction::RecoverSubnet, args.payload()?),
+                args.submission,
+            ),
+            Self::BlessReplicaVersion(args) => (
+                Mutation::proposal(NnsFunction::BlessReplicaVersion, args.payload()),
+                args.submission,
+            ),
+            Self::UpdateSubnetReplicaVersion(args) => (
+                Mutation::proposal(
+                    NnsFunction::UpdateSubnetReplicaVersion,
+                    UpdateSubnetReplicaVersionPayload {
+                        subnet_id: args.subnet,
+                        replica_version_id: args.replica_version_id,
+                    },
+                ),
+                args.submission,
+            ),
+            Self::AddNodeOperator(args) => (
+                Mutation::proposal(
+                    NnsFunction::AssignNoid,
+                    AddNodeOperatorPayload {
+                        node_operator_principal_id: Some(args.node_operator),
+                        node_provider_principal_id: Some(args.node_provider),
+                        node_allowance: args.node_allowance,
+                        dc_id: args.dc_id,
+                        rewardable_nodes: parse_json(&args.rewardable_nodes)?,
+                    },
+                ),
+                args.submission,
+            ),
+            Self::UpdateNodeOperatorConfig(args) => (
+                Mutation::proposal(
+                    NnsFunction::UpdateNodeOperatorConfig,
+                    UpdateNodeOperatorConfigPayload {
+                        node_operator_id: Some(args.node_operator),
+                        node_allowance: args.node_allowance,
+                        dc_id: args.dc_id,
+                        rewardable_nodes: match &args.rewardable_nodes {
+                            Some(json) => parse_json(json)?,
+                            None => BTreeMap::new(),
+                        },
+                    },
+                ),
+                args.submission,
+            ),
+            Self::SetFirewallConfig(args) => {
+                let firewall_config =
+                    std::fs::read_to_string(&args.firewall_config_file).map_err(|e| {
+                        format!(
+                            "Failed to read {}: {}",
+                            args.firewall_config_file.display(),
+                            e
+                        )
+                    })?;
+                (
+                    Mutation::proposal(
+                        NnsFunction::SetFirewallConfig,
+                        SetFirewallConfigPayload {
+                            firewall_config,
+                            ipv4_prefixes: args.ipv4_prefixes,
+                            ipv6_prefixes: args.ipv6_prefixes,
+                        },
+                    ),
+                    args.submission,
+                )
+            }
+            Self::ClearProvisionalWhitelist(args) => (
+                Mutation::proposal_without_payload(NnsFunction::ClearProvisionalWhitelist),
+                args.submission,
+            ),
+            Self::UpdateIcpXdrConversionRate(args) => (
+                Mutation::proposal(
+                    NnsFunction::IcpXdrConversionRate,
+                    UpdateIcpXdrConversionRatePayload {
+                        data_source: args.data_source,
+                        timestamp_seconds: args.timestamp_seconds,
+                        xdr_permyriad_per_icp: args.xdr_permyriad_per_icp,
+                    },
+                ),
+                args.submission,
+            ),
+            Self::UpdateNodeRewardsTable(args) => (
+                Mutation::proposal(NnsFunction::UpdateNodeRewardsTable, args.payload()?),
+                args.submission,
+            ),
+            Self::AddOrRemoveDataCenters(args) => (
+                Mutation::proposal(NnsFunction::AddOrRemoveDataCenters, args.payload()?),
+                args.submission,
+            ),
+        })
+    }
+}
+
+#[derive(Debug, StructOpt)]
+pub struct SubmissionOnlyArgs {
+    #[structopt(flatten)]
+    submission: SubmissionArgs,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct CreateSubnetArgs {
+    /// The nodes that form the subnet.
+    #[structopt(long, required = true, min_values = 1)]
+    nodes: Vec<PrincipalId>,
+
+    /// Use this ID for the subnet instead of deriving it.
+    #[structopt(long)]
+    subnet_id_override: Option<PrincipalId>,
+
+    /// The replica version that the subnet runs, which must be blessed.
+    #[structopt(long)]
+    replica_version_id: String,
+
+    #[structopt(long, default_value = "application")]
+    subnet_type: SubnetType,
+
+    /// A comma-separated list of features to enable, or "None".
+    #[structopt(long, default_value = "None")]
+    features: SubnetFeatures,
+
+    #[structopt(long, default_value = "2097152")]
+    ingress_bytes_per_block_soft_cap: u64,
+    #[structopt(long, default_value = "2097152")]
+    max_ingress_bytes_per_message: u64,
+    #[structopt(long, default_value = "1000")]
+    max_ingress_messages_per_block: u64,
+    #[structopt(long, default_value = "4194304")]
+    max_block_payload_size: u64,
+    #[structopt(long, default_value = "1000")]
+    unit_delay_millis: u64,
+    #[structopt(long, default_value = "600")]
+    initial_notary_delay_millis: u64,
+    #[structopt(long, default_value = "499")]
+    dkg_interval_length: u64,
+    #[structopt(long, default_value = "1")]
+    dkg_dealings_per_block: u64,
+
+    #[structopt(long)]
+    gossip_max_artifact_streams_per_peer: Option<u32>,
+    #[structopt(long)]
+    gossip_max_chunk_wait_ms: Option<u32>,
+    #[structopt(long)]
+    gossip_max_duplicity: Option<u32>,
+    #[structopt(long)]
+    gossip_max_chunk_size: Option<u32>,
+    #[structopt(long)]
+    gossip_receive_check_cache_size: Option<u32>,
+    #[structopt(long)]
+    gossip_pfn_evaluation_period_ms: Option<u32>,
+    #[structopt(long)]
+    gossip_registry_poll_period_ms: Option<u32>,
+    #[structopt(long)]
+    gossip_retransmission_request_ms: Option<u32>,
+    #[structopt(long)]
+    advert_best_effort_percentage: Option<u32>,
+
+    /// Start the subnet as the NNS subnet.
+    #[structopt(long)]
+    start_as_nns: bool,
+
+    /// Create the subnet halted.
+    #[structopt(long)]
+    is_halted: bool,
+
+    #[structopt(long, default_value = "5000000000")]
+    max_instructions_per_message: u64,
+    #[structopt(long, default_value = "7000000000")]
+    max_instructions_per_round: u64,
+    #[structopt(long, default_value = "200000000000")]
+    max_instructions_per_install_code: u64,
+
+    /// The maximum number of canisters on the subnet, 0 means unlimited.
+    #[structopt(long, default_value = "0")]
+    max_number_of_canisters: u64,
+
+    /// The public keys with read-only SSH access to the nodes.
+    #[structopt(long)]
+    ssh_readonly_access: Vec<String>,
+    /// The public keys with SSH access to the backup of the nodes.
+    #[structopt(long)]
+    ssh_backup_access: Vec<String>,
+
+    #[structopt(flatten)]
+    submission: SubmissionArgs,
+}
+
+impl CreateSubnetArgs {
+    fn payload(&self) -> CreateSubnetPayload {
+        CreateSubnetPayload {
+            node_ids: node_ids(self.nodes.clone()),
+            subnet_id_override: self.subnet_id_override,
+            ingress_bytes_per_block_soft_cap: self.ingress_bytes_per_block_soft_cap,
+            max_ingress_bytes_per_message: self.max_ingress_bytes_per_message,
+            max_ingress_messages_per_block: self.max_ingress_messages_per_block,
+            max_block_payload_size: self.max_block_payload_size,
+            unit_delay_millis: self.unit_delay_millis,
+            initial_notary_delay_millis: self.initial_notary_delay_millis,
+            replica_version_id: self.replica_version_id.clone(),
+            dkg_interval_length: self.dkg_interval_length,
+            dkg_dealings_per_block: self.dkg_dealings_per_block,
+            gossip_max_artifact_streams_per_peer: self
+                .gossip_max_artifact_streams_per_peer
+                .unwrap_or(p2p::MAX_ARTIFACT_STREAMS_PER_PEER),
+            gossip_max_chunk_wait_ms: self
+                .gossip_max_chunk_wait_ms
+                .unwrap_or(p2p::MAX_CHUNK_WAIT_MS),
+            gossip_max_duplicity: self.gossip_max_duplicity.unwrap_or(p2p::MAX_DUPLICITY),
+            gossip_max_chunk_size: self.gossip_max_chunk_size.unwrap_or(p2p::MAX_CHUNK_SIZE),
+            gossip_receive_check_cache_size: self
+                .gossip_receive_check_cache_size
+                .unwrap_or(p2p::RECEIVE_CHECK_PEER_SET_SIZE),
+            gossip_pfn_evaluation_period_ms: self
+                .gossip_pfn_evaluation_period_ms
+                .unwrap_or(p2p::PFN_EVALUATION_PERIOD_MS),
+            gossip_registry_poll_period_ms: self
+                .gossip_registry_poll_period_ms
+                .unwrap_or(p2p::REGISTRY_POLL_PERIOD_MS),
+            gossip_retransmission_request_ms: self
+                .gossip_retransmission_request_ms
+                .unwrap_or(p2p::RETRANSMISSION_REQUEST_MS),
+            advert_best_effort_percentage: self.advert_best_effort_percentage,
+            start_as_nns: self.start_as_nns,
+            subnet_type: self.subnet_type,
+            is_halted: self.is_halted,
+            max_instructions_per_message: self.max_instructions_per_message,
+            max_instructions_per_round: self.max_instructions_per_round,
+            max_instructions_per_install_code: self.max_instructions_per_install_code,
+            features: self.features,
+            max_number_of_canisters: self.max_number_of_canisters,
+            ssh_readonly_access: self.ssh_readonly_access.clone(),
+            ssh_backup_access: self.ssh_backup_access.clone(),
+        }
+    }
+}
+
+#[derive(Debug, StructOpt)]
+pub struct UpdateSubnetArgs {
+    /// The subnet to update.
+    #[structopt(long)]
+    subnet: PrincipalId,
+
+    #[structopt(long)]
+    ingress_bytes_per_block_soft_cap: Option<u64>,
+    #[structopt(long)]
+    max_ingress_bytes_per_message: Option<u64>,
+    #[structopt(long)]
+    max_block_payload_size: Option<u64>,
+    #[structopt(long)]
+    unit_delay_millis: Option<u64>,
+    #[structopt(long)]
+    initial_notary_delay_millis: Option<u64>,
+    #[structopt(long)]
+    dkg_interval_length: Option<u64>,
+    #[structopt(long)]
+    dkg_dealings_per_block: Option<u64>,
+
+    #[structopt(long)]
+    gossip_max_artifact_streams_per_peer: Option<u32>,
+    #[structopt(long)]
+    gossip_max_chunk_wait_ms: Option<u32>,
+    #[structopt(long)]
+    gossip_max_duplicity: Option<u32>,
+    #[structopt(long)]
+    gossip_max_chunk_size: Option<u32>,
+    #[structopt(long)]
+    gossip_receive_check_cache_size: Option<u32>,
+    #[structopt(long)]
+    gossip_pfn_evaluation_period_ms: Option<u32>,
+    #[structopt(long)]
+    gossip_registry_poll_period_ms: Option<u32>,
+    #[structopt(long)]
+    gossip_retransmission_request_ms: Option<u32>,
+    #[structopt(long)]
+    advert_best_effort_percentage: Option<u32>,
+
+    /// Reset the gossip config to its defaults.
+    #[structopt(long)]
+    set_gossip_config_to_default: bool,
+
+    #[structopt(long)]
+    start_as_nns: Option<bool>,
+    #[structopt(long)]
+    subnet_type: Option<SubnetType>,
+    #[structopt(long)]
+    is_halted: Option<bool>,
+
+    #[structopt(long)]
+    max_instructions_per_message: Option<u64>,
+    #[structopt(long)]
+    max_instructions_per_round: Option<u64>,
+    #[structopt(long)]
+    max_instructions_per_install_code: Option<u64>,
+
+    /// A comma-separated list of features to enable, or "None".
+    #[structopt(long)]
+    features: Option<SubnetFeatures>,
+
+    #[structopt(long)]
+    max_number_of_canisters: Option<u64>,
+
+    #[structopt(long)]
+    ssh_readonly_access: Option<Vec<String>>,
+    #[structopt(long)]
+    ssh_backup_access: Option<Vec<String>>,
+
+    #[structopt(flatten)]
+    submission: SubmissionArgs,
+}
+
+impl UpdateSubnetArgs {
+    fn payload(&self) -> UpdateSubnetPayload {
+        UpdateSubnetPayload {
+            subnet_id: SubnetId::from(self.subnet),
+            ingress_bytes_per_block_soft_cap: self.ingress_bytes_per_block_soft_cap,
+            max_ingress_bytes_per_message: self.max_ingress_bytes_per_message,
+            max_block_payload_size: self.max_block_payload_size,
+            unit_delay_millis: self.unit_delay_millis,
+            initial_notary_delay_millis: self.initial_notary_delay_millis,
+            dkg_interval_length: self.dkg_interval_length,
+            dkg_dealings_per_block: self.dkg_dealings_per_block,
+            max_artifact_streams_per_peer: self.gossip_max_artifact_streams_per_peer,
+            max_chunk_wait_ms: self.gossip_max_chunk_wait_ms,
+            max_duplicity: self.gossip_max_duplicity,
+            max_chunk_size: self.gossip_max_chunk_size,
+            receive_check_cache_size: self.gossip_receive_check_cache_size,
+            pfn_evaluation_period_ms: self.gossip_pfn_evaluation_period_ms,
+            registry_poll_period_ms: self.gossip_registry_poll_period_ms,
+            retransmission_request_ms: self.gossip_retransmission_request_ms,
+            advert_best_effort_percentage: self.advert_best_effort_percentage,
+            set_gossip_config_to_default: self.set_gossip_config_to_default,
+            start_as_nns: self.start_as_nns,
+            subnet_type: self.subnet_type,
+            is_halted: self.is_halted,
+            max_instructions_per_message: self.max_instructions_per_message,
+            max_instructions_per_round: self.max_instructions_per_round,
+            max_instructions_per_install_code: self.max_instructions_per_install_code,
+            features: self.features,
+            max_number_of_canisters: self.max_number_of_canisters,
+            ssh_readonly_access: self.ssh_readonly_access.clone(),
+            ssh_backup_access: self.ssh_backup_access.clone(),
+        }
+    }
+}
+
+#[derive(Debug, StructOpt)]
+pub struct DeleteSubnetArgs {
+    /// The subnet to delete.
+    #[structopt(long)]
+    subnet: PrincipalId,
+
+    #[structopt(flatten)]
+    submission: SubmissionArgs,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct AddNodesToSubnetArgs {
+    /// The subnet to add the nodes to.
+    #[structopt(long)]
+    subnet: PrincipalId,
+
+    /// The nodes to add.
+    #[structopt(long, required = true, min_values = 1)]
+    nodes: Vec<PrincipalId>,
+
+    #[structopt(flatten)]
+    submission: SubmissionArgs,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct NodesArgs {
+    /// The nodes to remove.
+    #[structopt(long, required = true, min_values = 1)]
+    nodes: Vec<PrincipalId>,
+
+    #[structopt(flatten)]
+    submission: SubmissionArgs,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct RemoveNodeDirectlyArgs {
+    /// The node to remove.
+    #[structopt(long)]
+    node: PrincipalId,
+
+    #[structopt(flatten)]
+    submission: SubmissionArgs,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct RecoverSubnetArgs {
+    /// The subnet to recover.
+    #[structopt(long)]
+    subnet: PrincipalId,
+
+    /// The height of the recovery catch-up package.
+    #[structopt(long)]
+    height: u64,
+
+    /// The block time of the recovery catch-up package, in nanoseconds since
+    /// the Unix epoch.
+    #[structopt(long)]
+    time_ns: u64,
+
+    /// The hex-encoded hash of the state to recover from.
+    #[structopt(long)]
+    state_hash: String,
+
+    /// Replace the members of the subnet with these nodes.
+    #[structopt(long)]
+    replacement_nodes: Option<Vec<PrincipalId>>,
+
+    /// The URI from which the nodes download a registry local store that
+    /// replaces theirs.
+    #[structopt(long, requires_all = &["registry-store-sha256", "registry-version"])]
+    registry_store_uri: Option<String>,
+
+    /// The hex-encoded SHA-256 hash of the registry local store.
+    #[structopt(long)]
+    registry_store_sha256: Option<String>,
+
+    /// The registry version of the registry local store.
+    #[structopt(long)]
+    registry_version: Option<u64>,
+
+    #[structopt(flatten)]
+    submission: SubmissionArgs,
+}
+
+impl RecoverSubnetArgs {
+    fn payload(&self) -> Result<RecoverSubnetPayload, String> {
+        let state_hash = hex::decode(&self.state_hash)
+            .map_err(|e| format!("Invalid state hash {:?}: {}", self.state_hash, e))?;
+        let registry_store_uri = match (
+            &self.registry_store_uri,
+            &self.registry_store_sha256,
+            self.registry_version,
+        ) {
+            (Some(uri), Some(sha256), Some(version)) => {
+                Some((uri.clone(), sha256.clone(), version))
+            }
+            _ => None,
+        };
+        Ok(RecoverSubnetPayload {
+            subnet_id: self.subnet,
+            height: self.height,
+            time_ns: self.time_ns,
+            state_hash,
+            replacement_nodes: self.replacement_nodes.clone().map(node_ids),
+            registry_store_uri,
+        })
+    }
+}
+
+#[derive(Debug, StructOpt)]
+pub struct BlessReplicaVersionArgs {
+    /// The ID of the replica version.
+    #[structopt(long)]
+    replica_version_id: String,
+
+    /// The URL of the replica binary.
+    #[structopt(long, default_value = "")]
+    binary_url: String,
+    /// The hex-encoded SHA-256 hash of the replica binary.
+    #[structopt(long, default_value = "")]
+    sha256_hex: String,
+
+    /// The URL of the node manager binary.
+    #[structopt(long, default_value = "")]
+    node_manager_binary_url: String,
+    /// The hex-encoded SHA-256 hash of the node manager binary.
+    #[structopt(long, default_value = "")]
+    node_manager_sha256_hex: String,
+
+    /// The URL of the release package.
+    #[structopt(long, default_value = "")]
+    release_package_url: String,
+    /// The hex-encoded SHA-256 hash of the release package.
+    #[structopt(long, default_value = "")]
+    release_package_sha256_hex: String,
+
+    #[structopt(flatten)]
+    submission: SubmissionArgs,
+}
+
+impl BlessReplicaVersionArgs {
+    fn payload(&self) -> BlessReplicaVersionPayload {
+        BlessReplicaVersionPayload {
+            replica_version_id: self.replica_version_id.clone(),
+            binary_url: self.binary_url.clone(),
+            sha256_hex: self.sha256_hex.clone(),
+            node_manager_binary_url: self.node_manager_binary_url.clone(),
+            node_manager_sha256_hex: self.node_manager_sha256_hex.clone(),
+            release_package_url: self.release_package_url.clone(),
+            release_package_sha256_hex: self.release_package_sha256_hex.clone(),
+        }
+    }
+}
+
+#[derive(Debug, StructOpt)]
+pub struct UpdateSubnetReplicaVersionArgs {
+    /// The subnet to upgrade.
+    #[structopt(long)]
+    subnet: PrincipalId,
+
+    /// The blessed replica version to upgrade to.
+    #[structopt(long)]
+    replica_version_id: String,
+
+    #[structopt(flatten)]
+    submission: SubmissionArgs,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct AddNodeOperatorArgs {
+    /// The principal of the node operator.
+    #[structopt(long)]
+    node_operator: PrincipalId,
+
+    /// The principal of the node provider that the node operator belongs to.
+    #[structopt(long)]
+    node_provider: PrincipalId,
+
+    /// The number of nodes that the node operator may add.
+    #[structopt(long)]
+    node_allowance: u64,
+
+    /// The data center where the node operator hosts its nodes.
+    #[structopt(long, default_value = "")]
+    dc_id: String,
+
+    /// The number of rewardable nodes per node type as a JSON object, e.g.
+    /// '{ "default": 10 }'.
+    #[structopt(long, default_value = "{}")]
+    rewardable_nodes: String,
+
+    #[structopt(flatten)]
+    submission: SubmissionArgs,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct UpdateNodeOperatorConfigArgs {
+    /// The principal of the node operator.
+    #[structopt(long)]
+    node_operator: PrincipalId,
+
+    /// The number of nodes that the node operator may add.
+    #[structopt(long)]
+    node_allowance: Option<u64>,
+
+    /// The data center where the node operator hosts its nodes.
+    #[structopt(long)]
+    dc_id: Option<String>,
+
+    /// The number of rewardable nodes per node type as a JSON object, e.g.
+    /// '{ "default": 10 }'.
+    #[structopt(long)]
+    rewardable_nodes: Option<String>,
+
+    #[structopt(flatten)]
+    submission: SubmissionArgs,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct SetFirewallConfigArgs {
+    /// The file holding the firewall config.
+    #[structopt(long, parse(from_os_str))]
+    firewall_config_file: PathBuf,
+
+    /// The IPv4 prefixes that are allowed to connect.
+    #[structopt(long)]
+    ipv4_prefixes: Vec<String>,
+
+    /// The IPv6 prefixes that are allowed to connect.
+    #[structopt(long)]
+    ipv6_prefixes: Vec<String>,
+
+    #[structopt(flatten)]
+    submission: SubmissionArgs,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct UpdateIcpXdrConversionRateArgs {
+    /// The source of the conversion rate.
+    #[structopt(long)]
+    data_source: String,
+
+    /// The time of the conversion rate, in seconds since the Unix epoch.
+    #[structopt(long)]
+    timestamp_seconds: u64,
+
+    /// The number of 1/10,000ths of an XDR that one ICP is worth.
+    #[structopt(long)]
+    xdr_permyriad_per_icp: u64,
+
+    #[structopt(flatten)]
+    submission: SubmissionArgs,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct UpdateNodeRewardsTableArgs {
+    /// The new entries as a JSON object that maps regions to node types to
+    /// monthly rewards in 1/10,000ths of an XDR, e.g.
+    /// '{ "EU": { "default": 240 } }'.
+    #[structopt(long)]
+    updated_node_rewards: String,
+
+    #[structopt(flatten)]
+    submission: SubmissionArgs,
+}
+
+impl UpdateNodeRewardsTableArgs {
+    fn payload(&self) -> Result<UpdateNodeRewardsTableProposalPayload, String> {
+        let rewards: BTreeMap<String, BTreeMap<String, u64>> =
+            parse_json(&self.updated_node_rewards)?;
+        let new_entries = rewards
+            .into_iter()
+            .map(|(region, rates)| {
+                let rates = rates
+                    .into_iter()
+                    .map(|(node_type, xdr_permyriad_per_node_per_month)| {
+                        (
+                            node_type,
+                            NodeRewardRate {
+                                xdr_permyriad_per_node_per_month,
+                            },
+                        )
+                    })
+                    .collect();
+                (region, NodeRewardRates { rates })
+            })
+            .collect();
+        Ok(UpdateNodeRewardsTableProposalPayload { new_entries })
+    }
+}
+
+#[derive(Debug, StructOpt)]
+pub struct AddOrRemoveDataCentersArgs {
+    /// The data center records to add, each as a JSON object, e.g.
+    /// '{ "id": "zh1", "region": "Europe", "owner": "owner", "gps": null }'.
+    #[structopt(long)]
+    data_centers_to_add: Vec<String>,
+
+    /// The IDs of the data centers to remove.
+    #[structopt(long)]
+    data_centers_to_remove: Vec<String>,
+
+    #[structopt(flatten)]
+    submission: SubmissionArgs,
+}
+
+impl AddOrRemoveDataCentersArgs {
+    fn payload(&self) -> Result<AddOrRemoveDataCentersProposalPayload, String> {
+        let data_centers_to_add = self
+            .data_centers_to_add
+            .iter()
+            .map(|json| parse_json::<DataCenterRecord>(json))
+            .collect::<Result<_, _>>()?;
+        Ok(AddOrRemoveDataCentersProposalPayload {
+            data_centers_to_add,
+            data_centers_to_remove: self.data_centers_to_remove.clone(),
+        })
+    }
+}
+
+fn node_ids(principals: Vec<PrincipalId>) -> Vec<NodeId> {
+    principals.into_iter().map(NodeId::from).collect()
+}
+
+fn parse_json<T: serde::de::DeserializeOwned>(json: &str) -> Result<T, String> {
+    serde_json::from_str(json).map_err(|e| format!("Invalid JSON {:?}: {}", json, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Command, Opts};
+    use candid::Decode;
+
+    fn mutation(args: &[&str]) -> (Mutation, SubmissionArgs) {
+        let opts =
+            Opts::from_iter_safe(std::iter::once("ic-admin").chain(args.iter().copied())).unwrap();
+        match opts.command {
+            Command::Mutation(command) => command.into_mutation().unwrap(),
+            Command::Read(command) => panic!("Unexpected read command {:?}", command),
+        }
+    }
+
+    #[test]
+    fn should_build_create_subnet_payload() {
+        let node = PrincipalId::new_node_test_id(1);
+        let (mutation, submission) = mutation(&[
+            "create-subnet",
+            "--nodes",
+            &node.to_string(),
+            "--replica-version-id",
+            "0.8.0",
+            "--subnet-type",
+            "system",
+            "--features",
+            "canister_sandboxing",
+            "--proposer",
+            "42",
+        ]);
+
+        assert_eq!(mutation.nns_function, Some(NnsFunction::CreateSubnet));
+        assert_eq!(mutation.canister_id, REGISTRY_CANISTER_ID);
+        assert_eq!(mutation.method, "create_subnet");
+        assert_eq!(submission.proposer, Some(42));
+        let payload = Decode!(&mutation.arg, CreateSubnetPayload).unwrap();
+        assert_eq!(payload.node_ids, vec![NodeId::from(node)]);
+        assert_eq!(payload.replica_version_id, "0.8.0");
+        assert_eq!(payload.subnet_type, SubnetType::System);
+        assert!(payload.features.canister_sandboxing);
+        assert_eq!(
+            payload.gossip_max_chunk_size,
+            p2p::MAX_CHUNK_SIZE,
+            "unspecified gossip settings default to the gossip defaults"
+        );
+    }
+
+    #[test]
+    fn should_leave_unspecified_subnet_settings_unchanged() {
+        let subnet = PrincipalId::new_subnet_test_id(1);
+        let (mutation, _) = mutation(&[
+            "update-subnet",
+            "--subnet",
+            &subnet.to_string(),
+            "--is-halted",
+            "true",
+            "--direct",
+        ]);
+
+        assert_eq!(mutation.method, "update_subnet");
+        let payload = Decode!(&mutation.arg, UpdateSubnetPayload).unwrap();
+        assert_eq!(payload.subnet_id, SubnetId::from(subnet));
+        assert_eq!(payload.is_halted, Some(true));
+        assert_eq!(payload.max_block_payload_size, None);
+        assert_eq!(payload.features, None);
+    }
+
+    #[test]
+    fn should_build_node_rewards_table_payload() {
+        let (mutation, _) = mutation(&[
+            "update-node-rewards-table",
+            "--updated-node-rewards",
+            r#"{ "EU": { "default": 240 } }"#,
+            "--dry-run",
+        ]);
+
+        let payload = Decode!(&mutation.arg, UpdateNodeRewardsTableProposalPayload).unwrap();
+        assert_eq!(
+            payload.new_entries["EU"].rates["default"].xdr_permyriad_per_node_per_month,
+            240
+        );
+    }
+
+    #[test]
+    fn direct_only_mutations_have_no_nns_function() {
+        let (mutation, submission) = mutation(&[
+            "delete-subnet",
+            "--subnet",
+            &PrincipalId::new_subnet_test_id(1).to_string(),
+            "--direct",
+        ]);
+
+        assert_eq!(mutation.nns_function, None);
+        assert_eq!(mutation.method, "delete_subnet");
+        assert!(submission.direct);
+    }
+
+    #[test]
+    fn should_require_a_submission_mode() {
+        let node = PrincipalId::new_node_test_id(1).to_string();
+        let result = Opts::from_iter_safe(&["ic-admin", "remove-nodes", "--nodes", node.as_str()]);
+
+        assert!(result.is_err());
+    }
+}
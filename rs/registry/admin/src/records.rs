@@ -0,0 +1,185 @@
+//! The commands that read and decode registry records.
+use ic_protobuf::registry::{
+    conversion_rate::v1::IcpXdrConversionRateRecord,
+    dc::v1::DataCenterRecord,
+    firewall::v1::FirewallConfig,
+    node::v1::NodeRecord,
+    node_operator::v1::NodeOperatorRecord,
+    node_rewards::v2::NodeRewardsTable,
+    provisional_whitelist::v1::ProvisionalWhitelist,
+    replica_version::v1::{BlessedReplicaVersions, ReplicaVersionRecord},
+    routing_table::v1::RoutingTable,
+    subnet::v1::{SubnetListRecord, SubnetRecord},
+};
+use ic_registry_common::registry::RegistryCanister;
+use ic_registry_keys::{
+    make_blessed_replica_version_key, make_data_center_record_key, make_firewall_config_record_key,
+    make_icp_xdr_conversion_rate_record_key, make_node_operator_record_key, make_node_record_key,
+    make_provisional_whitelist_record_key, make_replica_version_key, make_routing_table_record_key,
+    make_subnet_list_record_key, make_subnet_record_key, NODE_REWARDS_TABLE_KEY,
+};
+use ic_types::{NodeId, PrincipalId, SubnetId};
+use prost::Message;
+use std::convert::TryFrom;
+use std::fmt::Debug;
+use structopt::StructOpt;
+use url::Url;
+
+#[derive(Debug, StructOpt)]
+pub enum ReadCommand {
+    /// Print the latest registry version.
+    GetRegistryVersion,
+    /// Print the record of a subnet.
+    GetSubnet(PrincipalArgs),
+    /// Print the IDs of all subnets.
+    GetSubnetList(VersionArgs),
+    /// Print the record of a node.
+    GetNode(PrincipalArgs),
+    /// Print the record of a node operator.
+    GetNodeOperator(PrincipalArgs),
+    /// Print the record of a replica version.
+    GetReplicaVersion(IdArgs),
+    /// Print the blessed replica versions.
+    GetBlessedReplicaVersions(VersionArgs),
+    /// Print the routing table.
+    GetRoutingTable(VersionArgs),
+    /// Print the firewall config.
+    GetFirewallConfig(VersionArgs),
+    /// Print the provisional whitelist.
+    GetProvisionalWhitelist(VersionArgs),
+    /// Print the ICP/XDR conversion rate.
+    GetIcpXdrConversionRate(VersionArgs),
+    /// Print the record of a data center.
+    GetDataCenter(IdArgs),
+    /// Print the node rewards table.
+    GetNodeRewardsTable(VersionArgs),
+}
+
+#[derive(Debug, StructOpt)]
+pub struct VersionArgs {
+    /// Read the record at this registry version instead of the latest one.
+    #[structopt(long)]
+    at_version: Option<u64>,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct PrincipalArgs {
+    principal: PrincipalId,
+    #[structopt(flatten)]
+    version: VersionArgs,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct IdArgs {
+    id: String,
+    #[structopt(flatten)]
+    version: VersionArgs,
+}
+
+/// Reads the record(s) that `command` refers to from the registry canister and
+/// returns them in human-readable form.
+pub(crate) async fn read(nns_url: &Url, command: ReadCommand) -> Result<String, String> {
+    let registry = RegistryCanister::new(vec![nns_url.clone()]);
+    match command {
+        ReadCommand::GetRegistryVersion => registry
+            .get_latest_version()
+            .await
+            .map(|version| version.to_string())
+            .map_err(|e| e.to_string()),
+        ReadCommand::GetSubnet(args) => {
+            let key = make_subnet_record_key(SubnetId::from(args.principal));
+            let (record, version) = get::<SubnetRecord>(&registry, &key, args.version).await?;
+            let members = record
+                .membership
+                .iter()
+                .map(|bytes| decode_principal(bytes).map(NodeId::from))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(format!(
+                "{}\nMembers: {:?}",
+                describe(&key, version, &record),
+                members
+            ))
+        }
+        ReadCommand::GetSubnetList(args) => {
+            let key = make_subnet_list_record_key();
+            let (record, version) = get::<SubnetListRecord>(&registry, &key, args).await?;
+            let subnets = record
+                .subnets
+                .iter()
+                .map(|bytes| decode_principal(bytes).map(SubnetId::from))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(describe(&key, version, &subnets))
+        }
+        ReadCommand::GetNode(args) => {
+            let key = make_node_record_key(NodeId::from(args.principal));
+            read_record::<NodeRecord>(&registry, &key, args.version).await
+        }
+        ReadCommand::GetNodeOperator(args) => {
+            let key = make_node_operator_record_key(args.principal);
+            read_record::<NodeOperatorRecord>(&registry, &key, args.version).await
+        }
+        ReadCommand::GetReplicaVersion(args) => {
+            let key = make_replica_version_key(&args.id);
+            read_record::<ReplicaVersionRecord>(&registry, &key, args.version).await
+        }
+        ReadCommand::GetBlessedReplicaVersions(args) => {
+            let key = make_blessed_replica_version_key();
+            read_record::<BlessedReplicaVersions>(&registry, &key, args).await
+        }
+        ReadCommand::GetRoutingTable(args) => {
+            let key = make_routing_table_record_key();
+            read_record::<RoutingTable>(&registry, &key, args).await
+        }
+        ReadCommand::GetFirewallConfig(args) => {
+            let key = make_firewall_config_record_key();
+            read_record::<FirewallConfig>(&registry, &key, args).await
+        }
+        ReadCommand::GetProvisionalWhitelist(args) => {
+            let key = make_provisional_whitelist_record_key();
+            read_record::<ProvisionalWhitelist>(&registry, &key, args).await
+        }
+        ReadCommand::GetIcpXdrConversionRate(args) => {
+            let key = make_icp_xdr_conversion_rate_record_key();
+            read_record::<IcpXdrConversionRateRecord>(&registry, &key, args).await
+        }
+        ReadCommand::GetDataCenter(args) => {
+            let key = make_data_center_record_key(&args.id);
+            read_record::<DataCenterRecord>(&registry, &key, args.version).await
+        }
+        ReadCommand::GetNodeRewardsTable(args) => {
+            read_record::<NodeRewardsTable>(&registry, NODE_REWARDS_TABLE_KEY, args).await
+        }
+    }
+}
+
+async fn read_record<T: Message + Default>(
+    registry: &RegistryCanister,
+    key: &str,
+    version: VersionArgs,
+) -> Result<String, String> {
+    let (record, version) = get::<T>(registry, key, version).await?;
+    Ok(describe(key, version, &record))
+}
+
+/// Returns the record at `key` and the registry version at which it was read.
+async fn get<T: Message + Default>(
+    registry: &RegistryCanister,
+    key: &str,
+    version: VersionArgs,
+) -> Result<(T, u64), String> {
+    let (bytes, version) = registry
+        .get_value(key.as_bytes().to_vec(), version.at_version)
+        .await
+        .map_err(|e| e.to_string())?;
+    let record =
+        T::decode(bytes.as_slice()).map_err(|e| format!("Failed to decode {}: {}", key, e))?;
+    Ok((record, version))
+}
+
+fn describe<T: Debug>(key: &str, version: u64, record: &T) -> String {
+    format!("{} at version {}:\n{:#?}", key, version, record)
+}
+
+fn decode_principal(bytes: &[u8]) -> Result<PrincipalId, String> {
+    PrincipalId::try_from(bytes).map_err(|e| format!("Invalid principal {:?}: {}", bytes, e))
+}
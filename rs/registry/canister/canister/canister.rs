@@ -13,7 +13,7 @@ use ic_nns_common::{
 };
 use ic_protobuf::registry::{
     dc::v1::AddOrRemoveDataCentersProposalPayload,
-    node_rewards::v2::UpdateNodeRewardsTableProposalPayload,
+    node_rewards::v2::{NodeProvidersMonthlyXdrRewards, UpdateNodeRewardsTableProposalPayload},
 };
 use ic_registry_transport::{
     deserialize_atomic_mutate_request, deserialize_get_changes_since_request,
@@ -40,6 +40,7 @@ use registry_canister::{
         do_remove_nodes_from_subnet::RemoveNodesFromSubnetPayload,
         do_update_icp_xdr_conversion_rate::UpdateIcpXdrConversionRatePayload,
         do_update_node_operator_config::UpdateNodeOperatorConfigPayload,
+        do_update_node_public_keys::UpdateNodePublicKeysPayload,
         do_update_subnet::UpdateSubnetPayload,
         do_update_subnet_replica::UpdateSubnetReplicaVersionPayload,
     },
@@ -304,6 +305,43 @@ fn get_certified_latest_version() {
     });
 }
 
+/// Returns the monthly rewards, in 10,000ths of XDR, of every node provider,
+/// as derived from the node rewards table, the node operator records and the
+/// data center records.
+#[export_name = "canister_query get_node_providers_monthly_xdr_rewards"]
+fn get_node_providers_monthly_xdr_rewards() {
+    over(
+        candid,
+        |()| -> Result<NodeProvidersMonthlyXdrRewards, String> {
+            registry().get_node_providers_monthly_xdr_rewards()
+        },
+    );
+}
+
+/// Returns the invariant violations that `update_subnet` would run into with
+/// the given payload, without applying it. An empty list means that the
+/// payload would be accepted by the current registry.
+///
+/// Governance calls this when an `UpdateConfigOfSubnet` proposal is submitted.
+#[export_name = "canister_query dry_run_update_subnet"]
+fn dry_run_update_subnet() {
+    over(candid_one, |payload: UpdateSubnetPayload| -> Vec<String> {
+        registry().dry_run_update_subnet(payload)
+    });
+}
+
+/// Returns the invariant violations that `create_subnet` would run into with
+/// the given payload, without applying it. An empty list means that the
+/// payload would be accepted by the current registry.
+///
+/// Governance calls this when a `CreateSubnet` proposal is submitted.
+#[export_name = "canister_query dry_run_create_subnet"]
+fn dry_run_create_subnet() {
+    over(candid_one, |payload: CreateSubnetPayload| -> Vec<String> {
+        registry().dry_run_create_subnet(payload)
+    });
+}
+
 #[export_name = "canister_update atomic_mutate"]
 fn atomic_mutate() {
     let caller = dfn_core::api::caller();
@@ -392,6 +430,22 @@ fn add_node() {
     });
 }
 
+#[export_name = "canister_update update_node_public_keys"]
+fn update_node_public_keys() {
+    // This method can be called by any node, but only for its own keys
+    println!(
+        "{}call: {} from: {}",
+        LOG_PREFIX,
+        "update_node_public_keys".to_string(),
+        dfn_core::api::caller()
+    );
+    over_may_reject(candid_one, |payload: UpdateNodePublicKeysPayload| {
+        let result = registry_mut().do_update_node_public_keys(payload);
+        recertify_registry();
+        result
+    });
+}
+
 #[export_name = "canister_update add_node_operator"]
 fn add_node_operator() {
     check_caller_is_governance_and_log("add_node_operator");
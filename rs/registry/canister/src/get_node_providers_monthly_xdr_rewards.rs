@@ -0,0 +1,252 @@
+use crate::{mutations::common::decode_registry_value, registry::Registry};
+
+use ic_base_types::PrincipalId;
+use ic_protobuf::registry::{
+    dc::v1::DataCenterRecord,
+    node_operator::v1::NodeOperatorRecord,
+    node_rewards::v2::{NodeProvidersMonthlyXdrRewards, NodeRewardsTable},
+};
+use ic_registry_keys::{
+    make_data_center_record_key, NODE_OPERATOR_RECORD_KEY_PREFIX, NODE_REWARDS_TABLE_KEY,
+};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+impl Registry {
+    /// Computes the monthly rewards, in 10,000ths of XDR, of every node
+    /// provider that has rewardable nodes, from the latest version of the
+    /// node rewards table, node operator records and data center records.
+    pub fn get_node_providers_monthly_xdr_rewards(
+        &self,
+    ) -> Result<NodeProvidersMonthlyXdrRewards, String> {
+        let version = self.latest_version();
+
+        let rewards_table = self
+            .get(NODE_REWARDS_TABLE_KEY.as_bytes(), version)
+            .map(|v| decode_registry_value::<NodeRewardsTable>(v.value.clone()))
+            .ok_or_else(|| "Node rewards table is not present in the registry".to_string())?;
+
+        let node_operators: Vec<NodeOperatorRecord> = self
+            .store
+            .keys()
+            .filter(|key| key.starts_with(NODE_OPERATOR_RECORD_KEY_PREFIX.as_bytes()))
+            .filter_map(|key| self.get(key, version))
+            .map(|v| decode_registry_value::<NodeOperatorRecord>(v.value.clone()))
+            .collect();
+
+        let mut data_centers = BTreeMap::new();
+        for node_operator in &node_operators {
+            if data_centers.contains_key(&node_operator.dc_id) {
+                continue;
+            }
+            if let Some(v) = self.get(
+                make_data_center_record_key(&node_operator.dc_id).as_bytes(),
+                version,
+            ) {
+                data_centers.insert(
+                    node_operator.dc_id.clone(),
+                    decode_registry_value::<DataCenterRecord>(v.value.clone()),
+                );
+            }
+        }
+
+        calculate_rewards(&rewards_table, &node_operators, &data_centers)
+    }
+}
+
+/// Sums up, per node provider, the monthly rewards of the rewardable nodes of
+/// all its node operators, at the rates of the region of the operator's data
+/// center.
+fn calculate_rewards(
+    rewards_table: &NodeRewardsTable,
+    node_operators: &[NodeOperatorRecord],
+    data_centers: &BTreeMap<String, DataCenterRecord>,
+) -> Result<NodeProvidersMonthlyXdrRewards, String> {
+    let mut rewards = BTreeMap::<String, u64>::new();
+
+    for node_operator in node_operators {
+        if node_operator.rewardable_nodes.is_empty() {
+            continue;
+        }
+
+        let node_operator_id = PrincipalId::try_from(&node_operator.node_operator_principal_id)
+            .map_err(|e| format!("Invalid node operator principal id: {}", e))?;
+        let node_provider_id = PrincipalId::try_from(&node_operator.node_provider_principal_id)
+            .map_err(|e| {
+                format!(
+                    "Node operator {} has an invalid node provider principal id: {}",
+                    node_operator_id, e
+                )
+            })?;
+
+        let data_center = data_centers.get(&node_operator.dc_id).ok_or_else(|| {
+            format!(
+                "Data center {} of node operator {} is not present in the registry",
+                node_operator.dc_id, node_operator_id
+            )
+        })?;
+        let rates = rewards_table
+            .table
+            .get(&data_center.region)
+            .ok_or_else(|| {
+                format!(
+                    "The node rewards table has no entry for region {} (data center {})",
+                    data_center.region, data_center.id
+                )
+            })?;
+
+        let mut node_operator_reward = 0u64;
+        for (node_type, node_count) in &node_operator.rewardable_nodes {
+            let rate = rates.rates.get(node_type).ok_or_else(|| {
+                format!(
+                    "The node rewards table has no rate for node type {} in region {}",
+                    node_type, data_center.region
+                )
+            })?;
+            node_operator_reward = rate
+                .xdr_permyriad_per_node_per_month
+                .checked_mul(*node_count as u64)
+                .and_then(|reward| node_operator_reward.checked_add(reward))
+                .ok_or_else(|| {
+                    format!(
+                        "Overflow when computing the rewards of node operator {}",
+                        node_operator_id
+                    )
+                })?;
+        }
+
+        let node_provider_reward = rewards.entry(node_provider_id.to_string()).or_insert(0);
+        *node_provider_reward = node_provider_reward
+            .checked_add(node_operator_reward)
+            .ok_or_else(|| {
+                format!(
+                    "Overflow when computing the rewards of node provider {}",
+                    node_provider_id
+                )
+            })?;
+    }
+
+    Ok(NodeProvidersMonthlyXdrRewards { rewards })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_protobuf::registry::node_rewards::v2::{NodeRewardRate, NodeRewardRates};
+    use maplit::btreemap;
+
+    fn node_operator(
+        operator: u64,
+        provider: u64,
+        dc_id: &str,
+        rewardable_nodes: BTreeMap<String, u32>,
+    ) -> NodeOperatorRecord {
+        NodeOperatorRecord {
+            node_operator_principal_id: PrincipalId::new_user_test_id(operator).to_vec(),
+            node_allowance: 0,
+            node_provider_principal_id: PrincipalId::new_user_test_id(provider).to_vec(),
+            dc_id: dc_id.to_string(),
+            rewardable_nodes,
+        }
+    }
+
+    fn data_center(id: &str, region: &str) -> (String, DataCenterRecord) {
+        (
+            id.to_string(),
+            DataCenterRecord {
+                id: id.to_string(),
+                region: region.to_string(),
+                owner: "owner".to_string(),
+                gps: None,
+            },
+        )
+    }
+
+    fn rewards_table() -> NodeRewardsTable {
+        NodeRewardsTable {
+            table: btreemap! {
+                "EU".to_string() => NodeRewardRates {
+                    rates: btreemap! {
+                        "default".to_string() => NodeRewardRate {
+                            xdr_permyriad_per_node_per_month: 24_000,
+                        },
+                        "small".to_string() => NodeRewardRate {
+                            xdr_permyriad_per_node_per_month: 35_000,
+                        },
+                    }
+                },
+                "North America".to_string() => NodeRewardRates {
+                    rates: btreemap! {
+                        "default".to_string() => NodeRewardRate {
+                            xdr_permyriad_per_node_per_month: 67_000,
+                        },
+                    }
+                },
+            },
+        }
+    }
+
+    #[test]
+    fn rewards_are_summed_per_node_provider() {
+        let node_operators = vec![
+            node_operator(
+                1,
+                100,
+                "zh1",
+                btreemap! { "default".to_string() => 2, "small".to_string() => 1 },
+            ),
+            node_operator(2, 100, "sf1", btreemap! { "default".to_string() => 3 }),
+            node_operator(3, 200, "zh1", btreemap! { "default".to_string() => 1 }),
+            // Node operators without rewardable nodes are ignored.
+            node_operator(4, 300, "unknown", BTreeMap::new()),
+        ];
+        let data_centers = vec![
+            data_center("zh1", "EU"),
+            data_center("sf1", "North America"),
+        ]
+        .into_iter()
+        .collect();
+
+        let rewards = calculate_rewards(&rewards_table(), &node_operators, &data_centers).unwrap();
+
+        assert_eq!(
+            rewards.rewards,
+            btreemap! {
+                PrincipalId::new_user_test_id(100).to_string() => 2 * 24_000 + 35_000 + 3 * 67_000,
+                PrincipalId::new_user_test_id(200).to_string() => 24_000,
+            }
+        );
+    }
+
+    #[test]
+    fn rewards_fail_for_unknown_region_or_node_type() {
+        let data_centers: BTreeMap<String, DataCenterRecord> =
+            vec![data_center("zh1", "EU"), data_center("tk1", "Asia")]
+                .into_iter()
+                .collect();
+
+        let unknown_region = vec![node_operator(
+            1,
+            100,
+            "tk1",
+            btreemap! { "default".to_string() => 1 },
+        )];
+        assert!(calculate_rewards(&rewards_table(), &unknown_region, &data_centers).is_err());
+
+        let unknown_node_type = vec![node_operator(
+            1,
+            100,
+            "zh1",
+            btreemap! { "storage_upgrade".to_string() => 1 },
+        )];
+        assert!(calculate_rewards(&rewards_table(), &unknown_node_type, &data_centers).is_err());
+
+        let unknown_data_center = vec![node_operator(
+            1,
+            100,
+            "sf1",
+            btreemap! { "default".to_string() => 1 },
+        )];
+        assert!(calculate_rewards(&rewards_table(), &unknown_data_center, &data_centers).is_err());
+    }
+}
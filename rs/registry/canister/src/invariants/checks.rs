@@ -1,10 +1,12 @@
 use crate::{
     common::LOG_PREFIX,
     invariants::{
-        common::RegistrySnapshot, endpoint::check_endpoint_invariants,
+        common::{InvariantCheckError, RegistrySnapshot},
+        endpoint::check_endpoint_invariants,
         node_operator::check_node_operator_invariants,
         replica_version::check_replica_version_invariants,
-        routing_table::check_routing_table_invariants, subnet::check_subnet_invariants,
+        routing_table::check_routing_table_invariants,
+        subnet::check_subnet_invariants,
     },
     mutations::common::decode_registry_value,
     registry::Registry,
@@ -18,10 +20,23 @@ impl Registry {
     pub fn check_global_invariants(&self, mutations: &[RegistryMutation]) {
         println!("{}check_global_invariants: {:?}", LOG_PREFIX, mutations);
 
-        let snapshot = self.take_latest_snapshot_with_mutations(mutations);
+        let violations = self.global_invariant_violations(mutations);
+        if !violations.is_empty() {
+            panic!(
+                "{} invariant check failed with message:{}",
+                LOG_PREFIX,
+                violations.join("; ")
+            );
+        }
+    }
 
-        // Conversion Rate invariants
-        self.check_conversion_rate_invariants(&snapshot);
+    /// Returns the messages of the global invariants that would be violated
+    /// if the mutations were applied, without applying them.
+    ///
+    /// Checks that cannot make sense of the resulting registry, e.g. because a
+    /// subnet in the subnet list has no record, still panic.
+    pub fn global_invariant_violations(&self, mutations: &[RegistryMutation]) -> Vec<String> {
+        let snapshot = self.take_latest_snapshot_with_mutations(mutations);
 
         // Node invariants
         // TODO(NNS1-202): re-enable this check when cd hourly test issues are sorted
@@ -32,33 +47,33 @@ impl Registry {
         //    println!("{}check_node_crypto_keys_invariants: {}", LOG_PREFIX, e)
         // }
 
-        // Node Operator invariants
-        let mut result = check_node_operator_invariants(&snapshot, false);
-
-        // Routing Table invariants
-        result = result.and(check_routing_table_invariants(&snapshot));
-
-        // Subnet invariants
-        result = result.and(check_subnet_invariants(&snapshot));
-
-        // Replica version invariants
-        result = result.and(check_replica_version_invariants(&snapshot, false));
-
-        // Endpoint invariants
-        result = result.and(check_endpoint_invariants(&snapshot, false));
-
-        if let Err(e) = result {
-            panic!(
-                "{} invariant check failed with message:{}",
-                LOG_PREFIX, e.msg
-            );
-        }
+        vec![
+            // Conversion Rate invariants
+            self.check_conversion_rate_invariants(&snapshot),
+            // Node Operator invariants
+            check_node_operator_invariants(&snapshot, false),
+            // Routing Table invariants
+            check_routing_table_invariants(&snapshot),
+            // Subnet invariants
+            check_subnet_invariants(&snapshot),
+            // Replica version invariants
+            check_replica_version_invariants(&snapshot, false),
+            // Endpoint invariants
+            check_endpoint_invariants(&snapshot, false),
+        ]
+        .into_iter()
+        .flat_map(Result::err)
+        .map(|e| e.msg)
+        .collect()
     }
 
     /// If there is a proposal for a new conversion rate, the function makes
     /// sure that the timestamp of the proposed conversion rate record is
     /// larger than the current timestamp in the current record.
-    fn check_conversion_rate_invariants(&self, snapshot: &RegistrySnapshot) {
+    fn check_conversion_rate_invariants(
+        &self,
+        snapshot: &RegistrySnapshot,
+    ) -> Result<(), InvariantCheckError> {
         // Check if there is a conversion rate in the mutated snapshot:
         if let Some(proposed_conversion_rate_bytes) =
             snapshot.get(&make_icp_xdr_conversion_rate_record_key().into_bytes())
@@ -67,9 +82,14 @@ impl Registry {
             let proposed_conversion_rate = decode_registry_value::<IcpXdrConversionRateRecord>(
                 proposed_conversion_rate_bytes.clone(),
             );
-            // Assert that the rate is positive (this is an additional sanity check as the
+            // Check that the rate is positive (this is an additional sanity check as the
             // rate should always be at least `minimum_icp_xdr_rate`):
-            assert!(proposed_conversion_rate.xdr_permyriad_per_icp > 0);
+            if proposed_conversion_rate.xdr_permyriad_per_icp == 0 {
+                return Err(InvariantCheckError {
+                    msg: "The ICP/XDR conversion rate must be positive".to_string(),
+                    source: None,
+                });
+            }
             // Check if there is a conversion rate in the registry (without mutations):
             if let Some(conversion_rate_bytes) = self.get(
                 &make_icp_xdr_conversion_rate_record_key().into_bytes(),
@@ -79,15 +99,25 @@ impl Registry {
                 let conversion_rate = decode_registry_value::<IcpXdrConversionRateRecord>(
                     conversion_rate_bytes.clone().value,
                 );
-                // Assert that the records are equal, i.e., there is no mutation, or the
+                // Check that the records are equal, i.e., there is no mutation, or the
                 // timestamp is larger in the proposed conversion rate:
-                assert!(
-                    proposed_conversion_rate == conversion_rate
-                        || proposed_conversion_rate.timestamp_seconds
-                            > conversion_rate.timestamp_seconds
-                );
+                if proposed_conversion_rate != conversion_rate
+                    && proposed_conversion_rate.timestamp_seconds
+                        <= conversion_rate.timestamp_seconds
+                {
+                    return Err(InvariantCheckError {
+                        msg: format!(
+                            "The timestamp of the proposed ICP/XDR conversion rate ({}) is not \
+                            larger than the timestamp of the current one ({})",
+                            proposed_conversion_rate.timestamp_seconds,
+                            conversion_rate.timestamp_seconds
+                        ),
+                        source: None,
+                    });
+                }
             }
         }
+        Ok(())
     }
 
     fn take_latest_snapshot_with_mutations(
@@ -132,7 +162,7 @@ mod tests {
         node_operator::v1::NodeOperatorRecord, routing_table::v1::RoutingTable,
     };
     use ic_registry_keys::{make_node_operator_record_key, make_routing_table_record_key};
-    use ic_registry_transport::{delete, insert, pb::v1::RegistryMutation};
+    use ic_registry_transport::{delete, insert, pb::v1::RegistryMutation, upsert};
     use std::collections::BTreeMap;
 
     /// Shorthand to try a mutation
@@ -237,7 +267,7 @@ mod tests {
         )]);
         // The conversion rate invariants should be satisfied because there is no record
         // in the registry:
-        registry.check_conversion_rate_invariants(&snapshot);
+        assert!(registry.check_conversion_rate_invariants(&snapshot).is_ok());
 
         // Manually add an initial rate with a smaller timestamp:
         let initial_conversion_rate = IcpXdrConversionRateRecord {
@@ -249,7 +279,7 @@ mod tests {
             encode_or_panic::<IcpXdrConversionRateRecord>(&initial_conversion_rate),
         )]);
         // The conversion rate invariants should still be satisfied:
-        registry.check_conversion_rate_invariants(&snapshot);
+        assert!(registry.check_conversion_rate_invariants(&snapshot).is_ok());
     }
 
     #[test]
@@ -257,7 +287,6 @@ mod tests {
     /// invariants check if the proposal contains a timestamp that is
     /// smaller than the timestamp of the conversion rate record in the
     /// registry.
-    #[should_panic]
     fn conversion_rate_invariant_invalid_timestamp() {
         // Create a valid registry:
         let mut registry = create_valid_registry();
@@ -282,7 +311,9 @@ mod tests {
         )]);
         // The conversion rate invariants should not be satisfied because the timestamp
         // in the proposal is smaller:
-        registry.check_conversion_rate_invariants(&snapshot);
+        assert!(registry
+            .check_conversion_rate_invariants(&snapshot)
+            .is_err());
     }
 
     #[test]
@@ -308,4 +339,48 @@ mod tests {
             encode_or_panic(&RoutingTable::default()),
         )]);
     }
+
+    #[test]
+    /// The test ensures that a dry run reports the invariant violations of
+    /// mutations without applying them.
+    fn dry_run_reports_violations_without_applying_mutations() {
+        // Create a valid registry with a conversion rate:
+        let mut registry = create_valid_registry();
+        registry.maybe_apply_mutation_internal(vec![insert(
+            &make_icp_xdr_conversion_rate_record_key().into_bytes(),
+            encode_or_panic::<IcpXdrConversionRateRecord>(&IcpXdrConversionRateRecord {
+                timestamp_seconds: 1000000,
+                xdr_permyriad_per_icp: 2000000,
+            }),
+        )]);
+        let version = registry.latest_version();
+
+        // A conversion rate with a smaller timestamp violates the invariants:
+        let mutations = vec![upsert(
+            &make_icp_xdr_conversion_rate_record_key().into_bytes(),
+            encode_or_panic::<IcpXdrConversionRateRecord>(&IcpXdrConversionRateRecord {
+                timestamp_seconds: 999999,
+                xdr_permyriad_per_icp: 2000000,
+            }),
+        )];
+        let violations = registry.dry_run_mutations(&mutations);
+        assert_eq!(violations.len(), 1);
+        assert!(violations[0].contains("is not larger than the timestamp"));
+
+        // A mutation with an unsatisfied precondition is reported as well:
+        let violations = registry
+            .dry_run_mutations(&[delete(make_node_operator_record_key(*TEST_USER1_PRINCIPAL))]);
+        assert_eq!(violations.len(), 1);
+
+        // A valid mutation has no violations:
+        assert!(registry
+            .dry_run_mutations(&[upsert(
+                make_routing_table_record_key(),
+                encode_or_panic(&RoutingTable::default()),
+            )])
+            .is_empty());
+
+        // Nothing was applied:
+        assert_eq!(registry.latest_version(), version);
+    }
 }
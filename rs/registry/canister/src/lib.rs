@@ -1,5 +1,6 @@
 pub mod certification;
 pub mod common;
+pub mod get_node_providers_monthly_xdr_rewards;
 pub mod init;
 mod invariants;
 pub mod mutations;
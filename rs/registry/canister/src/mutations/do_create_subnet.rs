@@ -46,44 +46,9 @@ impl Registry {
     pub async fn do_create_subnet(&mut self, payload: CreateSubnetPayload) {
         println!("{}do_create_subnet: {:?}", LOG_PREFIX, payload);
 
-        let node_ids_hash_set: HashSet<NodeId> = payload.node_ids.iter().cloned().collect();
-
-        // Verify that all Nodes exist
-        payload.node_ids.iter().for_each(|node_id| {
-            match self.get(
-                make_node_record_key(*node_id).as_bytes(),
-                self.latest_version(),
-            ) {
-                Some(RegistryValue {
-                    value,
-                    version: _,
-                    deletion_marker: _,
-                }) => assert_ne!(
-                    decode_registry_value::<NodeRecord>(value.clone()),
-                    NodeRecord::default()
-                ),
-                None => panic!("A NodeRecord for Node with id {} was not found", node_id),
-            };
-        });
-
-        // Ensure that none of the Nodes are part of another Subnet
-        let mut subnet_members: HashSet<NodeId> = HashSet::new();
-        self.get_subnet_list_record()
-            .subnets
-            .iter()
-            .map(|s| SubnetId::from(PrincipalId::try_from(s).unwrap()))
-            .for_each(|subnet_id| {
-                let subnet_record = self.get_subnet_or_panic(subnet_id);
-                subnet_record.membership.iter().for_each(|v| {
-                    subnet_members.insert(NodeId::from(PrincipalId::try_from(v).unwrap()));
-                });
-            });
-        let intersection = subnet_members
-            .intersection(&node_ids_hash_set)
-            .copied()
-            .collect::<HashSet<_>>();
-        if !intersection.is_empty() {
-            panic!("Some Nodes are already members of Subnets");
+        let errors = self.check_create_subnet_preconditions(&payload);
+        if !errors.is_empty() {
+            panic!("{}do_create_subnet: {}", LOG_PREFIX, errors.join("; "));
         }
 
         // The steps are now:
@@ -139,11 +104,114 @@ impl Registry {
             value: encode_or_panic(&response.subnet_threshold_public_key),
         };
 
-        let subnet_record: SubnetRecord = payload.into();
-
         // 4. Update registry with the new subnet data
         // The subnet data is the new subnet record plus the update to the global
         // subnet list.
+        let mut mutations = self.make_create_subnet_mutations(payload, subnet_id);
+        mutations.push(new_subnet_dkg);
+        mutations.push(new_subnet_threshold_signing_pubkey);
+
+        // Check invariants before applying mutations
+        self.maybe_apply_mutation_internal(mutations);
+    }
+
+    /// Returns the invariant violations that `do_create_subnet` would run
+    /// into with the given payload, without changing the registry.
+    ///
+    /// The NI-DKG is not run, so the catch-up package contents and the
+    /// threshold signing public key of the new subnet are not checked. If the
+    /// payload does not override the subnet id, a placeholder id is used.
+    pub fn dry_run_create_subnet(&self, payload: CreateSubnetPayload) -> Vec<String> {
+        println!("{}dry_run_create_subnet: {:?}", LOG_PREFIX, payload);
+
+        let errors = self.check_create_subnet_preconditions(&payload);
+        if !errors.is_empty() {
+            return errors;
+        }
+
+        let subnet_id = SubnetId::new(
+            payload
+                .subnet_id_override
+                .unwrap_or_else(|| PrincipalId::new_self_authenticating(b"dry_run_create_subnet")),
+        );
+        let mutations = self.make_create_subnet_mutations(payload, subnet_id);
+        self.dry_run_mutations(&mutations)
+    }
+
+    /// Returns the reasons why the subnet cannot be created: some of its
+    /// nodes do not exist or already belong to a subnet, or its id is taken.
+    fn check_create_subnet_preconditions(&self, payload: &CreateSubnetPayload) -> Vec<String> {
+        let mut errors = vec![];
+        let node_ids_hash_set: HashSet<NodeId> = payload.node_ids.iter().cloned().collect();
+
+        // Verify that all Nodes exist
+        payload.node_ids.iter().for_each(|node_id| {
+            match self.get(
+                make_node_record_key(*node_id).as_bytes(),
+                self.latest_version(),
+            ) {
+                Some(RegistryValue {
+                    value,
+                    version: _,
+                    deletion_marker: _,
+                }) => {
+                    if decode_registry_value::<NodeRecord>(value.clone()) == NodeRecord::default() {
+                        errors.push(format!(
+                            "The NodeRecord for Node with id {} is empty",
+                            node_id
+                        ));
+                    }
+                }
+                None => errors.push(format!(
+                    "A NodeRecord for Node with id {} was not found",
+                    node_id
+                )),
+            };
+        });
+
+        // Ensure that none of the Nodes are part of another Subnet
+        let mut subnet_members: HashSet<NodeId> = HashSet::new();
+        let subnet_ids: Vec<SubnetId> = self
+            .get_subnet_list_record()
+            .subnets
+            .iter()
+            .map(|s| SubnetId::from(PrincipalId::try_from(s).unwrap()))
+            .collect();
+        subnet_ids.iter().for_each(|subnet_id| {
+            let subnet_record = self.get_subnet_or_panic(*subnet_id);
+            subnet_record.membership.iter().for_each(|v| {
+                subnet_members.insert(NodeId::from(PrincipalId::try_from(v).unwrap()));
+            });
+        });
+        let intersection = subnet_members
+            .intersection(&node_ids_hash_set)
+            .copied()
+            .collect::<HashSet<_>>();
+        if !intersection.is_empty() {
+            errors.push("Some Nodes are already members of Subnets".to_string());
+        }
+
+        if let Some(subnet_id) = payload.subnet_id_override {
+            if subnet_ids.contains(&SubnetId::new(subnet_id)) {
+                errors.push(format!(
+                    "Subnet already present in subnet list record: {}",
+                    subnet_id
+                ));
+            }
+        }
+
+        errors
+    }
+
+    /// Returns the mutations that add the subnet record of the new subnet and
+    /// add it to the subnet list and to the routing table.
+    fn make_create_subnet_mutations(
+        &self,
+        payload: CreateSubnetPayload,
+        subnet_id: SubnetId,
+    ) -> Vec<RegistryMutation> {
+        let subnet_record: SubnetRecord = payload.into();
+
         let mut subnet_list_record = self.get_subnet_list_record();
         if subnet_list_record
             .subnets
@@ -172,16 +240,7 @@ impl Registry {
         let routing_table_mutation =
             self.add_subnet_to_routing_table(self.latest_version(), subnet_id);
 
-        let mutations = vec![
-            subnet_list_mutation,
-            new_subnet,
-            new_subnet_dkg,
-            new_subnet_threshold_signing_pubkey,
-            routing_table_mutation,
-        ];
-
-        // Check invariants before applying mutations
-        self.maybe_apply_mutation_internal(mutations);
+        vec![subnet_list_mutation, new_subnet, routing_table_mutation]
     }
 }
 
@@ -0,0 +1,252 @@
+use crate::{
+    common::LOG_PREFIX,
+    mutations::common::{decode_registry_value, encode_or_panic},
+    registry::Registry,
+};
+
+use candid::{CandidType, Deserialize};
+#[cfg(target_arch = "wasm32")]
+use dfn_core::println;
+
+use ic_base_types::NodeId;
+use ic_crypto_node_key_validation::ValidNodePublicKeys;
+use ic_protobuf::{
+    crypto::v1::NodePublicKeys,
+    registry::crypto::v1::{PublicKey, X509PublicKeyCert},
+};
+use ic_registry_keys::{make_crypto_node_key, make_crypto_tls_cert_key, make_node_record_key};
+use ic_registry_transport::{pb::v1::RegistryValue, update};
+use ic_types::crypto::KeyPurpose;
+
+use prost::Message;
+
+impl Registry {
+    /// Replaces the committee signing key, the DKG dealing encryption key and
+    /// the TLS certificate of a node with rotated ones.
+    ///
+    /// This method is called directly by the node whose keys are rotated. The
+    /// node signing key cannot be rotated, as the node ID is derived from it.
+    pub fn do_update_node_public_keys(
+        &mut self,
+        payload: UpdateNodePublicKeysPayload,
+    ) -> Result<(), String> {
+        println!("{}do_update_node_public_keys: {:?}", LOG_PREFIX, payload);
+
+        // 1. Check that the caller is the node itself
+        let caller = dfn_core::api::caller();
+        if caller != payload.node_id.get() {
+            return Err(format!(
+                "{}do_update_node_public_keys: The caller {} is not the node {}.",
+                LOG_PREFIX, caller, payload.node_id
+            ));
+        }
+
+        // 2. Check that the node exists and get its node signing key
+        if self
+            .get(
+                make_node_record_key(payload.node_id).as_bytes(),
+                self.latest_version(),
+            )
+            .is_none()
+        {
+            return Err(format!(
+                "{}do_update_node_public_keys: Node Id {} not found in the registry.",
+                LOG_PREFIX, payload.node_id
+            ));
+        }
+        let node_signing_key = make_crypto_node_key(payload.node_id, KeyPurpose::NodeSigning);
+        let RegistryValue {
+            value: node_signing_pk,
+            version: _,
+            deletion_marker: _,
+        } = self
+            .get(node_signing_key.as_bytes(), self.latest_version())
+            .ok_or_else(|| {
+                format!(
+                    "{}do_update_node_public_keys: Node signing key of node {} not found in the registry.",
+                    LOG_PREFIX, payload.node_id
+                )
+            })?;
+        let node_signing_pk = decode_registry_value::<PublicKey>(node_signing_pk.clone());
+
+        // 3. Validate the rotated keys
+        let valid_pks = valid_rotated_keys_from_payload(&payload, node_signing_pk)?;
+
+        // 4. Replace the keys
+        let mutations = vec![
+            update(
+                make_crypto_node_key(payload.node_id, KeyPurpose::CommitteeSigning)
+                    .as_bytes()
+                    .to_vec(),
+                encode_or_panic(valid_pks.committee_signing_key()),
+            ),
+            update(
+                make_crypto_node_key(payload.node_id, KeyPurpose::DkgDealingEncryption)
+                    .as_bytes()
+                    .to_vec(),
+                encode_or_panic(valid_pks.dkg_dealing_encryption_key()),
+            ),
+            update(
+                make_crypto_tls_cert_key(payload.node_id)
+                    .as_bytes()
+                    .to_vec(),
+                encode_or_panic(valid_pks.tls_certificate()),
+            ),
+        ];
+
+        // Check invariants before applying mutations
+        self.maybe_apply_mutation_internal(mutations);
+
+        Ok(())
+    }
+}
+
+/// The payload of an update request to replace the public keys of a node with
+/// rotated ones.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct UpdateNodePublicKeysPayload {
+    /// The node whose keys are rotated, which must be the caller.
+    pub node_id: NodeId,
+    // Raw bytes of the protobuf, but these should be PublicKey
+    pub committee_signing_pk: Vec<u8>,
+    pub ni_dkg_dealing_encryption_pk: Vec<u8>,
+    // Raw bytes of the protobuf, but this should be X509PublicKeyCert
+    pub transport_tls_cert: Vec<u8>,
+}
+
+/// Validates the rotated keys in the payload together with the node's
+/// registered node signing key.
+fn valid_rotated_keys_from_payload(
+    payload: &UpdateNodePublicKeysPayload,
+    node_signing_pk: PublicKey,
+) -> Result<ValidNodePublicKeys, String> {
+    let committee_signing_pk =
+        PublicKey::decode(&payload.committee_signing_pk[..]).map_err(|e| {
+            format!(
+                "committee_signing_pk is not in the expected format: {:?}",
+                e
+            )
+        })?;
+    let dkg_dealing_encryption_pk = PublicKey::decode(&payload.ni_dkg_dealing_encryption_pk[..])
+        .map_err(|e| {
+            format!(
+                "ni_dkg_dealing_encryption_pk is not in the expected format: {:?}",
+                e
+            )
+        })?;
+    let tls_certificate = X509PublicKeyCert::decode(&payload.transport_tls_cert[..])
+        .map_err(|e| format!("transport_tls_cert is not in the expected format: {:?}", e))?;
+
+    let node_pks = NodePublicKeys {
+        version: 1, // irrelevant
+        node_signing_pk: Some(node_signing_pk),
+        committee_signing_pk: Some(committee_signing_pk),
+        tls_certificate: Some(tls_certificate),
+        dkg_dealing_encryption_pk: Some(dkg_dealing_encryption_pk),
+    };
+
+    ValidNodePublicKeys::try_from(&node_pks, payload.node_id)
+        .map_err(|e| format!("Could not validate public keys, due to {:?}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_config::crypto::CryptoConfig;
+    use ic_crypto::utils::{get_node_keys_or_generate_if_missing, rotate_node_keys};
+    use lazy_static::lazy_static;
+
+    #[derive(Clone)]
+    struct TestData {
+        node_id: NodeId,
+        node_pks: NodePublicKeys,
+        rotated_pks: NodePublicKeys,
+    }
+
+    impl TestData {
+        fn new() -> Self {
+            CryptoConfig::run_with_temp_config(|config| {
+                let (node_pks, node_id) = get_node_keys_or_generate_if_missing(&config.crypto_root);
+                let rotated_pks = rotate_node_keys(&config, node_id);
+                Self {
+                    node_id,
+                    node_pks,
+                    rotated_pks,
+                }
+            })
+        }
+    }
+
+    // This is to avoid calling the expensive key generation operation for every
+    // test.
+    lazy_static! {
+        static ref TEST_DATA: TestData = TestData::new();
+    }
+
+    fn protobuf_to_vec<M: Message>(entry: &M) -> Vec<u8> {
+        let mut buf: Vec<u8> = Vec::new();
+        entry.encode(&mut buf).expect("This must not fail");
+        buf
+    }
+
+    fn payload_for(node_id: NodeId, node_pks: &NodePublicKeys) -> UpdateNodePublicKeysPayload {
+        UpdateNodePublicKeysPayload {
+            node_id,
+            committee_signing_pk: protobuf_to_vec(node_pks.committee_signing_pk.as_ref().unwrap()),
+            ni_dkg_dealing_encryption_pk: protobuf_to_vec(
+                node_pks.dkg_dealing_encryption_pk.as_ref().unwrap(),
+            ),
+            transport_tls_cert: protobuf_to_vec(node_pks.tls_certificate.as_ref().unwrap()),
+        }
+    }
+
+    fn registered_node_signing_pk() -> PublicKey {
+        TEST_DATA.node_pks.node_signing_pk.clone().unwrap()
+    }
+
+    #[test]
+    fn rotated_keys_are_valid() {
+        let payload = payload_for(TEST_DATA.node_id, &TEST_DATA.rotated_pks);
+
+        let valid_pks =
+            valid_rotated_keys_from_payload(&payload, registered_node_signing_pk()).unwrap();
+
+        assert_eq!(valid_pks.node_id(), TEST_DATA.node_id);
+        assert_eq!(
+            Some(valid_pks.committee_signing_key()),
+            TEST_DATA.rotated_pks.committee_signing_pk.as_ref()
+        );
+        assert_eq!(
+            Some(valid_pks.dkg_dealing_encryption_key()),
+            TEST_DATA.rotated_pks.dkg_dealing_encryption_pk.as_ref()
+        );
+        assert_eq!(
+            Some(valid_pks.tls_certificate()),
+            TEST_DATA.rotated_pks.tls_certificate.as_ref()
+        );
+    }
+
+    #[test]
+    fn empty_committee_signing_key_is_detected() {
+        let mut payload = payload_for(TEST_DATA.node_id, &TEST_DATA.rotated_pks);
+        payload.committee_signing_pk = vec![];
+
+        assert!(valid_rotated_keys_from_payload(&payload, registered_node_signing_pk()).is_err());
+    }
+
+    #[test]
+    fn corrupted_dkg_dealing_key_is_detected() {
+        let mut payload = payload_for(TEST_DATA.node_id, &TEST_DATA.rotated_pks);
+        payload.ni_dkg_dealing_encryption_pk.push(42);
+
+        assert!(valid_rotated_keys_from_payload(&payload, registered_node_signing_pk()).is_err());
+    }
+
+    #[test]
+    fn keys_of_another_node_are_detected() {
+        let other_node_id = NodeId::from(ic_base_types::PrincipalId::new_node_test_id(42));
+        let payload = payload_for(other_node_id, &TEST_DATA.rotated_pks);
+
+        assert!(valid_rotated_keys_from_payload(&payload, registered_node_signing_pk()).is_err());
+    }
+}
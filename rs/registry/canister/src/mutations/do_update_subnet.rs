@@ -19,19 +19,32 @@ impl Registry {
     pub fn do_update_subnet(&mut self, payload: UpdateSubnetPayload) {
         println!("{}do_update_subnet: {:?}", LOG_PREFIX, payload);
 
+        let mutations = self.make_update_subnet_mutations(payload);
+
+        // Check invariants before applying mutations
+        self.maybe_apply_mutation_internal(mutations);
+    }
+
+    /// Returns the invariant violations that `do_update_subnet` would run
+    /// into with the given payload, without changing the registry.
+    pub fn dry_run_update_subnet(&self, payload: UpdateSubnetPayload) -> Vec<String> {
+        println!("{}dry_run_update_subnet: {:?}", LOG_PREFIX, payload);
+
+        let mutations = self.make_update_subnet_mutations(payload);
+        self.dry_run_mutations(&mutations)
+    }
+
+    fn make_update_subnet_mutations(&self, payload: UpdateSubnetPayload) -> Vec<RegistryMutation> {
         let subnet_id = payload.subnet_id;
 
         let subnet_record = self.get_subnet_or_panic(subnet_id);
 
         let new_subnet_record = merge_subnet_record(subnet_record, payload);
-        let mutations = vec![RegistryMutation {
+        vec![RegistryMutation {
             mutation_type: registry_mutation::Type::Upsert as i32,
             key: make_subnet_record_key(subnet_id).as_bytes().to_vec(),
             value: encode_or_panic(&new_subnet_record),
-        }];
-
-        // Check invariants before applying mutations
-        self.maybe_apply_mutation_internal(mutations);
+        }]
     }
 }
 
@@ -276,6 +276,17 @@ impl Registry {
         self.apply_mutations(mutations);
     }
 
+    /// Returns the reasons why `maybe_apply_mutation_internal` would reject
+    /// the mutations, without applying them. An empty result means that the
+    /// mutations would be applied.
+    pub fn dry_run_mutations(&self, mutations: &[RegistryMutation]) -> Vec<String> {
+        let errors = self.verify_mutation_type(mutations);
+        if !errors.is_empty() {
+            return errors.iter().map(|e| format!("{}", e)).collect();
+        }
+        self.global_invariant_violations(mutations)
+    }
+
     /// Serializes the registry contents using the specified version of stable
     /// representation.
     fn serializable_form_at(&self, repr_version: ReprVersion) -> RegistryStableStorage {
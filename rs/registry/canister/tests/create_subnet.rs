@@ -1,7 +1,7 @@
 use std::convert::TryFrom;
 
 use candid::Encode;
-use dfn_candid::candid;
+use dfn_candid::{candid, candid_one};
 
 use ic_base_types::{PrincipalId, SubnetId};
 use ic_crypto::utils::get_node_keys_or_generate_if_missing;
@@ -531,3 +531,92 @@ fn test_accepted_proposal_mutates_the_registry_some_subnets_present() {
         Ok(())
     });
 }
+
+#[test]
+fn test_anyone_can_dry_run_the_creation_of_a_subnet() {
+    local_test_on_nns_subnet(|runtime| async move {
+        let (init_mutate, node_ids) = prepare_registry(4);
+        let registry = set_up_registry_canister(
+            &runtime,
+            RegistryCanisterInitPayloadBuilder::new()
+                .push_init_mutate_request(invariant_compliant_mutation_as_atomic_req())
+                .push_init_mutate_request(init_mutate)
+                .build(),
+        )
+        .await;
+
+        let initial_subnet_list_record =
+            get_value::<SubnetListRecord>(&registry, make_subnet_list_record_key().as_bytes())
+                .await;
+
+        let payload = CreateSubnetPayload {
+            node_ids: node_ids.clone(),
+            subnet_id_override: None,
+            ingress_bytes_per_block_soft_cap: 2 * 1024 * 1024,
+            max_ingress_bytes_per_message: 60 * 1024 * 1024,
+            max_block_payload_size: 4 * 1024 * 1024,
+            max_ingress_messages_per_block: 1000,
+            unit_delay_millis: 500,
+            initial_notary_delay_millis: 1500,
+            replica_version_id: "version_42".to_string(),
+            dkg_interval_length: 0,
+            dkg_dealings_per_block: 1,
+            gossip_max_artifact_streams_per_peer: 0,
+            gossip_max_chunk_wait_ms: 0,
+            gossip_max_duplicity: 0,
+            gossip_max_chunk_size: 0,
+            gossip_receive_check_cache_size: 0,
+            gossip_pfn_evaluation_period_ms: 0,
+            gossip_registry_poll_period_ms: 0,
+            gossip_retransmission_request_ms: 0,
+            advert_best_effort_percentage: None,
+            start_as_nns: false,
+            subnet_type: SubnetType::Application,
+            is_halted: false,
+            max_instructions_per_message: 5_000_000_000,
+            max_instructions_per_round: 7_000_000_000,
+            max_instructions_per_install_code: 200_000_000_000,
+            features: SubnetFeatures::default(),
+            max_number_of_canisters: 0,
+            ssh_readonly_access: vec![],
+            ssh_backup_access: vec![],
+        };
+
+        // The creation would not violate any invariant...
+        let violations: Vec<String> = registry
+            .query_("dry_run_create_subnet", candid_one, payload.clone())
+            .await
+            .unwrap();
+        assert_eq!(violations, Vec::<String>::new());
+
+        // ... and the dry run did not create the subnet.
+        let subnet_list_record =
+            get_value::<SubnetListRecord>(&registry, make_subnet_list_record_key().as_bytes())
+                .await;
+        assert_eq!(subnet_list_record, initial_subnet_list_record);
+
+        // Creating a subnet with a node that does not exist is reported,
+        // rather than trapping.
+        let unknown_node_id = NodeId::from(PrincipalId::new_node_test_id(999));
+        let violations: Vec<String> = registry
+            .query_(
+                "dry_run_create_subnet",
+                candid_one,
+                CreateSubnetPayload {
+                    node_ids: vec![node_ids[0], unknown_node_id],
+                    ..payload
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            violations,
+            vec![format!(
+                "A NodeRecord for Node with id {} was not found",
+                unknown_node_id
+            )]
+        );
+
+        Ok(())
+    });
+}
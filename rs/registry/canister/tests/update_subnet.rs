@@ -1,6 +1,6 @@
 use assert_matches::assert_matches;
 use candid::Encode;
-use dfn_candid::candid;
+use dfn_candid::{candid, candid_one};
 use ic_base_types::{PrincipalId, SubnetId};
 use ic_nns_common::registry::encode_or_panic;
 use ic_nns_test_utils::{
@@ -383,3 +383,90 @@ fn test_the_proposals_canister_can_update_a_subnets_configuration() {
         Ok(())
     });
 }
+
+#[test]
+fn test_anyone_can_dry_run_an_update_of_a_subnets_configuration() {
+    local_test_on_nns_subnet(|runtime| async move {
+        let subnet_id = SubnetId::from(PrincipalId::new_subnet_test_id(999));
+        let initial_subnet_record = SubnetRecord {
+            membership: vec![],
+            subnet_type: i32::from(SubnetType::Application),
+            replica_version_id: "version_42".to_string(),
+            ..Default::default()
+        };
+
+        let registry = set_up_registry_canister(
+            &runtime,
+            RegistryCanisterInitPayloadBuilder::new()
+                .push_init_mutate_request(invariant_compliant_mutation_as_atomic_req())
+                .push_init_mutate_request(RegistryAtomicMutateRequest {
+                    mutations: vec![insert(
+                        make_subnet_record_key(subnet_id).as_bytes().to_vec(),
+                        encode_or_panic(&initial_subnet_record),
+                    )],
+                    preconditions: vec![],
+                })
+                .build(),
+        )
+        .await;
+
+        let payload = UpdateSubnetPayload {
+            subnet_id,
+            ingress_bytes_per_block_soft_cap: None,
+            max_ingress_bytes_per_message: None,
+            max_block_payload_size: None,
+            unit_delay_millis: Some(100),
+            initial_notary_delay_millis: None,
+            dkg_interval_length: None,
+            dkg_dealings_per_block: None,
+            max_artifact_streams_per_peer: None,
+            max_chunk_wait_ms: None,
+            max_duplicity: None,
+            max_chunk_size: None,
+            receive_check_cache_size: None,
+            pfn_evaluation_period_ms: None,
+            registry_poll_period_ms: None,
+            retransmission_request_ms: None,
+            advert_best_effort_percentage: None,
+            set_gossip_config_to_default: false,
+            start_as_nns: None,
+            subnet_type: None,
+            is_halted: None,
+            max_instructions_per_message: None,
+            max_instructions_per_round: None,
+            max_instructions_per_install_code: None,
+            features: None,
+            max_number_of_canisters: None,
+            ssh_readonly_access: None,
+            ssh_backup_access: None,
+        };
+
+        // The update would not violate any invariant...
+        let violations: Vec<String> = registry
+            .query_("dry_run_update_subnet", candid_one, payload.clone())
+            .await
+            .unwrap();
+        assert_eq!(violations, Vec::<String>::new());
+
+        // ... and the dry run did not apply it.
+        let subnet_record =
+            get_value::<SubnetRecord>(&registry, make_subnet_record_key(subnet_id).as_bytes())
+                .await;
+        assert_eq!(subnet_record, initial_subnet_record);
+
+        // Updating a subnet that does not exist is rejected.
+        let response: Result<Vec<String>, String> = registry
+            .query_(
+                "dry_run_update_subnet",
+                candid_one,
+                UpdateSubnetPayload {
+                    subnet_id: SubnetId::from(PrincipalId::new_subnet_test_id(1000)),
+                    ..payload
+                },
+            )
+            .await;
+        assert!(response.is_err());
+
+        Ok(())
+    });
+}
@@ -32,6 +32,13 @@ impl RegistryCanister {
         Self::new_with_agent_transformer(url, |a| a.with_query_timeout(t))
     }
 
+    /// Creates a `RegistryCanister` whose agents verify the certificates of
+    /// the NNS subnet against `nns_public_key`, which `atomic_mutate` needs to
+    /// read the replies of its update calls.
+    pub fn new_with_nns_public_key(url: Vec<Url>, nns_public_key: ThresholdSigPublicKey) -> Self {
+        Self::new_with_agent_transformer(url, |a| a.with_root_key(nns_public_key))
+    }
+
     fn new_with_agent_transformer<F>(url: Vec<Url>, f: F) -> Self
     where
         F: FnMut(Agent) -> Agent,
@@ -191,6 +198,9 @@ impl RegistryCanister {
     }
 
     /// Applies 'mutations' to the registry.
+    ///
+    /// Fails unless this `RegistryCanister` was created with
+    /// `new_with_nns_public_key`, as the reply cannot be verified otherwise.
     pub async fn atomic_mutate(
         &self,
         mutations: Vec<RegistryMutation>,
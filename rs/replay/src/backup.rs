@@ -0,0 +1,341 @@
+//! Read access to the consensus backup spool written by the artifact pool
+//! (see `ic_artifact_pool::backup`).
+//!
+//! The spool stores every artifact in a separate protobuf file under
+//!
+//! `<backup_dir>/<subnet_id>/<replica_version>/<group>/<height>/<file>.bin`
+//!
+//! Since a subnet may have been upgraded several times during the backed up
+//! period, artifacts of the same height may be found below several replica
+//! version directories. The spool indexes all height directories it finds
+//! below the given root and merges their contents.
+
+use crate::ReplayError;
+use ic_protobuf::types::v1 as pb;
+use ic_types::{
+    consensus::{Block, BlockProposal, CatchUpPackage, Finalization, HasHeight, RandomTape},
+    Height,
+};
+use prost::Message;
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    fs,
+    path::{Path, PathBuf},
+};
+
+const FINALIZATION_PREFIX: &str = "finalization_";
+const BLOCK_PROPOSAL_PREFIX: &str = "block_proposal_";
+const RANDOM_TAPE_FILE: &str = "random_tape.bin";
+const CATCH_UP_PACKAGE_FILE: &str = "catch_up_package.bin";
+
+/// All artifacts needed to deliver the batch of a single finalized height.
+pub struct FinalizedHeight {
+    /// The finalized block at this height.
+    pub block: Block,
+    /// The random tape used to derive the randomness of the batch.
+    pub random_tape: RandomTape,
+    /// The catch-up package created at this height, if any. Its state hash is
+    /// used to verify the re-computed state.
+    pub catch_up_package: Option<CatchUpPackage>,
+}
+
+/// An index of the height directories of a backup spool.
+pub struct BackupSpool {
+    heights: BTreeMap<Height, Vec<PathBuf>>,
+}
+
+impl BackupSpool {
+    /// Indexes all height directories found below `path`, which can either
+    /// point to the backup directory of a subnet or of a single replica
+    /// version.
+    pub fn open(path: &Path) -> Result<Self, ReplayError> {
+        if !path.is_dir() {
+            return Err(ReplayError::InvalidBackup(format!(
+                "{} is not a directory",
+                path.display()
+            )));
+        }
+        let mut heights = BTreeMap::new();
+        index_height_dirs(path, &mut heights)?;
+        Ok(Self { heights })
+    }
+
+    /// Returns the lowest height found in the spool.
+    pub fn min_height(&self) -> Option<Height> {
+        self.heights.keys().next().cloned()
+    }
+
+    /// Returns the highest height found in the spool.
+    pub fn max_height(&self) -> Option<Height> {
+        self.heights.keys().next_back().cloned()
+    }
+
+    /// Returns the catch-up package stored at the given height, if any.
+    pub fn catch_up_package(&self, height: Height) -> Result<Option<CatchUpPackage>, ReplayError> {
+        match self.find_file(height, |name| name == CATCH_UP_PACKAGE_FILE) {
+            Some(path) => {
+                let proto: pb::CatchUpPackage = read_proto(&path)?;
+                CatchUpPackage::try_from(&proto)
+                    .map(Some)
+                    .map_err(|err| ReplayError::decoding(&path, err))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Loads the finalized block of the given height together with its random
+    /// tape. Returns `Ok(None)` if the spool does not contain a finalized
+    /// block at this height.
+    ///
+    /// The finalized block is identified either through a finalization or
+    /// through a catch-up package of the same height. In both cases, the hash
+    /// of the block is re-computed and compared against the hash referenced by
+    /// the finalization, so that a corrupted spool is detected before the
+    /// block is executed.
+    pub fn finalized_height(&self, height: Height) -> Result<Option<FinalizedHeight>, ReplayError> {
+        if !self.heights.contains_key(&height) {
+            return Ok(None);
+        }
+        let catch_up_package = self.catch_up_package(height)?;
+        let block = match self.finalized_block(height)? {
+            Some(block) => block,
+            None => match &catch_up_package {
+                Some(cup) => cup.content.block.as_ref().clone(),
+                None => return Ok(None),
+            },
+        };
+        let random_tape = match self.find_file(height, |name| name == RANDOM_TAPE_FILE) {
+            Some(path) => {
+                let proto: pb::RandomTape = read_proto(&path)?;
+                RandomTape::try_from(proto).map_err(|err| ReplayError::decoding(&path, err))?
+            }
+            None => {
+                return Err(ReplayError::MissingArtifact {
+                    height,
+                    artifact: "random tape",
+                })
+            }
+        };
+        Ok(Some(FinalizedHeight {
+            block,
+            random_tape,
+            catch_up_package,
+        }))
+    }
+
+    // Returns the block referenced by a finalization of the given height.
+    fn finalized_block(&self, height: Height) -> Result<Option<Block>, ReplayError> {
+        let finalization = match self
+            .find_file(height, |name| name.starts_with(FINALIZATION_PREFIX))
+        {
+            Some(path) => {
+                let proto: pb::Finalization = read_proto(&path)?;
+                Finalization::try_from(proto).map_err(|err| ReplayError::decoding(&path, err))?
+            }
+            None => return Ok(None),
+        };
+        if finalization.height() != height {
+            return Err(ReplayError::InvalidBackup(format!(
+                "finalization stored at height {} has height {}",
+                height,
+                finalization.height()
+            )));
+        }
+        for path in self.files(height) {
+            if !file_name_matches(&path, |name| name.starts_with(BLOCK_PROPOSAL_PREFIX)) {
+                continue;
+            }
+            let proto: pb::BlockProposal = read_proto(&path)?;
+            let proposal =
+                BlockProposal::try_from(proto).map_err(|err| ReplayError::decoding(&path, err))?;
+            if proposal.content.get_hash() != &finalization.content.block {
+                continue;
+            }
+            let block = proposal.content.into_inner();
+            if ic_crypto::crypto_hash(&block) != finalization.content.block {
+                return Err(ReplayError::InvalidBackup(format!(
+                    "block proposal {} does not match the hash of its block",
+                    path.display()
+                )));
+            }
+            return Ok(Some(block));
+        }
+        Err(ReplayError::MissingArtifact {
+            height,
+            artifact: "finalized block proposal",
+        })
+    }
+
+    fn files(&self, height: Height) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = self
+            .heights
+            .get(&height)
+            .into_iter()
+            .flatten()
+            .filter_map(|dir| fs::read_dir(dir).ok())
+            .flatten()
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file())
+            .collect();
+        // Sort the files to keep the replay deterministic.
+        files.sort();
+        files
+    }
+
+    fn find_file<F: Fn(&str) -> bool>(&self, height: Height, predicate: F) -> Option<PathBuf> {
+        self.files(height)
+            .into_iter()
+            .find(|path| file_name_matches(path, &predicate))
+    }
+}
+
+fn file_name_matches<F: Fn(&str) -> bool>(path: &Path, predicate: F) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| name.ends_with(".bin") && predicate(name))
+        .unwrap_or(false)
+}
+
+// Traverses the given directory and collects all directories named after a
+// height which are located in a directory named after a height group.
+fn index_height_dirs(
+    dir: &Path,
+    heights: &mut BTreeMap<Height, Vec<PathBuf>>,
+) -> Result<(), ReplayError> {
+    for entry in fs::read_dir(dir).map_err(|err| ReplayError::io(dir, err))? {
+        let path = entry.map_err(|err| ReplayError::io(dir, err))?.path();
+        if !path.is_dir() {
+            continue;
+        }
+        match (parse_height(&path), parse_height(dir)) {
+            (Some(height), Some(_group)) => heights.entry(height).or_default().push(path),
+            _ => index_height_dirs(&path, heights)?,
+        }
+    }
+    Ok(())
+}
+
+fn parse_height(path: &Path) -> Option<Height> {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.parse::<u64>().ok())
+        .map(Height::from)
+}
+
+fn read_proto<M: Message + Default>(path: &Path) -> Result<M, ReplayError> {
+    let bytes = fs::read(path).map_err(|err| ReplayError::io(path, err))?;
+    M::decode(bytes.as_slice()).map_err(|err| ReplayError::decoding(path, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_test_utilities::{consensus::fake::*, mock_time, types::ids::node_test_id};
+    use ic_types::{
+        batch::{BatchPayload, ValidationContext},
+        consensus::{dkg, BlockPayload, FinalizationContent, Payload, RandomTapeContent, Rank},
+        crypto::{CryptoHash, CryptoHashOf},
+        RegistryVersion,
+    };
+
+    fn fake_block(height: u64) -> Block {
+        Block::new(
+            CryptoHashOf::from(CryptoHash(Vec::new())),
+            Payload::new(
+                ic_crypto::crypto_hash,
+                BlockPayload::from((
+                    BatchPayload::default(),
+                    dkg::Dealings::new_empty(Height::from(0)),
+                )),
+            ),
+            Height::from(height),
+            Rank(0),
+            ValidationContext {
+                registry_version: RegistryVersion::from(1),
+                certified_height: Height::from(0),
+                time: mock_time(),
+            },
+        )
+    }
+
+    fn write_proto<M: Message>(dir: &Path, name: &str, message: M) {
+        fs::create_dir_all(dir).unwrap();
+        let mut buf = Vec::new();
+        message.encode(&mut buf).unwrap();
+        fs::write(dir.join(name), buf).unwrap();
+    }
+
+    // Writes a finalized height in the same layout as the artifact pool backup.
+    fn write_height(root: &Path, height: u64, finalize: bool) -> BlockProposal {
+        let dir = root.join("0").join(height.to_string());
+        let proposal = BlockProposal::fake(fake_block(height), node_test_id(1));
+        let hash = proposal.content.get_hash().clone();
+        write_proto(
+            &dir,
+            &format!("block_proposal_{}.bin", height),
+            pb::BlockProposal::from(&proposal),
+        );
+        if finalize {
+            let finalization =
+                Finalization::fake(FinalizationContent::new(Height::from(height), hash));
+            write_proto(
+                &dir,
+                &format!("finalization_{}.bin", height),
+                pb::Finalization::from(&finalization),
+            );
+        }
+        let tape = RandomTape::fake(RandomTapeContent::new(Height::from(height)));
+        write_proto(&dir, RANDOM_TAPE_FILE, pb::RandomTape::from(&tape));
+        proposal
+    }
+
+    #[test]
+    fn finalized_blocks_are_loaded_across_version_directories() {
+        let tmp = tempfile::tempdir().unwrap();
+        let proposal_1 = write_height(&tmp.path().join("version_a"), 1, true);
+        let proposal_2 = write_height(&tmp.path().join("version_b"), 2, true);
+
+        let spool = BackupSpool::open(tmp.path()).unwrap();
+        assert_eq!(spool.min_height(), Some(Height::from(1)));
+        assert_eq!(spool.max_height(), Some(Height::from(2)));
+
+        for (height, proposal) in &[(1, proposal_1), (2, proposal_2)] {
+            let finalized = spool
+                .finalized_height(Height::from(*height))
+                .unwrap()
+                .unwrap();
+            assert_eq!(
+                ic_crypto::crypto_hash(&finalized.block),
+                *proposal.content.get_hash()
+            );
+            assert_eq!(finalized.random_tape.height(), Height::from(*height));
+            assert!(finalized.catch_up_package.is_none());
+        }
+        assert!(spool.finalized_height(Height::from(3)).unwrap().is_none());
+    }
+
+    #[test]
+    fn heights_without_finalization_are_not_replayed() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_height(tmp.path(), 7, false);
+
+        let spool = BackupSpool::open(tmp.path()).unwrap();
+        assert!(spool.finalized_height(Height::from(7)).unwrap().is_none());
+    }
+
+    #[test]
+    fn missing_random_tape_is_reported() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_height(tmp.path(), 3, true);
+        fs::remove_file(tmp.path().join("0").join("3").join(RANDOM_TAPE_FILE)).unwrap();
+
+        let spool = BackupSpool::open(tmp.path()).unwrap();
+        match spool.finalized_height(Height::from(3)) {
+            Err(ReplayError::MissingArtifact { height, .. }) => {
+                assert_eq!(height, Height::from(3))
+            }
+            _ => panic!("expected a missing random tape"),
+        }
+    }
+}
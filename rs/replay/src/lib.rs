@@ -0,0 +1,241 @@
+//! The replay tool re-executes the finalized blocks of a consensus backup on
+//! top of a checkpoint, without running consensus or networking.
+//!
+//! Starting from the latest checkpoint found in the state root of the given
+//! replica config, the tool reads the finalized blocks spooled by the artifact
+//! pool backup (see `ic_artifact_pool::backup`) height by height and delivers
+//! them as batches to `MessageRouting`. Whenever a catch-up package is found in
+//! the backup, the hash of the re-computed state at its height is compared
+//! against the state hash certified by the catch-up package. The replay stops
+//! at the first divergence and reports it.
+//!
+//! This can be used to recover the state of a subnet after an incident, and
+//! to test a new replica version against real traffic.
+
+use ic_config::{subnet_config::SubnetConfigs, Config, ConfigSource};
+use ic_interfaces::registry::RegistryClient;
+use ic_logger::{info, new_replica_logger, LoggerImpl};
+use ic_metrics::MetricsRegistry;
+use ic_registry_client::{
+    client::{create_data_provider, RegistryClientImpl},
+    helper::subnet::SubnetRegistry,
+};
+use ic_registry_subnet_type::SubnetType;
+use ic_types::{crypto::CryptoHashOfState, Height, PrincipalId, SubnetId};
+use ic_utils::ic_features::*;
+use std::{convert::TryFrom, fmt, path::Path, path::PathBuf, str::FromStr, sync::Arc};
+use structopt::StructOpt;
+
+pub mod backup;
+pub mod player;
+
+use backup::BackupSpool;
+use player::Player;
+
+/// Command line arguments of the replay tool.
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "ic-replay",
+    about = "Replays the finalized blocks of a consensus backup on top of a checkpoint."
+)]
+pub struct ReplayToolArgs {
+    /// The path to the replica config file. Its state root must contain the
+    /// checkpoint to start from and its registry data provider must point to
+    /// a local store covering all registry versions referenced by the replayed
+    /// blocks.
+    #[structopt(long, parse(from_os_str))]
+    pub config: PathBuf,
+
+    /// The backup directory of the subnet, or of a single replica version.
+    #[structopt(long, parse(from_os_str))]
+    pub backup_dir: PathBuf,
+
+    /// The ID of the subnet whose backup is replayed.
+    #[structopt(long)]
+    pub subnet_id: String,
+
+    /// Stop the replay after delivering the batch of this height. Defaults to
+    /// the highest height found in the backup.
+    #[structopt(long)]
+    pub replay_until_height: Option<u64>,
+}
+
+/// The errors that abort a replay before a report could be produced.
+#[derive(Debug)]
+pub enum ReplayError {
+    /// The config could not be loaded or is incomplete.
+    InvalidConfig(String),
+    /// The registry does not contain the expected records.
+    Registry(String),
+    /// The backup directory could not be read.
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    /// A backup artifact could not be decoded.
+    Decoding { path: PathBuf, error: String },
+    /// The backup is inconsistent.
+    InvalidBackup(String),
+    /// An artifact required to deliver the batch of a finalized height is
+    /// missing from the backup.
+    MissingArtifact {
+        height: Height,
+        artifact: &'static str,
+    },
+    /// The batch could not be delivered to message routing.
+    BatchDelivery { height: Height, error: String },
+}
+
+impl ReplayError {
+    pub(crate) fn io(path: &Path, error: std::io::Error) -> Self {
+        Self::Io {
+            path: path.to_path_buf(),
+            error,
+        }
+    }
+
+    pub(crate) fn decoding<E: fmt::Display>(path: &Path, error: E) -> Self {
+        Self::Decoding {
+            path: path.to_path_buf(),
+            error: error.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidConfig(msg) => write!(f, "Invalid config: {}", msg),
+            Self::Registry(msg) => write!(f, "Registry error: {}", msg),
+            Self::Io { path, error } => write!(f, "Failed to read {}: {}", path.display(), error),
+            Self::Decoding { path, error } => {
+                write!(f, "Failed to decode {}: {}", path.display(), error)
+            }
+            Self::InvalidBackup(msg) => write!(f, "Invalid backup: {}", msg),
+            Self::MissingArtifact { height, artifact } => {
+                write!(f, "Missing {} at height {} in the backup", artifact, height)
+            }
+            Self::BatchDelivery { height, error } => write!(
+                f,
+                "Failed to deliver the batch at height {}: {}",
+                height, error
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+/// A state whose re-computed hash differs from the one certified in the
+/// backup.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StateDivergence {
+    pub height: Height,
+    pub certified_hash: CryptoHashOfState,
+    pub computed_hash: CryptoHashOfState,
+}
+
+/// The outcome of a replay.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReplayReport {
+    /// The height of the checkpoint the replay started from.
+    pub checkpoint_height: Height,
+    /// The height of the last batch that was delivered.
+    pub last_replayed_height: Height,
+    /// The heights whose state hash matched the one certified in the backup.
+    pub verified_heights: Vec<Height>,
+    /// The first height whose state hash did not match, if any.
+    pub divergence: Option<StateDivergence>,
+}
+
+impl fmt::Display for ReplayReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Started from checkpoint at height {}",
+            self.checkpoint_height
+        )?;
+        writeln!(
+            f,
+            "Replayed finalized blocks up to height {}",
+            self.last_replayed_height
+        )?;
+        for height in &self.verified_heights {
+            writeln!(f, "State hash at height {} verified", height)?;
+        }
+        match &self.divergence {
+            Some(divergence) => write!(
+                f,
+                "State diverged at height {}: certified hash {:?}, computed hash {:?}",
+                divergence.height, divergence.certified_hash, divergence.computed_hash
+            ),
+            None => write!(f, "No divergence found"),
+        }
+    }
+}
+
+/// Replays the backup described by the given arguments and returns a report.
+pub fn replay(args: ReplayToolArgs) -> Result<ReplayReport, ReplayError> {
+    let subnet_id = SubnetId::from(PrincipalId::from_str(&args.subnet_id).map_err(|err| {
+        ReplayError::InvalidConfig(format!("Invalid subnet id {}: {}", args.subnet_id, err))
+    })?);
+    let spool = BackupSpool::open(&args.backup_dir)?;
+
+    Config::run_with_temp_config(|default_config| {
+        let source = ConfigSource::File(args.config.clone());
+        let cfg = Config::load_with_default(&source, default_config)
+            .map_err(|err| ReplayError::InvalidConfig(err.to_string()))?;
+
+        let base_logger = LoggerImpl::new(&cfg.logger, "replay".to_string());
+        let log = new_replica_logger(base_logger.root.clone(), &cfg.logger);
+
+        let data_provider_config = cfg.registry_client.data_provider.as_ref().ok_or_else(|| {
+            ReplayError::InvalidConfig("No registry data provider configured".to_string())
+        })?;
+        let metrics_registry = MetricsRegistry::global();
+        let registry = Arc::new(RegistryClientImpl::new(
+            create_data_provider(data_provider_config, None),
+            Some(&metrics_registry),
+        ));
+        registry
+            .fetch_and_start_polling()
+            .map_err(|err| ReplayError::Registry(err.to_string()))?;
+
+        let subnet_type = get_subnet_type(registry.as_ref(), subnet_id)?;
+        let subnet_config = SubnetConfigs::default().own_subnet_config(subnet_type);
+        // This mirrors the CoW setup of the replica in `rs/replica/src/main.rs`, so
+        // that the replica and the replay tool of the same version have an
+        // identical behavior wrt. CoW.
+        if subnet_config.cow_memory_manager_config.enabled {
+            cow_state_feature::enable(cow_state_feature::cow_state);
+        } else {
+            cow_state_feature::disable(cow_state_feature::cow_state);
+        }
+
+        let player = Player::new(
+            cfg,
+            subnet_config,
+            subnet_id,
+            subnet_type,
+            registry,
+            &metrics_registry,
+            log.clone(),
+        );
+        let report = player.replay(&spool, args.replay_until_height.map(Height::from))?;
+        info!(log, "Replay finished:\n{}", report);
+        Ok(report)
+    })
+}
+
+fn get_subnet_type(
+    registry: &dyn RegistryClient,
+    subnet_id: SubnetId,
+) -> Result<SubnetType, ReplayError> {
+    let record = registry
+        .get_subnet_record(subnet_id, registry.get_latest_version())
+        .map_err(|err| ReplayError::Registry(err.to_string()))?
+        .ok_or_else(|| {
+            ReplayError::Registry(format!("No subnet record found for subnet {}", subnet_id))
+        })?;
+    SubnetType::try_from(record.subnet_type).map_err(|err| ReplayError::Registry(err.to_string()))
+}
@@ -0,0 +1,24 @@
+use ic_replay::{replay, ReplayToolArgs};
+use structopt::StructOpt;
+
+#[tokio::main]
+async fn main() {
+    let args = ReplayToolArgs::from_args();
+    // The execution stack blocks on the replay, so we move it off the async
+    // runtime which is only needed by the registry client and execution.
+    let result = tokio::task::spawn_blocking(move || replay(args))
+        .await
+        .expect("The replay thread panicked");
+    match result {
+        Ok(report) => {
+            println!("{}", report);
+            if report.divergence.is_some() {
+                std::process::exit(1);
+            }
+        }
+        Err(err) => {
+            eprintln!("Replay failed: {}", err);
+            std::process::exit(2);
+        }
+    }
+}
@@ -0,0 +1,256 @@
+//! The player sets up the execution stack of a replica, i.e. the state
+//! manager, execution and message routing, and delivers the finalized blocks
+//! of a backup spool to it.
+
+use crate::{
+    backup::{BackupSpool, FinalizedHeight},
+    ReplayError, ReplayReport, StateDivergence,
+};
+use ic_config::{subnet_config::SubnetConfig, Config};
+use ic_consensus::consensus::{generate_responses_to_subnet_calls, utils::crypto_hashable_to_seed};
+use ic_cycles_account_manager::CyclesAccountManager;
+use ic_execution_environment::setup_execution;
+use ic_interfaces::{
+    messaging::{MessageRouting, MessageRoutingError},
+    registry::RegistryClient,
+    state_manager::{StateHashError, StateManager, StateReader},
+};
+use ic_logger::{info, warn, ReplicaLogger};
+use ic_messaging::MessageRoutingImpl;
+use ic_metrics::MetricsRegistry;
+use ic_registry_subnet_type::SubnetType;
+use ic_state_manager::StateManagerImpl;
+use ic_test_utilities::consensus::fake::FakeVerifier;
+use ic_types::{
+    batch::{Batch, BatchPayload},
+    consensus::{BlockPayload, CatchUpPackage, HasHeight},
+    crypto::CryptoHashOfState,
+    Height, Randomness, SubnetId,
+};
+use std::{sync::Arc, thread::sleep, time::Duration};
+
+// How long to wait before polling message routing or the state manager again.
+const WAIT_DURATION: Duration = Duration::from_millis(10);
+
+/// Re-executes finalized blocks on top of the latest local checkpoint.
+pub struct Player {
+    state_manager: Arc<StateManagerImpl>,
+    message_routing: MessageRoutingImpl,
+    log: ReplicaLogger,
+}
+
+impl Player {
+    /// Creates the execution stack from the given config. The state manager
+    /// loads the latest checkpoint found in the configured state root.
+    pub fn new(
+        cfg: Config,
+        subnet_config: SubnetConfig,
+        subnet_id: SubnetId,
+        subnet_type: SubnetType,
+        registry: Arc<dyn RegistryClient>,
+        metrics_registry: &MetricsRegistry,
+        log: ReplicaLogger,
+    ) -> Self {
+        let cycles_account_manager = Arc::new(CyclesAccountManager::new(
+            subnet_config.scheduler_config.max_instructions_per_message,
+            cfg.hypervisor.max_cycles_per_canister,
+            subnet_type,
+            subnet_id,
+            subnet_config.cycles_account_manager_config,
+        ));
+        // The verifier is only used to verify certifications of states fetched
+        // through state sync. The player never fetches states, so the fake
+        // verifier saves us from setting up the crypto component of a node.
+        let state_manager = Arc::new(StateManagerImpl::new(
+            Arc::new(FakeVerifier::new()),
+            subnet_id,
+            subnet_type,
+            log.clone(),
+            metrics_registry,
+            &cfg.state_manager,
+            cfg.malicious_behaviour.malicious_flags.clone(),
+        ));
+        let (_, ingress_history_writer, _, _, _, scheduler) = setup_execution(
+            log.clone(),
+            metrics_registry,
+            subnet_id,
+            subnet_type,
+            subnet_config.scheduler_config,
+            cfg.hypervisor.clone(),
+            Arc::clone(&cycles_account_manager),
+            Arc::clone(&state_manager) as Arc<_>,
+        );
+        let message_routing = MessageRoutingImpl::new(
+            Arc::clone(&state_manager) as Arc<_>,
+            Arc::clone(&state_manager) as Arc<_>,
+            ingress_history_writer,
+            scheduler,
+            cfg.hypervisor,
+            cycles_account_manager,
+            subnet_id,
+            metrics_registry,
+            log.clone(),
+            registry,
+        );
+        Self {
+            state_manager,
+            message_routing,
+            log,
+        }
+    }
+
+    /// Delivers all finalized blocks of the spool following the checkpoint,
+    /// up to `target_height` if specified, and verifies the state hashes at
+    /// all heights having a catch-up package in the spool.
+    ///
+    /// The replay stops at the first state divergence, or at the first height
+    /// missing a finalized block.
+    pub fn replay(
+        &self,
+        spool: &BackupSpool,
+        target_height: Option<Height>,
+    ) -> Result<ReplayReport, ReplayError> {
+        let checkpoint_height = self.state_manager.latest_state_height();
+        let mut report = ReplayReport {
+            checkpoint_height,
+            last_replayed_height: checkpoint_height,
+            ..ReplayReport::default()
+        };
+        info!(
+            self.log,
+            "Replaying backup on top of the checkpoint at height {}", checkpoint_height
+        );
+
+        if let Some(cup) = spool.catch_up_package(checkpoint_height)? {
+            if !self.verify_state_hash(&cup, &mut report)? {
+                return Ok(report);
+            }
+        }
+
+        let target_height = match target_height.or_else(|| spool.max_height()) {
+            Some(height) => height,
+            None => return Ok(report),
+        };
+        let mut height = self.message_routing.expected_batch_height();
+        while height <= target_height {
+            let finalized = match spool.finalized_height(height)? {
+                Some(finalized) => finalized,
+                None => {
+                    warn!(
+                        self.log,
+                        "No finalized block found at height {}, stopping the replay", height
+                    );
+                    break;
+                }
+            };
+            let cup = finalized.catch_up_package.clone();
+            self.deliver(height, finalized)?;
+            report.last_replayed_height = height;
+
+            if let Some(cup) = cup {
+                if !self.verify_state_hash(&cup, &mut report)? {
+                    break;
+                }
+            }
+            height = height.increment();
+        }
+        Ok(report)
+    }
+
+    // Builds the batch of the given finalized height the same way consensus
+    // does in `deliver_batches` and waits until the resulting state has been
+    // committed.
+    fn deliver(&self, height: Height, finalized: FinalizedHeight) -> Result<(), ReplayError> {
+        let FinalizedHeight {
+            block, random_tape, ..
+        } = finalized;
+        let mut consensus_responses = Vec::new();
+        let is_summary = block.payload.is_summary();
+        if is_summary {
+            let summary = block.payload.as_ref().as_summary();
+            consensus_responses = generate_responses_to_subnet_calls(
+                &*self.state_manager,
+                block.context.certified_height,
+                summary.dkg.transcripts_for_new_subnets(),
+                block.context.time,
+                &self.log,
+            );
+        }
+        let batch = Batch {
+            batch_number: height,
+            requires_full_state_hash: is_summary,
+            payload: if is_summary {
+                BatchPayload::default()
+            } else {
+                BlockPayload::from(block.payload).into_data().batch
+            },
+            randomness: Randomness::from(crypto_hashable_to_seed(&random_tape)),
+            registry_version: block.context.registry_version,
+            time: block.context.time,
+            consensus_responses,
+        };
+
+        loop {
+            match self.message_routing.deliver_batch(batch.clone()) {
+                Ok(()) => break,
+                Err(MessageRoutingError::QueueIsFull) => sleep(WAIT_DURATION),
+                Err(err) => {
+                    return Err(ReplayError::BatchDelivery {
+                        height,
+                        error: format!("{:?}", err),
+                    })
+                }
+            }
+        }
+        while self.state_manager.latest_state_height() < height {
+            sleep(WAIT_DURATION);
+        }
+        Ok(())
+    }
+
+    // Compares the hash of the local state at the height of the given
+    // catch-up package with the certified one. Returns `false` and records
+    // the divergence in the report if they do not match.
+    fn verify_state_hash(
+        &self,
+        cup: &CatchUpPackage,
+        report: &mut ReplayReport,
+    ) -> Result<bool, ReplayError> {
+        let height = cup.height();
+        let computed_hash = match self.wait_for_state_hash(height) {
+            Some(hash) => hash,
+            None => return Ok(true),
+        };
+        if computed_hash == cup.content.state_hash {
+            info!(self.log, "State hash at height {} verified", height);
+            report.verified_heights.push(height);
+            Ok(true)
+        } else {
+            warn!(self.log, "State diverged at height {}", height);
+            report.divergence = Some(StateDivergence {
+                height,
+                certified_hash: cup.content.state_hash.clone(),
+                computed_hash,
+            });
+            Ok(false)
+        }
+    }
+
+    // Waits until the full state hash at the given height has been computed.
+    // Returns `None` if the state manager will never be able to provide it.
+    fn wait_for_state_hash(&self, height: Height) -> Option<CryptoHashOfState> {
+        loop {
+            match self.state_manager.get_state_hash_at(height) {
+                Ok(hash) => return Some(hash),
+                Err(StateHashError::Transient(_)) => sleep(WAIT_DURATION),
+                Err(StateHashError::Permanent(err)) => {
+                    warn!(
+                        self.log,
+                        "Cannot verify the state hash at height {}: {}", height, err
+                    );
+                    return None;
+                }
+            }
+        }
+    }
+}
@@ -5,15 +5,18 @@ use ic_config::registry_client::DataProviderConfig;
 use ic_config::{subnet_config::SubnetConfigs, Config};
 use ic_crypto_sha::Sha256;
 use ic_crypto_tls_interfaces::TlsHandshake;
-use ic_interfaces::crypto::IngressSigVerifier;
+use ic_interfaces::crypto::{BasicSigner, IngressSigVerifier};
 use ic_interfaces::registry::{LocalStoreCertifiedTimeReader, RegistryClient};
-use ic_logger::info;
+use ic_logger::{info, warn};
 use ic_metrics::MetricsRegistry;
 use ic_metrics_exporter::MetricsRuntimeImpl;
 use ic_registry_client::helper::subnet::SubnetRegistry;
 use ic_replica::{args::ReplicaArgs, setup};
 use ic_sys::PAGE_SIZE;
-use ic_types::{replica_version::REPLICA_BINARY_HASH, PrincipalId, ReplicaVersion, SubnetId};
+use ic_types::{
+    messages::QueryResponseContent, replica_version::REPLICA_BINARY_HASH, PrincipalId,
+    ReplicaVersion, SubnetId,
+};
 use ic_utils::ic_features::*;
 use nix::unistd::{setpgid, Pid};
 use static_assertions::assert_eq_size;
@@ -157,6 +160,16 @@ async fn run() -> io::Result<()> {
     #[cfg(target_os = "linux")]
     metrics_registry.register(jemalloc_metrics::JemallocMetrics::new());
 
+    match ic_tracing::IngressTracer::new(
+        &config.tracing,
+        "replica",
+        logger.clone(),
+        &metrics_registry,
+    ) {
+        Ok(tracer) => ic_tracing::set_global_tracer(tracer),
+        Err(e) => warn!(logger, "Ingress tracing is disabled: {}", e),
+    }
+
     let (registry, crypto) = setup::setup_crypto_registry(
         config.clone(),
         Some(&metrics_registry),
@@ -276,6 +289,7 @@ async fn run() -> io::Result<()> {
         metrics_registry.clone(),
         registry.clone(),
         Arc::clone(&crypto) as Arc<dyn TlsHandshake + Send + Sync>,
+        Some(logger.inner_logger.control().clone()),
         &logger.inner_logger.root,
     );
 
@@ -326,6 +340,8 @@ async fn run() -> io::Result<()> {
         registry,
         Arc::clone(&crypto) as Arc<dyn TlsHandshake + Send + Sync>,
         Arc::clone(&crypto) as Arc<dyn IngressSigVerifier + Send + Sync>,
+        node_id,
+        Arc::clone(&crypto) as Arc<dyn BasicSigner<QueryResponseContent> + Send + Sync>,
         subnet_id,
         root_subnet_id,
         logger.clone(),
@@ -247,6 +247,16 @@ impl CanisterState {
         }
     }
 
+    /// Returns true if the canister exports the `canister_on_low_cycles`
+    /// system method.
+    pub fn exports_on_low_cycles_method(&self) -> bool {
+        match &self.execution_state {
+            Some(execution_state) => execution_state
+                .exports_method(&WasmMethod::System(SystemMethod::CanisterOnLowCycles)),
+            None => false,
+        }
+    }
+
     /// Returns true if the canister contains an exported query method with the
     /// name provided, false otherwise.
     pub fn exports_query_method(&self, method_name: String) -> bool {
@@ -347,6 +347,18 @@ impl CanisterQueues {
         Ok(())
     }
 
+    /// Pops and drops the message at the head of the output queue to
+    /// `own_canister_id`, if any.
+    pub(super) fn discard_message_to_self(&mut self, own_canister_id: CanisterId) {
+        if let Some((_, msg)) = self
+            .output_queues
+            .get_mut(&own_canister_id)
+            .and_then(OutputQueue::pop)
+        {
+            self.memory_usage_stats -= MemoryUsageStats::stats_delta(QueueOp::Pop, &msg);
+        }
+    }
+
     /// Returns the number of enqueued ingress messages.
     pub fn ingress_queue_message_count(&self) -> usize {
         self.ingress_queue.size()
@@ -2,7 +2,7 @@ mod call_context_manager;
 
 pub use super::queues::memory_required_to_push_request;
 use super::{queues::can_push, ENFORCE_MESSAGE_MEMORY_USAGE};
-use crate::{CanisterQueues, Memory, NumWasmPages64, StateError};
+use crate::{CanisterQueues, Memory, NumWasmPages64, StateError, QUEUE_INDEX_NONE};
 pub use call_context_manager::{CallContext, CallContextAction, CallContextManager, CallOrigin};
 use ic_base_types::NumSeconds;
 use ic_interfaces::messages::CanisterInputMessage;
@@ -11,17 +11,24 @@ use ic_protobuf::{
     state::canister_state_bits::v1 as pb,
 };
 use ic_types::{
-    messages::{Ingress, Request, RequestOrResponse, Response, StopCanisterContext},
+    messages::{
+        CallbackId, Ingress, Payload, RejectContext, Request, RequestOrResponse, Response,
+        StopCanisterContext,
+    },
     nominal_cycles::NominalCycles,
+    user_error::RejectCode,
     xnet::QueueId,
-    CanisterId, Cycles, MemoryAllocation, NumBytes, PrincipalId, QueueIndex,
+    CanisterId, Cycles, MemoryAllocation, NumBytes, PrincipalId, QueueIndex, Time,
 };
 use lazy_static::lazy_static;
 use maplit::btreeset;
 use serde::{Deserialize, Serialize};
 use std::convert::{TryFrom, TryInto};
 use std::str::FromStr;
-use std::{collections::BTreeSet, sync::Arc};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
 
 lazy_static! {
     static ref DEFAULT_PRINCIPAL_MULTIPLE_CONTROLLERS: PrincipalId =
@@ -41,6 +48,53 @@ pub struct CanisterMetrics {
     pub executed: u64,
     pub interruped_during_execution: u64,
     pub consumed_cycles_since_replica_started: NominalCycles,
+    /// Cycles consumed since the canister was created, by use case.
+    pub consumed_cycles_by_use_case: BTreeMap<CyclesUseCase, NominalCycles>,
+}
+
+/// The purposes for which a canister consumes cycles.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CyclesUseCase {
+    /// Executing messages, including the cycles reserved for executing the
+    /// responses to outgoing requests.
+    Execution,
+    /// Inducting ingress messages addressed to the canister.
+    IngressInduction,
+    /// Sending requests and receiving responses over the network.
+    Xnet,
+    /// Paying for the memory used or reserved by the canister.
+    Storage,
+    /// Paying for the compute allocation of the canister.
+    ComputeAllocation,
+}
+
+impl From<CyclesUseCase> for pb::CyclesUseCase {
+    fn from(item: CyclesUseCase) -> Self {
+        match item {
+            CyclesUseCase::Execution => pb::CyclesUseCase::Execution,
+            CyclesUseCase::IngressInduction => pb::CyclesUseCase::IngressInduction,
+            CyclesUseCase::Xnet => pb::CyclesUseCase::Xnet,
+            CyclesUseCase::Storage => pb::CyclesUseCase::Storage,
+            CyclesUseCase::ComputeAllocation => pb::CyclesUseCase::ComputeAllocation,
+        }
+    }
+}
+
+impl TryFrom<pb::CyclesUseCase> for CyclesUseCase {
+    type Error = ProxyDecodeError;
+    fn try_from(value: pb::CyclesUseCase) -> Result<Self, Self::Error> {
+        match value {
+            pb::CyclesUseCase::Unspecified => Err(ProxyDecodeError::ValueOutOfRange {
+                typ: "CyclesUseCase",
+                err: "Unspecified".to_string(),
+            }),
+            pb::CyclesUseCase::Execution => Ok(Self::Execution),
+            pb::CyclesUseCase::IngressInduction => Ok(Self::IngressInduction),
+            pb::CyclesUseCase::Xnet => Ok(Self::Xnet),
+            pb::CyclesUseCase::Storage => Ok(Self::Storage),
+            pb::CyclesUseCase::ComputeAllocation => Ok(Self::ComputeAllocation),
+        }
+    }
 }
 
 /// State that is controlled and owned by the system (IC).
@@ -58,6 +112,9 @@ pub struct SystemState {
     pub stable_memory: Memory<NumWasmPages64>,
     /// The canister's memory allocation.
     pub memory_allocation: MemoryAllocation,
+    /// The controller-set upper limit on the canister's Wasm heap. Growing
+    /// the heap past it traps, except in installation and upgrade hooks.
+    pub wasm_memory_limit: Option<NumBytes>,
     pub freeze_threshold: NumSeconds,
     /// The status of the canister: Running, Stopping, or Stopped.
     /// Different statuses allow for different behaviors on the SystemState.
@@ -87,6 +144,62 @@ pub struct SystemState {
     ///     2. executing the operation and return `cycles_spent`
     ///     3. reimburse the canister with `cycles_reserved` - `cycles_spent`
     pub cycles_balance: Cycles,
+
+    /// Set once the canister failed to pay for its resource allocation and
+    /// usage. `None` while the canister is able to pay.
+    pub out_of_cycles_stage: Option<OutOfCyclesStage>,
+
+    /// The controller-set cycles balance below which the
+    /// `canister_on_low_cycles` system method is executed. Zero disables the
+    /// notification.
+    pub low_cycles_threshold: Cycles,
+
+    /// Whether the cycles balance was below `low_cycles_threshold` the last
+    /// time it was checked. Used to notify the canister only when the balance
+    /// crosses the threshold, rather than every time it is checked.
+    pub below_low_cycles_threshold: bool,
+
+    /// Whether `canister_on_low_cycles` is due to be executed.
+    pub low_cycles_notification_pending: bool,
+}
+
+/// How far a canister that failed to pay for its resource allocation and
+/// usage has progressed towards having its Wasm state deleted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutOfCyclesStage {
+    /// Calls to the canister are rejected, but its state is kept until the
+    /// grace period that started at `since` has elapsed. Topping up the
+    /// canister during the grace period makes it active again.
+    Frozen { since: Time },
+    /// The grace period elapsed and the Wasm state of the canister was
+    /// deleted. The canister stays in this stage until code is installed again.
+    Uninstalled,
+}
+
+impl From<&OutOfCyclesStage> for pb::OutOfCyclesStage {
+    fn from(item: &OutOfCyclesStage) -> Self {
+        use pb::out_of_cycles_stage::Stage;
+        let stage = match item {
+            OutOfCyclesStage::Frozen { since } => Stage::Frozen(pb::OutOfCyclesFrozen {
+                since_nanos: since.as_nanos_since_unix_epoch(),
+            }),
+            OutOfCyclesStage::Uninstalled => Stage::Uninstalled(pb::OutOfCyclesUninstalled {}),
+        };
+        Self { stage: Some(stage) }
+    }
+}
+
+impl TryFrom<pb::OutOfCyclesStage> for OutOfCyclesStage {
+    type Error = ProxyDecodeError;
+    fn try_from(value: pb::OutOfCyclesStage) -> Result<Self, Self::Error> {
+        use pb::out_of_cycles_stage::Stage;
+        match try_from_option_field(value.stage, "OutOfCyclesStage::stage")? {
+            Stage::Frozen(pb::OutOfCyclesFrozen { since_nanos }) => Ok(Self::Frozen {
+                since: Time::from_nanos_since_unix_epoch(since_nanos),
+            }),
+            Stage::Uninstalled(pb::OutOfCyclesUninstalled {}) => Ok(Self::Uninstalled),
+        }
+    }
 }
 
 /// A wrapper around the different canister statuses.
@@ -233,10 +346,15 @@ impl SystemState {
             stable_memory: Memory::default(),
             cycles_balance: initial_cycles,
             memory_allocation: MemoryAllocation::BestEffort,
+            wasm_memory_limit: None,
             freeze_threshold,
             status,
             certified_data: Default::default(),
             canister_metrics: CanisterMetrics::default(),
+            out_of_cycles_stage: None,
+            low_cycles_threshold: Cycles::from(0),
+            below_low_cycles_threshold: false,
+            low_cycles_notification_pending: false,
         }
     }
 
@@ -263,11 +381,16 @@ impl SystemState {
         queues: CanisterQueues,
         stable_memory: Memory<NumWasmPages64>,
         memory_allocation: MemoryAllocation,
+        wasm_memory_limit: Option<NumBytes>,
         freeze_threshold: NumSeconds,
         status: CanisterStatus,
         certified_data: Vec<u8>,
         canister_metrics: CanisterMetrics,
         cycles_balance: Cycles,
+        out_of_cycles_stage: Option<OutOfCyclesStage>,
+        low_cycles_threshold: Cycles,
+        below_low_cycles_threshold: bool,
+        low_cycles_notification_pending: bool,
     ) -> Self {
         Self {
             controllers,
@@ -275,11 +398,16 @@ impl SystemState {
             queues,
             stable_memory,
             memory_allocation,
+            wasm_memory_limit,
             freeze_threshold,
             status,
             certified_data,
             canister_metrics,
             cycles_balance,
+            out_of_cycles_stage,
+            low_cycles_threshold,
+            below_low_cycles_threshold,
+            low_cycles_notification_pending,
         }
     }
 
@@ -287,6 +415,28 @@ impl SystemState {
         self.canister_id
     }
 
+    /// Returns true if the canister failed to pay for its resource allocation
+    /// and usage and is waiting out its grace period.
+    pub fn is_frozen_out_of_cycles(&self) -> bool {
+        matches!(
+            self.out_of_cycles_stage,
+            Some(OutOfCyclesStage::Frozen { .. })
+        )
+    }
+
+    /// Checks whether the cycles balance crossed `low_cycles_threshold` from
+    /// above since the last check, in which case a `canister_on_low_cycles`
+    /// notification becomes pending and `true` is returned.
+    pub fn check_low_cycles_threshold(&mut self) -> bool {
+        let below_threshold = self.cycles_balance < self.low_cycles_threshold;
+        let crossed = below_threshold && !self.below_low_cycles_threshold;
+        if crossed {
+            self.low_cycles_notification_pending = true;
+        }
+        self.below_low_cycles_threshold = below_threshold;
+        crossed
+    }
+
     /// This method is used for maintaining the backwards compatibility.
     /// Returns:
     /// - controller ID as-is, if there is only one controller.
@@ -394,6 +544,8 @@ impl SystemState {
     ///  * `Running` system states accept requests and responses.
     ///  * `Stopping` system states accept responses only.
     ///  * `Stopped` system states accept neither.
+    ///  * Responses to best-effort calls that already had a response enqueued
+    ///    (or whose callback is gone) are silently dropped.
     ///
     /// # Errors
     ///
@@ -439,14 +591,100 @@ impl SystemState {
 
             // Everything else is accepted iff there is available memory and queue slots.
             (_, CanisterStatus::Running { .. })
-            | (RequestOrResponse::Response(_), CanisterStatus::Stopping { .. }) => push_input(
-                &mut self.queues,
-                index,
-                msg,
-                canister_available_memory,
-                subnet_available_memory,
-            ),
+            | (RequestOrResponse::Response(_), CanisterStatus::Stopping { .. }) => {
+                let callback_id = match &msg {
+                    RequestOrResponse::Response(response) => {
+                        if !self.accepts_response(response) {
+                            return Ok(());
+                        }
+                        Some(response.originator_reply_callback)
+                    }
+                    RequestOrResponse::Request(_) => None,
+                };
+                push_input(
+                    &mut self.queues,
+                    index,
+                    msg,
+                    canister_available_memory,
+                    subnet_available_memory,
+                )?;
+                if let Some(callback_id) = callback_id {
+                    self.on_response_enqueued(callback_id);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns `true` if `response` should be enqueued, i.e. unless it is a
+    /// late response to a best-effort call. See
+    /// `CallContextManager::accepts_response()`.
+    fn accepts_response(&self, response: &Response) -> bool {
+        self.call_context_manager()
+            .map_or(true, |ccm| ccm.accepts_response(response))
+    }
+
+    /// Records that a response for `callback_id` was enqueued.
+    fn on_response_enqueued(&mut self, callback_id: CallbackId) {
+        if let Some(ccm) = self.call_context_manager_mut() {
+            ccm.on_response_enqueued(callback_id);
+        }
+    }
+
+    /// Enqueues a `SYS_UNKNOWN` reject response for every best-effort call
+    /// whose deadline expired at `current_time` without a response having been
+    /// enqueued. Returns the number of calls timed out and the number of calls
+    /// that could not be timed out.
+    ///
+    /// The reject responses go into the input queue slots that were reserved
+    /// for the responses when the requests were enqueued, so enqueuing them
+    /// does not fail. Should it fail nonetheless, the call is left as it is and
+    /// timing it out is retried in the next round.
+    ///
+    /// Any cycles attached to a timed out call are not refunded, as the callee
+    /// may have already accepted them.
+    pub fn time_out_callbacks(&mut self, current_time: Time) -> (usize, usize) {
+        let rejects: Vec<_> = match self.call_context_manager() {
+            Some(ccm) => ccm
+                .expired_callbacks(current_time)
+                .into_iter()
+                .map(|(callback_id, callback)| {
+                    callback.respondent.map(|respondent| Response {
+                        originator: self.canister_id,
+                        respondent,
+                        originator_reply_callback: callback_id,
+                        refund: Cycles::zero(),
+                        response_payload: Payload::Reject(RejectContext {
+                            code: RejectCode::SysUnknown,
+                            message: "Call deadline has expired.".to_string(),
+                        }),
+                        deadline: callback.deadline,
+                    })
+                })
+                .collect(),
+            None => return (0, 0),
+        };
+
+        let (mut timed_out, mut failed) = (0, 0);
+        for reject in rejects {
+            let response = match reject {
+                Some(response) => response,
+                // A best-effort callback always has a respondent.
+                None => {
+                    failed += 1;
+                    continue;
+                }
+            };
+            let callback_id = response.originator_reply_callback;
+            match self.queues.push_input(QUEUE_INDEX_NONE, response.into()) {
+                Ok(()) => {
+                    self.on_response_enqueued(callback_id);
+                    timed_out += 1;
+                }
+                Err(_) => failed += 1,
+            }
         }
+        (timed_out, failed)
     }
 
     /// Pushes an ingress message into the induction pool.
@@ -512,6 +750,12 @@ impl SystemState {
         self.queues.filter_ingress_messages(filter);
     }
 
+    /// Returns the memory used by the canister's input and output queues,
+    /// whether or not it counts towards the canister's memory usage.
+    pub fn message_memory_usage(&self) -> NumBytes {
+        (self.queues.memory_usage() as u64).into()
+    }
+
     /// Returns the memory that is currently used by the `SystemState`.
     pub fn memory_usage(&self) -> NumBytes {
         let mut memory_usage = crate::num_bytes_try_from64(self.stable_memory.size)
@@ -571,7 +815,7 @@ impl SystemState {
         }
 
         if !ENFORCE_MESSAGE_MEMORY_USAGE {
-            while self.queues.induct_message_to_self(self.canister_id).is_ok() {}
+            while self.induct_message_to_self().is_ok() {}
             return;
         }
 
@@ -586,11 +830,7 @@ impl SystemState {
             }
 
             // Attempt inducting `msg`. May fail if the input queue is full.
-            if self
-                .queues
-                .induct_message_to_self(self.canister_id)
-                .is_err()
-            {
+            if self.induct_message_to_self().is_err() {
                 return;
             }
 
@@ -604,6 +844,30 @@ impl SystemState {
             *subnet_available_memory -= memory_usage;
         }
     }
+
+    /// Inducts the message at the head of the output queue to `self` into the
+    /// input queue from `self`; or drops it, if it is a late response to a
+    /// best-effort call. Returns `Err(())` if there was no message to induct or
+    /// the input queue was full.
+    fn induct_message_to_self(&mut self) -> Result<(), ()> {
+        let msg = self.queues.peek_output(&self.canister_id).ok_or(())?;
+        let callback_id = match &*msg {
+            RequestOrResponse::Response(response) => {
+                if !self.accepts_response(response) {
+                    self.queues.discard_message_to_self(self.canister_id);
+                    return Ok(());
+                }
+                Some(response.originator_reply_callback)
+            }
+            RequestOrResponse::Request(_) => None,
+        };
+
+        self.queues.induct_message_to_self(self.canister_id)?;
+        if let Some(callback_id) = callback_id {
+            self.on_response_enqueued(callback_id);
+        }
+        Ok(())
+    }
 }
 
 /// Implements memory limits verification for pushing a canister-to-canister
@@ -7,12 +7,12 @@ use ic_protobuf::state::canister_state_bits::v1 as pb;
 use ic_protobuf::types::v1 as pb_types;
 use ic_types::{
     ingress::WasmResult,
-    messages::{CallContextId, CallbackId, MessageId},
+    messages::{CallContextId, CallbackId, MessageId, Response},
     methods::Callback,
-    user_id_into_protobuf, user_id_try_from_protobuf, CanisterId, Cycles, Funds, UserId,
+    user_id_into_protobuf, user_id_try_from_protobuf, CanisterId, Cycles, Funds, Time, UserId,
 };
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::convert::{From, TryFrom, TryInto};
 
 /// Call context contains all context information related to an incoming call.
@@ -33,6 +33,10 @@ pub struct CallContext {
 
     /// Cycles that were sent in the request that created the CallContext.
     available_cycles: Cycles,
+
+    /// The deadline of the best-effort request that created the CallContext,
+    /// to be included in the response. `None` for all other call contexts.
+    deadline: Option<Time>,
 }
 
 impl CallContext {
@@ -47,6 +51,7 @@ impl CallContext {
             responded,
             deleted,
             available_cycles,
+            deadline: None,
         }
     }
 
@@ -79,6 +84,12 @@ impl CallContext {
         self.deleted
     }
 
+    /// Returns the deadline of the best-effort request that created this call
+    /// context, if any.
+    pub fn deadline(&self) -> Option<Time> {
+        self.deadline
+    }
+
     /// Mark the call context as deleted.
     pub fn mark_deleted(&mut self) {
         self.deleted = true;
@@ -102,6 +113,9 @@ impl From<&CallContext> for pb::CallContext {
             responded: item.responded,
             deleted: item.deleted,
             available_funds: Some((&funds).into()),
+            deadline_nanos: item
+                .deadline
+                .map_or(0, |deadline| deadline.as_nanos_since_unix_epoch()),
         }
     }
 }
@@ -117,6 +131,10 @@ impl TryFrom<pb::CallContext> for CallContext {
             responded: value.responded,
             deleted: value.deleted,
             available_cycles: funds.cycles(),
+            deadline: match value.deadline_nanos {
+                0 => None,
+                nanos => Some(Time::from_nanos_since_unix_epoch(nanos)),
+            },
         })
     }
 }
@@ -192,6 +210,10 @@ pub struct CallContextManager {
     // maps call context to its responded status
     call_contexts: BTreeMap<CallContextId, CallContext>,
     callbacks: BTreeMap<CallbackId, Callback>,
+    // The best-effort callbacks that did not have a response enqueued yet,
+    // ordered by deadline, so that the expired ones can be found without
+    // scanning all callbacks. Derived from `callbacks`.
+    callback_deadlines: BTreeSet<(Time, CallbackId)>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -273,6 +295,17 @@ impl CallContextManager {
     /// Must be used to create a new call context at the beginning of every new
     /// ingress or inter-canister message.
     pub fn new_call_context(&mut self, call_origin: CallOrigin, cycles: Cycles) -> CallContextId {
+        self.new_call_context_with_deadline(call_origin, cycles, None)
+    }
+
+    /// Same as `new_call_context()`, for a call context created by a request
+    /// with the given (optional) best-effort `deadline`.
+    pub fn new_call_context_with_deadline(
+        &mut self,
+        call_origin: CallOrigin,
+        cycles: Cycles,
+        deadline: Option<Time>,
+    ) -> CallContextId {
         self.next_call_context_id += 1;
         let id = CallContextId::from(self.next_call_context_id);
         self.call_contexts.insert(
@@ -282,6 +315,7 @@ impl CallContextManager {
                 responded: false,
                 deleted: false,
                 available_cycles: cycles,
+                deadline,
             },
         );
         id
@@ -415,6 +449,9 @@ impl CallContextManager {
     pub fn register_callback(&mut self, callback: Callback) -> CallbackId {
         self.next_callback_id += 1;
         let callback_id = CallbackId::from(self.next_callback_id);
+        if let Some(deadline) = pending_deadline(&callback) {
+            self.callback_deadlines.insert((deadline, callback_id));
+        }
         self.callbacks.insert(callback_id, callback);
         callback_id
     }
@@ -427,7 +464,45 @@ impl CallContextManager {
     /// If we get a response for one of the outstanding calls, we unregister
     /// the callback and return it.
     pub fn unregister_callback(&mut self, callback_id: CallbackId) -> Option<Callback> {
-        self.callbacks.remove(&callback_id)
+        let callback = self.callbacks.remove(&callback_id)?;
+        if let Some(deadline) = pending_deadline(&callback) {
+            self.callback_deadlines.remove(&(deadline, callback_id));
+        }
+        Some(callback)
+    }
+
+    /// Returns `true` if `response` should be enqueued. Responses to
+    /// best-effort calls are only enqueued if the callback still exists and no
+    /// response (e.g. a `SYS_UNKNOWN` reject after the deadline expired) was
+    /// enqueued for it yet; all other responses are always enqueued.
+    pub fn accepts_response(&self, response: &Response) -> bool {
+        match self.callbacks.get(&response.originator_reply_callback) {
+            Some(callback) if callback.deadline.is_some() => !callback.response_enqueued,
+            Some(_) => true,
+            None => response.deadline.is_none(),
+        }
+    }
+
+    /// Records that a response to the best-effort call with the given
+    /// `callback_id` was enqueued, so that any further responses are dropped.
+    pub fn on_response_enqueued(&mut self, callback_id: CallbackId) {
+        if let Some(callback) = self.callbacks.get_mut(&callback_id) {
+            if let Some(deadline) = pending_deadline(callback) {
+                self.callback_deadlines.remove(&(deadline, callback_id));
+                callback.response_enqueued = true;
+            }
+        }
+    }
+
+    /// Returns the callbacks of all best-effort calls whose deadline expired
+    /// at `current_time` without a response having been enqueued, in order of
+    /// their deadlines. Only looks at the expired callbacks.
+    pub fn expired_callbacks(&self, current_time: Time) -> Vec<(CallbackId, &Callback)> {
+        self.callback_deadlines
+            .iter()
+            .take_while(|(deadline, _)| *deadline <= current_time)
+            .map(|(_, callback_id)| (*callback_id, &self.callbacks[callback_id]))
+            .collect()
     }
 
     pub fn unregister_call_context(
@@ -461,6 +536,12 @@ impl CallContextManager {
     }
 }
 
+/// Returns the deadline of `callback`, if it is a best-effort callback that did
+/// not have a response enqueued yet.
+fn pending_deadline(callback: &Callback) -> Option<Time> {
+    callback.deadline.filter(|_| !callback.response_enqueued)
+}
+
 impl From<&RequestOrIngress> for CallOrigin {
     fn from(msg: &RequestOrIngress) -> Self {
         match msg {
@@ -525,11 +606,19 @@ impl TryFrom<pb::CallContextManager> for CallContextManager {
             );
         }
 
+        let callback_deadlines = callbacks
+            .iter()
+            .filter_map(|(callback_id, callback)| {
+                pending_deadline(callback).map(|deadline| (deadline, *callback_id))
+            })
+            .collect();
+
         Ok(Self {
             next_call_context_id: value.next_call_context_id,
             next_callback_id: value.next_callback_id,
             call_contexts,
             callbacks,
+            callback_deadlines,
         })
     }
 }
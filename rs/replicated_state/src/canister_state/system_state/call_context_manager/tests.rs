@@ -1,6 +1,6 @@
 use super::*;
 use ic_test_utilities::types::ids::canister_test_id;
-use ic_types::methods::WasmClosure;
+use ic_types::{messages::Payload, methods::WasmClosure};
 
 #[test]
 fn call_context_origin() {
@@ -208,3 +208,106 @@ fn withdraw_cycles_succeeds_when_enough_available_cycles() {
         Ok(())
     );
 }
+
+#[test]
+fn best_effort_callbacks_expire_once() {
+    let mut ccm = CallContextManager::default();
+    let respondent = canister_test_id(13);
+    let deadline = Time::from_nanos_since_unix_epoch(1_000);
+    let cc_id = ccm.new_call_context(
+        CallOrigin::CanisterUpdate(canister_test_id(42), CallbackId::from(1)),
+        Cycles::from(0),
+    );
+    let guaranteed_cb_id = ccm.register_callback(Callback::new(
+        cc_id,
+        Cycles::from(0),
+        WasmClosure::new(0, 1),
+        WasmClosure::new(2, 3),
+        None,
+    ));
+    let best_effort_cb_id = ccm.register_callback(
+        Callback::new(
+            cc_id,
+            Cycles::from(0),
+            WasmClosure::new(4, 5),
+            WasmClosure::new(6, 7),
+            None,
+        )
+        .with_deadline(respondent, deadline),
+    );
+    let response_to = |callback_id, deadline| Response {
+        originator: canister_test_id(42),
+        respondent,
+        originator_reply_callback: callback_id,
+        refund: Cycles::from(0),
+        response_payload: Payload::Data(vec![]),
+        deadline,
+    };
+
+    // Nothing expires before the deadline.
+    assert!(ccm
+        .expired_callbacks(Time::from_nanos_since_unix_epoch(999))
+        .is_empty());
+    assert!(ccm.accepts_response(&response_to(best_effort_cb_id, Some(deadline))));
+
+    // Only the best-effort callback expires, until a response is enqueued.
+    let expired = ccm.expired_callbacks(deadline);
+    assert_eq!(
+        expired.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+        vec![best_effort_cb_id]
+    );
+    ccm.on_response_enqueued(best_effort_cb_id);
+    assert!(ccm.expired_callbacks(deadline).is_empty());
+
+    // Late responses to the best-effort call are no longer accepted.
+    assert!(!ccm.accepts_response(&response_to(best_effort_cb_id, Some(deadline))));
+    assert!(ccm.accepts_response(&response_to(guaranteed_cb_id, None)));
+
+    // Nor are they once the callback is gone, unlike responses to unknown
+    // guaranteed response callbacks.
+    ccm.unregister_callback(best_effort_cb_id).unwrap();
+    assert!(!ccm.accepts_response(&response_to(best_effort_cb_id, Some(deadline))));
+    assert!(ccm.accepts_response(&response_to(CallbackId::from(13), None)));
+}
+
+#[test]
+fn expired_callbacks_are_ordered_by_deadline() {
+    let mut ccm = CallContextManager::default();
+    let cc_id = ccm.new_call_context(
+        CallOrigin::CanisterUpdate(canister_test_id(42), CallbackId::from(1)),
+        Cycles::from(0),
+    );
+    let mut register_best_effort_callback = |deadline_nanos| {
+        ccm.register_callback(
+            Callback::new(
+                cc_id,
+                Cycles::from(0),
+                WasmClosure::new(0, 1),
+                WasmClosure::new(2, 3),
+                None,
+            )
+            .with_deadline(
+                canister_test_id(13),
+                Time::from_nanos_since_unix_epoch(deadline_nanos),
+            ),
+        )
+    };
+    let late_cb_id = register_best_effort_callback(3_000);
+    let early_cb_id = register_best_effort_callback(1_000);
+    let unregistered_cb_id = register_best_effort_callback(2_000);
+    ccm.unregister_callback(unregistered_cb_id).unwrap();
+
+    let expired_ids = |ccm: &CallContextManager, nanos| {
+        ccm.expired_callbacks(Time::from_nanos_since_unix_epoch(nanos))
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(vec![early_cb_id], expired_ids(&ccm, 2_500));
+    assert_eq!(vec![early_cb_id, late_cb_id], expired_ids(&ccm, 3_000));
+
+    // The deadlines are restored from the callbacks when decoding.
+    let decoded = CallContextManager::try_from(pb::CallContextManager::from(&ccm)).unwrap();
+    assert_eq!(ccm, decoded);
+    assert_eq!(vec![early_cb_id, late_cb_id], expired_ids(&decoded, 3_000));
+}
@@ -13,7 +13,7 @@ pub use canister_state::{
     num_bytes_from, num_bytes_try_from64,
     system_state::{
         memory_required_to_push_request, CallContext, CallContextAction, CallContextManager,
-        CallOrigin, CanisterMetrics, CanisterStatus, SystemState,
+        CallOrigin, CanisterMetrics, CanisterStatus, CyclesUseCase, OutOfCyclesStage, SystemState,
     },
     CanisterQueues, CanisterState, EmbedderCache, ExecutionState, ExportedFunctions, Global,
     NumWasmPages, NumWasmPages64, SchedulerState,
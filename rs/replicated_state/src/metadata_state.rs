@@ -26,7 +26,7 @@ use ic_types::{
     CountBytes, CryptoHashOfPartialState, NodeId, NumBytes, PrincipalId, SubnetId,
 };
 use std::{
-    collections::{BTreeMap, BTreeSet},
+    collections::{BTreeMap, BTreeSet, VecDeque},
     convert::{From, TryFrom, TryInto},
     mem::size_of,
     sync::Arc,
@@ -238,6 +238,8 @@ impl TryFrom<pb_metadata::SubnetTopology> for SubnetTopology {
 pub struct NodeTopology {
     pub ip_address: String,
     pub http_port: u16,
+    /// The DER-encoded Ed25519 signing public key of the node.
+    pub public_key: Vec<u8>,
 }
 
 impl From<&NodeTopology> for pb_metadata::NodeTopology {
@@ -245,6 +247,7 @@ impl From<&NodeTopology> for pb_metadata::NodeTopology {
         Self {
             ip_address: item.ip_address.clone(),
             http_port: item.http_port as u32,
+            public_key: item.public_key.clone(),
         }
     }
 }
@@ -255,6 +258,7 @@ impl TryFrom<pb_metadata::NodeTopology> for NodeTopology {
         Ok(Self {
             ip_address: item.ip_address,
             http_port: item.http_port as u16,
+            public_key: item.public_key,
         })
     }
 }
@@ -410,11 +414,15 @@ pub struct Stream {
     /// Index of the next expected reverse stream message.
     ///
     /// Conceptually we use a gap-free queue containing one signal for each
-    /// inducted message; but because these signals are all "Accept" (as we
-    /// generate responses when rejecting messages), that queue can be safely
-    /// represented by its end index (pointing just beyond the last signal).
+    /// inducted message; but because most signals are "Accept", that queue is
+    /// represented by its end index (pointing just beyond the last signal) plus
+    /// the indices of the "Reject" signals.
     signals_end: StreamIndex,
 
+    /// Stream indices of rejected reverse stream messages, in strictly
+    /// increasing order, all below `signals_end`.
+    reject_signals: VecDeque<StreamIndex>,
+
     /// Estimated stream byte size.
     size_bytes: usize,
 }
@@ -423,10 +431,12 @@ impl Default for Stream {
     fn default() -> Self {
         let messages = Default::default();
         let signals_end = Default::default();
+        let reject_signals = Default::default();
         let size_bytes = Self::size_bytes(&messages);
         Self {
             messages,
             signals_end,
+            reject_signals,
             size_bytes,
         }
     }
@@ -442,6 +452,7 @@ impl From<&Stream> for pb_queues::Stream {
                 .map(|(_, req_or_resp)| req_or_resp.into())
                 .collect(),
             signals_end: item.signals_end.get(),
+            reject_signals: item.reject_signals.iter().map(|i| i.get()).collect(),
         }
     }
 }
@@ -459,6 +470,7 @@ impl TryFrom<pb_queues::Stream> for Stream {
         Ok(Self {
             messages,
             signals_end: item.signals_end.into(),
+            reject_signals: item.reject_signals.into_iter().map(|i| i.into()).collect(),
             size_bytes,
         })
     }
@@ -467,10 +479,21 @@ impl TryFrom<pb_queues::Stream> for Stream {
 impl Stream {
     /// Creates a new `Stream` with the given `messages` and `signals_end`.
     pub fn new(messages: StreamIndexedQueue<RequestOrResponse>, signals_end: StreamIndex) -> Self {
+        Self::with_signals(messages, signals_end, Default::default())
+    }
+
+    /// Creates a new `Stream` with the given `messages`, `signals_end` and
+    /// `reject_signals`.
+    pub fn with_signals(
+        messages: StreamIndexedQueue<RequestOrResponse>,
+        signals_end: StreamIndex,
+        reject_signals: VecDeque<StreamIndex>,
+    ) -> Self {
         let size_bytes = Self::size_bytes(&messages);
         Self {
             messages,
             signals_end,
+            reject_signals,
             size_bytes,
         }
     }
@@ -488,6 +511,7 @@ impl Stream {
             begin: self.messages.begin(),
             end: self.messages.end(),
             signals_end: self.signals_end,
+            reject_signals: self.reject_signals.clone(),
         }
     }
 
@@ -544,6 +568,34 @@ impl Stream {
         self.signals_end.inc_assign()
     }
 
+    /// Returns the stream indices of the rejected reverse stream messages.
+    pub fn reject_signals(&self) -> &VecDeque<StreamIndex> {
+        &self.reject_signals
+    }
+
+    /// Appends a reject signal for the reverse stream message at `index`
+    /// (which must be equal to `signals_end`) and increments `signals_end`.
+    pub fn push_reject_signal(&mut self, index: StreamIndex) {
+        assert_eq!(
+            index, self.signals_end,
+            "Expecting reject signal with stream index {}, got {}",
+            self.signals_end, index
+        );
+        self.reject_signals.push_back(index);
+        self.signals_end.inc_assign()
+    }
+
+    /// Garbage collects reject signals before `new_signals_begin`, i.e. those
+    /// already consumed by the remote subnet.
+    pub fn discard_signals_before(&mut self, new_signals_begin: StreamIndex) {
+        while let Some(index) = self.reject_signals.front() {
+            if *index >= new_signals_begin {
+                break;
+            }
+            self.reject_signals.pop_front();
+        }
+    }
+
     /// Calculates the byte size of a `Stream` holding the given messages.
     fn size_bytes(messages: &StreamIndexedQueue<RequestOrResponse>) -> usize {
         let messages_bytes: usize = messages.iter().map(|(_, m)| m.count_bytes()).sum();
@@ -564,6 +616,7 @@ impl From<Stream> for StreamSlice {
                 begin: val.messages.begin(),
                 end: val.messages.end(),
                 signals_end: val.signals_end,
+                reject_signals: val.reject_signals,
             },
             val.messages,
         )
@@ -706,6 +759,11 @@ impl<'a> StreamHandle<'a> {
         self.stream.messages_end()
     }
 
+    /// Returns a reference to the message queue.
+    pub fn messages(&self) -> &StreamIndexedQueue<RequestOrResponse> {
+        self.stream.messages()
+    }
+
     /// Returns the index just beyond the last sent signal.
     pub fn signals_end(&self) -> StreamIndex {
         self.stream.signals_end
@@ -727,6 +785,17 @@ impl<'a> StreamHandle<'a> {
         self.stream.increment_signals_end();
     }
 
+    /// Appends a reject signal for the reverse stream message at `index` and
+    /// increments `signals_end`.
+    pub fn push_reject_signal(&mut self, index: StreamIndex) {
+        self.stream.push_reject_signal(index);
+    }
+
+    /// Garbage collects reject signals before `new_signals_begin`.
+    pub fn discard_signals_before(&mut self, new_signals_begin: StreamIndex) {
+        self.stream.discard_signals_before(new_signals_begin);
+    }
+
     /// Garbage collects messages before `new_begin`.
     pub fn discard_before(&mut self, new_begin: StreamIndex) {
         // Update stats for each discarded message.
@@ -194,3 +194,43 @@ fn streams_stats_after_deserialization() {
         deserialized_system_metadata.streams.responses_size_bytes()
     );
 }
+
+#[test]
+fn stream_reject_signals() {
+    let mut stream = Stream::new(StreamIndexedQueue::with_begin(0.into()), 10.into());
+
+    stream.increment_signals_end();
+    stream.push_reject_signal(11.into());
+    stream.increment_signals_end();
+    stream.push_reject_signal(13.into());
+    stream.push_reject_signal(14.into());
+
+    assert_eq!(StreamIndex::from(15), stream.signals_end());
+    assert_eq!(
+        &VecDeque::from(vec![11.into(), 13.into(), 14.into()]),
+        stream.reject_signals()
+    );
+    assert_eq!(stream.reject_signals(), &stream.header().reject_signals);
+
+    // Reject signals survive a protobuf roundtrip.
+    let stream_proto: pb_queues::Stream = (&stream).into();
+    assert_eq!(stream, stream_proto.try_into().unwrap());
+
+    // Only reject signals before the new signals begin are discarded.
+    stream.discard_signals_before(13.into());
+    assert_eq!(
+        &VecDeque::from(vec![13.into(), 14.into()]),
+        stream.reject_signals()
+    );
+    stream.discard_signals_before(15.into());
+    assert!(stream.reject_signals().is_empty());
+    assert_eq!(StreamIndex::from(15), stream.signals_end());
+}
+
+#[test]
+#[should_panic(expected = "Expecting reject signal with stream index 10, got 11")]
+fn stream_push_reject_signal_out_of_order() {
+    let mut stream = Stream::new(StreamIndexedQueue::with_begin(0.into()), 10.into());
+
+    stream.push_reject_signal(11.into());
+}
@@ -3,13 +3,19 @@ use ic_interfaces::messages::CanisterInputMessage;
 use ic_replicated_state::{
     canister_state::{DEFAULT_QUEUE_CAPACITY, ENFORCE_MESSAGE_MEMORY_USAGE, QUEUE_INDEX_NONE},
     testing::SystemStateTesting,
-    SystemState,
+    CallOrigin, SystemState,
 };
 use ic_test_utilities::types::{
     ids::{canister_test_id, user_test_id},
     messages::{RequestBuilder, ResponseBuilder},
 };
-use ic_types::{freeze_threshold_cycles, messages::RequestOrResponse, Cycles, QueueIndex};
+use ic_types::{
+    freeze_threshold_cycles,
+    messages::{Payload, RejectContext, RequestOrResponse},
+    methods::{Callback, WasmClosure},
+    user_error::RejectCode,
+    Cycles, QueueIndex, Time,
+};
 
 const CANISTER_AVAILABLE_MEMORY: i64 = 4 << 30;
 const SUBNET_AVAILABLE_MEMORY: i64 = 300 << 30;
@@ -269,3 +275,120 @@ fn induct_messages_to_self_full_queue() {
         system_state.output_into_iter(canister_id).count()
     );
 }
+
+#[test]
+fn time_out_callbacks_rejects_best_effort_call_and_drops_late_response() {
+    let canister_id = canister_test_id(1);
+    let mut system_state = SystemState::new_running(
+        canister_id,
+        user_test_id(1).get(),
+        Cycles::new(5_000_000_000_000),
+        NumSeconds::new(0),
+    );
+    let deadline = Time::from_nanos_since_unix_epoch(1_000);
+
+    // Best-effort request to self.
+    let ccm = system_state.call_context_manager_mut().unwrap();
+    let call_context_id = ccm.new_call_context(CallOrigin::Heartbeat, Cycles::zero());
+    let callback_id = ccm.register_callback(
+        Callback::new(
+            call_context_id,
+            Cycles::zero(),
+            WasmClosure::new(0, 0),
+            WasmClosure::new(0, 0),
+            None,
+        )
+        .with_deadline(canister_id, deadline),
+    );
+    let request = RequestBuilder::default()
+        .sender(canister_id)
+        .receiver(canister_id)
+        .sender_reply_callback(callback_id)
+        .deadline(Some(deadline))
+        .build();
+    system_state
+        .queues_mut()
+        .push_output_request(request.clone())
+        .unwrap();
+    system_state.induct_messages_to_self(
+        CANISTER_AVAILABLE_MEMORY,
+        &mut SUBNET_AVAILABLE_MEMORY.clone(),
+    );
+    assert_eq!(
+        Some(CanisterInputMessage::Request(request)),
+        system_state.pop_input()
+    );
+
+    // Nothing times out before the deadline.
+    assert_eq!(
+        (0, 0),
+        system_state.time_out_callbacks(Time::from_nanos_since_unix_epoch(999))
+    );
+    assert!(!system_state.has_input());
+
+    // The callee responds, but the call times out before the response is inducted.
+    system_state.push_output_response(
+        ResponseBuilder::default()
+            .originator(canister_id)
+            .respondent(canister_id)
+            .originator_reply_callback(callback_id)
+            .deadline(Some(deadline))
+            .build(),
+    );
+    assert_eq!((1, 0), system_state.time_out_callbacks(deadline));
+    assert_eq!((0, 0), system_state.time_out_callbacks(deadline));
+
+    // The late response is dropped instead of being inducted.
+    system_state.induct_messages_to_self(
+        CANISTER_AVAILABLE_MEMORY,
+        &mut SUBNET_AVAILABLE_MEMORY.clone(),
+    );
+    assert!(!system_state.queues().has_output());
+
+    // Only the `SYS_UNKNOWN` reject is enqueued.
+    match system_state.pop_input() {
+        Some(CanisterInputMessage::Response(response)) => {
+            assert_eq!(callback_id, response.originator_reply_callback);
+            match response.response_payload {
+                Payload::Reject(RejectContext { code, .. }) => {
+                    assert_eq!(RejectCode::SysUnknown, code)
+                }
+                payload => panic!("Expected a reject, got {:?}", payload),
+            }
+        }
+        other => panic!("Expected a reject response, got {:?}", other),
+    }
+    assert_eq!(None, system_state.pop_input());
+}
+
+#[test]
+fn time_out_callbacks_does_not_panic_without_reserved_slot() {
+    let canister_id = canister_test_id(1);
+    let mut system_state = SystemState::new_running(
+        canister_id,
+        user_test_id(1).get(),
+        Cycles::new(5_000_000_000_000),
+        NumSeconds::new(0),
+    );
+    let deadline = Time::from_nanos_since_unix_epoch(1_000);
+
+    // A best-effort callback whose request was never enqueued, so no input
+    // queue slot is reserved for its response.
+    let ccm = system_state.call_context_manager_mut().unwrap();
+    let call_context_id = ccm.new_call_context(CallOrigin::Heartbeat, Cycles::zero());
+    ccm.register_callback(
+        Callback::new(
+            call_context_id,
+            Cycles::zero(),
+            WasmClosure::new(0, 0),
+            WasmClosure::new(0, 0),
+            None,
+        )
+        .with_deadline(canister_test_id(2), deadline),
+    );
+
+    // The call cannot be timed out, and is retried in later rounds.
+    assert_eq!((0, 1), system_state.time_out_callbacks(deadline));
+    assert_eq!((0, 1), system_state.time_out_callbacks(deadline));
+    assert!(!system_state.has_input());
+}
@@ -0,0 +1,358 @@
+//! The state of the account index canister, which follows the ledger and
+//! records the transactions of every account.
+use crate::stable_storage::{
+    read_u64, with_storage, write_u64, RegionId, StableAccountMap, StableBlocks,
+};
+use crate::{
+    AccountIdentifier, BlockHeight, EncodedBlock, HashOf, Operation, TimeStamp, Transaction,
+};
+use candid::CandidType;
+use ic_types::CanisterId;
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+
+/// The maximum number of transactions returned by one query.
+pub const MAX_TRANSACTIONS_PER_RESPONSE: usize = 1000;
+
+// This is how we pass arguments to 'init' in index_node.rs
+#[derive(Serialize, Deserialize, CandidType, Clone, Debug, PartialEq, Eq)]
+pub struct IndexCanisterInitPayload {
+    pub ledger_canister_id: CanisterId,
+}
+
+/// A transaction together with the height and the timestamp of its block.
+#[derive(Serialize, Deserialize, CandidType, Clone, Debug, PartialEq, Eq)]
+pub struct TransactionWithHeight {
+    pub block_height: BlockHeight,
+    pub timestamp: TimeStamp,
+    pub transaction: Transaction,
+}
+
+#[derive(Serialize, Deserialize, CandidType, Clone, Debug, PartialEq, Eq)]
+pub struct GetAccountTransactionsArgs {
+    pub account: AccountIdentifier,
+    /// The height of the most recent transaction to return. Transactions
+    /// are returned from the most recent to the oldest, so this is the
+    /// `next_start` of the previous page, or `None` for the first page.
+    pub start: Option<BlockHeight>,
+    pub max_results: u64,
+}
+
+#[derive(Serialize, Deserialize, CandidType, Clone, Debug, PartialEq, Eq)]
+pub struct GetAccountTransactionsResult {
+    pub transactions: Vec<TransactionWithHeight>,
+    /// Where the next page starts, if there are older transactions.
+    pub next_start: Option<BlockHeight>,
+}
+
+#[derive(Serialize, Deserialize, CandidType, Clone, Debug, PartialEq, Eq)]
+pub struct IndexStatus {
+    pub ledger_canister_id: CanisterId,
+    pub num_blocks_synced: u64,
+    pub num_accounts: u64,
+}
+
+/// The index of the transactions of every account. The blocks and the
+/// index live in stable memory, so upgrades only persist this struct.
+///
+/// The blocks of an account form a list from the most recent to the oldest:
+/// every block records, for each of its accounts, the height of the previous
+/// block of that account.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct IndexState {
+    pub ledger_canister_id: CanisterId,
+    /// The blocks synced so far.
+    blocks: StableBlocks,
+    /// For every block and each of its accounts, in the order of
+    /// `block_accounts`, the height plus one of the previous block of the
+    /// account, or zero.
+    links: RegionId,
+    /// The height plus one of the most recent block of each account.
+    accounts: StableAccountMap,
+    last_hash: Option<HashOf<EncodedBlock>>,
+    #[serde(skip)]
+    pub last_upgrade_timestamp: u64,
+}
+
+/// The accounts whose transactions include the operation.
+fn block_accounts(operation: &Operation) -> Vec<AccountIdentifier> {
+    match *operation {
+        Operation::Burn { from, .. } => vec![from],
+        Operation::Mint { to, .. } => vec![to],
+        Operation::Transfer { from, to, .. } if from == to => vec![from],
+        Operation::Transfer { from, to, .. } => vec![from, to],
+    }
+}
+
+/// The offset in the links of the previous block of the `i`-th account of
+/// the block at `height`.
+fn link_offset(height: BlockHeight, i: usize) -> u64 {
+    height * 16 + i as u64 * 8
+}
+
+impl IndexState {
+    pub fn new(ledger_canister_id: CanisterId) -> Self {
+        Self {
+            ledger_canister_id,
+            blocks: StableBlocks::default(),
+            links: with_storage(|s| s.new_region()),
+            accounts: StableAccountMap::default(),
+            last_hash: None,
+            last_upgrade_timestamp: 0,
+        }
+    }
+
+    /// The number of blocks synced, which is the height of the next block.
+    pub fn num_blocks(&self) -> u64 {
+        self.blocks.len() as u64
+    }
+
+    pub fn num_accounts(&self) -> usize {
+        self.accounts.len()
+    }
+
+    /// Indexes the next block of the chain. Fails if it does not refer to
+    /// the hash of the previous block.
+    pub fn append_block(&mut self, encoded_block: &EncodedBlock) -> Result<(), String> {
+        let block = encoded_block.decode()?;
+        if block.parent_hash != self.last_hash {
+            return Err(format!(
+                "Block {} refers to parent hash {:?}, expected {:?}",
+                self.num_blocks(),
+                block.parent_hash,
+                self.last_hash
+            ));
+        }
+        let height = self.num_blocks();
+        for (i, account) in block_accounts(&block.transaction.operation)
+            .into_iter()
+            .enumerate()
+        {
+            let mut previous = 0;
+            self.accounts
+                .update(account, |last| -> Result<u64, Infallible> {
+                    previous = last.unwrap_or(0);
+                    Ok(height + 1)
+                })
+                .unwrap();
+            write_u64(self.links, link_offset(height, i), previous);
+        }
+        self.blocks.push(encoded_block);
+        self.last_hash = Some(encoded_block.hash());
+        Ok(())
+    }
+
+    /// Returns the transaction of the account at `height`, if the account
+    /// takes part in it, and the height of the previous one.
+    fn account_transaction(
+        &self,
+        account: &AccountIdentifier,
+        height: BlockHeight,
+    ) -> Option<(TransactionWithHeight, Option<BlockHeight>)> {
+        let block = self
+            .blocks
+            .get(height as usize)?
+            .decode()
+            .expect("Failed to decode an indexed block");
+        let i = block_accounts(&block.transaction.operation)
+            .iter()
+            .position(|a| a == account)?;
+        let previous = read_u64(self.links, link_offset(height, i)).checked_sub(1);
+        let transaction = TransactionWithHeight {
+            block_height: height,
+            timestamp: block.timestamp,
+            transaction: block.transaction,
+        };
+        Some((transaction, previous))
+    }
+
+    /// Returns the height of the most recent transaction of the account at
+    /// or before `start`, given the height of its last transaction.
+    fn page_start(
+        &self,
+        account: &AccountIdentifier,
+        last: BlockHeight,
+        start: Option<BlockHeight>,
+    ) -> Option<BlockHeight> {
+        let start = match start {
+            Some(start) if start < last => start,
+            _ => return Some(last),
+        };
+        // Pages usually start at the `next_start` of the previous page, which
+        // is a transaction of the account. Otherwise, this follows the
+        // transactions of the account back from the most recent one.
+        if self.account_transaction(account, start).is_some() {
+            return Some(start);
+        }
+        let mut height = Some(last);
+        while let Some(h) = height.filter(|h| *h > start) {
+            height = self
+                .account_transaction(account, h)
+                .expect("The index refers to a block without the account")
+                .1;
+        }
+        height
+    }
+
+    pub fn get_account_transactions(
+        &self,
+        args: GetAccountTransactionsArgs,
+    ) -> GetAccountTransactionsResult {
+        let mut next = match self.accounts.get(&args.account) {
+            Some(last) => self.page_start(&args.account, last - 1, args.start),
+            None => None,
+        };
+        let max_results = (args.max_results as usize).min(MAX_TRANSACTIONS_PER_RESPONSE);
+        let mut transactions = vec![];
+        while transactions.len() < max_results {
+            let height = match next {
+                Some(height) => height,
+                None => break,
+            };
+            let (transaction, previous) = self
+                .account_transaction(&args.account, height)
+                .expect("The index refers to a block without the account");
+            transactions.push(transaction);
+            next = previous;
+        }
+        GetAccountTransactionsResult {
+            transactions,
+            next_start: next,
+        }
+    }
+
+    pub fn status(&self) -> IndexStatus {
+        IndexStatus {
+            ledger_canister_id: self.ledger_canister_id,
+            num_blocks_synced: self.num_blocks(),
+            num_accounts: self.num_accounts() as u64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Block, ICPTs, Memo};
+    use ic_types::PrincipalId;
+    use std::time::SystemTime;
+
+    fn account(i: u64) -> AccountIdentifier {
+        PrincipalId::new_user_test_id(i).into()
+    }
+
+    fn block(parent_hash: Option<HashOf<EncodedBlock>>, operation: Operation) -> EncodedBlock {
+        Block::new(
+            parent_hash,
+            operation,
+            Memo::default(),
+            SystemTime::UNIX_EPOCH.into(),
+            SystemTime::UNIX_EPOCH.into(),
+        )
+        .unwrap()
+        .encode()
+        .unwrap()
+    }
+
+    fn index_with_blocks(operations: Vec<Operation>) -> IndexState {
+        let mut index = IndexState::new(CanisterId::from_u64(2));
+        let mut last_hash = None;
+        for operation in operations {
+            let block = block(last_hash, operation);
+            index.append_block(&block).unwrap();
+            last_hash = Some(block.hash());
+        }
+        index
+    }
+
+    fn heights(result: &GetAccountTransactionsResult) -> Vec<BlockHeight> {
+        result
+            .transactions
+            .iter()
+            .map(|tx| tx.block_height)
+            .collect()
+    }
+
+    #[test]
+    fn blocks_must_extend_the_chain() {
+        let mut index = IndexState::new(CanisterId::from_u64(2));
+        let genesis = block(
+            None,
+            Operation::Mint {
+                to: account(1),
+                amount: ICPTs::from_e8s(100),
+            },
+        );
+        index.append_block(&genesis).unwrap();
+        let orphan = block(
+            None,
+            Operation::Burn {
+                from: account(1),
+                amount: ICPTs::from_e8s(10),
+            },
+        );
+        assert!(index.append_block(&orphan).is_err());
+        assert_eq!(index.num_blocks(), 1);
+    }
+
+    #[test]
+    fn account_transactions_are_paginated_from_the_most_recent() {
+        let mut operations = vec![];
+        for i in 0..5 {
+            operations.push(Operation::Mint {
+                to: account(1),
+                amount: ICPTs::from_e8s(100),
+            });
+            operations.push(Operation::Transfer {
+                from: account(1),
+                to: account(2 + i % 2),
+                amount: ICPTs::from_e8s(10),
+                fee: ICPTs::from_e8s(1),
+            });
+        }
+        let index = index_with_blocks(operations);
+        assert_eq!(index.num_accounts(), 3);
+
+        let args = |start, max_results| GetAccountTransactionsArgs {
+            account: account(2),
+            start,
+            max_results,
+        };
+        let first_page = index.get_account_transactions(args(None, 2));
+        assert_eq!(heights(&first_page), vec![9, 5]);
+        assert_eq!(first_page.next_start, Some(1));
+        let second_page = index.get_account_transactions(args(first_page.next_start, 2));
+        assert_eq!(heights(&second_page), vec![1]);
+        assert_eq!(second_page.next_start, None);
+        assert_eq!(
+            second_page.transactions[0].transaction.operation,
+            Operation::Transfer {
+                from: account(1),
+                to: account(2),
+                amount: ICPTs::from_e8s(10),
+                fee: ICPTs::from_e8s(1),
+            }
+        );
+
+        let all = index.get_account_transactions(GetAccountTransactionsArgs {
+            account: account(1),
+            start: Some(4),
+            max_results: 100,
+        });
+        assert_eq!(heights(&all), vec![4, 3, 2, 1, 0]);
+        assert_eq!(all.next_start, None);
+
+        // Block 4 is not a transaction of account 2, so the page starts at
+        // the one before.
+        let from_other_block = index.get_account_transactions(args(Some(4), 10));
+        assert_eq!(heights(&from_other_block), vec![1]);
+        assert_eq!(from_other_block.next_start, None);
+
+        let unknown = index.get_account_transactions(GetAccountTransactionsArgs {
+            account: account(9),
+            start: None,
+            max_results: 10,
+        });
+        assert!(unknown.transactions.is_empty());
+    }
+}
@@ -0,0 +1,236 @@
+use ledger_canister::{
+    index::{
+        GetAccountTransactionsArgs, GetAccountTransactionsResult, IndexCanisterInitPayload,
+        IndexState, IndexStatus,
+    },
+    metrics_encoder::MetricsEncoder,
+    protobuf::{ArchiveIndexResponse, TipOfChainRequest},
+    stable_storage, BlockHeight, GetBlocksArgs, GetBlocksRes, TipOfChainRes,
+};
+
+use dfn_candid::{candid, candid_one, CandidOne};
+use dfn_core::api::{call_with_cleanup, trap_with};
+use dfn_core::{over, over_init, stable, BytesS};
+use dfn_protobuf::protobuf;
+use ic_types::CanisterId;
+use std::convert::TryFrom;
+use std::sync::RwLock;
+
+lazy_static::lazy_static! {
+    static ref INDEX_STATE: RwLock<IndexState> = RwLock::new(IndexState::new(ic_nns_constants::LEDGER_CANISTER_ID));
+    // Whether blocks are being fetched, so that heartbeats don't start
+    // another sync in the meantime.
+    static ref SYNCING: RwLock<bool> = RwLock::new(false);
+}
+
+/// The maximum number of blocks fetched with one call to the ledger or to an
+/// archive node.
+const MAX_BLOCKS_PER_CALL: u64 = 2000;
+
+// Helper to print messages in green
+fn print<S: std::convert::AsRef<str>>(s: S)
+where
+    yansi::Paint<S>: std::string::ToString,
+{
+    dfn_core::api::print(yansi::Paint::green(s).to_string());
+}
+
+fn init(arg: IndexCanisterInitPayload) {
+    print(format!(
+        "[index] init(): following ledger {}",
+        arg.ledger_canister_id
+    ));
+    *INDEX_STATE.write().unwrap() = IndexState::new(arg.ledger_canister_id);
+}
+
+/// Returns the canister that stores the block at `height`, either an archive
+/// node or the ledger itself, and the height of the last block it stores, if
+/// it is an archive node.
+fn locate_block(
+    ledger_canister_id: CanisterId,
+    archive_index: &ArchiveIndexResponse,
+    height: BlockHeight,
+) -> Result<(CanisterId, Option<BlockHeight>), String> {
+    for entry in archive_index.entries.iter() {
+        if entry.height_from <= height && height <= entry.height_to {
+            let node = entry
+                .canister_id
+                .ok_or_else(|| format!("Archive node of block {} has no canister ID", height))?;
+            let node = CanisterId::try_from(node).map_err(|e| e.to_string())?;
+            return Ok((node, Some(entry.height_to)));
+        }
+    }
+    Ok((ledger_canister_id, None))
+}
+
+/// Clears `SYNCING` when dropped. The future of a sync is dropped when it
+/// completes and, as all calls are made with `call_with_cleanup`, also when
+/// a callback traps, so a trap does not stop the syncing for good.
+struct SyncingGuard;
+
+impl SyncingGuard {
+    /// Returns a guard if no sync is in progress.
+    fn acquire() -> Option<Self> {
+        let mut syncing = SYNCING.write().unwrap();
+        if *syncing {
+            return None;
+        }
+        *syncing = true;
+        Some(SyncingGuard)
+    }
+}
+
+impl Drop for SyncingGuard {
+    fn drop(&mut self) {
+        *SYNCING.write().unwrap() = false;
+    }
+}
+
+/// Fetches the next blocks from the ledger or from the archive node that
+/// stores them, and indexes them.
+async fn sync() -> Result<u64, String> {
+    let (ledger_canister_id, next) = {
+        let state = INDEX_STATE.read().unwrap();
+        (state.ledger_canister_id, state.num_blocks())
+    };
+
+    let tip: TipOfChainRes = call_with_cleanup(
+        ledger_canister_id,
+        "tip_of_chain_pb",
+        protobuf,
+        TipOfChainRequest {},
+    )
+    .await
+    .map_err(|(_, msg)| format!("Failed to get the tip of the chain: {}", msg))?;
+    if next > tip.tip_index {
+        return Ok(0);
+    }
+
+    let archive_index: ArchiveIndexResponse =
+        call_with_cleanup(ledger_canister_id, "get_archive_index_pb", protobuf, ())
+            .await
+            .map_err(|(_, msg)| format!("Failed to get the archive index: {}", msg))?;
+
+    let (canister_id, last_stored) = locate_block(ledger_canister_id, &archive_index, next)?;
+    let last = last_stored
+        .unwrap_or(tip.tip_index)
+        .min(next + MAX_BLOCKS_PER_CALL - 1);
+    let GetBlocksRes(blocks) = call_with_cleanup(
+        canister_id,
+        "get_blocks_pb",
+        protobuf,
+        GetBlocksArgs::new(next, (last - next + 1) as usize),
+    )
+    .await
+    .map_err(|(_, msg)| format!("Failed to get blocks from {}: {}", canister_id, msg))?;
+    let blocks = blocks?;
+
+    let mut state = INDEX_STATE.write().unwrap();
+    if state.num_blocks() != next {
+        return Err("Blocks were indexed concurrently".to_string());
+    }
+    for block in blocks.iter() {
+        state.append_block(block)?;
+    }
+    Ok(blocks.len() as u64)
+}
+
+#[export_name = "canister_heartbeat"]
+fn canister_heartbeat() {
+    let guard = match SyncingGuard::acquire() {
+        Some(guard) => guard,
+        None => return,
+    };
+    // canister_heartbeat must be synchronous, so we cannot .await the future
+    dfn_core::api::futures::spawn(async move {
+        let _guard = guard;
+        match sync().await {
+            Ok(0) => (),
+            Ok(num_blocks) => print(format!("[index] indexed {} blocks", num_blocks)),
+            Err(msg) => print(format!("[index] sync failed: {}", msg)),
+        }
+    });
+}
+
+#[export_name = "canister_init"]
+fn main() {
+    over_init(|CandidOne(arg)| init(arg))
+}
+
+/// Get the transactions of an account, from the most recent to the oldest.
+#[export_name = "canister_query get_account_transactions"]
+fn get_account_transactions_() {
+    over(
+        candid_one,
+        |args: GetAccountTransactionsArgs| -> GetAccountTransactionsResult {
+            INDEX_STATE.read().unwrap().get_account_transactions(args)
+        },
+    );
+}
+
+#[export_name = "canister_query status"]
+fn status_() {
+    over(candid, |()| -> IndexStatus {
+        INDEX_STATE.read().unwrap().status()
+    });
+}
+
+#[export_name = "canister_post_upgrade"]
+fn post_upgrade() {
+    over_init(|_: BytesS| {
+        if let Err(e) = stable_storage::open_storage() {
+            trap_with(&format!("Failed to open the index stable storage: {}", e));
+        }
+        let metadata = stable_storage::with_storage(|storage| storage.read_metadata())
+            .expect("The stable memory holds no index state");
+        let mut state = INDEX_STATE.write().unwrap();
+        *state = serde_cbor::from_slice(&metadata).expect("Decoding the index state failed");
+        state.last_upgrade_timestamp = dfn_core::api::time_nanos();
+    });
+}
+
+/// The blocks and the index already live in stable memory, so this only
+/// persists the remaining state of the index.
+#[export_name = "canister_pre_upgrade"]
+fn pre_upgrade() {
+    dfn_core::setup::START.call_once(|| {
+        dfn_core::printer::hook();
+    });
+
+    let state = INDEX_STATE
+        .read()
+        // This should never happen, but it's better to be safe than sorry
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let metadata = serde_cbor::to_vec(&*state).unwrap();
+    stable_storage::with_storage(|storage| storage.write_metadata(&metadata));
+}
+
+fn encode_metrics(w: &mut MetricsEncoder<Vec<u8>>) -> std::io::Result<()> {
+    let state = INDEX_STATE.read().unwrap();
+    w.encode_gauge(
+        "index_synced_blocks",
+        state.num_blocks() as f64,
+        "Number of ledger blocks indexed by this canister.",
+    )?;
+    w.encode_gauge(
+        "index_accounts",
+        state.num_accounts() as f64,
+        "Number of accounts with at least one indexed transaction.",
+    )?;
+    w.encode_gauge(
+        "index_stable_memory_pages",
+        stable::stable64_size() as f64,
+        "Size of the stable memory allocated by this canister measured in 64K Wasm pages.",
+    )?;
+    w.encode_gauge(
+        "index_last_upgrade_time_seconds",
+        state.last_upgrade_timestamp as f64 / 1_000_000_000.0,
+        "IC timestamp of the last upgrade performed on this canister.",
+    )?;
+    Ok(())
+}
+
+#[export_name = "canister_query http_request"]
+fn http_request() {
+    ledger_canister::http_request::serve_metrics(encode_metrics);
+}
@@ -26,7 +26,9 @@ use std::time::{Duration, SystemTime};
 pub mod account_identifier;
 pub mod http_request;
 pub mod icpts;
+pub mod index;
 pub mod metrics_encoder;
+pub mod stable_storage;
 #[path = "../gen/ic_ledger.pb.v1.rs"]
 #[rustfmt::skip]
 pub mod protobuf;
@@ -43,6 +45,7 @@ pub mod spawn;
 pub use account_identifier::{AccountIdentifier, Subaccount};
 pub use icpts::{ICPTs, DECIMAL_PLACES, ICP_SUBDIVIDABLE_BY, MIN_BURN_AMOUNT, TRANSACTION_FEE};
 pub use protobuf::TimeStamp;
+pub use stable_storage::{StableBalances, StableBlocks};
 
 // Helper to print messages in magenta
 pub fn print<S: std::convert::AsRef<str>>(s: S)
@@ -202,30 +205,30 @@ pub type BlockHeight = u64;
 
 pub type Certification = Option<Vec<u8>>;
 
-pub type LedgerBalances = Balances<HashMap<AccountIdentifier, ICPTs>>;
+pub type LedgerBalances = Balances<StableBalances>;
 
 pub trait BalancesStore {
-    fn get_balance(&self, k: &AccountIdentifier) -> Option<&ICPTs>;
+    fn get_balance(&self, k: &AccountIdentifier) -> Option<ICPTs>;
     // Update balance for an account using function f.
     // Its arg is previous balance or None if not found and
     // return value is the new balance.
     fn update<F, E>(&mut self, acc: AccountIdentifier, action_on_acc: F) -> Result<ICPTs, E>
     where
-        F: FnMut(Option<&ICPTs>) -> Result<ICPTs, E>;
+        F: FnMut(Option<ICPTs>) -> Result<ICPTs, E>;
 }
 
 impl BalancesStore for HashMap<AccountIdentifier, ICPTs> {
-    fn get_balance(&self, k: &AccountIdentifier) -> Option<&ICPTs> {
-        self.get(k)
+    fn get_balance(&self, k: &AccountIdentifier) -> Option<ICPTs> {
+        self.get(k).cloned()
     }
 
     fn update<F, E>(&mut self, k: AccountIdentifier, mut f: F) -> Result<ICPTs, E>
     where
-        F: FnMut(Option<&ICPTs>) -> Result<ICPTs, E>,
+        F: FnMut(Option<ICPTs>) -> Result<ICPTs, E>,
     {
         match self.entry(k) {
             Occupied(mut entry) => {
-                let new_v = f(Some(entry.get()))?;
+                let new_v = f(Some(*entry.get()))?;
                 if new_v != ICPTs::ZERO {
                     *entry.get_mut() = new_v;
                 } else {
@@ -309,7 +312,7 @@ impl<S: Default + BalancesStore> Balances<S> {
     ) -> Result<ICPTs, BalanceError> {
         self.store.update(*from, |prev| {
             let mut balance = match prev {
-                Some(x) => *x,
+                Some(x) => x,
                 None => {
                     return Err(BalanceError::InsufficientFunds {
                         balance: ICPTs::ZERO,
@@ -330,16 +333,13 @@ impl<S: Default + BalancesStore> Balances<S> {
     pub fn credit(&mut self, to: &AccountIdentifier, amount: ICPTs) {
         self.store
             .update(*to, |prev| -> Result<ICPTs, std::convert::Infallible> {
-                Ok((amount + *prev.unwrap_or(&ICPTs::ZERO)).expect("integer overflow"))
+                Ok((amount + prev.unwrap_or(ICPTs::ZERO)).expect("integer overflow"))
             })
             .unwrap();
     }
 
     pub fn account_balance(&self, account: &AccountIdentifier) -> ICPTs {
-        self.store
-            .get_balance(account)
-            .cloned()
-            .unwrap_or(ICPTs::ZERO)
+        self.store.get_balance(account).unwrap_or(ICPTs::ZERO)
     }
 
     /// Returns the total quantity of ICPs that are "in existence" -- that
@@ -366,15 +366,15 @@ impl LedgerBalances {
 
         // Accumulate up to `trim_quantity` accounts
         for (account, balance) in iter.by_ref().take(num_accounts) {
-            to_trim.push((*balance, *account));
+            to_trim.push((balance, account));
         }
 
         for (account, balance) in iter {
             // If any account's balance is lower than the maximum in our set,
             // include that account, and remove the current maximum
             if let Some((greatest_balance, _)) = to_trim.peek() {
-                if balance < greatest_balance {
-                    to_trim.push((*balance, *account));
+                if balance < *greatest_balance {
+                    to_trim.push((balance, account));
                     to_trim.pop();
                 }
             }
@@ -508,7 +508,8 @@ impl Block {
 /// Stores a chain of transactions with their metadata
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Blockchain {
-    pub blocks: Vec<EncodedBlock>,
+    /// The blocks that have not been archived yet.
+    pub blocks: StableBlocks,
     pub last_hash: Option<HashOf<EncodedBlock>>,
 
     /// The timestamp of the most recent block. Must be monotonically
@@ -523,7 +524,7 @@ pub struct Blockchain {
 impl Default for Blockchain {
     fn default() -> Self {
         Self {
-            blocks: vec![],
+            blocks: StableBlocks::default(),
             last_hash: None,
             last_timestamp: SystemTime::UNIX_EPOCH.into(),
             archive: Arc::new(RwLock::new(None)),
@@ -554,11 +555,11 @@ impl Blockchain {
         }
         self.last_hash = Some(encoded_block.hash());
         self.last_timestamp = block.timestamp;
-        self.blocks.push(encoded_block);
+        self.blocks.push(&encoded_block);
         Ok(self.chain_length().checked_sub(1).unwrap())
     }
 
-    pub fn get(&self, height: BlockHeight) -> Option<&EncodedBlock> {
+    pub fn get(&self, height: BlockHeight) -> Option<EncodedBlock> {
         if height < self.num_archived_blocks() {
             None
         } else {
@@ -567,10 +568,38 @@ impl Blockchain {
         }
     }
 
-    pub fn last(&self) -> Option<&EncodedBlock> {
+    pub fn last(&self) -> Option<EncodedBlock> {
         self.blocks.last()
     }
 
+    /// Returns the `length` blocks starting at height `start`, all of which
+    /// must not be archived yet.
+    pub fn get_blocks(&self, start: BlockHeight, length: usize) -> GetBlocksRes {
+        let first = self.num_archived_blocks();
+        let end = start.saturating_add(length as u64);
+        if start < first || end > self.chain_length() {
+            return GetBlocksRes(Err(format!(
+                "Requested blocks outside the range stored in the ledger. \
+                 Requested [{} .. {}). Available [{} .. {}).",
+                start,
+                end,
+                first,
+                self.chain_length()
+            )));
+        }
+        GetBlocksRes(Ok(self
+            .blocks
+            .get_range((start - first) as usize, (end - first) as usize)))
+    }
+
+    /// Returns up to `length` of the unarchived blocks, skipping the first
+    /// `offset` of them.
+    pub fn iter_blocks(&self, offset: usize, length: usize) -> IterBlocksRes {
+        let start = offset.min(self.blocks.len());
+        let end = start.saturating_add(length).min(self.blocks.len());
+        IterBlocksRes(self.blocks.get_range(start, end))
+    }
+
     pub fn num_archived_blocks(&self) -> u64 {
         self.num_archived_blocks
     }
@@ -584,16 +613,7 @@ impl Blockchain {
     }
 
     pub fn remove_archived_blocks(&mut self, len: usize) {
-        // redundant since split_off would panic, but here we can give a more
-        // descriptive message
-        if len > self.blocks.len() {
-            panic!(
-                "Asked to remove more blocks than present. Present: {}, to remove: {}",
-                self.blocks.len(),
-                len
-            );
-        }
-        self.blocks = self.blocks.split_off(len);
+        self.blocks.remove_front(len);
         self.num_archived_blocks += len as u64;
     }
 
@@ -613,8 +633,10 @@ impl Blockchain {
             return VecDeque::new();
         }
 
-        let blocks_to_archive: VecDeque<EncodedBlock> =
-            VecDeque::from(self.blocks[0..num_blocks_to_archive.min(num_blocks_before)].to_vec());
+        let blocks_to_archive: VecDeque<EncodedBlock> = VecDeque::from(
+            self.blocks
+                .get_range(0, num_blocks_to_archive.min(num_blocks_before)),
+        );
 
         print(format!(
             "get_blocks_for_archiving(): trigger_threshold: {}, num_blocks: {}, blocks before archiving: {}, blocks to archive: {}",
@@ -719,6 +741,87 @@ struct TransactionInfo {
     transaction_hash: HashOf<Transaction>,
 }
 
+/// The state of the ledger as it was persisted across upgrades before the
+/// balances and the blocks moved to stable memory.
+#[derive(Deserialize)]
+#[cfg_attr(test, derive(Serialize))]
+pub struct LegacyLedger {
+    balances: Balances<HashMap<AccountIdentifier, ICPTs>>,
+    blockchain: LegacyBlockchain,
+    maximum_number_of_accounts: usize,
+    accounts_overflow_trim_quantity: usize,
+    minting_account_id: Option<AccountIdentifier>,
+    #[serde(
+        serialize_with = "serialize_int_map",
+        deserialize_with = "deserialize_int_map",
+        default = "IntMap::new"
+    )]
+    blocks_notified: IntMap<()>,
+    transaction_window: Duration,
+    transactions_by_hash: BTreeMap<HashOf<Transaction>, BlockHeight>,
+    transactions_by_height: VecDeque<TransactionInfo>,
+    send_whitelist: HashSet<CanisterId>,
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(test, derive(Serialize))]
+struct LegacyBlockchain {
+    blocks: Vec<EncodedBlock>,
+    last_hash: Option<HashOf<EncodedBlock>>,
+    last_timestamp: TimeStamp,
+    archive: Arc<RwLock<Option<Archive>>>,
+    num_archived_blocks: u64,
+}
+
+impl From<LegacyLedger> for Ledger {
+    /// Moves the balances and the blocks of the legacy state to stable
+    /// memory.
+    fn from(legacy: LegacyLedger) -> Self {
+        // Size the balances table for the existing accounts, so that inserting
+        // them does not grow it.
+        let mut balances = LedgerBalances {
+            store: StableBalances::with_capacity(legacy.balances.store.len()),
+            icpt_pool: legacy.balances.icpt_pool,
+        };
+        // Insert the accounts in a fixed order, so that all replicas end up
+        // with the same layout of the balances table.
+        let mut accounts: Vec<_> = legacy.balances.store.into_iter().collect();
+        accounts.sort_unstable();
+        for (account, balance) in accounts {
+            balances
+                .store
+                .update(account, |_| -> Result<ICPTs, std::convert::Infallible> {
+                    Ok(balance)
+                })
+                .unwrap();
+        }
+
+        let mut blocks = StableBlocks::default();
+        for block in legacy.blockchain.blocks.iter() {
+            blocks.push(block);
+        }
+
+        Self {
+            balances,
+            blockchain: Blockchain {
+                blocks,
+                last_hash: legacy.blockchain.last_hash,
+                last_timestamp: legacy.blockchain.last_timestamp,
+                archive: legacy.blockchain.archive,
+                num_archived_blocks: legacy.blockchain.num_archived_blocks,
+            },
+            maximum_number_of_accounts: legacy.maximum_number_of_accounts,
+            accounts_overflow_trim_quantity: legacy.accounts_overflow_trim_quantity,
+            minting_account_id: legacy.minting_account_id,
+            blocks_notified: legacy.blocks_notified,
+            transaction_window: legacy.transaction_window,
+            transactions_by_hash: legacy.transactions_by_hash,
+            transactions_by_height: legacy.transactions_by_height,
+            send_whitelist: legacy.send_whitelist,
+        }
+    }
+}
+
 impl Default for Ledger {
     fn default() -> Self {
         Self {
@@ -1134,7 +1237,7 @@ mod tests {
         })
         .unwrap();
         // verify that an account entry exists for the `canister`
-        assert_eq!(b.store.get(&canister), Some(&ICPTs::from_e8s(1000)));
+        assert_eq!(b.store.get_balance(&canister), Some(ICPTs::from_e8s(1000)));
         // make 2 transfers that empty the account
         for _ in 0..2 {
             b.add_payment(&Operation::Transfer {
@@ -1146,9 +1249,12 @@ mod tests {
             .unwrap();
         }
         // target canister's balance adds up
-        assert_eq!(b.store.get(&target_canister), Some(&ICPTs::from_e8s(800)));
+        assert_eq!(
+            b.store.get_balance(&target_canister),
+            Some(ICPTs::from_e8s(800))
+        );
         // source canister has been removed
-        assert_eq!(b.store.get(&canister), None);
+        assert_eq!(b.store.get_balance(&canister), None);
         assert_eq!(b.account_balance(&canister), ICPTs::ZERO);
 
         // one account left in the store
@@ -1164,7 +1270,10 @@ mod tests {
         // No new account should have been created
         assert_eq!(b.store.len(), 1);
         // and the fee should have been taken from sender
-        assert_eq!(b.store.get(&target_canister), Some(&ICPTs::from_e8s(700)));
+        assert_eq!(
+            b.store.get_balance(&target_canister),
+            Some(ICPTs::from_e8s(700))
+        );
 
         b.add_payment(&Operation::Mint {
             to: canister,
@@ -1515,19 +1624,94 @@ mod tests {
             state.add_block(block).unwrap();
         }
 
-        let blocks = &state.blockchain.blocks;
-
-        let first_blocks = super::get_blocks(blocks, 0, 1, 5).0.unwrap();
+        let first_blocks = state.blockchain.get_blocks(1, 5).0.unwrap();
         for i in 0..first_blocks.len() {
             let block = first_blocks.get(i).unwrap().decode().unwrap();
             assert_eq!(block.transaction.memo.0, i as u64);
         }
 
-        let last_blocks = super::get_blocks(blocks, 0, 6, 5).0.unwrap();
+        let last_blocks = state.blockchain.get_blocks(6, 5).0.unwrap();
         for i in 0..last_blocks.len() {
             let block = last_blocks.get(i).unwrap().decode().unwrap();
             assert_eq!(block.transaction.memo.0, 5 + i as u64);
         }
+
+        assert!(state.blockchain.get_blocks(8, 5).0.is_err());
+        state.remove_archived_blocks(3);
+        assert!(state.blockchain.get_blocks(1, 5).0.is_err());
+        let blocks = state.blockchain.get_blocks(3, 5).0.unwrap();
+        assert_eq!(blocks[0].decode().unwrap().transaction.memo.0, 2);
+    }
+
+    #[test]
+    fn upgrade_from_legacy_state() {
+        let accounts: HashMap<AccountIdentifier, ICPTs> = (0..10)
+            .map(|i| {
+                (
+                    PrincipalId::new_user_test_id(i).into(),
+                    ICPTs::from_e8s(i + 1),
+                )
+            })
+            .collect();
+        let mut blockchain = LegacyBlockchain {
+            blocks: vec![],
+            last_hash: None,
+            last_timestamp: SystemTime::UNIX_EPOCH.into(),
+            archive: Arc::new(RwLock::new(None)),
+            num_archived_blocks: 5,
+        };
+        for (to, amount) in accounts.iter() {
+            let block = Block::new(
+                blockchain.last_hash,
+                Operation::Mint {
+                    to: *to,
+                    amount: *amount,
+                },
+                Memo::default(),
+                SystemTime::UNIX_EPOCH.into(),
+                SystemTime::UNIX_EPOCH.into(),
+            )
+            .unwrap()
+            .encode()
+            .unwrap();
+            blockchain.last_hash = Some(block.hash());
+            blockchain.blocks.push(block);
+        }
+        let first_block = blockchain.blocks[0].clone();
+        let last_hash = blockchain.last_hash;
+        let legacy = LegacyLedger {
+            balances: Balances {
+                store: accounts.clone(),
+                icpt_pool: ICPTs::from_e8s(1000),
+            },
+            blockchain,
+            maximum_number_of_accounts: 8,
+            accounts_overflow_trim_quantity: 2,
+            minting_account_id: Some(PrincipalId::new_user_test_id(137).into()),
+            blocks_notified: IntMap::new(),
+            transaction_window: Duration::from_secs(60),
+            transactions_by_hash: BTreeMap::new(),
+            transactions_by_height: VecDeque::new(),
+            send_whitelist: HashSet::new(),
+        };
+        let legacy_bytes = serde_cbor::to_vec(&legacy).unwrap();
+
+        let ledger = Ledger::from(serde_cbor::from_slice::<LegacyLedger>(&legacy_bytes).unwrap());
+        // What `pre_upgrade` persists from now on.
+        let metadata = serde_cbor::to_vec(&ledger).unwrap();
+        assert!(metadata.len() < legacy_bytes.len() / 2);
+        let ledger: Ledger = serde_cbor::from_slice(&metadata).unwrap();
+
+        assert_eq!(ledger.balances.icpt_pool, ICPTs::from_e8s(1000));
+        assert_eq!(ledger.balances.store.len(), accounts.len());
+        for (account, balance) in accounts.iter() {
+            assert_eq!(ledger.balances.account_balance(account), *balance);
+        }
+        assert_eq!(ledger.blockchain.chain_length(), 15);
+        assert_eq!(ledger.blockchain.last_hash, last_hash);
+        assert_eq!(ledger.blockchain.get(4), None);
+        assert_eq!(ledger.blockchain.get(5), Some(first_block));
+        assert_eq!(ledger.transaction_window, Duration::from_secs(60));
     }
 
     #[test]
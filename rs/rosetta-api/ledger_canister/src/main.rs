@@ -373,7 +373,7 @@ fn block(block_index: BlockHeight) -> Option<Result<EncodedBlock, CanisterId>> {
             "[ledger] Checking the ledger for block [{}]",
             block_index
         ));
-        state.blockchain.get(block_index).cloned().map(Ok)
+        state.blockchain.get(block_index).map(Ok)
     }
 }
 
@@ -408,9 +408,23 @@ fn main() {
 #[export_name = "canister_post_upgrade"]
 fn post_upgrade() {
     over_init(|_: BytesS| {
+        if let Err(e) = stable_storage::open_storage() {
+            trap_with(&format!("Failed to open the ledger stable storage: {}", e));
+        }
         let mut ledger = LEDGER.write().unwrap();
-        *ledger = serde_cbor::from_reader(&mut stable::StableReader::new())
-            .expect("Decoding stable memory failed");
+        *ledger = match stable_storage::with_storage(|storage| storage.read_metadata()) {
+            Some(metadata) => {
+                serde_cbor::from_slice(&metadata).expect("Decoding the ledger metadata failed")
+            }
+            // The whole ledger was serialized to stable memory by a version that
+            // kept the balances and the blocks on the heap.
+            None => {
+                let legacy: LegacyLedger =
+                    serde_cbor::from_reader(&mut stable::StableReader::new())
+                        .expect("Decoding stable memory failed");
+                Ledger::from(legacy)
+            }
+        };
 
         set_certified_data(
             &ledger
@@ -422,10 +436,10 @@ fn post_upgrade() {
     })
 }
 
+/// The balances and the blocks already live in stable memory, so this only
+/// persists the remaining state of the ledger.
 #[export_name = "canister_pre_upgrade"]
 fn pre_upgrade() {
-    use std::io::Write;
-
     setup::START.call_once(|| {
         printer::hook();
     });
@@ -434,11 +448,8 @@ fn pre_upgrade() {
         .read()
         // This should never happen, but it's better to be safe than sorry
         .unwrap_or_else(|poisoned| poisoned.into_inner());
-    let mut writer = stable::StableWriter::new();
-    serde_cbor::to_writer(&mut writer, &*ledger).unwrap();
-    writer
-        .flush()
-        .expect("failed to flush stable memory writer");
+    let metadata = serde_cbor::to_vec(&*ledger).unwrap();
+    stable_storage::with_storage(|storage| storage.write_metadata(&metadata));
 }
 
 /// Upon reaching a `trigger_threshold` we will archive `num_blocks`.
@@ -683,8 +694,7 @@ fn total_supply_() {
 #[export_name = "canister_query iter_blocks_pb"]
 fn iter_blocks_() {
     over(protobuf, |IterBlocksArgs { start, length }| {
-        let blocks = &LEDGER.read().unwrap().blockchain.blocks;
-        ledger_canister::iter_blocks(blocks, start, length)
+        LEDGER.read().unwrap().blockchain.iter_blocks(start, length)
     });
 }
 
@@ -693,9 +703,7 @@ fn iter_blocks_() {
 #[export_name = "canister_query get_blocks_pb"]
 fn get_blocks_() {
     over(protobuf, |GetBlocksArgs { start, length }| {
-        let blockchain: &Blockchain = &LEDGER.read().unwrap().blockchain;
-        let start_offset = blockchain.num_archived_blocks();
-        ledger_canister::get_blocks(&blockchain.blocks, start_offset, start, length)
+        LEDGER.read().unwrap().blockchain.get_blocks(start, length)
     });
 }
 
@@ -730,12 +738,12 @@ fn encode_metrics(w: &mut metrics_encoder::MetricsEncoder<Vec<u8>>) -> std::io::
     )?;
     w.encode_gauge(
         "ledger_stable_memory_pages",
-        dfn_core::api::stable_memory_size_in_pages() as f64,
+        stable::stable64_size() as f64,
         "Size of the stable memory allocated by this canister measured in 64K Wasm pages.",
     )?;
     w.encode_gauge(
         "ledger_stable_memory_bytes",
-        (dfn_core::api::stable_memory_size_in_pages() * 64 * 1024) as f64,
+        (stable::stable64_size() * 64 * 1024) as f64,
         "Size of the stable memory allocated by this canister.",
     )?;
     w.encode_gauge(
@@ -751,7 +759,7 @@ fn encode_metrics(w: &mut metrics_encoder::MetricsEncoder<Vec<u8>>) -> std::io::
     w.encode_gauge(
         "ledger_blocks",
         ledger.blockchain.blocks.len() as f64,
-        "Total number of blocks stored in the main memory.",
+        "Total number of blocks stored in the stable memory.",
     )?;
     // This value can go down -- the number is increased before archiving, and if
     // archiving fails it is decremented.
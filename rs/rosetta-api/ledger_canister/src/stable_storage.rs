@@ -0,0 +1,1042 @@
+//! Storage of the account balances and of the unarchived blocks in stable
+//! memory, so that upgrades only have to persist the (small) remaining state
+//! of the ledger.
+//!
+//! The stable memory is split into a header page followed by segments of
+//! `SEGMENT_PAGES` pages. Every segment belongs to a region, which is a
+//! growable byte array: the header records which region owns a segment and
+//! at which position within the region. This lets the balances table and the
+//! block log grow independently, and lets them give back memory once it is no
+//! longer used.
+//!
+//! 0        64             64 KiB             64 KiB + 8 MiB
+//! +--------+--------------+-------------------+------------------->
+//! | fields | segment table | segment 0         | segment 1 ...
+//! +--------+--------------+-------------------+------------------->
+use crate::{AccountIdentifier, BalancesStore, EncodedBlock, ICPTs};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::rc::Rc;
+
+const PAGE_SIZE: u64 = 64 * 1024;
+const HEADER_PAGES: u64 = 1;
+const SEGMENT_PAGES: u64 = 128;
+const SEGMENT_SIZE: u64 = SEGMENT_PAGES * PAGE_SIZE;
+
+const MAGIC: &[u8; 8] = b"ICPLEDGR";
+const VERSION: u32 = 1;
+const SEGMENT_TABLE_OFFSET: u64 = 64;
+const SEGMENT_TABLE_ENTRY_SIZE: u64 = 8;
+const MAX_SEGMENTS: u64 =
+    (HEADER_PAGES * PAGE_SIZE - SEGMENT_TABLE_OFFSET) / SEGMENT_TABLE_ENTRY_SIZE;
+
+pub type RegionId = u32;
+
+/// The region of segments that are not used by any region.
+const FREE_REGION: RegionId = 0;
+/// The region holding the state persisted by `pre_upgrade`.
+const METADATA_REGION: RegionId = 1;
+const FIRST_REGION: RegionId = 2;
+
+/// A growable byte-addressable memory made of Wasm pages.
+pub trait Memory {
+    /// The size of the memory in pages.
+    fn size(&self) -> u64;
+    /// Grows the memory by the given number of pages, returning the previous
+    /// size or -1 if the memory could not be grown.
+    fn grow(&self, pages: u64) -> i64;
+    fn read(&self, offset: u64, dst: &mut [u8]);
+    fn write(&self, offset: u64, src: &[u8]);
+}
+
+/// The stable memory of the canister.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StableMemory;
+
+impl Memory for StableMemory {
+    fn size(&self) -> u64 {
+        dfn_core::stable::stable64_size()
+    }
+
+    fn grow(&self, pages: u64) -> i64 {
+        dfn_core::stable::stable64_grow(pages)
+    }
+
+    fn read(&self, offset: u64, dst: &mut [u8]) {
+        let size = dst.len() as u64;
+        dfn_core::stable::stable64_read(dst, offset, size)
+    }
+
+    fn write(&self, offset: u64, src: &[u8]) {
+        dfn_core::stable::stable64_write(offset, src)
+    }
+}
+
+/// A memory on the heap, used in place of the stable memory outside of
+/// canisters, e.g. in unit tests.
+#[derive(Clone, Debug, Default)]
+pub struct VecMemory(Rc<RefCell<Vec<u8>>>);
+
+impl Memory for VecMemory {
+    fn size(&self) -> u64 {
+        self.0.borrow().len() as u64 / PAGE_SIZE
+    }
+
+    fn grow(&self, pages: u64) -> i64 {
+        let mut bytes = self.0.borrow_mut();
+        let previous = bytes.len() as u64 / PAGE_SIZE;
+        bytes.resize(((previous + pages) * PAGE_SIZE) as usize, 0);
+        previous as i64
+    }
+
+    fn read(&self, offset: u64, dst: &mut [u8]) {
+        let offset = offset as usize;
+        dst.copy_from_slice(&self.0.borrow()[offset..offset + dst.len()]);
+    }
+
+    fn write(&self, offset: u64, src: &[u8]) {
+        let offset = offset as usize;
+        self.0.borrow_mut()[offset..offset + src.len()].copy_from_slice(src);
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub type DefaultMemory = StableMemory;
+#[cfg(not(target_arch = "wasm32"))]
+pub type DefaultMemory = VecMemory;
+
+thread_local! {
+    static STORAGE: RefCell<Option<Storage<DefaultMemory>>> = RefCell::new(None);
+}
+
+/// Opens the storage of this canister, failing if the stable memory holds
+/// data in an unsupported version of the format. `post_upgrade` calls this
+/// before anything else touches the storage, so that it can report the
+/// error.
+pub fn open_storage() -> Result<(), String> {
+    let storage = Storage::open(DefaultMemory::default())?;
+    STORAGE.with(|s| *s.borrow_mut() = Some(storage));
+    Ok(())
+}
+
+/// Runs `f` on the storage of this canister, opening it on first use.
+pub fn with_storage<R>(f: impl FnOnce(&mut Storage<DefaultMemory>) -> R) -> R {
+    STORAGE.with(|storage| {
+        let mut storage = storage.borrow_mut();
+        let storage = storage.get_or_insert_with(|| {
+            Storage::open(DefaultMemory::default())
+                .unwrap_or_else(|e| panic!("Failed to open the ledger stable storage: {}", e))
+        });
+        f(storage)
+    })
+}
+
+/// The regions of a memory.
+///
+/// Nothing is written to the memory before the first write to a region, so a
+/// storage can be opened on a memory that holds data in another format,
+/// which is then overwritten by the first write.
+pub struct Storage<M: Memory> {
+    memory: M,
+    initialized: bool,
+    next_region: RegionId,
+    metadata_len: u64,
+    /// The region and the position in the region of every segment.
+    segments: Vec<(RegionId, u32)>,
+    by_region: BTreeMap<(RegionId, u32), u32>,
+}
+
+impl<M: Memory> Storage<M> {
+    /// Opens the regions stored in the memory, or returns an error if the
+    /// memory holds an unsupported version of this format.
+    pub fn open(memory: M) -> Result<Self, String> {
+        let mut storage = Self {
+            memory,
+            initialized: false,
+            next_region: FIRST_REGION,
+            metadata_len: 0,
+            segments: vec![],
+            by_region: BTreeMap::new(),
+        };
+        if storage.memory.size() < HEADER_PAGES {
+            return Ok(storage);
+        }
+        let mut magic = [0u8; 8];
+        storage.memory.read(0, &mut magic);
+        if &magic != MAGIC {
+            return Ok(storage);
+        }
+        let version = storage.read_u32(8);
+        if version != VERSION {
+            return Err(format!(
+                "Unsupported version {} of the ledger stable storage, expected {}",
+                version, VERSION
+            ));
+        }
+        storage.initialized = true;
+        storage.next_region = storage.read_u32(12);
+        storage.metadata_len = storage.read_u64(16);
+        for segment in 0..storage.read_u32(24) {
+            let entry = SEGMENT_TABLE_OFFSET + u64::from(segment) * SEGMENT_TABLE_ENTRY_SIZE;
+            let owner = (storage.read_u32(entry), storage.read_u32(entry + 4));
+            storage.segments.push(owner);
+            if owner.0 != FREE_REGION {
+                storage.by_region.insert(owner, segment);
+            }
+        }
+        Ok(storage)
+    }
+
+    /// Whether the memory holds data in this format.
+    pub fn is_initialized(&self) -> bool {
+        self.initialized
+    }
+
+    /// Returns the ID of a new, empty region.
+    pub fn new_region(&mut self) -> RegionId {
+        let region = self.next_region;
+        self.next_region += 1;
+        if self.initialized {
+            self.write_u32(12, self.next_region);
+        }
+        region
+    }
+
+    /// Reads from a region. Bytes that were never written read as zero.
+    pub fn read(&self, region: RegionId, offset: u64, dst: &mut [u8]) {
+        let mut done = 0;
+        while done < dst.len() {
+            let position = offset + done as u64;
+            let in_segment = position % SEGMENT_SIZE;
+            let len = (dst.len() - done).min((SEGMENT_SIZE - in_segment) as usize);
+            let chunk = &mut dst[done..done + len];
+            match self.by_region.get(&(region, segment_index(position))) {
+                Some(&segment) => self
+                    .memory
+                    .read(segment_offset(segment) + in_segment, chunk),
+                None => chunk.iter_mut().for_each(|b| *b = 0),
+            }
+            done += len;
+        }
+    }
+
+    /// Writes to a region, allocating the segments that it needs.
+    pub fn write(&mut self, region: RegionId, offset: u64, src: &[u8]) {
+        let mut done = 0;
+        while done < src.len() {
+            let position = offset + done as u64;
+            let in_segment = position % SEGMENT_SIZE;
+            let len = (src.len() - done).min((SEGMENT_SIZE - in_segment) as usize);
+            let segment = self.segment(region, segment_index(position));
+            self.memory
+                .write(segment_offset(segment) + in_segment, &src[done..done + len]);
+            done += len;
+        }
+    }
+
+    /// Frees the segments of a region that only hold bytes before `end`.
+    /// These bytes read as zero afterwards.
+    pub fn free_before(&mut self, region: RegionId, end: u64) {
+        let freed: Vec<_> = self
+            .by_region
+            .range((region, 0)..(region, segment_index(end)))
+            .map(|(owner, segment)| (*owner, *segment))
+            .collect();
+        for (owner, segment) in freed {
+            self.by_region.remove(&owner);
+            self.set_owner(segment, (FREE_REGION, 0));
+        }
+    }
+
+    /// Frees all segments of a region.
+    pub fn free(&mut self, region: RegionId) {
+        self.free_before(region, u64::from(u32::MAX) * SEGMENT_SIZE)
+    }
+
+    /// Replaces the state persisted across upgrades.
+    pub fn write_metadata(&mut self, bytes: &[u8]) {
+        self.free(METADATA_REGION);
+        self.write(METADATA_REGION, 0, bytes);
+        self.metadata_len = bytes.len() as u64;
+        self.write_header();
+    }
+
+    /// Returns the state persisted across upgrades, or `None` if the memory
+    /// is not in this format.
+    pub fn read_metadata(&self) -> Option<Vec<u8>> {
+        if !self.initialized {
+            return None;
+        }
+        let mut bytes = vec![0; self.metadata_len as usize];
+        self.read(METADATA_REGION, 0, &mut bytes);
+        Some(bytes)
+    }
+
+    /// The number of segments used by all regions.
+    pub fn num_used_segments(&self) -> usize {
+        self.by_region.len()
+    }
+
+    fn segment(&mut self, region: RegionId, index: u32) -> u32 {
+        if let Some(segment) = self.by_region.get(&(region, index)) {
+            return *segment;
+        }
+        if !self.initialized {
+            self.write_header();
+        }
+        let segment = match self.segments.iter().position(|(r, _)| *r == FREE_REGION) {
+            Some(segment) => segment as u32,
+            None => {
+                let segment = self.segments.len() as u32;
+                assert!(
+                    u64::from(segment) < MAX_SEGMENTS,
+                    "The ledger stable storage is full"
+                );
+                self.segments.push((FREE_REGION, 0));
+                self.write_u32(24, self.segments.len() as u32);
+                segment
+            }
+        };
+        self.clear_segment(segment);
+        self.set_owner(segment, (region, index));
+        self.by_region.insert((region, index), segment);
+        segment
+    }
+
+    /// Makes sure the segment exists in memory and holds zeros only.
+    fn clear_segment(&mut self, segment: u32) {
+        let start = segment_offset(segment);
+        let end = start + SEGMENT_SIZE;
+        let size = self.memory.size() * PAGE_SIZE;
+        if size < end {
+            let pages = (end - size) / PAGE_SIZE;
+            assert!(
+                self.memory.grow(pages) != -1,
+                "Failed to grow the stable memory by {} pages",
+                pages
+            );
+        }
+        let zeros = vec![0; PAGE_SIZE as usize];
+        let mut offset = start;
+        while offset < size.min(end) {
+            self.memory.write(offset, &zeros);
+            offset += PAGE_SIZE;
+        }
+    }
+
+    fn set_owner(&mut self, segment: u32, owner: (RegionId, u32)) {
+        self.segments[segment as usize] = owner;
+        let entry = SEGMENT_TABLE_OFFSET + u64::from(segment) * SEGMENT_TABLE_ENTRY_SIZE;
+        self.write_u32(entry, owner.0);
+        self.write_u32(entry + 4, owner.1);
+    }
+
+    fn write_header(&mut self) {
+        if self.memory.size() < HEADER_PAGES {
+            let pages = HEADER_PAGES - self.memory.size();
+            assert!(
+                self.memory.grow(pages) != -1,
+                "Failed to grow the stable memory by {} pages",
+                pages
+            );
+        }
+        self.initialized = true;
+        self.memory.write(0, MAGIC);
+        self.write_u32(8, VERSION);
+        self.write_u32(12, self.next_region);
+        self.memory.write(16, &self.metadata_len.to_le_bytes());
+        self.write_u32(24, self.segments.len() as u32);
+    }
+
+    fn read_u32(&self, offset: u64) -> u32 {
+        let mut bytes = [0u8; 4];
+        self.memory.read(offset, &mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn read_u64(&self, offset: u64) -> u64 {
+        let mut bytes = [0u8; 8];
+        self.memory.read(offset, &mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn write_u32(&self, offset: u64, value: u32) {
+        self.memory.write(offset, &value.to_le_bytes());
+    }
+}
+
+fn segment_index(position: u64) -> u32 {
+    (position / SEGMENT_SIZE)
+        .try_into()
+        .expect("region offset out of range")
+}
+
+fn segment_offset(segment: u32) -> u64 {
+    (HEADER_PAGES + u64::from(segment) * SEGMENT_PAGES) * PAGE_SIZE
+}
+
+pub(crate) fn read_u64(region: RegionId, offset: u64) -> u64 {
+    let mut bytes = [0u8; 8];
+    with_storage(|s| s.read(region, offset, &mut bytes));
+    u64::from_le_bytes(bytes)
+}
+
+pub(crate) fn write_u64(region: RegionId, offset: u64, value: u64) {
+    with_storage(|s| s.write(region, offset, &value.to_le_bytes()))
+}
+
+/// A map from accounts to non-zero values, in an open addressing hash table
+/// with linear probing whose slots hold the account hash followed by the
+/// value. Zero values are not stored, so a zero value marks an empty slot.
+///
+/// The table doubles in size when it becomes half full. Rehashing a large
+/// table does not fit in a single message, so the entries of the previous
+/// table are moved a few slots at a time on every update, and lookups check
+/// both tables until all entries are moved.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct StableAccountMap {
+    table: Table,
+    /// The number of accounts in both tables.
+    len: u64,
+    /// The previous table, while its entries are being moved.
+    migration: Option<Migration>,
+}
+
+const SLOT_SIZE: u64 = 36;
+const MIN_CAPACITY: u64 = 1 << 16;
+/// The number of slots read at once when scanning the table.
+const SLOTS_PER_CHUNK: u64 = 1024;
+/// The number of slots of the previous table moved on every update. Moving
+/// more than two slots per update empties the previous table before the
+/// current one is half full.
+const SLOTS_MOVED_PER_UPDATE: u64 = 8;
+
+/// A hash table in a region of the stable storage.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+struct Table {
+    region: RegionId,
+    /// The number of slots, zero or a power of two.
+    capacity: u64,
+}
+
+/// The previous table, while its entries are moved to the current one.
+///
+/// The slots are moved in order, wrapping around, from an empty slot.
+/// Nothing is inserted in the previous table, so no cluster of entries spans
+/// that slot and removing an entry only moves entries that were not moved
+/// yet. Moved entries stay in place so that lookups can still probe through
+/// them, but their value is the one in the current table.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+struct Migration {
+    table: Table,
+    start: u64,
+    /// The number of slots moved so far.
+    moved: u64,
+}
+
+impl Default for StableAccountMap {
+    fn default() -> Self {
+        Self {
+            table: Table::new(0),
+            len: 0,
+            migration: None,
+        }
+    }
+}
+
+impl StableAccountMap {
+    /// Returns an empty table that holds `accounts` accounts without
+    /// growing.
+    pub fn with_capacity(accounts: usize) -> Self {
+        Self {
+            table: Table::new(MIN_CAPACITY.max((2 * accounts as u64).next_power_of_two())),
+            len: 0,
+            migration: None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the accounts and their values, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (AccountIdentifier, u64)> + '_ {
+        let unmoved = self.migration.iter().flat_map(|migration| {
+            let [(first_from, first_to), (second_from, second_to)] = migration.unmoved_slots();
+            migration
+                .table
+                .entries(first_from, first_to)
+                .chain(migration.table.entries(second_from, second_to))
+        });
+        self.table.entries(0, self.table.capacity).chain(unmoved)
+    }
+
+    /// Returns the slot of the previous table holding the account and its
+    /// value, if the account was not moved to the current table yet.
+    fn find_unmoved(&self, account: &AccountIdentifier) -> Option<(u64, u64)> {
+        let migration = self.migration.as_ref()?;
+        match migration.table.find(account) {
+            (slot, Some(value)) if !migration.is_moved(slot) => Some((slot, value)),
+            _ => None,
+        }
+    }
+
+    /// Moves the entries of up to `slots` slots of the previous table to the
+    /// current one, and frees the previous table once all are moved.
+    fn move_entries(&mut self, slots: u64) {
+        let migration = match &mut self.migration {
+            Some(migration) => migration,
+            None => return,
+        };
+        let mask = migration.table.capacity - 1;
+        let end = migration
+            .table
+            .capacity
+            .min(migration.moved.saturating_add(slots));
+        while migration.moved < end {
+            let slot = (migration.start + migration.moved) & mask;
+            if let Some((account, value)) = migration.table.read_slot(slot) {
+                let (target, _) = self.table.find(&account);
+                self.table.write_slot(target, Some((account, value)));
+            }
+            migration.moved += 1;
+        }
+        if migration.moved == migration.table.capacity {
+            with_storage(|s| s.free(migration.table.region));
+            self.migration = None;
+        }
+    }
+
+    fn grow(&mut self) {
+        // The previous growth is done long before the table is half full
+        // again, this only keeps the invariant of a single previous table.
+        self.move_entries(u64::MAX);
+        let table = Table::new(MIN_CAPACITY.max(self.table.capacity * 2));
+        let previous = std::mem::replace(&mut self.table, table);
+        if previous.capacity == 0 {
+            return;
+        }
+        let start = (0..previous.capacity)
+            .find(|slot| previous.read_slot(*slot).is_none())
+            .expect("A half full table has empty slots");
+        self.migration = Some(Migration {
+            table: previous,
+            start,
+            moved: 0,
+        });
+    }
+}
+
+impl Table {
+    fn new(capacity: u64) -> Self {
+        Self {
+            region: with_storage(|s| s.new_region()),
+            capacity,
+        }
+    }
+
+    /// Returns the entries of the slots `from..to`.
+    fn entries(&self, from: u64, to: u64) -> impl Iterator<Item = (AccountIdentifier, u64)> + '_ {
+        (from..to)
+            .step_by(SLOTS_PER_CHUNK as usize)
+            .flat_map(move |first| {
+                let slots = SLOTS_PER_CHUNK.min(to - first);
+                let mut bytes = vec![0; (slots * SLOT_SIZE) as usize];
+                with_storage(|s| s.read(self.region, first * SLOT_SIZE, &mut bytes));
+                bytes
+                    .chunks(SLOT_SIZE as usize)
+                    .filter_map(decode_slot)
+                    .collect::<Vec<_>>()
+            })
+    }
+
+    fn home(&self, account: &AccountIdentifier) -> u64 {
+        u64::from_le_bytes(account.hash[..8].try_into().unwrap()) & (self.capacity - 1)
+    }
+
+    fn read_slot(&self, slot: u64) -> Option<(AccountIdentifier, u64)> {
+        let mut bytes = [0u8; SLOT_SIZE as usize];
+        with_storage(|s| s.read(self.region, slot * SLOT_SIZE, &mut bytes));
+        decode_slot(&bytes)
+    }
+
+    fn write_slot(&self, slot: u64, entry: Option<(AccountIdentifier, u64)>) {
+        let mut bytes = [0u8; SLOT_SIZE as usize];
+        if let Some((account, value)) = entry {
+            bytes[..28].copy_from_slice(&account.hash);
+            bytes[28..].copy_from_slice(&value.to_le_bytes());
+        }
+        with_storage(|s| s.write(self.region, slot * SLOT_SIZE, &bytes));
+    }
+
+    /// Returns the slot holding the account, or the empty slot where it
+    /// would be inserted, and its value.
+    fn find(&self, account: &AccountIdentifier) -> (u64, Option<u64>) {
+        if self.capacity == 0 {
+            return (0, None);
+        }
+        let mut slot = self.home(account);
+        loop {
+            match self.read_slot(slot) {
+                None => return (slot, None),
+                Some((other, value)) if other == *account => return (slot, Some(value)),
+                Some(_) => slot = (slot + 1) & (self.capacity - 1),
+            }
+        }
+    }
+
+    /// Empties the slot, moving back the entries after it that would no
+    /// longer be found otherwise.
+    fn remove_slot(&self, slot: u64) {
+        let mask = self.capacity - 1;
+        let mut hole = slot;
+        let mut next = slot;
+        loop {
+            next = (next + 1) & mask;
+            let entry = match self.read_slot(next) {
+                None => break,
+                Some(entry) => entry,
+            };
+            let home = self.home(&entry.0);
+            let reachable_from_home = if hole <= next {
+                hole < home && home <= next
+            } else {
+                hole < home || home <= next
+            };
+            if !reachable_from_home {
+                self.write_slot(hole, Some(entry));
+                hole = next;
+            }
+        }
+        self.write_slot(hole, None);
+    }
+}
+
+impl Migration {
+    fn is_moved(&self, slot: u64) -> bool {
+        (slot + self.table.capacity - self.start) & (self.table.capacity - 1) < self.moved
+    }
+
+    /// The slots that were not moved yet, as two ranges.
+    fn unmoved_slots(&self) -> [(u64, u64); 2] {
+        let next = self.start + self.moved;
+        if next < self.table.capacity {
+            [(next, self.table.capacity), (0, self.start)]
+        } else {
+            [(next - self.table.capacity, self.start), (0, 0)]
+        }
+    }
+}
+
+fn decode_slot(bytes: &[u8]) -> Option<(AccountIdentifier, u64)> {
+    let value = u64::from_le_bytes(bytes[28..36].try_into().unwrap());
+    if value == 0 {
+        return None;
+    }
+    let account = AccountIdentifier {
+        hash: bytes[..28].try_into().unwrap(),
+    };
+    Some((account, value))
+}
+
+impl StableAccountMap {
+    pub fn get(&self, k: &AccountIdentifier) -> Option<u64> {
+        self.table
+            .find(k)
+            .1
+            .or_else(|| self.find_unmoved(k).map(|(_, value)| value))
+    }
+
+    /// Replaces the value of the account with the one returned by `f`,
+    /// which is passed the current value. A zero value removes the account.
+    pub fn update<F, E>(&mut self, k: AccountIdentifier, mut f: F) -> Result<u64, E>
+    where
+        F: FnMut(Option<u64>) -> Result<u64, E>,
+    {
+        self.move_entries(SLOTS_MOVED_PER_UPDATE);
+        let (slot, current) = self.table.find(&k);
+        let unmoved = match current {
+            Some(_) => None,
+            None => self.find_unmoved(&k),
+        };
+        let new_v = f(current.or_else(|| unmoved.map(|(_, value)| value)))?;
+        if let (Some((unmoved_slot, _)), Some(migration)) = (unmoved, &self.migration) {
+            // The account is moved to the current table by the insertion
+            // below, unless its value drops to zero.
+            migration.table.remove_slot(unmoved_slot);
+            self.len -= 1;
+        }
+        match current {
+            Some(_) if new_v == 0 => {
+                self.table.remove_slot(slot);
+                self.len -= 1;
+            }
+            Some(_) => self.table.write_slot(slot, Some((k, new_v))),
+            None if new_v == 0 => (),
+            None => {
+                let slot = if (self.len + 1) * 2 > self.table.capacity {
+                    self.grow();
+                    self.table.find(&k).0
+                } else {
+                    slot
+                };
+                self.table.write_slot(slot, Some((k, new_v)));
+                self.len += 1;
+            }
+        }
+        Ok(new_v)
+    }
+}
+
+/// The balances of the accounts, in e8s.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+pub struct StableBalances(StableAccountMap);
+
+impl StableBalances {
+    /// Returns an empty table that holds the balances of `accounts` accounts
+    /// without growing.
+    pub fn with_capacity(accounts: usize) -> Self {
+        Self(StableAccountMap::with_capacity(accounts))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns the accounts and their balances, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (AccountIdentifier, ICPTs)> + '_ {
+        self.0
+            .iter()
+            .map(|(account, e8s)| (account, ICPTs::from_e8s(e8s)))
+    }
+}
+
+impl BalancesStore for StableBalances {
+    fn get_balance(&self, k: &AccountIdentifier) -> Option<ICPTs> {
+        self.0.get(k).map(ICPTs::from_e8s)
+    }
+
+    fn update<F, E>(&mut self, k: AccountIdentifier, mut f: F) -> Result<ICPTs, E>
+    where
+        F: FnMut(Option<ICPTs>) -> Result<ICPTs, E>,
+    {
+        self.0
+            .update(k, |e8s| {
+                f(e8s.map(ICPTs::from_e8s)).map(|balance| balance.get_e8s())
+            })
+            .map(ICPTs::from_e8s)
+    }
+}
+
+/// The blocks that have not been archived yet, in a log of encoded blocks
+/// and an index of the offset of each block in the log. Archived blocks are
+/// dropped from the front of both.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct StableBlocks {
+    index: RegionId,
+    data: RegionId,
+    /// The number of blocks dropped from the front.
+    first: u64,
+    /// The number of blocks ever pushed.
+    end: u64,
+    /// The offset after the last block in the log.
+    data_end: u64,
+}
+
+impl Default for StableBlocks {
+    fn default() -> Self {
+        with_storage(|s| Self {
+            index: s.new_region(),
+            data: s.new_region(),
+            first: 0,
+            end: 0,
+            data_end: 0,
+        })
+    }
+}
+
+impl StableBlocks {
+    pub fn len(&self) -> usize {
+        (self.end - self.first) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.first == self.end
+    }
+
+    pub fn push(&mut self, block: &EncodedBlock) {
+        write_u64(self.index, self.end * 8, self.data_end);
+        with_storage(|s| s.write(self.data, self.data_end, &block.0));
+        self.data_end += block.0.len() as u64;
+        self.end += 1;
+    }
+
+    pub fn get(&self, i: usize) -> Option<EncodedBlock> {
+        if i >= self.len() {
+            return None;
+        }
+        self.get_range(i, i + 1).pop()
+    }
+
+    pub fn last(&self) -> Option<EncodedBlock> {
+        self.len().checked_sub(1).and_then(|i| self.get(i))
+    }
+
+    /// Returns the blocks at positions `from..to`, which must be in range.
+    pub fn get_range(&self, from: usize, to: usize) -> Vec<EncodedBlock> {
+        assert!(
+            from <= to && to <= self.len(),
+            "Block range {}..{} out of bounds, {} blocks available",
+            from,
+            to,
+            self.len()
+        );
+        if from == to {
+            return vec![];
+        }
+        let from = self.first + from as u64;
+        let to = self.first + to as u64;
+        let offsets: Vec<u64> = (from..=to).map(|i| self.offset(i)).collect();
+        let mut bytes = vec![0; (offsets[offsets.len() - 1] - offsets[0]) as usize];
+        with_storage(|s| s.read(self.data, offsets[0], &mut bytes));
+        offsets
+            .windows(2)
+            .map(|w| {
+                let start = (w[0] - offsets[0]) as usize;
+                let end = (w[1] - offsets[0]) as usize;
+                EncodedBlock(bytes[start..end].to_vec().into_boxed_slice())
+            })
+            .collect()
+    }
+
+    /// Drops the first `len` blocks, freeing the memory they used.
+    pub fn remove_front(&mut self, len: usize) {
+        assert!(
+            len <= self.len(),
+            "Asked to remove more blocks than present. Present: {}, to remove: {}",
+            self.len(),
+            len
+        );
+        self.first += len as u64;
+        let data_start = self.offset(self.first);
+        with_storage(|s| {
+            s.free_before(self.index, self.first * 8);
+            s.free_before(self.data, data_start);
+        });
+    }
+
+    fn offset(&self, i: u64) -> u64 {
+        if i == self.end {
+            self.data_end
+        } else {
+            read_u64(self.index, i * 8)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_types::PrincipalId;
+    use std::collections::HashMap;
+
+    fn account(i: u64) -> AccountIdentifier {
+        PrincipalId::new_user_test_id(i).into()
+    }
+
+    #[test]
+    fn regions_survive_reopening() {
+        let memory = VecMemory::default();
+        let mut storage = Storage::open(memory.clone()).unwrap();
+        assert_eq!(storage.read_metadata(), None);
+        let a = storage.new_region();
+        let b = storage.new_region();
+        storage.write(a, SEGMENT_SIZE - 2, &[1, 2, 3, 4]);
+        storage.write(b, 0, &[5, 6]);
+        storage.write_metadata(&[7, 8, 9]);
+
+        let mut storage = Storage::open(memory).unwrap();
+        assert_eq!(storage.read_metadata(), Some(vec![7, 8, 9]));
+        let mut bytes = [0u8; 4];
+        storage.read(a, SEGMENT_SIZE - 2, &mut bytes);
+        assert_eq!(bytes, [1, 2, 3, 4]);
+        storage.read(b, 0, &mut bytes);
+        assert_eq!(bytes, [5, 6, 0, 0]);
+        assert_ne!(storage.new_region(), b);
+    }
+
+    #[test]
+    fn freed_segments_are_reused_and_cleared() {
+        let mut storage = Storage::open(VecMemory::default()).unwrap();
+        let a = storage.new_region();
+        storage.write(a, 0, &[1; 16]);
+        storage.write(a, SEGMENT_SIZE, &[2; 16]);
+        assert_eq!(storage.num_used_segments(), 2);
+
+        storage.free_before(a, SEGMENT_SIZE + 1);
+        assert_eq!(storage.num_used_segments(), 1);
+        let mut bytes = [0u8; 2];
+        storage.read(a, 0, &mut bytes);
+        assert_eq!(bytes, [0, 0]);
+
+        let b = storage.new_region();
+        storage.write(b, 1, &[3]);
+        assert_eq!(storage.num_used_segments(), 2);
+        assert_eq!(storage.memory.size(), HEADER_PAGES + 2 * SEGMENT_PAGES);
+        storage.read(b, 0, &mut bytes);
+        assert_eq!(bytes, [0, 3]);
+    }
+
+    #[test]
+    fn storage_does_not_touch_memory_until_written() {
+        let memory = VecMemory::default();
+        memory.grow(1);
+        memory.write(0, &[1, 2, 3, 4]);
+        let mut storage = Storage::open(memory.clone()).unwrap();
+        assert!(!storage.is_initialized());
+        storage.new_region();
+        let mut bytes = [0u8; 4];
+        memory.read(0, &mut bytes);
+        assert_eq!(bytes, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn balances_match_a_hash_map() {
+        let mut balances = StableBalances::default();
+        let mut expected = HashMap::new();
+        // Enough accounts for the table to grow, with some removed again.
+        for i in 0..(MIN_CAPACITY / 2 + 100) {
+            let amount = ICPTs::from_e8s(i + 1);
+            balances
+                .update(account(i), |_| Ok::<_, ()>(amount))
+                .unwrap();
+            expected.insert(account(i), amount);
+        }
+        for i in (0..MIN_CAPACITY / 2).step_by(3) {
+            balances
+                .update(account(i), |_| Ok::<_, ()>(ICPTs::ZERO))
+                .unwrap();
+            expected.remove(&account(i));
+        }
+        assert_eq!(balances.0.table.capacity, 2 * MIN_CAPACITY);
+        assert_eq!(balances.0.migration, None);
+        assert_eq!(balances.len(), expected.len());
+        for i in 0..(MIN_CAPACITY / 2 + 100) {
+            assert_eq!(
+                balances.get_balance(&account(i)),
+                expected.get(&account(i)).cloned()
+            );
+        }
+        assert_eq!(balances.iter().collect::<HashMap<_, _>>(), expected);
+    }
+
+    #[test]
+    fn balances_grow_incrementally() {
+        let mut balances = StableBalances::default();
+        let mut expected = HashMap::new();
+        let set = |balances: &mut StableBalances, i: u64, e8s: u64| {
+            balances
+                .update(account(i), |_| Ok::<_, ()>(ICPTs::from_e8s(e8s)))
+                .unwrap();
+        };
+        for i in 0..=(MIN_CAPACITY / 2) {
+            set(&mut balances, i, i + 1);
+            expected.insert(account(i), ICPTs::from_e8s(i + 1));
+        }
+        let used_segments = with_storage(|s| s.num_used_segments());
+        assert_eq!(balances.0.table.capacity, 2 * MIN_CAPACITY);
+        let moved = balances.0.migration.as_ref().unwrap().moved;
+        assert!(moved <= SLOTS_MOVED_PER_UPDATE);
+
+        // Accounts that were not moved yet can be updated and removed.
+        for i in (0..MIN_CAPACITY / 2).step_by(5) {
+            if i % 2 == 0 {
+                set(&mut balances, i, 0);
+                expected.remove(&account(i));
+            } else {
+                set(&mut balances, i, 1);
+                expected.insert(account(i), ICPTs::from_e8s(1));
+            }
+        }
+        assert!(balances.0.migration.is_some());
+        assert_eq!(balances.len(), expected.len());
+        assert_eq!(balances.iter().collect::<HashMap<_, _>>(), expected);
+        for i in 0..=(MIN_CAPACITY / 2) {
+            assert_eq!(
+                balances.get_balance(&account(i)),
+                expected.get(&account(i)).cloned()
+            );
+        }
+
+        let mut i = MIN_CAPACITY;
+        while balances.0.migration.is_some() {
+            set(&mut balances, i, 7);
+            expected.insert(account(i), ICPTs::from_e8s(7));
+            i += 1;
+        }
+        assert_eq!(balances.0.table.capacity, 2 * MIN_CAPACITY);
+        assert_eq!(balances.len(), expected.len());
+        assert_eq!(balances.iter().collect::<HashMap<_, _>>(), expected);
+        // The previous table was freed.
+        assert!(with_storage(|s| s.num_used_segments()) < used_segments);
+    }
+
+    #[test]
+    fn balances_with_capacity_do_not_grow() {
+        let mut balances = StableBalances::with_capacity(MIN_CAPACITY as usize);
+        assert_eq!(balances.0.table.capacity, 2 * MIN_CAPACITY);
+        for i in 0..MIN_CAPACITY {
+            balances
+                .update(account(i), |_| Ok::<_, ()>(ICPTs::from_e8s(1)))
+                .unwrap();
+        }
+        assert_eq!(balances.0.table.capacity, 2 * MIN_CAPACITY);
+        assert_eq!(balances.0.migration, None);
+        assert_eq!(balances.len(), MIN_CAPACITY as usize);
+    }
+
+    #[test]
+    fn open_fails_on_unsupported_version() {
+        let memory = VecMemory::default();
+        Storage::open(memory.clone())
+            .unwrap()
+            .write_metadata(&[1, 2, 3]);
+        memory.write(8, &(VERSION + 1).to_le_bytes());
+        assert!(Storage::open(memory).is_err());
+    }
+
+    #[test]
+    fn blocks_can_be_dropped_from_the_front() {
+        let mut blocks = StableBlocks::default();
+        let block = |i: usize| EncodedBlock(vec![i as u8; 1 + i * 5000].into_boxed_slice());
+        for i in 0..100 {
+            blocks.push(&block(i));
+        }
+        let used_segments = with_storage(|s| s.num_used_segments());
+        assert_eq!(blocks.len(), 100);
+        assert_eq!(blocks.get(7), Some(block(7)));
+        assert_eq!(blocks.last(), Some(block(99)));
+        assert_eq!(blocks.get(100), None);
+
+        blocks.remove_front(60);
+        assert_eq!(blocks.len(), 40);
+        assert_eq!(blocks.get(0), Some(block(60)));
+        assert_eq!(
+            blocks.get_range(10, 13),
+            vec![block(70), block(71), block(72)]
+        );
+        assert!(blocks.get_range(40, 40).is_empty());
+        // The first 60 blocks filled the first segment of the log.
+        assert_eq!(with_storage(|s| s.num_used_segments()), used_segments - 1);
+    }
+}
@@ -3,6 +3,9 @@ use dfn_candid::{candid, candid_one, CandidOne};
 use dfn_protobuf::protobuf;
 use ic_canister_client::Sender;
 use ic_types::{CanisterId, PrincipalId};
+use ledger_canister::index::{
+    GetAccountTransactionsArgs, GetAccountTransactionsResult, IndexCanisterInitPayload, IndexStatus,
+};
 use ledger_canister::{
     AccountBalanceArgs, AccountIdentifier, ArchiveOptions, BinaryAccountBalanceArgs, Block,
     BlockArg, BlockHeight, BlockRes, EncodedBlock, GetBlocksArgs, GetBlocksRes, ICPTs,
@@ -1631,3 +1634,157 @@ fn call_with_cleanup() {
         Ok(())
     })
 }
+
+/// Waits until the index canister has synced `num_blocks` blocks, which it
+/// does in its heartbeats.
+async fn wait_for_index_sync(index: &Canister<'_>, num_blocks: u64) -> IndexStatus {
+    for _ in 0..100 {
+        let status: IndexStatus = index.query_("status", candid, ()).await.unwrap();
+        if status.num_blocks_synced >= num_blocks {
+            return status;
+        }
+        std::thread::sleep(Duration::from_millis(200));
+    }
+    panic!("The index did not sync {} blocks", num_blocks);
+}
+
+/// Returns the heights of all transactions of the account, fetching them in
+/// pages of `page_size`.
+async fn index_account_heights(
+    index: &Canister<'_>,
+    account: AccountIdentifier,
+    page_size: u64,
+) -> Vec<BlockHeight> {
+    let mut heights = vec![];
+    let mut start = None;
+    loop {
+        let page: GetAccountTransactionsResult = index
+            .query_(
+                "get_account_transactions",
+                candid_one,
+                GetAccountTransactionsArgs {
+                    account,
+                    start,
+                    max_results: page_size,
+                },
+            )
+            .await
+            .unwrap();
+        assert!(page.transactions.len() as u64 <= page_size);
+        heights.extend(page.transactions.iter().map(|tx| tx.block_height));
+        match page.next_start {
+            Some(next_start) => start = Some(next_start),
+            None => return heights,
+        }
+    }
+}
+
+// The index canister follows the ledger, fetching the archived blocks from the
+// archive nodes, and returns the transactions of an account in pages, from
+// the most recent to the oldest, also after an upgrade.
+#[test]
+fn index_test() {
+    local_test_e(|r| async move {
+        let proj = Project::new(env!("CARGO_MANIFEST_DIR"));
+
+        let minting_account = create_sender(0);
+        let alice = create_sender(1);
+        let bob = create_sender(2);
+        let carol = create_sender(3);
+
+        let mut accounts = HashMap::new();
+        accounts.insert(
+            alice.get_principal_id().into(),
+            ICPTs::from_icpts(100).unwrap(),
+        );
+        let archive_options = ArchiveOptions {
+            trigger_threshold: 8,
+            num_blocks_to_archive: 4,
+            node_max_memory_size_bytes: Some(example_block().encode().unwrap().size_bytes() * 16),
+            max_message_size_bytes: Some(1024 * 1024),
+            controller_id: CanisterId::from_u64(876),
+        };
+        let ledger = proj
+            .cargo_bin("ledger-canister")
+            .install_(
+                &r,
+                CandidOne(LedgerCanisterInitPayload::new(
+                    CanisterId::try_from(minting_account.get_principal_id())
+                        .unwrap()
+                        .into(),
+                    accounts,
+                    Some(archive_options),
+                    None,
+                    None,
+                    HashSet::new(),
+                )),
+            )
+            .await?;
+
+        // Block 0 mints the initial balance of alice.
+        let mut bob_heights = vec![];
+        let mut carol_heights = vec![];
+        for i in 0..11 {
+            let to = if i % 3 == 0 { &bob } else { &carol };
+            let height = simple_send(&ledger, to, &alice, 1000, TRANSACTION_FEE.get_e8s()).await?;
+            if i % 3 == 0 {
+                bob_heights.push(height);
+            } else {
+                carol_heights.push(height);
+            }
+        }
+        let num_blocks = 12;
+        // Some blocks were moved to an archive node.
+        ledger_assert_num_nodes(&ledger, 1).await;
+
+        let mut index = proj
+            .cargo_bin("ledger-index-canister")
+            .install_(
+                &r,
+                CandidOne(IndexCanisterInitPayload {
+                    ledger_canister_id: ledger.canister_id(),
+                }),
+            )
+            .await?;
+
+        let status = wait_for_index_sync(&index, num_blocks).await;
+        assert_eq!(status.num_blocks_synced, num_blocks);
+        assert_eq!(status.num_accounts, 3);
+
+        bob_heights.reverse();
+        carol_heights.reverse();
+        let alice_heights: Vec<BlockHeight> = (0..num_blocks).rev().collect();
+        let alice_account = alice.get_principal_id().into();
+        let bob_account = bob.get_principal_id().into();
+        let carol_account = carol.get_principal_id().into();
+        assert_eq!(
+            index_account_heights(&index, alice_account, 5).await,
+            alice_heights
+        );
+        assert_eq!(
+            index_account_heights(&index, bob_account, 2).await,
+            bob_heights
+        );
+        assert_eq!(
+            index_account_heights(&index, carol_account, 3).await,
+            carol_heights
+        );
+
+        index.upgrade_to_self_binary(Vec::new()).await?;
+
+        // The index is kept across upgrades and keeps following the ledger.
+        let height = simple_send(&ledger, &bob, &alice, 1000, TRANSACTION_FEE.get_e8s()).await?;
+        wait_for_index_sync(&index, num_blocks + 1).await;
+        bob_heights.insert(0, height);
+        assert_eq!(
+            index_account_heights(&index, bob_account, 2).await,
+            bob_heights
+        );
+        assert_eq!(
+            index_account_heights(&index, carol_account, 100).await,
+            carol_heights
+        );
+
+        Ok(())
+    });
+}
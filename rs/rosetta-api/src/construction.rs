@@ -0,0 +1,135 @@
+//! Offline construction of `send_pb` calls to the ledger.
+//!
+//! An unsigned transaction is the CBOR encoding of the `HttpCanisterUpdate`
+//! of the call; its signing payload is the domain separated message ID. A
+//! signed transaction is the CBOR encoding of the request envelope that is
+//! posted to the replica. Both are hex encoded in the API.
+use crate::convert::{principal_id_from_public_key, public_key_bytes};
+use crate::errors::ApiError;
+use crate::models;
+use dfn_protobuf::ProtoBuf;
+use ed25519_dalek::Verifier;
+use ic_canister_client::ed25519_public_key_to_der;
+use ic_interfaces::crypto::DOMAIN_IC_REQUEST;
+use ic_types::messages::{
+    Blob, HttpCanisterUpdate, HttpRequestEnvelope, HttpSubmitContent, MessageId, SignedRequestBytes,
+};
+use ic_types::{time::current_time_and_expiry_time, CanisterId, PrincipalId};
+use ledger_canister::{AccountIdentifier, SendArgs, Transaction};
+use on_wire::{FromWire, IntoWire};
+use std::convert::TryFrom;
+
+/// The ledger method that transactions call.
+pub const SEND_METHOD: &str = "send_pb";
+
+/// Builds the update call of `send_args` to the ledger by the given sender,
+/// which expires at the end of the maximum ingress TTL from now.
+pub fn make_send_update(
+    ledger_canister_id: &CanisterId,
+    sender: PrincipalId,
+    send_args: SendArgs,
+) -> Result<HttpCanisterUpdate, ApiError> {
+    let arg = ProtoBuf(send_args)
+        .into_bytes()
+        .map_err(|e| ApiError::InternalError(format!("Failed to encode send args: {}", e)))?;
+    Ok(HttpCanisterUpdate {
+        canister_id: Blob(ledger_canister_id.get().into_vec()),
+        method_name: SEND_METHOD.to_string(),
+        arg: Blob(arg),
+        sender: Blob(sender.into_vec()),
+        ingress_expiry: current_time_and_expiry_time().1.as_nanos_since_unix_epoch(),
+        nonce: None,
+    })
+}
+
+/// The bytes that the sender signs to authenticate the update.
+pub fn signable_bytes(message_id: &MessageId) -> Vec<u8> {
+    let mut bytes = DOMAIN_IC_REQUEST.to_vec();
+    bytes.extend_from_slice(message_id.as_bytes());
+    bytes
+}
+
+pub fn encode_unsigned(update: &HttpCanisterUpdate) -> Result<String, ApiError> {
+    serde_cbor::to_vec(update)
+        .map(hex::encode)
+        .map_err(|e| ApiError::InternalError(format!("Failed to encode transaction: {}", e)))
+}
+
+pub fn decode_unsigned(unsigned: &str) -> Result<HttpCanisterUpdate, ApiError> {
+    let bytes = hex::decode(unsigned)
+        .map_err(|e| ApiError::InvalidRequest(format!("Invalid unsigned transaction: {}", e)))?;
+    serde_cbor::from_slice(&bytes)
+        .map_err(|e| ApiError::InvalidRequest(format!("Invalid unsigned transaction: {}", e)))
+}
+
+pub fn encode_signed(envelope: HttpRequestEnvelope<HttpSubmitContent>) -> Result<String, ApiError> {
+    SignedRequestBytes::try_from(envelope)
+        .map(|bytes| hex::encode(bytes.as_ref()))
+        .map_err(|e| ApiError::InternalError(format!("Failed to encode transaction: {}", e)))
+}
+
+pub fn decode_signed(signed: &str) -> Result<HttpRequestEnvelope<HttpSubmitContent>, ApiError> {
+    let bytes = hex::decode(signed)
+        .map_err(|e| ApiError::InvalidRequest(format!("Invalid signed transaction: {}", e)))?;
+    HttpRequestEnvelope::try_from(&SignedRequestBytes::from(bytes))
+        .map_err(|e| ApiError::InvalidRequest(format!("Invalid signed transaction: {}", e)))
+}
+
+/// Attaches the signature of the sender to the update, after checking that
+/// it was made with the sender's key over the update's signing payload.
+pub fn combine(
+    update: HttpCanisterUpdate,
+    signature: &models::Signature,
+) -> Result<HttpRequestEnvelope<HttpSubmitContent>, ApiError> {
+    let public_key = public_key_bytes(&signature.public_key)?;
+    if principal_id_from_public_key(&signature.public_key)?.as_slice() != update.sender.0 {
+        return Err(ApiError::InvalidRequest(
+            "The signature was not made with the key of the sender".to_string(),
+        ));
+    }
+    let signature_bytes = hex::decode(&signature.hex_bytes)
+        .map_err(|e| ApiError::InvalidRequest(format!("Invalid signature: {}", e)))?;
+
+    let message_id = update.id();
+    let verifying_key = ed25519_dalek::PublicKey::from_bytes(&public_key)
+        .map_err(|e| ApiError::InvalidRequest(format!("Invalid public key: {}", e)))?;
+    let ed25519_signature = ed25519_dalek::Signature::try_from(signature_bytes.as_slice())
+        .map_err(|e| ApiError::InvalidRequest(format!("Invalid signature: {}", e)))?;
+    verifying_key
+        .verify(&signable_bytes(&message_id), &ed25519_signature)
+        .map_err(|e| ApiError::InvalidRequest(format!("Invalid signature: {}", e)))?;
+
+    Ok(HttpRequestEnvelope {
+        content: HttpSubmitContent::Call { update },
+        sender_pubkey: Some(Blob(ed25519_public_key_to_der(public_key))),
+        sender_sig: Some(Blob(signature_bytes)),
+        sender_delegation: None,
+    })
+}
+
+/// Recovers the ledger transaction that executing the update creates, if
+/// it succeeds.
+pub fn update_to_transaction(update: &HttpCanisterUpdate) -> Result<Transaction, ApiError> {
+    if update.method_name != SEND_METHOD {
+        return Err(ApiError::InvalidRequest(format!(
+            "Unsupported method {}",
+            update.method_name
+        )));
+    }
+    let sender = PrincipalId::try_from(update.sender.0.as_slice())
+        .map_err(|e| ApiError::InvalidRequest(format!("Invalid sender: {}", e)))?;
+    let send_args = ProtoBuf::<SendArgs>::from_bytes(update.arg.0.clone())
+        .map_err(|e| ApiError::InvalidRequest(format!("Invalid send args: {}", e)))?
+        .get();
+    let created_at_time = send_args.created_at_time.ok_or_else(|| {
+        ApiError::InvalidRequest("The transaction has no creation time".to_string())
+    })?;
+    Ok(Transaction::new(
+        AccountIdentifier::new(sender, send_args.from_subaccount),
+        send_args.to,
+        send_args.amount,
+        send_args.fee,
+        send_args.memo,
+        created_at_time,
+    ))
+}
@@ -0,0 +1,256 @@
+//! Conversions between the ledger's types and the Rosetta API models.
+use crate::errors::ApiError;
+use crate::models;
+use crate::store::HashedBlock;
+use crate::{DEFAULT_TOKEN_SYMBOL, OPERATION_STATUS_COMPLETED};
+use ic_canister_client::ed25519_public_key_to_der;
+use ic_types::PrincipalId;
+use ledger_canister::{
+    AccountIdentifier, BlockHeight, EncodedBlock, HashOf, ICPTs, Operation, TimeStamp, Transaction,
+    DECIMAL_PLACES,
+};
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+pub const TRANSACTION: &str = "TRANSACTION";
+pub const FEE: &str = "FEE";
+pub const MINT: &str = "MINT";
+pub const BURN: &str = "BURN";
+
+/// All operation types, as listed in `/network/options`.
+pub const OPERATION_TYPES: [&str; 4] = [TRANSACTION, FEE, MINT, BURN];
+
+pub fn icp_currency() -> models::Currency {
+    models::Currency {
+        symbol: DEFAULT_TOKEN_SYMBOL.to_string(),
+        decimals: DECIMAL_PLACES,
+    }
+}
+
+pub fn amount(e8s: i128) -> models::Amount {
+    models::Amount {
+        value: e8s.to_string(),
+        currency: icp_currency(),
+    }
+}
+
+fn debit(icpts: ICPTs) -> models::Amount {
+    amount(-i128::from(icpts.get_e8s()))
+}
+
+fn credit(icpts: ICPTs) -> models::Amount {
+    amount(i128::from(icpts.get_e8s()))
+}
+
+/// Parses the signed number of e8s of an amount, which must be in ICP.
+pub fn from_amount(amount: &models::Amount) -> Result<i128, ApiError> {
+    if amount.currency != icp_currency() {
+        return Err(ApiError::InvalidRequest(format!(
+            "Unsupported currency {:?}",
+            amount.currency
+        )));
+    }
+    i128::from_str(&amount.value)
+        .map_err(|e| ApiError::InvalidRequest(format!("Invalid amount {}: {}", amount.value, e)))
+}
+
+pub fn to_model_account_identifier(account: &AccountIdentifier) -> models::AccountIdentifier {
+    models::AccountIdentifier {
+        address: account.to_hex(),
+    }
+}
+
+pub fn from_model_account_identifier(
+    account: &models::AccountIdentifier,
+) -> Result<AccountIdentifier, ApiError> {
+    AccountIdentifier::from_hex(&account.address).map_err(|e| {
+        ApiError::InvalidRequest(format!("Invalid account {}: {}", account.address, e))
+    })
+}
+
+pub fn block_identifier(hb: &HashedBlock) -> models::BlockIdentifier {
+    models::BlockIdentifier {
+        index: hb.index,
+        hash: hb.hash.to_string(),
+    }
+}
+
+pub fn parse_block_hash(hash: &str) -> Result<HashOf<EncodedBlock>, ApiError> {
+    HashOf::from_str(hash)
+        .map_err(|e| ApiError::InvalidRequest(format!("Invalid block hash {}: {}", hash, e)))
+}
+
+pub fn timestamp_millis(timestamp: TimeStamp) -> u64 {
+    timestamp.as_nanos_since_unix_epoch() / 1_000_000
+}
+
+pub fn transaction_identifier(transaction: &Transaction) -> models::TransactionIdentifier {
+    models::TransactionIdentifier {
+        hash: transaction.hash().to_string(),
+    }
+}
+
+/// Converts a synced block whose parent is `parent` (which is the block
+/// itself for the genesis block) to a Rosetta block with one transaction.
+pub fn block_to_model(hb: &HashedBlock, parent: &HashedBlock) -> Result<models::Block, ApiError> {
+    let block = hb
+        .block
+        .decode()
+        .map_err(|e| ApiError::InternalError(format!("Failed to decode block: {}", e)))?;
+    Ok(models::Block {
+        block_identifier: block_identifier(hb),
+        parent_block_identifier: block_identifier(parent),
+        timestamp: timestamp_millis(block.timestamp),
+        transactions: vec![transaction_to_model(
+            &block.transaction,
+            Some(OPERATION_STATUS_COMPLETED),
+        )],
+    })
+}
+
+pub fn transaction_to_model(
+    transaction: &Transaction,
+    status: Option<&str>,
+) -> models::Transaction {
+    models::Transaction {
+        transaction_identifier: transaction_identifier(transaction),
+        operations: operation_to_model(&transaction.operation, status),
+        metadata: models::TransactionMetadata {
+            memo: transaction.memo.0,
+            created_at_time: transaction.created_at_time.as_nanos_since_unix_epoch(),
+        },
+    }
+}
+
+/// Splits a ledger operation into Rosetta operations: a transfer becomes a
+/// debit and a credit `TRANSACTION` and a `FEE` debit of the sender.
+pub fn operation_to_model(operation: &Operation, status: Option<&str>) -> Vec<models::Operation> {
+    let op = |index: u64, type_: &str, account: &AccountIdentifier, amount: models::Amount| {
+        models::Operation {
+            operation_identifier: models::OperationIdentifier { index },
+            related_operations: vec![],
+            type_: type_.to_string(),
+            status: status.map(str::to_string),
+            account: to_model_account_identifier(account),
+            amount,
+        }
+    };
+    match operation {
+        Operation::Transfer {
+            from,
+            to,
+            amount,
+            fee,
+        } => vec![
+            op(0, TRANSACTION, from, debit(*amount)),
+            op(1, TRANSACTION, to, credit(*amount)),
+            op(2, FEE, from, debit(*fee)),
+        ],
+        Operation::Mint { to, amount } => vec![op(0, MINT, to, credit(*amount))],
+        Operation::Burn { from, amount } => vec![op(0, BURN, from, debit(*amount))],
+    }
+}
+
+/// A transfer described by the operations of a construction request.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Transfer {
+    pub from: AccountIdentifier,
+    pub to: AccountIdentifier,
+    pub amount: ICPTs,
+    pub fee: ICPTs,
+}
+
+/// Converts the operations of a construction request to a transfer. Only
+/// transfers are supported, i.e. the operations must be a `TRANSACTION`
+/// debit, a `TRANSACTION` credit of the same amount, and a `FEE` debit of
+/// the sender, in any order.
+pub fn operations_to_transfer(operations: &[models::Operation]) -> Result<Transfer, ApiError> {
+    let mut debit = None;
+    let mut credit = None;
+    let mut fee = None;
+    for op in operations {
+        let account = from_model_account_identifier(&op.account)?;
+        let value = from_amount(&op.amount)?;
+        let slot = match (op.type_.as_str(), value < 0) {
+            (TRANSACTION, true) => &mut debit,
+            (TRANSACTION, false) => &mut credit,
+            (FEE, true) => &mut fee,
+            _ => {
+                return Err(ApiError::InvalidRequest(format!(
+                    "Unsupported operation {} of {}",
+                    op.type_, op.amount.value
+                )))
+            }
+        };
+        if slot.replace((account, value.unsigned_abs())).is_some() {
+            return Err(ApiError::InvalidRequest(format!(
+                "Duplicate {} operation",
+                op.type_
+            )));
+        }
+    }
+    let missing = |name: &str| ApiError::InvalidRequest(format!("Missing {} operation", name));
+    let (from, debited) = debit.ok_or_else(|| missing("debit"))?;
+    let (to, credited) = credit.ok_or_else(|| missing("credit"))?;
+    let (fee_payer, fee) = fee.ok_or_else(|| missing("fee"))?;
+    if debited != credited {
+        return Err(ApiError::InvalidRequest(format!(
+            "The debited amount {} differs from the credited amount {}",
+            debited, credited
+        )));
+    }
+    if fee_payer != from {
+        return Err(ApiError::InvalidRequest(
+            "The fee must be paid by the sender".to_string(),
+        ));
+    }
+    let to_icpts = |e8s: u128| {
+        u64::try_from(e8s)
+            .map(ICPTs::from_e8s)
+            .map_err(|_| ApiError::InvalidRequest(format!("Amount {} is too large", e8s)))
+    };
+    Ok(Transfer {
+        from,
+        to,
+        amount: to_icpts(debited)?,
+        fee: to_icpts(fee)?,
+    })
+}
+
+/// Returns the raw bytes of an Ed25519 public key.
+pub fn public_key_bytes(public_key: &models::PublicKey) -> Result<Vec<u8>, ApiError> {
+    let bytes = hex::decode(&public_key.hex_bytes)
+        .map_err(|e| ApiError::InvalidRequest(format!("Invalid public key: {}", e)))?;
+    if bytes.len() != ed25519_dalek::PUBLIC_KEY_LENGTH {
+        return Err(ApiError::InvalidRequest(format!(
+            "Invalid public key: expected {} bytes, got {}",
+            ed25519_dalek::PUBLIC_KEY_LENGTH,
+            bytes.len()
+        )));
+    }
+    Ok(bytes)
+}
+
+/// The self-authenticating principal of an Ed25519 public key.
+pub fn principal_id_from_public_key(
+    public_key: &models::PublicKey,
+) -> Result<PrincipalId, ApiError> {
+    let der = ed25519_public_key_to_der(public_key_bytes(public_key)?);
+    Ok(PrincipalId::new_self_authenticating(&der))
+}
+
+/// The account with the default subaccount of the principal of the key.
+pub fn account_from_public_key(
+    public_key: &models::PublicKey,
+) -> Result<AccountIdentifier, ApiError> {
+    Ok(AccountIdentifier::new(
+        principal_id_from_public_key(public_key)?,
+        None,
+    ))
+}
+
+/// Returns the index of the block whose successor is `index`, i.e. the
+/// genesis block is its own parent.
+pub fn parent_index(index: BlockHeight) -> BlockHeight {
+    index.saturating_sub(1)
+}
@@ -0,0 +1,115 @@
+use crate::models;
+use crate::store::BlockStoreError;
+use std::fmt;
+
+/// An error returned by the endpoints of the server. Each variant has a
+/// fixed code that is advertised in `/network/options`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ApiError {
+    /// The request is malformed, e.g. it contains an invalid account or
+    /// operation.
+    InvalidRequest(String),
+    /// The network identifier of the request is not the one of this server.
+    InvalidNetworkId(String),
+    /// The requested block is not in the local store (yet).
+    BlockNotFound(String),
+    /// The local store is empty because no block has been synced yet.
+    NotAvailableOffline(String),
+    /// The signed transaction was not accepted by the replica.
+    SubmitFailed(String),
+    /// Talking to the ledger failed; the request may succeed when retried.
+    LedgerUnavailable(String),
+    InternalError(String),
+}
+
+impl ApiError {
+    pub fn code(&self) -> u32 {
+        match self {
+            ApiError::InvalidRequest(_) => 700,
+            ApiError::InvalidNetworkId(_) => 701,
+            ApiError::BlockNotFound(_) => 702,
+            ApiError::NotAvailableOffline(_) => 703,
+            ApiError::SubmitFailed(_) => 704,
+            ApiError::LedgerUnavailable(_) => 705,
+            ApiError::InternalError(_) => 706,
+        }
+    }
+
+    pub fn is_retriable(&self) -> bool {
+        matches!(
+            self,
+            ApiError::BlockNotFound(_)
+                | ApiError::NotAvailableOffline(_)
+                | ApiError::LedgerUnavailable(_)
+        )
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            ApiError::InvalidRequest(_) => "Invalid request",
+            ApiError::InvalidNetworkId(_) => "Invalid network identifier",
+            ApiError::BlockNotFound(_) => "Block not found",
+            ApiError::NotAvailableOffline(_) => "No blocks synced yet",
+            ApiError::SubmitFailed(_) => "Transaction submission failed",
+            ApiError::LedgerUnavailable(_) => "Ledger unavailable",
+            ApiError::InternalError(_) => "Internal error",
+        }
+    }
+
+    fn details(&self) -> &str {
+        match self {
+            ApiError::InvalidRequest(d)
+            | ApiError::InvalidNetworkId(d)
+            | ApiError::BlockNotFound(d)
+            | ApiError::NotAvailableOffline(d)
+            | ApiError::SubmitFailed(d)
+            | ApiError::LedgerUnavailable(d)
+            | ApiError::InternalError(d) => d,
+        }
+    }
+
+    /// All errors, without details, as listed in `/network/options`.
+    pub fn all() -> Vec<ApiError> {
+        vec![
+            ApiError::InvalidRequest(String::new()),
+            ApiError::InvalidNetworkId(String::new()),
+            ApiError::BlockNotFound(String::new()),
+            ApiError::NotAvailableOffline(String::new()),
+            ApiError::SubmitFailed(String::new()),
+            ApiError::LedgerUnavailable(String::new()),
+            ApiError::InternalError(String::new()),
+        ]
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.description(), self.details())
+    }
+}
+
+impl From<ApiError> for models::Error {
+    fn from(err: ApiError) -> Self {
+        let details = if err.details().is_empty() {
+            None
+        } else {
+            Some(serde_json::json!({ "error_message": err.details() }))
+        };
+        models::Error {
+            code: err.code(),
+            message: err.description().to_string(),
+            retriable: err.is_retriable(),
+            details,
+        }
+    }
+}
+
+impl From<BlockStoreError> for ApiError {
+    fn from(err: BlockStoreError) -> Self {
+        match err {
+            BlockStoreError::NotFound(msg) => ApiError::BlockNotFound(msg),
+            BlockStoreError::InvalidChain(msg) => ApiError::InternalError(msg),
+            BlockStoreError::Other(msg) => ApiError::InternalError(msg),
+        }
+    }
+}
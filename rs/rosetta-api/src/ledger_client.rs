@@ -0,0 +1,411 @@
+//! Access to the ledger canister and its archive nodes.
+use crate::errors::ApiError;
+use crate::store::{BlockStore, HashedBlock};
+use async_trait::async_trait;
+use dfn_protobuf::ProtoBuf;
+use ic_canister_client::{update_path, Agent, HttpStatusCode, Sender};
+use ic_certified_vars::verify_certified_tree;
+use ic_crypto_tree_hash::{lookup_path, LabeledTree};
+use ic_types::crypto::threshold_sig::ThresholdSigPublicKey;
+use ic_types::messages::{HttpRequestEnvelope, HttpSubmitContent, SignedRequestBytes};
+use ic_types::CanisterId;
+use ledger_canister::protobuf::{ArchiveIndexResponse, TipOfChainRequest};
+use ledger_canister::{
+    BlockHeight, EncodedBlock, GetBlocksArgs, GetBlocksRes, HashOf, TipOfChainRes, HASH_LENGTH,
+};
+use on_wire::{FromWire, IntoWire};
+use std::convert::TryFrom;
+use std::time::Duration;
+use url::Url;
+
+/// The maximum number of blocks requested with one `get_blocks_pb` call.
+const MAX_BLOCKS_PER_REQUEST: u64 = 2000;
+
+const SUBMIT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[async_trait]
+pub trait LedgerAccess {
+    /// The store of the blocks synced so far.
+    fn read_blocks(&self) -> &BlockStore;
+
+    /// Fetches the blocks that were added to the ledger since the last sync
+    /// and appends them to the store.
+    async fn sync_blocks(&self) -> Result<(), ApiError>;
+
+    fn ledger_canister_id(&self) -> &CanisterId;
+
+    /// Submits the signed update call to the ledger, without waiting for it
+    /// to be executed.
+    async fn submit(
+        &self,
+        envelope: HttpRequestEnvelope<HttpSubmitContent>,
+    ) -> Result<(), ApiError>;
+}
+
+/// The queries to the ledger canister and its archive nodes that are needed
+/// to sync blocks.
+#[async_trait]
+pub trait LedgerCanister {
+    /// Returns the height of the last block of the ledger, together with the
+    /// certificate of the ledger's certified data, i.e., the hash of that
+    /// block.
+    async fn tip_of_chain(&self) -> Result<TipOfChainRes, ApiError>;
+
+    /// Returns the (inclusive) ranges of blocks stored in the archive nodes,
+    /// with the node that stores them.
+    async fn archive_index(&self) -> Result<Vec<(BlockHeight, BlockHeight, CanisterId)>, ApiError>;
+
+    /// Returns up to `length` blocks from height `start` that are stored in
+    /// `canister_id`, which is either the ledger or one of its archive nodes.
+    async fn get_blocks(
+        &self,
+        canister_id: &CanisterId,
+        start: BlockHeight,
+        length: u64,
+    ) -> Result<Vec<EncodedBlock>, ApiError>;
+}
+
+/// Queries the ledger canister and its archive nodes through a replica.
+struct AgentLedgerCanister {
+    agent: Agent,
+    canister_id: CanisterId,
+}
+
+impl AgentLedgerCanister {
+    async fn query<Arg, Res>(
+        &self,
+        canister_id: &CanisterId,
+        method: &str,
+        arg: Arg,
+    ) -> Result<Res, ApiError>
+    where
+        ProtoBuf<Arg>: IntoWire,
+        ProtoBuf<Res>: FromWire,
+    {
+        let bytes = ProtoBuf(arg)
+            .into_bytes()
+            .map_err(|e| ApiError::InternalError(format!("Failed to encode {}: {}", method, e)))?;
+        let reply = self
+            .agent
+            .execute_query(canister_id, method, bytes)
+            .await
+            .map_err(|e| ApiError::LedgerUnavailable(format!("{} failed: {}", method, e)))?
+            .ok_or_else(|| ApiError::LedgerUnavailable(format!("{} returned no reply", method)))?;
+        ProtoBuf::<Res>::from_bytes(reply)
+            .map(|res| res.get())
+            .map_err(|e| ApiError::InternalError(format!("Failed to decode {}: {}", method, e)))
+    }
+}
+
+#[async_trait]
+impl LedgerCanister for AgentLedgerCanister {
+    async fn tip_of_chain(&self) -> Result<TipOfChainRes, ApiError> {
+        self.query(&self.canister_id, "tip_of_chain_pb", TipOfChainRequest {})
+            .await
+    }
+
+    async fn archive_index(&self) -> Result<Vec<(BlockHeight, BlockHeight, CanisterId)>, ApiError> {
+        let response: ArchiveIndexResponse = self
+            .query(&self.canister_id, "get_archive_index_pb", ())
+            .await?;
+        response
+            .entries
+            .into_iter()
+            .map(|entry| {
+                let principal_id = entry.canister_id.ok_or_else(|| {
+                    ApiError::InternalError("Archive index entry without canister".to_string())
+                })?;
+                let canister_id = CanisterId::try_from(principal_id).map_err(|e| {
+                    ApiError::InternalError(format!("Invalid archive canister: {}", e))
+                })?;
+                Ok((entry.height_from, entry.height_to, canister_id))
+            })
+            .collect()
+    }
+
+    async fn get_blocks(
+        &self,
+        canister_id: &CanisterId,
+        start: BlockHeight,
+        length: u64,
+    ) -> Result<Vec<EncodedBlock>, ApiError> {
+        let GetBlocksRes(blocks) = self
+            .query(
+                canister_id,
+                "get_blocks_pb",
+                GetBlocksArgs::new(start, length as usize),
+            )
+            .await?;
+        blocks.map_err(ApiError::LedgerUnavailable)
+    }
+}
+
+/// Accesses the ledger on the Internet Computer through a replica.
+///
+/// Synced blocks are only stored once the hash of the last one matches the
+/// certified data of the ledger, as certified by the Internet Computer with
+/// the root key.
+pub struct LedgerClient {
+    blocks: BlockStore,
+    canister_id: CanisterId,
+    root_key: ThresholdSigPublicKey,
+    ledger_canister: Box<dyn LedgerCanister + Send + Sync>,
+    agent: Agent,
+    url: Url,
+}
+
+impl LedgerClient {
+    pub fn new(
+        url: Url,
+        canister_id: CanisterId,
+        root_key: ThresholdSigPublicKey,
+        blocks: BlockStore,
+    ) -> Self {
+        let agent = Agent::new(url.clone(), Sender::Anonymous).with_root_key(root_key);
+        let ledger_canister = AgentLedgerCanister {
+            agent: agent.clone(),
+            canister_id,
+        };
+        Self::new_with_ledger_canister(
+            url,
+            canister_id,
+            root_key,
+            blocks,
+            Box::new(ledger_canister),
+        )
+    }
+
+    /// Like `new`, but syncs blocks through `ledger_canister` instead of
+    /// querying the replica at `url`.
+    pub fn new_with_ledger_canister(
+        url: Url,
+        canister_id: CanisterId,
+        root_key: ThresholdSigPublicKey,
+        blocks: BlockStore,
+        ledger_canister: Box<dyn LedgerCanister + Send + Sync>,
+    ) -> Self {
+        Self {
+            blocks,
+            canister_id,
+            root_key,
+            ledger_canister,
+            agent: Agent::new(url.clone(), Sender::Anonymous).with_root_key(root_key),
+            url,
+        }
+    }
+
+    /// Checks that `certificate` is a valid certificate of the Internet
+    /// Computer and returns the certified data of the ledger in it, i.e., the
+    /// hash of the tip of the ledger's chain.
+    fn certified_tip_hash(
+        &self,
+        certificate: Option<&[u8]>,
+    ) -> Result<HashOf<EncodedBlock>, ApiError> {
+        let certificate = certificate.ok_or_else(|| {
+            ApiError::LedgerUnavailable("tip_of_chain_pb returned no certificate".to_string())
+        })?;
+        let (tree, _time) = verify_certified_tree(certificate, &self.canister_id, &self.root_key)
+            .map_err(|e| {
+            ApiError::InternalError(format!("Invalid certificate of the tip: {}", e))
+        })?;
+        let canister_label = self.canister_id.get().into_vec();
+        match lookup_path(&tree, &[b"canister", &canister_label, b"certified_data"]) {
+            Some(LabeledTree::Leaf(certified_data)) => {
+                let bytes =
+                    <[u8; HASH_LENGTH]>::try_from(certified_data.as_slice()).map_err(|_| {
+                        ApiError::InternalError(format!(
+                            "The certified data {} of the ledger is not a block hash",
+                            hex::encode(certified_data)
+                        ))
+                    })?;
+                Ok(HashOf::new(bytes))
+            }
+            _ => Err(ApiError::InternalError(
+                "The certificate of the tip lacks the certified data of the ledger".to_string(),
+            )),
+        }
+    }
+
+    /// Fetches the blocks `[start, end)` backwards, from the archive nodes
+    /// that store them or from the ledger itself, and stages them batch by
+    /// batch. Block `end - 1` must have the hash `end_hash`, and every other
+    /// block the parent hash of its successor, so every staged block is
+    /// vouched for by `end_hash`. Returns the parent hash of block `start`.
+    async fn fetch_and_stage_blocks(
+        &self,
+        archive_index: &[(BlockHeight, BlockHeight, CanisterId)],
+        start: BlockHeight,
+        end: BlockHeight,
+        end_hash: HashOf<EncodedBlock>,
+    ) -> Result<Option<HashOf<EncodedBlock>>, ApiError> {
+        let mut end = end;
+        let mut expected_hash = Some(end_hash);
+        let mut batch_size = MAX_BLOCKS_PER_REQUEST;
+        while start < end {
+            // Blocks are fetched from the archive node that stores them, and
+            // from the ledger itself if they were not archived.
+            let last = end - 1;
+            let (canister_id, range_start) = archive_index
+                .iter()
+                .find(|(from, to, _)| (*from..=*to).contains(&last))
+                .map_or_else(
+                    || {
+                        let first_unarchived = archive_index.iter().map(|(_, to, _)| to + 1).max();
+                        (self.canister_id, first_unarchived.unwrap_or(0))
+                    },
+                    |(from, _, canister_id)| (*canister_id, *from),
+                );
+            let mut length = (end - start.max(range_start)).min(batch_size);
+            let blocks = loop {
+                let blocks = self
+                    .ledger_canister
+                    .get_blocks(&canister_id, end - length, length)
+                    .await?;
+                if blocks.is_empty() {
+                    return Err(ApiError::LedgerUnavailable(format!(
+                        "{} returned no blocks from height {}",
+                        canister_id,
+                        end - length
+                    )));
+                }
+                if blocks.len() as u64 >= length {
+                    break blocks;
+                }
+                // The canister serves fewer blocks per call than requested,
+                // so the batch is requested again such that it ends at `end`.
+                length = blocks.len() as u64;
+                batch_size = length;
+            };
+
+            let mut batch = Vec::with_capacity(length as usize);
+            for (offset, block) in blocks.into_iter().take(length as usize).enumerate().rev() {
+                let index = end - length + offset as u64;
+                if Some(block.hash()) != expected_hash {
+                    return Err(ApiError::InternalError(format!(
+                        "Block {} from {} does not have the hash {:?} that the certified tip \
+                         vouches for",
+                        index, canister_id, expected_hash
+                    )));
+                }
+                let decoded = block.decode().map_err(|e| {
+                    ApiError::InternalError(format!("Undecodable block {}: {}", index, e))
+                })?;
+                expected_hash = decoded.parent_hash;
+                batch.push(HashedBlock::hash_block(block, decoded.parent_hash, index));
+            }
+            self.blocks.stage(&batch)?;
+            end -= length;
+        }
+        Ok(expected_hash)
+    }
+}
+
+#[async_trait]
+impl LedgerAccess for LedgerClient {
+    fn read_blocks(&self) -> &BlockStore {
+        &self.blocks
+    }
+
+    /// Fetches the blocks up to the tip of the ledger, from the archive nodes
+    /// that store them or from the ledger itself. As every block refers to
+    /// the hash of its predecessor, the certificate of the ledger's certified
+    /// data, i.e., the hash of the tip, vouches for all blocks. The blocks
+    /// are therefore fetched backwards from the tip, verified batch by batch
+    /// and staged in the store, so that neither the whole chain is kept in
+    /// memory nor verified blocks are lost if a later batch fails to be
+    /// fetched. Once the staged blocks reach the stored blocks, they are
+    /// appended to them.
+    async fn sync_blocks(&self) -> Result<(), ApiError> {
+        let TipOfChainRes {
+            certification,
+            tip_index,
+        } = self.ledger_canister.tip_of_chain().await?;
+        // Blocks staged by a sync that was interrupted right before applying
+        // them may already extend the stored blocks.
+        self.blocks.apply_staged()?;
+        let last = self.blocks.last()?;
+        let next = last.as_ref().map_or(0, |hb| hb.index + 1);
+        if next > tip_index {
+            return Ok(());
+        }
+        let tip_hash = self.certified_tip_hash(certification.as_deref())?;
+        let archive_index = self.ledger_canister.archive_index().await?;
+
+        // Only the blocks above and below the staged blocks, which are
+        // already verified, need to be fetched.
+        let (end, end_hash) = match self.blocks.staged_range()? {
+            None => (tip_index + 1, Some(tip_hash)),
+            Some((lowest, highest)) => {
+                if highest.index < tip_index {
+                    let parent_hash = self
+                        .fetch_and_stage_blocks(
+                            &archive_index,
+                            highest.index + 1,
+                            tip_index + 1,
+                            tip_hash,
+                        )
+                        .await?;
+                    if parent_hash != Some(highest.hash) {
+                        return Err(ApiError::InternalError(format!(
+                            "Block {} does not refer to the hash of the staged block {}",
+                            highest.index + 1,
+                            highest.index
+                        )));
+                    }
+                }
+                (lowest.index, lowest.parent_hash)
+            }
+        };
+        if next < end {
+            let end_hash = end_hash.ok_or_else(|| {
+                ApiError::InternalError(format!("Staged block {} has no parent hash", end))
+            })?;
+            let parent_hash = self
+                .fetch_and_stage_blocks(&archive_index, next, end, end_hash)
+                .await?;
+            if parent_hash != last.map(|hb| hb.hash) {
+                return Err(ApiError::InternalError(format!(
+                    "Block {} does not refer to the hash of block {}",
+                    next,
+                    next.saturating_sub(1)
+                )));
+            }
+        }
+        self.blocks.apply_staged()?;
+        Ok(())
+    }
+
+    fn ledger_canister_id(&self) -> &CanisterId {
+        &self.canister_id
+    }
+
+    async fn submit(
+        &self,
+        envelope: HttpRequestEnvelope<HttpSubmitContent>,
+    ) -> Result<(), ApiError> {
+        let body = SignedRequestBytes::try_from(envelope)
+            .map_err(|e| ApiError::InvalidRequest(format!("Failed to encode request: {}", e)))?;
+        let url = self
+            .url
+            .join(&update_path(self.canister_id))
+            .map_err(|e| ApiError::InternalError(format!("Invalid URL: {}", e)))?;
+        let (response, status) = self
+            .agent
+            .http_client()
+            .send_post_request(
+                url.as_str(),
+                body.into(),
+                tokio::time::Instant::now() + SUBMIT_TIMEOUT,
+            )
+            .await
+            .map_err(ApiError::LedgerUnavailable)?;
+        if status != HttpStatusCode::ACCEPTED {
+            return Err(ApiError::SubmitFailed(format!(
+                "The replica returned {}: {}",
+                status,
+                String::from_utf8_lossy(&response)
+            )));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,82 @@
+//! A server that implements the Rosetta Data and Construction APIs
+//! (https://www.rosetta-api.org) for the ICP ledger.
+//!
+//! The server syncs the blocks of the ledger canister and of its archive
+//! nodes into a local SQLite store, checking that every block refers to the
+//! hash of its predecessor, and answers Data API requests from the store.
+//! The Construction API builds `send_pb` calls to the ledger that are signed
+//! offline by the holder of the sender's Ed25519 key; only
+//! `/construction/submit` talks to the ledger.
+pub mod construction;
+pub mod convert;
+pub mod errors;
+pub mod ledger_client;
+pub mod models;
+pub mod request_handler;
+pub mod rosetta_server;
+pub mod store;
+
+use ic_config::logger::Config as LoggerConfig;
+use ic_crypto_utils_threshold_sig::parse_threshold_sig_key;
+use ic_logger::{new_replica_logger, LoggerImpl};
+use ic_nns_constants::LEDGER_CANISTER_ID;
+use ic_types::CanisterId;
+use ledger_client::LedgerClient;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use store::BlockStore;
+use structopt::StructOpt;
+use url::Url;
+
+pub const DEFAULT_BLOCKCHAIN: &str = "Internet Computer";
+pub const DEFAULT_TOKEN_SYMBOL: &str = "ICP";
+pub const OPERATION_STATUS_COMPLETED: &str = "COMPLETED";
+/// The version of the Rosetta specification that the server implements.
+pub const ROSETTA_VERSION: &str = "1.4.10";
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "ic-rosetta-api",
+    about = "Serve the Rosetta API for the ICP ledger"
+)]
+pub struct Opts {
+    /// The address to serve the API on.
+    #[structopt(long, default_value = "0.0.0.0:8080")]
+    pub address: SocketAddr,
+
+    /// The URL of a replica of the subnet of the ledger.
+    #[structopt(long, default_value = "https://ic0.app")]
+    pub ic_url: Url,
+
+    /// The ID of the ledger canister. Defaults to the ledger of the NNS.
+    #[structopt(long)]
+    pub canister_id: Option<CanisterId>,
+
+    /// The directory of the block store.
+    #[structopt(long, default_value = "./data", parse(from_os_str))]
+    pub store_location: PathBuf,
+
+    /// The PEM file of the root public key of the Internet Computer, which is
+    /// used to verify the certified tip of the ledger.
+    #[structopt(long, parse(from_os_str))]
+    pub root_key: PathBuf,
+}
+
+pub async fn run(opts: Opts) -> Result<(), String> {
+    let logger_config = LoggerConfig::default();
+    let base_logger = LoggerImpl::new(&logger_config, "ic-rosetta-api".to_string());
+    let log = new_replica_logger(base_logger.root.clone(), &logger_config);
+
+    let canister_id = opts.canister_id.unwrap_or(LEDGER_CANISTER_ID);
+    let root_key = parse_threshold_sig_key(&opts.root_key).map_err(|e| {
+        format!(
+            "Failed to read the root key from {}: {}",
+            opts.root_key.display(),
+            e
+        )
+    })?;
+    let blocks = BlockStore::new(&opts.store_location).map_err(|e| e.to_string())?;
+    let ledger = LedgerClient::new(opts.ic_url, canister_id, root_key, blocks);
+    rosetta_server::run(opts.address, Arc::new(ledger), log).await
+}
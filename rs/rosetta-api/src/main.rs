@@ -0,0 +1,10 @@
+use ic_rosetta_api::{run, Opts};
+use structopt::StructOpt;
+
+#[tokio::main]
+async fn main() {
+    if let Err(err) = run(Opts::from_args()).await {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
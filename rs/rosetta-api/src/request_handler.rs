@@ -0,0 +1,322 @@
+use crate::construction;
+use crate::convert::{
+    self, account_from_public_key, block_identifier, block_to_model, from_model_account_identifier,
+    operations_to_transfer, parent_index, parse_block_hash, principal_id_from_public_key,
+    to_model_account_identifier, transaction_identifier,
+};
+use crate::errors::ApiError;
+use crate::ledger_client::LedgerAccess;
+use crate::models::*;
+use crate::store::HashedBlock;
+use crate::{DEFAULT_BLOCKCHAIN, OPERATION_STATUS_COMPLETED, ROSETTA_VERSION};
+use ic_types::messages::HttpSubmitContent;
+use ledger_canister::{Memo, SendArgs, TimeStamp, TRANSACTION_FEE};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// Implements the endpoints of the Data and Construction APIs on top of the
+/// blocks synced from the ledger.
+#[derive(Clone)]
+pub struct RosettaRequestHandler {
+    ledger: Arc<dyn LedgerAccess + Send + Sync>,
+}
+
+impl RosettaRequestHandler {
+    pub fn new(ledger: Arc<dyn LedgerAccess + Send + Sync>) -> Self {
+        Self { ledger }
+    }
+
+    /// The identifier of the network of the ledger, whose name is the ID of
+    /// the ledger canister.
+    pub fn network_id(&self) -> NetworkIdentifier {
+        NetworkIdentifier {
+            blockchain: DEFAULT_BLOCKCHAIN.to_string(),
+            network: self.ledger.ledger_canister_id().to_string(),
+        }
+    }
+
+    fn verify_network_id(&self, network_id: &NetworkIdentifier) -> Result<(), ApiError> {
+        if *network_id != self.network_id() {
+            return Err(ApiError::InvalidNetworkId(format!(
+                "Expected {:?}, got {:?}",
+                self.network_id(),
+                network_id
+            )));
+        }
+        Ok(())
+    }
+
+    fn last_block(&self) -> Result<HashedBlock, ApiError> {
+        self.ledger
+            .read_blocks()
+            .last()?
+            .ok_or_else(|| ApiError::NotAvailableOffline("The store is empty".to_string()))
+    }
+
+    /// Looks up a block by index and/or hash, or returns the last synced
+    /// block if neither is given.
+    fn get_block(&self, id: &PartialBlockIdentifier) -> Result<HashedBlock, ApiError> {
+        let blocks = self.ledger.read_blocks();
+        match (id.index, &id.hash) {
+            (None, None) => self.last_block(),
+            (Some(index), None) => Ok(blocks.get_at(index)?),
+            (None, Some(hash)) => Ok(blocks.get_by_hash(&parse_block_hash(hash)?)?),
+            (Some(index), Some(hash)) => {
+                let hb = blocks.get_at(index)?;
+                if hb.hash != parse_block_hash(hash)? {
+                    return Err(ApiError::InvalidRequest(format!(
+                        "Block {} does not have hash {}",
+                        index, hash
+                    )));
+                }
+                Ok(hb)
+            }
+        }
+    }
+
+    /// Data API: /network/list
+    pub fn network_list(&self, _: MetadataRequest) -> NetworkListResponse {
+        NetworkListResponse {
+            network_identifiers: vec![self.network_id()],
+        }
+    }
+
+    /// Data API: /network/options
+    pub fn network_options(&self, msg: NetworkRequest) -> Result<NetworkOptionsResponse, ApiError> {
+        self.verify_network_id(&msg.network_identifier)?;
+        Ok(NetworkOptionsResponse {
+            version: Version {
+                rosetta_version: ROSETTA_VERSION.to_string(),
+                node_version: env!("CARGO_PKG_VERSION").to_string(),
+                middleware_version: env!("CARGO_PKG_VERSION").to_string(),
+            },
+            allow: Allow {
+                operation_statuses: vec![OperationStatus {
+                    status: OPERATION_STATUS_COMPLETED.to_string(),
+                    successful: true,
+                }],
+                operation_types: convert::OPERATION_TYPES
+                    .iter()
+                    .map(|t| t.to_string())
+                    .collect(),
+                errors: ApiError::all().into_iter().map(Error::from).collect(),
+                historical_balance_lookup: true,
+            },
+        })
+    }
+
+    /// Data API: /network/status
+    pub fn network_status(&self, msg: NetworkRequest) -> Result<NetworkStatusResponse, ApiError> {
+        self.verify_network_id(&msg.network_identifier)?;
+        let last = self.last_block()?;
+        let genesis = self.ledger.read_blocks().get_at(0)?;
+        let timestamp = last
+            .block
+            .decode()
+            .map_err(|e| ApiError::InternalError(format!("Failed to decode block: {}", e)))?
+            .timestamp;
+        Ok(NetworkStatusResponse {
+            current_block_identifier: block_identifier(&last),
+            current_block_timestamp: convert::timestamp_millis(timestamp),
+            genesis_block_identifier: block_identifier(&genesis),
+            oldest_block_identifier: block_identifier(&genesis),
+            peers: vec![],
+        })
+    }
+
+    /// Data API: /block
+    pub fn block(&self, msg: BlockRequest) -> Result<BlockResponse, ApiError> {
+        self.verify_network_id(&msg.network_identifier)?;
+        let hb = self.get_block(&msg.block_identifier)?;
+        let parent = self.ledger.read_blocks().get_at(parent_index(hb.index))?;
+        Ok(BlockResponse {
+            block: block_to_model(&hb, &parent)?,
+        })
+    }
+
+    /// Data API: /account/balance
+    pub fn account_balance(
+        &self,
+        msg: AccountBalanceRequest,
+    ) -> Result<AccountBalanceResponse, ApiError> {
+        self.verify_network_id(&msg.network_identifier)?;
+        let account = from_model_account_identifier(&msg.account_identifier)?;
+        let hb = self.get_block(&msg.block_identifier.unwrap_or_default())?;
+        let balance = self.ledger.read_blocks().get_balance(&account, hb.index)?;
+        Ok(AccountBalanceResponse {
+            block_identifier: block_identifier(&hb),
+            balances: vec![convert::amount(i128::from(balance.get_e8s()))],
+        })
+    }
+
+    /// Construction API: /construction/derive
+    pub fn construction_derive(
+        &self,
+        msg: ConstructionDeriveRequest,
+    ) -> Result<ConstructionDeriveResponse, ApiError> {
+        self.verify_network_id(&msg.network_identifier)?;
+        Ok(ConstructionDeriveResponse {
+            account_identifier: to_model_account_identifier(&account_from_public_key(
+                &msg.public_key,
+            )?),
+        })
+    }
+
+    /// Construction API: /construction/preprocess
+    pub fn construction_preprocess(
+        &self,
+        msg: ConstructionPreprocessRequest,
+    ) -> Result<ConstructionPreprocessResponse, ApiError> {
+        self.verify_network_id(&msg.network_identifier)?;
+        let transfer = operations_to_transfer(&msg.operations)?;
+        Ok(ConstructionPreprocessResponse {
+            required_public_keys: vec![to_model_account_identifier(&transfer.from)],
+        })
+    }
+
+    /// Construction API: /construction/metadata
+    pub fn construction_metadata(
+        &self,
+        msg: ConstructionMetadataRequest,
+    ) -> Result<ConstructionMetadataResponse, ApiError> {
+        self.verify_network_id(&msg.network_identifier)?;
+        Ok(ConstructionMetadataResponse {
+            suggested_fee: vec![convert::amount(i128::from(TRANSACTION_FEE.get_e8s()))],
+        })
+    }
+
+    /// Construction API: /construction/payloads
+    ///
+    /// Builds the `send_pb` call of the transfer, which must debit the
+    /// default account of one of the given public keys.
+    pub fn construction_payloads(
+        &self,
+        msg: ConstructionPayloadsRequest,
+    ) -> Result<ConstructionPayloadsResponse, ApiError> {
+        self.verify_network_id(&msg.network_identifier)?;
+        let transfer = operations_to_transfer(&msg.operations)?;
+        if transfer.fee != TRANSACTION_FEE {
+            return Err(ApiError::InvalidRequest(format!(
+                "The fee must be {}, got {}",
+                TRANSACTION_FEE, transfer.fee
+            )));
+        }
+
+        let mut sender = None;
+        for public_key in &msg.public_keys {
+            if account_from_public_key(public_key)? == transfer.from {
+                sender = Some(principal_id_from_public_key(public_key)?);
+            }
+        }
+        let sender = sender.ok_or_else(|| {
+            ApiError::InvalidRequest(format!(
+                "None of the public keys controls account {}",
+                transfer.from
+            ))
+        })?;
+
+        let metadata = msg.metadata.unwrap_or_default();
+        let created_at_time = metadata.created_at_time.map_or_else(
+            || TimeStamp::from(SystemTime::now()),
+            TimeStamp::from_nanos_since_unix_epoch,
+        );
+        let send_args = SendArgs {
+            memo: Memo(metadata.memo.unwrap_or(0)),
+            amount: transfer.amount,
+            fee: transfer.fee,
+            from_subaccount: None,
+            to: transfer.to,
+            created_at_time: Some(created_at_time),
+        };
+        let update =
+            construction::make_send_update(self.ledger.ledger_canister_id(), sender, send_args)?;
+
+        Ok(ConstructionPayloadsResponse {
+            payloads: vec![SigningPayload {
+                account_identifier: to_model_account_identifier(&transfer.from),
+                hex_bytes: hex::encode(construction::signable_bytes(&update.id())),
+                signature_type: SignatureType::Ed25519,
+            }],
+            unsigned_transaction: construction::encode_unsigned(&update)?,
+        })
+    }
+
+    /// Construction API: /construction/combine
+    pub fn construction_combine(
+        &self,
+        msg: ConstructionCombineRequest,
+    ) -> Result<ConstructionCombineResponse, ApiError> {
+        self.verify_network_id(&msg.network_identifier)?;
+        let update = construction::decode_unsigned(&msg.unsigned_transaction)?;
+        let signature = match msg.signatures.as_slice() {
+            [signature] => signature,
+            _ => {
+                return Err(ApiError::InvalidRequest(format!(
+                    "Expected exactly one signature, got {}",
+                    msg.signatures.len()
+                )))
+            }
+        };
+        let envelope = construction::combine(update, signature)?;
+        Ok(ConstructionCombineResponse {
+            signed_transaction: construction::encode_signed(envelope)?,
+        })
+    }
+
+    /// Construction API: /construction/parse
+    pub fn construction_parse(
+        &self,
+        msg: ConstructionParseRequest,
+    ) -> Result<ConstructionParseResponse, ApiError> {
+        self.verify_network_id(&msg.network_identifier)?;
+        let update = if msg.signed {
+            let HttpSubmitContent::Call { update } =
+                construction::decode_signed(&msg.transaction)?.content;
+            update
+        } else {
+            construction::decode_unsigned(&msg.transaction)?
+        };
+        let transaction = construction::update_to_transaction(&update)?;
+        let from = match &transaction.operation {
+            ledger_canister::Operation::Transfer { from, .. } => *from,
+            _ => unreachable!("a send always creates a transfer"),
+        };
+        Ok(ConstructionParseResponse {
+            operations: convert::operation_to_model(&transaction.operation, None),
+            account_identifier_signers: if msg.signed {
+                vec![to_model_account_identifier(&from)]
+            } else {
+                vec![]
+            },
+        })
+    }
+
+    /// Construction API: /construction/hash
+    pub fn construction_hash(
+        &self,
+        msg: ConstructionHashRequest,
+    ) -> Result<TransactionIdentifierResponse, ApiError> {
+        self.verify_network_id(&msg.network_identifier)?;
+        let HttpSubmitContent::Call { update } =
+            construction::decode_signed(&msg.signed_transaction)?.content;
+        let transaction = construction::update_to_transaction(&update)?;
+        Ok(TransactionIdentifierResponse {
+            transaction_identifier: transaction_identifier(&transaction),
+        })
+    }
+
+    /// Construction API: /construction/submit
+    pub async fn construction_submit(
+        &self,
+        msg: ConstructionSubmitRequest,
+    ) -> Result<TransactionIdentifierResponse, ApiError> {
+        self.verify_network_id(&msg.network_identifier)?;
+        let envelope = construction::decode_signed(&msg.signed_transaction)?;
+        let HttpSubmitContent::Call { update } = &envelope.content;
+        let transaction = construction::update_to_transaction(update)?;
+        self.ledger.submit(envelope).await?;
+        Ok(TransactionIdentifierResponse {
+            transaction_identifier: transaction_identifier(&transaction),
+        })
+    }
+}
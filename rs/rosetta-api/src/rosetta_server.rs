@@ -0,0 +1,129 @@
+use crate::errors::ApiError;
+use crate::ledger_client::LedgerAccess;
+use crate::models;
+use crate::request_handler::RosettaRequestHandler;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use ic_logger::{info, warn, ReplicaLogger};
+use serde::{de::DeserializeOwned, Serialize};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How long the sync loop waits before it asks the ledger for new blocks.
+const SYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Serves the Rosetta API on `addr` and keeps syncing blocks from the
+/// ledger in the background until the server stops.
+pub async fn run(
+    addr: SocketAddr,
+    ledger: Arc<dyn LedgerAccess + Send + Sync>,
+    log: ReplicaLogger,
+) -> Result<(), String> {
+    tokio::spawn(sync_loop(Arc::clone(&ledger), log.clone()));
+
+    let handler = RosettaRequestHandler::new(ledger);
+    let make_service = make_service_fn(move |_| {
+        let handler = handler.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let handler = handler.clone();
+                async move { Ok::<_, Infallible>(handle(&handler, request).await) }
+            }))
+        }
+    });
+
+    info!(log, "Serving the Rosetta API on {}", addr);
+    Server::bind(&addr)
+        .serve(make_service)
+        .await
+        .map_err(|e| format!("Server error: {}", e))
+}
+
+async fn sync_loop(ledger: Arc<dyn LedgerAccess + Send + Sync>, log: ReplicaLogger) {
+    loop {
+        if let Err(err) = ledger.sync_blocks().await {
+            warn!(log, "Failed to sync blocks: {}", err);
+        }
+        tokio::time::sleep(SYNC_INTERVAL).await;
+    }
+}
+
+/// Routes a request to the handler of its path. All endpoints take a JSON
+/// body and are only served on `POST`.
+pub async fn handle(handler: &RosettaRequestHandler, request: Request<Body>) -> Response<Body> {
+    if request.method() != Method::POST {
+        return Response::builder()
+            .status(StatusCode::METHOD_NOT_ALLOWED)
+            .body(Body::empty())
+            .unwrap();
+    }
+    let path = request.uri().path().to_string();
+    let body = match hyper::body::to_bytes(request.into_body()).await {
+        Ok(body) => body,
+        Err(e) => {
+            return error_response(ApiError::InvalidRequest(format!(
+                "Failed to read body: {}",
+                e
+            )))
+        }
+    };
+
+    match path.as_str() {
+        "/network/list" => respond(&body, |msg| Ok(handler.network_list(msg))),
+        "/network/options" => respond(&body, |msg| handler.network_options(msg)),
+        "/network/status" => respond(&body, |msg| handler.network_status(msg)),
+        "/block" => respond(&body, |msg| handler.block(msg)),
+        "/account/balance" => respond(&body, |msg| handler.account_balance(msg)),
+        "/construction/derive" => respond(&body, |msg| handler.construction_derive(msg)),
+        "/construction/preprocess" => respond(&body, |msg| handler.construction_preprocess(msg)),
+        "/construction/metadata" => respond(&body, |msg| handler.construction_metadata(msg)),
+        "/construction/payloads" => respond(&body, |msg| handler.construction_payloads(msg)),
+        "/construction/combine" => respond(&body, |msg| handler.construction_combine(msg)),
+        "/construction/parse" => respond(&body, |msg| handler.construction_parse(msg)),
+        "/construction/hash" => respond(&body, |msg| handler.construction_hash(msg)),
+        "/construction/submit" => match parse(&body) {
+            Ok(msg) => to_response(handler.construction_submit(msg).await),
+            Err(err) => error_response(err),
+        },
+        _ => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::empty())
+            .unwrap(),
+    }
+}
+
+fn parse<T: DeserializeOwned>(body: &[u8]) -> Result<T, ApiError> {
+    serde_json::from_slice(body)
+        .map_err(|e| ApiError::InvalidRequest(format!("Failed to parse request: {}", e)))
+}
+
+fn respond<Req: DeserializeOwned, Res: Serialize>(
+    body: &[u8],
+    f: impl FnOnce(Req) -> Result<Res, ApiError>,
+) -> Response<Body> {
+    to_response(parse(body).and_then(f))
+}
+
+fn to_response<Res: Serialize>(result: Result<Res, ApiError>) -> Response<Body> {
+    match result {
+        Ok(res) => json_response(StatusCode::OK, &res),
+        Err(err) => error_response(err),
+    }
+}
+
+/// Rosetta clients expect errors as a JSON `Error` with status 500.
+fn error_response(err: ApiError) -> Response<Body> {
+    json_response(StatusCode::INTERNAL_SERVER_ERROR, &models::Error::from(err))
+}
+
+fn json_response<T: Serialize>(status: StatusCode, body: &T) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .body(Body::from(
+            serde_json::to_vec(body).expect("Failed to serialize the response"),
+        ))
+        .unwrap()
+}
@@ -0,0 +1,407 @@
+//! A SQLite store of the blocks synced from the ledger and of the balance of
+//! every account after each block that changed it.
+use ledger_canister::{
+    AccountIdentifier, BlockHeight, EncodedBlock, HashOf, ICPTs, Operation, HASH_LENGTH,
+};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::convert::TryInto;
+use std::fmt;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// An encoded block together with its position in the chain and its hash,
+/// and the hash of its parent.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HashedBlock {
+    pub block: EncodedBlock,
+    pub hash: HashOf<EncodedBlock>,
+    pub parent_hash: Option<HashOf<EncodedBlock>>,
+    pub index: BlockHeight,
+}
+
+impl HashedBlock {
+    pub fn hash_block(
+        block: EncodedBlock,
+        parent_hash: Option<HashOf<EncodedBlock>>,
+        index: BlockHeight,
+    ) -> Self {
+        Self {
+            hash: block.hash(),
+            block,
+            parent_hash,
+            index,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BlockStoreError {
+    /// There is no block with the requested index or hash.
+    NotFound(String),
+    /// A block does not extend the chain in the store.
+    InvalidChain(String),
+    /// Reading or writing the database failed.
+    Other(String),
+}
+
+impl fmt::Display for BlockStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockStoreError::NotFound(msg) => write!(f, "Block not found: {}", msg),
+            BlockStoreError::InvalidChain(msg) => write!(f, "Invalid chain: {}", msg),
+            BlockStoreError::Other(msg) => write!(f, "Block store error: {}", msg),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for BlockStoreError {
+    fn from(err: rusqlite::Error) -> Self {
+        BlockStoreError::Other(err.to_string())
+    }
+}
+
+/// The blocks of a prefix of the ledger's chain, starting at the genesis
+/// block. Every block is only appended after checking that it refers to the
+/// hash of the last block in the store.
+///
+/// Blocks that are already verified against a certified tip of the ledger,
+/// but do not yet extend the stored prefix, can be staged until the blocks
+/// between them and the prefix are synced. Staged blocks survive restarts,
+/// but are not visible through the getters until they are applied.
+pub struct BlockStore {
+    connection: Mutex<Connection>,
+}
+
+impl BlockStore {
+    /// Opens (or creates) the store in `blocks.db` in the given directory.
+    pub fn new(location: &Path) -> Result<Self, BlockStoreError> {
+        std::fs::create_dir_all(location).map_err(|e| {
+            BlockStoreError::Other(format!(
+                "Failed to create directory {}: {}",
+                location.display(),
+                e
+            ))
+        })?;
+        Self::with_connection(Connection::open(location.join("blocks.db"))?)
+    }
+
+    /// Creates a store that is not persisted, e.g. for tests.
+    pub fn new_in_memory() -> Result<Self, BlockStoreError> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(connection: Connection) -> Result<Self, BlockStoreError> {
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS blocks (
+                idx INTEGER NOT NULL PRIMARY KEY,
+                hash BLOB NOT NULL UNIQUE,
+                parent_hash BLOB,
+                block BLOB NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS staged_blocks (
+                idx INTEGER NOT NULL PRIMARY KEY,
+                hash BLOB NOT NULL,
+                parent_hash BLOB,
+                block BLOB NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS balances (
+                account TEXT NOT NULL,
+                idx INTEGER NOT NULL,
+                amount INTEGER NOT NULL,
+                PRIMARY KEY (account, idx)
+            );",
+        )?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    /// Appends the block to the store and records the balances of the
+    /// accounts it changes.
+    ///
+    /// Fails if the block is not the successor of the last block in the
+    /// store, i.e. if its index does not follow the last index, or if its
+    /// parent hash, both as given and as encoded in the block itself, is not
+    /// the hash of the last block.
+    pub fn push(&self, hb: &HashedBlock) -> Result<(), BlockStoreError> {
+        let block = hb
+            .block
+            .decode()
+            .map_err(|e| BlockStoreError::InvalidChain(format!("Undecodable block: {}", e)))?;
+        if block.parent_hash != hb.parent_hash {
+            return Err(BlockStoreError::InvalidChain(format!(
+                "Block {} refers to parent hash {:?}, but was expected to refer to {:?}",
+                hb.index, block.parent_hash, hb.parent_hash
+            )));
+        }
+        if hb.hash != hb.block.hash() {
+            return Err(BlockStoreError::InvalidChain(format!(
+                "The hash of block {} does not match its content",
+                hb.index
+            )));
+        }
+
+        let mut connection = self.connection.lock().unwrap();
+        let last = last_block(&connection)?;
+        let (expected_index, expected_parent_hash) = match &last {
+            None => (0, None),
+            Some(last) => (last.index + 1, Some(last.hash)),
+        };
+        if hb.index != expected_index || hb.parent_hash != expected_parent_hash {
+            return Err(BlockStoreError::InvalidChain(format!(
+                "Block {} with parent hash {:?} does not extend the chain, \
+                 expected block {} with parent hash {:?}",
+                hb.index, hb.parent_hash, expected_index, expected_parent_hash
+            )));
+        }
+
+        let tx = connection.transaction()?;
+        tx.execute(
+            "INSERT INTO blocks (idx, hash, parent_hash, block) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                hb.index as i64,
+                hb.hash.into_bytes().to_vec(),
+                hb.parent_hash.map(|h| h.into_bytes().to_vec()),
+                hb.block.0.to_vec(),
+            ],
+        )?;
+        match block.transaction.operation {
+            Operation::Transfer {
+                from,
+                to,
+                amount,
+                fee,
+            } => {
+                let debit = (amount + fee).map_err(BlockStoreError::InvalidChain)?;
+                debit_account(&tx, &from, debit, hb.index)?;
+                credit_account(&tx, &to, amount, hb.index)?;
+            }
+            Operation::Burn { from, amount } => debit_account(&tx, &from, amount, hb.index)?,
+            Operation::Mint { to, amount } => credit_account(&tx, &to, amount, hb.index)?,
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Stages the blocks, replacing previously staged blocks with the same
+    /// indices.
+    pub fn stage(&self, blocks: &[HashedBlock]) -> Result<(), BlockStoreError> {
+        let mut connection = self.connection.lock().unwrap();
+        let tx = connection.transaction()?;
+        for hb in blocks {
+            tx.execute(
+                "INSERT OR REPLACE INTO staged_blocks (idx, hash, parent_hash, block) \
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    hb.index as i64,
+                    hb.hash.into_bytes().to_vec(),
+                    hb.parent_hash.map(|h| h.into_bytes().to_vec()),
+                    hb.block.0.to_vec(),
+                ],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Returns the staged block with the lowest index and the highest staged
+    /// block up to which all blocks from the lowest one are staged, if any
+    /// block is staged.
+    ///
+    /// Blocks can be staged above a gap if a sync was interrupted, and are
+    /// then staged again once the gap is filled.
+    pub fn staged_range(&self) -> Result<Option<(HashedBlock, HashedBlock)>, BlockStoreError> {
+        let connection = self.connection.lock().unwrap();
+        let lowest = connection
+            .query_row(
+                "SELECT idx, hash, parent_hash, block FROM staged_blocks ORDER BY idx ASC LIMIT 1",
+                params![],
+                read_hashed_block,
+            )
+            .optional()?;
+        let highest = connection
+            .query_row(
+                "SELECT idx, hash, parent_hash, block FROM staged_blocks AS s \
+                 WHERE NOT EXISTS (SELECT 1 FROM staged_blocks WHERE idx = s.idx + 1) \
+                 ORDER BY idx ASC LIMIT 1",
+                params![],
+                read_hashed_block,
+            )
+            .optional()?;
+        Ok(lowest.zip(highest))
+    }
+
+    /// Appends the staged blocks that extend the stored prefix, in order, and
+    /// discards the staged blocks that are already stored.
+    ///
+    /// If a staged block fails to extend the chain, all staged blocks are
+    /// discarded, as they cannot be trusted either.
+    pub fn apply_staged(&self) -> Result<(), BlockStoreError> {
+        loop {
+            let staged = {
+                let connection = self.connection.lock().unwrap();
+                let next = last_block(&connection)?.map_or(0, |hb| hb.index + 1);
+                connection.execute(
+                    "DELETE FROM staged_blocks WHERE idx < ?1",
+                    params![next as i64],
+                )?;
+                connection
+                    .query_row(
+                        "SELECT idx, hash, parent_hash, block FROM staged_blocks WHERE idx = ?1",
+                        params![next as i64],
+                        read_hashed_block,
+                    )
+                    .optional()?
+            };
+            match staged {
+                None => return Ok(()),
+                Some(hb) => {
+                    if let Err(err) = self.push(&hb) {
+                        self.connection
+                            .lock()
+                            .unwrap()
+                            .execute("DELETE FROM staged_blocks", params![])?;
+                        return Err(err);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn get_at(&self, index: BlockHeight) -> Result<HashedBlock, BlockStoreError> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .query_row(
+                "SELECT idx, hash, parent_hash, block FROM blocks WHERE idx = ?1",
+                params![index as i64],
+                read_hashed_block,
+            )
+            .optional()?
+            .ok_or_else(|| BlockStoreError::NotFound(format!("no block at index {}", index)))
+    }
+
+    pub fn get_by_hash(&self, hash: &HashOf<EncodedBlock>) -> Result<HashedBlock, BlockStoreError> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .query_row(
+                "SELECT idx, hash, parent_hash, block FROM blocks WHERE hash = ?1",
+                params![hash.into_bytes().to_vec()],
+                read_hashed_block,
+            )
+            .optional()?
+            .ok_or_else(|| BlockStoreError::NotFound(format!("no block with hash {}", hash)))
+    }
+
+    /// Returns the genesis block, if it was synced.
+    pub fn first(&self) -> Result<Option<HashedBlock>, BlockStoreError> {
+        match self.get_at(0) {
+            Ok(block) => Ok(Some(block)),
+            Err(BlockStoreError::NotFound(_)) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Returns the most recently synced block.
+    pub fn last(&self) -> Result<Option<HashedBlock>, BlockStoreError> {
+        last_block(&self.connection.lock().unwrap())
+    }
+
+    /// Returns the balance of the account right after the block at the given
+    /// index was applied.
+    pub fn get_balance(
+        &self,
+        account: &AccountIdentifier,
+        index: BlockHeight,
+    ) -> Result<ICPTs, BlockStoreError> {
+        balance_at(&self.connection.lock().unwrap(), account, index)
+    }
+}
+
+fn read_hashed_block(row: &rusqlite::Row) -> rusqlite::Result<HashedBlock> {
+    let index: i64 = row.get(0)?;
+    let hash: Vec<u8> = row.get(1)?;
+    let parent_hash: Option<Vec<u8>> = row.get(2)?;
+    let block: Vec<u8> = row.get(3)?;
+    Ok(HashedBlock {
+        block: EncodedBlock(block.into_boxed_slice()),
+        hash: to_hash(hash),
+        parent_hash: parent_hash.map(to_hash),
+        index: index as BlockHeight,
+    })
+}
+
+fn to_hash<T>(bytes: Vec<u8>) -> HashOf<T> {
+    let bytes: [u8; HASH_LENGTH] = bytes
+        .as_slice()
+        .try_into()
+        .expect("stored hash does not have the correct length");
+    HashOf::new(bytes)
+}
+
+fn last_block(connection: &Connection) -> Result<Option<HashedBlock>, BlockStoreError> {
+    Ok(connection
+        .query_row(
+            "SELECT idx, hash, parent_hash, block FROM blocks ORDER BY idx DESC LIMIT 1",
+            params![],
+            read_hashed_block,
+        )
+        .optional()?)
+}
+
+// Amounts are stored as the bit pattern of the `u64` number of e8s in a
+// signed SQLite integer.
+fn balance_at(
+    connection: &Connection,
+    account: &AccountIdentifier,
+    index: BlockHeight,
+) -> Result<ICPTs, BlockStoreError> {
+    let amount: Option<i64> = connection
+        .query_row(
+            "SELECT amount FROM balances WHERE account = ?1 AND idx <= ?2 \
+             ORDER BY idx DESC LIMIT 1",
+            params![account.to_hex(), index as i64],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(ICPTs::from_e8s(amount.map_or(0, |e8s| e8s as u64)))
+}
+
+fn set_balance(
+    connection: &Connection,
+    account: &AccountIdentifier,
+    amount: ICPTs,
+    index: BlockHeight,
+) -> Result<(), BlockStoreError> {
+    connection.execute(
+        "INSERT OR REPLACE INTO balances (account, idx, amount) VALUES (?1, ?2, ?3)",
+        params![account.to_hex(), index as i64, amount.get_e8s() as i64],
+    )?;
+    Ok(())
+}
+
+fn debit_account(
+    connection: &Connection,
+    account: &AccountIdentifier,
+    amount: ICPTs,
+    index: BlockHeight,
+) -> Result<(), BlockStoreError> {
+    let balance = balance_at(connection, account, index)?;
+    let new_balance = (balance - amount).map_err(|e| {
+        BlockStoreError::InvalidChain(format!(
+            "Block {} debits {} from account {} with balance {}: {}",
+            index, amount, account, balance, e
+        ))
+    })?;
+    set_balance(connection, account, new_balance, index)
+}
+
+fn credit_account(
+    connection: &Connection,
+    account: &AccountIdentifier,
+    amount: ICPTs,
+    index: BlockHeight,
+) -> Result<(), BlockStoreError> {
+    let balance = balance_at(connection, account, index)?;
+    let new_balance = (balance + amount).map_err(BlockStoreError::InvalidChain)?;
+    set_balance(connection, account, new_balance, index)
+}
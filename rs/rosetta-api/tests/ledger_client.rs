@@ -0,0 +1,333 @@
+use async_trait::async_trait;
+use ic_crypto::combined_threshold_signature_and_public_key;
+use ic_crypto_tree_hash::{
+    flatmap, HashTreeBuilder, HashTreeBuilderImpl, Label, LabeledTree, MixedHashTree,
+    WitnessGenerator,
+};
+use ic_nns_constants::LEDGER_CANISTER_ID;
+use ic_rosetta_api::errors::ApiError;
+use ic_rosetta_api::ledger_client::{LedgerAccess, LedgerCanister, LedgerClient};
+use ic_rosetta_api::store::{BlockStore, HashedBlock};
+use ic_types::consensus::certification::CertificationContent;
+use ic_types::crypto::threshold_sig::ThresholdSigPublicKey;
+use ic_types::crypto::{CombinedThresholdSigOf, CryptoHash};
+use ic_types::{CanisterId, CryptoHashOfPartialState, PrincipalId, Randomness};
+use ledger_canister::{
+    AccountIdentifier, Block, BlockHeight, EncodedBlock, HashOf, ICPTs, Memo, Operation, TimeStamp,
+    TipOfChainRes,
+};
+use std::sync::{Arc, Mutex};
+use url::Url;
+
+const ARCHIVE_CANISTER_ID: CanisterId = CanisterId::from_u64(100);
+
+/// A ledger whose first blocks are stored in an archive node and that serves
+/// at most `max_blocks_per_call` blocks per `get_blocks` call, like the
+/// ledger and archive canisters, which are limited by the message size.
+struct FakeLedgerCanister {
+    blocks: Vec<EncodedBlock>,
+    num_archived: u64,
+    max_blocks_per_call: u64,
+    certified_data: Option<HashOf<EncodedBlock>>,
+    /// The number of `blocks` that were added to the ledger so far.
+    num_blocks: Arc<Mutex<u64>>,
+    /// A canister whose `get_blocks` calls fail.
+    unavailable_canister: Arc<Mutex<Option<CanisterId>>>,
+    /// The `(canister_id, start, length)` of every `get_blocks` call.
+    calls: Arc<Mutex<Vec<(CanisterId, BlockHeight, u64)>>>,
+}
+
+#[async_trait]
+impl LedgerCanister for FakeLedgerCanister {
+    async fn tip_of_chain(&self) -> Result<TipOfChainRes, ApiError> {
+        let tip_index = *self.num_blocks.lock().unwrap() - 1;
+        let tip_hash = self
+            .certified_data
+            .unwrap_or_else(|| self.blocks[tip_index as usize].hash());
+        let (_root_key, certificate) = certificate(&tip_hash);
+        Ok(TipOfChainRes {
+            certification: Some(certificate),
+            tip_index,
+        })
+    }
+
+    async fn archive_index(&self) -> Result<Vec<(BlockHeight, BlockHeight, CanisterId)>, ApiError> {
+        Ok(vec![(0, self.num_archived - 1, ARCHIVE_CANISTER_ID)])
+    }
+
+    async fn get_blocks(
+        &self,
+        canister_id: &CanisterId,
+        start: BlockHeight,
+        length: u64,
+    ) -> Result<Vec<EncodedBlock>, ApiError> {
+        self.calls
+            .lock()
+            .unwrap()
+            .push((*canister_id, start, length));
+        if *self.unavailable_canister.lock().unwrap() == Some(*canister_id) {
+            return Err(ApiError::LedgerUnavailable(format!(
+                "{} is unavailable",
+                canister_id
+            )));
+        }
+        let (first, end) = if *canister_id == ARCHIVE_CANISTER_ID {
+            (0, self.num_archived)
+        } else {
+            (self.num_archived, *self.num_blocks.lock().unwrap())
+        };
+        if start < first || start + length > end {
+            return Err(ApiError::LedgerUnavailable(format!(
+                "{} does not store blocks [{} .. {})",
+                canister_id,
+                start,
+                start + length
+            )));
+        }
+        let served = length.min(self.max_blocks_per_call);
+        Ok(self.blocks[start as usize..(start + served) as usize].to_vec())
+    }
+}
+
+/// Returns a certificate of `certified_data` as the certified data of the
+/// ledger, and the root key that verifies it. The key is derived from a fixed
+/// seed, so every call returns the same key.
+fn certificate(certified_data: &HashOf<EncodedBlock>) -> (ThresholdSigPublicKey, Vec<u8>) {
+    #[derive(serde::Serialize)]
+    struct Certificate {
+        tree: MixedHashTree,
+        signature: CombinedThresholdSigOf<CertificationContent>,
+    }
+
+    fn hash_full_tree(b: &mut HashTreeBuilderImpl, t: &LabeledTree<Vec<u8>>) {
+        match t {
+            LabeledTree::Leaf(bytes) => {
+                b.start_leaf();
+                b.write_leaf(&bytes[..]);
+                b.finish_leaf();
+            }
+            LabeledTree::SubTree(map) => {
+                b.start_subtree();
+                for (l, child) in map.iter() {
+                    b.new_edge(l.clone());
+                    hash_full_tree(b, child);
+                }
+                b.finish_subtree();
+            }
+        }
+    }
+
+    let mut encoded_time = vec![];
+    leb128::write::unsigned(&mut encoded_time, 1234567).unwrap();
+    let tree = LabeledTree::SubTree(flatmap![
+        Label::from("canister") => LabeledTree::SubTree(flatmap![
+            Label::from(LEDGER_CANISTER_ID.get_ref().to_vec()) => LabeledTree::SubTree(flatmap![
+                Label::from("certified_data") =>
+                    LabeledTree::Leaf(certified_data.into_bytes().to_vec()),
+            ])
+        ]),
+        Label::from("time") => LabeledTree::Leaf(encoded_time)
+    ]);
+
+    let mut b = HashTreeBuilderImpl::new();
+    hash_full_tree(&mut b, &tree);
+    let witness_gen = b.witness_generator().unwrap();
+    let root_hash =
+        CryptoHashOfPartialState::from(CryptoHash(witness_gen.hash_tree().digest().to_vec()));
+    let (signature, root_key) = combined_threshold_signature_and_public_key(
+        Randomness::from([0; 32]),
+        &CertificationContent::new(root_hash),
+    );
+    let certificate = serde_cbor::to_vec(&Certificate {
+        tree: witness_gen.mixed_hash_tree(&tree).unwrap(),
+        signature,
+    })
+    .unwrap();
+    (root_key, certificate)
+}
+
+fn chain(length: u64) -> Vec<EncodedBlock> {
+    let mut blocks: Vec<EncodedBlock> = vec![];
+    for index in 0..length {
+        let timestamp = TimeStamp::from_nanos_since_unix_epoch(1_000_000_000 * (index + 1));
+        let block = Block::new(
+            blocks.last().map(|block| block.hash()),
+            Operation::Mint {
+                to: AccountIdentifier::new(PrincipalId::new_user_test_id(1), None),
+                amount: ICPTs::from_e8s(1000),
+            },
+            Memo(index),
+            timestamp,
+            timestamp,
+        )
+        .unwrap()
+        .encode()
+        .unwrap();
+        blocks.push(block);
+    }
+    blocks
+}
+
+struct Fixture {
+    client: LedgerClient,
+    num_blocks: Arc<Mutex<u64>>,
+    unavailable_canister: Arc<Mutex<Option<CanisterId>>>,
+    calls: Arc<Mutex<Vec<(CanisterId, BlockHeight, u64)>>>,
+}
+
+fn fixture(
+    blocks: Vec<EncodedBlock>,
+    num_archived: u64,
+    max_blocks_per_call: u64,
+    certified_data: Option<HashOf<EncodedBlock>>,
+) -> Fixture {
+    let (root_key, _certificate) = certificate(&blocks[0].hash());
+    let num_blocks = Arc::new(Mutex::new(blocks.len() as u64));
+    let unavailable_canister = Arc::new(Mutex::new(None));
+    let calls = Arc::new(Mutex::new(vec![]));
+    let ledger_canister = FakeLedgerCanister {
+        blocks,
+        num_archived,
+        max_blocks_per_call,
+        certified_data,
+        num_blocks: Arc::clone(&num_blocks),
+        unavailable_canister: Arc::clone(&unavailable_canister),
+        calls: Arc::clone(&calls),
+    };
+    let client = LedgerClient::new_with_ledger_canister(
+        Url::parse("http://localhost:8080").unwrap(),
+        LEDGER_CANISTER_ID,
+        root_key,
+        BlockStore::new_in_memory().unwrap(),
+        Box::new(ledger_canister),
+    );
+    Fixture {
+        client,
+        num_blocks,
+        unavailable_canister,
+        calls,
+    }
+}
+
+fn synced_hashes(client: &LedgerClient) -> Vec<HashOf<EncodedBlock>> {
+    let blocks = client.read_blocks();
+    let length = blocks.last().unwrap().map_or(0, |hb| hb.index + 1);
+    (0..length)
+        .map(|index| blocks.get_at(index).unwrap().hash)
+        .collect()
+}
+
+#[tokio::test]
+async fn should_sync_blocks_across_archive_and_ledger_boundary() {
+    let blocks = chain(7);
+    let expected_hashes: Vec<_> = blocks.iter().map(EncodedBlock::hash).collect();
+    let fixture = fixture(blocks, 3, 100, None);
+
+    fixture.client.sync_blocks().await.unwrap();
+
+    assert_eq!(synced_hashes(&fixture.client), expected_hashes);
+    assert_eq!(
+        *fixture.calls.lock().unwrap(),
+        vec![(LEDGER_CANISTER_ID, 3, 4), (ARCHIVE_CANISTER_ID, 0, 3)]
+    );
+}
+
+#[tokio::test]
+async fn should_sync_blocks_if_canisters_return_fewer_blocks_than_requested() {
+    let blocks = chain(7);
+    let expected_hashes: Vec<_> = blocks.iter().map(EncodedBlock::hash).collect();
+    let fixture = fixture(blocks, 3, 2, None);
+
+    fixture.client.sync_blocks().await.unwrap();
+
+    assert_eq!(synced_hashes(&fixture.client), expected_hashes);
+    assert_eq!(
+        *fixture.calls.lock().unwrap(),
+        vec![
+            (LEDGER_CANISTER_ID, 3, 4),
+            (LEDGER_CANISTER_ID, 5, 2),
+            (LEDGER_CANISTER_ID, 3, 2),
+            (ARCHIVE_CANISTER_ID, 1, 2),
+            (ARCHIVE_CANISTER_ID, 0, 1),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn should_not_store_blocks_if_tip_is_not_certified() {
+    let blocks = chain(7);
+    let other_hash = blocks[5].hash();
+    let fixture = fixture(blocks, 3, 100, Some(other_hash));
+
+    let result = fixture.client.sync_blocks().await;
+
+    assert!(matches!(result, Err(ApiError::InternalError(_))));
+    assert!(fixture.client.read_blocks().last().unwrap().is_none());
+}
+
+#[tokio::test]
+async fn should_keep_verified_blocks_if_sync_fails() {
+    let blocks = chain(9);
+    let expected_hashes: Vec<_> = blocks.iter().map(EncodedBlock::hash).collect();
+    let fixture = fixture(blocks, 3, 100, None);
+    *fixture.num_blocks.lock().unwrap() = 7;
+    *fixture.unavailable_canister.lock().unwrap() = Some(ARCHIVE_CANISTER_ID);
+
+    let result = fixture.client.sync_blocks().await;
+
+    assert!(matches!(result, Err(ApiError::LedgerUnavailable(_))));
+    let blocks = fixture.client.read_blocks();
+    assert!(blocks.last().unwrap().is_none());
+    let staged = blocks.staged_range().unwrap();
+    assert_eq!(
+        staged.map(|(lowest, highest)| (lowest.index, highest.index)),
+        Some((3, 6))
+    );
+
+    // The staged blocks are not fetched again, even though the ledger grew
+    // in the meantime.
+    *fixture.num_blocks.lock().unwrap() = 9;
+    *fixture.unavailable_canister.lock().unwrap() = None;
+    fixture.calls.lock().unwrap().clear();
+
+    fixture.client.sync_blocks().await.unwrap();
+
+    assert_eq!(synced_hashes(&fixture.client), expected_hashes);
+    assert!(fixture
+        .client
+        .read_blocks()
+        .staged_range()
+        .unwrap()
+        .is_none());
+    assert_eq!(
+        *fixture.calls.lock().unwrap(),
+        vec![(LEDGER_CANISTER_ID, 7, 2), (ARCHIVE_CANISTER_ID, 0, 3)]
+    );
+}
+
+#[test]
+fn store_applies_staged_blocks_once_they_extend_the_chain() {
+    let mut hashed: Vec<HashedBlock> = vec![];
+    for (index, block) in chain(6).into_iter().enumerate() {
+        let parent_hash = hashed.last().map(|hb| hb.hash);
+        hashed.push(HashedBlock::hash_block(block, parent_hash, index as u64));
+    }
+    let store = BlockStore::new_in_memory().unwrap();
+    store.stage(&hashed[4..6]).unwrap();
+    store.stage(&hashed[1..3]).unwrap();
+    assert_eq!(
+        store.staged_range().unwrap(),
+        Some((hashed[1].clone(), hashed[2].clone()))
+    );
+
+    store.apply_staged().unwrap();
+    assert!(store.last().unwrap().is_none());
+
+    store.push(&hashed[0]).unwrap();
+    store.apply_staged().unwrap();
+    assert_eq!(store.last().unwrap(), Some(hashed[2].clone()));
+    assert_eq!(
+        store.staged_range().unwrap(),
+        Some((hashed[4].clone(), hashed[5].clone()))
+    );
+}
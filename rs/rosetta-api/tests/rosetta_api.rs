@@ -0,0 +1,472 @@
+use async_trait::async_trait;
+use ed25519_dalek::{Keypair, Signer};
+use ic_nns_constants::LEDGER_CANISTER_ID;
+use ic_rosetta_api::convert::{self, to_model_account_identifier};
+use ic_rosetta_api::errors::ApiError;
+use ic_rosetta_api::ledger_client::LedgerAccess;
+use ic_rosetta_api::models::{self, *};
+use ic_rosetta_api::request_handler::RosettaRequestHandler;
+use ic_rosetta_api::store::{BlockStore, BlockStoreError, HashedBlock};
+use ic_types::messages::{HttpRequestEnvelope, HttpSubmitContent};
+use ic_types::{CanisterId, PrincipalId};
+use ledger_canister::{AccountIdentifier, Block, ICPTs, Memo, Operation, TimeStamp};
+use rand_chacha::ChaChaRng;
+use rand_core::SeedableRng;
+use std::sync::{Arc, Mutex};
+
+/// A ledger whose blocks are added directly to the store and that records
+/// submitted requests instead of sending them.
+struct TestLedger {
+    blocks: BlockStore,
+    submitted: Mutex<Vec<HttpRequestEnvelope<HttpSubmitContent>>>,
+}
+
+impl TestLedger {
+    fn new() -> Self {
+        Self {
+            blocks: BlockStore::new_in_memory().unwrap(),
+            submitted: Mutex::new(vec![]),
+        }
+    }
+
+    fn add_block(&self, operation: Operation) -> HashedBlock {
+        let last = self.blocks.last().unwrap();
+        let parent_hash = last.as_ref().map(|hb| hb.hash);
+        let index = last.map_or(0, |hb| hb.index + 1);
+        let timestamp = TimeStamp::from_nanos_since_unix_epoch(1_000_000_000 * (index + 1));
+        let block = Block::new(parent_hash, operation, Memo(index), timestamp, timestamp)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let hb = HashedBlock::hash_block(block, parent_hash, index);
+        self.blocks.push(&hb).unwrap();
+        hb
+    }
+}
+
+#[async_trait]
+impl LedgerAccess for TestLedger {
+    fn read_blocks(&self) -> &BlockStore {
+        &self.blocks
+    }
+
+    async fn sync_blocks(&self) -> Result<(), ApiError> {
+        Ok(())
+    }
+
+    fn ledger_canister_id(&self) -> &CanisterId {
+        &LEDGER_CANISTER_ID
+    }
+
+    async fn submit(
+        &self,
+        envelope: HttpRequestEnvelope<HttpSubmitContent>,
+    ) -> Result<(), ApiError> {
+        self.submitted.lock().unwrap().push(envelope);
+        Ok(())
+    }
+}
+
+fn setup() -> (Arc<TestLedger>, RosettaRequestHandler) {
+    let ledger = Arc::new(TestLedger::new());
+    let handler = RosettaRequestHandler::new(ledger.clone());
+    (ledger, handler)
+}
+
+fn account(i: u64) -> AccountIdentifier {
+    AccountIdentifier::new(PrincipalId::new_user_test_id(i), None)
+}
+
+fn keypair(seed: u64) -> Keypair {
+    Keypair::generate(&mut ChaChaRng::seed_from_u64(seed))
+}
+
+fn public_key(keypair: &Keypair) -> PublicKey {
+    PublicKey {
+        hex_bytes: hex::encode(keypair.public.to_bytes()),
+        curve_type: CurveType::Edwards25519,
+    }
+}
+
+fn balance(
+    handler: &RosettaRequestHandler,
+    account: &AccountIdentifier,
+    index: Option<u64>,
+) -> String {
+    handler
+        .account_balance(AccountBalanceRequest {
+            network_identifier: handler.network_id(),
+            account_identifier: to_model_account_identifier(account),
+            block_identifier: Some(PartialBlockIdentifier { index, hash: None }),
+        })
+        .unwrap()
+        .balances[0]
+        .value
+        .clone()
+}
+
+#[test]
+fn store_rejects_blocks_that_do_not_extend_the_chain() {
+    let ledger = TestLedger::new();
+    let genesis = ledger.add_block(Operation::Mint {
+        to: account(1),
+        amount: ICPTs::from_e8s(1000),
+    });
+
+    let timestamp = TimeStamp::from_nanos_since_unix_epoch(2_000_000_000);
+    let make_block = |parent_hash| {
+        Block::new(
+            parent_hash,
+            Operation::Burn {
+                from: account(1),
+                amount: ICPTs::from_e8s(10),
+            },
+            Memo(0),
+            timestamp,
+            timestamp,
+        )
+        .unwrap()
+        .encode()
+        .unwrap()
+    };
+
+    // The block refers to a parent other than the last block.
+    let orphan = make_block(None);
+    let hb = HashedBlock::hash_block(orphan, Some(genesis.hash), 1);
+    assert!(matches!(
+        ledger.blocks.push(&hb),
+        Err(BlockStoreError::InvalidChain(_))
+    ));
+
+    // The block does not follow the last index.
+    let hb = HashedBlock::hash_block(make_block(Some(genesis.hash)), Some(genesis.hash), 2);
+    assert!(matches!(
+        ledger.blocks.push(&hb),
+        Err(BlockStoreError::InvalidChain(_))
+    ));
+
+    let hb = HashedBlock::hash_block(make_block(Some(genesis.hash)), Some(genesis.hash), 1);
+    ledger.blocks.push(&hb).unwrap();
+    assert_eq!(ledger.blocks.last().unwrap(), Some(hb));
+}
+
+#[test]
+fn account_balance_is_reported_at_every_height() {
+    let (ledger, handler) = setup();
+    ledger.add_block(Operation::Mint {
+        to: account(1),
+        amount: ICPTs::from_e8s(100_000),
+    });
+    ledger.add_block(Operation::Transfer {
+        from: account(1),
+        to: account(2),
+        amount: ICPTs::from_e8s(30_000),
+        fee: ICPTs::from_e8s(10_000),
+    });
+    ledger.add_block(Operation::Burn {
+        from: account(2),
+        amount: ICPTs::from_e8s(5_000),
+    });
+
+    assert_eq!(balance(&handler, &account(1), Some(0)), "100000");
+    assert_eq!(balance(&handler, &account(2), Some(0)), "0");
+    assert_eq!(balance(&handler, &account(1), Some(1)), "60000");
+    assert_eq!(balance(&handler, &account(2), Some(1)), "30000");
+    assert_eq!(balance(&handler, &account(1), None), "60000");
+    assert_eq!(balance(&handler, &account(2), None), "25000");
+    assert_eq!(balance(&handler, &account(3), None), "0");
+}
+
+#[test]
+fn block_is_found_by_index_and_hash() {
+    let (ledger, handler) = setup();
+    let genesis = ledger.add_block(Operation::Mint {
+        to: account(1),
+        amount: ICPTs::from_e8s(100_000),
+    });
+    let transfer = ledger.add_block(Operation::Transfer {
+        from: account(1),
+        to: account(2),
+        amount: ICPTs::from_e8s(30_000),
+        fee: ICPTs::from_e8s(10_000),
+    });
+
+    let get_block = |index, hash| {
+        handler
+            .block(BlockRequest {
+                network_identifier: handler.network_id(),
+                block_identifier: PartialBlockIdentifier { index, hash },
+            })
+            .map(|res| res.block)
+    };
+
+    let block = get_block(Some(0), None).unwrap();
+    assert_eq!(block.block_identifier, convert::block_identifier(&genesis));
+    // The genesis block is its own parent.
+    assert_eq!(
+        block.parent_block_identifier,
+        convert::block_identifier(&genesis)
+    );
+
+    let block = get_block(None, Some(transfer.hash.to_string())).unwrap();
+    assert_eq!(block.block_identifier, convert::block_identifier(&transfer));
+    assert_eq!(
+        block.parent_block_identifier,
+        convert::block_identifier(&genesis)
+    );
+    assert_eq!(block.timestamp, 2_000);
+    let operations = &block.transactions[0].operations;
+    assert_eq!(
+        operations
+            .iter()
+            .map(|op| (op.type_.as_str(), op.amount.value.as_str()))
+            .collect::<Vec<_>>(),
+        vec![
+            (convert::TRANSACTION, "-30000"),
+            (convert::TRANSACTION, "30000"),
+            (convert::FEE, "-10000"),
+        ]
+    );
+
+    assert_eq!(get_block(None, None).unwrap(), block);
+    assert!(matches!(
+        get_block(Some(2), None),
+        Err(ApiError::BlockNotFound(_))
+    ));
+    assert!(matches!(
+        get_block(Some(0), Some(transfer.hash.to_string())),
+        Err(ApiError::InvalidRequest(_))
+    ));
+}
+
+#[test]
+fn network_status_reports_genesis_and_tip() {
+    let (ledger, handler) = setup();
+    let request = NetworkRequest {
+        network_identifier: handler.network_id(),
+    };
+    assert!(matches!(
+        handler.network_status(request.clone()),
+        Err(ApiError::NotAvailableOffline(_))
+    ));
+
+    let genesis = ledger.add_block(Operation::Mint {
+        to: account(1),
+        amount: ICPTs::from_e8s(100_000),
+    });
+    let tip = ledger.add_block(Operation::Burn {
+        from: account(1),
+        amount: ICPTs::from_e8s(10_000),
+    });
+    let status = handler.network_status(request).unwrap();
+    assert_eq!(
+        status.genesis_block_identifier,
+        convert::block_identifier(&genesis)
+    );
+    assert_eq!(
+        status.current_block_identifier,
+        convert::block_identifier(&tip)
+    );
+
+    let other_network = NetworkRequest {
+        network_identifier: NetworkIdentifier {
+            blockchain: "Internet Computer".to_string(),
+            network: "other".to_string(),
+        },
+    };
+    assert!(matches!(
+        handler.network_status(other_network),
+        Err(ApiError::InvalidNetworkId(_))
+    ));
+}
+
+fn transfer_operations(from: &AccountIdentifier, to: &AccountIdentifier) -> Vec<models::Operation> {
+    let op = |index, type_: &str, account, value: i128| models::Operation {
+        operation_identifier: OperationIdentifier { index },
+        related_operations: vec![],
+        type_: type_.to_string(),
+        status: None,
+        account: to_model_account_identifier(account),
+        amount: convert::amount(value),
+    };
+    vec![
+        op(0, convert::TRANSACTION, from, -50_000),
+        op(1, convert::TRANSACTION, to, 50_000),
+        op(2, convert::FEE, from, -10_000),
+    ]
+}
+
+#[tokio::test]
+async fn send_is_constructed_signed_offline_and_submitted() {
+    let (ledger, handler) = setup();
+    let network_identifier = handler.network_id();
+    let keypair = keypair(1);
+
+    let from = handler
+        .construction_derive(ConstructionDeriveRequest {
+            network_identifier: network_identifier.clone(),
+            public_key: public_key(&keypair),
+        })
+        .unwrap()
+        .account_identifier;
+    let from = convert::from_model_account_identifier(&from).unwrap();
+    let operations = transfer_operations(&from, &account(2));
+
+    let preprocess = handler
+        .construction_preprocess(ConstructionPreprocessRequest {
+            network_identifier: network_identifier.clone(),
+            operations: operations.clone(),
+        })
+        .unwrap();
+    assert_eq!(
+        preprocess.required_public_keys,
+        vec![to_model_account_identifier(&from)]
+    );
+
+    let payloads = handler
+        .construction_payloads(ConstructionPayloadsRequest {
+            network_identifier: network_identifier.clone(),
+            operations: operations.clone(),
+            public_keys: vec![public_key(&keypair)],
+            metadata: Some(ConstructionPayloadsMetadata {
+                memo: Some(7),
+                created_at_time: Some(1_000),
+            }),
+        })
+        .unwrap();
+    let unsigned = handler
+        .construction_parse(ConstructionParseRequest {
+            network_identifier: network_identifier.clone(),
+            signed: false,
+            transaction: payloads.unsigned_transaction.clone(),
+        })
+        .unwrap();
+    assert_eq!(unsigned.operations, operations);
+    assert!(unsigned.account_identifier_signers.is_empty());
+
+    // The holder of the key signs the payload offline.
+    let payload = &payloads.payloads[0];
+    let signature = keypair.sign(&hex::decode(&payload.hex_bytes).unwrap());
+    let signed = handler
+        .construction_combine(ConstructionCombineRequest {
+            network_identifier: network_identifier.clone(),
+            unsigned_transaction: payloads.unsigned_transaction.clone(),
+            signatures: vec![Signature {
+                signing_payload: payload.clone(),
+                public_key: public_key(&keypair),
+                signature_type: SignatureType::Ed25519,
+                hex_bytes: hex::encode(signature.to_bytes()),
+            }],
+        })
+        .unwrap()
+        .signed_transaction;
+
+    let parsed = handler
+        .construction_parse(ConstructionParseRequest {
+            network_identifier: network_identifier.clone(),
+            signed: true,
+            transaction: signed.clone(),
+        })
+        .unwrap();
+    assert_eq!(parsed.operations, operations);
+    assert_eq!(
+        parsed.account_identifier_signers,
+        vec![to_model_account_identifier(&from)]
+    );
+
+    let expected_hash = ledger_canister::Transaction::new(
+        from,
+        account(2),
+        ICPTs::from_e8s(50_000),
+        ICPTs::from_e8s(10_000),
+        Memo(7),
+        TimeStamp::from_nanos_since_unix_epoch(1_000),
+    )
+    .hash()
+    .to_string();
+    let hash = handler
+        .construction_hash(ConstructionHashRequest {
+            network_identifier: network_identifier.clone(),
+            signed_transaction: signed.clone(),
+        })
+        .unwrap();
+    assert_eq!(hash.transaction_identifier.hash, expected_hash);
+
+    let submitted = handler
+        .construction_submit(ConstructionSubmitRequest {
+            network_identifier,
+            signed_transaction: signed,
+        })
+        .await
+        .unwrap();
+    assert_eq!(submitted.transaction_identifier.hash, expected_hash);
+
+    let submitted = ledger.submitted.lock().unwrap();
+    assert_eq!(submitted.len(), 1);
+    let envelope = &submitted[0];
+    let HttpSubmitContent::Call { update } = &envelope.content;
+    assert_eq!(update.method_name, "send_pb");
+    assert_eq!(
+        envelope.sender_sig.as_ref().unwrap().0,
+        signature.to_bytes().to_vec()
+    );
+}
+
+#[test]
+fn combine_rejects_signatures_by_other_keys() {
+    let (_ledger, handler) = setup();
+    let network_identifier = handler.network_id();
+    let sender = keypair(1);
+    let other = keypair(2);
+    let from = AccountIdentifier::new(
+        convert::principal_id_from_public_key(&public_key(&sender)).unwrap(),
+        None,
+    );
+
+    let payloads = handler
+        .construction_payloads(ConstructionPayloadsRequest {
+            network_identifier: network_identifier.clone(),
+            operations: transfer_operations(&from, &account(2)),
+            public_keys: vec![public_key(&sender)],
+            metadata: None,
+        })
+        .unwrap();
+    let payload = &payloads.payloads[0];
+    let message = hex::decode(&payload.hex_bytes).unwrap();
+    let combine = |signer: &Keypair, public_key: PublicKey| {
+        handler.construction_combine(ConstructionCombineRequest {
+            network_identifier: network_identifier.clone(),
+            unsigned_transaction: payloads.unsigned_transaction.clone(),
+            signatures: vec![Signature {
+                signing_payload: payload.clone(),
+                public_key,
+                signature_type: SignatureType::Ed25519,
+                hex_bytes: hex::encode(signer.sign(&message).to_bytes()),
+            }],
+        })
+    };
+
+    // Signed with the key of someone other than the sender.
+    assert!(matches!(
+        combine(&other, public_key(&other)),
+        Err(ApiError::InvalidRequest(_))
+    ));
+    // Signed with another key, but claimed to be signed with the sender's key.
+    assert!(matches!(
+        combine(&other, public_key(&sender)),
+        Err(ApiError::InvalidRequest(_))
+    ));
+    assert!(combine(&sender, public_key(&sender)).is_ok());
+}
+
+#[test]
+fn payloads_require_the_key_of_the_sender() {
+    let (_ledger, handler) = setup();
+    let result = handler.construction_payloads(ConstructionPayloadsRequest {
+        network_identifier: handler.network_id(),
+        operations: transfer_operations(&account(1), &account(2)),
+        public_keys: vec![public_key(&keypair(1))],
+        metadata: None,
+    });
+    assert!(matches!(result, Err(ApiError::InvalidRequest(_))));
+}
@@ -69,6 +69,7 @@ pub mod ic0 {
         );
         pub fn call_data_append(src: u32, size: u32);
         pub fn call_on_cleanup(fun: usize, env: u32);
+        pub fn call_with_best_effort_response(timeout_seconds: u32);
         pub fn call_cycles_add(amount: u64);
         pub fn call_perform() -> i32;
         pub fn stable_size() -> u32;
@@ -195,6 +196,10 @@ pub mod ic0 {
         wrong_arch("call_on_cleanup")
     }
 
+    pub unsafe fn call_with_best_effort_response(_timeout_seconds: u32) {
+        wrong_arch("call_with_best_effort_response")
+    }
+
     pub unsafe fn call_cycles_add(_amount: u64) {
         wrong_arch("call_cycles_add")
     }
@@ -4,6 +4,7 @@ pub mod endpoint;
 pub mod printer;
 pub mod setup;
 pub mod stable;
+pub mod stable_structures;
 
 pub use api::futures::FutureResult;
 pub use api::{call, call_explicit, CanisterId};
@@ -0,0 +1,313 @@
+//! Data structures that live in stable memory and are read and written in
+//! place, so that they survive upgrades without being serialized in
+//! `canister_pre_upgrade`.
+//!
+//! The stable memory is managed by an allocator, which takes over the whole
+//! stable memory: the structures of this module cannot be used together with
+//! `stable::set`, `StableWriter` and the like. The layout is the following:
+//!
+//! 0       8         12         16         24          32                256
+//! +-------+---------+----------+----------+-----------+-----------------+---->
+//! | magic | version | reserved | heap end | free list | roots (16 * u64) | heap
+//! +-------+---------+----------+----------+-----------+-----------------+---->
+//!
+//! The heap is a sequence of blocks, each prefixed by its size. Free blocks
+//! form a list sorted by address, so that adjacent free blocks get merged.
+//! Each structure starts with a header holding its own magic and version, so
+//! that a new version of a structure can convert older ones in place.
+//!
+//! A canister typically calls [`init`] in `canister_init` and
+//! `canister_post_upgrade`, and keeps the addresses of its structures in the
+//! root slots, see [`set_root`] and [`root`].
+use crate::stable::{stable64_grow, stable64_read, stable64_size, stable64_write};
+
+mod btreemap;
+mod log;
+mod vec;
+
+pub use btreemap::{StableBTreeMap, StableBTreeMapIter};
+pub use log::StableLog;
+pub use vec::StableVec;
+
+/// The version of the allocator layout.
+pub const VERSION: u32 = 1;
+
+/// The number of root slots.
+pub const NUM_ROOTS: usize = 16;
+
+/// The address used for "no block".
+pub const NULL: u64 = 0;
+
+const PAGE_SIZE: u64 = 64 * 1024;
+const MAGIC: &[u8; 8] = b"DFNSTMEM";
+const VERSION_OFFSET: u64 = 8;
+const HEAP_END_OFFSET: u64 = 16;
+const FREE_LIST_OFFSET: u64 = 24;
+const ROOTS_OFFSET: u64 = 32;
+const HEADER_SIZE: u64 = 256;
+
+/// Each block starts with its size, including this header.
+const BLOCK_HEADER_SIZE: u64 = 8;
+/// A free block must hold its size and the address of the next free block.
+const MIN_BLOCK_SIZE: u64 = 16;
+
+/// A value that can be stored in stable memory.
+pub trait Storable {
+    fn to_bytes(&self) -> Vec<u8>;
+    fn from_bytes(bytes: Vec<u8>) -> Self;
+}
+
+impl Storable for Vec<u8> {
+    fn to_bytes(&self) -> Vec<u8> {
+        self.clone()
+    }
+
+    fn from_bytes(bytes: Vec<u8>) -> Self {
+        bytes
+    }
+}
+
+impl Storable for String {
+    fn to_bytes(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+
+    fn from_bytes(bytes: Vec<u8>) -> Self {
+        String::from_utf8(bytes).expect("Stored string is not valid UTF-8")
+    }
+}
+
+macro_rules! storable_int {
+    ($($t:ty),*) => {
+        $(
+            impl Storable for $t {
+                fn to_bytes(&self) -> Vec<u8> {
+                    self.to_le_bytes().to_vec()
+                }
+
+                fn from_bytes(bytes: Vec<u8>) -> Self {
+                    let mut buf = [0; std::mem::size_of::<$t>()];
+                    buf.copy_from_slice(&bytes);
+                    <$t>::from_le_bytes(buf)
+                }
+            }
+        )*
+    };
+}
+
+storable_int!(u8, u16, u32, u64, i32, i64);
+
+/// Formats the stable memory if it is empty, or checks that it was formatted
+/// by a compatible version otherwise.
+pub fn init() -> Result<(), String> {
+    if stable64_size() == 0 {
+        ensure_size(HEADER_SIZE);
+        write_bytes(0, MAGIC);
+        write_u32(VERSION_OFFSET, VERSION);
+        write_u64(HEAP_END_OFFSET, HEADER_SIZE);
+        write_u64(FREE_LIST_OFFSET, NULL);
+        return Ok(());
+    }
+    let mut magic = [0; 8];
+    stable64_read(&mut magic, 0, 8);
+    if &magic != MAGIC {
+        return Err("The stable memory does not hold stable structures".to_string());
+    }
+    let version = read_u32(VERSION_OFFSET);
+    if version != VERSION {
+        return Err(format!(
+            "The stable memory has version {} of the stable structures, expected {}",
+            version, VERSION
+        ));
+    }
+    Ok(())
+}
+
+/// Returns the address stored in the given root slot, or `NULL`.
+pub fn root(slot: usize) -> u64 {
+    assert!(slot < NUM_ROOTS, "There are only {} root slots", NUM_ROOTS);
+    read_u64(ROOTS_OFFSET + 8 * slot as u64)
+}
+
+/// Stores an address in the given root slot.
+pub fn set_root(slot: usize, addr: u64) {
+    assert!(slot < NUM_ROOTS, "There are only {} root slots", NUM_ROOTS);
+    write_u64(ROOTS_OFFSET + 8 * slot as u64, addr)
+}
+
+/// The end of the part of the stable memory used so far.
+pub fn heap_end() -> u64 {
+    read_u64(HEAP_END_OFFSET)
+}
+
+/// Allocates `size` bytes of stable memory and returns their address. Traps
+/// if the stable memory cannot grow.
+pub fn allocate(size: u64) -> u64 {
+    let block_size = ((size + 7) / 8 * 8 + BLOCK_HEADER_SIZE).max(MIN_BLOCK_SIZE);
+
+    // First fit in the free list.
+    let mut prev = NULL;
+    let mut block = read_u64(FREE_LIST_OFFSET);
+    while block != NULL {
+        let free_size = read_u64(block);
+        let next = read_u64(block + BLOCK_HEADER_SIZE);
+        if free_size >= block_size {
+            let next = if free_size - block_size >= MIN_BLOCK_SIZE {
+                let rest = block + block_size;
+                write_u64(rest, free_size - block_size);
+                write_u64(rest + BLOCK_HEADER_SIZE, next);
+                write_u64(block, block_size);
+                rest
+            } else {
+                next
+            };
+            set_next_free(prev, next);
+            return block + BLOCK_HEADER_SIZE;
+        }
+        prev = block;
+        block = next;
+    }
+
+    let block = heap_end();
+    ensure_size(block + block_size);
+    write_u64(HEAP_END_OFFSET, block + block_size);
+    write_u64(block, block_size);
+    block + BLOCK_HEADER_SIZE
+}
+
+/// Frees memory returned by [`allocate`].
+pub fn deallocate(addr: u64) {
+    let block = addr - BLOCK_HEADER_SIZE;
+    let mut size = read_u64(block);
+    debug_assert!(block + size <= heap_end());
+
+    let mut prev = NULL;
+    let mut next = read_u64(FREE_LIST_OFFSET);
+    while next != NULL && next < block {
+        prev = next;
+        next = read_u64(next + BLOCK_HEADER_SIZE);
+    }
+    if next != NULL && block + size == next {
+        size += read_u64(next);
+        next = read_u64(next + BLOCK_HEADER_SIZE);
+    }
+    if prev != NULL && prev + read_u64(prev) == block {
+        write_u64(prev, read_u64(prev) + size);
+        write_u64(prev + BLOCK_HEADER_SIZE, next);
+    } else {
+        write_u64(block, size);
+        write_u64(block + BLOCK_HEADER_SIZE, next);
+        set_next_free(prev, block);
+    }
+}
+
+/// Moves the first `old_size` bytes at `addr` to a new allocation of
+/// `new_size` bytes, and frees the old one.
+pub fn reallocate(addr: u64, old_size: u64, new_size: u64) -> u64 {
+    let new_addr = allocate(new_size);
+    let mut buf = vec![0; old_size.min(PAGE_SIZE) as usize];
+    let mut copied = 0;
+    while copied < old_size {
+        let len = (old_size - copied).min(PAGE_SIZE);
+        stable64_read(&mut buf[..len as usize], addr + copied, len);
+        write_bytes(new_addr + copied, &buf[..len as usize]);
+        copied += len;
+    }
+    if addr != NULL {
+        deallocate(addr);
+    }
+    new_addr
+}
+
+fn set_next_free(prev: u64, next: u64) {
+    if prev == NULL {
+        write_u64(FREE_LIST_OFFSET, next)
+    } else {
+        write_u64(prev + BLOCK_HEADER_SIZE, next)
+    }
+}
+
+fn ensure_size(end: u64) {
+    let pages = stable64_size();
+    let needed = (end + PAGE_SIZE - 1) / PAGE_SIZE;
+    if needed > pages && stable64_grow(needed - pages) < 0 {
+        panic!("Out of stable memory: cannot grow to {} pages", needed);
+    }
+}
+
+/// Stores a value in a new allocation, prefixed by its length.
+fn store<T: Storable>(value: &T) -> u64 {
+    let bytes = value.to_bytes();
+    let addr = allocate(8 + bytes.len() as u64);
+    write_u64(addr, bytes.len() as u64);
+    write_bytes(addr + 8, &bytes);
+    addr
+}
+
+/// Reads a value written by [`store`].
+fn load<T: Storable>(addr: u64) -> T {
+    T::from_bytes(read_bytes(addr + 8, read_u64(addr)))
+}
+
+/// Writes the magic and version that start the header of a structure.
+fn write_structure_header(addr: u64, magic: &[u8; 4], version: u32) {
+    write_bytes(addr, magic);
+    write_u32(addr + 4, version);
+}
+
+/// Checks the magic and version that start the header of a structure.
+fn check_structure_header(addr: u64, magic: &[u8; 4], version: u32) -> Result<(), String> {
+    if addr == NULL || addr >= heap_end() {
+        return Err(format!("No structure at address {}", addr));
+    }
+    if read_bytes(addr, 4) != magic {
+        return Err(format!(
+            "The structure at address {} is not a {}",
+            addr,
+            String::from_utf8_lossy(magic)
+        ));
+    }
+    let found = read_u32(addr + 4);
+    if found != version {
+        return Err(format!(
+            "The {} at address {} has version {}, expected {}",
+            String::from_utf8_lossy(magic),
+            addr,
+            found,
+            version
+        ));
+    }
+    Ok(())
+}
+
+fn read_bytes(addr: u64, len: u64) -> Vec<u8> {
+    let mut buf = vec![0; len as usize];
+    stable64_read(&mut buf, addr, len);
+    buf
+}
+
+fn write_bytes(addr: u64, bytes: &[u8]) {
+    if !bytes.is_empty() {
+        stable64_write(addr, bytes);
+    }
+}
+
+fn read_u32(addr: u64) -> u32 {
+    let mut buf = [0; 4];
+    stable64_read(&mut buf, addr, 4);
+    u32::from_le_bytes(buf)
+}
+
+fn write_u32(addr: u64, value: u32) {
+    stable64_write(addr, &value.to_le_bytes());
+}
+
+fn read_u64(addr: u64) -> u64 {
+    let mut buf = [0; 8];
+    stable64_read(&mut buf, addr, 8);
+    u64::from_le_bytes(buf)
+}
+
+fn write_u64(addr: u64, value: u64) {
+    stable64_write(addr, &value.to_le_bytes());
+}
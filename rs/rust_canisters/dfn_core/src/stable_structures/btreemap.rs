@@ -0,0 +1,463 @@
+use super::{
+    allocate, check_structure_header, deallocate, load, read_bytes, read_u64, store, write_bytes,
+    write_structure_header, write_u64, Storable, NULL,
+};
+use std::cmp::Ordering;
+use std::marker::PhantomData;
+
+/// The version of the map layout.
+pub const VERSION: u32 = 1;
+
+/// The header of a map:
+/// 0       4         8     16          24
+/// +-------+---------+-----+-----------+
+/// | magic | version | len | root addr |
+/// +-------+---------+-----+-----------+
+const MAGIC: &[u8; 4] = b"SBTR";
+const LEN_OFFSET: u64 = 8;
+const ROOT_OFFSET: u64 = 16;
+const HEADER_SIZE: u64 = 24;
+
+/// The minimum degree of the B-tree: every node but the root has between
+/// `B - 1` and `2 * B - 1` keys.
+const B: usize = 6;
+const CAPACITY: usize = 2 * B - 1;
+
+/// A node holds the number of its keys and children, followed by the
+/// addresses of its keys, of its values and of its children:
+/// 0       4           8                                    8 + 16 * CAPACITY
+/// +-------+-----------+--------------------+----------------------+----------+
+/// | #keys | #children | keys (CAPACITY * 8) | values (CAPACITY * 8) | children |
+/// +-------+-----------+--------------------+----------------------+----------+
+const KEYS_OFFSET: usize = 8;
+const VALUES_OFFSET: usize = KEYS_OFFSET + 8 * CAPACITY;
+const CHILDREN_OFFSET: usize = VALUES_OFFSET + 8 * CAPACITY;
+const NODE_SIZE: usize = CHILDREN_OFFSET + 8 * (CAPACITY + 1);
+
+/// A node loaded in the heap. Keys and values are the addresses where they
+/// are stored.
+struct Node {
+    addr: u64,
+    keys: Vec<u64>,
+    values: Vec<u64>,
+    children: Vec<u64>,
+}
+
+impl Node {
+    fn new() -> Self {
+        Self {
+            addr: allocate(NODE_SIZE as u64),
+            keys: vec![],
+            values: vec![],
+            children: vec![],
+        }
+    }
+
+    fn load(addr: u64) -> Self {
+        let buf = read_bytes(addr, NODE_SIZE as u64);
+        let read = |offset: usize| {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(&buf[offset..offset + 8]);
+            u64::from_le_bytes(bytes)
+        };
+        let num_keys = (read(0) & 0xffff_ffff) as usize;
+        let num_children = (read(0) >> 32) as usize;
+        Self {
+            addr,
+            keys: (0..num_keys).map(|i| read(KEYS_OFFSET + 8 * i)).collect(),
+            values: (0..num_keys).map(|i| read(VALUES_OFFSET + 8 * i)).collect(),
+            children: (0..num_children)
+                .map(|i| read(CHILDREN_OFFSET + 8 * i))
+                .collect(),
+        }
+    }
+
+    fn save(&self) {
+        debug_assert!(self.keys.len() <= CAPACITY);
+        let mut buf = vec![0; NODE_SIZE];
+        let mut write = |offset: usize, value: u64| {
+            buf[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+        };
+        write(
+            0,
+            self.keys.len() as u64 | (self.children.len() as u64) << 32,
+        );
+        for (i, key) in self.keys.iter().enumerate() {
+            write(KEYS_OFFSET + 8 * i, *key);
+        }
+        for (i, value) in self.values.iter().enumerate() {
+            write(VALUES_OFFSET + 8 * i, *value);
+        }
+        for (i, child) in self.children.iter().enumerate() {
+            write(CHILDREN_OFFSET + 8 * i, *child);
+        }
+        write_bytes(self.addr, &buf);
+    }
+
+    fn is_leaf(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    /// Returns `Ok` with the position of `key` if the node holds it, or `Err`
+    /// with the position where it would be inserted.
+    fn search<K: Storable + Ord>(&self, key: &K) -> Result<usize, usize> {
+        let (mut low, mut high) = (0, self.keys.len());
+        while low < high {
+            let mid = (low + high) / 2;
+            match load::<K>(self.keys[mid]).cmp(key) {
+                Ordering::Less => low = mid + 1,
+                Ordering::Greater => high = mid,
+                Ordering::Equal => return Ok(mid),
+            }
+        }
+        Err(low)
+    }
+}
+
+/// A sorted map in stable memory, implemented as a B-tree.
+pub struct StableBTreeMap<K, V> {
+    addr: u64,
+    _marker: PhantomData<(K, V)>,
+}
+
+impl<K: Storable + Ord, V: Storable> Default for StableBTreeMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Storable + Ord, V: Storable> StableBTreeMap<K, V> {
+    /// Allocates a new, empty map.
+    pub fn new() -> Self {
+        let addr = allocate(HEADER_SIZE);
+        write_structure_header(addr, MAGIC, VERSION);
+        write_u64(addr + LEN_OFFSET, 0);
+        write_u64(addr + ROOT_OFFSET, NULL);
+        Self {
+            addr,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Opens the map at the given address.
+    pub fn load(addr: u64) -> Result<Self, String> {
+        check_structure_header(addr, MAGIC, VERSION)?;
+        Ok(Self {
+            addr,
+            _marker: PhantomData,
+        })
+    }
+
+    /// The address of the map, to be passed to [`StableBTreeMap::load`].
+    pub fn addr(&self) -> u64 {
+        self.addr
+    }
+
+    pub fn len(&self) -> u64 {
+        read_u64(self.addr + LEN_OFFSET)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        let mut addr = self.root();
+        while addr != NULL {
+            let node = Node::load(addr);
+            match node.search(key) {
+                Ok(i) => return Some(load(node.values[i])),
+                Err(_) if node.is_leaf() => return None,
+                Err(i) => addr = node.children[i],
+            }
+        }
+        None
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Inserts a value, and returns the value previously stored under the same
+    /// key, if any.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let root = self.root();
+        let mut node = if root == NULL {
+            let node = Node::new();
+            self.set_root(node.addr);
+            node
+        } else {
+            Node::load(root)
+        };
+
+        // Full nodes are split on the way down, so that there is always room
+        // for a key moving up from a split child.
+        if node.keys.len() == CAPACITY {
+            let mut new_root = Node::new();
+            new_root.children.push(node.addr);
+            split_child(&mut new_root, 0, node);
+            self.set_root(new_root.addr);
+            node = new_root;
+        }
+
+        loop {
+            match node.search(&key) {
+                Ok(i) => {
+                    let old = load(node.values[i]);
+                    deallocate(node.values[i]);
+                    node.values[i] = store(&value);
+                    node.save();
+                    return Some(old);
+                }
+                Err(i) if node.is_leaf() => {
+                    node.keys.insert(i, store(&key));
+                    node.values.insert(i, store(&value));
+                    node.save();
+                    write_u64(self.addr + LEN_OFFSET, self.len() + 1);
+                    return None;
+                }
+                Err(i) => {
+                    let child = Node::load(node.children[i]);
+                    if child.keys.len() == CAPACITY {
+                        // Search the node again, as it received the median of
+                        // the child.
+                        split_child(&mut node, i, child);
+                    } else {
+                        node = child;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Removes a key, and returns its value, if any.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let root = self.root();
+        if root == NULL {
+            return None;
+        }
+        let removed = remove_from::<K>(Node::load(root), key);
+
+        // Merges on the way down may have emptied the root, even if the key
+        // was not found.
+        let root_node = Node::load(root);
+        if root_node.keys.is_empty() {
+            self.set_root(root_node.children.first().copied().unwrap_or(NULL));
+            deallocate(root);
+        }
+
+        let (key, value) = removed?;
+        write_u64(self.addr + LEN_OFFSET, self.len() - 1);
+        deallocate(key);
+        let old = load(value);
+        deallocate(value);
+        Some(old)
+    }
+
+    /// Iterates over the entries in increasing order of keys, reading them
+    /// one at a time.
+    pub fn iter(&self) -> StableBTreeMapIter<'_, K, V> {
+        let mut iter = StableBTreeMapIter {
+            stack: vec![],
+            _marker: PhantomData,
+        };
+        iter.push_leftmost(self.root());
+        iter
+    }
+
+    fn root(&self) -> u64 {
+        read_u64(self.addr + ROOT_OFFSET)
+    }
+
+    fn set_root(&mut self, root: u64) {
+        write_u64(self.addr + ROOT_OFFSET, root)
+    }
+}
+
+/// Splits the full child at position `i` of `parent` in two, moving its
+/// median key up to `parent`.
+fn split_child(parent: &mut Node, i: usize, mut child: Node) {
+    let mut sibling = Node::new();
+    sibling.keys = child.keys.split_off(B);
+    sibling.values = child.values.split_off(B);
+    if !child.is_leaf() {
+        sibling.children = child.children.split_off(B);
+    }
+    parent.keys.insert(i, child.keys.pop().unwrap());
+    parent.values.insert(i, child.values.pop().unwrap());
+    parent.children.insert(i + 1, sibling.addr);
+    child.save();
+    sibling.save();
+    parent.save();
+}
+
+/// Merges the child at position `i + 1` of `parent`, and the key separating
+/// it from its left sibling, into that left sibling.
+fn merge_children(parent: &mut Node, i: usize, mut left: Node, right: Node) -> Node {
+    left.keys.push(parent.keys.remove(i));
+    left.values.push(parent.values.remove(i));
+    parent.children.remove(i + 1);
+    left.keys.extend(right.keys);
+    left.values.extend(right.values);
+    left.children.extend(right.children);
+    deallocate(right.addr);
+    left.save();
+    parent.save();
+    left
+}
+
+/// Makes sure that the child at position `i` of `parent` has at least `B`
+/// keys, by moving a key from a sibling or by merging it with a sibling, and
+/// returns it.
+fn fill_child(parent: &mut Node, i: usize, mut child: Node) -> Node {
+    let left = if i > 0 {
+        Some(Node::load(parent.children[i - 1]))
+    } else {
+        None
+    };
+    if let Some(mut left) = left.filter(|left| left.keys.len() >= B) {
+        child.keys.insert(0, parent.keys[i - 1]);
+        child.values.insert(0, parent.values[i - 1]);
+        parent.keys[i - 1] = left.keys.pop().unwrap();
+        parent.values[i - 1] = left.values.pop().unwrap();
+        if !left.is_leaf() {
+            child.children.insert(0, left.children.pop().unwrap());
+        }
+        left.save();
+        child.save();
+        parent.save();
+        return child;
+    }
+
+    if i < parent.keys.len() {
+        let mut right = Node::load(parent.children[i + 1]);
+        if right.keys.len() < B {
+            return merge_children(parent, i, child, right);
+        }
+        child.keys.push(parent.keys[i]);
+        child.values.push(parent.values[i]);
+        parent.keys[i] = right.keys.remove(0);
+        parent.values[i] = right.values.remove(0);
+        if !right.is_leaf() {
+            child.children.push(right.children.remove(0));
+        }
+        right.save();
+        child.save();
+        parent.save();
+        return child;
+    }
+
+    let left = Node::load(parent.children[i - 1]);
+    merge_children(parent, i - 1, left, child)
+}
+
+/// Removes `key` from the subtree rooted at `node`, which has at least `B`
+/// keys unless it is the root, and returns the addresses of the removed key
+/// and value.
+fn remove_from<K: Storable + Ord>(mut node: Node, key: &K) -> Option<(u64, u64)> {
+    loop {
+        match node.search(key) {
+            Ok(i) if node.is_leaf() => {
+                let removed = (node.keys.remove(i), node.values.remove(i));
+                node.save();
+                return Some(removed);
+            }
+            Ok(i) => {
+                let left = Node::load(node.children[i]);
+                if left.keys.len() >= B {
+                    // Replace the key with its predecessor.
+                    let predecessor: K = load(last_key(left.addr));
+                    let (key, value) = remove_from(left, &predecessor).unwrap();
+                    let removed = (node.keys[i], node.values[i]);
+                    node.keys[i] = key;
+                    node.values[i] = value;
+                    node.save();
+                    return Some(removed);
+                }
+                let right = Node::load(node.children[i + 1]);
+                if right.keys.len() >= B {
+                    // Replace the key with its successor.
+                    let successor: K = load(first_key(right.addr));
+                    let (key, value) = remove_from(right, &successor).unwrap();
+                    let removed = (node.keys[i], node.values[i]);
+                    node.keys[i] = key;
+                    node.values[i] = value;
+                    node.save();
+                    return Some(removed);
+                }
+                node = merge_children(&mut node, i, left, right);
+            }
+            Err(_) if node.is_leaf() => return None,
+            Err(i) => {
+                let child = Node::load(node.children[i]);
+                node = if child.keys.len() >= B {
+                    child
+                } else {
+                    fill_child(&mut node, i, child)
+                };
+            }
+        }
+    }
+}
+
+/// The address of the smallest key of the subtree rooted at `addr`.
+fn first_key(mut addr: u64) -> u64 {
+    loop {
+        let node = Node::load(addr);
+        if node.is_leaf() {
+            return node.keys[0];
+        }
+        addr = node.children[0];
+    }
+}
+
+/// The address of the largest key of the subtree rooted at `addr`.
+fn last_key(mut addr: u64) -> u64 {
+    loop {
+        let node = Node::load(addr);
+        if node.is_leaf() {
+            return *node.keys.last().unwrap();
+        }
+        addr = *node.children.last().unwrap();
+    }
+}
+
+/// An iterator over the entries of a [`StableBTreeMap`].
+pub struct StableBTreeMapIter<'a, K, V> {
+    /// The nodes from the root to the current one, with the position of the
+    /// next key to visit in each of them.
+    stack: Vec<(Node, usize)>,
+    _marker: PhantomData<&'a StableBTreeMap<K, V>>,
+}
+
+impl<'a, K, V> StableBTreeMapIter<'a, K, V> {
+    fn push_leftmost(&mut self, mut addr: u64) {
+        while addr != NULL {
+            let node = Node::load(addr);
+            let child = node.children.first().copied().unwrap_or(NULL);
+            self.stack.push((node, 0));
+            addr = child;
+        }
+    }
+}
+
+impl<'a, K: Storable, V: Storable> Iterator for StableBTreeMapIter<'a, K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (node, i) = self.stack.last_mut()?;
+            if *i == node.keys.len() {
+                self.stack.pop();
+                continue;
+            }
+            let entry = (load(node.keys[*i]), load(node.values[*i]));
+            *i += 1;
+            let child = node.children.get(*i).copied();
+            if let Some(child) = child {
+                self.push_leftmost(child);
+            }
+            return Some(entry);
+        }
+    }
+}
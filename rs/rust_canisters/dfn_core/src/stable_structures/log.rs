@@ -0,0 +1,160 @@
+use super::{
+    allocate, check_structure_header, read_bytes, read_u64, write_bytes, write_structure_header,
+    write_u64, StableVec, Storable,
+};
+use std::convert::TryInto;
+use std::marker::PhantomData;
+
+/// The version of the log layout.
+pub const VERSION: u32 = 1;
+
+/// The header of a log:
+/// 0       4         8     16          24                  32                 40
+/// +-------+---------+-----+-----------+-------------------+------------------+
+/// | magic | version | len | data size | index chunks addr | data chunks addr |
+/// +-------+---------+-----+-----------+-------------------+------------------+
+///
+/// The entries are stored one after the other in the data buffer, and the
+/// index holds the offset of the end of each entry in the data buffer. Both
+/// are split into chunks of `CHUNK_SIZE` bytes, whose addresses are kept in a
+/// [`StableVec`], so they grow by allocating new chunks rather than by moving
+/// what they hold. An entry may span several chunks.
+const MAGIC: &[u8; 4] = b"SLOG";
+const LEN_OFFSET: u64 = 8;
+const DATA_SIZE_OFFSET: u64 = 16;
+const INDEX_CHUNKS_OFFSET: u64 = 24;
+const DATA_CHUNKS_OFFSET: u64 = 32;
+const HEADER_SIZE: u64 = 40;
+
+/// The size of the chunks of the index and of the data buffer. A multiple of
+/// 8, so that no index entry spans two chunks.
+const CHUNK_SIZE: u64 = 64 * 1024;
+
+/// An append-only sequence of entries in stable memory.
+///
+/// Unlike [`super::StableVec`], entries are not allocated one by one but
+/// stored one after the other in fixed-size chunks, so appending an entry
+/// only writes the entry itself and never copies the existing ones. The
+/// entries cannot be changed or removed.
+pub struct StableLog<T> {
+    addr: u64,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Storable> Default for StableLog<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Storable> StableLog<T> {
+    /// Allocates a new, empty log.
+    pub fn new() -> Self {
+        let addr = allocate(HEADER_SIZE);
+        write_structure_header(addr, MAGIC, VERSION);
+        write_u64(addr + LEN_OFFSET, 0);
+        write_u64(addr + DATA_SIZE_OFFSET, 0);
+        write_u64(addr + INDEX_CHUNKS_OFFSET, StableVec::<u64>::new().addr());
+        write_u64(addr + DATA_CHUNKS_OFFSET, StableVec::<u64>::new().addr());
+        Self {
+            addr,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Opens the log at the given address.
+    pub fn load(addr: u64) -> Result<Self, String> {
+        check_structure_header(addr, MAGIC, VERSION)?;
+        Ok(Self {
+            addr,
+            _marker: PhantomData,
+        })
+    }
+
+    /// The address of the log, to be passed to [`StableLog::load`].
+    pub fn addr(&self) -> u64 {
+        self.addr
+    }
+
+    pub fn len(&self) -> u64 {
+        read_u64(self.addr + LEN_OFFSET)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Appends an entry and returns its index.
+    pub fn append(&mut self, value: &T) -> u64 {
+        let bytes = value.to_bytes();
+        let len = self.len();
+        let data_size = read_u64(self.addr + DATA_SIZE_OFFSET);
+        let end = data_size + bytes.len() as u64;
+
+        self.write(DATA_CHUNKS_OFFSET, data_size, &bytes);
+        self.write(INDEX_CHUNKS_OFFSET, 8 * len, &end.to_le_bytes());
+        write_u64(self.addr + DATA_SIZE_OFFSET, end);
+        write_u64(self.addr + LEN_OFFSET, len + 1);
+        len
+    }
+
+    pub fn get(&self, index: u64) -> Option<T> {
+        if index >= self.len() {
+            return None;
+        }
+        let start = if index == 0 { 0 } else { self.end(index - 1) };
+        let end = self.end(index);
+        Some(T::from_bytes(self.read(
+            DATA_CHUNKS_OFFSET,
+            start,
+            end - start,
+        )))
+    }
+
+    /// Iterates over the entries, reading them one at a time.
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        (0..self.len()).map(move |index| self.get(index).unwrap())
+    }
+
+    /// The offset of the end of an entry in the data buffer.
+    fn end(&self, index: u64) -> u64 {
+        let bytes = self.read(INDEX_CHUNKS_OFFSET, 8 * index, 8);
+        u64::from_le_bytes(bytes.as_slice().try_into().unwrap())
+    }
+
+    /// The addresses of the chunks of the index or of the data buffer.
+    fn chunks(&self, chunks_offset: u64) -> StableVec<u64> {
+        StableVec::load(read_u64(self.addr + chunks_offset))
+            .expect("The chunks of a log are not a vector")
+    }
+
+    /// Writes `bytes` at `offset` of the index or of the data buffer,
+    /// allocating the chunks that are missing.
+    fn write(&mut self, chunks_offset: u64, offset: u64, bytes: &[u8]) {
+        let mut chunks = self.chunks(chunks_offset);
+        while chunks.len() * CHUNK_SIZE < offset + bytes.len() as u64 {
+            chunks.push(&allocate(CHUNK_SIZE));
+        }
+        let mut written = 0;
+        while written < bytes.len() {
+            let pos = offset + written as u64;
+            let len = ((CHUNK_SIZE - pos % CHUNK_SIZE) as usize).min(bytes.len() - written);
+            let chunk = chunks.get(pos / CHUNK_SIZE).unwrap();
+            write_bytes(chunk + pos % CHUNK_SIZE, &bytes[written..written + len]);
+            written += len;
+        }
+    }
+
+    /// Reads `len` bytes at `offset` of the index or of the data buffer.
+    fn read(&self, chunks_offset: u64, offset: u64, len: u64) -> Vec<u8> {
+        let chunks = self.chunks(chunks_offset);
+        let mut bytes = Vec::with_capacity(len as usize);
+        while (bytes.len() as u64) < len {
+            let pos = offset + bytes.len() as u64;
+            let chunk_len = (CHUNK_SIZE - pos % CHUNK_SIZE).min(len - bytes.len() as u64);
+            let chunk = chunks.get(pos / CHUNK_SIZE).unwrap();
+            bytes.extend(read_bytes(chunk + pos % CHUNK_SIZE, chunk_len));
+        }
+        bytes
+    }
+}
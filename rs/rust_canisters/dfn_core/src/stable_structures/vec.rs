@@ -0,0 +1,133 @@
+use super::{
+    allocate, check_structure_header, deallocate, load, read_u64, reallocate, store,
+    write_structure_header, write_u64, Storable, NULL,
+};
+use std::marker::PhantomData;
+
+/// The version of the vector layout.
+pub const VERSION: u32 = 1;
+
+/// The header of a vector:
+/// 0       4         8     16         24           32
+/// +-------+---------+-----+----------+------------+
+/// | magic | version | len | capacity | items addr |
+/// +-------+---------+-----+----------+------------+
+///
+/// The items are an array of `capacity` addresses, each pointing to a stored
+/// element.
+const MAGIC: &[u8; 4] = b"SVEC";
+const LEN_OFFSET: u64 = 8;
+const CAPACITY_OFFSET: u64 = 16;
+const ITEMS_OFFSET: u64 = 24;
+const HEADER_SIZE: u64 = 32;
+
+/// A growable array in stable memory.
+pub struct StableVec<T> {
+    addr: u64,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Storable> Default for StableVec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Storable> StableVec<T> {
+    /// Allocates a new, empty vector.
+    pub fn new() -> Self {
+        let addr = allocate(HEADER_SIZE);
+        write_structure_header(addr, MAGIC, VERSION);
+        write_u64(addr + LEN_OFFSET, 0);
+        write_u64(addr + CAPACITY_OFFSET, 0);
+        write_u64(addr + ITEMS_OFFSET, NULL);
+        Self {
+            addr,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Opens the vector at the given address.
+    pub fn load(addr: u64) -> Result<Self, String> {
+        check_structure_header(addr, MAGIC, VERSION)?;
+        Ok(Self {
+            addr,
+            _marker: PhantomData,
+        })
+    }
+
+    /// The address of the vector, to be passed to [`StableVec::load`].
+    pub fn addr(&self) -> u64 {
+        self.addr
+    }
+
+    pub fn len(&self) -> u64 {
+        read_u64(self.addr + LEN_OFFSET)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, index: u64) -> Option<T> {
+        if index >= self.len() {
+            return None;
+        }
+        Some(load(self.item(index)))
+    }
+
+    /// Replaces the element at `index`. Panics if it is out of bounds.
+    pub fn set(&mut self, index: u64, value: &T) {
+        let len = self.len();
+        assert!(
+            index < len,
+            "Index {} is out of bounds of a vector of length {}",
+            index,
+            len
+        );
+        deallocate(self.item(index));
+        self.set_item(index, store(value));
+    }
+
+    pub fn push(&mut self, value: &T) {
+        let len = self.len();
+        let capacity = read_u64(self.addr + CAPACITY_OFFSET);
+        if len == capacity {
+            let new_capacity = (2 * capacity).max(4);
+            let items = reallocate(
+                read_u64(self.addr + ITEMS_OFFSET),
+                8 * capacity,
+                8 * new_capacity,
+            );
+            write_u64(self.addr + ITEMS_OFFSET, items);
+            write_u64(self.addr + CAPACITY_OFFSET, new_capacity);
+        }
+        self.set_item(len, store(value));
+        write_u64(self.addr + LEN_OFFSET, len + 1);
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        let len = self.len();
+        if len == 0 {
+            return None;
+        }
+        let item = self.item(len - 1);
+        let value = load(item);
+        deallocate(item);
+        write_u64(self.addr + LEN_OFFSET, len - 1);
+        Some(value)
+    }
+
+    /// Iterates over the elements, reading them one at a time.
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        (0..self.len()).map(move |index| load(self.item(index)))
+    }
+
+    fn item(&self, index: u64) -> u64 {
+        read_u64(read_u64(self.addr + ITEMS_OFFSET) + 8 * index)
+    }
+
+    fn set_item(&mut self, index: u64, item: u64) {
+        write_u64(read_u64(self.addr + ITEMS_OFFSET) + 8 * index, item)
+    }
+}
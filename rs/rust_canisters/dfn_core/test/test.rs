@@ -66,3 +66,43 @@ fn stable_memory_read_write() {
         Ok(())
     })
 }
+
+#[test]
+fn stable_structures_test() {
+    local_test_e(|r| async move {
+        let proj = Project::new(env!("CARGO_MANIFEST_DIR"));
+
+        let canister = proj.cargo_bin("wasm").install_(&r, Vec::new()).await?;
+
+        let _ = canister
+            .update_("exercise_stable_structures", bytes, vec![])
+            .await?;
+
+        Ok(())
+    })
+}
+
+#[test]
+fn stable_structures_survive_upgrades() {
+    local_test_e(|r| async move {
+        let proj = Project::new(env!("CARGO_MANIFEST_DIR"));
+
+        let mut canister = proj.cargo_bin("wasm").install_(&r, Vec::new()).await?;
+
+        let _ = canister
+            .update_("write_stable_structures", bytes, vec![3, 1, 2])
+            .await?;
+
+        canister.upgrade_to_self_binary(Vec::new()).await?;
+
+        let res = canister
+            .query_("read_stable_structures", bytes, vec![])
+            .await?;
+
+        assert_eq!(
+            String::from_utf8(res).unwrap(),
+            "[(1, 1), (2, 2), (3, 0)] [3, 1, 2] [[], [1], [2, 2]]"
+        );
+        Ok(())
+    })
+}
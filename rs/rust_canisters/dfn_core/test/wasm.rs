@@ -1,4 +1,6 @@
 use dfn_core::endpoint::{bytes, over};
+use dfn_core::stable_structures::{self, StableBTreeMap, StableLog, StableVec};
+use std::collections::BTreeMap;
 
 #[export_name = "canister_query reverse"]
 fn reverse() {
@@ -56,4 +58,130 @@ fn set_stable_memory_reader() {
     })
 }
 
+/// A deterministic pseudo-random sequence, to exercise the stable structures.
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self, bound: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % bound
+    }
+}
+
+/// Runs random operations on the stable structures and on their heap
+/// counterparts, and traps if they ever differ.
+#[export_name = "canister_update exercise_stable_structures"]
+fn exercise_stable_structures() {
+    over(bytes, |_| {
+        stable_structures::init().unwrap();
+        let mut rng = XorShift(42);
+
+        let mut map: StableBTreeMap<u64, Vec<u8>> = StableBTreeMap::new();
+        let mut expected_map = BTreeMap::new();
+        for _ in 0..5000 {
+            let key = rng.next(500);
+            match rng.next(3) {
+                0 | 1 => {
+                    let value = vec![key as u8; rng.next(100) as usize];
+                    assert_eq!(
+                        map.insert(key, value.clone()),
+                        expected_map.insert(key, value)
+                    );
+                }
+                _ => assert_eq!(map.remove(&key), expected_map.remove(&key)),
+            }
+            assert_eq!(map.get(&key), expected_map.get(&key).cloned());
+        }
+        assert_eq!(map.len(), expected_map.len() as u64);
+        assert!(map.iter().eq(expected_map.clone().into_iter()));
+
+        // Freed memory is reused.
+        for key in expected_map.keys() {
+            map.remove(key);
+        }
+        assert!(map.is_empty());
+        let heap_end = stable_structures::heap_end();
+        for key in expected_map.keys() {
+            map.insert(*key, vec![]);
+        }
+        assert_eq!(stable_structures::heap_end(), heap_end);
+
+        let mut vec: StableVec<String> = StableVec::new();
+        let mut expected_vec = vec![];
+        for i in 0..1000 {
+            match rng.next(4) {
+                0 => assert_eq!(vec.pop(), expected_vec.pop()),
+                1 if !expected_vec.is_empty() => {
+                    let index = rng.next(expected_vec.len() as u64);
+                    vec.set(index, &i.to_string());
+                    expected_vec[index as usize] = i.to_string();
+                }
+                _ => {
+                    vec.push(&i.to_string());
+                    expected_vec.push(i.to_string());
+                }
+            }
+        }
+        assert!(vec.iter().eq(expected_vec.into_iter()));
+
+        let mut log: StableLog<Vec<u8>> = StableLog::new();
+        let mut expected_log = vec![];
+        for i in 0..1000 {
+            let entry = vec![i as u8; rng.next(300) as usize];
+            assert_eq!(log.append(&entry), i);
+            expected_log.push(entry);
+        }
+        // An entry that spans several chunks of the data buffer.
+        let entry = vec![7; 150_000];
+        assert_eq!(log.append(&entry), 1000);
+        expected_log.push(entry);
+        assert!(log.iter().eq(expected_log.into_iter()));
+        assert_eq!(log.get(1001), None);
+
+        vec![]
+    })
+}
+
+/// Creates a map, a vector and a log holding the given bytes, and stores their
+/// addresses in the first root slots.
+#[export_name = "canister_update write_stable_structures"]
+fn write_stable_structures() {
+    over(bytes, |bytes| {
+        stable_structures::init().unwrap();
+        let mut map = StableBTreeMap::new();
+        let mut vec = StableVec::new();
+        let mut log = StableLog::new();
+        for (i, byte) in bytes.iter().enumerate() {
+            map.insert(*byte as u64, i as u64);
+            vec.push(byte);
+            log.append(&vec![*byte; i]);
+        }
+        stable_structures::set_root(0, map.addr());
+        stable_structures::set_root(1, vec.addr());
+        stable_structures::set_root(2, log.addr());
+        bytes
+    })
+}
+
+/// Reads the structures created by `write_stable_structures`.
+#[export_name = "canister_query read_stable_structures"]
+fn read_stable_structures() {
+    over(bytes, |_| {
+        stable_structures::init().unwrap();
+        let map: StableBTreeMap<u64, u64> =
+            StableBTreeMap::load(stable_structures::root(0)).unwrap();
+        let vec: StableVec<u8> = StableVec::load(stable_structures::root(1)).unwrap();
+        let log: StableLog<Vec<u8>> = StableLog::load(stable_structures::root(2)).unwrap();
+        format!(
+            "{:?} {:?} {:?}",
+            map.iter().collect::<Vec<_>>(),
+            vec.iter().collect::<Vec<_>>(),
+            log.iter().collect::<Vec<_>>()
+        )
+        .into_bytes()
+    })
+}
+
 fn main() {}
@@ -0,0 +1,399 @@
+//! Static assets served with a certificate, so that they can be verified
+//! without trusting the replica or a proxy that served them.
+//!
+//! The certified data of the canister is the root hash of the following tree,
+//! where each leaf is the SHA-256 of the body of an asset:
+//!
+//! ```text
+//! http_assets
+//! ├── /           -> sha256(body of /index.html)
+//! ├── /app.js     -> sha256(body)
+//! └── /index.html -> sha256(body)
+//! ```
+//!
+//! `/` is served `/index.html`, so its hash is certified under `/` too, for
+//! the response to `/` to be verified against the path that was requested.
+//!
+//! Each response carries an `IC-Certificate` header with the certificate of
+//! the canister and a witness for the path of the asset.
+use crate::types::{
+    HttpRequest, HttpResponse, StreamingCallbackHttpResponse, StreamingStrategy, Token,
+};
+use candid::{Func, Nat, Principal};
+use ic_crypto_sha::Sha256;
+use ic_crypto_tree_hash::{
+    FlatMap, HashTreeBuilder, HashTreeBuilderImpl, Label, LabeledTree, MixedHashTree,
+    WitnessGenerator, WitnessGeneratorImpl,
+};
+use num_traits::cast::ToPrimitive;
+use serde::Serialize;
+use serde_bytes::ByteBuf;
+use std::collections::BTreeMap;
+
+/// The label under which the hashes of the assets are certified.
+pub const ASSETS_LABEL: &str = "http_assets";
+
+/// The name of the query method that serves the chunks after the first one.
+pub const STREAMING_CALLBACK_METHOD: &str = "http_request_streaming_callback";
+
+/// The default size of the chunks in which bodies are sent, which keeps each
+/// response under the message size limit.
+pub const DEFAULT_CHUNK_SIZE: usize = 1_900_000;
+
+/// The path of the asset that is served at `/`.
+pub const INDEX_PATH: &str = "/index.html";
+
+struct Asset {
+    content_type: String,
+    body: Vec<u8>,
+    sha256: [u8; 32],
+}
+
+impl Asset {
+    fn new(content_type: &str, body: Vec<u8>) -> Self {
+        Self {
+            content_type: content_type.to_string(),
+            sha256: Sha256::hash(&body),
+            body,
+        }
+    }
+}
+
+/// A set of assets, by path, and the certification tree of their hashes.
+pub struct CertifiedAssets {
+    assets: BTreeMap<String, Asset>,
+    chunk_size: usize,
+    witness_generator: WitnessGeneratorImpl,
+}
+
+impl Default for CertifiedAssets {
+    fn default() -> Self {
+        Self::with_chunk_size(DEFAULT_CHUNK_SIZE)
+    }
+}
+
+impl CertifiedAssets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an empty set of assets whose bodies are sent in chunks of at
+    /// most `chunk_size` bytes.
+    pub fn with_chunk_size(chunk_size: usize) -> Self {
+        assert!(chunk_size > 0, "The chunk size must be positive");
+        let assets = BTreeMap::new();
+        let witness_generator = build_tree(&assets);
+        Self {
+            assets,
+            chunk_size,
+            witness_generator,
+        }
+    }
+
+    /// Adds or replaces the asset at `path`, and certifies the new set of
+    /// assets.
+    ///
+    /// This sets the certified data of the canister, so it can only be called
+    /// from `canister_init`, `canister_post_upgrade` or an update.
+    pub fn insert(&mut self, path: &str, content_type: &str, body: Vec<u8>) {
+        self.assets
+            .insert(path.to_string(), Asset::new(content_type, body));
+        self.certify();
+    }
+
+    /// Removes the asset at `path`, and certifies the new set of assets. The
+    /// same restrictions as for [`CertifiedAssets::insert`] apply.
+    pub fn remove(&mut self, path: &str) -> bool {
+        let removed = self.assets.remove(path).is_some();
+        self.certify();
+        removed
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.assets.contains_key(path)
+    }
+
+    /// The root hash of the certification tree.
+    pub fn root_hash(&self) -> [u8; 32] {
+        self.witness_generator.hash_tree().digest().0
+    }
+
+    /// Answers a request with the asset at the path of its URL, or with a 404
+    /// if there is none. `/` is served [`INDEX_PATH`].
+    ///
+    /// If the body is larger than the chunk size, the response holds the first
+    /// chunk and the others can be fetched with the streaming callback.
+    pub fn http_request(&self, request: HttpRequest) -> HttpResponse {
+        let path = match request.url.find('?') {
+            None => &request.url[..],
+            Some(index) => &request.url[..index],
+        };
+
+        let asset = match self.assets.get(asset_path(path)) {
+            Some(asset) => asset,
+            None => {
+                return HttpResponse {
+                    status_code: 404,
+                    headers: vec![],
+                    body: ByteBuf::from("not found"),
+                    streaming_strategy: None,
+                }
+            }
+        };
+
+        let mut headers = vec![("Content-Type".to_string(), asset.content_type.clone())];
+        if let Some(certificate) = dfn_core::api::data_certificate() {
+            headers.push((
+                "IC-Certificate".to_string(),
+                certificate_header(&certificate, &self.witness(path)),
+            ));
+        }
+
+        HttpResponse {
+            status_code: 200,
+            headers,
+            body: ByteBuf::from(self.chunk(asset, 0)),
+            streaming_strategy: self.next_token(asset_path(path), asset, 0).map(|token| {
+                StreamingStrategy::Callback {
+                    callback: Func {
+                        principal: Principal::from_slice(dfn_core::api::id().get().as_slice()),
+                        method: STREAMING_CALLBACK_METHOD.to_string(),
+                    },
+                    token,
+                }
+            }),
+        }
+    }
+
+    /// Returns the chunk designated by a token of a previous response, and the
+    /// token of the next chunk, if any.
+    ///
+    /// Panics if the asset no longer exists or changed since the first chunk
+    /// was served, so that the client does not assemble a corrupted body.
+    pub fn http_request_streaming_callback(&self, token: Token) -> StreamingCallbackHttpResponse {
+        let asset = self
+            .assets
+            .get(&token.key)
+            .unwrap_or_else(|| panic!("No asset at {}", token.key));
+        if token.sha256.as_ref().map(|sha256| &sha256[..]) != Some(&asset.sha256[..]) {
+            panic!("The asset at {} changed while it was streamed", token.key);
+        }
+        let index = token.index.0.to_usize().expect("Chunk index out of range");
+        StreamingCallbackHttpResponse {
+            body: ByteBuf::from(self.chunk(asset, index)),
+            token: self.next_token(&token.key, asset, index),
+        }
+    }
+
+    fn certify(&mut self) {
+        self.witness_generator = build_tree(&self.assets);
+        dfn_core::api::set_certified_data(&self.root_hash());
+    }
+
+    /// A witness that the asset served at `path` has the certified hash.
+    fn witness(&self, path: &str) -> MixedHashTree {
+        let asset_hash = self.assets[asset_path(path)].sha256.to_vec();
+        let partial_tree = LabeledTree::SubTree(FlatMap::from_key_values(vec![(
+            Label::from(ASSETS_LABEL),
+            LabeledTree::SubTree(FlatMap::from_key_values(vec![(
+                Label::from(path),
+                LabeledTree::Leaf(asset_hash),
+            )])),
+        )]));
+        self.witness_generator
+            .mixed_hash_tree(&partial_tree)
+            .expect("The path of an asset is in the certification tree")
+    }
+
+    fn chunk<'a>(&self, asset: &'a Asset, index: usize) -> &'a [u8] {
+        let start = index.saturating_mul(self.chunk_size).min(asset.body.len());
+        let end = start.saturating_add(self.chunk_size).min(asset.body.len());
+        &asset.body[start..end]
+    }
+
+    fn next_token(&self, path: &str, asset: &Asset, index: usize) -> Option<Token> {
+        let next = index + 1;
+        if next.saturating_mul(self.chunk_size) >= asset.body.len() {
+            return None;
+        }
+        Some(Token {
+            key: path.to_string(),
+            content_encoding: "identity".to_string(),
+            index: Nat::from(next),
+            sha256: Some(ByteBuf::from(asset.sha256.to_vec())),
+        })
+    }
+}
+
+fn build_tree(assets: &BTreeMap<String, Asset>) -> WitnessGeneratorImpl {
+    let mut builder = HashTreeBuilderImpl::new();
+    builder.start_subtree();
+    builder.new_edge(ASSETS_LABEL);
+    builder.start_subtree();
+    for (path, sha256) in certified_hashes(assets) {
+        builder.new_edge(path);
+        builder.start_leaf();
+        builder.write_leaf(&sha256[..]);
+        builder.finish_leaf();
+    }
+    builder.finish_subtree();
+    builder.finish_subtree();
+    builder
+        .witness_generator()
+        .expect("The certification tree is complete")
+}
+
+/// The path of the asset that is served at `path`.
+fn asset_path(path: &str) -> &str {
+    if path == "/" {
+        INDEX_PATH
+    } else {
+        path
+    }
+}
+
+/// The hashes to certify, by path in ascending order: those of the assets,
+/// and that of [`INDEX_PATH`] under `/` too.
+fn certified_hashes(assets: &BTreeMap<String, Asset>) -> BTreeMap<&str, &[u8; 32]> {
+    let mut hashes: BTreeMap<&str, &[u8; 32]> = assets
+        .iter()
+        .map(|(path, asset)| (&path[..], &asset.sha256))
+        .collect();
+    if let Some(index) = assets.get(INDEX_PATH) {
+        hashes.insert("/", &index.sha256);
+    }
+    hashes
+}
+
+/// The value of the `IC-Certificate` header, holding the certificate and the
+/// witness, encoded in self-describing CBOR.
+fn certificate_header(certificate: &[u8], witness: &MixedHashTree) -> String {
+    let mut serializer = serde_cbor::ser::Serializer::new(vec![]);
+    serializer.self_describe().unwrap();
+    witness.serialize(&mut serializer).unwrap();
+    format!(
+        "certificate=:{}:, tree=:{}:",
+        base64::encode(certificate),
+        base64::encode(serializer.into_inner())
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_crypto_tree_hash::lookup_path;
+    use std::convert::TryFrom;
+
+    const CHUNK_SIZE: usize = 10;
+
+    /// Builds the certification tree as `insert` does, without setting the
+    /// certified data, which only a canister can do.
+    fn certified_assets(contents: Vec<(&str, Vec<u8>)>) -> CertifiedAssets {
+        let mut certified_assets = CertifiedAssets::with_chunk_size(CHUNK_SIZE);
+        for (path, body) in contents {
+            certified_assets.assets.insert(
+                path.to_string(),
+                Asset::new("application/octet-stream", body),
+            );
+        }
+        certified_assets.witness_generator = build_tree(&certified_assets.assets);
+        certified_assets
+    }
+
+    /// The chunks of the body at `path`, as a client streams them.
+    fn stream(certified_assets: &CertifiedAssets, path: &str) -> Vec<Vec<u8>> {
+        let asset = &certified_assets.assets[path];
+        let mut chunks = vec![certified_assets.chunk(asset, 0).to_vec()];
+        let mut token = certified_assets.next_token(path, asset, 0);
+        while let Some(next) = token {
+            let response = certified_assets.http_request_streaming_callback(next);
+            chunks.push(response.body.into_vec());
+            token = response.token;
+        }
+        chunks
+    }
+
+    fn body(len: usize) -> Vec<u8> {
+        (0..len).map(|i| i as u8).collect()
+    }
+
+    #[test]
+    fn empty_body_is_a_single_empty_chunk() {
+        let certified_assets = certified_assets(vec![("/empty", vec![])]);
+        assert_eq!(stream(&certified_assets, "/empty"), vec![Vec::<u8>::new()]);
+    }
+
+    #[test]
+    fn body_smaller_than_a_chunk_is_not_streamed() {
+        let certified_assets = certified_assets(vec![("/small", body(CHUNK_SIZE - 1))]);
+        assert_eq!(
+            stream(&certified_assets, "/small"),
+            vec![body(CHUNK_SIZE - 1)]
+        );
+    }
+
+    #[test]
+    fn body_of_a_multiple_of_the_chunk_size_has_no_empty_last_chunk() {
+        let certified_assets = certified_assets(vec![("/exact", body(3 * CHUNK_SIZE))]);
+        let chunks = stream(&certified_assets, "/exact");
+        assert_eq!(
+            chunks.iter().map(Vec::len).collect::<Vec<_>>(),
+            vec![CHUNK_SIZE; 3]
+        );
+        assert_eq!(chunks.concat(), body(3 * CHUNK_SIZE));
+    }
+
+    #[test]
+    fn last_chunk_holds_the_rest_of_the_body() {
+        let certified_assets = certified_assets(vec![("/large", body(2 * CHUNK_SIZE + 1))]);
+        let chunks = stream(&certified_assets, "/large");
+        assert_eq!(
+            chunks.iter().map(Vec::len).collect::<Vec<_>>(),
+            vec![CHUNK_SIZE, CHUNK_SIZE, 1]
+        );
+        assert_eq!(chunks.concat(), body(2 * CHUNK_SIZE + 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "changed while it was streamed")]
+    fn streaming_an_asset_that_changed_panics() {
+        let mut certified_assets = certified_assets(vec![("/large", body(2 * CHUNK_SIZE))]);
+        let token = certified_assets
+            .next_token("/large", &certified_assets.assets["/large"], 0)
+            .unwrap();
+        certified_assets.assets.insert(
+            "/large".to_string(),
+            Asset::new("application/octet-stream", body(2 * CHUNK_SIZE + 1)),
+        );
+        certified_assets.http_request_streaming_callback(token);
+    }
+
+    #[test]
+    fn witnesses_reconstruct_the_root_hash() {
+        let certified_assets = certified_assets(vec![
+            (INDEX_PATH, b"<html></html>".to_vec()),
+            ("/app.js", b"main()".to_vec()),
+            ("/large", body(2 * CHUNK_SIZE + 1)),
+        ]);
+        for (path, served) in &[
+            ("/", INDEX_PATH),
+            (INDEX_PATH, INDEX_PATH),
+            ("/app.js", "/app.js"),
+            ("/large", "/large"),
+        ] {
+            let witness = certified_assets.witness(path);
+            assert_eq!(witness.digest().0, certified_assets.root_hash());
+
+            let tree = LabeledTree::try_from(witness).unwrap();
+            assert_eq!(
+                lookup_path(&tree, &[ASSETS_LABEL.as_bytes(), path.as_bytes()]),
+                Some(&LabeledTree::Leaf(
+                    Sha256::hash(&certified_assets.assets[*served].body).to_vec()
+                )),
+                "The witness of {} does not certify the body of {}",
+                path,
+                served
+            );
+        }
+    }
+}
@@ -1,3 +1,4 @@
+pub mod assets;
 pub mod types;
 
 use candid::CandidType;
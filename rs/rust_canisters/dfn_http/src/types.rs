@@ -17,8 +17,18 @@ pub struct HttpResponse {
     pub streaming_strategy: Option<StreamingStrategy>,
 }
 
+/// Designates the next chunk of a streamed body.
 #[derive(Clone, Debug, CandidType, Deserialize)]
-pub struct Token {}
+pub struct Token {
+    /// The path of the asset.
+    pub key: String,
+    pub content_encoding: String,
+    /// The index of the chunk.
+    pub index: candid::Nat,
+    /// The SHA-256 of the whole body, to detect a change of the asset while
+    /// it is streamed.
+    pub sha256: Option<ByteBuf>,
+}
 
 #[derive(Clone, Debug, CandidType, Deserialize)]
 pub enum StreamingStrategy {
@@ -0,0 +1,115 @@
+use canister_test::*;
+use dfn_candid::candid_one;
+use dfn_http::assets::ASSETS_LABEL;
+use dfn_http::types::{
+    HttpRequest, HttpResponse, StreamingCallbackHttpResponse, StreamingStrategy,
+};
+use ic_crypto_sha::Sha256;
+use ic_crypto_tree_hash::{lookup_path, LabeledTree, MixedHashTree};
+use ic_types::messages::Certificate;
+use ic_types::CanisterId;
+use serde_bytes::ByteBuf;
+use std::convert::TryFrom;
+
+fn get(url: &str) -> HttpRequest {
+    HttpRequest {
+        method: "GET".to_string(),
+        url: url.to_string(),
+        headers: vec![],
+        body: ByteBuf::new(),
+    }
+}
+
+/// Checks that the `IC-Certificate` header of `response` certifies `body` at
+/// `path`: the witness must reconstruct the certified data of the canister in
+/// the certificate, and hold the hash of `body` under `path`.
+///
+/// The signature of the certificate is not checked, as the test runtime does
+/// not expose the public key of the subnet.
+fn assert_certified(canister_id: CanisterId, response: &HttpResponse, path: &str, body: &[u8]) {
+    let header = response
+        .headers
+        .iter()
+        .find(|(name, _)| name == "IC-Certificate")
+        .map(|(_, value)| value)
+        .expect("The response has no IC-Certificate header");
+    let field = |name: &str| {
+        let start = header.find(&format!("{}=:", name)).unwrap() + name.len() + 2;
+        let end = start + header[start..].find(':').unwrap();
+        base64::decode(&header[start..end]).unwrap()
+    };
+
+    let certificate: Certificate = serde_cbor::from_slice(&field("certificate")).unwrap();
+    let certificate_tree = LabeledTree::try_from(certificate.tree).unwrap();
+    let certified_data = match lookup_path(
+        &certificate_tree,
+        &[b"canister", canister_id.get().as_slice(), b"certified_data"],
+    ) {
+        Some(LabeledTree::Leaf(certified_data)) => certified_data.clone(),
+        other => panic!("No certified data in the certificate: {:?}", other),
+    };
+
+    let witness: MixedHashTree = serde_cbor::from_slice(&field("tree")).unwrap();
+    assert_eq!(witness.digest().0.to_vec(), certified_data);
+    let witness_tree = LabeledTree::try_from(witness).unwrap();
+    assert_eq!(
+        lookup_path(&witness_tree, &[ASSETS_LABEL.as_bytes(), path.as_bytes()]),
+        Some(&LabeledTree::Leaf(Sha256::hash(body).to_vec()))
+    );
+}
+
+#[test]
+fn certified_assets_test() {
+    local_test_e(|r| async move {
+        let proj = Project::new(env!("CARGO_MANIFEST_DIR"));
+
+        let canister = proj.cargo_bin("http_wasm").install_(&r, Vec::new()).await?;
+
+        let response: HttpResponse = canister
+            .query_("http_request", candid_one, get("/large.bin"))
+            .await?;
+        assert_eq!(response.status_code, 200);
+        assert_certified(
+            canister.canister_id(),
+            &response,
+            "/large.bin",
+            &(0..10_000).map(|i| i as u8).collect::<Vec<u8>>(),
+        );
+
+        // The wasm serves chunks of 1024 bytes, so the body comes in 10 chunks.
+        let mut chunks = vec![response.body.into_vec()];
+        let mut token = response.streaming_strategy.map(|strategy| match strategy {
+            StreamingStrategy::Callback { token, .. } => token,
+        });
+        while let Some(next) = token {
+            let response: StreamingCallbackHttpResponse = canister
+                .query_("http_request_streaming_callback", candid_one, next)
+                .await?;
+            chunks.push(response.body.into_vec());
+            token = response.token;
+        }
+        assert_eq!(chunks.len(), 10);
+        assert_eq!(
+            chunks.concat(),
+            (0..10_000).map(|i| i as u8).collect::<Vec<u8>>()
+        );
+
+        // `/` is served `/index.html`, certified under `/`.
+        let index: HttpResponse = canister
+            .query_("http_request", candid_one, get("/index.html"))
+            .await?;
+        let root: HttpResponse = canister
+            .query_("http_request", candid_one, get("/"))
+            .await?;
+        assert_eq!(root.body, index.body);
+        assert_certified(canister.canister_id(), &root, "/", &index.body);
+        assert_certified(canister.canister_id(), &index, "/index.html", &index.body);
+
+        let missing: HttpResponse = canister
+            .query_("http_request", candid_one, get("/missing"))
+            .await?;
+        assert_eq!(missing.status_code, 404);
+
+        Ok(())
+    })
+}
@@ -1,4 +1,44 @@
+use dfn_candid::candid_one;
+use dfn_core::over;
+use dfn_http::assets::CertifiedAssets;
+use dfn_http::types::{HttpRequest, HttpResponse, StreamingCallbackHttpResponse, Token};
 use dfn_http::*;
+use std::cell::RefCell;
+
+thread_local! {
+    // Small chunks, so that streaming can be exercised without large assets.
+    static ASSETS: RefCell<CertifiedAssets> = RefCell::new(CertifiedAssets::with_chunk_size(1024));
+}
+
+#[export_name = "canister_init"]
+fn init() {
+    ASSETS.with(|assets| {
+        let mut assets = assets.borrow_mut();
+        assets.insert("/index.html", "text/html", BODY.as_bytes().to_vec());
+        assets.insert(
+            "/large.bin",
+            "application/octet-stream",
+            (0..10_000).map(|i| i as u8).collect(),
+        );
+    });
+}
+
+#[export_name = "canister_query http_request"]
+fn http_request() {
+    over(candid_one, |request: HttpRequest| -> HttpResponse {
+        ASSETS.with(|assets| assets.borrow().http_request(request))
+    })
+}
+
+#[export_name = "canister_query http_request_streaming_callback"]
+fn http_request_streaming_callback() {
+    over(
+        candid_one,
+        |token: Token| -> StreamingCallbackHttpResponse {
+            ASSETS.with(|assets| assets.borrow().http_request_streaming_callback(token))
+        },
+    )
+}
 
 #[export_name = "canister_query http_query"]
 pub fn main() {
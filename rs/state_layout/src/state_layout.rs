@@ -11,7 +11,8 @@ use ic_protobuf::{
     },
 };
 use ic_replicated_state::{
-    CallContextManager, CanisterStatus, ExportedFunctions, Global, NumWasmPages, NumWasmPages64,
+    CallContextManager, CanisterStatus, CyclesUseCase, ExportedFunctions, Global, NumWasmPages,
+    NumWasmPages64, OutOfCyclesStage,
 };
 use ic_types::{
     nominal_cycles::NominalCycles, AccumulatedPriority, CanisterId, ComputeAllocation, Cycles,
@@ -24,7 +25,10 @@ use std::io::Write;
 use std::marker::PhantomData;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use std::{collections::BTreeSet, sync::Arc};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
 
 pub trait CheckpointManager: Send + Sync {
     /// Returns the base directory path managed by checkpoint manager.
@@ -168,6 +172,7 @@ pub struct CanisterStateBits {
     pub accumulated_priority: AccumulatedPriority,
     pub execution_state_bits: Option<ExecutionStateBits>,
     pub memory_allocation: MemoryAllocation,
+    pub wasm_memory_limit: Option<NumBytes>,
     pub freeze_threshold: NumSeconds,
     pub cycles_balance: Cycles,
     pub status: CanisterStatus,
@@ -177,8 +182,13 @@ pub struct CanisterStateBits {
     pub interruped_during_execution: u64,
     pub certified_data: Vec<u8>,
     pub consumed_cycles_since_replica_started: NominalCycles,
+    pub consumed_cycles_by_use_case: BTreeMap<CyclesUseCase, NominalCycles>,
     pub stable_memory_size: NumWasmPages64,
     pub heap_delta_debit: NumBytes,
+    pub out_of_cycles_stage: Option<OutOfCyclesStage>,
+    pub low_cycles_threshold: Cycles,
+    pub below_low_cycles_threshold: bool,
+    pub low_cycles_notification_pending: bool,
 }
 
 /// `StateLayout` provides convenience functions to construct correct
@@ -995,6 +1005,21 @@ impl From<CanisterStateBits> for pb_canister_state_bits::CanisterStateBits {
             },
             stable_memory_size64: item.stable_memory_size.get(),
             heap_delta_debit: item.heap_delta_debit.get(),
+            out_of_cycles_stage: item.out_of_cycles_stage.as_ref().map(|v| v.into()),
+            low_cycles_threshold: Some(item.low_cycles_threshold.into()),
+            below_low_cycles_threshold: item.below_low_cycles_threshold,
+            low_cycles_notification_pending: item.low_cycles_notification_pending,
+            wasm_memory_limit: item.wasm_memory_limit.map_or(0, |limit| limit.get()),
+            consumed_cycles_by_use_case: item
+                .consumed_cycles_by_use_case
+                .iter()
+                .map(
+                    |(use_case, cycles)| pb_canister_state_bits::ConsumedCyclesByUseCase {
+                        use_case: pb_canister_state_bits::CyclesUseCase::from(*use_case) as i32,
+                        cycles: Some(cycles.into()),
+                    },
+                )
+                .collect(),
         }
     }
 }
@@ -1020,6 +1045,19 @@ impl TryFrom<pb_canister_state_bits::CanisterStateBits> for CanisterStateBits {
             Err(_) => NominalCycles::default(),
         };
 
+        let mut consumed_cycles_by_use_case = BTreeMap::new();
+        for entry in value.consumed_cycles_by_use_case.into_iter() {
+            let use_case = pb_canister_state_bits::CyclesUseCase::from_i32(entry.use_case)
+                .unwrap_or(pb_canister_state_bits::CyclesUseCase::Unspecified);
+            consumed_cycles_by_use_case.insert(
+                CyclesUseCase::try_from(use_case)?,
+                try_from_option_field(
+                    entry.cycles,
+                    "CanisterStateBits::consumed_cycles_by_use_case::cycles",
+                )?,
+            );
+        }
+
         let mut controllers = BTreeSet::new();
         for controller in value.controllers.into_iter() {
             controllers.insert(PrincipalId::try_from(controller)?);
@@ -1054,6 +1092,11 @@ impl TryFrom<pb_canister_state_bits::CanisterStateBits> for CanisterStateBits {
             value.stable_memory_size as u64
         };
 
+        let out_of_cycles_stage = value
+            .out_of_cycles_stage
+            .map(|s| s.try_into())
+            .transpose()?;
+
         Ok(Self {
             controllers,
             last_full_execution_round: value.last_full_execution_round.into(),
@@ -1083,8 +1126,20 @@ impl TryFrom<pb_canister_state_bits::CanisterStateBits> for CanisterStateBits {
             interruped_during_execution: value.interruped_during_execution,
             certified_data: value.certified_data,
             consumed_cycles_since_replica_started,
+            consumed_cycles_by_use_case,
             stable_memory_size: NumWasmPages64::from(stable_memory_size),
             heap_delta_debit: NumBytes::from(value.heap_delta_debit),
+            out_of_cycles_stage,
+            low_cycles_threshold: value
+                .low_cycles_threshold
+                .map(Cycles::from)
+                .unwrap_or_else(|| Cycles::from(0)),
+            below_low_cycles_threshold: value.below_low_cycles_threshold,
+            low_cycles_notification_pending: value.low_cycles_notification_pending,
+            wasm_memory_limit: match value.wasm_memory_limit {
+                0 => None,
+                limit => Some(NumBytes::from(limit)),
+            },
         })
     }
 }
@@ -1132,6 +1187,7 @@ mod test {
 
     use ic_test_utilities::types::ids::canister_test_id;
     use ic_types::ic00::IC_00;
+    use ic_types::Time;
 
     #[test]
     fn test_encode_decode_empty_controllers() {
@@ -1144,6 +1200,7 @@ mod test {
             accumulated_priority: AccumulatedPriority::from(0),
             execution_state_bits: None,
             memory_allocation: MemoryAllocation::default(),
+            wasm_memory_limit: None,
             freeze_threshold: NumSeconds::from(0),
             cycles_balance: Cycles::from(0),
             status: CanisterStatus::Stopped,
@@ -1153,8 +1210,13 @@ mod test {
             interruped_during_execution: 0,
             certified_data: vec![],
             consumed_cycles_since_replica_started: NominalCycles::from(0),
+            consumed_cycles_by_use_case: BTreeMap::new(),
             stable_memory_size: NumWasmPages64::from(0),
             heap_delta_debit: NumBytes::from(0),
+            out_of_cycles_stage: None,
+            low_cycles_threshold: Cycles::from(0),
+            below_low_cycles_threshold: false,
+            low_cycles_notification_pending: false,
         };
 
         let pb_bits = pb_canister_state_bits::CanisterStateBits::from(canister_state_bits);
@@ -1183,6 +1245,7 @@ mod test {
             accumulated_priority: AccumulatedPriority::from(0),
             execution_state_bits: None,
             memory_allocation: MemoryAllocation::default(),
+            wasm_memory_limit: None,
             freeze_threshold: NumSeconds::from(0),
             cycles_balance: Cycles::from(0),
             status: CanisterStatus::Stopped,
@@ -1192,8 +1255,13 @@ mod test {
             interruped_during_execution: 0,
             certified_data: vec![],
             consumed_cycles_since_replica_started: NominalCycles::from(0),
+            consumed_cycles_by_use_case: BTreeMap::new(),
             stable_memory_size: NumWasmPages64::from(0),
             heap_delta_debit: NumBytes::from(0),
+            out_of_cycles_stage: None,
+            low_cycles_threshold: Cycles::from(0),
+            below_low_cycles_threshold: false,
+            low_cycles_notification_pending: false,
         };
 
         let pb_bits = pb_canister_state_bits::CanisterStateBits::from(canister_state_bits);
@@ -1224,6 +1292,7 @@ mod test {
             accumulated_priority: AccumulatedPriority::from(0),
             execution_state_bits: None,
             memory_allocation: MemoryAllocation::default(),
+            wasm_memory_limit: None,
             freeze_threshold: NumSeconds::from(0),
             cycles_balance: Cycles::from(0),
             status: CanisterStatus::Stopped,
@@ -1233,8 +1302,13 @@ mod test {
             interruped_during_execution: 0,
             certified_data: vec![],
             consumed_cycles_since_replica_started: NominalCycles::from(0),
+            consumed_cycles_by_use_case: BTreeMap::new(),
             stable_memory_size: NumWasmPages64::from(0),
             heap_delta_debit: NumBytes::from(0),
+            out_of_cycles_stage: None,
+            low_cycles_threshold: Cycles::from(0),
+            below_low_cycles_threshold: false,
+            low_cycles_notification_pending: false,
         };
 
         let pb_bits = pb_canister_state_bits::CanisterStateBits::from(canister_state_bits);
@@ -1243,4 +1317,137 @@ mod test {
 
         assert_eq!(canister_state_bits.controllers, controllers)
     }
+
+    #[test]
+    fn test_encode_decode_out_of_cycles_stage_and_low_cycles_state() {
+        let canister_state_bits = CanisterStateBits {
+            controllers: BTreeSet::new(),
+            last_full_execution_round: ExecutionRound::from(0),
+            call_context_manager: None,
+            compute_allocation: ComputeAllocation::try_from(0).unwrap(),
+            accumulated_priority: AccumulatedPriority::from(0),
+            execution_state_bits: None,
+            memory_allocation: MemoryAllocation::default(),
+            wasm_memory_limit: None,
+            freeze_threshold: NumSeconds::from(0),
+            cycles_balance: Cycles::from(0),
+            status: CanisterStatus::Stopped,
+            scheduled_as_first: 0,
+            skipped_round_due_to_no_messages: 0,
+            executed: 0,
+            interruped_during_execution: 0,
+            certified_data: vec![],
+            consumed_cycles_since_replica_started: NominalCycles::from(0),
+            consumed_cycles_by_use_case: BTreeMap::new(),
+            stable_memory_size: NumWasmPages64::from(0),
+            heap_delta_debit: NumBytes::from(0),
+            out_of_cycles_stage: Some(OutOfCyclesStage::Frozen {
+                since: Time::from_nanos_since_unix_epoch(42),
+            }),
+            low_cycles_threshold: Cycles::from(1_000_000u64),
+            below_low_cycles_threshold: true,
+            low_cycles_notification_pending: true,
+        };
+
+        let pb_bits = pb_canister_state_bits::CanisterStateBits::from(canister_state_bits);
+        let canister_state_bits = CanisterStateBits::try_from(pb_bits).unwrap();
+
+        assert_eq!(
+            canister_state_bits.out_of_cycles_stage,
+            Some(OutOfCyclesStage::Frozen {
+                since: Time::from_nanos_since_unix_epoch(42),
+            })
+        );
+        assert_eq!(
+            canister_state_bits.low_cycles_threshold,
+            Cycles::from(1_000_000u64)
+        );
+        assert!(canister_state_bits.below_low_cycles_threshold);
+        assert!(canister_state_bits.low_cycles_notification_pending);
+    }
+
+    #[test]
+    fn test_encode_decode_wasm_memory_limit() {
+        let canister_state_bits = CanisterStateBits {
+            controllers: BTreeSet::new(),
+            last_full_execution_round: ExecutionRound::from(0),
+            call_context_manager: None,
+            compute_allocation: ComputeAllocation::try_from(0).unwrap(),
+            accumulated_priority: AccumulatedPriority::from(0),
+            execution_state_bits: None,
+            memory_allocation: MemoryAllocation::default(),
+            wasm_memory_limit: Some(NumBytes::from(3 << 30)),
+            freeze_threshold: NumSeconds::from(0),
+            cycles_balance: Cycles::from(0),
+            status: CanisterStatus::Stopped,
+            scheduled_as_first: 0,
+            skipped_round_due_to_no_messages: 0,
+            executed: 0,
+            interruped_during_execution: 0,
+            certified_data: vec![],
+            consumed_cycles_since_replica_started: NominalCycles::from(0),
+            consumed_cycles_by_use_case: BTreeMap::new(),
+            stable_memory_size: NumWasmPages64::from(0),
+            heap_delta_debit: NumBytes::from(0),
+            out_of_cycles_stage: None,
+            low_cycles_threshold: Cycles::from(0),
+            below_low_cycles_threshold: false,
+            low_cycles_notification_pending: false,
+        };
+
+        let pb_bits = pb_canister_state_bits::CanisterStateBits::from(canister_state_bits);
+        let canister_state_bits = CanisterStateBits::try_from(pb_bits).unwrap();
+
+        assert_eq!(
+            canister_state_bits.wasm_memory_limit,
+            Some(NumBytes::from(3 << 30))
+        );
+    }
+
+    #[test]
+    fn test_encode_decode_consumed_cycles_by_use_case() {
+        let consumed_cycles_by_use_case: BTreeMap<_, _> = vec![
+            (CyclesUseCase::Execution, NominalCycles::from(1_000u128)),
+            (
+                CyclesUseCase::Storage,
+                NominalCycles::from(u64::MAX as u128 + 1),
+            ),
+        ]
+        .into_iter()
+        .collect();
+        let canister_state_bits = CanisterStateBits {
+            controllers: BTreeSet::new(),
+            last_full_execution_round: ExecutionRound::from(0),
+            call_context_manager: None,
+            compute_allocation: ComputeAllocation::try_from(0).unwrap(),
+            accumulated_priority: AccumulatedPriority::from(0),
+            execution_state_bits: None,
+            memory_allocation: MemoryAllocation::default(),
+            wasm_memory_limit: None,
+            freeze_threshold: NumSeconds::from(0),
+            cycles_balance: Cycles::from(0),
+            status: CanisterStatus::Stopped,
+            scheduled_as_first: 0,
+            skipped_round_due_to_no_messages: 0,
+            executed: 0,
+            interruped_during_execution: 0,
+            certified_data: vec![],
+            consumed_cycles_since_replica_started: NominalCycles::from(0),
+            consumed_cycles_by_use_case: consumed_cycles_by_use_case.clone(),
+            stable_memory_size: NumWasmPages64::from(0),
+            heap_delta_debit: NumBytes::from(0),
+            out_of_cycles_stage: None,
+            low_cycles_threshold: Cycles::from(0),
+            below_low_cycles_threshold: false,
+            low_cycles_notification_pending: false,
+        };
+
+        let pb_bits = pb_canister_state_bits::CanisterStateBits::from(canister_state_bits);
+        let canister_state_bits = CanisterStateBits::try_from(pb_bits).unwrap();
+
+        assert_eq!(
+            canister_state_bits.consumed_cycles_by_use_case,
+            consumed_cycles_by_use_case
+        );
+    }
 }
@@ -136,6 +136,7 @@ fn serialize_canister_to_tip(
                 compute_allocation: canister_state.scheduler_state.compute_allocation,
                 accumulated_priority: canister_state.scheduler_state.accumulated_priority,
                 memory_allocation: canister_state.system_state.memory_allocation,
+                wasm_memory_limit: canister_state.system_state.wasm_memory_limit,
                 freeze_threshold: canister_state.system_state.freeze_threshold,
                 cycles_balance: canister_state.system_state.cycles_balance,
                 execution_state_bits,
@@ -158,8 +159,19 @@ fn serialize_canister_to_tip(
                     .system_state
                     .canister_metrics
                     .consumed_cycles_since_replica_started,
+                consumed_cycles_by_use_case: canister_state
+                    .system_state
+                    .canister_metrics
+                    .consumed_cycles_by_use_case
+                    .clone(),
                 stable_memory_size: canister_state.system_state.stable_memory.size,
                 heap_delta_debit: canister_state.scheduler_state.heap_delta_debit,
+                out_of_cycles_stage: canister_state.system_state.out_of_cycles_stage,
+                low_cycles_threshold: canister_state.system_state.low_cycles_threshold,
+                below_low_cycles_threshold: canister_state.system_state.below_low_cycles_threshold,
+                low_cycles_notification_pending: canister_state
+                    .system_state
+                    .low_cycles_notification_pending,
             }
             .into(),
         )
@@ -316,6 +328,7 @@ fn load_canister_state_from_checkpoint<P: ReadPolicy>(
         interruped_during_execution: canister_state_bits.interruped_during_execution,
         consumed_cycles_since_replica_started: canister_state_bits
             .consumed_cycles_since_replica_started,
+        consumed_cycles_by_use_case: canister_state_bits.consumed_cycles_by_use_case,
     };
     let system_state = SystemState::new_from_checkpoint(
         canister_state_bits.controllers,
@@ -323,11 +336,16 @@ fn load_canister_state_from_checkpoint<P: ReadPolicy>(
         queues,
         stable_memory,
         canister_state_bits.memory_allocation,
+        canister_state_bits.wasm_memory_limit,
         canister_state_bits.freeze_threshold,
         canister_state_bits.status,
         canister_state_bits.certified_data,
         canister_metrics,
         canister_state_bits.cycles_balance,
+        canister_state_bits.out_of_cycles_stage,
+        canister_state_bits.low_cycles_threshold,
+        canister_state_bits.below_low_cycles_threshold,
+        canister_state_bits.low_cycles_notification_pending,
     );
 
     Ok(CanisterState {
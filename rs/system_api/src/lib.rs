@@ -733,6 +733,37 @@ impl<A: SystemStateAccessor> SystemApiImpl<A> {
         }
     }
 
+    /// Checks that growing the Wasm heap to `new_size` pages stays within the
+    /// canister's `wasm_memory_limit`.
+    ///
+    /// Installation and upgrade hooks are exempt, so that a canister whose
+    /// heap reached the limit can still be upgraded.
+    fn check_wasm_memory_limit(&self, new_size: NumWasmPages64) -> HypervisorResult<()> {
+        let limit = match self.execution_parameters.wasm_memory_limit {
+            Some(limit) => limit,
+            None => return Ok(()),
+        };
+        match self.api_type {
+            ApiType::Start | ApiType::Init { .. } | ApiType::PreUpgrade { .. } => Ok(()),
+            ApiType::Update { .. }
+            | ApiType::ReplicatedQuery { .. }
+            | ApiType::NonReplicatedQuery { .. }
+            | ApiType::ReplyCallback { .. }
+            | ApiType::RejectCallback { .. }
+            | ApiType::InspectMessage { .. }
+            | ApiType::Heartbeat { .. }
+            | ApiType::Cleanup { .. } => {
+                let size = ic_replicated_state::num_bytes_try_from64(new_size)
+                    .map_err(|_| HypervisorError::OutOfMemory)?;
+                if size > limit {
+                    Err(HypervisorError::WasmMemoryLimitExceeded { size, limit })
+                } else {
+                    Ok(())
+                }
+            }
+        }
+    }
+
     fn ic0_canister_cycles_balance_helper(&self, method_name: &str) -> HypervisorResult<Cycles> {
         match &self.api_type {
             ApiType::Start {} => Err(self.error_for(method_name)),
@@ -1436,6 +1467,7 @@ impl<A: SystemStateAccessor> SystemApi for SystemApiImpl<A> {
                     method_payload: payload,
                     sender_reply_callback: callback_id,
                     payment: Cycles::zero(),
+                    deadline: None,
                 };
                 self.push_output_request(msg)
             }
@@ -1579,6 +1611,55 @@ impl<A: SystemStateAccessor> SystemApi for SystemApiImpl<A> {
         }
     }
 
+    fn ic0_call_with_best_effort_response(&mut self, timeout_seconds: u32) -> HypervisorResult<()> {
+        match &mut self.api_type {
+            ApiType::Start { .. }
+            | ApiType::Init { .. }
+            | ApiType::ReplicatedQuery { .. }
+            | ApiType::NonReplicatedQuery {
+                query_kind: NonReplicatedQueryKind::Pure,
+                ..
+            }
+            | ApiType::Cleanup { .. }
+            | ApiType::PreUpgrade { .. }
+            | ApiType::InspectMessage { .. } => {
+                Err(self.error_for("ic0_call_with_best_effort_response"))
+            }
+            ApiType::Update {
+                time,
+                outgoing_request,
+                ..
+            }
+            | ApiType::NonReplicatedQuery {
+                time,
+                outgoing_request,
+                query_kind: NonReplicatedQueryKind::Stateful,
+                ..
+            }
+            | ApiType::Heartbeat {
+                time,
+                outgoing_request,
+                ..
+            }
+            | ApiType::ReplyCallback {
+                time,
+                outgoing_request,
+                ..
+            }
+            | ApiType::RejectCallback {
+                time,
+                outgoing_request,
+                ..
+            } => match outgoing_request {
+                None => Err(HypervisorError::ContractViolation(
+                    "ic0.call_with_best_effort_response called when no call is under construction."
+                        .to_string(),
+                )),
+                Some(request) => request.set_timeout(timeout_seconds, *time),
+            },
+        }
+    }
+
     fn ic0_call_cycles_add(&mut self, amount: u64) -> HypervisorResult<()> {
         self.ic0_call_cycles_add_helper("ic0_call_cycles_add", Cycles::from(amount))
     }
@@ -1922,6 +2003,10 @@ impl<A: SystemStateAccessor> SystemApi for SystemApiImpl<A> {
         if native_memory_grow_res == -1 {
             return Ok(-1);
         }
+        // `memory.grow` returns the previous size of the heap in pages.
+        self.check_wasm_memory_limit(NumWasmPages64::from(
+            native_memory_grow_res as u64 + additional_pages as u64,
+        ))?;
         match self.memory_usage.allocate_pages(additional_pages as u64) {
             Ok(()) => Ok(native_memory_grow_res),
             Err(_err) => Err(HypervisorError::OutOfMemory),
@@ -7,10 +7,16 @@ use ic_registry_subnet_type::SubnetType;
 use ic_types::{
     messages::{CallContextId, Request},
     methods::{Callback, WasmClosure},
-    CanisterId, Cycles, NumBytes, PrincipalId, SubnetId,
+    CanisterId, Cycles, NumBytes, PrincipalId, SubnetId, Time,
 };
 use serde::{Deserialize, Serialize};
-use std::{collections::BTreeMap, convert::TryFrom, sync::Arc};
+use std::{collections::BTreeMap, convert::TryFrom, sync::Arc, time::Duration};
+
+/// The maximum timeout that can be set on a best-effort call. Longer timeouts
+/// passed to `ic0.call_with_best_effort_response` are capped at this value
+/// rather than rejected, so that the deadline of a call is at most 5 minutes
+/// after the time the call was made.
+pub const MAX_CALL_TIMEOUT_SECONDS: u32 = 300;
 
 /// Represents an under construction `Request`.
 ///
@@ -40,6 +46,9 @@ pub struct RequestInPrep {
     cycles: Cycles,
     method_name: String,
     method_payload: Vec<u8>,
+    /// The deadline of a best-effort call; `None` for calls with a guaranteed
+    /// response.
+    deadline: Option<Time>,
     /// The maximum size of a message that will go to a canister on another
     /// subnet.
     max_size_inter_subnet: NumBytes,
@@ -97,6 +106,7 @@ impl RequestInPrep {
             cycles: Cycles::from(0),
             method_name,
             method_payload: Vec::new(),
+            deadline: None,
             max_size_inter_subnet,
             multiplier_max_size_intra_subnet,
         })
@@ -114,6 +124,20 @@ impl RequestInPrep {
         }
     }
 
+    /// Sets the deadline of the call to `timeout_seconds` (capped at
+    /// `MAX_CALL_TIMEOUT_SECONDS`) after `time`.
+    pub(crate) fn set_timeout(&mut self, timeout_seconds: u32, time: Time) -> HypervisorResult<()> {
+        if self.deadline.is_some() {
+            return Err(HypervisorError::ContractViolation(
+                "ic0.call_with_best_effort_response can be called at most once between `ic0.call_new` and `ic0.call_perform`"
+                    .to_string(),
+            ));
+        }
+        let timeout = Duration::from_secs(timeout_seconds.min(MAX_CALL_TIMEOUT_SECONDS) as u64);
+        self.deadline = Some(time + timeout);
+        Ok(())
+    }
+
     pub(crate) fn take_cycles(self) -> Cycles {
         self.cycles
     }
@@ -158,6 +182,7 @@ pub(crate) fn into_request(
         cycles,
         method_name,
         method_payload,
+        deadline,
         max_size_inter_subnet,
         multiplier_max_size_intra_subnet,
     }: RequestInPrep,
@@ -243,13 +268,19 @@ pub(crate) fn into_request(
         )));
     }
 
-    let callback_id = system_state_accessor.register_callback(Callback::new(
-        call_context_id,
-        cycles,
-        on_reply,
-        on_reject,
-        on_cleanup,
-    ));
+    // Calls to the management canister always have a guaranteed response, as
+    // some of them (e.g. `stop_canister`) are only responded to much later.
+    let deadline = if callee == IC_00.get() {
+        None
+    } else {
+        deadline
+    };
+
+    let mut callback = Callback::new(call_context_id, cycles, on_reply, on_reject, on_cleanup);
+    if let Some(deadline) = deadline {
+        callback = callback.with_deadline(destination_canister, deadline);
+    }
+    let callback_id = system_state_accessor.register_callback(callback);
 
     Ok(Request {
         sender,
@@ -258,6 +289,7 @@ pub(crate) fn into_request(
         method_payload,
         sender_reply_callback: callback_id,
         payment: cycles,
+        deadline,
     })
 }
 
@@ -241,3 +241,33 @@ fn application_subnet_cannot_send_cycles_to_verified_subnet() {
     )
     .unwrap_err();
 }
+
+#[test]
+fn call_timeout_is_capped_at_max_call_timeout() {
+    let callback = WasmClosure::new(0, 0);
+    let mut req_in_prep = RequestInPrep::new(
+        CanisterId::from(1),
+        0,
+        1,
+        0,
+        1,
+        &[0; 1024],
+        callback.clone(),
+        callback,
+        NumBytes::from(10),
+        1,
+    )
+    .unwrap();
+    let time = Time::from_nanos_since_unix_epoch(1_000_000_000);
+
+    req_in_prep
+        .set_timeout(MAX_CALL_TIMEOUT_SECONDS + 1, time)
+        .unwrap();
+    assert_eq!(
+        Some(time + Duration::from_secs(MAX_CALL_TIMEOUT_SECONDS as u64)),
+        req_in_prep.deadline
+    );
+
+    // The timeout can only be set once.
+    req_in_prep.set_timeout(1, time).unwrap_err();
+}
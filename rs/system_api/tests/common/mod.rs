@@ -24,6 +24,7 @@ pub fn execution_parameters() -> ExecutionParameters {
         canister_memory_limit: NumBytes::new(4 << 30),
         subnet_available_memory: SubnetAvailableMemory::new(i64::MAX / 2),
         compute_allocation: ComputeAllocation::default(),
+        wasm_memory_limit: None,
     }
 }
 
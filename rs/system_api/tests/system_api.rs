@@ -1634,6 +1634,46 @@ fn update_available_memory_updates_subnet_available_memory() {
     assert_eq!(subnet_available_memory.get(), wasm_page_size);
 }
 
+#[test]
+fn update_available_memory_respects_wasm_memory_limit() {
+    let wasm_page_size = 64 << 10;
+    let execution_parameters = ExecutionParameters {
+        wasm_memory_limit: Some(NumBytes::from(2 * wasm_page_size)),
+        ..execution_parameters()
+    };
+    let new_api = |api_type: ApiType| {
+        let system_state = SystemStateBuilder::default().build();
+        let cycles_account_manager = CyclesAccountManagerBuilder::new().build();
+        let system_state_accessor =
+            SystemStateAccessorDirect::new(system_state, Arc::new(cycles_account_manager));
+        SystemApiImpl::new(
+            system_state_accessor.canister_id(),
+            api_type,
+            system_state_accessor,
+            CANISTER_CURRENT_MEMORY_USAGE,
+            execution_parameters.clone(),
+            no_op_logger(),
+        )
+    };
+
+    // Update calls may grow the heap up to the limit, but not past it.
+    let mut api = new_api(get_update_api_type());
+    assert_eq!(api.update_available_memory(0, 2).unwrap(), 0);
+    assert_eq!(
+        api.update_available_memory(2, 1).unwrap_err(),
+        HypervisorError::WasmMemoryLimitExceeded {
+            size: NumBytes::from(3 * wasm_page_size),
+            limit: NumBytes::from(2 * wasm_page_size),
+        }
+    );
+
+    // Upgrade hooks may use the headroom above the limit.
+    let mut api = new_api(ApiType::pre_upgrade(mock_time(), user_test_id(1).get()));
+    assert_eq!(api.update_available_memory(2, 1).unwrap(), 2);
+    let mut api = new_api(ApiType::init(mock_time(), vec![], user_test_id(1).get()));
+    assert_eq!(api.update_available_memory(2, 1).unwrap(), 2);
+}
+
 #[test]
 fn push_output_request_respects_memory_limits() {
     let subnet_available_memory_bytes = MAX_RESPONSE_COUNT_BYTES as i64 + 13;
@@ -3,6 +3,7 @@ use ic_config::subnet_config::{CyclesAccountManagerConfig, SubnetConfigs};
 use ic_cycles_account_manager::CyclesAccountManager;
 use ic_registry_subnet_type::SubnetType;
 use ic_types::{Cycles, NumInstructions, SubnetId};
+use std::time::Duration;
 
 pub struct CyclesAccountManagerBuilder {
     subnet_id: SubnetId,
@@ -51,6 +52,11 @@ impl CyclesAccountManagerBuilder {
         self
     }
 
+    pub fn with_out_of_cycles_grace_period(mut self, grace_period: Duration) -> Self {
+        self.config.out_of_cycles_grace_period = grace_period;
+        self
+    }
+
     pub fn with_cycles_limit_per_canister(
         mut self,
         cycles_limit_per_canister: Option<Cycles>,
@@ -657,6 +657,7 @@ prop_compose! {
     pub fn arb_stream(min_size: usize, max_size: usize)(
         msg_start in 0..10000u64,
         sig_end in 0..10000u64,
+        reject_signals in prop::collection::btree_set(0..10000u64, 0..5),
         reqs in prop::collection::vec(arbitrary::request(), min_size..=max_size),
     ) -> Stream {
         let mut messages = StreamIndexedQueue::with_begin(StreamIndex::from(msg_start));
@@ -665,8 +666,13 @@ prop_compose! {
         }
 
         let signals_end = StreamIndex::from(sig_end);
+        let reject_signals = reject_signals
+            .into_iter()
+            .filter(|index| *index < sig_end)
+            .map(StreamIndex::from)
+            .collect();
 
-        Stream::new(messages, signals_end)
+        Stream::with_signals(messages, signals_end, reject_signals)
     }
 }
 
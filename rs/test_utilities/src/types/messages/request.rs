@@ -1,7 +1,7 @@
 use crate::types::ids::canister_test_id;
 use ic_types::{
     messages::{CallbackId, Request},
-    CanisterId, Cycles,
+    CanisterId, Cycles, Time,
 };
 
 pub struct RequestBuilder {
@@ -20,6 +20,7 @@ impl Default for RequestBuilder {
                 payment: Cycles::zero(),
                 method_name: name.to_string(),
                 method_payload: Vec::new(),
+                deadline: None,
             },
         }
     }
@@ -67,6 +68,12 @@ impl RequestBuilder {
         self
     }
 
+    /// Sets the deadline attribute.
+    pub fn deadline(mut self, deadline: Option<Time>) -> Self {
+        self.request.deadline = deadline;
+        self
+    }
+
     pub fn build(self) -> Request {
         self.request
     }
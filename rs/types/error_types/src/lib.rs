@@ -19,6 +19,9 @@ pub enum RejectCode {
     DestinationInvalid = 3,
     CanisterReject = 4,
     CanisterError = 5,
+    /// The outcome of a best-effort call is unknown, e.g. because its
+    /// deadline expired before a response was received.
+    SysUnknown = 6,
 }
 
 impl ToString for RejectCode {
@@ -35,6 +38,7 @@ impl RejectCode {
             RejectCode::DestinationInvalid => "DESTINATION_INVALID",
             RejectCode::CanisterReject => "CANISTER_REJECT",
             RejectCode::CanisterError => "CANISTER_ERROR",
+            RejectCode::SysUnknown => "SYS_UNKNOWN",
         }
     }
 }
@@ -48,6 +52,7 @@ impl TryFrom<u64> for RejectCode {
             3 => Ok(RejectCode::DestinationInvalid),
             4 => Ok(RejectCode::CanisterReject),
             5 => Ok(RejectCode::CanisterError),
+            6 => Ok(RejectCode::SysUnknown),
             _ => Err(ProxyDecodeError::ValueOutOfRange {
                 typ: "RejectCode",
                 err: code.to_string(),
@@ -84,6 +84,9 @@ impl From<CanisterId> for CanisterIdRecord {
 ///     controller : principal;
 ///     compute_allocation: nat;
 ///     memory_allocation: opt nat;
+///     freezing_threshold: nat;
+///     low_cycles_threshold: nat;
+///     wasm_memory_limit: nat;
 /// })`
 #[derive(CandidType, Deserialize, Debug, Eq, PartialEq)]
 pub struct DefiniteCanisterSettingsArgs {
@@ -92,6 +95,8 @@ pub struct DefiniteCanisterSettingsArgs {
     compute_allocation: candid::Nat,
     memory_allocation: candid::Nat,
     freezing_threshold: candid::Nat,
+    low_cycles_threshold: candid::Nat,
+    wasm_memory_limit: candid::Nat,
 }
 
 impl DefiniteCanisterSettingsArgs {
@@ -101,6 +106,8 @@ impl DefiniteCanisterSettingsArgs {
         compute_allocation: u64,
         memory_allocation: Option<u64>,
         freezing_threshold: u64,
+        low_cycles_threshold: u128,
+        wasm_memory_limit: Option<u64>,
     ) -> Self {
         let memory_allocation = match memory_allocation {
             None => candid::Nat::from(0),
@@ -112,12 +119,26 @@ impl DefiniteCanisterSettingsArgs {
             compute_allocation: candid::Nat::from(compute_allocation),
             memory_allocation,
             freezing_threshold: candid::Nat::from(freezing_threshold),
+            low_cycles_threshold: candid::Nat::from(low_cycles_threshold),
+            wasm_memory_limit: candid::Nat::from(wasm_memory_limit.unwrap_or(0)),
         }
     }
 
     pub fn controllers(&self) -> Vec<PrincipalId> {
         self.controllers.clone()
     }
+
+    pub fn low_cycles_threshold(&self) -> u128 {
+        self.low_cycles_threshold.0.to_u128().unwrap()
+    }
+
+    /// Returns `None` if the canister has no Wasm memory limit.
+    pub fn wasm_memory_limit(&self) -> Option<u64> {
+        match self.wasm_memory_limit.0.to_u64().unwrap() {
+            0 => None,
+            limit => Some(limit),
+        }
+    }
 }
 
 impl Payload<'_> for DefiniteCanisterSettingsArgs {}
@@ -178,6 +199,145 @@ impl CanisterStatusResult {
 
 impl Payload<'_> for CanisterStatusResult {}
 
+/// How far a canister that failed to pay for its resource allocation and
+/// usage has progressed towards having its Wasm state deleted.
+///
+/// Struct used for encoding/decoding
+/// `variant {
+///     active;
+///     frozen: record { since: nat64; uninstall_at: nat64 };
+///     uninstalled;
+/// }`
+#[derive(CandidType, Clone, Debug, Deserialize, Eq, PartialEq)]
+pub enum CanisterCyclesStage {
+    /// The canister is able to pay for its resources.
+    #[serde(rename = "active")]
+    Active,
+    /// Calls to the canister are rejected, and its Wasm state is deleted at
+    /// `uninstall_at` unless it is topped up before. Both times are in
+    /// nanoseconds since the Unix epoch.
+    #[serde(rename = "frozen")]
+    Frozen { since: u64, uninstall_at: u64 },
+    /// The Wasm state of the canister was deleted because it did not get
+    /// topped up in time.
+    #[serde(rename = "uninstalled")]
+    Uninstalled,
+}
+
+/// Breakdown of the memory used by a canister, in bytes.
+///
+/// Struct used for encoding/decoding
+/// `record {
+///     wasm_memory_size: nat;
+///     stable_memory_size: nat;
+///     globals_size: nat;
+///     wasm_module_size: nat;
+///     message_memory_size: nat;
+/// }`
+#[derive(CandidType, Clone, Debug, Deserialize, Eq, PartialEq)]
+pub struct MemoryMetrics {
+    wasm_memory_size: candid::Nat,
+    stable_memory_size: candid::Nat,
+    globals_size: candid::Nat,
+    wasm_module_size: candid::Nat,
+    message_memory_size: candid::Nat,
+}
+
+impl MemoryMetrics {
+    pub fn new(
+        wasm_memory_size: NumBytes,
+        stable_memory_size: NumBytes,
+        globals_size: NumBytes,
+        wasm_module_size: NumBytes,
+        message_memory_size: NumBytes,
+    ) -> Self {
+        Self {
+            wasm_memory_size: candid::Nat::from(wasm_memory_size.get()),
+            stable_memory_size: candid::Nat::from(stable_memory_size.get()),
+            globals_size: candid::Nat::from(globals_size.get()),
+            wasm_module_size: candid::Nat::from(wasm_module_size.get()),
+            message_memory_size: candid::Nat::from(message_memory_size.get()),
+        }
+    }
+
+    pub fn wasm_memory_size(&self) -> NumBytes {
+        NumBytes::from(self.wasm_memory_size.0.to_u64().unwrap())
+    }
+
+    pub fn stable_memory_size(&self) -> NumBytes {
+        NumBytes::from(self.stable_memory_size.0.to_u64().unwrap())
+    }
+
+    pub fn globals_size(&self) -> NumBytes {
+        NumBytes::from(self.globals_size.0.to_u64().unwrap())
+    }
+
+    pub fn wasm_module_size(&self) -> NumBytes {
+        NumBytes::from(self.wasm_module_size.0.to_u64().unwrap())
+    }
+
+    pub fn message_memory_size(&self) -> NumBytes {
+        NumBytes::from(self.message_memory_size.0.to_u64().unwrap())
+    }
+}
+
+/// Cycles a canister consumed since it was created, by use case.
+///
+/// Struct used for encoding/decoding
+/// `record {
+///     execution: nat;
+///     ingress_induction: nat;
+///     xnet: nat;
+///     storage: nat;
+///     compute_allocation: nat;
+/// }`
+#[derive(CandidType, Clone, Debug, Deserialize, Eq, PartialEq)]
+pub struct CyclesSpentMetrics {
+    execution: candid::Nat,
+    ingress_induction: candid::Nat,
+    xnet: candid::Nat,
+    storage: candid::Nat,
+    compute_allocation: candid::Nat,
+}
+
+impl CyclesSpentMetrics {
+    pub fn new(
+        execution: u128,
+        ingress_induction: u128,
+        xnet: u128,
+        storage: u128,
+        compute_allocation: u128,
+    ) -> Self {
+        Self {
+            execution: candid::Nat::from(execution),
+            ingress_induction: candid::Nat::from(ingress_induction),
+            xnet: candid::Nat::from(xnet),
+            storage: candid::Nat::from(storage),
+            compute_allocation: candid::Nat::from(compute_allocation),
+        }
+    }
+
+    pub fn execution(&self) -> u128 {
+        self.execution.0.to_u128().unwrap()
+    }
+
+    pub fn ingress_induction(&self) -> u128 {
+        self.ingress_induction.0.to_u128().unwrap()
+    }
+
+    pub fn xnet(&self) -> u128 {
+        self.xnet.0.to_u128().unwrap()
+    }
+
+    pub fn storage(&self) -> u128 {
+        self.storage.0.to_u128().unwrap()
+    }
+
+    pub fn compute_allocation(&self) -> u128 {
+        self.compute_allocation.0.to_u128().unwrap()
+    }
+}
+
 /// Struct used for encoding/decoding
 /// `(record {
 ///     status : variant { running; stopping; stopped };
@@ -185,7 +345,11 @@ impl Payload<'_> for CanisterStatusResult {}
 ///     module_hash: opt blob;
 ///     controller: principal;
 ///     memory_size: nat;
+///     memory_metrics: memory_metrics;
 ///     cycles: nat;
+///     cycles_stage: variant { active; frozen: record {...}; uninstalled };
+///     idle_cycles_burned_per_day: nat;
+///     cycles_spent: cycles_spent_metrics;
 /// })`
 #[derive(CandidType, Debug, Deserialize, Eq, PartialEq)]
 pub struct CanisterStatusResultV2 {
@@ -198,6 +362,10 @@ pub struct CanisterStatusResultV2 {
     // this is for compat with Spec 0.12/0.13
     balance: Vec<(Vec<u8>, candid::Nat)>,
     freezing_threshold: candid::Nat,
+    cycles_stage: CanisterCyclesStage,
+    memory_metrics: MemoryMetrics,
+    idle_cycles_burned_per_day: candid::Nat,
+    cycles_spent: CyclesSpentMetrics,
 }
 
 impl CanisterStatusResultV2 {
@@ -212,6 +380,12 @@ impl CanisterStatusResultV2 {
         compute_allocation: u64,
         memory_allocation: Option<u64>,
         freezing_threshold: u64,
+        low_cycles_threshold: u128,
+        wasm_memory_limit: Option<u64>,
+        cycles_stage: CanisterCyclesStage,
+        memory_metrics: MemoryMetrics,
+        idle_cycles_burned_per_day: u128,
+        cycles_spent: CyclesSpentMetrics,
     ) -> Self {
         Self {
             status,
@@ -228,8 +402,14 @@ impl CanisterStatusResultV2 {
                 compute_allocation,
                 memory_allocation,
                 freezing_threshold,
+                low_cycles_threshold,
+                wasm_memory_limit,
             ),
             freezing_threshold: candid::Nat::from(freezing_threshold),
+            cycles_stage,
+            memory_metrics,
+            idle_cycles_burned_per_day: candid::Nat::from(idle_cycles_burned_per_day),
+            cycles_spent,
         }
     }
 
@@ -256,6 +436,26 @@ impl CanisterStatusResultV2 {
     pub fn freezing_threshold(&self) -> u64 {
         self.freezing_threshold.0.to_u64().unwrap()
     }
+
+    pub fn settings(&self) -> &DefiniteCanisterSettingsArgs {
+        &self.settings
+    }
+
+    pub fn cycles_stage(&self) -> CanisterCyclesStage {
+        self.cycles_stage.clone()
+    }
+
+    pub fn memory_metrics(&self) -> &MemoryMetrics {
+        &self.memory_metrics
+    }
+
+    pub fn idle_cycles_burned_per_day(&self) -> u128 {
+        self.idle_cycles_burned_per_day.0.to_u128().unwrap()
+    }
+
+    pub fn cycles_spent(&self) -> &CyclesSpentMetrics {
+        &self.cycles_spent
+    }
 }
 
 impl Payload<'_> for CanisterStatusResultV2 {}
@@ -387,6 +587,9 @@ impl Payload<'_> for UpdateSettingsArgs {}
 ///     controllers: opt vec principal;
 ///     compute_allocation: opt nat;
 ///     memory_allocation: opt nat;
+///     freezing_threshold: opt nat;
+///     low_cycles_threshold: opt nat;
+///     wasm_memory_limit: opt nat;
 /// })`
 #[derive(Default, Clone, CandidType, Deserialize, Debug)]
 pub struct CanisterSettingsArgs {
@@ -395,6 +598,10 @@ pub struct CanisterSettingsArgs {
     pub compute_allocation: Option<candid::Nat>,
     pub memory_allocation: Option<candid::Nat>,
     pub freezing_threshold: Option<candid::Nat>,
+    pub low_cycles_threshold: Option<candid::Nat>,
+    /// Upper limit on the Wasm heap, in bytes, outside of installation and
+    /// upgrade hooks. Zero removes the limit.
+    pub wasm_memory_limit: Option<candid::Nat>,
 }
 
 impl Payload<'_> for CanisterSettingsArgs {}
@@ -292,6 +292,9 @@ pub enum CryptoError {
     },
     /// Root subnet public key not found at given registry version.
     RootSubnetPublicKeyNotFound { registry_version: RegistryVersion },
+    /// An internal error that may go away if the operation is retried, e.g.,
+    /// because a remote CSP vault could not be reached.
+    TransientInternalError { internal_error: String },
 }
 
 impl From<ThresholdSigPublicKeyBytesConversionError> for CryptoError {
@@ -318,6 +321,10 @@ impl CryptoError {
         matches!(self, CryptoError::SecretKeyNotFound { .. })
     }
 
+    pub fn is_transient_internal_error(&self) -> bool {
+        matches!(self, CryptoError::TransientInternalError { .. })
+    }
+
     pub fn is_malformed_secret_key(&self) -> bool {
         matches!(self, CryptoError::MalformedSecretKey { .. })
     }
@@ -523,7 +530,10 @@ impl fmt::Debug for CryptoError {
                 f,
                 "Cannot find root subnet public key at registry version {:?}",
                 registry_version
-            )
+            ),
+            CryptoError::TransientInternalError { internal_error } => {
+                write!(f, "Transient internal error: {}", internal_error)
+            }
         }
     }
 }
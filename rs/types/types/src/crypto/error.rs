@@ -2,10 +2,11 @@
 pub mod conversions;
 pub use super::CryptoError;
 use crate::crypto::{AlgorithmId, KeyId};
+use serde::{Deserialize, Serialize};
 use std::fmt; // Probably move all the errors into this file
 
 /// Occurs if an argument is invalid.
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct InvalidArgumentError {
     pub message: String,
 }
@@ -17,7 +18,7 @@ impl fmt::Display for InvalidArgumentError {
 }
 
 /// Occurs if a public key is malformed.
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct MalformedPublicKeyError {
     pub algorithm: AlgorithmId,
     pub key_bytes: Option<Vec<u8>>,
@@ -37,7 +38,7 @@ impl fmt::Display for MalformedPublicKeyError {
 }
 
 /// Malformed X for other types of X.
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct MalformedDataError {
     pub algorithm: AlgorithmId,
     pub internal_error: String,
@@ -57,7 +58,7 @@ impl fmt::Display for MalformedDataError {
 }
 
 /// The secret key was not found in the secret key store.
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct KeyNotFoundError {
     pub internal_error: String,
     pub key_id: KeyId,
@@ -13,6 +13,10 @@ pub enum ThresholdSignError {
         algorithm: AlgorithmId,
         key_id: KeyId,
     },
+    /// The CSP vault could not be reached; retrying may succeed.
+    TransientInternalError {
+        internal_error: String,
+    },
 }
 
 impl fmt::Display for ThresholdSignError {
@@ -30,6 +34,11 @@ impl fmt::Display for ThresholdSignError {
                 Reloading the transcript does not help since the transcript has been loaded already.",
                 prefix, algorithm, dkg_id, key_id
             ),
+            ThresholdSignError::TransientInternalError { internal_error } => write!(
+                f,
+                "{}Transient internal error: {}", prefix,
+                internal_error
+            ),
         }
     }
 }
@@ -49,6 +58,9 @@ impl From<ThresholdSignError> for CryptoError {
                 // ThresholdSigDataNotFound must not be used here, see CRP-586.
                 CryptoError::SecretKeyNotFound { algorithm, key_id }
             }
+            ThresholdSignError::TransientInternalError { internal_error } => {
+                CryptoError::TransientInternalError { internal_error }
+            }
         }
     }
 }
@@ -12,6 +12,8 @@ pub enum DkgCreateDealingError {
     Registry(RegistryClientError),
     MalformedFsEncryptionPublicKey(MalformedFsEncryptionPublicKeyError),
     ThresholdSigningKeyNotInSecretKeyStore(KeyNotFoundError),
+    /// The CSP vault could not be reached; retrying may succeed.
+    TransientInternalError(String),
     // Reminder: document error definition changes on `NiDkgAlgorithm::create_dealing`.
 }
 
@@ -30,6 +32,9 @@ impl fmt::Display for DkgCreateDealingError {
             DkgCreateDealingError::ThresholdSigningKeyNotInSecretKeyStore(error) => {
                 write!(f, "{}{}. `NiDkgAlgorithm::load_transcript` must be called prior to calling this method", prefix, error)
             }
+            DkgCreateDealingError::TransientInternalError(error) => {
+                write!(f, "{}Transient internal error: {}", prefix, error)
+            }
         }
     }
 }
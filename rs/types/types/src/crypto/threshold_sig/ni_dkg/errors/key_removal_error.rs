@@ -16,6 +16,8 @@ pub enum DkgKeyRemovalError {
     MalformedFsEncryptionPublicKey(MalformedFsEncryptionPublicKeyError),
     Registry(RegistryClientError),
     FsKeyNotInSecretKeyStoreError(KeyNotFoundError),
+    /// The CSP vault could not be reached; retrying may succeed.
+    TransientInternalError(String),
 }
 
 impl fmt::Display for DkgKeyRemovalError {
@@ -14,6 +14,8 @@ pub enum DkgLoadTranscriptError {
     Registry(RegistryClientError),
     InvalidTranscript(InvalidArgumentError),
     MalformedFsEncryptionPublicKey(MalformedFsEncryptionPublicKeyError),
+    /// The CSP vault could not be reached; retrying may succeed.
+    TransientInternalError(String),
     // Reminder: document error definition changes on `NiDkgAlgorithm::load_transcript`.
 }
 
@@ -29,6 +31,9 @@ impl fmt::Display for DkgLoadTranscriptError {
                 write!(f, "{}{}", prefix, error)
             }
             DkgLoadTranscriptError::InvalidTranscript(error) => write!(f, "{}{}", prefix, error),
+            DkgLoadTranscriptError::TransientInternalError(error) => {
+                write!(f, "{}Transient internal error: {}", prefix, error)
+            }
         }
     }
 }
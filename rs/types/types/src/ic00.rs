@@ -1,7 +1,8 @@
 //! Data types used for encoding/decoding the Candid payloads of ic:00.
 pub use ic_ic00_types::{
-    CanisterIdRecord, CanisterSettingsArgs, CanisterStatusResult, CanisterStatusResultV2,
-    CreateCanisterArgs, EmptyBlob, InstallCodeArgs, Method, Payload,
-    ProvisionalCreateCanisterWithCyclesArgs, ProvisionalTopUpCanisterArgs, SetControllerArgs,
-    SetupInitialDKGArgs, SetupInitialDKGResponse, UpdateSettingsArgs, IC_00,
+    CanisterCyclesStage, CanisterIdRecord, CanisterSettingsArgs, CanisterStatusResult,
+    CanisterStatusResultV2, CreateCanisterArgs, CyclesSpentMetrics, EmptyBlob, InstallCodeArgs,
+    MemoryMetrics, Method, Payload, ProvisionalCreateCanisterWithCyclesArgs,
+    ProvisionalTopUpCanisterArgs, SetControllerArgs, SetupInitialDKGArgs, SetupInitialDKGResponse,
+    UpdateSettingsArgs, IC_00,
 };
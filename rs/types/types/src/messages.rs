@@ -12,8 +12,9 @@ pub use self::http::{
     Authentication, Certificate, CertificateDelegation, Delegation, HasCanisterId,
     HttpCanisterUpdate, HttpQueryResponse, HttpQueryResponseReply, HttpReadContent, HttpReadState,
     HttpReadStateResponse, HttpReply, HttpRequest, HttpRequestContent, HttpRequestEnvelope,
-    HttpResponseStatus, HttpStatusResponse, HttpSubmitContent, HttpUserQuery, RawHttpRequestVal,
-    ReplicaHealthStatus, SignedDelegation,
+    HttpResponseStatus, HttpSignedQueryResponse, HttpStatusResponse, HttpSubmitContent,
+    HttpUserQuery, NodeSignature, QueryResponseContent, RawHttpRequestVal, ReplicaHealthStatus,
+    SignedDelegation,
 };
 use crate::{user_id_into_protobuf, user_id_try_from_protobuf, Cycles, Funds, NumBytes, UserId};
 pub use blob::Blob;
@@ -14,7 +14,10 @@ use maplit::btreemap;
 #[cfg(test)]
 use proptest_derive::Arbitrary;
 use serde::{Deserialize, Serialize};
-use std::{collections::BTreeSet, convert::TryFrom};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::TryFrom,
+};
 
 /// Describes the fields of a canister update call as defined in
 /// https://sdk.dfinity.org/docs/interface-spec/index.html#api-update.
@@ -484,6 +487,7 @@ pub enum RawHttpRequestVal {
     String(String),
     U64(u64),
     Array(Vec<RawHttpRequestVal>),
+    Map(BTreeMap<String, RawHttpRequestVal>),
 }
 
 /// The status of an update call.
@@ -532,6 +536,94 @@ pub struct HttpQueryResponseReply {
     pub arg: Blob,
 }
 
+/// The signature of a replica on a query response.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct NodeSignature {
+    /// The time at which the response was signed. Represented as nanoseconds
+    /// since UNIX epoch.
+    pub timestamp: u64,
+    /// The basic signature of the node on the corresponding
+    /// `QueryResponseContent`.
+    pub signature: Blob,
+    /// The ID of the node that created the signature.
+    pub identity: Blob,
+}
+
+/// The response to `/api/v2/canister/_/query`, together with the signatures of
+/// the replicas that executed the query.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HttpSignedQueryResponse {
+    #[serde(flatten)]
+    pub response: HttpQueryResponse,
+    /// Omitted by replicas that do not sign query responses.
+    #[serde(default)]
+    pub signatures: Vec<NodeSignature>,
+}
+
+/// The content of a query response that is signed by the replica, i.e. the
+/// response itself, the ID of the query it answers and the time at which it
+/// was signed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryResponseContent {
+    response: HttpQueryResponse,
+    request_id: MessageId,
+    timestamp: Time,
+}
+
+impl QueryResponseContent {
+    pub fn new(response: HttpQueryResponse, request_id: MessageId, timestamp: Time) -> Self {
+        Self {
+            response,
+            request_id,
+            timestamp,
+        }
+    }
+
+    pub fn response(&self) -> &HttpQueryResponse {
+        &self.response
+    }
+
+    pub fn request_id(&self) -> &MessageId {
+        &self.request_id
+    }
+
+    pub fn timestamp(&self) -> Time {
+        self.timestamp
+    }
+}
+
+impl SignedBytesWithoutDomainSeparator for QueryResponseContent {
+    fn as_signed_bytes_without_domain_separator(&self) -> Vec<u8> {
+        use RawHttpRequestVal::*;
+
+        let mut map = match &self.response {
+            HttpQueryResponse::Replied { reply } => btreemap! {
+                "status".to_string() => String("replied".to_string()),
+                "reply".to_string() => Map(btreemap! {
+                    "arg".to_string() => Bytes(reply.arg.0.clone()),
+                }),
+            },
+            HttpQueryResponse::Rejected {
+                reject_code,
+                reject_message,
+            } => btreemap! {
+                "status".to_string() => String("rejected".to_string()),
+                "reject_code".to_string() => U64(*reject_code),
+                "reject_message".to_string() => String(reject_message.clone()),
+            },
+        };
+        map.insert(
+            "timestamp".to_string(),
+            U64(self.timestamp.as_nanos_since_unix_epoch()),
+        );
+        map.insert(
+            "request_id".to_string(),
+            Bytes(self.request_id.as_bytes().to_vec()),
+        );
+        hash_of_map(&map).to_vec()
+    }
+}
+
 /// The response to a `read_state` request.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct HttpReadStateResponse {
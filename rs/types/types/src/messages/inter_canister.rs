@@ -1,4 +1,4 @@
-use crate::{ingress::WasmResult, CanisterId, CountBytes, Cycles, Funds, NumBytes};
+use crate::{ingress::WasmResult, CanisterId, CountBytes, Cycles, Funds, NumBytes, Time};
 use ic_error_types::{RejectCode, UserError};
 use ic_protobuf::{
     proxy::{try_from_option_field, ProxyDecodeError},
@@ -31,6 +31,10 @@ pub struct Request {
     pub method_name: String,
     #[serde(with = "serde_bytes")]
     pub method_payload: Vec<u8>,
+    /// For best-effort calls, the time after which the caller gives up on
+    /// the call and is delivered a `SYS_UNKNOWN` reject instead. `None` for
+    /// calls with a guaranteed response.
+    pub deadline: Option<Time>,
 }
 
 impl Request {
@@ -55,6 +59,13 @@ impl Request {
         let bytes = self.method_name.len() + self.method_payload.len();
         NumBytes::from(bytes as u64)
     }
+
+    /// Returns `true` if this is a best-effort `Request` whose deadline has
+    /// passed at `current_time`.
+    pub fn is_expired(&self, current_time: Time) -> bool {
+        self.deadline
+            .map_or(false, |deadline| deadline <= current_time)
+    }
 }
 
 /// The context attached when an inter-canister message is rejected.
@@ -143,6 +154,9 @@ pub struct Response {
     pub originator_reply_callback: CallbackId,
     pub refund: Cycles,
     pub response_payload: Payload,
+    /// The deadline of the best-effort `Request` this is a response to.
+    /// `None` for responses to calls with a guaranteed response.
+    pub deadline: Option<Time>,
 }
 
 /// Canister-to-canister message.
@@ -221,6 +235,9 @@ impl From<&Request> for pb_queues::Request {
             method_name: req.method_name.clone(),
             method_payload: req.method_payload.clone(),
             cycles_payment: Some((req.payment).into()),
+            deadline_nanos: req
+                .deadline
+                .map_or(0, |deadline| deadline.as_nanos_since_unix_epoch()),
         }
     }
 }
@@ -244,6 +261,10 @@ impl TryFrom<pb_queues::Request> for Request {
             payment,
             method_name: req.method_name,
             method_payload: req.method_payload,
+            deadline: match req.deadline_nanos {
+                0 => None,
+                nanos => Some(Time::from_nanos_since_unix_epoch(nanos)),
+            },
         })
     }
 }
@@ -281,6 +302,9 @@ impl From<&Response> for pb_queues::Response {
             refund: Some((&Funds::new(rep.refund)).into()),
             response_payload: Some(p),
             cycles_refund: Some((rep.refund).into()),
+            deadline_nanos: rep
+                .deadline
+                .map_or(0, |deadline| deadline.as_nanos_since_unix_epoch()),
         }
     }
 }
@@ -311,6 +335,10 @@ impl TryFrom<pb_queues::Response> for Response {
             originator_reply_callback: rep.originator_reply_callback.into(),
             refund,
             response_payload,
+            deadline: match rep.deadline_nanos {
+                0 => None,
+                nanos => Some(Time::from_nanos_since_unix_epoch(nanos)),
+            },
         })
     }
 }
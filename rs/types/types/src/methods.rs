@@ -1,7 +1,7 @@
 //! This module contains a collection of types and structs that define the
 //! various types of methods in the IC.
 
-use crate::{messages::CallContextId, Cycles};
+use crate::{messages::CallContextId, CanisterId, Cycles, Time};
 use ic_protobuf::proxy::{try_from_option_field, ProxyDecodeError};
 use ic_protobuf::state::{canister_state_bits::v1 as pb, queues::v1::Cycles as PbCycles};
 use serde::{Deserialize, Serialize};
@@ -89,6 +89,7 @@ impl From<&WasmMethod> for pb::WasmMethod {
                     SystemMethod::CanisterPostUpgrade => PbSystemMethod::CanisterPostUpgrade,
                     SystemMethod::CanisterInspectMessage => PbSystemMethod::CanisterInspectMessage,
                     SystemMethod::CanisterHeartbeat => PbSystemMethod::CanisterHeartbeat,
+                    SystemMethod::CanisterOnLowCycles => PbSystemMethod::CanisterOnLowCycles,
                     SystemMethod::Empty => PbSystemMethod::Empty,
                 } as i32)),
             },
@@ -122,6 +123,7 @@ impl TryFrom<pb::WasmMethod> for WasmMethod {
                     PbSystemMethod::CanisterPostUpgrade => SystemMethod::CanisterPostUpgrade,
                     PbSystemMethod::CanisterInspectMessage => SystemMethod::CanisterInspectMessage,
                     PbSystemMethod::CanisterHeartbeat => SystemMethod::CanisterHeartbeat,
+                    PbSystemMethod::CanisterOnLowCycles => SystemMethod::CanisterOnLowCycles,
                     PbSystemMethod::Empty => SystemMethod::Empty,
                 }))
             }
@@ -145,6 +147,9 @@ pub enum SystemMethod {
     CanisterInspectMessage,
     /// A system method that is run at regular intervals for cron support.
     CanisterHeartbeat,
+    /// A system method that is run when the cycles balance of the canister
+    /// drops below the low cycles threshold set by its controllers.
+    CanisterOnLowCycles,
     /// This is introduced as temporary scaffolding to aid in construction of
     /// the initial ExecutionState. This isn't used to execute any actual wasm
     /// but as a way to get to the wasm embedder from execution. Eventually, we
@@ -164,6 +169,7 @@ impl TryFrom<&str> for SystemMethod {
             "canister_start" => Ok(SystemMethod::CanisterStart),
             "canister_inspect_message" => Ok(SystemMethod::CanisterInspectMessage),
             "canister_heartbeat" => Ok(SystemMethod::CanisterHeartbeat),
+            "canister_on_low_cycles" => Ok(SystemMethod::CanisterOnLowCycles),
             "empty" => Ok(SystemMethod::Empty),
             _ => Err(format!("Cannot convert {} to SystemMethod.", value)),
         }
@@ -179,6 +185,7 @@ impl fmt::Display for SystemMethod {
             Self::CanisterStart => write!(f, "canister_start"),
             Self::CanisterInspectMessage => write!(f, "canister_inspect_message"),
             Self::CanisterHeartbeat => write!(f, "canister_heartbeat"),
+            Self::CanisterOnLowCycles => write!(f, "canister_on_low_cycles"),
             Self::Empty => write!(f, "empty"),
         }
     }
@@ -210,6 +217,16 @@ pub struct Callback {
     /// An optional closure to be executed if the execution of `on_reply` or
     /// `on_reject` traps.
     pub on_cleanup: Option<WasmClosure>,
+    /// For best-effort calls, the canister that the request was sent to.
+    pub respondent: Option<CanisterId>,
+    /// For best-effort calls, the time after which the call is rejected with
+    /// `SYS_UNKNOWN` if no response was received. `None` for calls with a
+    /// guaranteed response.
+    pub deadline: Option<Time>,
+    /// Whether a response to this best-effort call (either the actual response
+    /// or the `SYS_UNKNOWN` reject) was already enqueued. Any other responses
+    /// are dropped.
+    pub response_enqueued: bool,
 }
 
 impl Callback {
@@ -226,8 +243,29 @@ impl Callback {
             on_reply,
             on_reject,
             on_cleanup,
+            respondent: None,
+            deadline: None,
+            response_enqueued: false,
         }
     }
+
+    /// Turns this into the callback of a best-effort call to `respondent`
+    /// with the given `deadline`.
+    pub fn with_deadline(mut self, respondent: CanisterId, deadline: Time) -> Self {
+        self.respondent = Some(respondent);
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Returns `true` if this is the callback of a best-effort call whose
+    /// deadline has passed at `current_time` and for which no response was
+    /// enqueued yet.
+    pub fn is_expired(&self, current_time: Time) -> bool {
+        !self.response_enqueued
+            && self
+                .deadline
+                .map_or(false, |deadline| deadline <= current_time)
+    }
 }
 
 impl From<&Callback> for pb::Callback {
@@ -247,6 +285,11 @@ impl From<&Callback> for pb::Callback {
                 func_idx: on_cleanup.func_idx,
                 env: on_cleanup.env,
             }),
+            respondent: item.respondent.map(|respondent| respondent.into()),
+            deadline_nanos: item
+                .deadline
+                .map_or(0, |deadline| deadline.as_nanos_since_unix_epoch()),
+            response_enqueued: item.response_enqueued,
         }
     }
 }
@@ -277,6 +320,12 @@ impl TryFrom<pb::Callback> for Callback {
                 func_idx: on_cleanup.func_idx,
                 env: on_cleanup.env,
             }),
+            respondent: value.respondent.map(CanisterId::try_from).transpose()?,
+            deadline: match value.deadline_nanos {
+                0 => None,
+                nanos => Some(Time::from_nanos_since_unix_epoch(nanos)),
+            },
+            response_enqueued: value.response_enqueued,
         })
     }
 }
@@ -307,6 +356,7 @@ impl FuncRef {
             | Self::Method(WasmMethod::System(SystemMethod::CanisterPreUpgrade))
             | Self::Method(WasmMethod::System(SystemMethod::CanisterPostUpgrade))
             | Self::Method(WasmMethod::System(SystemMethod::CanisterHeartbeat))
+            | Self::Method(WasmMethod::System(SystemMethod::CanisterOnLowCycles))
             | Self::UpdateClosure(_) => true,
             Self::QueryClosure(_)
             | Self::Method(WasmMethod::Query(_))
@@ -157,10 +157,15 @@ pub struct StreamHeader {
     /// Index of the next expected reverse stream message.
     ///
     /// Conceptually we use a gap-free queue containing one signal for each
-    /// inducted message; but because these signals are all "Accept" (as we
-    /// generate responses when rejecting messages), that queue can be safely
-    /// represented by its end index (pointing just beyond the last signal).
+    /// inducted message; but because most signals are "Accept", that queue is
+    /// represented by its end index (pointing just beyond the last signal) plus
+    /// the indices of the "Reject" signals (`reject_signals`).
     pub signals_end: StreamIndex,
+
+    /// Stream indices of reverse stream messages that were rejected by the
+    /// receiving subnet, in strictly increasing order. All are strictly less
+    /// than `signals_end`.
+    pub reject_signals: VecDeque<StreamIndex>,
 }
 
 /// A continuous slice of messages pulled from a remote subnet.  The slice also
@@ -129,6 +129,7 @@ prop_compose! {
             payment: Cycles::from(cycles_payment),
             method_name,
             method_payload,
+            deadline: None,
         }
     }
 }
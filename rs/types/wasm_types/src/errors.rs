@@ -125,6 +125,9 @@ pub enum WasmEngineError {
     FailedToInstantiateModule,
     FailedToSetAsyncStack,
     FailedToSetWasmStack,
+    /// The sandbox process executing the canister terminated (e.g. because
+    /// it made a forbidden system call) before completing the execution.
+    SandboxProcessTerminated,
 }
 
 impl std::fmt::Display for WasmEngineError {
@@ -142,6 +145,9 @@ impl std::fmt::Display for WasmEngineError {
             Self::FailedToSetAsyncStack => {
                 write!(f, "Failed to set async stack")
             }
+            Self::SandboxProcessTerminated => {
+                write!(f, "Sandbox process terminated unexpectedly")
+            }
         }
     }
 }